@@ -0,0 +1,25 @@
+//! 启用 `ffi` 特性时，由 cbindgen 根据 `src/ffi.rs` 生成 C 头文件 `$OUT_DIR/chat.h`
+//!
+//! 构建不改动源码目录：仓库中的 `include/chat.h` 是提交的副本，`ffi` 模块的测试检查它与生成的头文件一致，
+//! 修改 C 接口后把测试输出中的生成路径复制到 `include/chat.h`
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR 未设置");
+    let out = std::env::var("OUT_DIR").expect("OUT_DIR 未设置");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml"))
+        .expect("无法读取 cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&dir)
+        .with_config(config)
+        .generate()
+        .expect("无法生成 C 头文件")
+        .write_to_file(format!("{out}/chat.h"));
+}
@@ -0,0 +1,107 @@
+/* 由 cbindgen 根据 src/ffi.rs 生成，请勿手动修改 */
+
+#ifndef ASYNC_CHAT_H
+#define ASYNC_CHAT_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// 消息类型，与 [`MessageKind`] 对应
+typedef enum ChatMessageKind {
+  // 普通文本消息
+  CHAT_MESSAGE_KIND_TEXT = 0,
+  // 动作消息，显示为 "* 发送者 动作"
+  CHAT_MESSAGE_KIND_ACTION = 1,
+  // 未能解密的端到端加密消息，内容为密文
+  CHAT_MESSAGE_KIND_ENCRYPTED = 2,
+} ChatMessageKind;
+
+// 调用结果
+typedef enum ChatStatus {
+  // 成功
+  CHAT_STATUS_OK = 0,
+  // 参数为空指针或不是有效的 UTF-8
+  CHAT_STATUS_INVALID_ARGUMENT = 1,
+  // 发送失败（连接已断开等）
+  CHAT_STATUS_SEND_FAILED = 2,
+} ChatStatus;
+
+// 客户端句柄，由 [`chat_client_connect`] 创建、[`chat_client_close`] 释放
+typedef struct ChatClient ChatClient;
+
+// 传给消息回调的一条消息，其中的字符串只在回调期间有效
+typedef struct ChatMessage {
+  // 发送者
+  const char *from;
+  // 接收者
+  const char *to;
+  // 消息内容
+  const char *content;
+  // 发送时间（`时:分:秒`）
+  const char *time_stamp;
+  // 消息类型
+  enum ChatMessageKind kind;
+  // 服务器分配的消息编号，没有编号时为 0
+  uint64_t id;
+} ChatMessage;
+
+// 收到消息时调用的回调
+typedef void (*ChatMessageCallback)(void *user_data, const struct ChatMessage *message);
+
+// 收到服务器错误时调用的回调：`code` 为 `UNKNOWN_USER` 这样的错误码，`message` 为说明文字
+typedef void (*ChatErrorCallback)(void *user_data, const char *code, const char *message);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// 连接服务器并以 `name` 注册
+//
+// `addr` 可以是以逗号分隔的多个服务器地址，依次尝试直到连接成功。
+// 失败时返回空指针，可通过 [`chat_last_error`] 获取原因。
+//
+// # Safety
+// `addr` 与 `name` 须为以 NUL 结尾的有效字符串；回调可能在连接存续期间的任意时刻于
+// 库内部的线程上调用，`user_data` 须在 [`chat_client_close`] 返回前保持有效且可以跨线程使用。
+struct ChatClient *chat_client_connect(const char *addr,
+                                       const char *name,
+                                       ChatMessageCallback on_message,
+                                       ChatErrorCallback on_error,
+                                       void *user_data);
+
+// 发送一条文本消息给 `to`
+//
+// # Safety
+// `client` 须为 [`chat_client_connect`] 返回且尚未关闭的句柄，`to` 与 `content` 须为以 NUL
+// 结尾的有效字符串；同一句柄不能在多个线程上同时调用。
+enum ChatStatus chat_client_send(struct ChatClient *client,
+                                 const char *to,
+                                 const char *content);
+
+// 发送一条动作消息给 `to`，接收方显示为 "* 用户名 动作"
+//
+// # Safety
+// 同 [`chat_client_send`]。
+enum ChatStatus chat_client_send_action(struct ChatClient *client,
+                                        const char *to,
+                                        const char *action);
+
+// 主动退出（发送 Goodbye 帧）并释放句柄；`client` 为空指针时不做任何事
+//
+// # Safety
+// `client` 须为 [`chat_client_connect`] 返回且尚未关闭的句柄，调用后不能再使用；
+// 不能在回调中调用。
+void chat_client_close(struct ChatClient *client);
+
+// 当前线程上一次失败的说明；没有失败时返回空指针
+//
+// 返回的字符串在当前线程下一次调用本库的函数前有效。
+const char *chat_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ASYNC_CHAT_H */
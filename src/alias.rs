@@ -0,0 +1,118 @@
+/*!
+# 指令别名模块
+
+客户端在分发输入前展开用户定义的别名。别名保存在配置目录下的 `aliases.conf`
+（见 [`crate::config_dir`]），每行一个 `名称 = 展开内容`，`#` 开头的行为注释：
+
+```text
+b = /msg bob
+standup = /msg team "standup in 5"
+```
+
+输入 `/b 你好` 会展开为 `/msg bob 你好`：别名之后的参数追加在展开内容末尾。
+展开结果本身也可以是别名，最多展开 [`MAX_DEPTH`] 层以避免循环定义。
+*/
+
+use crate::tr;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 别名嵌套展开的最大层数
+pub const MAX_DEPTH: usize = 8;
+
+/// 用户定义的指令别名
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    map: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    /// 创建空的别名表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 解析别名配置文本
+    ///
+    /// # 返回值
+    /// 格式错误时返回出错的行号（从 1 开始）及原因
+    pub fn parse(text: &str) -> Result<Self, (usize, String)> {
+        let mut aliases = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, expansion)) = line.split_once('=') else {
+                return Err((i + 1, tr!("alias.missing_eq")));
+            };
+            let name = name.trim().trim_start_matches('/');
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err((i + 1, tr!("alias.invalid_name", name = name)));
+            }
+            aliases.insert(name, expansion.trim());
+        }
+        Ok(aliases)
+    }
+
+    /// 从文件载入别名；文件不存在时返回空表
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+        Self::parse(&text).map_err(|(line, reason)| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!(
+                    "alias.error",
+                    path = path.display(),
+                    line = line,
+                    reason = reason
+                ),
+            )
+        })
+    }
+
+    /// 默认别名文件路径
+    pub fn default_path() -> Option<PathBuf> {
+        crate::config_dir().map(|dir| dir.join("aliases.conf"))
+    }
+
+    /// 定义（或覆盖）一个别名
+    pub fn insert(&mut self, name: &str, expansion: &str) {
+        self.map.insert(name.to_string(), expansion.to_string());
+    }
+
+    /// 所有别名，按名称排序
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.map.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// 展开以 `/别名` 开头的输入
+    ///
+    /// # 返回值
+    /// 输入不是别名时返回 `None`
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let mut current = input.to_string();
+        let mut expanded = false;
+        for _ in 0..MAX_DEPTH {
+            let Some(rest) = current.strip_prefix('/') else {
+                break;
+            };
+            let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let Some(expansion) = self.map.get(name) else {
+                break;
+            };
+            current = match args.trim() {
+                "" => expansion.clone(),
+                args => format!("{} {}", expansion, args),
+            };
+            expanded = true;
+        }
+        expanded.then_some(current)
+    }
+}
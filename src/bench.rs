@@ -0,0 +1,523 @@
+/*!
+# 吞吐量与延迟压测
+
+`chat bench` 在同一进程中启动服务器与 N 个客户端，测量消息经服务器转发的端到端延迟与吞吐量：
+
+- 客户端经进程内的双向管道（[`Transport::Duplex`]，默认）或本机回环 TCP 连接（[`Transport::Tcp`]）
+  接入服务器，前者只测服务器自身的转发路径，后者另外包含内核网络栈；
+- 全部客户端注册完成后同时开始发送：第 i 个客户端向第 i+1 个客户端（首尾相接）连续发送指定条数的消息，
+  消息内容以发送时刻开头，接收方据此计算延迟；
+- 结束后给出吞吐量、延迟的均值与分位数，以及按 2 的幂划分的延迟直方图（[`Report`]）；
+- 结果可以保存为基线（JSON 文件），之后的运行与基线比较（[`Report::compare`]），
+  吞吐量下降或延迟上升超过阈值时视为性能退化，命令以退出码 1 结束，便于在持续集成中跟踪。
+
+压测时服务器不逐条记录转发的消息（[`ServerConfig::log_messages`]），也不限制消息内容的长度
+（[`ServerConfig::max_message_len`]），`--size` 只受帧长度上限约束。
+
+命令行用法：
+
+```text
+chat bench                                   # 8 个客户端，每个发送 1000 条 64 字节的消息
+chat bench --clients=32 --messages=5000 --size=256 --transport=tcp
+chat bench --save-baseline=bench.json        # 把结果保存为基线
+chat bench --baseline=bench.json --max-regression=10
+```
+*/
+
+use crate::codec::{Codec, FrameReader, FrameWriter};
+use crate::protocol::{Control, Frame, Hello, Registration};
+use crate::server::{Listeners, Server, ServerConfig};
+use crate::transport::{BoxReader, BoxWriter};
+use crate::{tr, ArcString, Message};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::Barrier;
+use tokio::task::JoinSet;
+
+/// 等待全部消息送达的最长时间
+pub const BENCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 默认的性能退化阈值（百分比）
+pub const DEFAULT_MAX_REGRESSION: f64 = 10.0;
+
+/// 进程内管道每个方向的缓冲区大小
+const DUPLEX_BUFFER: usize = 64 * 1024;
+
+/// 直方图中画出的条形的最大宽度
+const BAR_WIDTH: u64 = 40;
+
+/// 客户端接入服务器的方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    /// 进程内的 `tokio::io::duplex` 管道
+    #[default]
+    Duplex,
+    /// 本机回环 TCP 连接
+    Tcp,
+}
+
+impl Transport {
+    /// 从名称（`duplex` 或 `tcp`）解析接入方式
+    pub fn from_string(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "duplex" => Some(Transport::Duplex),
+            "tcp" => Some(Transport::Tcp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Duplex => "duplex",
+            Transport::Tcp => "tcp",
+        })
+    }
+}
+
+/// 压测参数
+#[derive(Clone, Debug)]
+pub struct BenchConfig {
+    /// 客户端数量（至少 2 个）
+    pub clients: usize,
+    /// 每个客户端发送的消息条数
+    pub messages: usize,
+    /// 每条消息内容的字节数
+    pub size: usize,
+    /// 接入方式
+    pub transport: Transport,
+    /// 客户端使用的分帧格式
+    pub codec: Codec,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            clients: 8,
+            messages: 1000,
+            size: 64,
+            transport: Transport::default(),
+            codec: Codec::default(),
+        }
+    }
+}
+
+/// 延迟统计，单位为微秒
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+/// 延迟直方图的一格：延迟不超过 `le_us` 微秒（且超过上一格）的消息条数
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub le_us: u64,
+    pub count: u64,
+}
+
+/// 一次压测的结果，也是保存的基线的格式
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub transport: Transport,
+    /// 分帧格式的名称
+    pub codec: String,
+    pub clients: usize,
+    /// 每个客户端发送的消息条数
+    pub messages: usize,
+    /// 每条消息内容的字节数
+    pub size: usize,
+    /// 送达的消息总数
+    pub delivered: u64,
+    /// 从开始发送到全部送达的秒数
+    pub elapsed_secs: f64,
+    /// 每秒送达的消息条数
+    pub throughput: f64,
+    pub latency: Latency,
+    pub histogram: Vec<Bucket>,
+}
+
+/// 与基线相比的一项指标
+#[derive(Clone, Copy, Debug)]
+pub struct Change {
+    /// 指标名称：`throughput`、`mean`、`p50`、`p90` 或 `p99`
+    pub metric: &'static str,
+    pub current: f64,
+    pub baseline: f64,
+    /// 相对基线变化的百分比，正数表示数值变大
+    pub percent: f64,
+    /// 是否朝不利的方向变化超过了阈值
+    pub regressed: bool,
+}
+
+impl Report {
+    /// 读取保存的基线
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// 把结果保存为基线
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        fs::write(path, data)
+    }
+
+    /// 两次压测的参数是否相同，参数不同时比较结果没有意义
+    pub fn comparable(&self, baseline: &Report) -> bool {
+        self.transport == baseline.transport
+            && self.codec == baseline.codec
+            && self.clients == baseline.clients
+            && self.messages == baseline.messages
+            && self.size == baseline.size
+    }
+
+    /// 与基线逐项比较：吞吐量下降或延迟上升超过 `max_regression` 个百分点时标记为退化
+    pub fn compare(&self, baseline: &Report, max_regression: f64) -> Vec<Change> {
+        let (current, base) = (&self.latency, &baseline.latency);
+        // 指标、当前值、基线值、数值越大越好
+        let metrics = [
+            ("throughput", self.throughput, baseline.throughput, true),
+            ("mean", current.mean_us, base.mean_us, false),
+            ("p50", current.p50_us as f64, base.p50_us as f64, false),
+            ("p90", current.p90_us as f64, base.p90_us as f64, false),
+            ("p99", current.p99_us as f64, base.p99_us as f64, false),
+        ];
+        metrics
+            .into_iter()
+            .map(|(metric, current, baseline, higher_is_better)| {
+                let percent = if baseline > 0.0 {
+                    (current - baseline) / baseline * 100.0
+                } else {
+                    0.0
+                };
+                let worse = if higher_is_better { -percent } else { percent };
+                Change {
+                    metric,
+                    current,
+                    baseline,
+                    percent,
+                    regressed: worse > max_regression,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Report {
+    /// 多行的结果摘要与延迟直方图
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            tr!(
+                "bench.summary",
+                delivered = self.delivered,
+                secs = format!("{:.3}", self.elapsed_secs),
+                throughput = format!("{:.0}", self.throughput)
+            )
+        )?;
+        let latency = &self.latency;
+        writeln!(
+            f,
+            "{}",
+            tr!(
+                "bench.latency",
+                mean = format!("{:.1}", latency.mean_us),
+                p50 = latency.p50_us,
+                p90 = latency.p90_us,
+                p99 = latency.p99_us,
+                max = latency.max_us
+            )
+        )?;
+        let peak = self.histogram.iter().map(|b| b.count).max().unwrap_or(0);
+        for bucket in &self.histogram {
+            let bar = (bucket.count * BAR_WIDTH).div_ceil(peak.max(1)) as usize;
+            writeln!(
+                f,
+                "  ≤ {:>8} µs {:>9} {}",
+                bucket.le_us,
+                bucket.count,
+                "█".repeat(bar)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// 按 `config` 运行一次压测
+pub async fn run(config: &BenchConfig) -> io::Result<Report> {
+    if config.clients < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!("bench.too_few_clients"),
+        ));
+    }
+    let server_config = ServerConfig {
+        log_messages: false,
+        max_message_len: None,
+        ..ServerConfig::default()
+    };
+    let server = Server::with_config(server_config.clone());
+    let (addr, server_task) = match config.transport {
+        Transport::Duplex => (None, None),
+        Transport::Tcp => {
+            let listeners = Listeners::bind("127.0.0.1:0", &server_config).await?;
+            let addr = listeners.local_addr()?;
+            let server = server.clone();
+            let task = tokio::spawn(async move {
+                if let Err(e) = server.run_on(listeners).await {
+                    eprintln!("{}", tr!("cli.server_failed", error = e.to_string()));
+                }
+            });
+            (Some(addr), Some(task))
+        }
+    };
+
+    // 各客户端与压测本身在同一时刻开始计时
+    let barrier = Arc::new(Barrier::new(config.clients + 1));
+    let epoch = Instant::now();
+    let mut clients = JoinSet::new();
+    for index in 0..config.clients {
+        let (reader, writer) = connect(&server, addr).await?;
+        let client = BenchClient {
+            name: client_name(index),
+            to: client_name((index + 1) % config.clients),
+            from: client_name((index + config.clients - 1) % config.clients),
+            config: config.clone(),
+            epoch,
+        };
+        let barrier = Arc::clone(&barrier);
+        clients.spawn(async move { client.run(reader, writer, barrier).await });
+    }
+    let outcome = tokio::time::timeout(BENCH_TIMEOUT, async {
+        barrier.wait().await;
+        let started = Instant::now();
+        let mut latencies = Vec::with_capacity(config.clients * config.messages);
+        while let Some(joined) = clients.join_next().await {
+            latencies.extend(joined.map_err(io::Error::other)??);
+        }
+        Ok::<_, io::Error>((started.elapsed(), latencies))
+    })
+    .await;
+
+    if let Some(task) = server_task {
+        server.shutdown();
+        let _ = task.await;
+    }
+    let (elapsed, latencies) = match outcome {
+        Ok(result) => result?,
+        Err(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                tr!("bench.timeout", secs = BENCH_TIMEOUT.as_secs()),
+            ))
+        }
+    };
+    Ok(report(config, elapsed, latencies))
+}
+
+fn client_name(index: usize) -> String {
+    format!("bench{}", index)
+}
+
+/// 建立一条到服务器的连接：TCP 连接到 `addr`，未给出时经进程内管道直接交给服务器处理
+async fn connect(server: &Server, addr: Option<SocketAddr>) -> io::Result<(BoxReader, BoxWriter)> {
+    if let Some(addr) = addr {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        return Ok((Box::new(reader), Box::new(writer)));
+    }
+    let (local, remote) = tokio::io::duplex(DUPLEX_BUFFER);
+    let (reader, writer) = tokio::io::split(remote);
+    let server = server.clone();
+    tokio::spawn(async move {
+        let peer = SocketAddr::from(([127, 0, 0, 1], 0));
+        if let Err(e) = server
+            .serve_stream(Box::new(reader), Box::new(writer), peer)
+            .await
+        {
+            let error = e.to_string();
+            eprintln!(
+                "{}",
+                tr!("server.connection_error", addr = peer, error = error)
+            );
+        }
+    });
+    let (reader, writer) = tokio::io::split(local);
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// 压测中的一个客户端：向 `to` 发送消息，并接收 `from` 发来的消息
+struct BenchClient {
+    name: String,
+    to: String,
+    from: String,
+    config: BenchConfig,
+    /// 消息内容中发送时刻的起点
+    epoch: Instant,
+}
+
+impl BenchClient {
+    /// 注册后等待所有客户端就绪，再同时收发消息；返回收到的每条消息的延迟（微秒）
+    async fn run(
+        self,
+        reader: BoxReader,
+        writer: BoxWriter,
+        barrier: Arc<Barrier>,
+    ) -> io::Result<Vec<u64>> {
+        let codec = self.config.codec;
+        let mut reader = FrameReader::new(reader, codec);
+        let mut writer = FrameWriter::new(writer, codec);
+        let hello = Hello::new(self.name.clone(), Vec::new());
+        writer
+            .write_frame(&Registration::Hello(hello))
+            .await
+            .map_err(io::Error::other)?;
+        // 服务器按顺序处理帧，收到心跳响应即说明注册已经完成
+        writer
+            .write_frame(&Frame::Control(Control::Ping { seq: 0 }))
+            .await
+            .map_err(io::Error::other)?;
+        loop {
+            match reader
+                .read_frame::<Frame>()
+                .await
+                .map_err(io::Error::other)?
+            {
+                Some(Frame::Control(Control::Pong { .. })) => break,
+                Some(_) => {}
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        tr!("bench.refused", name = self.name),
+                    ))
+                }
+            }
+        }
+        barrier.wait().await;
+
+        let padding = "x".repeat(self.config.size.saturating_sub(21));
+        let sender = ArcString::new(self.name.clone());
+        let send = async {
+            for _ in 0..self.config.messages {
+                let sent = self.epoch.elapsed().as_nanos();
+                let content = format!("{:020} {}", sent, padding);
+                let msg = Message::new(sender.clone(), self.to.clone(), content);
+                writer
+                    .write_frame(&Frame::Message(msg))
+                    .await
+                    .map_err(io::Error::other)?;
+            }
+            Ok::<_, io::Error>(())
+        };
+        let receive = async {
+            let mut latencies = Vec::with_capacity(self.config.messages);
+            while latencies.len() < self.config.messages {
+                let frame = reader
+                    .read_frame::<Frame>()
+                    .await
+                    .map_err(io::Error::other)?;
+                let msg = match frame {
+                    Some(Frame::Message(msg)) => msg,
+                    Some(Frame::Control(_)) => continue,
+                    None => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            tr!(
+                                "bench.closed",
+                                name = self.name,
+                                received = latencies.len(),
+                                expected = self.config.messages
+                            ),
+                        ))
+                    }
+                };
+                if msg.from() != self.from {
+                    continue;
+                }
+                let sent = msg
+                    .content()
+                    .split(' ')
+                    .next()
+                    .and_then(|nanos| nanos.parse::<u128>().ok());
+                if let Some(sent) = sent {
+                    let latency = self.epoch.elapsed().as_nanos().saturating_sub(sent);
+                    latencies.push((latency / 1000) as u64);
+                }
+            }
+            Ok(latencies)
+        };
+        let (sent, latencies) = tokio::join!(send, receive);
+        sent?;
+        let latencies = latencies?;
+        // 告别后读到服务器关闭连接为止，服务器发来的离线通知不会写入已关闭的连接
+        let goodbye = Frame::Control(Control::Goodbye { message: None });
+        if writer.write_frame(&goodbye).await.is_ok() {
+            while let Ok(Some(_)) = reader.read_frame::<Frame>().await {}
+        }
+        Ok(latencies)
+    }
+}
+
+/// 由各条消息的延迟汇总结果
+fn report(config: &BenchConfig, elapsed: Duration, mut latencies: Vec<u64>) -> Report {
+    latencies.sort_unstable();
+    let delivered = latencies.len() as u64;
+    let percentile = |p: f64| -> u64 {
+        if latencies.is_empty() {
+            return 0;
+        }
+        let rank = ((latencies.len() as f64 * p).ceil() as usize).clamp(1, latencies.len());
+        latencies[rank - 1]
+    };
+    let latency = Latency {
+        mean_us: if latencies.is_empty() {
+            0.0
+        } else {
+            latencies.iter().sum::<u64>() as f64 / latencies.len() as f64
+        },
+        p50_us: percentile(0.50),
+        p90_us: percentile(0.90),
+        p99_us: percentile(0.99),
+        max_us: latencies.last().copied().unwrap_or(0),
+    };
+    let mut histogram: Vec<Bucket> = Vec::new();
+    for &us in &latencies {
+        let le_us = us.max(1).next_power_of_two();
+        match histogram.last_mut() {
+            Some(bucket) if bucket.le_us == le_us => bucket.count += 1,
+            _ => histogram.push(Bucket { le_us, count: 1 }),
+        }
+    }
+    let secs = elapsed.as_secs_f64();
+    Report {
+        transport: config.transport,
+        codec: config.codec.to_string(),
+        clients: config.clients,
+        messages: config.messages,
+        size: config.size,
+        delivered,
+        elapsed_secs: secs,
+        throughput: if secs > 0.0 {
+            delivered as f64 / secs
+        } else {
+            0.0
+        },
+        latency,
+        histogram,
+    }
+}
@@ -5,26 +5,631 @@
 - 连接服务器并注册（发送用户名）
 - 启动独立任务实时接收服务器转发的消息
 - 主循环中读取用户输入，构造消息并发送到服务器
-- 支持退出（输入 "exit" 即退出程序）
+- 输入 `/exit [告别语]` 退出：发送 Goodbye 帧、关闭连接并停止接收任务后从 [`Client::run`] 返回
+- 可选地将收发的消息保存到本地（见 [`crate::history`]），通过 `/history <用户>` 查看，
+  `/export <用户> [路径]` 导出为独立的 HTML 文件（见 [`crate::export`]）
+- 高亮提及自己的 `@用户名` 及配置的关键词（见 [`crate::highlight`]），
+  通过 `/mute <用户>`、`/unmute <用户>` 在本地静音会话；服务器按保存在资料中的通知偏好
+  （`/notify`，见 [`crate::notify`]）为消息附上提示建议时，按建议决定是否响铃
+- 通过 `/msg <用户> <内容>` 一行发送消息；`/别名` 按配置展开（见 [`crate::alias`]），
+  `/alias` 列出所有别名
+- 消息内容输入 `/multiline`（以单独一行 `/end` 结束）或以 `"""` 开头（以 `"""` 结尾）时
+  进入多行输入模式，可发送包含换行与代码块的消息
+- 识别消息中的链接并加上编号，通过 `/open <编号>` 在默认浏览器中打开（见 [`crate::link`]）
+- 收到的图片文件在消息区显示缩略图（支持 kitty 或 sixel 图形的终端）或格式、尺寸与大小，
+  `/preview <编号|路径>` 预览消息中的图片链接或本地图片（见 [`crate::preview`]）
+- 收到的消息带有本地编号 `#N`，通过 `/forward <编号> <用户>` 转发并注明原发送者
+- 消息内容以 `/me ` 开头时发送动作消息，接收方显示为 "* Alice 挥手"
+- 光标控制、颜色能力检测与读取输入经由 [`crate::terminal`]，在旧版 Windows 控制台上同样可用；
+  所有输出由唯一的渲染线程写到终端，收到的消息不会打乱正在输入的内容
+- 以 ANSI 样式渲染消息中的基础 Markdown（见 [`crate::markdown`]），可通过 `--plain` 关闭
+- 发送前将 `:smile:` 等短代码展开为表情（见 [`crate::emoji`]），通过 `/emoji <查询>` 查找短代码
+- 支持以逗号分隔的多个服务器地址，连接失败或中断时自动切换；只写域名的地址按 DNS SRV 记录
+  查找服务器（见 [`crate::srv`]）
+- 定期发送心跳（Ping），超过两个心跳间隔未收到服务器的任何帧（包括 Pong）即视为连接已断开，
+  按断线流程退出或重连，避免 NAT 悄悄丢弃连接后读取一直挂起
+- 重连时上报最后收到的消息编号，由服务器补发断线期间的消息；发送失败重连后
+  重发同一条消息，收发两端按消息编号去重（见 [`crate::dedup`]）
+- 服务器的错误帧（接收方不存在、不在线等，见 [`crate::protocol::ErrorCode`]）以错误样式显示，
+  或交给通过 [`ClientBuilder::on_error`] 设置的处理器
+- 提示文字使用当前进程的语言（见 [`crate::i18n`]），并在握手时上报给服务器，
+  服务器的回复与提示也使用该语言
+- 通过 `/sendfile <用户> <路径>` 发送文件，`/accept <编号>`、`/reject <编号>` 应答收到的文件；
+  由服务器撮合双方直连，打洞失败时经服务器中转（见 [`crate::transfer`]）；传输期间输入行上方显示进度，
+  `/transfers` 列出待接收与进行中的传输，`/cancel-transfer <编号>` 取消其中一个；
+  断线中断的传输在重新连接、对方在线时自动重新提议，接收方接受后从断点续传
+- 配置了身份密钥时（需启用 `crypto` 特性，下同）为发出的消息签名，收到的消息验证签名，公钥更换、签名无效或缺失时显示警告
+  （见 [`crate::identity`]）；通过 `/fingerprint [用户]` 查看公钥指纹，与对方带外核对后以
+  `/verify <用户> <指纹>` 标记为已验证，此后该用户签名有效的消息显示 ✓（`/unverify <用户>` 取消）；
+  已验证的联系人更换公钥时响铃并醒目警告，`/trust <用户>` 接受对方更换后的公钥
+- 通过 `/e2e <用户>` 与对方建立端到端加密会话，此后的消息只有双方可以解密，会话密钥定期自动
+  重新交换（见 [`crate::e2e`]）；`/e2e off <用户>` 结束会话，`/e2e` 列出会话
+- 接收服务器推送的在线名单：显示用户的上线、下线与状态变化（已静音的用户除外），
+  输入时按 `Tab` 补全在线用户名，无需反复发送 `/list`
+- 服务器关闭或升级前发来关闭通知（见 [`crate::protocol::Control::ServerShutdown`]）时显示原因，
+  附带重连建议时在建议的时间（加上一小段随机延迟）到达后自动重连
+- 通过 [`Client::builder`] 配置超时、重连策略、TLS、编码及自定义消息处理器，
+  供程序化使用者通过 [`Client::connect`] 获得不依赖终端交互的连接
 
 详细说明请参见各函数注释。
 */
 
-use crate::{ArcString, Message};
-use colored::*;
-use serde_json;
-use std::io::{self, Write};
-use std::net::SocketAddr;
+use crate::alias::Aliases;
+use crate::client_core::{self, ClientCore, Event};
+use crate::codec::{Codec, FrameReader, FrameWriter};
+#[cfg(feature = "crypto")]
+use crate::e2e::{E2e, Sealing};
+use crate::emoji;
+use crate::export;
+use crate::highlight::HighlightRules;
+use crate::history::{self, History};
+use crate::i18n;
+#[cfg(feature = "crypto")]
+use crate::identity::{fingerprint, Identity, KnownKeys, Trust, Verify};
+use crate::link;
+use crate::markdown::{self, Span, Style};
+use crate::notify::NotifyHint;
+use crate::pow;
+use crate::preview::{self, Preview};
+#[cfg(feature = "crypto")]
+use crate::protocol::FEATURE_E2E;
+use crate::protocol::{
+    Control, ErrorFrame, Frame, RosterChange, RosterEntry, RosterStatus, ShutdownReason,
+    FEATURE_ERRORS, FEATURE_FILES, FEATURE_POW, FEATURE_RESUME, FEATURE_ROSTER, FEATURE_SHUTDOWN,
+};
+use crate::sanitize::sanitize;
+use crate::terminal::{self, Input};
+use crate::theme::Theme;
+use crate::transfer::{Transfers, DEFAULT_DOWNLOADS};
+use crate::transport::{self, BoxReader, BoxWriter, SocketOptions, TlsConfig};
+use crate::{humanize_duration, tr, ArcString, Message, MessageKind};
+use colored::Colorize;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 use std::process;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::spawn;
+use tokio::sync::watch;
+use tokio::task::{AbortHandle, JoinHandle};
+
+/// 经渲染线程输出一行，不会打乱正在输入的内容（见 [`terminal::Screen`]）
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        terminal::screen().println(format!($($arg)*))
+    };
+}
+
+/// 同 [`outln!`]，但输出到标准错误
+macro_rules! errln {
+    ($($arg:tt)*) => {
+        terminal::screen().eprintln(format!($($arg)*))
+    };
+}
+
+/// 自定义消息处理器，每收到一条消息调用一次
+pub type MessageHandler = Arc<dyn Fn(Message) + Send + Sync>;
+
+/// 自定义错误处理器，每收到一个服务器错误帧调用一次
+pub type ErrorHandler = Arc<dyn Fn(ErrorFrame) + Send + Sync>;
+
+/// 连接断开后的重连策略
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReconnectPolicy {
+    /// 不重连
+    #[default]
+    Never,
+    /// 以固定间隔重试，最多 `max_attempts` 次
+    Fixed { max_attempts: u32, delay: Duration },
+    /// 指数退避重试，间隔从 `initial` 开始翻倍，不超过 `max`
+    ExponentialBackoff {
+        max_attempts: u32,
+        initial: Duration,
+        max: Duration,
+    },
+}
+
+impl ReconnectPolicy {
+    /// 计算第 `attempt` 次（从 0 开始）重连前需要等待的时间
+    ///
+    /// # 返回值
+    /// 若已超过最大重试次数（或策略为 `Never`），返回 `None`
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        match *self {
+            ReconnectPolicy::Never => None,
+            ReconnectPolicy::Fixed {
+                max_attempts,
+                delay,
+            } => (attempt < max_attempts).then_some(delay),
+            ReconnectPolicy::ExponentialBackoff {
+                max_attempts,
+                initial,
+                max,
+            } => (attempt < max_attempts)
+                .then(|| initial.saturating_mul(1 << attempt.min(16)).min(max)),
+        }
+    }
+}
+
+/// 默认连接超时，避免连接不可达地址时长时间挂起
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 默认心跳间隔
+pub const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// 连续多少个心跳间隔未收到任何帧即视为连接已断开
+const HEARTBEAT_MISSES: u32 = 2;
+
+/// 关闭连接时等待 Goodbye 帧写出的最长时间
+pub const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 按服务器关闭通知重连前，在建议的等待时间之外最多再等待的随机时长，避免所有客户端同时重连
+const RECONNECT_JITTER: Duration = Duration::from_secs(1);
+
+/// 由发送消息的主循环与心跳任务共享的写入端
+pub(crate) type SharedWriter = Arc<tokio::sync::Mutex<FrameWriter<BoxWriter>>>;
+
+/// `Client` 的构建器
+pub struct ClientBuilder {
+    name: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    heartbeat: Option<Duration>,
+    reconnect: ReconnectPolicy,
+    tls: Option<TlsConfig>,
+    socket: SocketOptions,
+    codec: Codec,
+    vhost: Option<String>,
+    invite: Option<String>,
+    token: Option<String>,
+    handler: Option<MessageHandler>,
+    error_handler: Option<ErrorHandler>,
+    history: Option<History>,
+    highlight: HighlightRules,
+    emoji_on_display: bool,
+    markdown: bool,
+    preview: Preview,
+    theme: Theme,
+    aliases: Aliases,
+    downloads: PathBuf,
+    #[cfg(feature = "crypto")]
+    identity: Option<Identity>,
+    #[cfg(feature = "crypto")]
+    known_keys: KnownKeys,
+}
+
+impl ClientBuilder {
+    /// 设置连接超时（默认为 [`DEFAULT_CONNECT_TIMEOUT`]）
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// 取消连接超时，一直等待直到操作系统放弃连接
+    pub fn no_connect_timeout(mut self) -> Self {
+        self.connect_timeout = None;
+        self
+    }
+
+    /// 设置读取超时：超过该时长未收到任何帧即视为连接失效
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// 设置心跳间隔（默认为 [`DEFAULT_HEARTBEAT`]）
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    /// 不发送心跳，也不检测失效连接
+    pub fn no_heartbeat(mut self) -> Self {
+        self.heartbeat = None;
+        self
+    }
+
+    /// 设置重连策略
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
+
+    /// 启用 TLS
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// 设置套接字选项（默认开启 `TCP_NODELAY` 与 keepalive）
+    pub fn socket(mut self, options: SocketOptions) -> Self {
+        self.socket = options;
+        self
+    }
+
+    /// 设置偏好的帧编码
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// 设置要进入的虚拟主机（默认进入服务器的默认空间）
+    pub fn vhost(mut self, name: impl Into<String>) -> Self {
+        self.vhost = Some(name.into());
+        self
+    }
+
+    /// 设置注册时出示的邀请码（服务器仅限邀请注册时，首次登录的用户需要）
+    pub fn invite(mut self, code: impl Into<String>) -> Self {
+        self.invite = Some(code.into());
+        self
+    }
+
+    /// 设置登录令牌：机器人账号的令牌由管理员通过 `/bot create` 签发，管理员的令牌由运营者以 `chat admin token` 生成
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// 设置自定义消息处理器，替代默认的终端打印
+    pub fn on_message<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(Message) + Send + Sync + 'static,
+    {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// 设置自定义错误处理器，替代默认的终端打印
+    pub fn on_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(ErrorFrame) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// 将收发的消息保存到本地聊天记录
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// 设置提及与关键词高亮规则
+    pub fn highlight(mut self, rules: HighlightRules) -> Self {
+        self.highlight = rules;
+        self
+    }
+
+    /// 显示收到的消息时也展开表情短代码（默认只在发送前展开）
+    pub fn emoji_on_display(mut self, enabled: bool) -> Self {
+        self.emoji_on_display = enabled;
+        self
+    }
+
+    /// 是否以 ANSI 样式渲染消息中的 Markdown（默认开启）
+    pub fn markdown(mut self, enabled: bool) -> Self {
+        self.markdown = enabled;
+        self
+    }
+
+    /// 设置图片的显示方式，默认按终端的图形能力自动判断（见 [`Preview::detect`]）
+    pub fn preview(mut self, preview: Preview) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// 设置终端配色
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// 设置指令别名
+    pub fn aliases(mut self, aliases: Aliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// 设置收到的文件的保存目录，默认为当前目录下的 [`DEFAULT_DOWNLOADS`]
+    pub fn downloads(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.downloads = dir.into();
+        self
+    }
+
+    /// 设置身份密钥，为发出的消息签名（默认不签名）
+    #[cfg(feature = "crypto")]
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// 设置已知用户的公钥表，用于验证收到的消息签名（默认只保存在内存中）
+    #[cfg(feature = "crypto")]
+    pub fn known_keys(mut self, keys: KnownKeys) -> Self {
+        self.known_keys = keys;
+        self
+    }
+
+    /// 构建 `Client`
+    pub fn build(self) -> Client {
+        let theme = Arc::new(self.theme);
+        let name = ArcString::new(self.name.trim().to_string());
+        #[cfg(feature = "crypto")]
+        let identity = self.identity.map(Arc::new);
+        #[cfg(feature = "crypto")]
+        let known_keys = Arc::new(self.known_keys);
+        // 未启用 `crypto` 特性时不声明 `e2e`，其他客户端不会向本客户端发起端到端加密会话
+        let features = [
+            FEATURE_RESUME,
+            FEATURE_ERRORS,
+            FEATURE_POW,
+            FEATURE_FILES,
+            #[cfg(feature = "crypto")]
+            FEATURE_E2E,
+            FEATURE_ROSTER,
+            FEATURE_SHUTDOWN,
+        ];
+        let core = ClientCore::new(name.clone(), features.map(String::from).to_vec())
+            .with_lang(i18n::lang())
+            .with_host(self.vhost)
+            .with_invite(self.invite)
+            .with_token(self.token);
+        #[cfg(feature = "crypto")]
+        let core = core.with_identity(identity.as_ref().map(|identity| identity.public_key()));
+        Client {
+            name: name.clone(),
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            heartbeat: self.heartbeat,
+            reconnect: self.reconnect,
+            tls: self.tls,
+            socket: self.socket,
+            codec: self.codec,
+            handler: self.handler,
+            error_handler: self.error_handler,
+            history: self.history,
+            highlight: self.highlight,
+            emoji_on_display: self.emoji_on_display,
+            markdown: self.markdown,
+            preview: self.preview,
+            transfers: Arc::new(Transfers::new(self.downloads, theme.clone(), self.preview)),
+            #[cfg(feature = "crypto")]
+            e2e: Arc::new(E2e::new(
+                name.clone(),
+                identity.clone(),
+                known_keys.clone(),
+                theme.clone(),
+            )),
+            theme,
+            aliases: self.aliases,
+            #[cfg(feature = "crypto")]
+            identity,
+            #[cfg(feature = "crypto")]
+            known_keys,
+            muted: Arc::new(Mutex::new(HashSet::new())),
+            received: Arc::new(Mutex::new(Recent::new(RECEIVED_CAPACITY))),
+            links: Arc::new(Mutex::new(Recent::new(LINKS_CAPACITY))),
+            core: Arc::new(Mutex::new(core)),
+        }
+    }
+}
 
 /// 聊天客户端结构体
-#[derive(Debug)]
 pub struct Client {
     /// 客户端用户名，使用 `ArcString` 封装以避免重复克隆
     name: ArcString,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    heartbeat: Option<Duration>,
+    reconnect: ReconnectPolicy,
+    tls: Option<TlsConfig>,
+    socket: SocketOptions,
+    codec: Codec,
+    handler: Option<MessageHandler>,
+    error_handler: Option<ErrorHandler>,
+    history: Option<History>,
+    highlight: HighlightRules,
+    emoji_on_display: bool,
+    markdown: bool,
+    /// 图片的显示方式，`/preview` 使用
+    preview: Preview,
+    theme: Arc<Theme>,
+    aliases: Aliases,
+    /// 为发出的消息签名的身份密钥
+    #[cfg(feature = "crypto")]
+    identity: Option<Arc<Identity>>,
+    /// 已知用户的公钥，与接收任务共享
+    #[cfg(feature = "crypto")]
+    known_keys: Arc<KnownKeys>,
+    /// 本地静音的会话对象，与接收任务共享
+    muted: Arc<Mutex<HashSet<String>>>,
+    /// 最近收到的消息及其本地编号，供 `/forward` 使用
+    received: Arc<Mutex<Recent<Message>>>,
+    /// 最近消息中识别出的链接及其编号，供 `/open` 使用
+    links: Arc<Mutex<Recent<String>>>,
+    /// 协议状态（握手、断线续传与去重，见 [`crate::client_core`]），与接收任务共享
+    core: Arc<Mutex<ClientCore>>,
+    /// 进行中的文件传输，与接收任务共享
+    transfers: Arc<Transfers>,
+    /// 端到端加密会话，与接收任务共享
+    #[cfg(feature = "crypto")]
+    e2e: Arc<E2e>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("Client");
+        f.field("name", &self.name)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("read_timeout", &self.read_timeout)
+            .field("heartbeat", &self.heartbeat)
+            .field("reconnect", &self.reconnect)
+            .field("tls", &self.tls)
+            .field("socket", &self.socket)
+            .field("codec", &self.codec)
+            .field("handler", &self.handler.is_some())
+            .field("error_handler", &self.error_handler.is_some())
+            .field("history", &self.history)
+            .field("highlight", &self.highlight)
+            .field("emoji_on_display", &self.emoji_on_display)
+            .field("markdown", &self.markdown)
+            .field("preview", &self.preview)
+            .field("theme", &self.theme)
+            .field("aliases", &self.aliases)
+            .field("muted", &self.muted)
+            .field("received", &self.received.lock().unwrap().items.len())
+            .field("links", &self.links.lock().unwrap().items.len())
+            .field("core", &self.core)
+            .field("transfers", &self.transfers);
+        #[cfg(feature = "crypto")]
+        f.field("identity", &self.identity)
+            .field("known_keys", &self.known_keys)
+            .field("e2e", &self.e2e);
+        f.finish()
+    }
+}
+
+/// 一条已注册的连接，供程序化使用者发送消息
+pub struct Connection {
+    server_addr: String,
+    writer: SharedWriter,
+    recv_task: JoinHandle<()>,
+    heartbeat_task: Option<JoinHandle<()>>,
+    history: Option<History>,
+    theme: Arc<Theme>,
+    #[cfg(feature = "crypto")]
+    identity: Option<Arc<Identity>>,
+    core: Arc<Mutex<ClientCore>>,
+    /// 服务器的关闭通知建议的重连时间，未收到建议时为 `None`
+    reconnect_at: watch::Receiver<Option<tokio::time::Instant>>,
+}
+
+impl Connection {
+    /// 发送一条消息给 `to`
+    pub async fn send(
+        &mut self,
+        to: &str,
+        content: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.send_kind(to, content, MessageKind::Text).await
+    }
+
+    /// 发送一条动作消息给 `to`，接收方显示为 "* 用户名 动作"
+    pub async fn send_action(
+        &mut self,
+        to: &str,
+        action: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.send_kind(to, action, MessageKind::Action).await
+    }
+
+    async fn send_kind(
+        &mut self,
+        to: &str,
+        content: &str,
+        kind: MessageKind,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let msg = outgoing(
+            &self.core,
+            to,
+            content,
+            kind,
+            #[cfg(feature = "crypto")]
+            self.identity.as_deref(),
+        );
+        self.send_message(&msg, &msg).await
+    }
+
+    /// 发送一条已构造好的消息；重发时使用同一条消息，服务器按编号去重
+    ///
+    /// `plain` 为写入本地聊天记录的消息：端到端加密的消息记录加密前的内容
+    async fn send_message(
+        &mut self,
+        msg: &Message,
+        plain: &Message,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.lock().await.write_frame(msg).await?;
+        if let Some(history) = &self.history {
+            if !msg.to().starts_with('/') {
+                if let Err(e) = history.record(msg.to(), plain) {
+                    errln!(
+                        "{}: {}",
+                        self.theme.error.paint(&tr!("client.history_save_failed")),
+                        e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 当前连接的服务器地址
+    pub fn server_addr(&self) -> &str {
+        &self.server_addr
+    }
+
+    /// 服务器推送的在线名单（按用户名排序）；服务器不支持推送时为空
+    pub fn roster(&self) -> Vec<RosterEntry> {
+        self.core.lock().unwrap().roster().cloned().collect()
+    }
+
+    /// 接收任务是否已经结束（服务器关闭连接、读取超时或读取出错）
+    pub fn is_closed(&self) -> bool {
+        self.recv_task.is_finished()
+    }
+
+    /// 等到服务器的关闭通知建议的重连时间；服务器没有发来建议时一直等待
+    ///
+    /// 可以与读取输入等操作一起放在 `tokio::select!` 中，返回后调用方应重新连接。
+    pub async fn reconnect_due(&self) {
+        let mut reconnect_at = self.reconnect_at.clone();
+        let deadline = match reconnect_at.wait_for(Option::is_some).await {
+            Ok(deadline) => *deadline,
+            Err(_) => None,
+        };
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// 结束连接：发送 Goodbye 帧并关闭写入端，然后停止心跳与接收任务并等待其结束
+    ///
+    /// 服务器迟迟不读取时最多等待 [`CLOSE_TIMEOUT`]。
+    pub async fn close(self) {
+        self.leave(None).await;
+    }
+
+    /// 同 [`Connection::close`]，并附带告别语，由服务器转告与自己互发过消息的用户
+    pub async fn leave(mut self, message: Option<String>) {
+        if let Some(task) = &self.heartbeat_task {
+            task.abort();
+        }
+        let goodbye = async {
+            let mut writer = self.writer.lock().await;
+            let _ = writer
+                .write_frame(&Frame::from(Control::Goodbye { message }))
+                .await;
+            let _ = writer.shutdown().await;
+        };
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, goodbye).await;
+        self.recv_task.abort();
+        let _ = (&mut self.recv_task).await;
+    }
+}
+
+impl Drop for Connection {
+    /// 连接被关闭或被重连后的新连接替换时，停止其接收与心跳任务
+    fn drop(&mut self) {
+        self.recv_task.abort();
+        if let Some(task) = &self.heartbeat_task {
+            task.abort();
+        }
+    }
 }
 
 impl Client {
@@ -36,109 +641,1677 @@ impl Client {
     /// # 返回值
     /// 返回 `Client` 实例
     pub fn new(name: String) -> Self {
-        Self {
-            name: ArcString::new(name.trim().to_string()),
+        Self::builder(name).build()
+    }
+
+    /// 创建客户端构建器
+    ///
+    /// # 参数
+    /// - `name`: 客户端用户名
+    pub fn builder(name: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            name: name.into(),
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            read_timeout: None,
+            heartbeat: Some(DEFAULT_HEARTBEAT),
+            reconnect: ReconnectPolicy::Never,
+            tls: None,
+            socket: SocketOptions::default(),
+            codec: Codec::default(),
+            vhost: None,
+            invite: None,
+            token: None,
+            handler: None,
+            error_handler: None,
+            history: None,
+            highlight: HighlightRules::default(),
+            emoji_on_display: false,
+            markdown: true,
+            preview: Preview::detect(),
+            theme: Theme::default(),
+            aliases: Aliases::default(),
+            downloads: PathBuf::from(DEFAULT_DOWNLOADS),
+            #[cfg(feature = "crypto")]
+            identity: None,
+            #[cfg(feature = "crypto")]
+            known_keys: KnownKeys::new(),
         }
     }
 
-    /// 启动客户端：连接服务器、注册用户、并同时处理发送和接收消息
-    pub async fn run(&self, addr: String) -> Result<(), Box<dyn std::error::Error>> {
-        // 强制转换为IPv4地址
-        let addr: SocketAddr = addr.parse()?;
-        // 连接到服务器
-        let stream = TcpStream::connect(addr).await?;
-        println!("{}", "成功连接到服务器".green().bold());
-
-        // 使用 split 分离读写任务
-        let (mut reader, mut writer) = stream.into_split();
-
-        // 发送注册消息，内容为用户名字符串
-        let reg_msg = self.name.get();
-        writer.write_all(reg_msg.as_bytes()).await?;
-
-        // 启动接收任务，处理来自服务器转发的消息
-        let _recv_task = spawn(async move {
-            let mut buf = [0u8; 1024];
-            loop {
-                match reader.read(&mut buf).await {
-                    Ok(0) => {
-                        print!("\r\x1b[K"); // \r 回到行首，\x1b[K 清除该行
-
-                        println!("{}", "服务器关闭了连接".red().bold());
-                        process::exit(1);
-                    }
-                    Ok(n) => {
-                        let json_str = String::from_utf8_lossy(&buf[..n]);
-                        match serde_json::from_str::<Message>(&json_str) {
-                            Ok(message) => {
-                                // **清除当前输入行并刷新终端**
-                                print!("\r\x1b[K"); // \r 回到行首，\x1b[K 清除该行
-                                                    // 打印接收到的消息（显示发送者和内容）
-                                println!(
-                                    "\n[{}] {}: {}",
-                                    message.time_stamp().bright_black(),
-                                    message.from().cyan().bold(),
-                                    message.content().yellow()
-                                );
-
-                                // **重新显示输入提示**
-                                print!("{}", "请输入接收方: ".cyan().bold());
-                                io::stdout().flush().unwrap();
-                            }
-                            Err(e) => {
-                                eprintln!("{}: {:?}", "解析服务器消息失败".red().bold(), e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("{}: {:?}", "读取服务器消息失败".red().bold(), e);
-                        break;
+    /// 连接服务器并完成注册，返回可用于发送消息的连接
+    ///
+    /// `addr` 可以是以逗号分隔的多个服务器地址，按顺序尝试直到连接成功。
+    /// 收到的消息交给自定义处理器；未设置处理器时打印到终端。
+    pub async fn connect(&self, addr: &str) -> Result<Connection, Box<dyn std::error::Error>> {
+        self.failover(&server_list(addr), 0, false).await
+    }
+
+    /// 从第 `start` 个地址开始依次尝试连接，直到某个服务器连接成功
+    async fn failover(
+        &self,
+        addrs: &[String],
+        start: usize,
+        interactive: bool,
+    ) -> Result<Connection, Box<dyn std::error::Error>> {
+        // 只有一个地址且不重连时，交互模式下连接断开即退出程序
+        let exit_on_close =
+            interactive && addrs.len() == 1 && self.reconnect == ReconnectPolicy::Never;
+        let mut last_error = None;
+        for i in 0..addrs.len() {
+            let addr = &addrs[(start + i) % addrs.len()];
+            match self.connect_one(addr, exit_on_close).await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => {
+                    if interactive && addrs.len() > 1 {
+                        errln!(
+                            "{} {}: {}",
+                            self.theme.error.paint(&tr!("client.connect_failed")),
+                            addr,
+                            e
+                        );
                     }
+                    last_error = Some(e);
                 }
             }
+        }
+        Err(last_error.unwrap_or_else(|| tr!("client.no_address").into()))
+    }
+
+    async fn connect_one(
+        &self,
+        addr: &str,
+        exit_on_close: bool,
+    ) -> Result<Connection, Box<dyn std::error::Error>> {
+        // 连接到服务器（解析地址、TCP 连接及 TLS 握手均受连接超时约束）
+        let (reader, writer) =
+            transport::connect(addr, self.tls.as_ref(), &self.socket, self.connect_timeout).await?;
+
+        // 发送注册握手帧
+        let mut writer = FrameWriter::new(writer, self.codec);
+        let hello = self.core.lock().unwrap().hello();
+        writer.write_frame(&hello).await?;
+
+        let reader = FrameReader::new(reader, self.codec);
+        let inbox = Inbox {
+            own_name: self.name.clone(),
+            handler: self.handler.clone(),
+            error_handler: self.error_handler.clone(),
+            history: self.history.clone(),
+            highlight: self.highlight.clone(),
+            emoji_on_display: self.emoji_on_display,
+            markdown: self.markdown,
+            theme: self.theme.clone(),
+            muted: self.muted.clone(),
+            received: self.received.clone(),
+            links: self.links.clone(),
+            core: self.core.clone(),
+            transfers: self.transfers.clone(),
+            #[cfg(feature = "crypto")]
+            known_keys: self.known_keys.clone(),
+            #[cfg(feature = "crypto")]
+            e2e: self.e2e.clone(),
+            #[cfg(feature = "crypto")]
+            identity: self.identity.clone(),
+        };
+        let last_frame = Arc::new(Mutex::new(Instant::now()));
+        let writer = Arc::new(tokio::sync::Mutex::new(writer));
+        let (reconnect_tx, reconnect_at) = watch::channel(None);
+        let recv_task = spawn(receive_loop(
+            reader,
+            writer.clone(),
+            self.read_timeout,
+            inbox,
+            last_frame.clone(),
+            reconnect_tx,
+            exit_on_close,
+        ));
+        let heartbeat_task = self.heartbeat.map(|interval| {
+            spawn(heartbeat_loop(
+                writer.clone(),
+                interval,
+                last_frame,
+                recv_task.abort_handle(),
+                self.theme.clone(),
+                exit_on_close,
+            ))
         });
 
+        Ok(Connection {
+            server_addr: addr.to_string(),
+            writer,
+            recv_task,
+            heartbeat_task,
+            history: self.history.clone(),
+            theme: self.theme.clone(),
+            #[cfg(feature = "crypto")]
+            identity: self.identity.clone(),
+            core: self.core.clone(),
+            reconnect_at,
+        })
+    }
+
+    /// 按服务器关闭通知的建议重新连接：先依次尝试各个服务器一次，全部失败后再按重连策略重试
+    async fn scheduled_reconnect(
+        &self,
+        addrs: &[String],
+        lost: &Connection,
+    ) -> Result<Connection, Box<dyn std::error::Error>> {
+        let current = addrs
+            .iter()
+            .position(|addr| addr == lost.server_addr())
+            .unwrap_or(0);
+        outln!(
+            "{}",
+            self.theme.system.paint(&tr!("client.reconnecting_now"))
+        );
+        match self.failover(addrs, current, true).await {
+            Ok(conn) => {
+                outln!(
+                    "{} {}",
+                    self.theme.success.paint(&tr!("client.connected")),
+                    conn.server_addr()
+                );
+                Ok(conn)
+            }
+            Err(e) => {
+                errln!(
+                    "{}: {}",
+                    self.theme.error.paint(&tr!("client.reconnect_failed")),
+                    e
+                );
+                self.reconnect(addrs, lost).await
+            }
+        }
+    }
+
+    /// 连接断开后重新连接：先立即切换到其余服务器，全部失败后再按重连策略重试
+    async fn reconnect(
+        &self,
+        addrs: &[String],
+        lost: &Connection,
+    ) -> Result<Connection, Box<dyn std::error::Error>> {
+        let current = addrs
+            .iter()
+            .position(|addr| addr == lost.server_addr())
+            .unwrap_or(0);
+        let mut last_error = None;
+        if addrs.len() > 1 {
+            outln!("{}", self.theme.system.paint(&tr!("client.switching")));
+            match self.failover(addrs, current + 1, true).await {
+                Ok(conn) => {
+                    outln!(
+                        "{} {}",
+                        self.theme.success.paint(&tr!("client.connected")),
+                        conn.server_addr()
+                    );
+                    return Ok(conn);
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let mut attempt = 0;
+        while let Some(delay) = self.reconnect.delay(attempt) {
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+            outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("client.reconnecting", attempt = attempt))
+            );
+            match self.failover(addrs, current, true).await {
+                Ok(conn) => {
+                    outln!(
+                        "{} {}",
+                        self.theme.success.paint(&tr!("client.connected")),
+                        conn.server_addr()
+                    );
+                    return Ok(conn);
+                }
+                Err(e) => {
+                    errln!(
+                        "{}: {}",
+                        self.theme.error.paint(&tr!("client.reconnect_failed")),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| tr!("client.reconnect_exhausted").into()))
+    }
+
+    /// 启动客户端：连接服务器、注册用户、并同时处理发送和接收消息
+    ///
+    /// `addr` 可以是以逗号分隔的多个服务器地址：连接失败或连接中断时
+    /// 自动切换到下一个地址，并以相同的用户名重新注册。
+    pub async fn run(&self, addr: String) -> Result<(), Box<dyn std::error::Error>> {
+        let addrs = server_list(&addr);
+        let mut conn = self.failover(&addrs, 0, true).await?;
+        outln!("{}", self.theme.success.paint(&tr!("client.connected")));
+
+        let own_name = self.name.get();
+        // 输入在专用线程中读取，等待输入时不占用运行时的工作线程
+        let mut input = Input::spawn();
+
         // 主循环：交互式读取用户输入并发送消息
         loop {
             // 提示输入目标接收方
-            print!("{}", "请输入接收方: ".cyan().bold());
-            io::stdout().flush()?;
-            let mut recipient = String::new();
-            io::stdin().read_line(&mut recipient)?;
-            let recipient = recipient.trim().to_string();
+            terminal::screen().prompt(self.theme.prompt.paint(&tr!("client.prompt_recipient")));
+            // 输入结束（EOF）时按 `/exit` 处理；等待输入期间服务器的关闭通知建议的重连时间到达时重新连接
+            let recipient = tokio::select! {
+                line = input.read_line() => line?.unwrap_or_else(|| "/exit".into()),
+                () = conn.reconnect_due() => {
+                    conn = self.scheduled_reconnect(&addrs, &conn).await?;
+                    continue;
+                }
+            };
+            let mut recipient = recipient.trim().to_string();
+            if let Some(expanded) = self.aliases.expand(&recipient) {
+                recipient = expanded;
+            }
             let mut content = String::new();
+            let mut kind = MessageKind::Text;
 
-            if recipient == "/exit" {
-                println!("{}", "再见！感谢使用 ChatApp!".green().bold());
-                process::exit(0);
-            } else if recipient == reg_msg {
-                println!("{}", "无法发送消息给自己".yellow().bold());
+            #[cfg(feature = "crypto")]
+            if self
+                .identity_command(&recipient, &own_name, &addrs, &mut conn)
+                .await?
+            {
+                continue;
+            }
+            if let Some(message) = command_args(&recipient, "/exit") {
+                outln!("{}", self.theme.success.paint(&tr!("client.goodbye")));
+                let message = (!message.is_empty()).then(|| message.to_string());
+                conn.leave(message).await;
+                return Ok(());
+            } else if recipient == own_name {
+                outln!("{}", self.theme.system.paint(&tr!("client.self_message")));
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/history") {
+                self.show_history(args);
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/export") {
+                self.export_history(args);
+                continue;
+            } else if let Some(query) = command_args(&recipient, "/emoji") {
+                show_emoji(&self.theme, query);
+                continue;
+            } else if command_args(&recipient, "/alias").is_some() {
+                self.show_aliases();
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/msg") {
+                // 一行发送：`/msg <用户> <内容>`，内容可以用双引号包围
+                let (to, text) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let text = text.trim();
+                let text = text
+                    .strip_prefix('"')
+                    .and_then(|text| text.strip_suffix('"'))
+                    .unwrap_or(text);
+                if to.is_empty() || text.is_empty() {
+                    outln!("{}", self.theme.system.paint(&tr!("client.usage_msg")));
+                    continue;
+                } else if to == own_name {
+                    outln!("{}", self.theme.system.paint(&tr!("client.self_message")));
+                    continue;
+                }
+                content = text.to_string();
+                recipient = to.to_string();
+            } else if let Some(args) = command_args(&recipient, "/forward") {
+                // 转发收到的消息：`/forward <编号> <用户>`，正文前附上原发送者
+                let (id, to) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let to = to.trim();
+                let id = id.trim_start_matches('#').parse().ok();
+                let (Some(id), false) = (id, to.is_empty()) else {
+                    outln!("{}", self.theme.system.paint(&tr!("client.usage_forward")));
+                    continue;
+                };
+                if to == own_name {
+                    outln!("{}", self.theme.system.paint(&tr!("client.self_message")));
+                    continue;
+                }
+                let Some(original) = self.received.lock().unwrap().get(id).cloned() else {
+                    outln!(
+                        "{}",
+                        self.theme.system.paint(&tr!("client.no_message", id = id))
+                    );
+                    continue;
+                };
+                content = forwarded(&original);
+                recipient = to.to_string();
+            } else if let Some(args) = command_args(&recipient, "/sendfile") {
+                // 发送文件：`/sendfile <用户> <路径>`，路径可以用双引号包围
+                let (to, path) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let path = path.trim();
+                let path = path
+                    .strip_prefix('"')
+                    .and_then(|path| path.strip_suffix('"'))
+                    .unwrap_or(path);
+                if to.is_empty() || path.is_empty() {
+                    outln!("{}", self.theme.system.paint(&tr!("client.usage_sendfile")));
+                } else if to == own_name {
+                    outln!("{}", self.theme.system.paint(&tr!("client.self_message")));
+                } else {
+                    if conn.is_closed() {
+                        conn = self.reconnect(&addrs, &conn).await?;
+                    }
+                    self.transfers
+                        .send(conn.writer.clone(), conn.server_addr(), to, path)
+                        .await;
+                }
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/accept") {
+                self.answer_file(&conn, args, true).await;
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/reject") {
+                self.answer_file(&conn, args, false).await;
+                continue;
+            } else if command_args(&recipient, "/transfers").is_some() {
+                self.transfers.list();
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/cancel-transfer") {
+                match args.trim_start_matches('#').parse() {
+                    Ok(number) => self.transfers.cancel(&conn.writer, number).await,
+                    Err(_) => outln!(
+                        "{}",
+                        self.theme
+                            .system
+                            .paint(&tr!("client.usage_cancel_transfer"))
+                    ),
+                }
                 continue;
-            } else if recipient == "/list" {
-                content = String::from("");
+            } else if let Some(args) = command_args(&recipient, "/open") {
+                self.open_link(args);
+                continue;
+            } else if let Some(args) = command_args(&recipient, "/preview") {
+                self.preview_image(args);
+                continue;
+            } else if let Some(peer) = command_args(&recipient, "/mute") {
+                self.mute(peer, true);
+                continue;
+            } else if let Some(peer) = command_args(&recipient, "/unmute") {
+                self.mute(peer, false);
+                continue;
+            } else if recipient.starts_with('/') {
+                // 其余指令交给服务器处理：指令作为接收者，参数作为消息内容
+                let (command, args) = recipient
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((&recipient, ""));
+                content = args.trim().to_string();
+                recipient = command.to_string();
             } else {
                 // 提示输入消息内容
-                print!("{}", "请输入消息内容: ".purple().bold());
-                io::stdout().flush()?;
-                io::stdin().read_line(&mut content)?;
+                terminal::screen().prompt(
+                    self.theme
+                        .content_prompt
+                        .paint(&tr!("client.prompt_content")),
+                );
+                content.push_str(&input.read_line().await?.unwrap_or_default());
+
+                // 多行输入模式：逐行读取直到结束标记
+                let line = content.trim().to_string();
+                content = if line == "/multiline" {
+                    self.read_multiline(&mut input, None).await?
+                } else if let Some(first) = line.strip_prefix("\"\"\"") {
+                    self.read_multiline(&mut input, Some(first)).await?
+                } else {
+                    line
+                };
+                if content.is_empty() {
+                    continue;
+                }
+            }
+
+            // 发给用户的消息：以 `/me ` 开头的内容作为动作消息，并展开表情短代码
+            if !recipient.starts_with('/') {
+                if let Some(action) = command_args(&content, "/me") {
+                    if action.is_empty() {
+                        outln!("{}", self.theme.system.paint(&tr!("client.usage_me")));
+                        continue;
+                    }
+                    kind = MessageKind::Action;
+                    content = action.to_string();
+                }
+                content = emoji::expand(&content).into_owned();
+            } else if recipient == "/profile" {
+                // 头像、简介中的表情短代码同样展开，如 `/profile avatar :cat:`
+                content = emoji::expand(&content).into_owned();
+            }
+
+            // 接收任务已结束说明连接已断开，切换服务器或按策略重连后再发送
+            if conn.is_closed() {
+                conn = self.reconnect(&addrs, &conn).await?;
             }
 
-            // 构造消息对象，from 为自身用户名，to 为用户输入的接收方
-            let msg = Message::new(
-                self.name.clone(),
-                recipient.trim().to_string(),
-                content.trim().to_string(),
+            // 与对方有端到端加密会话时先加密，再为密文签名
+            #[cfg(feature = "crypto")]
+            let (sent_kind, sent_content) = if recipient.starts_with('/') {
+                (kind, content.clone())
+            } else {
+                match self
+                    .e2e
+                    .seal(&conn.writer, &recipient, kind, &content)
+                    .await
+                {
+                    Sealing::Plain => (kind, content.clone()),
+                    Sealing::Sealed(sealed) => (MessageKind::Encrypted, sealed),
+                    Sealing::Pending => {
+                        outln!(
+                            "{}",
+                            self.theme
+                                .system
+                                .paint(&tr!("e2e.pending", user = recipient))
+                        );
+                        continue;
+                    }
+                }
+            };
+            #[cfg(not(feature = "crypto"))]
+            let (sent_kind, sent_content) = (kind, content.clone());
+
+            // 将消息发送到服务器；失败时重连并重发同一条消息（编号不变，服务器会去重）
+            let msg = outgoing(
+                &self.core,
+                &recipient,
+                &sent_content,
+                sent_kind,
+                #[cfg(feature = "crypto")]
+                self.identity.as_deref(),
             );
-            let json_msg = serde_json::to_string(&msg)?;
-            // 将消息发送到服务器
-            if let Err(e) = writer.write_all(json_msg.as_bytes()).await {
-                eprintln!("发送消息失败: {:?}", e);
-                return Ok(());
+            let plain = msg.clone().with_kind(kind).with_content(content);
+            if let Err(e) = conn.send_message(&msg, &plain).await {
+                errln!("{}", tr!("client.send_failed", error = format!("{:?}", e)));
+                if addrs.len() == 1 && self.reconnect == ReconnectPolicy::Never {
+                    return Ok(());
+                }
+                conn = self.reconnect(&addrs, &conn).await?;
+                conn.send_message(&msg, &plain).await?;
             }
         }
+    }
 
-        // 一般不会到达此处，等待接收任务结束
-        //recv_task.await?;
-        //Ok(())
+    /// 多行输入模式：逐行读取消息内容，保留换行与缩进
+    ///
+    /// # 参数
+    /// - `input`: 输入源
+    /// - `quoted`: 以 `"""` 开始时为该行 `"""` 之后的内容，读取到以 `"""` 结尾的行为止；
+    ///   为 `None` 时（`/multiline`）读取到单独一行 `/end` 为止
+    async fn read_multiline(&self, input: &mut Input, quoted: Option<&str>) -> io::Result<String> {
+        let mut lines = Vec::new();
+        if let Some(first) = quoted {
+            // 同一行内即以 `"""` 结尾
+            if let Some(text) = first.strip_suffix("\"\"\"") {
+                return Ok(text.to_string());
+            }
+            if !first.is_empty() {
+                lines.push(first.to_string());
+            }
+        }
+        let hint = match quoted {
+            Some(_) => tr!("client.multiline_quoted"),
+            None => tr!("client.multiline_end"),
+        };
+        outln!("{}", self.theme.dim.paint(&hint));
+        loop {
+            terminal::screen().prompt(self.theme.dim.paint("... "));
+            let Some(line) = input.read_line().await? else {
+                break;
+            };
+            let line = line.as_str();
+            match quoted {
+                Some(_) => {
+                    if let Some(last) = line.strip_suffix("\"\"\"") {
+                        if !last.is_empty() {
+                            lines.push(last.to_string());
+                        }
+                        break;
+                    }
+                }
+                None if line.trim() == "/end" => break,
+                None => {}
+            }
+            lines.push(line.to_string());
+        }
+        Ok(lines.join("\n").trim_end().to_string())
+    }
+
+    /// 处理 `/accept <编号>` 与 `/reject <编号>`：应答收到的文件
+    async fn answer_file(&self, conn: &Connection, args: &str, accept: bool) {
+        let Ok(number) = args.trim_start_matches('#').parse() else {
+            let usage = if accept {
+                tr!("client.usage_accept")
+            } else {
+                tr!("client.usage_reject")
+            };
+            outln!("{}", self.theme.system.paint(&usage));
+            return;
+        };
+        self.transfers
+            .answer(conn.writer.clone(), conn.server_addr(), number, accept)
+            .await;
+    }
+
+    /// 处理 `/alias`：列出所有别名
+    fn show_aliases(&self) {
+        let mut aliases = self.aliases.iter().peekable();
+        if aliases.peek().is_none() {
+            outln!("{}", self.theme.system.paint(&tr!("client.no_aliases")));
+            return;
+        }
+        for (name, expansion) in aliases {
+            outln!("  /{} = {}", name, expansion);
+        }
+    }
+
+    /// 处理 `/history <用户> [条数]`：打印与该用户的本地聊天记录
+    fn show_history(&self, args: &str) {
+        let mut args = args.split_whitespace();
+        let Some(peer) = args.next() else {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_history")));
+            return;
+        };
+        let limit = args.next().and_then(|n| n.parse().ok()).unwrap_or(20);
+        let Some(history) = &self.history else {
+            outln!(
+                "{}",
+                self.theme.system.paint(&tr!("client.history_disabled"))
+            );
+            return;
+        };
+        match history.recent(peer, limit) {
+            Ok(entries) if entries.is_empty() => {
+                outln!(
+                    "{}",
+                    self.theme
+                        .system
+                        .paint(&tr!("client.history_empty", peer = peer))
+                );
+            }
+            Ok(entries) => {
+                for entry in entries {
+                    let recorded_at = entry.recorded_at.format("%Y-%m-%d %H:%M:%S");
+                    let message = &entry.message;
+                    match message.kind() {
+                        MessageKind::Text | MessageKind::Encrypted => outln!(
+                            "[{}] {}: {}",
+                            self.theme.timestamp.paint(&recorded_at.to_string()),
+                            self.theme.sender.paint(message.from()),
+                            self.theme.content.paint(message.content())
+                        ),
+                        MessageKind::Action => outln!(
+                            "[{}] {}",
+                            self.theme.timestamp.paint(&recorded_at.to_string()),
+                            self.theme
+                                .action
+                                .paint(&format!("* {} {}", message.from(), message.content()))
+                                .italic()
+                        ),
+                    }
+                }
+            }
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("client.history_read_failed")),
+                e
+            ),
+        }
+    }
+
+    /// 处理 `/export <用户> [路径]`：把与该用户的全部本地聊天记录导出为 HTML 文件，
+    /// 默认写到当前目录下的 `<用户>.html`
+    fn export_history(&self, args: &str) {
+        let (peer, path) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let path = path.trim();
+        let path = path
+            .strip_prefix('"')
+            .and_then(|path| path.strip_suffix('"'))
+            .unwrap_or(path);
+        if peer.is_empty() {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_export")));
+            return;
+        }
+        let Some(history) = &self.history else {
+            outln!(
+                "{}",
+                self.theme.system.paint(&tr!("client.history_disabled"))
+            );
+            return;
+        };
+        let entries = match history.recent(peer, usize::MAX) {
+            Ok(entries) => entries,
+            Err(e) => {
+                errln!(
+                    "{}: {}",
+                    self.theme.error.paint(&tr!("client.history_read_failed")),
+                    e
+                );
+                return;
+            }
+        };
+        if entries.is_empty() {
+            outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("client.history_empty", peer = peer))
+            );
+            return;
+        }
+        let path = match path {
+            "" => PathBuf::from(format!("{}.html", history::file_stem(peer))),
+            path => PathBuf::from(path),
+        };
+        let html = export::to_html(&self.name.get(), peer, &entries, i18n::lang());
+        match fs::write(&path, html) {
+            Ok(()) => outln!(
+                "{}",
+                self.theme.success.paint(&tr!(
+                    "client.exported",
+                    count = entries.len(),
+                    path = path.display(),
+                ))
+            ),
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("client.export_failed")),
+                e
+            ),
+        }
+    }
+
+    /// 处理 `/open [编号]`：用浏览器打开消息中的链接，不带编号时列出最近的链接
+    fn open_link(&self, args: &str) {
+        let links = self.links.lock().unwrap();
+        if args.is_empty() {
+            if links.items.is_empty() {
+                outln!("{}", self.theme.system.paint(&tr!("client.no_links")));
+            }
+            for (id, url) in &links.items {
+                outln!("  [{}] {}", id, self.theme.link.paint(url));
+            }
+            return;
+        }
+        let Some(url) = args.parse().ok().and_then(|id| links.get(id)) else {
+            outln!(
+                "{}",
+                self.theme.system.paint(&tr!("client.no_link", id = args))
+            );
+            return;
+        };
+        match link::open(url) {
+            Ok(()) => outln!(
+                "{} {}",
+                self.theme.success.paint(&tr!("client.opened")),
+                url
+            ),
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("client.open_failed")),
+                e
+            ),
+        }
+    }
+
+    /// 处理 `/preview <编号|路径>`：在后台抓取第 N 个链接或读取本地文件，显示图片预览
+    fn preview_image(&self, args: &str) {
+        if args.is_empty() {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_preview")));
+            return;
+        }
+        let url = match args.parse() {
+            Ok(id) => match self.links.lock().unwrap().get(id) {
+                Some(url) => Some(url.clone()),
+                None => {
+                    outln!(
+                        "{}",
+                        self.theme.system.paint(&tr!("client.no_link", id = args))
+                    );
+                    return;
+                }
+            },
+            Err(_) => None,
+        };
+        let (label, mode, theme) = (args.to_string(), self.preview, self.theme.clone());
+        spawn(async move {
+            let (label, bytes) = match url {
+                Some(url) => (url.clone(), preview::fetch(&url).await),
+                None => (label.clone(), preview::read(label.as_ref()).await),
+            };
+            match bytes.map(|bytes| preview::render(&bytes, &label, mode)) {
+                Ok(Some(text)) => outln!("{}", text),
+                Ok(None) => outln!(
+                    "{}",
+                    theme.system.paint(&tr!("preview.not_image", name = label))
+                ),
+                Err(e) => errln!(
+                    "{}: {}",
+                    theme.error.paint(&tr!("preview.failed", name = label)),
+                    e
+                ),
+            }
+        });
+    }
+
+    /// 处理 `/mute [用户]` 与 `/unmute <用户>`：在本地静音或取消静音一个会话
+    fn mute(&self, peer: &str, mute: bool) {
+        let mut muted = self.muted.lock().unwrap();
+        if peer.is_empty() {
+            if !mute {
+                outln!("{}", self.theme.system.paint(&tr!("client.usage_unmute")));
+            } else if muted.is_empty() {
+                outln!("{}", self.theme.system.paint(&tr!("client.none_muted")));
+            } else {
+                let mut list: Vec<&String> = muted.iter().collect();
+                list.sort();
+                let list = list.into_iter().cloned().collect::<Vec<_>>().join(", ");
+                outln!("{}", tr!("client.muted_list", list = list));
+            }
+        } else if mute {
+            muted.insert(peer.to_string());
+            outln!(
+                "{}",
+                self.theme.success.paint(&tr!("client.muted", peer = peer))
+            );
+        } else if muted.remove(peer) {
+            outln!(
+                "{}",
+                self.theme
+                    .success
+                    .paint(&tr!("client.unmuted", peer = peer))
+            );
+        } else {
+            outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("client.not_muted", peer = peer))
+            );
+        }
+    }
+}
+
+/// 身份密钥与端到端加密的指令（需启用 `crypto` 特性）
+#[cfg(feature = "crypto")]
+impl Client {
+    /// 处理 `/fingerprint`、`/trust`、`/verify`、`/unverify` 与 `/e2e`，`recipient` 不是这些指令时返回 `false`
+    async fn identity_command(
+        &self,
+        recipient: &str,
+        own_name: &str,
+        addrs: &[String],
+        conn: &mut Connection,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(user) = command_args(recipient, "/fingerprint") {
+            self.show_fingerprint(user);
+        } else if let Some(user) = command_args(recipient, "/trust") {
+            self.trust(user);
+        } else if let Some(args) = command_args(recipient, "/verify") {
+            self.verify(args);
+        } else if let Some(user) = command_args(recipient, "/unverify") {
+            self.unverify(user);
+        } else if let Some(args) = command_args(recipient, "/e2e") {
+            // 端到端加密会话：`/e2e <用户>` 发起，`/e2e off <用户>` 结束，`/e2e` 列出
+            if args.is_empty() {
+                self.e2e.list();
+            } else if let Some(user) = command_args(args, "off") {
+                if user.is_empty() {
+                    outln!("{}", self.theme.system.paint(&tr!("client.usage_e2e")));
+                } else {
+                    self.e2e.stop(user);
+                }
+            } else if args == own_name {
+                outln!("{}", self.theme.system.paint(&tr!("client.self_message")));
+            } else {
+                if conn.is_closed() {
+                    *conn = self.reconnect(addrs, conn).await?;
+                }
+                self.e2e.start(&conn.writer, args).await;
+            }
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// 处理 `/fingerprint [用户]`：显示自己或对方的身份公钥指纹，供带外核对
+    fn show_fingerprint(&self, user: &str) {
+        if user.is_empty() {
+            let text = match &self.identity {
+                Some(identity) => tr!("identity.own", fingerprint = identity.fingerprint()),
+                None => tr!("identity.disabled"),
+            };
+            outln!("{}", self.theme.system.paint(&text));
+            return;
+        }
+        let text = match self.known_keys.get(user) {
+            Some(key) if self.known_keys.is_verified(user) => tr!(
+                "identity.peer_verified",
+                user = user,
+                fingerprint = fingerprint(&key)
+            ),
+            Some(key) => tr!(
+                "identity.peer",
+                user = user,
+                fingerprint = fingerprint(&key)
+            ),
+            None => tr!("identity.unknown", user = user),
+        };
+        outln!("{}", self.theme.system.paint(&text));
+    }
+
+    /// 处理 `/verify <用户> <指纹>`：与对方带外核对指纹后，把其公钥标记为已验证
+    fn verify(&self, args: &str) {
+        let (user, claimed) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        if user.is_empty() || claimed.trim().is_empty() {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_verify")));
+            return;
+        }
+        match self.known_keys.verify(user, claimed) {
+            Ok(Verify::Verified) => outln!(
+                "{}",
+                self.theme
+                    .success
+                    .paint(&tr!("identity.verified", user = user))
+            ),
+            Ok(Verify::Mismatch(expected)) => outln!(
+                "{}",
+                self.theme.error.paint(&tr!(
+                    "identity.verify_mismatch",
+                    user = user,
+                    fingerprint = expected
+                ))
+            ),
+            Ok(Verify::Unknown) => outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("identity.unknown", user = user))
+            ),
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("identity.save_failed")),
+                e
+            ),
+        }
+    }
+
+    /// 处理 `/unverify <用户>`：取消对方公钥的已验证标记
+    fn unverify(&self, user: &str) {
+        if user.is_empty() {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_unverify")));
+            return;
+        }
+        match self.known_keys.unverify(user) {
+            Ok(true) => outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("identity.unverified", user = user))
+            ),
+            Ok(false) => outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("identity.not_verified", user = user))
+            ),
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("identity.save_failed")),
+                e
+            ),
+        }
+    }
+
+    /// 处理 `/trust <用户>`：接受对方更换后的身份公钥
+    fn trust(&self, user: &str) {
+        if user.is_empty() {
+            outln!("{}", self.theme.system.paint(&tr!("client.usage_trust")));
+            return;
+        }
+        match self.known_keys.trust(user) {
+            Ok(Some(key)) => outln!(
+                "{}",
+                self.theme.success.paint(&tr!(
+                    "identity.trusted",
+                    user = user,
+                    fingerprint = fingerprint(&key)
+                ))
+            ),
+            Ok(None) => outln!(
+                "{}",
+                self.theme
+                    .system
+                    .paint(&tr!("identity.not_changed", user = user))
+            ),
+            Err(e) => errln!(
+                "{}: {}",
+                self.theme.error.paint(&tr!("identity.save_failed")),
+                e
+            ),
+        }
+    }
+}
+
+/// 处理 `/emoji <查询>`：列出匹配的表情短代码
+fn show_emoji(theme: &Theme, query: &str) {
+    if query.is_empty() {
+        outln!("{}", theme.system.paint(&tr!("client.usage_emoji")));
+        return;
+    }
+    let found = emoji::search(query, 20);
+    if found.is_empty() {
+        outln!(
+            "{}",
+            theme.system.paint(&tr!("client.no_emoji", query = query))
+        );
+        return;
+    }
+    for (code, emoji) in found {
+        outln!("  {} :{}:", emoji, code);
+    }
+}
+
+/// 构造一条待发送的消息，附带随机生成的编号供服务器去重（见 [`ClientCore::message`]）；
+/// 发给用户的消息由 `identity` 签名（需启用 `crypto` 特性）
+///
+/// 内容先按服务器的规则清除终端控制序列（见 [`crate::sanitize`]），
+/// 服务器不必再改动内容，签名在接收方仍然有效
+fn outgoing(
+    core: &Mutex<ClientCore>,
+    to: &str,
+    content: &str,
+    kind: MessageKind,
+    #[cfg(feature = "crypto")] identity: Option<&Identity>,
+) -> Message {
+    let content = sanitize(content);
+    let msg = core.lock().unwrap().message(to, &content, kind);
+    #[cfg(feature = "crypto")]
+    if let Some(identity) = identity.filter(|_| !to.starts_with('/')) {
+        let signature = identity.sign(&msg);
+        return msg.with_signature(signature);
+    }
+    msg
+}
+
+/// 转发消息的正文：第一行注明原发送者，其后为原消息内容
+fn forwarded(original: &Message) -> String {
+    let body = match original.kind() {
+        MessageKind::Text | MessageKind::Encrypted => original.content().to_string(),
+        MessageKind::Action => format!("* {} {}", original.from(), original.content()),
+    };
+    format!(
+        "{}\n{}",
+        tr!("client.forwarded", from = original.from()),
+        body
+    )
+}
+
+/// 若输入以指令 `command` 开头（整个单词匹配），返回指令之后的参数部分
+fn command_args<'a>(input: &'a str, command: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(command)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then(|| rest.trim())
+}
+
+/// 将以逗号分隔的服务器地址拆分为列表
+fn server_list(addr: &str) -> Vec<String> {
+    addr.split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 最多保留的已收消息条数，更早的消息无法再转发
+const RECEIVED_CAPACITY: usize = 200;
+
+/// 最多保留的链接条数，更早的链接无法再通过 `/open` 打开
+const LINKS_CAPACITY: usize = 50;
+
+/// 最近的若干条目，按记录顺序分配本地编号（从 1 开始，仅在本次运行内有效）
+#[derive(Debug)]
+struct Recent<T> {
+    capacity: usize,
+    next_id: u64,
+    items: VecDeque<(u64, T)>,
+}
+
+impl<T> Recent<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            next_id: 0,
+            items: VecDeque::new(),
+        }
+    }
+
+    /// 记录一个条目并返回其编号
+    fn push(&mut self, item: T) -> u64 {
+        self.next_id += 1;
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back((self.next_id, item));
+        self.next_id
+    }
+
+    fn get(&self, id: u64) -> Option<&T> {
+        self.items
+            .iter()
+            .find(|(n, _)| *n == id)
+            .map(|(_, item)| item)
+    }
+}
+
+/// 接收任务显示消息所需的共享状态
+struct Inbox {
+    own_name: ArcString,
+    handler: Option<MessageHandler>,
+    error_handler: Option<ErrorHandler>,
+    history: Option<History>,
+    highlight: HighlightRules,
+    emoji_on_display: bool,
+    markdown: bool,
+    theme: Arc<Theme>,
+    muted: Arc<Mutex<HashSet<String>>>,
+    received: Arc<Mutex<Recent<Message>>>,
+    links: Arc<Mutex<Recent<String>>>,
+    core: Arc<Mutex<ClientCore>>,
+    transfers: Arc<Transfers>,
+    #[cfg(feature = "crypto")]
+    known_keys: Arc<KnownKeys>,
+    #[cfg(feature = "crypto")]
+    e2e: Arc<E2e>,
+    #[cfg(feature = "crypto")]
+    identity: Option<Arc<Identity>>,
+}
+
+impl Inbox {
+    /// 保存并显示一条收到的消息
+    ///
+    /// # 返回值
+    /// 消息已向用户显示且带有服务器分配的编号时返回该编号，供发送已读回执
+    fn deliver(&self, message: Message) -> Option<u64> {
+        #[cfg(feature = "crypto")]
+        let (message, trust) = self.authenticate(message)?;
+        if let Some(history) = &self.history {
+            if message.from() != "Server" {
+                if let Err(e) = history.record(message.from(), &message) {
+                    errln!(
+                        "{}: {}",
+                        self.theme.error.paint(&tr!("client.history_save_failed")),
+                        e
+                    );
+                }
+            }
+        }
+        if let Some(handler) = &self.handler {
+            handler(message);
+            return None;
+        }
+        // 服务器回复不分配编号，也不能转发
+        let id = (message.from() != "Server")
+            .then(|| self.received.lock().unwrap().push(message.clone()));
+
+        let content = if self.emoji_on_display {
+            emoji::expand(message.content())
+        } else {
+            message.content().into()
+        };
+        let highlights = self.highlight.find(&self.own_name.get(), &content);
+        // 服务器按保存在资料中的通知偏好附上的建议优先，未附加时按本地的提及与关键词判断
+        let alert = match message.notify() {
+            Some(NotifyHint::Silent) => false,
+            Some(NotifyHint::Highlight) => true,
+            Some(NotifyHint::Normal) | None => !highlights.is_empty(),
+        };
+        let muted = self.muted.lock().unwrap().contains(message.from());
+        // 静音的会话只在需要提示且允许提示时显示
+        if muted && (!alert || !self.highlight.notifies_muted()) {
+            return None;
+        }
+        #[cfg(feature = "crypto")]
+        let verified = trust.is_some_and(|trust| self.show_trust(&message, &trust));
+        #[cfg(not(feature = "crypto"))]
+        let verified = false;
+        self.print_message(&message, id, &content, alert, muted, verified);
+        message.id()
+    }
+
+    /// 显示服务器发来的错误
+    fn error(&self, error: ErrorFrame) {
+        // 自己发起的文件传输被服务器拒绝时结束其传输任务，提示照常显示
+        self.transfers.refused(&error);
+        #[cfg(feature = "crypto")]
+        self.e2e.refused(&error);
+        if let Some(handler) = &self.error_handler {
+            handler(error);
+            return;
+        }
+        outln!("{}", self.theme.error.paint(&error.message));
+    }
+
+    /// 显示服务器的关闭通知
+    fn shutdown(&self, reason: ShutdownReason, reconnect_after: Option<Duration>) {
+        let reason = match reason {
+            ShutdownReason::Shutdown => tr!("client.shutdown_reason_shutdown"),
+            ShutdownReason::Upgrade => tr!("client.shutdown_reason_upgrade"),
+            ShutdownReason::Other => tr!("client.shutdown_reason_other"),
+        };
+        let text = match reconnect_after {
+            Some(after) => tr!(
+                "client.server_shutdown_reconnect",
+                reason = reason,
+                after = humanize_duration(after),
+            ),
+            None => tr!("client.server_shutdown", reason = reason),
+        };
+        outln!("{}", self.theme.system.paint(&text));
+    }
+
+    /// 向在线名单中的用户重新提议之前中断的文件发送（见 [`Transfers::resume`]）
+    async fn resume_transfers(&self, writer: &SharedWriter) {
+        let online: Vec<String> = self
+            .core
+            .lock()
+            .unwrap()
+            .roster()
+            .map(|user| user.name.clone())
+            .collect();
+        self.transfers.resume(writer, &online).await;
+    }
+
+    /// 在线名单有变化：更新输入时补全的用户名，并显示 `update` 中用户的上线、下线或状态变化
+    fn roster_changed(&self, update: Option<(RosterChange, RosterEntry)>) {
+        let own_name = self.own_name.get();
+        let names = self
+            .core
+            .lock()
+            .unwrap()
+            .roster()
+            .filter(|user| user.name != own_name)
+            .map(|user| user.name.clone())
+            .collect();
+        terminal::set_completions(names);
+        let Some((change, user)) = update else {
+            return;
+        };
+        if user.name == own_name || self.muted.lock().unwrap().contains(&user.name) {
+            return;
+        }
+        let text = match change {
+            RosterChange::Joined => tr!("client.roster_joined", user = user.name),
+            RosterChange::Left => tr!("client.roster_left", user = user.name),
+            RosterChange::Status => {
+                let status = match user.status {
+                    RosterStatus::Online => tr!("client.status_online"),
+                    RosterStatus::Away => tr!("client.status_away"),
+                    RosterStatus::Dnd => tr!("client.status_dnd"),
+                    RosterStatus::Other => tr!("client.status_other"),
+                };
+                tr!("client.roster_status", user = user.name, status = status)
+            }
+        };
+        outln!("{}", self.theme.system.paint(&text));
+    }
+
+    /// 默认消息处理：打印到终端（由渲染线程重画输入提示与已输入的内容）
+    ///
+    /// `id` 为消息的本地编号（可用于 `/forward`）；`content` 为待显示的消息内容；
+    /// `alert` 表示命中提及或关键词，需要响铃提示；`muted` 表示消息来自已静音的会话；
+    /// `verified` 表示签名有效且发送者的公钥经带外核对标记为已验证，发送者后显示 ✓
+    fn print_message(
+        &self,
+        message: &Message,
+        id: Option<u64>,
+        content: &str,
+        alert: bool,
+        muted: bool,
+        verified: bool,
+    ) {
+        // 按 Markdown 样式逐段渲染，段内命中的提及与关键词反色高亮
+        let spans = if self.markdown {
+            markdown::parse(content)
+        } else {
+            vec![Span {
+                text: content.to_string(),
+                style: Style::default(),
+            }]
+        };
+        let spans = self.mark_links(spans);
+        let own_name = self.own_name.get();
+        let mut rendered = String::new();
+        for span in &spans {
+            let mut pos = 0;
+            for range in self.highlight.find(&own_name, &span.text) {
+                let before = &span.text[pos..range.start];
+                rendered.push_str(&styled(&self.theme, before, span.style));
+                let matched = &span.text[range.clone()];
+                rendered.push_str(&self.theme.highlight.paint(matched).to_string());
+                pos = range.end;
+            }
+            rendered.push_str(&styled(&self.theme, &span.text[pos..], span.style));
+        }
+
+        // 打印接收到的消息（显示发送者和内容）；动作消息显示为 "* 发送者 动作"
+        let label = if muted {
+            tr!("client.muted_label")
+        } else {
+            String::new()
+        };
+        let id = id.map(|id| format!(" #{}", id)).unwrap_or_default();
+        let check = if verified { " ✓" } else { "" };
+        // 聊天室中的消息（接收方不是自己）在发送者后显示聊天室地址
+        let room = if message.to() != own_name && message.from() != "Server" {
+            format!(" [{}]", sanitize(message.to()))
+        } else {
+            String::new()
+        };
+        // 发送者在线且设置了表情头像时显示在名字之前
+        let avatar = self
+            .core
+            .lock()
+            .unwrap()
+            .avatar(message.from())
+            .map(|avatar| format!("{} ", sanitize(avatar)))
+            .unwrap_or_default();
+        match message.kind() {
+            MessageKind::Text | MessageKind::Encrypted => outln!(
+                "\n[{}]{} {}{}{}{}{}: {}",
+                self.theme.timestamp.paint(message.time_stamp()),
+                self.theme.dim.paint(&id),
+                avatar,
+                self.theme.sender.paint(message.from()),
+                self.theme.dim.paint(&room),
+                self.theme.success.paint(check),
+                self.theme.dim.paint(&label),
+                rendered
+            ),
+            MessageKind::Action => outln!(
+                "\n[{}]{}{} {} {}{}{}{} {}",
+                self.theme.timestamp.paint(message.time_stamp()),
+                self.theme.dim.paint(&id),
+                self.theme.dim.paint(&label),
+                self.theme.action.paint("*").bold(),
+                avatar,
+                self.theme.action.paint(message.from()).bold(),
+                self.theme.dim.paint(&room),
+                self.theme.success.paint(check),
+                rendered.italic()
+            ),
+        }
+        if alert {
+            terminal::screen().bell();
+        }
+    }
+
+    /// 把非代码片段中的链接拆分为单独的链接片段，并在其后附上 `/open` 使用的编号
+    fn mark_links(&self, spans: Vec<Span>) -> Vec<Span> {
+        let mut out = Vec::with_capacity(spans.len());
+        for span in spans {
+            let ranges = if span.style.code {
+                Vec::new()
+            } else {
+                link::find(&span.text)
+            };
+            if ranges.is_empty() {
+                out.push(span);
+                continue;
+            }
+            let mut pos = 0;
+            for range in ranges {
+                let url = &span.text[range.clone()];
+                let id = self.links.lock().unwrap().push(url.to_string());
+                out.push(Span {
+                    text: span.text[pos..range.start].to_string(),
+                    style: span.style,
+                });
+                out.push(Span {
+                    text: url.to_string(),
+                    style: Style {
+                        link: true,
+                        ..span.style
+                    },
+                });
+                out.push(Span {
+                    text: format!(" [{}]", id),
+                    style: Style {
+                        dim: true,
+                        ..Style::default()
+                    },
+                });
+                pos = range.end;
+            }
+            out.push(Span {
+                text: span.text[pos..].to_string(),
+                style: span.style,
+            });
+        }
+        out
+    }
+}
+
+/// 签名验证与端到端加密消息的解密（需启用 `crypto` 特性）
+#[cfg(feature = "crypto")]
+impl Inbox {
+    /// 验证收到的消息的签名并解密端到端加密的消息，返回明文与验证结果（服务器发来的消息不验证）；
+    /// 重放、过期或无法解密的消息返回 `None`，既不保存也不交给处理器
+    fn authenticate(&self, message: Message) -> Option<(Message, Option<Trust>)> {
+        let trust = (message.from() != "Server").then(|| self.known_keys.check(&message));
+        if let Some(rejected @ (Trust::Replayed | Trust::Stale)) = &trust {
+            self.show_trust(&message, rejected);
+            return None;
+        }
+        // 之后的保存、处理与显示都使用明文
+        if message.kind() != MessageKind::Encrypted {
+            return Some((message, trust));
+        }
+        let Some(plain) = self.e2e.open(&message) else {
+            errln!(
+                "{}",
+                self.theme
+                    .error
+                    .paint(&tr!("e2e.decrypt_failed", user = message.from()))
+            );
+            return None;
+        };
+        // 服务器看不到密文中的内容，由接收方清除其中的终端控制序列
+        let clean = sanitize(plain.content()).into_owned();
+        Some((plain.with_content(clean), trust))
+    }
+
+    /// 提示签名验证的结果（公钥的变化、无效或缺失的签名、被丢弃的重放消息），
+    /// 返回是否显示已验证标记：签名与记住的公钥一致，且该公钥经带外核对标记为已验证
+    fn show_trust(&self, message: &Message, trust: &Trust) -> bool {
+        let user = message.from();
+        let (text, warning) = match trust {
+            Trust::Verified => return self.known_keys.is_verified(user),
+            Trust::Unsigned => return false,
+            // 已验证的联系人更换公钥：响铃并以醒目的横幅警告，直到接受或重新核对
+            Trust::Changed(key) if self.known_keys.is_verified(user) => {
+                let text = tr!(
+                    "identity.verified_changed",
+                    user = user,
+                    fingerprint = fingerprint(key)
+                );
+                let rule = "!".repeat(60);
+                outln!(
+                    "\n{}\n{}\n{}",
+                    self.theme.error.paint(&rule).bold(),
+                    self.theme.error.paint(&text).bold(),
+                    self.theme.error.paint(&rule).bold()
+                );
+                terminal::screen().bell();
+                return false;
+            }
+            Trust::FirstSeen(key) => (
+                tr!(
+                    "identity.first_seen",
+                    user = user,
+                    fingerprint = fingerprint(key)
+                ),
+                false,
+            ),
+            Trust::Changed(key) => (
+                tr!(
+                    "identity.changed",
+                    user = user,
+                    fingerprint = fingerprint(key)
+                ),
+                true,
+            ),
+            Trust::Invalid => (tr!("identity.invalid", user = user), true),
+            Trust::Missing => (tr!("identity.missing", user = user), true),
+            Trust::Replayed => (tr!("identity.replayed", user = user), true),
+            Trust::Stale => (tr!("identity.stale", user = user), true),
+        };
+        if warning {
+            outln!("\n{}", self.theme.error.paint(&text));
+        } else {
+            outln!("\n{}", self.theme.system.paint(&text));
+        }
+        // 首次见到的公钥只是记住，尚未经带外核对
+        false
+    }
+}
+
+/// 按 Markdown 样式与配色为一段文本加上 ANSI 样式
+fn styled(theme: &Theme, text: &str, style: Style) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut out = if style.code {
+        theme.code.paint(text)
+    } else if style.link {
+        theme.link.paint(text)
+    } else if style.dim {
+        theme.dim.paint(text)
+    } else {
+        theme.content.paint(text)
+    };
+    if style.bold {
+        out = out.bold();
+    }
+    if style.italic {
+        out = out.italic();
+    }
+    if style.strikethrough {
+        out = out.strikethrough();
+    }
+    out.to_string()
+}
+
+/// 接收任务：处理来自服务器转发的消息，并记录最近一次收到帧的时间
+async fn receive_loop(
+    mut reader: FrameReader<BoxReader>,
+    writer: SharedWriter,
+    read_timeout: Option<Duration>,
+    inbox: Inbox,
+    last_frame: Arc<Mutex<Instant>>,
+    reconnect_at: watch::Sender<Option<tokio::time::Instant>>,
+    exit_on_close: bool,
+) {
+    loop {
+        let next = reader.read_frame::<Frame>();
+        let result = match read_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, next).await {
+                Ok(result) => result,
+                Err(_) => {
+                    errln!("{}", inbox.theme.error.paint(&tr!("client.read_timeout")));
+                    inbox.transfers.interrupt_all();
+                    return;
+                }
+            },
+            None => next.await,
+        };
+        *last_frame.lock().unwrap() = Instant::now();
+        match result {
+            Ok(None) => {
+                outln!("{}", inbox.theme.error.paint(&tr!("client.server_closed")));
+                inbox.transfers.interrupt_all();
+                // 服务器在关闭通知中建议了重连时，由主循环到时重新连接，不退出
+                if exit_on_close && reconnect_at.borrow().is_none() {
+                    terminal::restore();
+                    process::exit(1);
+                }
+                return;
+            }
+            Ok(Some(frame)) => {
+                // 协议核心更新续传编号并跳过重复的消息，其余事件在此分发
+                let event = inbox.core.lock().unwrap().receive(frame);
+                match event {
+                    Some(Event::Message(message)) => {
+                        if let Some(id) = inbox.deliver(message) {
+                            send_read(&writer, id).await;
+                        }
+                    }
+                    Some(Event::Error(error)) => inbox.error(error),
+                    Some(Event::Challenge { nonce, difficulty }) => {
+                        answer_challenge(&writer, &inbox.theme, nonce, difficulty).await;
+                    }
+                    Some(Event::Roster) => {
+                        inbox.roster_changed(None);
+                        inbox.resume_transfers(&writer).await;
+                    }
+                    Some(Event::Presence { change, user }) => {
+                        let joined = change == RosterChange::Joined;
+                        inbox.roster_changed(Some((change, user)));
+                        if joined {
+                            inbox.resume_transfers(&writer).await;
+                        }
+                    }
+                    Some(Event::Shutdown {
+                        reason,
+                        reconnect_after,
+                    }) => {
+                        inbox.shutdown(reason, reconnect_after);
+                        if let Some(after) = reconnect_after {
+                            let deadline = tokio::time::Instant::now() + after + reconnect_jitter();
+                            reconnect_at.send_replace(Some(deadline));
+                        }
+                    }
+                    Some(Event::Control(
+                        control @ (Control::FileOffer { .. }
+                        | Control::FileAnswer { .. }
+                        | Control::FileResume { .. }
+                        | Control::FileCandidates { .. }
+                        | Control::FileChunk { .. }
+                        | Control::FileDone { .. }
+                        | Control::FileCancel { .. }),
+                    )) => inbox.transfers.dispatch(control),
+                    #[cfg(feature = "crypto")]
+                    Some(Event::Control(control @ Control::KeyExchange { .. })) => {
+                        inbox.e2e.dispatch(&writer, control).await;
+                    }
+                    #[cfg(feature = "crypto")]
+                    Some(Event::Control(Control::IdentityChallenge { nonce })) => {
+                        answer_identity(&writer, &inbox, &nonce).await;
+                    }
+                    Some(Event::Control(_)) | None => {}
+                }
+            }
+            Err(crate::codec::CodecError::Decode(e)) => {
+                errln!(
+                    "{}: {:?}",
+                    inbox.theme.error.paint(&tr!("client.parse_failed")),
+                    e
+                );
+            }
+            Err(e) => {
+                errln!(
+                    "{}: {}",
+                    inbox.theme.error.paint(&tr!("client.read_failed")),
+                    e
+                );
+                inbox.transfers.interrupt_all();
+                return;
+            }
+        }
+    }
+}
+
+/// 按关闭通知重连前额外等待的随机时长，不超过 [`RECONNECT_JITTER`]
+fn reconnect_jitter() -> Duration {
+    let mut bytes = [0u8; 8];
+    // 取随机数失败时不加延迟
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return Duration::ZERO;
+    }
+    RECONNECT_JITTER.mul_f64(u64::from_le_bytes(bytes) as f64 / u64::MAX as f64)
+}
+
+/// 告知服务器已向用户显示编号为 `id` 的消息；发送失败时由接收任务随后发现连接中断，这里不提示
+async fn send_read(writer: &SharedWriter, id: u64) {
+    let _ = writer
+        .lock()
+        .await
+        .write_frame(&Frame::from(Control::Read { id }))
+        .await;
+}
+
+/// 应答服务器在注册时发出的工作量证明挑战：在阻塞线程中求解后回复 `Proof`
+///
+/// 难度超过 [`pow::MAX_DIFFICULTY`] 时不作应答，由服务器超时后拒绝注册。
+async fn answer_challenge(writer: &SharedWriter, theme: &Theme, nonce: String, difficulty: u8) {
+    if difficulty > pow::MAX_DIFFICULTY {
+        errln!(
+            "{}",
+            theme
+                .error
+                .paint(&tr!("client.pow_too_hard", difficulty = difficulty))
+        );
+        return;
+    }
+    outln!(
+        "{}",
+        theme
+            .system
+            .paint(&tr!("client.pow_solving", difficulty = difficulty))
+    );
+    let Ok(Some(proof)) =
+        tokio::task::spawn_blocking(move || client_core::solve_challenge(&nonce, difficulty)).await
+    else {
+        return;
+    };
+    let proof = Frame::from(proof);
+    if let Err(e) = writer.lock().await.write_frame(&proof).await {
+        errln!(
+            "{}",
+            theme.error.paint(&tr!("client.send_failed", error = e))
+        );
+    }
+}
+
+/// 以身份密钥为服务器的身份挑战签名并应答；没有身份密钥时不应答，由服务器超时后拒绝
+#[cfg(feature = "crypto")]
+async fn answer_identity(writer: &SharedWriter, inbox: &Inbox, nonce: &str) {
+    let Some(identity) = &inbox.identity else {
+        return;
+    };
+    let proof = Frame::from(Control::IdentityProof {
+        sig: identity.sign_login(&inbox.own_name.get(), nonce),
+    });
+    if let Err(e) = writer.lock().await.write_frame(&proof).await {
+        errln!(
+            "{}",
+            inbox
+                .theme
+                .error
+                .paint(&tr!("client.send_failed", error = e))
+        );
+    }
+}
+
+/// 心跳任务：每隔 `interval` 发送一次 Ping；连续 [`HEARTBEAT_MISSES`] 个间隔未收到任何帧时
+/// 视为连接已断开并结束接收任务，之后按断线流程退出（`exit_on_close`）或在下次发送时重连
+async fn heartbeat_loop(
+    writer: SharedWriter,
+    interval: Duration,
+    last_frame: Arc<Mutex<Instant>>,
+    recv_task: AbortHandle,
+    theme: Arc<Theme>,
+    exit_on_close: bool,
+) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    let mut seq = 0;
+    loop {
+        ticker.tick().await;
+        if recv_task.is_finished() {
+            return;
+        }
+        if last_frame.lock().unwrap().elapsed() >= interval * HEARTBEAT_MISSES {
+            outln!("{}", theme.error.paint(&tr!("client.heartbeat_timeout")));
+            if exit_on_close {
+                terminal::restore();
+                process::exit(1);
+            }
+            recv_task.abort();
+            return;
+        }
+        seq += 1;
+        let ping = Frame::from(Control::Ping { seq });
+        // 写入失败时由发送消息的主循环发现并处理；写入卡住时由下一次检查判定超时
+        let write = async { writer.lock().await.write_frame(&ping).await };
+        let _ = tokio::time::timeout(interval, write).await;
     }
 }
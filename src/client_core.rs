@@ -0,0 +1,263 @@
+/*!
+# 客户端协议核心
+
+与传输方式无关的客户端协议逻辑：注册握手、断线续传的消息编号、按编号去重、工作量证明，
+服务器推送的在线名单，以及构造带编号的待发送消息。[`ClientCore`] 不做任何 I/O，也不依赖 Tokio 或终端：
+调用方负责建立连接与收发帧（编码见 `codec` 模块），把收到的帧交给
+[`ClientCore::receive`]，再按返回的 [`Event`] 显示消息或交给相应的模块处理。
+
+终端客户端（`client` 模块，属于 `net` 特性）经 TCP 或 TLS 驱动它；浏览器中的客户端（`web` 模块，
+仅 wasm32 目标）经浏览器的 WebSocket 连接服务器的 `--ws` 监听地址（每条文本消息为一帧 JSON），
+复用同一套握手与状态处理。本模块不依赖 `net` 特性，关闭默认特性后可以编译到 `wasm32-unknown-unknown`。
+*/
+
+use crate::dedup::DedupWindow;
+use crate::i18n::Lang;
+use crate::pow;
+use crate::protocol::{
+    Control, ErrorFrame, Frame, Hello, RosterChange, RosterEntry, ShutdownReason,
+};
+use crate::{ArcString, Message, MessageKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 收到的帧经协议核心处理后需要调用方处理的事件
+#[derive(Debug)]
+pub enum Event {
+    /// 一条新消息（重复收到的消息已被跳过）
+    Message(Message),
+    /// 服务器的错误帧
+    Error(ErrorFrame),
+    /// 服务器要求完成工作量证明；求解可能耗时，由调用方通过 [`solve_challenge`] 在合适的线程中求解
+    Challenge { nonce: String, difficulty: u8 },
+    /// 服务器发来完整的在线名单，已替换 [`ClientCore::roster`]
+    Roster,
+    /// 在线名单中一个用户的变化；不改变名单的重复更新已被跳过，
+    /// 已在名单中的用户再次上线时按状态变化处理
+    Presence {
+        change: RosterChange,
+        user: RosterEntry,
+    },
+    /// 服务器即将关闭连接；`reconnect_after` 为服务器建议在连接关闭后等待多久再重连，
+    /// `None` 表示不建议自动重连
+    Shutdown {
+        reason: ShutdownReason,
+        reconnect_after: Option<Duration>,
+    },
+    /// 其余控制帧（文件传输、密钥交换等），交给相应的模块处理
+    Control(Control),
+}
+
+/// 一个客户端的协议状态，断线重连后继续使用同一个实例
+pub struct ClientCore {
+    name: ArcString,
+    features: Vec<String>,
+    lang: Option<Lang>,
+    /// 要进入的虚拟主机，`None` 表示服务器的默认空间
+    host: Option<String>,
+    /// 注册时出示的邀请码
+    invite: Option<String>,
+    /// 机器人账号或管理员的令牌
+    token: Option<String>,
+    /// 身份公钥（十六进制），服务器以身份挑战要求证明持有对应的私钥
+    identity: Option<String>,
+    /// 最后收到的服务器消息编号，重连时请求补发此后的消息
+    last_id: Option<u64>,
+    /// 最近收到的服务器消息编号，跳过重复收到的消息
+    seen: DedupWindow<u64>,
+    /// 服务器推送的在线名单（声明了 `roster` 特性时），键为用户名
+    roster: BTreeMap<String, RosterEntry>,
+}
+
+impl fmt::Debug for ClientCore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCore")
+            .field("name", &self.name)
+            .field("features", &self.features)
+            .field("lang", &self.lang)
+            .field("host", &self.host)
+            .field("invite", &self.invite.as_ref().map(|_| "***"))
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("identity", &self.identity)
+            .field("last_id", &self.last_id)
+            .field("roster", &self.roster.len())
+            .finish()
+    }
+}
+
+impl ClientCore {
+    /// 以用户名与握手时声明的特性创建协议状态
+    pub fn new(name: ArcString, features: Vec<String>) -> Self {
+        Self {
+            name,
+            features,
+            lang: None,
+            host: None,
+            invite: None,
+            token: None,
+            identity: None,
+            last_id: None,
+            seen: DedupWindow::default(),
+            roster: BTreeMap::new(),
+        }
+    }
+
+    /// 设置握手时上报的语言
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = Some(lang);
+        self
+    }
+
+    /// 设置要进入的虚拟主机
+    pub fn with_host(mut self, host: Option<String>) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// 设置注册时出示的邀请码
+    pub fn with_invite(mut self, invite: Option<String>) -> Self {
+        self.invite = invite;
+        self
+    }
+
+    /// 设置机器人账号或管理员登录时出示的令牌
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// 设置握手时出示的身份公钥（十六进制）；收到的 `IdentityChallenge` 以 [`Event::Control`] 交给调用方签名应答
+    pub fn with_identity(mut self, identity: Option<String>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// 用户名
+    pub fn name(&self) -> &ArcString {
+        &self.name
+    }
+
+    /// 最后收到的服务器消息编号
+    pub fn last_id(&self) -> Option<u64> {
+        self.last_id
+    }
+
+    /// 服务器推送的在线名单（按用户名排序）；未声明 `roster` 特性或尚未收到时为空
+    pub fn roster(&self) -> impl Iterator<Item = &RosterEntry> {
+        self.roster.values()
+    }
+
+    /// 在线名单中 `name` 的表情头像；用户不在名单中或未设置表情头像时为 `None`
+    pub fn avatar(&self, name: &str) -> Option<&str> {
+        self.roster.get(name)?.avatar.as_deref()
+    }
+
+    /// 建立连接后发送的注册握手帧；重连时附带最后收到的消息编号，由服务器补发此后的消息
+    pub fn hello(&self) -> Hello {
+        let hello = Hello::new(self.name.get(), self.features.clone())
+            .with_resume(self.last_id)
+            .with_host(self.host.clone())
+            .with_invite(self.invite.clone())
+            .with_token(self.token.clone())
+            .with_identity(self.identity.clone());
+        match self.lang {
+            Some(lang) => hello.with_lang(lang),
+            None => hello,
+        }
+    }
+
+    /// 处理收到的一帧
+    ///
+    /// # 返回值
+    /// 需要调用方处理的事件；重复的消息与只更新协议状态的帧（如 `Seq`）返回 `None`
+    pub fn receive(&mut self, frame: Frame) -> Option<Event> {
+        match frame {
+            Frame::Message(message) => {
+                if let Some(id) = message.id() {
+                    if !self.seen.insert(id) {
+                        return None;
+                    }
+                    self.advance(id);
+                }
+                Some(Event::Message(message))
+            }
+            Frame::Control(Control::Seq { last_id }) => {
+                self.advance(last_id);
+                None
+            }
+            Frame::Control(Control::Error(error)) => Some(Event::Error(error)),
+            Frame::Control(Control::Challenge { nonce, difficulty }) => {
+                Some(Event::Challenge { nonce, difficulty })
+            }
+            Frame::Control(Control::Roster { users }) => {
+                self.roster = users
+                    .into_iter()
+                    .map(|user| (user.name.clone(), user))
+                    .collect();
+                Some(Event::Roster)
+            }
+            Frame::Control(Control::RosterUpdate { change, user }) => {
+                self.update_roster(change, user)
+            }
+            Frame::Control(Control::ServerShutdown {
+                reason,
+                reconnect_after,
+            }) => Some(Event::Shutdown {
+                reason,
+                reconnect_after: reconnect_after.map(Duration::from_secs),
+            }),
+            Frame::Control(control) => Some(Event::Control(control)),
+        }
+    }
+
+    /// 构造一条发给 `to` 的消息，附带随机生成的编号供服务器去重；失败重发时应使用同一条消息
+    pub fn message(&self, to: &str, content: &str, kind: MessageKind) -> Message {
+        let mut bytes = [0u8; 8];
+        // 取随机数失败时退回到当前时间，去重只需要编号在短时间内不重复
+        let id = match getrandom::getrandom(&mut bytes) {
+            Ok(()) => u64::from_le_bytes(bytes),
+            Err(_) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+        };
+        Message::new(self.name.clone(), to.to_string(), content.to_string())
+            .with_kind(kind)
+            .with_id(id)
+    }
+
+    /// 应用一条在线名单更新，名单有变化时返回相应的事件
+    fn update_roster(&mut self, change: RosterChange, user: RosterEntry) -> Option<Event> {
+        let previous = match change {
+            RosterChange::Left => self.roster.remove(&user.name),
+            RosterChange::Joined | RosterChange::Status => {
+                self.roster.insert(user.name.clone(), user.clone())
+            }
+        };
+        let change = match (change, previous) {
+            (RosterChange::Left, Some(_)) => RosterChange::Left,
+            (RosterChange::Left, None) => return None,
+            (_, None) => RosterChange::Joined,
+            (_, Some(previous)) if previous == user => return None,
+            // 只有头像等资料有变化：名单已更新，不作为状态变化提示
+            (_, Some(previous)) if previous.status == user.status => return None,
+            (_, Some(_)) => RosterChange::Status,
+        };
+        Some(Event::Presence { change, user })
+    }
+
+    /// 记录已收到的最新消息编号
+    fn advance(&mut self, id: u64) {
+        self.last_id = Some(self.last_id.map_or(id, |last| last.max(id)));
+    }
+}
+
+/// 求解服务器的工作量证明挑战，得到应答的 `Proof` 帧
+///
+/// # 返回值
+/// 难度超过 [`pow::MAX_DIFFICULTY`] 时返回 `None`，客户端不应尝试求解
+pub fn solve_challenge(nonce: &str, difficulty: u8) -> Option<Control> {
+    (difficulty <= pow::MAX_DIFFICULTY).then(|| Control::Proof {
+        solution: pow::solve(nonce, difficulty),
+    })
+}
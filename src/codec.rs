@@ -0,0 +1,319 @@
+/*!
+# 编解码模块
+
+本模块定义了客户端与服务器之间的分帧格式，解决"一次读取即一条消息"的假设在
+TCP 粘包/拆包时失效的问题。目前支持两种编码：
+
+- **JsonLines**：每帧一行 JSON，以 `\n` 结尾（`serde_json` 会转义内容中的换行符）
+- **LengthPrefixed**：4 字节大端长度前缀 + JSON 负载
+
+服务器通过连接的首字节自动识别客户端使用的编码（见 [`Codec::detect`]），
+因此客户端可以自由选择偏好的编码而无需额外协商。
+*/
+
+use crate::tr;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// 单帧允许的最大字节数，防止恶意对端发送超大帧耗尽内存
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// 帧编码格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// 每帧一行 JSON
+    #[default]
+    JsonLines,
+    /// 4 字节大端长度前缀 + JSON 负载
+    LengthPrefixed,
+}
+
+impl Codec {
+    /// 根据连接的首字节判断对端使用的编码
+    ///
+    /// 长度前缀帧的首字节是长度的最高位字节，在 [`MAX_FRAME_LEN`] 限制下恒为 `0`，
+    /// 而 JSON 帧不可能以 `0` 开头。
+    pub fn detect(first_byte: u8) -> Codec {
+        if first_byte == 0 {
+            Codec::LengthPrefixed
+        } else {
+            Codec::JsonLines
+        }
+    }
+
+    /// 根据字符串返回对应的编码
+    ///
+    /// # 参数
+    /// - `name`: 编码名称（"json" 或 "length-prefixed"）
+    ///
+    /// # 返回值
+    /// 若匹配成功，返回对应的 `Codec`，否则返回 `None`
+    pub fn from_string(name: &str) -> Option<Codec> {
+        match name.to_lowercase().as_str() {
+            "json" | "jsonlines" | "json-lines" => Some(Codec::JsonLines),
+            "length-prefixed" | "lp" => Some(Codec::LengthPrefixed),
+            _ => None,
+        }
+    }
+
+    /// 将一个值编码为完整的帧（包含分隔符或长度前缀）
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut payload = serde_json::to_vec(value).map_err(CodecError::Encode)?;
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(CodecError::FrameTooLarge(payload.len()));
+        }
+        Ok(match self {
+            Codec::JsonLines => {
+                payload.push(b'\n');
+                payload
+            }
+            Codec::LengthPrefixed => self.frame(&payload),
+        })
+    }
+
+    /// 为已编码的 JSON 负载加上分隔符或长度前缀
+    pub fn frame(&self, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(payload.len() + 4);
+        match self {
+            Codec::JsonLines => {
+                frame.extend_from_slice(payload);
+                frame.push(b'\n');
+            }
+            Codec::LengthPrefixed => {
+                frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+                frame.extend_from_slice(payload);
+            }
+        }
+        frame
+    }
+}
+
+/// 将一个值编码为不含分帧的 JSON 负载
+///
+/// 负载与分帧格式无关，同一份负载可以经 [`FrameWriter::write_payload`] 写给使用不同编码的多个连接
+pub fn encode_payload<T: Serialize>(value: &T) -> Result<Bytes, CodecError> {
+    let payload = serde_json::to_vec(value).map_err(CodecError::Encode)?;
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(CodecError::FrameTooLarge(payload.len()));
+    }
+    Ok(Bytes::from(payload))
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Codec::JsonLines => write!(f, "json"),
+            Codec::LengthPrefixed => write!(f, "length-prefixed"),
+        }
+    }
+}
+
+/// 编解码过程中可能出现的错误
+#[derive(Debug)]
+pub enum CodecError {
+    /// 底层读写失败
+    Io(io::Error),
+    /// 序列化失败
+    Encode(serde_json::Error),
+    /// 帧内容无法解析；该帧已被完整消费，流仍保持同步，可以继续读取
+    Decode(serde_json::Error),
+    /// 帧长度超过 [`MAX_FRAME_LEN`]
+    FrameTooLarge(usize),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => f.write_str(&tr!("codec.io", error = e)),
+            CodecError::Encode(e) => f.write_str(&tr!("codec.encode", error = e)),
+            CodecError::Decode(e) => f.write_str(&tr!("codec.decode", error = e)),
+            CodecError::FrameTooLarge(len) => {
+                f.write_str(&tr!("codec.too_large", len = len, max = MAX_FRAME_LEN))
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+/// 从异步读取端按帧读取数据
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    inner: BufReader<R>,
+    codec: Codec,
+    bytes_read: u64,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// 使用指定编码创建帧读取器
+    pub fn new(reader: R, codec: Codec) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+            codec,
+            bytes_read: 0,
+        }
+    }
+
+    /// 读取首字节（不消费）以自动识别对端编码，并创建帧读取器
+    ///
+    /// # 返回值
+    /// 若连接在发送任何数据前关闭，返回 `Ok(None)`
+    pub async fn detect(reader: R) -> io::Result<Option<Self>> {
+        let mut inner = BufReader::new(reader);
+        let codec = match inner.fill_buf().await?.first() {
+            Some(&byte) => Codec::detect(byte),
+            None => return Ok(None),
+        };
+        Ok(Some(Self {
+            inner,
+            codec,
+            bytes_read: 0,
+        }))
+    }
+
+    /// 当前使用的编码
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// 累计读取的字节数（包括分隔符与长度前缀）
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// 读取并解析下一帧
+    ///
+    /// # 返回值
+    /// - `Ok(Some(value))`: 成功读取一帧
+    /// - `Ok(None)`: 对端正常关闭连接
+    /// - `Err(CodecError::Decode)`: 帧内容无法解析，可以继续读取下一帧
+    pub async fn read_frame<T: DeserializeOwned>(&mut self) -> Result<Option<T>, CodecError> {
+        let payload = match self.codec {
+            Codec::JsonLines => {
+                let mut line = Vec::new();
+                let limit = (MAX_FRAME_LEN + 1) as u64;
+                let len = (&mut self.inner)
+                    .take(limit)
+                    .read_until(b'\n', &mut line)
+                    .await?;
+                if len == 0 {
+                    return Ok(None);
+                }
+                self.bytes_read += len as u64;
+                if line.last() != Some(&b'\n') {
+                    if len as u64 == limit {
+                        return Err(CodecError::FrameTooLarge(len));
+                    }
+                    // 对端在一行未结束时关闭了连接
+                    return Ok(None);
+                }
+                line.pop();
+                line
+            }
+            Codec::LengthPrefixed => {
+                let mut len_buf = [0u8; 4];
+                match self.inner.read_exact(&mut len_buf).await {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                }
+                let len = u32::from_be_bytes(len_buf) as usize;
+                if len > MAX_FRAME_LEN {
+                    return Err(CodecError::FrameTooLarge(len));
+                }
+                let mut payload = vec![0u8; len];
+                self.inner.read_exact(&mut payload).await?;
+                self.bytes_read += 4 + len as u64;
+                payload
+            }
+        };
+        serde_json::from_slice(&payload)
+            .map(Some)
+            .map_err(CodecError::Decode)
+    }
+}
+
+/// 向异步写入端按帧写入数据
+#[derive(Debug)]
+pub struct FrameWriter<W> {
+    inner: W,
+    codec: Codec,
+    bytes_written: u64,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    /// 使用指定编码创建帧写入器
+    pub fn new(writer: W, codec: Codec) -> Self {
+        Self {
+            inner: writer,
+            codec,
+            bytes_written: 0,
+        }
+    }
+
+    /// 当前使用的编码
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// 累计写入的字节数（包括分隔符与长度前缀）
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// 编码并写入一帧
+    pub async fn write_frame<T: Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        let frame = self.codec.encode(value)?;
+        self.inner.write_all(&frame).await?;
+        self.inner.flush().await?;
+        self.bytes_written += frame.len() as u64;
+        Ok(())
+    }
+
+    /// 写入一段已编码的 JSON 负载（见 [`encode_payload`]），按本连接的编码加上分隔符或长度前缀
+    pub async fn write_payload(&mut self, payload: &[u8]) -> Result<(), CodecError> {
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(CodecError::FrameTooLarge(payload.len()));
+        }
+        match self.codec {
+            Codec::JsonLines => {
+                self.inner.write_all(payload).await?;
+                self.inner.write_all(b"\n").await?;
+            }
+            Codec::LengthPrefixed => {
+                self.inner
+                    .write_all(&(payload.len() as u32).to_be_bytes())
+                    .await?;
+                self.inner.write_all(payload).await?;
+            }
+        }
+        self.inner.flush().await?;
+        self.bytes_written +=
+            payload.len() as u64 + if self.codec == Codec::JsonLines { 1 } else { 4 };
+        Ok(())
+    }
+
+    /// 原样写入一段字节，故障注入以此写出被截断的帧
+    #[cfg(feature = "chaos")]
+    pub(crate) async fn write_raw(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes).await?;
+        self.inner.flush().await?;
+        self.bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// 关闭写入端
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        self.inner.shutdown().await
+    }
+}
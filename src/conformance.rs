@@ -0,0 +1,676 @@
+/*!
+# 协议一致性模块
+
+一组固定的线上格式样例与帧交互用例，本 crate 的客户端/服务器与第三方实现都可以据此
+检查自己是否遵守协议，避免无意中改动线上格式。分为三部分：
+
+- **帧样例**（[`FIXTURES`]）：每条给出一帧的标准 JSON 文本。[`check_fixtures`]
+  把样例解析为本 crate 的类型后重新编码，要求与样例逐字节一致，并核对两种分帧格式
+  （JSON Lines 与长度前缀，见 [`crate::codec`]）下的完整字节（[`Fixture::framed`]）。
+  第三方实现可以直接用这些样例核对自己的编码与解析。
+- **属性检查**（[`check_roundtrips`]）：用 proptest 随机生成消息、握手帧与控制帧
+  （用户名与内容包括任意 Unicode、表情与控制字符），在每种分帧格式下编码成帧再解码，
+  要求恰好得到一帧且与原值相同，用于发现转义与截断问题；失败时给出收缩后的最小反例。
+  同样的属性也作为单元测试，随 `cargo test --features conformance` 运行。
+- **交互用例**（[`EXCHANGES`]）：向运行中的服务器发送固定的帧序列并检查回复，
+  [`run_exchanges`] 可以对任何实现了该协议的服务器运行。用例中的 `{user}`
+  在运行时替换为不会与真实用户冲突的用户名。
+
+命令行用法：
+
+```text
+chat conformance                       # 只做本地检查（帧样例与属性检查）
+chat conformance 127.0.0.1:7891        # 另外对服务器运行交互用例
+chat conformance 127.0.0.1:7891 --codec=length-prefixed
+```
+*/
+
+use crate::codec::{Codec, CodecError, FrameReader, FrameWriter};
+use crate::protocol::{Control, ErrorCode, ErrorFrame, Frame, Hello, Registration};
+use crate::{tr, ArcString, Message, MessageKind};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestRunner};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+
+/// 交互用例中等待每个期望帧的最长时间
+pub const EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 属性检查中每项随机生成的用例数
+pub const PROPERTY_CASES: u32 = 256;
+
+/// 样例帧的类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixtureKind {
+    /// 连接建立后客户端发送的第一帧（[`Registration`]）
+    Registration,
+    /// 注册完成后双向传输的帧（[`Frame`]）
+    Frame,
+}
+
+/// 一条帧样例
+#[derive(Clone, Copy, Debug)]
+pub struct Fixture {
+    /// 样例名称
+    pub name: &'static str,
+    pub kind: FixtureKind,
+    /// 帧的标准 JSON 文本（不含分隔符）
+    pub json: &'static str,
+}
+
+impl Fixture {
+    /// 样例在指定分帧格式下的完整字节
+    pub fn framed(&self, codec: Codec) -> Vec<u8> {
+        let payload = self.json.as_bytes();
+        match codec {
+            Codec::JsonLines => [payload, b"\n"].concat(),
+            Codec::LengthPrefixed => [&(payload.len() as u32).to_be_bytes()[..], payload].concat(),
+        }
+    }
+}
+
+/// 帧样例
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "hello",
+        kind: FixtureKind::Registration,
+        json: r#"{"name":"alice","version":1,"client":"example/1.0","features":["resume","errors"]}"#,
+    },
+    Fixture {
+        name: "hello_resume_lang",
+        kind: FixtureKind::Registration,
+        json: r#"{"name":"alice","version":1,"client":"example/1.0","features":["resume"],"resume_from":42,"lang":"en"}"#,
+    },
+    Fixture {
+        name: "hello_vhost",
+        kind: FixtureKind::Registration,
+        json: r#"{"name":"alice","version":1,"client":"example/1.0","features":[],"host":"books"}"#,
+    },
+    Fixture {
+        name: "hello_invite",
+        kind: FixtureKind::Registration,
+        json: r#"{"name":"bob","version":1,"client":"example/1.0","features":["errors"],"invite":"7KQ4-MX2P"}"#,
+    },
+    Fixture {
+        name: "hello_bot_token",
+        kind: FixtureKind::Registration,
+        json: r#"{"name":"weather","version":1,"client":"example/1.0","features":["errors"],"token":"bot_0123456789abcdef"}"#,
+    },
+    Fixture {
+        name: "legacy_name",
+        kind: FixtureKind::Registration,
+        json: r#""alice""#,
+    },
+    Fixture {
+        name: "message",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"bob","time_stamp":"12:00:00","content":"hello"}"#,
+    },
+    Fixture {
+        name: "message_with_id",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"bob","time_stamp":"12:00:00","id":7,"content":"hello\nworld"}"#,
+    },
+    Fixture {
+        name: "signed_message",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"bob","time_stamp":"12:00:00","id":7,"content":"hello","signature":{"key":"30461130a4866cc7bc16002dbbfdcbd426ef10da6fdd064e66d9be290de71e03","nonce":"f890035b7c41a7eab7c1221d6ba733b8","signed_at":1792157247,"sig":"2e5340c7a0b8c2a370e7059d2663667babb2900b903bb69c2b11c9283490f9202123b587692b79c1fd7a5e0f70a88341ef5f42b47db6770ba21529440799c70e"}}"#,
+    },
+    Fixture {
+        name: "action",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"bob","time_stamp":"12:00:00","kind":"action","content":"waves"}"#,
+    },
+    Fixture {
+        name: "encrypted_message",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"bob","time_stamp":"12:00:00","kind":"encrypted","content":"3f2a9c1e.42552d8e48ba350373cd8e3d.6OD/YzrB0IwBou0IxCQ4mW4kI7fYowltx/lSObSUbPLRZyogBYe7xj1X3TXU1fYNYA=="}"#,
+    },
+    Fixture {
+        name: "command",
+        kind: FixtureKind::Frame,
+        json: r#"{"from":"alice","to":"/list","time_stamp":"12:00:00","content":""}"#,
+    },
+    Fixture {
+        name: "ping",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"ping","seq":1}"#,
+    },
+    Fixture {
+        name: "pong",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"pong","seq":1}"#,
+    },
+    Fixture {
+        name: "seq",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"seq","last_id":42}"#,
+    },
+    Fixture {
+        name: "challenge",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"challenge","nonce":"9f86d081884c7d65","difficulty":20}"#,
+    },
+    Fixture {
+        name: "proof",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"proof","solution":1048576}"#,
+    },
+    Fixture {
+        name: "peer_challenge",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"peer_challenge","nonce":"9f86d081884c7d65"}"#,
+    },
+    Fixture {
+        name: "peer_auth",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"peer_auth","mac":"5e884898da280471","nonce":"a665a45920422f9d"}"#,
+    },
+    Fixture {
+        name: "peer_welcome",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"peer_welcome","mac":"b3a8e0e1f9ab1bfe"}"#,
+    },
+    Fixture {
+        name: "relay",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"relay","message":{"from":"alice@a.example","to":"bob@b.example","time_stamp":"12:00:00","content":"hello"},"via":["a.example"]}"#,
+    },
+    Fixture {
+        name: "presence",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"presence","users":["bob","carol"]}"#,
+    },
+    Fixture {
+        name: "file_offer",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_offer","id":4242,"peer":"bob","name":"report.pdf","size":1048576,"sha256":"9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"}"#,
+    },
+    Fixture {
+        name: "file_answer",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_answer","id":4242,"peer":"alice","accept":true}"#,
+    },
+    Fixture {
+        name: "file_candidates",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_candidates","id":4242,"peer":"alice","addrs":["203.0.113.7:50123","192.168.1.20:50123"]}"#,
+    },
+    Fixture {
+        name: "file_chunk",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_chunk","id":4242,"peer":"bob","offset":0,"data":"aGVsbG8="}"#,
+    },
+    Fixture {
+        name: "file_done",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_done","id":4242,"peer":"alice"}"#,
+    },
+    Fixture {
+        name: "file_cancel",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"file_cancel","id":4242,"peer":"bob","reason":"bob went offline"}"#,
+    },
+    Fixture {
+        name: "key_exchange",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"key_exchange","peer":"bob","key":"623a76eb08edaf4457923944efabc21af99b3a47cd1bf541c601e21b87e76d1d","reply":false,"signer":"1a9438fb56b3544f6f360ee84322e0ee7cfb976c111636bf981b6ccaec44c1e1","sig":"2de9f828f15863119f7203851cfd38355a4a3a2f3e6fd8b54e853ceac7ba6bec0df594b00c66f893bf2c582fb6150477f4777cf7914949056ca9d9f834a1ee0a"}"#,
+    },
+    Fixture {
+        name: "error",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"error","code":"UNKNOWN_USER","target":"carol","id":7,"message":"carol is not a known user"}"#,
+    },
+    Fixture {
+        name: "goodbye",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"goodbye"}"#,
+    },
+    Fixture {
+        name: "goodbye_message",
+        kind: FixtureKind::Frame,
+        json: r#"{"type":"goodbye","message":"see you"}"#,
+    },
+];
+
+/// 交互用例中的一步
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    /// 发送一帧（JSON 文本）
+    Send(&'static str),
+    /// 等待一帧包含给定 JSON 对象中全部字段且取值相同的帧，期间收到的其他帧被跳过
+    Expect(&'static str),
+    /// 等待服务器关闭连接，期间收到的帧被跳过
+    ExpectClose,
+}
+
+/// 一个交互用例：在一条新连接上依次执行的步骤
+#[derive(Clone, Copy, Debug)]
+pub struct Exchange {
+    /// 用例名称
+    pub name: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// 交互用例
+pub const EXCHANGES: &[Exchange] = &[
+    Exchange {
+        name: "legacy_registration",
+        steps: &[
+            Step::Send(r#""{user}""#),
+            Step::Send(r#"{"from":"{user}","to":"/list","time_stamp":"00:00:00","content":""}"#),
+            Step::Expect(r#"{"from":"Server","to":"{user}"}"#),
+        ],
+    },
+    Exchange {
+        name: "hello_and_command",
+        steps: &[
+            Step::Send(r#"{"name":"{user}","version":1,"client":"conformance","features":[]}"#),
+            Step::Send(r#"{"from":"{user}","to":"/list","time_stamp":"00:00:00","content":""}"#),
+            Step::Expect(r#"{"from":"Server","to":"{user}"}"#),
+        ],
+    },
+    Exchange {
+        name: "ping_pong",
+        steps: &[
+            Step::Send(r#"{"name":"{user}","version":1,"client":"conformance","features":[]}"#),
+            Step::Send(r#"{"type":"ping","seq":7}"#),
+            Step::Expect(r#"{"type":"pong","seq":7}"#),
+        ],
+    },
+    Exchange {
+        name: "resume_seq",
+        steps: &[
+            Step::Send(
+                r#"{"name":"{user}","version":1,"client":"conformance","features":["resume"]}"#,
+            ),
+            Step::Expect(r#"{"type":"seq"}"#),
+        ],
+    },
+    Exchange {
+        name: "unknown_user_error",
+        steps: &[
+            Step::Send(
+                r#"{"name":"{user}","version":1,"client":"conformance","features":["errors"]}"#,
+            ),
+            Step::Send(
+                r#"{"from":"{user}","to":"{user}-nobody","time_stamp":"00:00:00","id":1,"content":"hi"}"#,
+            ),
+            Step::Expect(r#"{"type":"error","code":"UNKNOWN_USER","target":"{user}-nobody"}"#),
+        ],
+    },
+    Exchange {
+        name: "unknown_vhost_error",
+        steps: &[
+            Step::Send(
+                r#"{"name":"{user}","version":1,"client":"conformance","features":["errors"],"host":"{user}-nowhere"}"#,
+            ),
+            Step::Expect(r#"{"type":"error","code":"UNKNOWN_HOST","target":"{user}-nowhere"}"#),
+            Step::ExpectClose,
+        ],
+    },
+    Exchange {
+        name: "goodbye",
+        steps: &[
+            Step::Send(r#"{"name":"{user}","version":1,"client":"conformance","features":[]}"#),
+            Step::Send(r#"{"type":"goodbye","message":"bye"}"#),
+            Step::ExpectClose,
+        ],
+    },
+];
+
+/// 一个样例或用例的检查结果
+#[derive(Debug)]
+pub struct Outcome {
+    pub name: &'static str,
+    /// 失败时为原因
+    pub result: Result<(), String>,
+}
+
+/// 检查所有帧样例：解析后重新编码须与样例逐字节一致，两种分帧格式下的字节也须一致
+pub fn check_fixtures() -> Vec<Outcome> {
+    FIXTURES
+        .iter()
+        .map(|fixture| Outcome {
+            name: fixture.name,
+            result: match fixture.kind {
+                FixtureKind::Registration => round_trip::<Registration>(fixture),
+                FixtureKind::Frame => round_trip::<Frame>(fixture),
+            },
+        })
+        .collect()
+}
+
+fn round_trip<T: DeserializeOwned + Serialize>(fixture: &Fixture) -> Result<(), String> {
+    let value: T = serde_json::from_str(fixture.json)
+        .map_err(|e| tr!("conformance.decode_failed", error = e))?;
+    for codec in [Codec::JsonLines, Codec::LengthPrefixed] {
+        let encoded = codec.encode(&value).map_err(|e| e.to_string())?;
+        if encoded != fixture.framed(codec) {
+            let actual = String::from_utf8_lossy(&encoded);
+            return Err(tr!(
+                "conformance.encoding_differs",
+                codec = codec,
+                actual = actual.trim_end(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 对随机生成的消息、握手帧与控制帧做编解码往返检查
+pub fn check_roundtrips() -> Vec<Outcome> {
+    vec![
+        property("roundtrip_message", message().prop_map(Frame::from)),
+        property("roundtrip_hello", hello().prop_map(Registration::Hello)),
+        property("roundtrip_control", control().prop_map(Frame::Control)),
+    ]
+}
+
+/// 随机的消息
+fn message() -> impl Strategy<Value = Message> {
+    (text(), text(), text(), any::<bool>(), any::<Option<u64>>()).prop_map(
+        |(from, to, content, action, id)| {
+            let kind = if action {
+                MessageKind::Action
+            } else {
+                MessageKind::Text
+            };
+            let msg = Message::new(ArcString::new(from), to, content).with_kind(kind);
+            match id {
+                Some(id) => msg.with_id(id),
+                None => msg,
+            }
+        },
+    )
+}
+
+/// 随机的握手帧
+fn hello() -> impl Strategy<Value = Hello> {
+    (
+        text(),
+        prop::collection::vec(text(), 0..4),
+        any::<Option<u64>>(),
+        prop::option::of(text()),
+    )
+        .prop_map(|(name, features, last_id, host)| {
+            Hello::new(name, features)
+                .with_resume(last_id)
+                .with_host(host)
+        })
+}
+
+/// 随机的控制帧
+fn control() -> impl Strategy<Value = Control> {
+    prop_oneof![
+        any::<u64>().prop_map(|seq| Control::Ping { seq }),
+        any::<u64>().prop_map(|last_id| Control::Seq { last_id }),
+        (text(), any::<u8>())
+            .prop_map(|(nonce, difficulty)| Control::Challenge { nonce, difficulty }),
+        any::<u64>().prop_map(|solution| Control::Proof { solution }),
+        text().prop_map(|nonce| Control::IdentityChallenge { nonce }),
+        text().prop_map(|sig| Control::IdentityProof { sig }),
+        prop::option::of(text()).prop_map(|message| Control::Goodbye { message }),
+        (text(), text()).prop_map(|(mac, nonce)| Control::PeerAuth { mac, nonce }),
+        (text(), text(), prop::collection::vec(text(), 0..4)).prop_map(|(from, to, via)| {
+            Control::Relay {
+                message: Message::new(ArcString::new(from), to, String::new()),
+                via,
+            }
+        }),
+        prop::collection::vec(text(), 0..4).prop_map(|users| Control::Presence { users }),
+        (
+            any::<u64>(),
+            text(),
+            text(),
+            any::<u64>(),
+            text(),
+            any::<bool>()
+        )
+            .prop_map(|(id, peer, name, size, sha256, resumable)| {
+                Control::FileOffer {
+                    id,
+                    peer,
+                    name,
+                    size,
+                    sha256,
+                    resumable,
+                }
+            }),
+        (any::<u64>(), text(), any::<u64>()).prop_map(|(id, peer, offset)| Control::FileResume {
+            id,
+            peer,
+            offset
+        }),
+        (any::<u64>(), text(), any::<u64>(), text()).prop_map(|(id, peer, offset, data)| {
+            Control::FileChunk {
+                id,
+                peer,
+                offset,
+                data,
+            }
+        }),
+        (text(), text(), any::<bool>(), text(), text()).prop_map(
+            |(peer, key, reply, signer, sig)| Control::KeyExchange {
+                peer,
+                key,
+                reply,
+                signer,
+                sig,
+            }
+        ),
+        (text(), any::<Option<u64>>()).prop_map(|(message, id)| Control::Error(ErrorFrame {
+            code: ErrorCode::UnknownUser,
+            target: Some(message.clone()),
+            id,
+            message,
+        })),
+    ]
+}
+
+/// 任意字符组成的字符串，额外偏重表情、换行、引号等容易出错的字符
+fn text() -> impl Strategy<Value = String> {
+    let special = prop::sample::select(vec![
+        '\n', '\r', '\t', '\0', '"', '\\', '\u{7f}', '\u{2028}', '\u{feff}',
+    ]);
+    let ch = prop_oneof![
+        any::<char>(),
+        prop::char::range('\u{1f300}', '\u{1faff}'),
+        special
+    ];
+    prop::collection::vec(ch, 0..64).prop_map(String::from_iter)
+}
+
+/// 运行一项往返属性，失败时返回收缩后的最小反例
+fn property<T, S>(name: &'static str, strategy: S) -> Outcome
+where
+    T: Debug + DeserializeOwned + Serialize,
+    S: Strategy<Value = T>,
+{
+    let config = Config {
+        cases: PROPERTY_CASES,
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let result = TestRunner::new(config)
+        .run(&strategy, |value| {
+            [Codec::JsonLines, Codec::LengthPrefixed]
+                .into_iter()
+                .try_for_each(|codec| frame_roundtrip(codec, &value))
+                .map_err(TestCaseError::fail)
+        })
+        .map_err(|e| e.to_string());
+    Outcome { name, result }
+}
+
+/// 编码成帧再解码：须恰好读出一帧，且与原值序列化后相同
+fn frame_roundtrip<T: DeserializeOwned + Serialize>(codec: Codec, value: &T) -> Result<(), String> {
+    let bytes = codec.encode(value).map_err(|e| e.to_string())?;
+    let mut reader = FrameReader::new(&bytes[..], codec);
+    let decoded: T = match poll_now(reader.read_frame()) {
+        Ok(Some(decoded)) => decoded,
+        Ok(None) => return Err(tr!("conformance.roundtrip_empty", codec = codec)),
+        Err(e) => return Err(format!("{}: {}", codec, e)),
+    };
+    if !matches!(poll_now(reader.read_frame::<Value>()), Ok(None)) {
+        return Err(tr!("conformance.roundtrip_trailing", codec = codec));
+    }
+    let (expected, actual) = (serde_json::to_value(value), serde_json::to_value(&decoded));
+    match (expected, actual) {
+        (Ok(expected), Ok(actual)) if expected == actual => Ok(()),
+        (expected, actual) => Err(tr!(
+            "conformance.roundtrip_differs",
+            codec = codec,
+            expected = format!("{:?}", expected),
+            actual = format!("{:?}", actual),
+        )),
+    }
+}
+
+/// 从内存读取帧不会挂起，直接轮询一次得到结果
+fn poll_now<T>(future: impl Future<Output = Result<T, CodecError>>) -> Result<T, CodecError> {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(result) => result,
+        Poll::Pending => unreachable!("reading from memory never blocks"),
+    }
+}
+
+/// 依次在新连接上对服务器 `addr` 运行所有交互用例
+pub async fn run_exchanges(addr: &str, codec: Codec) -> Vec<Outcome> {
+    let mut outcomes = Vec::with_capacity(EXCHANGES.len());
+    for exchange in EXCHANGES {
+        let user = unique_name(exchange.name);
+        outcomes.push(Outcome {
+            name: exchange.name,
+            result: run_exchange(addr, codec, exchange, &user).await,
+        });
+    }
+    outcomes
+}
+
+async fn run_exchange(
+    addr: &str,
+    codec: Codec,
+    exchange: &Exchange,
+    user: &str,
+) -> Result<(), String> {
+    let stream = TcpStream::connect(addr)
+        .await
+        .map_err(|e| tr!("conformance.connect_failed", addr = addr, error = e))?;
+    let (reader, writer) = stream.into_split();
+    let mut reader = FrameReader::new(reader, codec);
+    let mut writer = FrameWriter::new(writer, codec);
+    for step in exchange.steps {
+        match step {
+            Step::Send(json) => {
+                let frame: Value = serde_json::from_str(&json.replace("{user}", user))
+                    .map_err(|e| e.to_string())?;
+                writer
+                    .write_frame(&frame)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Step::Expect(json) => {
+                let expected: Value = serde_json::from_str(&json.replace("{user}", user))
+                    .map_err(|e| e.to_string())?;
+                let found = async {
+                    while let Some(frame) = reader.read_frame::<Value>().await? {
+                        if contains(&frame, &expected) {
+                            return Ok(true);
+                        }
+                    }
+                    Ok::<_, crate::codec::CodecError>(false)
+                };
+                match tokio::time::timeout(EXPECT_TIMEOUT, found).await {
+                    Ok(Ok(true)) => {}
+                    Ok(Ok(false)) => return Err(tr!("conformance.closed", expected = expected)),
+                    Ok(Err(e)) => return Err(e.to_string()),
+                    Err(_) => return Err(tr!("conformance.timeout", expected = expected)),
+                }
+            }
+            Step::ExpectClose => {
+                let closed = async {
+                    while reader.read_frame::<Value>().await?.is_some() {}
+                    Ok::<_, crate::codec::CodecError>(())
+                };
+                match tokio::time::timeout(EXPECT_TIMEOUT, closed).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => return Err(e.to_string()),
+                    Err(_) => return Err(tr!("conformance.not_closed")),
+                }
+            }
+        }
+    }
+    let _ = writer.shutdown().await;
+    Ok(())
+}
+
+/// `actual` 是否包含 `expected` 中的全部字段且取值相同（对象逐层比较，其他值要求相等）
+fn contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .is_some_and(|actual| contains(actual, value))
+        }),
+        _ => actual == expected,
+    }
+}
+
+/// 为用例生成不会与真实用户冲突的用户名
+fn unique_name(case: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    format!("conformance-{}-{:08x}", case, nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<T: DeserializeOwned + Serialize>(value: &T) -> Result<(), TestCaseError> {
+        for codec in [Codec::JsonLines, Codec::LengthPrefixed] {
+            frame_roundtrip(codec, value).map_err(TestCaseError::fail)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fixtures_match() {
+        for outcome in check_fixtures() {
+            assert_eq!(outcome.result, Ok(()), "{}", outcome.name);
+        }
+    }
+
+    proptest! {
+        #![proptest_config(Config {
+            cases: PROPERTY_CASES,
+            failure_persistence: None,
+            ..Config::default()
+        })]
+
+        #[test]
+        fn roundtrip_message(message in message()) {
+            roundtrip(&Frame::from(message))?;
+        }
+
+        #[test]
+        fn roundtrip_hello(hello in hello()) {
+            roundtrip(&Registration::Hello(hello))?;
+        }
+
+        #[test]
+        fn roundtrip_control(control in control()) {
+            roundtrip(&Frame::Control(control))?;
+        }
+    }
+}
@@ -0,0 +1,56 @@
+/*!
+# 消息去重模块
+
+断线重连后客户端会重发未确认送达的消息，服务器也会补发客户端可能已经收到的消息。
+[`DedupWindow`] 记住最近见过的若干个消息编号，服务器据此丢弃重复发来的消息，
+客户端据此跳过重复收到的消息，保证同一条消息不会被投递或显示两次。
+*/
+
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+/// 默认记住的消息编号个数
+pub const DEFAULT_WINDOW: usize = 4096;
+
+/// 固定容量的滑动窗口，超过容量时忘记最早的编号
+#[derive(Debug)]
+pub struct DedupWindow<K> {
+    capacity: usize,
+    order: VecDeque<K>,
+    seen: HashSet<K>,
+}
+
+impl<K: Clone + Eq + Hash> DedupWindow<K> {
+    /// 创建最多记住 `capacity` 个编号的窗口
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// 记录一个编号
+    ///
+    /// # 返回值
+    /// 编号首次出现时返回 `true`，窗口内已见过时返回 `false`
+    pub fn insert(&mut self, key: K) -> bool {
+        if self.seen.contains(&key) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.seen.insert(key);
+        true
+    }
+}
+
+impl<K: Clone + Eq + Hash> Default for DedupWindow<K> {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
@@ -0,0 +1,464 @@
+/*!
+# 端到端加密会话
+
+`/e2e <用户>` 与对方建立端到端加密会话：双方各自生成 X25519 临时密钥对，以身份密钥
+（见 [`crate::identity`]）签名后经服务器交换临时公钥（[`Control::KeyExchange`]），
+再由 X25519 共享秘密经 HKDF-SHA256 派生会话密钥。此后发给对方的消息以 ChaCha20-Poly1305
+加密，作为 [`MessageKind::Encrypted`] 发送，服务器与联邦中的其他服务器只能看到密文。
+对方的身份公钥按首次使用即信任记住，已更换时拒绝交换，服务器因而无法冒充任何一方。
+
+会话每发出 [`REKEY_MESSAGES`] 条消息或距上次交换超过 [`REKEY_INTERVAL`] 时，由发送方
+自动发起新一轮交换：换用新的临时密钥，对方收到后同样换新并回复。旧的临时私钥随即丢弃，
+之后泄露的密钥无法解密更早的消息（前向保密）。为解密交换期间仍在途中的消息，每个会话
+保留最近 [`MAX_KEYS`] 把会话密钥；双方同时发起交换时也能收敛到同一把密钥。
+
+会话只保存在内存中，重启客户端后需要重新建立；对方以已失效的会话加密的消息无法解密时会给出提示。
+*/
+
+use crate::client::SharedWriter;
+use crate::identity::{fingerprint, verify_bytes, Identity, KnownKeys, Trust};
+use crate::protocol::{Control, ErrorCode, ErrorFrame, Frame};
+use crate::secrets::{decode_hex, encode_hex};
+use crate::terminal;
+use crate::theme::Theme;
+use crate::{tr, ArcString, Message, MessageKind};
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// 每发出这么多条加密消息后重新交换临时密钥
+pub const REKEY_MESSAGES: u32 = 100;
+
+/// 距上次交换超过该时长后，下一条消息发出时重新交换临时密钥
+pub const REKEY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// 每个会话保留的会话密钥数，用于解密交换期间仍在途中的消息
+pub const MAX_KEYS: usize = 6;
+
+/// 签名与密钥派生的域分隔前缀
+const DOMAIN: &str = "async-chat-e2e-v1";
+
+/// 加密前的消息内容
+#[derive(Deserialize, Serialize)]
+struct Plaintext {
+    kind: MessageKind,
+    content: String,
+}
+
+/// 与某个用户的加密会话
+struct Session {
+    /// 自己当前的临时私钥
+    mine: StaticSecret,
+    /// 上一把临时私钥：双方同时发起交换时，对方可能以它和对方的新公钥加密消息
+    previous: Option<StaticSecret>,
+    /// 对方最新的临时公钥，收到之前会话尚未建立
+    theirs: Option<PublicKey>,
+    /// 会话密钥（编号，密钥），第一把用于加密
+    keys: VecDeque<(String, Key)>,
+    /// 上次换用临时密钥后发出的消息数
+    sent: u32,
+    /// 上次换用临时密钥的时间
+    renewed_at: Instant,
+    /// 收到对方临时公钥的次数
+    exchanges: u64,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            mine: fresh_secret(),
+            previous: None,
+            theirs: None,
+            keys: VecDeque::new(),
+            sent: 0,
+            renewed_at: Instant::now(),
+            exchanges: 0,
+        }
+    }
+
+    fn established(&self) -> bool {
+        self.theirs.is_some()
+    }
+
+    /// 自己当前的临时公钥（十六进制）
+    fn public_key(&self) -> String {
+        encode_hex(PublicKey::from(&self.mine).as_bytes())
+    }
+
+    /// 是否到了重新交换的时候
+    fn due(&self) -> bool {
+        self.sent >= REKEY_MESSAGES || self.renewed_at.elapsed() >= REKEY_INTERVAL
+    }
+
+    /// 换用新的临时密钥，丢弃上上一把
+    fn renew(&mut self) {
+        self.previous = Some(std::mem::replace(&mut self.mine, fresh_secret()));
+        self.sent = 0;
+        self.renewed_at = Instant::now();
+    }
+
+    /// 记住对方新的临时公钥，并以自己当前与上一把临时私钥分别派生会话密钥
+    fn accept(&mut self, theirs: PublicKey) {
+        if let Some(previous) = &self.previous {
+            let derived = derive(previous, &theirs);
+            self.add_key(derived);
+        }
+        let derived = derive(&self.mine, &theirs);
+        self.add_key(derived);
+        self.theirs = Some(theirs);
+        self.exchanges += 1;
+    }
+
+    /// 以自己当前的临时私钥与对方的临时公钥派生会话密钥，作为加密用的密钥
+    fn rederive(&mut self) {
+        if let Some(theirs) = self.theirs {
+            let derived = derive(&self.mine, &theirs);
+            self.add_key(derived);
+        }
+    }
+
+    /// 把会话密钥放到最前（已有时移到最前），超出 [`MAX_KEYS`] 的旧密钥被丢弃
+    fn add_key(&mut self, (id, key): (String, Key)) {
+        self.keys.retain(|(known, _)| *known != id);
+        self.keys.push_front((id, key));
+        self.keys.truncate(MAX_KEYS);
+    }
+}
+
+/// 加密待发送的消息的结果
+pub(crate) enum Sealing {
+    /// 与对方没有加密会话，按原样发送
+    Plain,
+    /// 加密后的内容，以 [`MessageKind::Encrypted`] 发送
+    Sealed(String),
+    /// 会话尚未建立，消息不应发送
+    Pending,
+}
+
+/// 客户端的端到端加密会话，由输入循环与接收任务共享
+pub struct E2e {
+    name: ArcString,
+    identity: Option<Arc<Identity>>,
+    known_keys: Arc<KnownKeys>,
+    theme: Arc<Theme>,
+    /// 用户名到会话
+    sessions: Mutex<BTreeMap<String, Session>>,
+}
+
+impl fmt::Debug for E2e {
+    /// 不打印任何密钥
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sessions = self.sessions.lock().unwrap();
+        f.debug_struct("E2e")
+            .field("sessions", &sessions.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl E2e {
+    pub fn new(
+        name: ArcString,
+        identity: Option<Arc<Identity>>,
+        known_keys: Arc<KnownKeys>,
+        theme: Arc<Theme>,
+    ) -> Self {
+        Self {
+            name,
+            identity,
+            known_keys,
+            theme,
+            sessions: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// 处理 `/e2e <用户>`：向 `peer` 发起加密会话
+    pub async fn start(&self, writer: &SharedWriter, peer: &str) {
+        if self.identity.is_none() {
+            self.error(tr!("e2e.no_identity"));
+            return;
+        }
+        let key = {
+            let mut sessions = self.sessions.lock().unwrap();
+            if sessions.get(peer).is_some_and(Session::established) {
+                drop(sessions);
+                self.system(tr!("e2e.already", user = peer));
+                return;
+            }
+            let session = sessions
+                .entry(peer.to_string())
+                .or_insert_with(Session::new);
+            session.public_key()
+        };
+        self.exchange(writer, peer, key, false).await;
+        self.system(tr!("e2e.requested", user = peer));
+    }
+
+    /// 处理 `/e2e off <用户>`：结束与 `peer` 的加密会话
+    pub fn stop(&self, peer: &str) {
+        if self.sessions.lock().unwrap().remove(peer).is_some() {
+            self.system(tr!("e2e.stopped", user = peer));
+        } else {
+            self.system(tr!("e2e.not_active", user = peer));
+        }
+    }
+
+    /// 处理 `/e2e`：列出加密会话
+    pub fn list(&self) {
+        let sessions = self.sessions.lock().unwrap();
+        if sessions.is_empty() {
+            self.system(tr!("e2e.none"));
+        }
+        for (peer, session) in sessions.iter() {
+            let text = if session.established() {
+                tr!("e2e.session_ready", user = peer, count = session.exchanges)
+            } else {
+                tr!("e2e.session_pending", user = peer)
+            };
+            terminal::screen().println(text);
+        }
+    }
+
+    /// 加密发给 `peer` 的消息；到了重新交换的时候随即换用新的临时密钥并通知对方
+    pub(crate) async fn seal(
+        &self,
+        writer: &SharedWriter,
+        peer: &str,
+        kind: MessageKind,
+        content: &str,
+    ) -> Sealing {
+        let (sealed, renewed) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let Some(session) = sessions.get_mut(peer) else {
+                return Sealing::Plain;
+            };
+            let Some((id, key)) = session.keys.front().filter(|_| session.established()) else {
+                return Sealing::Pending;
+            };
+            let plaintext = Plaintext {
+                kind,
+                content: content.to_string(),
+            };
+            let Some(sealed) = encrypt(id, key, &plaintext) else {
+                return Sealing::Pending;
+            };
+            session.sent += 1;
+            let renewed = session.due().then(|| {
+                session.renew();
+                session.rederive();
+                session.public_key()
+            });
+            (sealed, renewed)
+        };
+        if let Some(key) = renewed {
+            self.exchange(writer, peer, key, false).await;
+        }
+        Sealing::Sealed(sealed)
+    }
+
+    /// 解密收到的加密消息，得到原本的类型与内容；无法解密时返回 `None`
+    pub fn open(&self, message: &Message) -> Option<Message> {
+        let mut parts = message.content().splitn(3, '.');
+        let (id, nonce, data) = (parts.next()?, parts.next()?, parts.next()?);
+        let nonce = decode_hex(nonce).filter(|nonce| nonce.len() == 12)?;
+        let data = Base64::decode_vec(data).ok()?;
+        let sessions = self.sessions.lock().unwrap();
+        let (_, key) = sessions
+            .get(message.from())?
+            .keys
+            .iter()
+            .find(|(known, _)| known == id)?;
+        let plaintext = ChaCha20Poly1305::new(key)
+            .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+            .ok()?;
+        let plaintext: Plaintext = serde_json::from_slice(&plaintext).ok()?;
+        if plaintext.kind == MessageKind::Encrypted {
+            return None;
+        }
+        Some(
+            message
+                .clone()
+                .with_kind(plaintext.kind)
+                .with_content(plaintext.content),
+        )
+    }
+
+    /// 处理对方经服务器转来的密钥交换帧
+    pub async fn dispatch(&self, writer: &SharedWriter, control: Control) {
+        let Control::KeyExchange {
+            peer,
+            key,
+            reply,
+            signer,
+            sig,
+        } = control
+        else {
+            return;
+        };
+        let bytes = exchange_bytes(&peer, &self.name.get(), &key, reply);
+        if !verify_bytes(&signer, &sig, &bytes) {
+            self.error(tr!("e2e.bad_signature", user = peer));
+            return;
+        }
+        match self.known_keys.check_key(&peer, &signer) {
+            Trust::Changed(_) => {
+                self.error(tr!("e2e.key_changed", user = peer));
+                return;
+            }
+            Trust::FirstSeen(key) => self.notice(tr!(
+                "identity.first_seen",
+                user = peer,
+                fingerprint = fingerprint(&key)
+            )),
+            _ => {}
+        }
+        let Some(theirs) = decode_hex(&key)
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .map(PublicKey::from)
+        else {
+            self.error(tr!("e2e.bad_signature", user = peer));
+            return;
+        };
+        if !reply && self.identity.is_none() {
+            self.notice(tr!("e2e.no_identity"));
+            return;
+        }
+
+        let (answer, established, started) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let started = !reply && !sessions.contains_key(&peer);
+            let Some(session) = (if reply {
+                sessions.get_mut(&peer)
+            } else {
+                Some(sessions.entry(peer.clone()).or_insert_with(Session::new))
+            }) else {
+                // 已经结束的会话迟到的回复
+                return;
+            };
+            let established = !session.established();
+            session.accept(theirs);
+            // 对方换用了新的临时密钥，自己也随之换新并回复
+            let answer = (!reply).then(|| {
+                session.renew();
+                session.rederive();
+                session.public_key()
+            });
+            (answer, established, started)
+        };
+        if started {
+            self.notice(tr!("e2e.started_by_peer", user = peer));
+        }
+        if let Some(key) = answer {
+            self.exchange(writer, &peer, key, true).await;
+        }
+        if established {
+            self.success(tr!("e2e.established", user = peer));
+        }
+    }
+
+    /// 服务器无法转发密钥交换时，移除尚未建立的会话；错误提示照常显示
+    pub(crate) fn refused(&self, error: &ErrorFrame) {
+        if error.code != ErrorCode::E2eUnavailable {
+            return;
+        }
+        if let Some(peer) = &error.target {
+            let mut sessions = self.sessions.lock().unwrap();
+            if sessions.get(peer).is_some_and(|s| !s.established()) {
+                sessions.remove(peer);
+            }
+        }
+    }
+
+    /// 向 `peer` 发送签名的临时公钥
+    async fn exchange(&self, writer: &SharedWriter, peer: &str, key: String, reply: bool) {
+        let Some(identity) = &self.identity else {
+            return;
+        };
+        let bytes = exchange_bytes(&self.name.get(), peer, &key, reply);
+        let (signer, sig) = identity.sign_bytes(&bytes);
+        let exchange = Control::KeyExchange {
+            peer: peer.to_string(),
+            key,
+            reply,
+            signer,
+            sig,
+        };
+        let _ = writer
+            .lock()
+            .await
+            .write_frame(&Frame::from(exchange))
+            .await;
+    }
+
+    fn notice(&self, text: String) {
+        terminal::screen().println(format!("\n{}", self.theme.system.paint(&text)));
+    }
+
+    fn system(&self, text: String) {
+        terminal::screen().println(self.theme.system.paint(&text).to_string());
+    }
+
+    fn success(&self, text: String) {
+        terminal::screen().println(format!("\n{}", self.theme.success.paint(&text)));
+    }
+
+    fn error(&self, text: String) {
+        terminal::screen().eprintln(self.theme.error.paint(&text).to_string());
+    }
+}
+
+/// 生成新的临时私钥
+fn fresh_secret() -> StaticSecret {
+    let mut bytes = [0u8; 32];
+    // 取不到随机数时无法保证密钥安全，不应继续
+    getrandom::getrandom(&mut bytes).expect("failed to read random bytes for an ephemeral key");
+    StaticSecret::from(bytes)
+}
+
+/// 由自己的临时私钥与对方的临时公钥派生会话密钥，返回（编号，密钥）
+///
+/// 派生时按字节序排列双方的临时公钥，双方得到相同的密钥与编号
+fn derive(mine: &StaticSecret, theirs: &PublicKey) -> (String, Key) {
+    let ours = PublicKey::from(mine);
+    let (a, b) = if ours.as_bytes() <= theirs.as_bytes() {
+        (ours, *theirs)
+    } else {
+        (*theirs, ours)
+    };
+    let info = [a.as_bytes().as_slice(), b.as_bytes().as_slice()].concat();
+    let shared = mine.diffie_hellman(theirs);
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(DOMAIN.as_bytes()), shared.as_bytes())
+        .expand(&info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    let id = encode_hex(&Sha256::digest(&info)[..4]);
+    (id, key)
+}
+
+/// 以会话密钥加密，结果为 `编号.随机数.密文`（随机数为十六进制，密文为 Base64）
+fn encrypt(id: &str, key: &Key, plaintext: &Plaintext) -> Option<String> {
+    let mut nonce = [0u8; 12];
+    getrandom::getrandom(&mut nonce).ok()?;
+    let plaintext = serde_json::to_vec(plaintext).ok()?;
+    let data = ChaCha20Poly1305::new(key)
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .ok()?;
+    Some(format!(
+        "{}.{}.{}",
+        id,
+        encode_hex(&nonce),
+        Base64::encode_string(&data)
+    ))
+}
+
+/// 被签名的密钥交换内容
+fn exchange_bytes(from: &str, to: &str, key: &str, reply: bool) -> Vec<u8> {
+    format!("{}\0{}\0{}\0{}\0{}", DOMAIN, from, to, key, reply).into_bytes()
+}
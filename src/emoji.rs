@@ -0,0 +1,98 @@
+/*!
+# 表情短代码模块
+
+客户端在发送前把 `:smile:` 形式的短代码展开为对应的 Unicode 表情，
+线路上传输的仍是普通文本，旧客户端无需任何改动即可显示。
+短代码表来自 GitHub / Slack 通用的 gemoji 数据（由 `emojis` 库提供）；
+未启用 `tui` 特性时没有短代码表，文本保持原样。
+*/
+
+use std::borrow::Cow;
+
+/// 将文本中可识别的 `:短代码:` 替换为表情，无法识别的保持原样
+///
+/// # 返回值
+/// 未发生替换时借用原文本，避免额外分配
+#[cfg(feature = "tui")]
+pub fn expand(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut replaced = false;
+    while let Some(start) = rest.find(':') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let emoji = after
+            .find(':')
+            .map(|end| &after[..end])
+            .filter(|code| is_shortcode(code))
+            .and_then(|code| emojis::get_by_shortcode(code).map(|emoji| (code, emoji)));
+        match emoji {
+            Some((code, emoji)) => {
+                out.push_str(emoji.as_str());
+                rest = &after[code.len() + 1..];
+                replaced = true;
+            }
+            // 不是短代码：保留这个冒号，从下一个字符继续查找
+            None => {
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    if !replaced {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// 按短代码或名称查找表情
+///
+/// # 参数
+/// - `query`: 查询词（大小写不敏感的子串匹配）
+/// - `limit`: 最多返回的条数
+///
+/// # 返回值
+/// `(短代码, 表情)` 列表，短代码与查询词完全相同的排在最前
+#[cfg(feature = "tui")]
+pub fn search(query: &str, limit: usize) -> Vec<(&'static str, &'static str)> {
+    let query = query.trim().trim_matches(':').to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut found: Vec<(&'static str, &'static str)> = emojis::iter()
+        .filter_map(|emoji| {
+            let code = emoji.shortcode()?;
+            let matched = emoji.shortcodes().any(|code| code.contains(&query))
+                || emoji.name().to_lowercase().contains(&query);
+            matched.then(|| (code, emoji.as_str()))
+        })
+        .collect();
+    found.sort_by_key(|(code, _)| *code != query);
+    found.truncate(limit);
+    found
+}
+
+/// 未启用 `tui` 特性时没有短代码表，原样返回
+#[cfg(not(feature = "tui"))]
+pub fn expand(text: &str) -> Cow<'_, str> {
+    Cow::Borrowed(text)
+}
+
+/// 未启用 `tui` 特性时没有短代码表，找不到任何表情
+#[cfg(not(feature = "tui"))]
+pub fn search(_query: &str, _limit: usize) -> Vec<(&'static str, &'static str)> {
+    Vec::new()
+}
+
+/// 短代码只包含小写字母、数字及 `_`、`+`、`-`
+#[cfg(feature = "tui")]
+fn is_shortcode(code: &str) -> bool {
+    !code.is_empty()
+        && code
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-'))
+}
@@ -0,0 +1,148 @@
+/*!
+# 会话导出模块
+
+把本地聊天记录（见 [`crate::history`]）中与某个用户的会话渲染为独立的 HTML 文件，
+样式内嵌在文件中，不依赖外部资源，可以直接用浏览器打开、分享或存档。
+
+消息内容按 [`crate::markdown`] 解析出的样式片段映射为 HTML 标签（粗体、斜体、删除线、代码），
+链接转换为可点击的 `<a>`；每条消息带有写入记录时的本地日期与时间，自己发出的消息靠右显示。
+未启用 `tui` 特性时 Markdown 不解析，内容按原文导出（仍保留换行与链接）。
+*/
+
+use crate::history::HistoryEntry;
+use crate::i18n::Lang;
+use crate::markdown::{self, Span};
+use crate::{link, tr, MessageKind};
+
+/// 内嵌的样式表
+const STYLE: &str = "\
+body{margin:0;background:#f4f5f7;color:#1f2328;font:15px/1.5 -apple-system,'Segoe UI','PingFang SC','Microsoft YaHei',sans-serif}
+header{padding:16px 24px;background:#2f3b52;color:#fff}
+header h1{margin:0;font-size:18px}
+header p{margin:4px 0 0;font-size:13px;opacity:.8}
+main{max-width:760px;margin:0 auto;padding:16px}
+.msg{margin:8px 0;display:flex;flex-direction:column;align-items:flex-start}
+.msg.mine{align-items:flex-end}
+.meta{font-size:12px;color:#6a737d;margin:0 4px 2px}
+.bubble{max-width:80%;padding:8px 12px;border-radius:10px;background:#fff;box-shadow:0 1px 2px rgba(0,0,0,.08);white-space:pre-wrap;word-wrap:break-word}
+.mine .bubble{background:#d8ecff}
+.action .bubble{background:transparent;box-shadow:none;font-style:italic;color:#57606a}
+code{font-family:ui-monospace,Consolas,monospace;background:rgba(0,0,0,.06);padding:0 3px;border-radius:3px}
+.dim{color:#8b949e}
+a{color:#0969da}
+";
+
+/// 以 `lang` 把 `owner` 与 `peer` 之间的聊天记录渲染为完整的 HTML 文档
+pub fn to_html(owner: &str, peer: &str, entries: &[HistoryEntry], lang: Lang) -> String {
+    let title = tr!(lang => "export.title", peer = peer);
+    let range = match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => tr!(lang => "export.range",
+            count = entries.len(),
+            from = first.recorded_at.format("%Y-%m-%d %H:%M"),
+            to = last.recorded_at.format("%Y-%m-%d %H:%M"),
+        ),
+        _ => String::new(),
+    };
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n\
+         <header><h1>{}</h1><p>{}</p></header>\n<main>\n",
+        lang.tag(),
+        escape(&title),
+        STYLE,
+        escape(&title),
+        escape(&range)
+    );
+    for entry in entries {
+        let message = &entry.message;
+        let mut class = String::from("msg");
+        if message.from() == owner {
+            class.push_str(" mine");
+        }
+        let body = match message.kind() {
+            MessageKind::Action => {
+                class.push_str(" action");
+                format!(
+                    "* {} {}",
+                    escape(message.from()),
+                    linkify(message.content())
+                )
+            }
+            MessageKind::Text | MessageKind::Encrypted => render(message.content()),
+        };
+        let time = entry.recorded_at.format("%Y-%m-%d %H:%M:%S");
+        html.push_str(&format!(
+            "<div class=\"{}\"><div class=\"meta\"><time datetime=\"{}\">{}</time> {}</div>\
+             <div class=\"bubble\">{}</div></div>\n",
+            class,
+            entry.recorded_at.to_rfc3339(),
+            time,
+            escape(message.from()),
+            body
+        ));
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+    html
+}
+
+/// 把消息内容的 Markdown 样式片段转换为 HTML
+fn render(content: &str) -> String {
+    markdown::parse(content).iter().map(span_html).collect()
+}
+
+/// 单个样式片段：按样式由内向外包上标签
+fn span_html(span: &Span) -> String {
+    let style = span.style;
+    let mut html = if style.code {
+        format!("<code>{}</code>", escape(&span.text))
+    } else {
+        linkify(&span.text)
+    };
+    for (enabled, tag) in [
+        (style.strikethrough, "del"),
+        (style.italic, "em"),
+        (style.bold, "strong"),
+    ] {
+        if enabled {
+            html = format!("<{tag}>{html}</{tag}>");
+        }
+    }
+    if style.dim {
+        html = format!("<span class=\"dim\">{}</span>", html);
+    }
+    html
+}
+
+/// 转义文本，并把其中的链接转换为 `<a>`
+fn linkify(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut pos = 0;
+    for range in link::find(text) {
+        html.push_str(&escape(&text[pos..range.start]));
+        let url = escape(&text[range.clone()]);
+        html.push_str(&format!(
+            "<a href=\"{}\" rel=\"noopener noreferrer\">{}</a>",
+            url, url
+        ));
+        pos = range.end;
+    }
+    html.push_str(&escape(&text[pos..]));
+    html
+}
+
+/// 转义 HTML 文本与属性值
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
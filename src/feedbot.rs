@@ -0,0 +1,296 @@
+/*!
+# 订阅源机器人
+
+内置的 RSS/Atom 订阅源机器人，也是基于机器人账号（见 `server` 模块的 `bots` 子模块）编写机器人的参考实现：
+
+```sh
+# 管理员先执行 /bot create news message 得到令牌
+cargo run -- feedbot 127.0.0.1:7891 --name=news --token=bot_0123... \
+    --feeds=https://blog.rust-lang.org/feed.xml --to=alice,bob --interval=600
+```
+
+- 每隔 [`FeedConfig::interval`] 依次抓取各订阅源，把新条目以一条消息发给 [`FeedConfig::to`] 中的每个用户；
+- 已见过的条目编号（RSS 的 `guid`、Atom 的 `id`，缺失时用链接或标题）保存在状态文件中，
+  重启后不会重复发送；首次抓取某个订阅源时只记录已有条目而不发送，避免刷屏；
+- 每次最多发送 [`MAX_POSTS_PER_POLL`] 条，其余记为已见；
+- 抓取使用 HTTP/1.0 GET（`https://` 需要启用 `tls` 特性），跟随最多 [`MAX_REDIRECTS`](crate::link::MAX_REDIRECTS) 次重定向；
+  XML 由 roxmltree 解析，同时识别 RSS 2.0、RSS 1.0 与 Atom；
+- 与服务器的连接断开后在下一轮抓取前重新连接。
+*/
+
+use crate::client::{Client, Connection};
+use crate::link;
+use crate::{read_json, tr, write_json};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// 每个订阅源最多记住的条目编号数
+pub const MAX_SEEN: usize = 1000;
+
+/// 每个订阅源每轮最多发送的新条目数
+pub const MAX_POSTS_PER_POLL: usize = 10;
+
+/// 单个订阅源响应的最大字节数
+const MAX_FEED_LEN: u64 = 4 * 1024 * 1024;
+
+/// 抓取订阅源时请求的内容类型
+const FEED_ACCEPT: &str = "application/rss+xml, application/atom+xml, application/xml, text/xml";
+
+/// 订阅源机器人的设置
+#[derive(Clone, Debug)]
+pub struct FeedConfig {
+    /// 订阅源地址（`http://` 或 `https://`）
+    pub feeds: Vec<String>,
+    /// 接收新条目的用户
+    pub to: Vec<String>,
+    /// 两轮抓取之间的间隔
+    pub interval: Duration,
+    /// 保存已见条目的状态文件，`None` 时只保存在内存中
+    pub state: Option<PathBuf>,
+}
+
+/// 订阅源中的一个条目
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// 条目编号，用于判断是否已经发送过
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+}
+
+/// 解析后的订阅源
+#[derive(Clone, Debug, Default)]
+pub struct Feed {
+    pub title: String,
+    /// 条目，按在订阅源中出现的顺序（通常最新的在前）
+    pub entries: Vec<Entry>,
+}
+
+/// 解析 RSS 2.0、RSS 1.0 或 Atom 文档
+pub fn parse_feed(xml: &str) -> Result<Feed, roxmltree::Error> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let root = doc.root_element();
+    // RSS 的标题在 channel 中，Atom 与 RSS 1.0 的标题在根元素（或其 channel）中
+    let channel = root
+        .children()
+        .find(|node| node.has_tag_name("channel"))
+        .unwrap_or(root);
+    let entries = root
+        .descendants()
+        .filter(|node| node.is_element() && matches!(node.tag_name().name(), "item" | "entry"))
+        .map(|node| {
+            let title = child_text(node, "title").unwrap_or_default();
+            let link = entry_link(node);
+            let id = child_text(node, "guid")
+                .or_else(|| child_text(node, "id"))
+                .or_else(|| link.clone())
+                .unwrap_or_else(|| title.clone());
+            Entry { id, title, link }
+        })
+        .collect();
+    Ok(Feed {
+        title: child_text(channel, "title").unwrap_or_default(),
+        entries,
+    })
+}
+
+/// 名为 `name` 的子元素的文本（去掉首尾空白），没有或为空时返回 `None`
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    let child = node
+        .children()
+        .find(|child| child.tag_name().name() == name)?;
+    let text: String = child
+        .descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// 条目的链接：Atom 取 `rel` 为空或 `alternate` 的 `<link href>`，RSS 取 `<link>` 的文本
+fn entry_link(node: roxmltree::Node) -> Option<String> {
+    let mut links = node
+        .children()
+        .filter(|child| child.tag_name().name() == "link");
+    links
+        .clone()
+        .find_map(|link| {
+            let rel = link.attribute("rel").unwrap_or("alternate");
+            (rel == "alternate")
+                .then(|| link.attribute("href"))
+                .flatten()
+        })
+        .map(str::to_string)
+        .or_else(|| {
+            links.find_map(|link| {
+                let text = link.text()?.trim();
+                (!text.is_empty()).then(|| text.to_string())
+            })
+        })
+}
+
+/// 抓取订阅源 `url` 的内容
+pub async fn fetch(url: &str) -> io::Result<String> {
+    let body = link::fetch(url, FEED_ACCEPT, MAX_FEED_LEN).await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// 订阅源机器人
+#[derive(Debug)]
+pub struct FeedBot {
+    config: FeedConfig,
+    /// 订阅源地址 → 已见过的条目编号（先见过的在前）
+    seen: BTreeMap<String, Vec<String>>,
+}
+
+impl FeedBot {
+    /// 创建机器人，并从状态文件载入已见过的条目
+    pub fn open(config: FeedConfig) -> io::Result<Self> {
+        let seen = match &config.state {
+            Some(path) => {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    fs::create_dir_all(dir)?;
+                }
+                read_json(path)?.unwrap_or_default()
+            }
+            None => BTreeMap::new(),
+        };
+        Ok(FeedBot { config, seen })
+    }
+
+    /// 连接服务器后按间隔抓取订阅源，直到收到 Ctrl+C
+    pub async fn run(
+        &mut self,
+        client: &Client,
+        addr: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // 尽早注册信号处理：抓取期间收到的 Ctrl+C 也要在本轮结束后生效
+        let (stop_tx, mut stop) = tokio::sync::oneshot::channel();
+        let interrupt = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                let _ = stop_tx.send(());
+            }
+        });
+        let mut conn = client.connect(addr).await?;
+        println!(
+            "{}",
+            tr!(
+                "feedbot.started",
+                count = self.config.feeds.len(),
+                interval = self.config.interval.as_secs(),
+            )
+        );
+        loop {
+            if conn.is_closed() {
+                match client.connect(addr).await {
+                    Ok(reconnected) => conn = reconnected,
+                    Err(e) => eprintln!("{}", tr!("feedbot.reconnect_failed", error = e)),
+                }
+            }
+            if !conn.is_closed() {
+                self.poll_all(&mut conn).await;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.interval) => {}
+                _ = &mut stop => break,
+            }
+        }
+        interrupt.abort();
+        conn.close().await;
+        Ok(())
+    }
+
+    /// 抓取所有订阅源，发送新条目并保存状态
+    async fn poll_all(&mut self, conn: &mut Connection) {
+        for url in self.config.feeds.clone() {
+            let feed = match fetch(&url).await {
+                Ok(xml) => match parse_feed(&xml) {
+                    Ok(feed) => feed,
+                    Err(e) => {
+                        eprintln!("{}", tr!("feedbot.parse_failed", url = url, error = e));
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{}", tr!("feedbot.fetch_failed", url = url, error = e));
+                    continue;
+                }
+            };
+            let fresh = self.record(&url, &feed);
+            if fresh.is_empty() {
+                continue;
+            }
+            println!("{}", tr!("feedbot.posting", url = url, count = fresh.len()));
+            for entry in fresh {
+                let text = match &entry.link {
+                    Some(link) => tr!(
+                        "feedbot.entry",
+                        feed = feed.title,
+                        title = entry.title,
+                        link = link,
+                    ),
+                    None => tr!(
+                        "feedbot.entry_no_link",
+                        feed = feed.title,
+                        title = entry.title
+                    ),
+                };
+                for to in &self.config.to {
+                    if let Err(e) = conn.send(to, &text).await {
+                        eprintln!("{}", tr!("feedbot.send_failed", user = to, error = e));
+                    }
+                }
+            }
+        }
+        self.save();
+    }
+
+    /// 记录 `feed` 中的条目为已见，返回需要发送的新条目（先发布的在前）
+    ///
+    /// 首次见到该订阅源时只记录、不发送。
+    fn record(&mut self, url: &str, feed: &Feed) -> Vec<Entry> {
+        let primed = self.seen.contains_key(url);
+        let seen = self.seen.entry(url.to_string()).or_default();
+        let known: HashSet<&String> = seen.iter().collect();
+        // 订阅源通常最新的在前，倒序后按发布先后发送
+        let mut fresh: Vec<Entry> = feed
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| !known.contains(&entry.id))
+            .cloned()
+            .collect();
+        fresh.dedup_by(|a, b| a.id == b.id);
+        seen.extend(fresh.iter().map(|entry| entry.id.clone()));
+        if seen.len() > MAX_SEEN {
+            let excess = seen.len() - MAX_SEEN;
+            seen.drain(..excess);
+        }
+        if !primed {
+            println!(
+                "{}",
+                tr!("feedbot.primed", url = url, count = feed.entries.len())
+            );
+            return Vec::new();
+        }
+        // 条目太多时只发送最新的几条
+        let skip = fresh.len().saturating_sub(MAX_POSTS_PER_POLL);
+        fresh.split_off(skip)
+    }
+
+    /// 保存状态文件，失败时仅记录日志
+    fn save(&self) {
+        if let Some(path) = &self.config.state {
+            if let Err(e) = write_json(path, &self.seen) {
+                eprintln!(
+                    "{}",
+                    tr!("feedbot.save_failed", path = path.display(), error = e)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+/*!
+# C 语言接口
+
+启用 `ffi` 特性后导出 C ABI，供其他语言编写的桌面程序嵌入客户端，无需重新实现协议。
+头文件 `include/chat.h` 随源码提交，构建时由 cbindgen 重新生成到 `OUT_DIR`，测试检查两者一致；动态库或静态库通过
+`cargo rustc --release --lib --features ffi --crate-type=cdylib`（或 `staticlib`）构建。
+
+- [`chat_client_connect`] 连接服务器并完成注册，返回客户端句柄；
+- [`chat_client_send`]、[`chat_client_send_action`] 发送消息；
+- 收到的消息与服务器错误通过连接时传入的回调通知，回调在库内部的线程上调用，
+  传入的字符串只在回调期间有效；`user_data` 原样传回，调用方须保证其可以跨线程使用；
+- [`chat_client_close`] 主动退出并释放句柄；
+- 调用失败时 [`chat_last_error`] 返回当前线程上一次失败的说明。
+
+所有字符串均为以 NUL 结尾的 UTF-8；消息内容中的 NUL 字符在传给回调前被移除。
+*/
+
+use crate::client::{Client, Connection};
+use crate::protocol::ErrorFrame;
+use crate::{Message, MessageKind};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use tokio::runtime::{self, Runtime};
+
+/// 客户端句柄，由 [`chat_client_connect`] 创建、[`chat_client_close`] 释放
+pub struct ChatClient {
+    runtime: Runtime,
+    connection: Connection,
+}
+
+/// 调用结果
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatStatus {
+    /// 成功
+    Ok = 0,
+    /// 参数为空指针或不是有效的 UTF-8
+    InvalidArgument = 1,
+    /// 发送失败（连接已断开等）
+    SendFailed = 2,
+}
+
+/// 消息类型，与 [`MessageKind`] 对应
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatMessageKind {
+    /// 普通文本消息
+    Text = 0,
+    /// 动作消息，显示为 "* 发送者 动作"
+    Action = 1,
+    /// 未能解密的端到端加密消息，内容为密文
+    Encrypted = 2,
+}
+
+/// 传给消息回调的一条消息，其中的字符串只在回调期间有效
+#[repr(C)]
+pub struct ChatMessage {
+    /// 发送者
+    pub from: *const c_char,
+    /// 接收者
+    pub to: *const c_char,
+    /// 消息内容
+    pub content: *const c_char,
+    /// 发送时间（`时:分:秒`）
+    pub time_stamp: *const c_char,
+    /// 消息类型
+    pub kind: ChatMessageKind,
+    /// 服务器分配的消息编号，没有编号时为 0
+    pub id: u64,
+}
+
+/// 收到消息时调用的回调
+pub type ChatMessageCallback =
+    Option<unsafe extern "C" fn(user_data: *mut c_void, message: *const ChatMessage)>;
+
+/// 收到服务器错误时调用的回调：`code` 为 `UNKNOWN_USER` 这样的错误码，`message` 为说明文字
+pub type ChatErrorCallback = Option<
+    unsafe extern "C" fn(user_data: *mut c_void, code: *const c_char, message: *const c_char),
+>;
+
+/// 调用方传入的 `user_data`，只原样传回给回调
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+// 调用方在 `chat_client_connect` 的约定中保证 `user_data` 可以跨线程使用
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+thread_local! {
+    /// 当前线程上一次失败的说明
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// 连接服务器并以 `name` 注册
+///
+/// `addr` 可以是以逗号分隔的多个服务器地址，依次尝试直到连接成功。
+/// 失败时返回空指针，可通过 [`chat_last_error`] 获取原因。
+///
+/// # Safety
+/// `addr` 与 `name` 须为以 NUL 结尾的有效字符串；回调可能在连接存续期间的任意时刻于
+/// 库内部的线程上调用，`user_data` 须在 [`chat_client_close`] 返回前保持有效且可以跨线程使用。
+#[no_mangle]
+pub unsafe extern "C" fn chat_client_connect(
+    addr: *const c_char,
+    name: *const c_char,
+    on_message: ChatMessageCallback,
+    on_error: ChatErrorCallback,
+    user_data: *mut c_void,
+) -> *mut ChatClient {
+    let (Some(addr), Some(name)) = (string_arg(addr), string_arg(name)) else {
+        return ptr::null_mut();
+    };
+    let runtime = match runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+    let user_data = UserData(user_data);
+    let mut builder = Client::builder(name);
+    if let Some(callback) = on_message {
+        builder = builder.on_message(move |message| {
+            let user_data = user_data;
+            deliver(callback, user_data.0, &message)
+        });
+    }
+    if let Some(callback) = on_error {
+        builder = builder.on_error(move |error| {
+            let user_data = user_data;
+            report(callback, user_data.0, &error)
+        });
+    }
+    let client = builder.build();
+    match runtime.block_on(client.connect(&addr)) {
+        Ok(connection) => Box::into_raw(Box::new(ChatClient {
+            runtime,
+            connection,
+        })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// 发送一条文本消息给 `to`
+///
+/// # Safety
+/// `client` 须为 [`chat_client_connect`] 返回且尚未关闭的句柄，`to` 与 `content` 须为以 NUL
+/// 结尾的有效字符串；同一句柄不能在多个线程上同时调用。
+#[no_mangle]
+pub unsafe extern "C" fn chat_client_send(
+    client: *mut ChatClient,
+    to: *const c_char,
+    content: *const c_char,
+) -> ChatStatus {
+    send(client, to, content, MessageKind::Text)
+}
+
+/// 发送一条动作消息给 `to`，接收方显示为 "* 用户名 动作"
+///
+/// # Safety
+/// 同 [`chat_client_send`]。
+#[no_mangle]
+pub unsafe extern "C" fn chat_client_send_action(
+    client: *mut ChatClient,
+    to: *const c_char,
+    action: *const c_char,
+) -> ChatStatus {
+    send(client, to, action, MessageKind::Action)
+}
+
+/// 主动退出（发送 Goodbye 帧）并释放句柄；`client` 为空指针时不做任何事
+///
+/// # Safety
+/// `client` 须为 [`chat_client_connect`] 返回且尚未关闭的句柄，调用后不能再使用；
+/// 不能在回调中调用。
+#[no_mangle]
+pub unsafe extern "C" fn chat_client_close(client: *mut ChatClient) {
+    if client.is_null() {
+        return;
+    }
+    let ChatClient {
+        runtime,
+        connection,
+    } = *Box::from_raw(client);
+    runtime.block_on(connection.close());
+}
+
+/// 当前线程上一次失败的说明；没有失败时返回空指针
+///
+/// 返回的字符串在当前线程下一次调用本库的函数前有效。
+#[no_mangle]
+pub extern "C" fn chat_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+unsafe fn send(
+    client: *mut ChatClient,
+    to: *const c_char,
+    content: *const c_char,
+    kind: MessageKind,
+) -> ChatStatus {
+    let Some(client) = client.as_mut() else {
+        set_last_error("client is null".to_string());
+        return ChatStatus::InvalidArgument;
+    };
+    let (Some(to), Some(content)) = (string_arg(to), string_arg(content)) else {
+        return ChatStatus::InvalidArgument;
+    };
+    let connection = &mut client.connection;
+    let sent = client.runtime.block_on(async {
+        match kind {
+            MessageKind::Action => connection.send_action(&to, &content).await,
+            _ => connection.send(&to, &content).await,
+        }
+    });
+    match sent {
+        Ok(()) => ChatStatus::Ok,
+        Err(e) => {
+            set_last_error(e.to_string());
+            ChatStatus::SendFailed
+        }
+    }
+}
+
+/// 读取字符串参数；为空指针或不是有效的 UTF-8 时记录错误并返回 `None`
+unsafe fn string_arg(arg: *const c_char) -> Option<String> {
+    if arg.is_null() {
+        set_last_error("argument is null".to_string());
+        return None;
+    }
+    match CStr::from_ptr(arg).to_str() {
+        Ok(text) => Some(text.to_string()),
+        Err(e) => {
+            set_last_error(e.to_string());
+            None
+        }
+    }
+}
+
+fn set_last_error(error: String) {
+    let error = c_string(&error);
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// 转为 C 字符串，移除其中的 NUL 字符
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+fn deliver(
+    callback: unsafe extern "C" fn(*mut c_void, *const ChatMessage),
+    user_data: *mut c_void,
+    message: &Message,
+) {
+    let from = c_string(message.from());
+    let to = c_string(message.to());
+    let content = c_string(message.content());
+    let time_stamp = c_string(message.time_stamp());
+    let kind = match message.kind() {
+        MessageKind::Text => ChatMessageKind::Text,
+        MessageKind::Action => ChatMessageKind::Action,
+        MessageKind::Encrypted => ChatMessageKind::Encrypted,
+    };
+    let message = ChatMessage {
+        from: from.as_ptr(),
+        to: to.as_ptr(),
+        content: content.as_ptr(),
+        time_stamp: time_stamp.as_ptr(),
+        kind,
+        id: message.id().unwrap_or(0),
+    };
+    // 调用方在 `chat_client_connect` 的约定中保证回调与 `user_data` 有效
+    unsafe { callback(user_data, &message) };
+}
+
+fn report(
+    callback: unsafe extern "C" fn(*mut c_void, *const c_char, *const c_char),
+    user_data: *mut c_void,
+    error: &ErrorFrame,
+) {
+    let code = serde_json::to_value(error.code)
+        .ok()
+        .and_then(|code| code.as_str().map(c_string))
+        .unwrap_or_default();
+    let message = c_string(&error.message);
+    // 调用方在 `chat_client_connect` 的约定中保证回调与 `user_data` 有效
+    unsafe { callback(user_data, code.as_ptr(), message.as_ptr()) };
+}
+
+#[cfg(test)]
+mod tests {
+    /// 提交的头文件须与 cbindgen 按当前的 C 接口生成的一致
+    #[test]
+    fn committed_header_is_current() {
+        let generated = concat!(env!("OUT_DIR"), "/chat.h");
+        assert!(
+            include_str!(concat!(env!("OUT_DIR"), "/chat.h")) == include_str!("../include/chat.h"),
+            "include/chat.h 已过期，请用 {generated} 覆盖"
+        );
+    }
+}
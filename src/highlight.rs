@@ -0,0 +1,94 @@
+/*!
+# 提及与高亮模块
+
+客户端在显示收到的消息前，用 [`HighlightRules`] 查找需要醒目显示的片段：
+
+- 提及自己的 `@用户名`（大小写不敏感，`@` 后的名字必须完整匹配）
+- 用户配置的关键词（大小写不敏感的子串匹配，适用于中文等无空格分词的文本）
+
+命中的消息会以高亮显示并响铃提示；若开启了 [`HighlightRules::notify_muted`]，
+即使该会话已被 `/mute` 静音，命中的消息仍会显示并提示。
+*/
+
+use std::ops::Range;
+
+/// 高亮规则
+#[derive(Clone, Debug, Default)]
+pub struct HighlightRules {
+    keywords: Vec<String>,
+    notify_muted: bool,
+}
+
+impl HighlightRules {
+    /// 创建只匹配 `@自己` 的规则
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个高亮关键词，空白关键词会被忽略
+    pub fn keyword(mut self, keyword: impl Into<String>) -> Self {
+        let keyword = keyword.into().trim().to_string();
+        if !keyword.is_empty() {
+            self.keywords.push(keyword);
+        }
+        self
+    }
+
+    /// 已静音的会话中出现提及或关键词时是否仍然提示
+    pub fn notify_muted(mut self, notify: bool) -> Self {
+        self.notify_muted = notify;
+        self
+    }
+
+    /// 是否在已静音的会话中提示
+    pub fn notifies_muted(&self) -> bool {
+        self.notify_muted
+    }
+
+    /// 查找 `content` 中需要高亮的片段
+    ///
+    /// # 参数
+    /// - `own_name`: 自己的用户名，用于匹配 `@用户名`
+    /// - `content`: 消息内容
+    ///
+    /// # 返回值
+    /// 按起始位置排序且互不重叠的字节区间
+    pub fn find(&self, own_name: &str, content: &str) -> Vec<Range<usize>> {
+        // 只做 ASCII 大小写折叠，保证折叠前后字节位置一致
+        let haystack = content.to_ascii_lowercase();
+        let mut ranges = Vec::new();
+
+        let mention = format!("@{}", own_name.to_ascii_lowercase());
+        if !own_name.is_empty() {
+            for (start, _) in haystack.match_indices(&mention) {
+                let end = start + mention.len();
+                let whole_word = haystack[end..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| !c.is_alphanumeric() && c != '_');
+                if whole_word {
+                    ranges.push(start..end);
+                }
+            }
+        }
+        for keyword in &self.keywords {
+            let keyword = keyword.to_ascii_lowercase();
+            ranges.extend(
+                haystack
+                    .match_indices(&keyword)
+                    .map(|(start, _)| start..start + keyword.len()),
+            );
+        }
+
+        // 合并重叠区间
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+}
@@ -0,0 +1,104 @@
+/*!
+# 本地聊天记录模块
+
+客户端将收发的消息按会话对象分别追加保存为 JSON Lines 文件
+（`<目录>/<对方用户名>.jsonl`），重启客户端后仍可通过 `/history <用户>`
+查看之前的记录，而不依赖服务器是否开启持久化。
+
+默认目录为 `$XDG_DATA_HOME/async-chat/<用户名>/history`
+（Windows 下为 `%APPDATA%\async-chat\<用户名>\history`）。
+*/
+
+use crate::Message;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 一条本地聊天记录
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// 写入记录的本地时间（消息本身只携带时分秒）
+    pub recorded_at: DateTime<Local>,
+    /// 消息内容
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+/// 按会话对象分文件保存的本地聊天记录
+#[derive(Clone, Debug)]
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    /// 打开（必要时创建）指定目录下的聊天记录
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// 返回用户 `owner` 的默认聊天记录目录
+    ///
+    /// # 返回值
+    /// 若无法确定用户数据目录（未设置相关环境变量），返回 `None`
+    pub fn default_dir(owner: &str) -> Option<PathBuf> {
+        crate::data_dir(owner).map(|dir| dir.join("history"))
+    }
+
+    /// 聊天记录所在目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 追加一条与 `peer` 之间的消息
+    pub fn record(&self, peer: &str, message: &Message) -> io::Result<()> {
+        let entry = HistoryEntry {
+            recorded_at: Local::now(),
+            message: message.clone(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path_for(peer))?
+            .write_all(&line)
+    }
+
+    /// 读取与 `peer` 之间最近的 `limit` 条记录（按时间先后排列）
+    ///
+    /// 无法解析的行会被跳过，以免个别损坏的记录导致整个会话不可读。
+    pub fn recent(&self, peer: &str, limit: usize) -> io::Result<Vec<HistoryEntry>> {
+        let content = match fs::read_to_string(self.path_for(peer)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let entries: Vec<HistoryEntry> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.into_iter().skip(skip).collect())
+    }
+
+    fn path_for(&self, peer: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", file_stem(peer)))
+    }
+}
+
+/// 将用户名转换为安全的文件名：保留字母数字及 `-`、`_`，其余字符按 UTF-8 字节转义为 `%XX`
+pub(crate) fn file_stem(name: &str) -> String {
+    let mut stem = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            stem.push(byte as char);
+        } else {
+            stem.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    stem
+}
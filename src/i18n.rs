@@ -0,0 +1,1813 @@
+/*!
+# 本地化模块
+
+客户端与服务器面向用户的提示文字都通过键值形式的消息目录取得，同一份程序可以为
+不同语言的用户服务。目前内置中文（`zh`，默认）与英文（`en`）两套目录。
+
+- 进程的语言由 `--lang=zh|en` 指定，未指定时依次读取 `LC_ALL`、`LC_MESSAGES`、`LANG`
+  环境变量（例如 `en_US.UTF-8`），无法识别时使用中文；
+- 客户端在握手时上报自己的语言（[`crate::protocol::Hello::lang`]），服务器发给该用户的
+  提示与指令回复使用该语言，服务器自身的日志使用服务器进程的语言。
+
+目录中的文本可以包含 `{名称}` 形式的占位符，由 [`tr!`](crate::tr) 的同名参数替换：
+
+```text
+tr!("client.connected")                              // 当前进程的语言
+tr!(session.lang => "server.unknown_user", user = to) // 用户 bob 不存在 / User bob does not exist
+```
+
+某种语言缺少的条目回退到中文目录，中文目录也没有时原样返回键名。
+*/
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+/// 支持的语言
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Lang {
+    /// 中文
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+impl Lang {
+    /// 解析语言标签，例如 `zh`、`en`、`zh_CN.UTF-8`、`en-US`
+    ///
+    /// # 返回值
+    /// 不支持的语言（包括 `C`、`POSIX`）返回 `None`
+    pub fn parse(tag: &str) -> Option<Lang> {
+        let primary = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Some(Lang::Zh),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// 从 `LC_ALL`、`LC_MESSAGES`、`LANG` 中第一个非空的环境变量读取语言
+    pub fn from_env() -> Option<Lang> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|key| env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Lang::parse(&value))
+    }
+
+    /// 语言标签，在握手帧中上报
+    pub fn tag(self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::En => "en",
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// 当前进程的语言：0 表示尚未确定，否则为 [`Lang`] 的序号加 1
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+/// 设置当前进程的语言
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8 + 1, Ordering::Relaxed);
+}
+
+/// 当前进程的语言；未通过 [`set_lang`] 设置时按环境变量确定
+pub fn lang() -> Lang {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Lang::Zh,
+        2 => Lang::En,
+        _ => {
+            let lang = Lang::from_env().unwrap_or_default();
+            set_lang(lang);
+            lang
+        }
+    }
+}
+
+/// 取出 `key` 在 `lang` 下的文本
+pub fn text(lang: Lang, key: &'static str) -> &'static str {
+    catalog(lang)
+        .get(key)
+        .or_else(|| catalog(Lang::Zh).get(key))
+        .copied()
+        .unwrap_or(key)
+}
+
+/// 取出 `key` 在 `lang` 下的文本，并用 `args` 替换其中的 `{名称}` 占位符
+///
+/// 通常通过 [`tr!`](crate::tr) 调用；没有对应参数的占位符保持原样。
+pub fn format(lang: Lang, key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    let template = text(lang, key);
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                out.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 取出本地化文本：`tr!("键", 名称 = 值, ...)` 使用当前进程的语言，
+/// `tr!(lang => "键", ...)` 使用指定的语言
+#[macro_export]
+macro_rules! tr {
+    ($key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::tr!($crate::i18n::lang() => $key $(, $name = $value)*)
+    };
+    ($lang:expr => $key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::format(
+            $lang,
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),*],
+        )
+    };
+}
+
+fn catalog(lang: Lang) -> &'static HashMap<&'static str, &'static str> {
+    static ZH_MAP: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    static EN_MAP: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    let (map, entries) = match lang {
+        Lang::Zh => (&ZH_MAP, ZH),
+        Lang::En => (&EN_MAP, EN),
+    };
+    map.get_or_init(|| entries.iter().copied().collect())
+}
+
+/// 中文目录
+const ZH: &[(&str, &str)] = &[
+    // 命令行
+    ("cli.mode_required", "请指定运行模式: server、client、conformance、feedbot、admin 或 bench"),
+    ("cli.invalid_mode", "无效的模式，请使用 server、client、conformance、feedbot、admin 或 bench"),
+    ("cli.invalid_codec", "无效的编码 {value}，请使用 json 或 length-prefixed"),
+    ("cli.invalid_lang", "无效的语言: {value}（可选 zh 或 en）"),
+    ("cli.invalid_option", "无效的 --{key}: {value}"),
+    ("cli.starting_server", "启动服务器模式..."),
+    ("cli.starting_client", "启动客户端模式..."),
+    ("cli.starting_feedbot", "启动订阅源机器人..."),
+    ("cli.admin_usage", "用法: admin token <用户名> | admin secret set|delete <条目> [--name=用户名] [--secrets=路径] | admin snapshot <快照路径> --data-dir=目录 [--spool=目录] | admin restore <快照路径> --data-dir=目录 [--spool=目录] [--force] | admin status|drain|audit [条数]|reports [dismiss <编号>]|quarantine [release|discard <编号>]|maintenance [on [HH:MM]|off] --admin-socket=路径"),
+    ("cli.snapshot_written", "已将 {users} 个用户、{messages} 条消息与 {spooled} 条联邦暂存消息写入快照 {path}"),
+    ("cli.snapshot_restored", "已从 {created} 的快照恢复 {users} 个用户、{messages} 条消息与 {spooled} 条联邦暂存消息到 {dir}"),
+    ("cli.admin_failed", "操作失败: {error}"),
+    ("cli.invalid_chaos", "无效的故障注入配置 {value}，应为 drop=概率,truncate=概率,duplicate=概率,delay=概率,max-delay=毫秒,seed=数字，概率之和不超过 1"),
+    ("cli.invalid_bench_option", "无效的压测参数 --{option}={value}"),
+    ("cli.invalid_transport", "无效的接入方式 {value}，请使用 duplex 或 tcp"),
+    ("cli.starting_bench", "开始压测：{clients} 个客户端经 {transport}（{codec}）各发送 {messages} 条 {size} 字节的消息"),
+    ("cli.bench_failed", "压测失败: {error}"),
+    ("cli.baseline_saved", "已将结果保存为基线 {path}"),
+    ("cli.baseline_failed", "无法读写基线 {path}: {error}"),
+    (
+        "cli.feature_disabled",
+        "{what} 需要 {feature} 特性，当前构建未启用（以 --features {feature} 重新编译）",
+    ),
+    ("cli.takeover_requires_admin_socket", "--takeover 需要同时指定旧进程的 --admin-socket"),
+    ("cli.took_over", "旧进程已断开所有用户并退出，开始接受连接"),
+    ("cli.nothing_to_take_over", "管理套接字上没有正在运行的服务器，直接启动"),
+    ("cli.takeover_failed", "接管旧进程失败: {error}"),
+    ("cli.feedbot_usage", "用法: feedbot [服务器地址] --name=机器人名 --token=令牌 --feeds=订阅源,... --to=用户,... [--interval=秒数] [--state=路径]"),
+    ("cli.invalid_interval", "无效的抓取间隔: {value}"),
+    ("cli.feedbot_state_failed", "无法载入订阅源状态文件: {error}"),
+    ("cli.invalid_idle_timeout", "无效的空闲超时: {value}"),
+    ("cli.invalid_slow_grace", "无效的宽限期: {value}"),
+    ("cli.invalid_reconnect_after", "无效的重连等待时间: {value}"),
+    ("cli.invalid_max_connections", "无效的连接数上限: {value}"),
+    ("cli.invalid_fd_reserve", "无效的文件描述符保留数: {value}"),
+    ("cli.invalid_max_message_len", "无效的消息长度上限: {value}"),
+    ("cli.invalid_spam", "无效的反垃圾规则: {value}（格式为 规则=数量,...，规则为 repeat、recipients、links 或 window（秒），例如 repeat=5,links=10）"),
+    ("cli.invalid_spam_throttle", "无效的限流时长: {value}"),
+    ("cli.invalid_max_file_size", "无效的文件大小上限: {value}（字节数，可以带 K、M、G 后缀）"),
+    ("cli.invalid_connect_rate", "无效的连接频率: {value}（格式为 次数/秒数，例如 10/60）"),
+    ("cli.invalid_cooldown", "无效的冷却时长: {value}"),
+    ("cli.invalid_bot_rate", "无效的机器人限流: {value}（格式为 消息数/秒数，例如 60/60）"),
+    ("cli.invalid_telegram_chat", "无效的 Telegram 群组编号: {value}"),
+    ("cli.telegram_token_required", "桥接 Telegram 需要以 --telegram-token、CHAT_TELEGRAM_TOKEN 环境变量或密钥存储中的 telegram-token 条目提供机器人令牌"),
+    ("cli.invalid_xmpp", "无效的 XMPP 网关配置: {value}（格式为 域名=密钥 或 域名=密钥@主机:端口）"),
+    ("cli.invalid_archive_after", "无效的归档天数: {value}（应为正整数）"),
+    ("cli.invalid_archive_to", "无效的归档地址: {value}（格式为 https://端点/桶[/前缀]）"),
+    ("cli.archive_incomplete", "--archive-after 与 --archive-to 须同时指定"),
+    ("cli.archive_credentials_required", "归档到对象存储需要在环境变量 AWS_ACCESS_KEY_ID 与 AWS_SECRET_ACCESS_KEY 或密钥存储的 aws-access-key-id 与 aws-secret-access-key 条目中提供访问密钥"),
+    ("cli.invalid_job", "无效的维护任务配置: {value}（格式为 任务=分 时 日 月 周，任务为 vacuum、expire 或 prune）"),
+    ("cli.invalid_queue_full", "无效的队列已满策略: {value}（可选 wait 或 drop）"),
+    ("cli.invalid_connect_timeout", "无效的连接超时: {value}"),
+    ("cli.invalid_heartbeat", "无效的心跳间隔: {value}"),
+    ("cli.open_data_dir_failed", "无法打开数据目录 {dir}: {error}"),
+    ("cli.storage_keys_failed", "无法读取存储密钥: {error}"),
+    ("cli.storage_reencrypted", "已用当前存储密钥重新加密数据目录 {dir} 中的 {count} 条消息"),
+    ("cli.open_geoip_failed", "无法载入 GeoIP 数据库 {path}: {error}"),
+    ("cli.invalid_admin", "无效的管理员: {value}（格式为 用户名=令牌摘要，由 chat admin token <用户名> 生成）"),
+    ("cli.admin_token_issued", "管理员 {name} 的令牌（只显示这一次，交给该管理员以 --token 出示）:\n  {token}\n服务器参数:\n  --admins={name}={digest}"),
+    ("cli.invalid_peer", "无效的联邦对端: {value}（格式为 名称=密钥 或 名称=密钥@主机:端口）"),
+    ("cli.federation_name_required", "配置 --peers、--upstream 或 --spool 时必须用 --federation=名称 指定本服务器的名称"),
+    ("cli.invalid_upstream", "上游 {value} 不是 --peers 中配置的对端"),
+    ("cli.invalid_vhost", "无效的虚拟主机: {value}（格式为 名称 或 名称=数据目录）"),
+    ("cli.invalid_pow", "无效的工作量证明难度: {value}（可选 1 到 {max}）"),
+    ("cli.vhosts", "虚拟主机: {list}"),
+    ("cli.server_failed", "服务器运行出错: {error}"),
+    ("cli.client_failed", "客户端运行出错: {error}"),
+    ("cli.read_script_failed", "无法读取脚本 {path}: {error}"),
+    ("cli.script_needs_name", "脚本模式需要通过 --name=用户名 指定用户名"),
+    ("cli.script_error", "[错误 {code}] {message}"),
+    ("cli.prompt_name", "请输入用户名 >> "),
+    ("cli.open_history_failed", "无法打开聊天记录目录 {dir}: {error}"),
+    ("cli.load_theme_failed", "无法载入配色文件: {error}"),
+    ("cli.load_aliases_failed", "无法载入别名文件: {error}"),
+    ("cli.invalid_preview", "无效的图片预览方式 {value}，请使用 auto、kitty、sixel、text 或 off"),
+    ("cli.load_identity_failed", "无法载入身份密钥 {path}: {error}"),
+    ("cli.open_secrets_failed", "无法打开密钥存储: {error}"),
+    ("cli.secret_prompt", "{entry} 的值: "),
+    ("cli.secret_saved", "已更新密钥存储中的条目 {entry}"),
+    // 客户端
+    ("client.prompt_recipient", "请输入接收方: "),
+    ("client.prompt_content", "请输入消息内容: "),
+    ("client.connected", "成功连接到服务器"),
+    ("client.connect_failed", "无法连接服务器"),
+    ("client.no_address", "未指定服务器地址"),
+    ("client.not_connected", "尚未连接服务器"),
+    ("client.switching", "正在切换到备用服务器..."),
+    ("client.reconnecting", "正在重新连接服务器... (第 {attempt} 次)"),
+    ("client.reconnect_failed", "重连失败"),
+    ("client.reconnect_exhausted", "重连次数已用尽"),
+    ("client.goodbye", "再见！感谢使用 ChatApp!"),
+    ("client.self_message", "无法发送消息给自己"),
+    ("client.send_failed", "发送消息失败: {error}"),
+    ("client.usage_msg", "用法: /msg <用户> <内容>"),
+    ("client.usage_forward", "用法: /forward <编号> <用户>"),
+    ("client.usage_sendfile", "用法: /sendfile <用户> <路径>"),
+    ("client.usage_accept", "用法: /accept <编号>"),
+    ("client.usage_reject", "用法: /reject <编号>"),
+    ("client.usage_cancel_transfer", "用法: /cancel-transfer <编号>（编号见 /transfers）"),
+    ("client.usage_trust", "用法: /trust <用户>"),
+    ("client.usage_verify", "用法: /verify <用户> <指纹>"),
+    ("client.usage_unverify", "用法: /unverify <用户>"),
+    ("client.usage_e2e", "用法: /e2e <用户> 或 /e2e off <用户>"),
+    ("client.usage_me", "用法: /me <动作>"),
+    ("client.usage_history", "用法: /history <用户> [条数]"),
+    ("client.usage_unmute", "用法: /unmute <用户>"),
+    ("client.roster_joined", "{user} 上线了"),
+    ("client.roster_left", "{user} 下线了"),
+    ("client.roster_status", "{user} 的状态变为 {status}"),
+    ("client.status_online", "在线"),
+    ("client.status_away", "离开"),
+    ("client.status_dnd", "请勿打扰"),
+    ("client.status_other", "未知"),
+    ("client.usage_emoji", "用法: /emoji <查询>"),
+    ("client.no_message", "找不到编号为 #{id} 的消息"),
+    ("client.forwarded", "[转发自 {from}]"),
+    ("client.multiline_quoted", "多行输入，以 \"\"\" 结尾发送"),
+    ("client.multiline_end", "多行输入，单独一行 /end 结束"),
+    ("client.no_aliases", "未定义任何别名"),
+    ("client.history_disabled", "未启用本地聊天记录"),
+    ("client.history_empty", "没有与 {peer} 的聊天记录"),
+    ("client.history_save_failed", "保存聊天记录失败"),
+    ("client.history_read_failed", "读取聊天记录失败"),
+    ("client.usage_export", "用法: /export <用户> [路径]"),
+    ("client.exported", "已导出 {count} 条聊天记录到 {path}"),
+    ("client.export_failed", "导出聊天记录失败"),
+    ("export.title", "与 {peer} 的聊天记录"),
+    ("export.range", "共 {count} 条，{from} 至 {to}"),
+    ("client.no_links", "最近的消息中没有链接"),
+    ("client.no_link", "找不到编号为 {id} 的链接"),
+    ("client.opened", "已打开"),
+    ("client.open_failed", "无法打开链接"),
+    ("client.usage_preview", "用法: /preview <链接编号|图片路径>（链接编号见 /open）"),
+    ("preview.caption", "{name}（{format}，{width}×{height}，{size}）"),
+    ("preview.placeholder", "[图片] {caption}"),
+    ("preview.placeholder_plain", "[图片] {name}（{size}）"),
+    ("preview.not_image", "{name} 不是可以识别的图片"),
+    ("preview.failed", "无法预览 {name}"),
+    ("preview.too_large", "文件超过预览的大小上限 {max}"),
+    ("client.none_muted", "没有已静音的会话"),
+    ("client.muted_list", "已静音: {list}"),
+    ("client.muted", "已静音与 {peer} 的会话"),
+    ("client.unmuted", "已取消静音与 {peer} 的会话"),
+    ("client.not_muted", "与 {peer} 的会话未被静音"),
+    ("client.muted_label", " (已静音)"),
+    ("client.no_emoji", "没有与 {query} 匹配的表情"),
+    ("client.read_timeout", "读取服务器消息超时，连接视为已断开"),
+    ("client.server_closed", "服务器关闭了连接"),
+    ("client.server_shutdown", "{reason}，连接即将断开"),
+    ("client.server_shutdown_reconnect", "{reason}，将在 {after} 后自动重连"),
+    ("client.shutdown_reason_shutdown", "服务器即将关闭"),
+    ("client.shutdown_reason_upgrade", "服务器正在升级"),
+    ("client.shutdown_reason_other", "服务器即将断开连接"),
+    ("client.reconnecting_now", "按服务器的建议重新连接..."),
+    ("client.parse_failed", "解析服务器消息失败"),
+    ("client.read_failed", "读取服务器消息失败"),
+    ("client.heartbeat_timeout", "心跳超时，连接视为已断开"),
+    ("client.pow_solving", "服务器要求完成工作量证明（难度 {difficulty}），正在计算..."),
+    ("client.pow_too_hard", "服务器要求的工作量证明难度 {difficulty} 过高，已放弃"),
+    // 服务器日志
+    ("server.listening", "服务器正在监听 {addr}"),
+    ("server.listening_dual_stack", "同时接受 IPv4 与 IPv6 连接"),
+    ("server.dual_stack_unavailable", "无法监听 IPv6（{error}），改为只监听 IPv4"),
+    ("server.ws_listening", "正在监听 WebSocket 连接 {addr}"),
+    ("server.webhook_listening", "正在监听传入 Webhook {addr}"),
+    ("server.webhook_created", "管理员 {admin} 为用户 {user} 创建了 Webhook {name}"),
+    ("server.webhook_revoked", "Webhook {name} 已被删除"),
+    ("server.webhook_delivered", "来自 {addr} 的 Webhook {name} 向 {user} 发送了消息"),
+    ("server.webhook_refused", "来自 {addr} 的 Webhook 请求令牌无效，已拒绝"),
+    ("server.telegram_started", "正在桥接 Telegram 群组 {chat}"),
+    ("server.telegram_connected", "已连接 Telegram Bot API"),
+    ("server.telegram_poll_failed", "接收 Telegram 消息失败，稍后重试: {error}"),
+    ("server.telegram_send_failed", "向 Telegram 群组发送消息失败: {error}"),
+    ("server.telegram_bad_api", "无效的 Bot API 地址 {api}（须以 http:// 或 https:// 开头）"),
+    ("server.telegram_http_status", "Bot API 返回 HTTP {status}"),
+    ("server.xmpp_linked", "XMPP 网关 {domain} 已连接 {addr}"),
+    ("server.xmpp_closed", "XMPP 服务器关闭了连接"),
+    ("server.xmpp_unlinked", "XMPP 网关连接已断开: {error}"),
+    ("server.xmpp_link_failed", "连接 XMPP 服务器 {addr} 失败: {error}"),
+    ("server.xmpp_protocol", "XMPP 服务器发来了无法识别的数据"),
+    ("server.xmpp_stream_error", "XMPP 服务器报告错误: {condition}"),
+    ("server.xmpp_too_large", "XMPP 消息节过大"),
+    ("server.xmpp_message", "Jabber 用户 {from} 向 {user} 发送了消息"),
+    ("server.xmpp_subscribed", "Jabber 用户 {contact} 订阅了 {user} 的在线状态"),
+    ("server.ctrl_c", "接收到 Ctrl+C，正在关闭服务器..."),
+    ("server.sigterm", "接收到 SIGTERM，正在关闭服务器..."),
+    ("server.systemd_listening", "沿用 systemd 传入的监听套接字 {addr}"),
+    ("server.systemd_no_ws", "配置了 WebSocket，但 systemd 只传入了一个监听套接字"),
+    ("server.systemd_status", "在线用户 {users} 人"),
+    ("server.systemd_status_refusing", "在线用户 {users} 人，正在拒绝新连接"),
+    ("server.connection_limit", "连接数已达上限，拒绝来自 {addr} 的连接"),
+    ("server.fd_exhausted", "空闲的文件描述符不足，拒绝来自 {addr} 的连接"),
+    ("server.fd_limit_low", "⚠️ 文件描述符上限 {limit} 容纳不下 {max} 个连接与保留的 {reserve} 个文件描述符，请调高 ulimit -n"),
+    ("server.refusing_started", "⚠️ 服务器开始拒绝新连接：{reason}"),
+    ("server.refusing_stopped", "服务器已恢复接受新连接，期间拒绝 {count} 次"),
+    ("server.overload_connection_limit", "连接数已达上限"),
+    ("server.overload_fd_headroom", "空闲的文件描述符不足"),
+    ("server.overload_accept_failed", "接受连接失败"),
+    ("server.new_connection", "接收到来自 {addr} 的新连接"),
+    ("server.new_ws_connection", "接收到来自 {addr} 的新 WebSocket 连接"),
+    ("server.ip_refused", "拒绝来自 {addr} 的连接: {reason}"),
+    ("server.vhost_selected", "来自 {addr} 的连接进入虚拟主机 {host}"),
+    ("server.invite_redeemed", "用户 {user} 使用邀请码 {code} 注册（已使用 {used}/{max} 次）"),
+    ("server.invite_refused", "来自 {addr} 的新用户 {user} 未出示有效的邀请码，已拒绝"),
+    ("server.bot_refused", "来自 {addr} 的 {user} 令牌认证失败，已拒绝"),
+    ("server.bot_authenticated", "机器人 {user} 已通过令牌认证"),
+    ("server.admin_authenticated", "管理员 {user} 已通过令牌认证"),
+    ("server.bot_created", "管理员 {admin} 创建了机器人 {name}（权限: {scopes}）"),
+    ("server.bot_revoked", "机器人 {name} 的令牌已被撤销"),
+    ("server.command_registered", "机器人 {bot} 注册了指令 {command}"),
+    ("server.not_a_bot", "{user} 不是机器人账号，不能以令牌登录"),
+    ("server.bot_token_required", "{user} 是机器人账号，须出示令牌登录"),
+    ("server.bot_token_invalid", "机器人 {user} 的令牌无效或已被撤销"),
+    ("server.admin_token_required", "{user} 是管理员账号，须出示管理员令牌登录"),
+    ("server.admin_token_invalid", "管理员 {user} 的令牌无效"),
+    ("server.bot_scope_denied", "机器人没有 {scope} 权限"),
+    ("server.bot_rate_limited", "发送过于频繁: 机器人每 {window} 最多发送 {max} 条消息"),
+    ("server.pow_refused", "来自 {addr} 的新用户 {user} 未通过工作量证明，已拒绝"),
+    ("server.pow_clamped", "工作量证明难度 {difficulty} 超过上限 {max}，已按 {max} 执行"),
+    ("server.identity_refused", "来自 {addr} 的 {user} 未能证明持有该用户名绑定的身份密钥，已拒绝"),
+    ("server.identity_bound", "用户名 {user} 已绑定身份公钥 {fingerprint}"),
+    ("server.maintenance_refused", "服务器处于维护模式，已拒绝来自 {addr} 的用户 {user}"),
+    ("server.name_refused", "用户名 {user} 不可用，已拒绝来自 {addr} 的连接"),
+    ("server.plugin_refused", "插件拒绝了来自 {addr} 的连接: {reason}"),
+    ("server.peer_linked", "已建立到联邦对端 {server} ({addr}) 的链路"),
+    ("server.peer_unlinked", "到联邦对端 {server} 的链路已断开: {error}"),
+    ("server.peer_link_failed", "无法建立到联邦对端 {server} ({addr}) 的链路: {error}"),
+    ("server.peer_closed", "对端关闭了连接"),
+    ("server.peer_timeout", "连接超时"),
+    ("server.peer_protocol", "对端不是联邦服务器"),
+    ("server.peer_auth_failed", "联邦认证失败"),
+    ("server.peer_refused", "拒绝来自 {addr} 的联邦连接（自称 {server}）: 未配置或认证失败"),
+    ("server.peer_accepted", "联邦对端 {server} 从 {addr} 连入"),
+    ("server.peer_left", "联邦对端 {server} 的入站链路已断开"),
+    ("server.relay_received", "收到联邦对端 {server} 转发的消息: {from} → {to}"),
+    ("server.relay_spooled", "与 {server} 的链路中断，已暂存 {from} 的转发消息"),
+    ("server.transfer_offered", "{from} 向 {to} 提议发送文件 {name}（{size}）"),
+    ("server.transfer_accepted", "{to} 接受了 {from} 发送的文件 {name}"),
+    ("server.transfer_resumed", "{to} 从 {offset} 处续传 {from} 发送的文件 {name}"),
+    ("server.transfer_rejected", "{to} 拒绝了 {from} 发送的文件 {name}"),
+    ("server.transfer_done", "{to} 已收到 {from} 发送的文件 {name}（经服务器中转 {relayed}）"),
+    ("server.transfer_cancelled", "{user} 取消了与 {peer} 之间的文件传输 {name}"),
+    ("server.transfer_too_large", "文件 {name}（{size}）超过了服务器允许的大小上限 {max}"),
+    ("server.transfer_extension", "服务器不允许传输 .{ext} 文件"),
+    ("server.transfer_blocked", "已拒绝 {from} 发给 {to} 的文件 {name}: {reason}"),
+    ("server.transfer_scanning", "已收齐 {from} 发给 {to} 的文件 {name}，正在扫描"),
+    ("server.transfer_quarantined", "{from} 发给 {to} 的文件 {name} 未通过扫描，已隔离: {path}"),
+    ("server.transfer_scan_failed", "无法扫描暂存的文件 {path}: {error}"),
+    ("server.transfer_infected", "文件 {name} 未通过服务器的安全扫描，传输已取消"),
+    ("server.transfer_unscanned", "服务器无法扫描文件 {name}，传输已取消"),
+    ("server.spool_flushed", "已向联邦对端 {server} 转发暂存的 {count} 条消息"),
+    ("server.spool_full", "联邦对端 {server} 的暂存已满，不再暂存新消息"),
+    ("server.spool_failed", "读写联邦对端 {server} 的暂存失败: {error}"),
+    ("server.relay_dropped", "丢弃联邦对端 {server} 转发的消息（{from} → {to}）: {reason}"),
+    ("federation.unknown_peer", "{server} 不是本服务器的联邦对端"),
+    ("federation.unknown_host", "未知的服务器 {host}（本服务器未加入联邦）"),
+    ("federation.spooled", "与 {server} 的联邦链路暂时中断，消息已暂存，恢复后转发"),
+    ("federation.unavailable", "与 {server} 的联邦链路未建立，消息未能发出"),
+    ("federation.spoofed", "来源与链路不符"),
+    ("federation.loop", "转发成环或经过的服务器过多"),
+    ("federation.bad_address", "收件地址无效"),
+    ("transfer.offline", "{user} 不在线，无法发送文件"),
+    ("transfer.unsupported", "{user} 的客户端不支持文件传输"),
+    ("transfer.too_many", "同时进行的文件传输已达上限（{max} 个），请等待已有的传输结束"),
+    ("transfer.peer_left", "{user} 已离线"),
+    ("transfer.not_a_file", "{path} 不是文件"),
+    ("transfer.read_failed", "无法读取 {path}: {error}"),
+    ("transfer.save_failed", "无法保存 {path}: {error}"),
+    ("transfer.offered", "已向 {peer} 提议发送 {name}（{size}），等待对方接受"),
+    ("transfer.incoming", "{peer} 想发送文件 {name}（{size}），输入 /accept {number} 接收或 /reject {number} 拒绝"),
+    ("transfer.incoming_resume", "{peer} 想继续发送文件 {name}（{size}，已接收 {received}），输入 /accept {number} 续传或 /reject {number} 拒绝"),
+    ("transfer.no_offer", "没有编号为 {number} 的待接收文件"),
+    ("transfer.you_rejected", "已拒绝 {peer} 发送的 {name}"),
+    ("transfer.rejected", "{peer} 拒绝接收 {name}"),
+    ("transfer.accepted", "{peer} 接受了 {name}，正在尝试直连"),
+    ("transfer.receiving", "正在接收 {peer} 发送的 {name}"),
+    ("transfer.receiving_resumed", "从 {received} 处继续接收 {peer} 发送的 {name}"),
+    ("transfer.resumed", "{peer} 从 {offset} 处继续接收 {name}，正在尝试直连"),
+    ("transfer.resuming", "{peer} 在线，重新提议发送之前中断的 {name}"),
+    ("transfer.interrupted", "与 {peer} 之间的文件传输 {name} 因断线中断，已传输的部分保留，重新连接后续传"),
+    ("transfer.direct_failed", "直连传输中断: {error}"),
+    ("transfer.sent_direct", "已通过直连把 {name} 发给 {peer}，等待对方确认"),
+    ("transfer.relaying", "无法与 {peer} 直连，{name} 改为经服务器中转"),
+    ("transfer.delivered", "{peer} 已收到 {name}"),
+    ("transfer.saved", "已收到 {peer} 发送的 {name}，保存为 {path}"),
+    ("transfer.checksum_mismatch", "文件校验失败"),
+    ("transfer.cancelled", "与 {peer} 之间的文件传输 {name} 已取消"),
+    ("transfer.cancelled_reason", "与 {peer} 之间的文件传输 {name} 已取消: {reason}"),
+    ("transfer.outgoing", "↑ {name} → {peer}"),
+    ("transfer.incoming_file", "↓ {name} ← {peer}"),
+    ("transfer.progress", "#{number} {file} {bar} {percent}% {done}/{size} {rate}/s 剩余 {eta}"),
+    ("transfer.progress_waiting", "#{number} {file} 准备中"),
+    ("transfer.list", "文件传输（{count} 个）:"),
+    ("transfer.list_offer", "#{number} {file}（{size}）等待你接受"),
+    ("transfer.list_empty", "没有待接收或进行中的文件传输"),
+    ("transfer.list_interrupted", "{file} 已中断，{peer} 在线后续传"),
+    ("transfer.no_transfer", "没有编号为 {number} 的进行中的文件传输"),
+    ("transfer.already_complete", "#{number} 的内容已经传输完毕，正在校验，无法取消"),
+    ("identity.bad_key_file", "身份密钥文件 {path} 格式无效"),
+    ("identity.bad_key_entry", "密钥存储中的身份密钥 {entry} 格式无效"),
+    ("identity.own", "你的身份公钥指纹: {fingerprint}"),
+    ("identity.disabled", "未启用身份密钥，发出的消息不带签名"),
+    ("identity.peer", "{user} 的身份公钥指纹: {fingerprint}"),
+    ("identity.peer_verified", "{user} 的身份公钥指纹: {fingerprint} ✓ 已验证"),
+    ("identity.unknown", "尚未收到 {user} 签名的消息"),
+    ("identity.first_seen", "已记住 {user} 的身份公钥，指纹 {fingerprint}；与对方带外核对一致后可通过 /verify {user} <指纹> 标记为已验证"),
+    ("identity.verified_changed", "严重警告: 已验证的联系人 {user} 的身份公钥已更换（新指纹 {fingerprint}），在重新核对之前不要相信以该身份发来的任何内容；核实后可通过 /trust {user} 接受，再以 /verify 重新验证"),
+    ("identity.changed", "警告: {user} 的身份公钥已更换（新指纹 {fingerprint}），可能是对方换了设备，也可能有人冒充；核实后可通过 /trust {user} 接受"),
+    ("identity.invalid", "警告: 来自 {user} 的消息签名无效，内容可能被篡改"),
+    ("identity.missing", "警告: 来自 {user} 的消息没有签名，而此前该用户的消息都有签名"),
+    ("identity.replayed", "警告: 已丢弃来自 {user} 的重复消息（签名随机数已出现过），可能是网络上的重放"),
+    ("identity.stale", "警告: 已丢弃来自 {user} 的过期消息（签名时间超出重放窗口），可能是网络上的重放"),
+    ("identity.trusted", "已接受 {user} 的新身份公钥，指纹 {fingerprint}"),
+    ("identity.not_changed", "{user} 的身份公钥没有更换"),
+    ("identity.verified", "已将 {user} 的身份公钥标记为已验证，此后其签名有效的消息显示 ✓"),
+    ("identity.verify_mismatch", "指纹与 {user} 的身份公钥不符（记住的指纹为 {fingerprint}），未标记为已验证"),
+    ("identity.unverified", "已取消 {user} 的已验证标记"),
+    ("identity.not_verified", "{user} 的身份公钥尚未验证"),
+    ("identity.save_failed", "保存已知公钥失败"),
+    ("e2e.unavailable", "无法与 {user} 建立端到端加密会话：对方不在线或不支持"),
+    ("e2e.no_identity", "端到端加密需要身份密钥（见 --identity）"),
+    ("e2e.requested", "已向 {user} 发起端到端加密会话，等待对方应答"),
+    ("e2e.already", "与 {user} 的端到端加密会话已经建立"),
+    ("e2e.started_by_peer", "{user} 发起了端到端加密会话"),
+    ("e2e.established", "与 {user} 的端到端加密会话已建立，此后的消息只有双方可以解密"),
+    ("e2e.stopped", "已结束与 {user} 的端到端加密会话，此后的消息不再加密"),
+    ("e2e.not_active", "与 {user} 没有端到端加密会话"),
+    ("e2e.none", "没有端到端加密会话"),
+    ("e2e.session_ready", "  {user}: 已建立，已交换 {count} 次临时密钥"),
+    ("e2e.session_pending", "  {user}: 等待对方应答"),
+    ("e2e.pending", "与 {user} 的端到端加密会话尚未建立，消息未发送；请稍后再试"),
+    ("e2e.bad_signature", "警告: {user} 发来的密钥交换签名无效，已忽略"),
+    ("e2e.key_changed", "警告: {user} 的身份公钥已更换，拒绝与其交换加密密钥；核实后可通过 /trust {user} 接受"),
+    ("e2e.decrypt_failed", "无法解密来自 {user} 的加密消息（会话已失效），可通过 /e2e {user} 重新建立会话"),
+    ("server.unknown_vhost", "来自 {addr} 的连接请求了不存在的虚拟主机 {host}，已断开"),
+    ("server.socket_options_failed", "设置来自 {addr} 的连接的套接字选项失败: {error}"),
+    ("server.connection_error", "处理来自 {addr} 的连接时出错: {error}"),
+    ("server.accept_failed", "接受连接失败: {error}，{delay} 毫秒后重试"),
+    ("server.force_close", "仍有 {count} 个连接未能及时关闭，强制结束"),
+    ("server.exited", "所有用户连接已释放，服务器退出。"),
+    ("server.handler_panic", "处理来自 {addr} 的连接的任务发生 panic（累计 {count} 次）: {message}"),
+    ("server.unknown_addr", "未知地址"),
+    ("server.no_panic_message", "（无文本信息）"),
+    ("server.registered", "用户 {user} 已注册 (编码: {codec})"),
+    ("server.write_failed", "向用户 {user} 写入失败，已断开连接: {error}"),
+    ("server.notices_missed", "用户 {user} 错过了 {count} 条系统通知"),
+    ("server.write_stalled", "用户 {user} 的写入超过 {grace} 未完成，断开连接"),
+    ("server.slow", "用户 {user} 接收消息过慢"),
+    ("server.slow_disconnect", "用户 {user} 接收消息过慢，断开连接"),
+    ("server.slow_timeout", "用户 {user} 接收消息过慢已超过 {grace}，断开连接"),
+    ("server.send_failed", "发送消息失败: {error}"),
+    ("server.disconnected", "用户 {user} 断开连接（在线 {online}，{stats}）"),
+    ("server.left", "用户 {user} 已退出（在线 {online}，{stats}）"),
+    ("server.save_user_failed", "保存用户 {user} 的数据失败: {error}"),
+    ("server.save_room_failed", "保存聊天室 {room} 的设置失败: {error}"),
+    ("server.room_created", "{user} 创建了聊天室 {room}"),
+    ("server.room_invite_redeemed", "{user} 使用邀请码 {code} 加入了聊天室 {room}（已使用 {used}/{max} 次）"),
+    ("server.room_encrypted", "端到端加密的消息不能发到聊天室"),
+    ("server.room_not_member", "你不是聊天室 {room} 的成员，先用 /join {room} 加入"),
+    ("server.rooms_rejoined", "已重新加入 {count} 个有新消息的聊天室:"),
+    ("server.rooms_rejoined_entry", "{room}：离线期间 {count} 条新消息，其中 {mentions} 条提到你"),
+    ("server.parse_failed", "解析 JSON 消息失败: {error}"),
+    ("server.duplicate", "忽略 {user} 重复发送的消息 (编号 {id})"),
+    ("server.sanitized", "已清除 {user} 发给 {to} 的消息中的终端控制字符"),
+    ("server.message_log", "[{time}] {from} 发送消息给 {to}: {content}"),
+    ("server.log_write_failed", "写入消息日志失败: {error}"),
+    ("server.log_read_failed", "读取消息日志失败: {error}"),
+    ("server.replayed", "为用户 {user} 补发 {count} 条消息"),
+    (
+        "server.history_truncated",
+        "部分离线消息已不在服务器上，只能补发编号 {from} 起的 {count} 条",
+    ),
+    ("server.idle", "用户 {user} ({addr}) 超过 {secs} 秒未活动，断开连接"),
+    ("server.handler_aborted", "用户 {user} ({addr}) 的连接处理任务异常结束，已移出在线用户表（{stats}）"),
+    ("server.dead_letter", "消息进入死信队列 #{id}: {from} -> {to} ({reason})"),
+    ("server.audit", "[审计] {entry}"),
+    ("server.audit_open_failed", "无法打开审计日志 {path}: {error}，事件只保存在内存中"),
+    ("server.audit_write_failed", "写入审计日志失败: {error}"),
+    // 服务器发给用户的提示
+    ("server.last_seen", "最后在线: {elapsed}前"),
+    ("server.last_seen_logged_out", "最后在线: {elapsed}前，已主动退出"),
+    ("server.too_large", "消息长度 {len} 超过上限，连接已断开"),
+    ("server.content_too_large", "消息内容 {len} 字节，超过上限 {max} 字节，未发送"),
+    ("server.content_too_large_log", "拒绝 {user} 发送的过长消息（{len} 字节）"),
+    ("server.spam_throttled", "发送过于频繁，消息未发送，请在{remaining}后再试"),
+    ("server.spam_alert", "[{action}] {user}: {rule}"),
+    ("server.undelivered", "消息未能送达 {user}: {reason}"),
+    ("server.auto_reply", "[自动回复] {text}"),
+    ("server.dnd_reply", "我正处于免打扰状态，消息将稍后送达"),
+    ("server.offline", "用户 {user} 不在线"),
+    ("server.offline_since", "用户 {user} 不在线（{last_seen}）"),
+    ("server.unknown_user", "用户 {user} 不存在"),
+    ("server.unknown_vhost_reply", "虚拟主机 {host} 不存在"),
+    ("server.invite_required", "本服务器仅限邀请注册，请使用邀请码登录"),
+    ("server.maintenance", "服务器正在维护，暂不接受新的连接，请稍后再试"),
+    ("server.plugin_denied", "服务器拒绝了连接: {reason}"),
+    ("server.maintenance_until", "服务器正在维护，预计 {time} 恢复，请届时再连接"),
+    ("server.ip_too_many", "来自同一地址的连接数已达上限（{limit} 个）"),
+    ("server.ip_cooldown", "连接过于频繁，请在 {remaining}后重试"),
+    ("server.pow_required", "本服务器要求新用户完成工作量证明，请升级客户端"),
+    ("server.pow_failed", "工作量证明未通过或超时"),
+    ("server.identity_failed", "身份证明未通过或超时"),
+    ("server.identity_required", "用户名 {user} 已绑定身份密钥，请使用注册时的身份密钥登录"),
+    ("server.name_reserved", "该用户名不可用：不能为空或 \"Server\"，不能以 # 开头，也不能含有控制字符或 /"),
+    ("server.invite_unknown", "邀请码无效、已用完或已被撤销"),
+    ("server.invite_expired", "邀请码已过期"),
+    ("server.partner_lost", "与 {user} 的连接已中断，最近发给对方的消息可能未送达"),
+    ("server.partner_left", "{user} 已退出聊天室"),
+    ("server.partner_left_message", "{user} 已退出聊天室：{message}"),
+    ("server.idle_notice", "由于超过 {secs} 秒未活动，连接已断开"),
+    ("server.slow_notice", "你的连接接收消息过慢，若 {grace} 内仍未恢复将被断开"),
+    ("server.shutdown_notice", "服务器即将关闭，所有用户已断开连接"),
+    ("server.upgrade_notice", "服务器正在升级，连接即将断开，客户端会自动重连"),
+    ("server.admin_socket_listening", "正在监听管理套接字 {path}"),
+    ("server.admin_socket_in_use", "已有服务器在监听管理套接字 {path}，如需接替它请加 --takeover"),
+    ("server.admin_socket_error", "处理管理命令失败: {error}"),
+    ("server.admin_socket_unsupported", "管理套接字仅支持 Unix 系统"),
+    ("server.admin_status", "进程 {pid}，在线用户 {users} 人"),
+    ("server.admin_accept_stats", "接受失败 {errors} 次，拒绝连接 {refused} 次{refusing}"),
+    ("server.refusing_now", "，正在拒绝新连接"),
+    ("server.admin_unknown_command", "未知的管理命令: {command}"),
+    ("server.chaos_enabled", "⚠ 故障注入已启用（仅用于测试）：丢弃 {drop}、截断 {truncate}、重复 {duplicate}、延迟 {delay}（最长 {max_delay} 毫秒）"),
+    ("server.chaos_dropped", "[故障注入] 丢弃了发给 {user} 的一帧"),
+    ("server.chaos_truncated", "[故障注入] 发给 {user} 的一帧只写出 {kept}/{len} 字节后断开连接"),
+    ("server.chaos_duplicated", "[故障注入] 重复写出发给 {user} 的一帧"),
+    ("server.chaos_disconnect", "故障注入截断了帧"),
+    ("server.draining", "收到 drain 命令，停止接受新连接，断开所有用户后退出..."),
+    ("server.announcement", "[公告] {user}: {text}"),
+    ("reason.queue_full", "接收方队列已满"),
+    ("reason.disconnected", "接收方连接已断开"),
+    ("reason.slow_consumer", "接收方处理过慢，已被断开"),
+    ("reason.too_large", "帧长度 {len} 超过上限"),
+    ("reason.held_overflow", "接收方免打扰期间暂存的消息过多"),
+    ("receipt.sent", "已发送"),
+    ("receipt.queued", "离线暂存"),
+    ("receipt.delivered", "已送达"),
+    ("receipt.read", "已读"),
+    ("trace.received", "收到"),
+    ("trace.queued", "暂存等待补发"),
+    ("trace.routed", "进入路由队列"),
+    ("trace.relayed", "经联邦转发"),
+    ("trace.spooled", "写入联邦暂存"),
+    ("trace.delivered", "已写入接收者的连接"),
+    ("audit.spam_flagged", "反垃圾告警"),
+    ("audit.spam_throttled", "反垃圾限流"),
+    ("audit.reported", "举报"),
+    ("audit.report_dismissed", "举报已处理"),
+    ("audit.quarantined", "隔离消息"),
+    ("audit.quarantine_released", "放行隔离消息"),
+    ("audit.quarantine_discarded", "丢弃隔离消息"),
+    ("audit.maintenance_started", "开启维护模式"),
+    ("audit.maintenance_ended", "关闭维护模式"),
+    ("audit.transfer_blocked", "拒绝文件传输"),
+    ("audit.transfer_quarantined", "隔离文件"),
+    ("maintenance.audit_on", "管理员 {admin} 开启了维护模式{back}"),
+    ("maintenance.audit_off", "管理员 {admin} 关闭了维护模式"),
+    ("moderation.hold_reported", "有待处理的举报 #{id}"),
+    ("moderation.held", "#{id} [{time}] {from} -> {to}: {content}（{cause}）"),
+    ("moderation.hold_alert", "[隔离 #{id}] {from} 发给 {to} 的消息待审核: {cause}（/quarantine 查看）"),
+    ("moderation.audit_hold", "#{id} 发给 {to}: {cause}"),
+    ("moderation.audit_release", "#{id} 发给 {to}，由 {admin} 放行"),
+    ("moderation.audit_discard", "#{id} 发给 {to}，由 {admin} 丢弃"),
+    ("server.quarantined", "发给 {user} 的消息正在等待管理员审核"),
+    ("moderation.report", "#{id} [{time}] {reporter} 举报 {reported}: {reason}"),
+    ("moderation.alert", "[举报 #{id}] {reporter} 举报了 {reported}: {reason}（/reports 查看）"),
+    ("moderation.audit_report", "#{id} 由 {reporter} 提交: {reason}"),
+    ("moderation.audit_dismiss", "#{id} 由 {admin} 处理完毕"),
+    ("spam.repeated", "相同的消息发送了 {count} 次"),
+    ("spam.recipients", "发给了 {count} 个不同的接收者"),
+    ("spam.links", "消息中共有 {count} 个链接"),
+    ("job.vacuumed", "丢弃 {dropped} 行无法读取的记录，日志减少 {bytes} 字节"),
+    ("job.expired", "清除 {invites} 个过期邀请码、{letters} 条过期死信"),
+    ("job.pruned", "删除 {count} 条超出补发窗口的消息"),
+    ("stats.summary", "收 {in_count} 条/{in_bytes}，发 {out_count} 条/{out_bytes}，解析错误 {errors}，空闲 {idle}"),
+    ("stats.slow", "，接收过慢已 {slow}"),
+    ("server.telegram_encrypted", "端到端加密的消息不能发到 Telegram 群组"),
+    ("server.telegram_busy", "发往 Telegram 的消息积压过多，请稍后再试"),
+    ("server.xmpp_encrypted", "端到端加密的消息不能发给 Jabber 用户"),
+    ("server.xmpp_busy", "发往 XMPP 服务器的消息积压过多，请稍后再试"),
+    ("server.xmpp_offline", "XMPP 网关当前未连接，消息未能发给 {user}"),
+    ("server.archive_started", "历史消息归档已启用：写入超过 {days} 天的消息将上传到 {location}"),
+    ("server.archived", "已归档 {count} 条消息（编号 {first} 至 {last}，压缩后 {size} 字节）到 {key}"),
+    ("server.archive_failed", "归档历史消息失败，将在下次检查时重试: {error}"),
+    ("server.archive_http_status", "对象存储返回 HTTP {status} {reason}"),
+    ("server.jobs_started", "已启用 {count} 个维护任务: {jobs}"),
+    ("server.job_finished", "维护任务 {job} 完成（耗时 {elapsed} 毫秒）: {result}"),
+    ("server.job_failed", "维护任务 {job} 失败: {error}"),
+    // 服务器指令
+    ("cmd.admin_only", "权限不足：仅管理员可以使用该指令"),
+    ("cmd.unknown", "未知指令 {command}"),
+    ("cmd.list_empty", "当前无其他在线用户"),
+    ("cmd.list", "当前在线用户 (共{count}人):"),
+    ("cmd.list_bot", "{user} [机器人]"),
+    ("cmd.peers_disabled", "本服务器未加入联邦"),
+    ("cmd.peers", "联邦对端（本服务器为 {server}）:"),
+    ("cmd.peer", "{server}: 出站链路{outbound}，入站链路{inbound}，在线: {users}"),
+    ("cmd.peer_up", "已连接"),
+    ("cmd.peer_down", "未连接"),
+    ("cmd.peer_nobody", "无"),
+    ("cmd.peer_spooled", "，暂存待转发 {count} 条"),
+    ("cmd.usage_whois", "用法: /whois <用户>"),
+    ("cmd.whois", "用户 {user} 的资料:"),
+    ("cmd.whois_display_name", "显示名: {name}"),
+    ("cmd.whois_avatar", "头像: {avatar}"),
+    ("cmd.whois_pronouns", "代词: {pronouns}"),
+    ("cmd.whois_bio", "简介: {bio}"),
+    ("cmd.whois_status", "状态: {status}"),
+    ("cmd.whois_idle", "空闲: {idle}"),
+    ("cmd.whois_client", "客户端: {client}"),
+    ("cmd.whois_origin", "来源: {origin}"),
+    ("cmd.whois_bot", "账号类型: 机器人"),
+    ("cmd.whois_bot_admin", "账号类型: 机器人（权限: {scopes}，由 {admin} 创建）"),
+    ("cmd.status_away", "离开（{text}）"),
+    ("cmd.status_dnd", "免打扰"),
+    ("cmd.status_online", "在线"),
+    ("cmd.status_offline", "离线"),
+    ("cmd.status_offline_since", "离线（{last_seen}）"),
+    ("cmd.display_name_cleared", "已清除显示名"),
+    ("cmd.display_name_set", "显示名已设置为 {name}"),
+    ("cmd.usage_profile", "用法: /profile <name|avatar|pronouns|bio> [<内容>]（内容为空时清除）"),
+    ("cmd.profile_set", "{field} 已设置为 {value}"),
+    ("cmd.profile_cleared", "已清除 {field}"),
+    ("cmd.profile_too_long", "{field} 不能超过 {max} 个字符"),
+    ("cmd.profile_bad_avatar", "头像应为一个表情（不超过 {chars} 个字符）或以 http:// 或 https:// 开头的图片链接（不超过 {len} 字节）"),
+    ("cmd.profile_control", "{field} 不能包含换行或控制字符"),
+    ("cmd.privacy", "当前隐私设置:"),
+    ("cmd.privacy_public", "公开"),
+    ("cmd.privacy_hidden", "隐藏"),
+    ("cmd.privacy_updated", "隐私设置 {field} 已更新为 {value}"),
+    ("cmd.privacy_unknown", "未知的隐私设置项 {field}"),
+    ("cmd.usage_privacy", "用法: /privacy [<status|idle|client> <on|off>]"),
+    ("cmd.notify", "通知偏好（各设备共用）:"),
+    ("cmd.notify_none", "无"),
+    ("cmd.notify_muted", "静音的会话: {list}"),
+    ("cmd.notify_keywords", "关键词: {list}"),
+    ("cmd.notify_quiet", "免打扰时段: {hours}"),
+    ("cmd.notify_muted_set", "已静音 {conversation}：其消息不再提示，提及你或命中关键词时除外"),
+    ("cmd.notify_already_muted", "{conversation} 已经静音"),
+    ("cmd.notify_unmuted", "已取消静音 {conversation}"),
+    ("cmd.notify_not_muted", "{conversation} 没有静音"),
+    ("cmd.notify_keyword_added", "已添加关键词 {keyword}"),
+    ("cmd.notify_keyword_removed", "已删除关键词 {keyword}"),
+    ("cmd.notify_keyword_unchanged", "关键词 {keyword} 没有变化"),
+    ("cmd.notify_quiet_set", "免打扰时段已设为 {hours}（服务器时间），期间的消息不提示"),
+    ("cmd.notify_quiet_cleared", "已取消免打扰时段"),
+    ("cmd.notify_invalid_quiet", "无效的免打扰时段: {value}（格式为 HH:MM-HH:MM，例如 22:00-07:00）"),
+    ("cmd.receipts", "发给 {user} 的最近 {count} 条消息:"),
+    ("cmd.receipt", "#{id} [{time}] {preview} — {state}（{updated}）"),
+    ("cmd.receipt_encrypted", "[加密消息]"),
+    ("cmd.receipts_empty", "最近没有发给 {user} 的消息的回执"),
+    ("cmd.usage_receipts", "用法: /receipts <用户> [<条数>]"),
+    ("cmd.usage_trace", "用法: /trace [<编号>]"),
+    ("cmd.trace", "消息 #{id}（发给 {to}）的路由记录:"),
+    ("cmd.trace_hop", "[{time}] +{elapsed} ms {node}: {event}"),
+    ("cmd.trace_unknown", "没有你发出的消息 #{id} 的路由记录"),
+    ("cmd.traces", "最近被追踪的 {count} 条消息:"),
+    ("cmd.trace_item", "#{id} 发给 {to} [{time}] — {event}（{node}）"),
+    ("cmd.traces_empty", "最近没有被追踪的消息"),
+    ("cmd.trace_disabled", "服务器未开启路由追踪"),
+    ("cmd.usage_report", "用法: /report <用户> <理由>"),
+    ("cmd.report_self", "不能举报自己"),
+    ("cmd.report_unknown", "用户 {user} 不存在"),
+    ("cmd.reported", "已举报 {user}（#{id}，附带 {context} 条往来消息），管理员会尽快处理"),
+    ("cmd.usage_notify", "用法: /notify [mute <会话>|unmute <会话>|keyword add <关键词>|keyword remove <关键词>|quiet <HH:MM-HH:MM>|quiet off]"),
+    ("cmd.away_cleared", "已取消离开状态"),
+    ("cmd.away_set", "已设置离开状态，自动回复: {text}"),
+    ("cmd.usage_dnd", "用法: /dnd [on|off]"),
+    ("cmd.dnd_on", "已开启免打扰，消息将暂存到关闭免打扰后送达"),
+    ("cmd.dnd_off", "已关闭免打扰，补发 {count} 条消息"),
+    ("cmd.stats_denied", "权限不足：只能查看自己的统计"),
+    ("cmd.stats", "用户 {user} 的连接统计:"),
+    ("cmd.stats_addr", "地址: {addr}"),
+    ("cmd.stats_panics", "服务器: 连接处理任务 panic {count} 次"),
+    ("cmd.stats_accept", "服务器: 接受失败 {errors} 次，拒绝连接 {refused} 次{refusing}"),
+    ("cmd.usage_announce", "用法: /announce <公告>"),
+    ("cmd.announced", "公告已发送给 {count} 个在线连接"),
+    ("cmd.deadletter_empty", "死信队列为空"),
+    ("cmd.deadletter", "死信队列 (共{count}条):"),
+    ("cmd.deadletter_missing", "死信队列中没有编号为 #{id} 的消息"),
+    ("cmd.deadletter_offline", "用户 {user} 不在线，消息保留为 #{id}"),
+    ("cmd.deadletter_replayed", "已重新投递 #{id} 给 {user}"),
+    ("cmd.deadletter_replay_failed", "重新投递失败（{reason}），消息保留为 #{id}"),
+    ("cmd.deadletter_dropped", "已删除 #{id}"),
+    ("cmd.deadletter_cleared", "已清空死信队列（{count} 条）"),
+    ("cmd.audit_empty", "审计日志为空"),
+    ("cmd.audit", "审计日志 (最近{count}条):"),
+    ("cmd.audit_usage", "用法: /audit [<条数>]"),
+    ("cmd.reports_empty", "没有待处理的举报"),
+    ("cmd.reports", "待处理的举报 ({count}条):"),
+    ("cmd.report_missing", "举报 #{id} 不存在"),
+    ("cmd.report_dismissed", "举报 #{id} 已处理，移出审核队列"),
+    ("cmd.usage_reports", "用法: /reports [dismiss <编号>]"),
+    ("cmd.quarantine_empty", "没有隔离的消息"),
+    ("cmd.quarantine", "隔离待审核的消息 ({count}条):"),
+    ("cmd.quarantine_missing", "隔离的消息 #{id} 不存在"),
+    ("cmd.quarantine_released", "已放行隔离的消息 #{id}，投递给 {user}"),
+    ("cmd.quarantine_discarded", "已丢弃隔离的消息 #{id}"),
+    ("cmd.usage_quarantine", "用法: /quarantine [release|discard <编号>]"),
+    ("cmd.usage_maintenance", "用法: /maintenance [on [HH:MM]|off]"),
+    ("cmd.maintenance_back", "，预计 {time} 恢复"),
+    ("cmd.maintenance_on", "已开启维护模式{back}：新的注册将被拒绝（管理员除外），当前 {users} 个在线会话不受影响"),
+    ("cmd.maintenance_off", "已关闭维护模式，恢复接受新的注册"),
+    ("cmd.maintenance_not_on", "维护模式未开启"),
+    ("cmd.maintenance_status_off", "维护模式未开启"),
+    ("cmd.maintenance_status_on", "维护模式已由 {admin} 于 {since} 开启{back}，尚有 {users} 个在线会话"),
+    ("cmd.usage_deadletter", "用法: /deadletter [replay <编号>|drop <编号>|clear]"),
+    ("cmd.usage_jobs", "用法: /jobs [run <任务>]"),
+    ("cmd.jobs", "维护任务 (共{count}个):"),
+    ("cmd.jobs_empty", "未配置维护任务"),
+    ("cmd.job", "{job}（{schedule}）: 已运行 {runs} 次，失败 {failures} 次，下次运行 {next}"),
+    ("cmd.job_last", "；上次运行于 {at}，耗时 {elapsed} 毫秒: {result}"),
+    ("cmd.job_never", "无"),
+    ("cmd.job_error", "失败（{error}）"),
+    ("cmd.job_in_progress", "（正在运行）"),
+    ("cmd.job_unknown", "未配置名为 {job} 的维护任务"),
+    ("cmd.job_running", "维护任务 {job} 正在运行，请稍后再试"),
+    ("cmd.job_done", "维护任务 {job} 已完成: {result}"),
+    ("cmd.job_failed", "维护任务 {job} 失败: {error}"),
+    ("cmd.usage_invite", "用法: /invite [<次数> [<有效小时数，0 表示不过期>]|list|revoke <邀请码>]"),
+    ("cmd.invite_created", "已生成邀请码 {code}（可使用 {max} 次，有效期至 {expires}）"),
+    ("cmd.invite_not_required", "注意: 服务器未开启仅限邀请注册（--invite-only），新用户无需邀请码"),
+    ("cmd.invite_failed", "生成邀请码失败: {error}"),
+    ("cmd.invite_never", "永久"),
+    ("cmd.invite_empty", "当前没有有效的邀请码"),
+    ("cmd.invite_list", "有效的邀请码 (共{count}个):"),
+    ("cmd.invite_entry", "{code} 已使用 {used}/{max} 次，有效期至 {expires}，由 {admin} 生成"),
+    ("cmd.invite_revoked", "已撤销邀请码 {code}"),
+    ("cmd.invite_missing", "邀请码 {code} 不存在或已失效"),
+    ("cmd.usage_bot", "用法: /bot [list|create <名称> [<权限>]|revoke <名称>|scope <名称> <权限>]，权限为以逗号分隔的 message、command"),
+    ("cmd.bot_invalid_scope", "无效的权限，可选 message、command（以逗号分隔）"),
+    ("cmd.bot_created", "已创建机器人 {name}（权限: {scopes}），令牌只显示这一次，请妥善保存: {token}"),
+    ("cmd.bot_exists", "机器人 {name} 已存在；需要新令牌时请先 /bot revoke {name}"),
+    ("cmd.bot_user_exists", "用户 {name} 已存在，不能用作机器人账号"),
+    ("cmd.bot_failed", "生成令牌失败: {error}"),
+    ("cmd.bot_missing", "机器人 {name} 不存在"),
+    ("cmd.bot_revoked", "已撤销机器人 {name} 的令牌，可用 /bot create {name} 重新签发"),
+    ("cmd.bot_already_revoked", "机器人 {name} 的令牌已被撤销"),
+    ("cmd.bot_scoped", "已将机器人 {name} 的权限设为 {scopes}"),
+    ("cmd.bot_empty", "当前没有机器人账号"),
+    ("cmd.bot_list", "机器人账号 (共{count}个):"),
+    ("cmd.bot_entry", "{name} 权限: {scopes}，由 {admin} 创建，{state}"),
+    ("cmd.bot_state_online", "在线"),
+    ("cmd.bot_state_offline", "离线"),
+    ("cmd.bot_state_revoked", "令牌已撤销"),
+    ("cmd.bot_only", "只有机器人账号可以使用该指令"),
+    ("cmd.usage_register", "用法: /register <指令> [<说明>]，例如 /register /weather 查询天气"),
+    ("cmd.usage_unregister", "用法: /unregister <指令>"),
+    ("cmd.command_invalid", "无效的指令名 {command}：须以 / 开头，后跟最多 32 个字母、数字、- 或 _"),
+    ("cmd.command_reserved", "{command} 是内置指令，不能注册"),
+    ("cmd.command_taken", "指令 {command} 已由机器人 {bot} 注册"),
+    ("cmd.command_registered", "已注册指令 {command}，用户调用时将转交给你"),
+    ("cmd.command_unregistered", "已取消指令 {command}"),
+    ("cmd.command_not_registered", "你没有注册指令 {command}"),
+    ("cmd.command_own", "{command} 是你自己注册的指令"),
+    ("cmd.command_bot_offline", "指令 {command} 由机器人 {bot} 处理，但它当前不在线"),
+    ("cmd.commands_empty", "当前没有机器人注册的指令"),
+    ("cmd.commands", "机器人指令 (共{count}个):"),
+    ("cmd.command_entry", "{command}（{bot}）"),
+    ("cmd.usage_webhook", "用法: /webhook [list|create <名称> <用户>|revoke <名称>]"),
+    ("cmd.webhook_invalid_name", "无效的 Webhook 名称 {name}：须为最多 32 个字母、数字、- 或 _"),
+    ("cmd.webhook_exists", "Webhook {name} 已存在"),
+    ("cmd.webhook_created", "已创建 Webhook {name}，消息将发给 {user}；路径只显示这一次，请妥善保存: POST {path}"),
+    ("cmd.webhook_not_listening", "（服务器未以 --hooks 监听 Webhook，重启时加上该选项后才能使用）"),
+    ("cmd.webhook_failed", "生成令牌失败: {error}"),
+    ("cmd.webhook_missing", "Webhook {name} 不存在"),
+    ("cmd.webhook_revoked", "已删除 Webhook {name}，其令牌随即失效"),
+    ("cmd.webhook_empty", "当前没有 Webhook"),
+    ("cmd.webhook_list", "Webhook (共{count}个):"),
+    ("cmd.webhook_entry", "{name} → {user}，由 {admin} 创建"),
+    ("cmd.usage_telegram", "用法: /telegram [join|leave]"),
+    ("cmd.telegram_disabled", "服务器未配置 Telegram 桥接"),
+    ("cmd.telegram_joined", "已加入 Telegram 桥接聊天室：群组中的消息会转给你，发给 {room}（或回复 tg: 开头的用户）的消息会发到群组；/telegram leave 退出"),
+    ("cmd.telegram_already_joined", "你已经在 Telegram 桥接聊天室中"),
+    ("cmd.telegram_left", "已退出 Telegram 桥接聊天室"),
+    ("cmd.telegram_not_joined", "你不在 Telegram 桥接聊天室中"),
+    ("cmd.telegram_connected", "已连接"),
+    ("cmd.telegram_disconnected", "未连接"),
+    ("cmd.telegram_status", "Telegram 群组 {chat}（{state}），聊天室地址 {room}"),
+    ("cmd.telegram_no_members", "聊天室中还没有成员"),
+    ("cmd.telegram_members", "成员 (共{count}人): {members}"),
+    ("cmd.usage_join", "用法: /join <#聊天室>|<邀请码>（名称为最多 32 个字母、数字、- 或 _）"),
+    ("cmd.usage_part", "用法: /part <#聊天室>"),
+    ("cmd.usage_room", "用法: /room <#聊天室> [retention <条数>|retention default|private on|off]"),
+    ("cmd.usage_invite_link", "用法: /invite-link <#聊天室> [<次数> [<有效小时数>]]、/invite-link list 或 /invite-link revoke <邀请码>（次数至少为 1，有效小时数为 0 表示永不过期）"),
+    ("cmd.room_created", "已创建并加入聊天室 {room}：发给 {room} 的消息会转给其他成员；/room {room} retention <条数> 修改重新登录时补发的消息条数"),
+    ("cmd.room_joined", "已加入聊天室 {room}（共{count}人）；/part {room} 退出"),
+    ("cmd.room_already_joined", "你已经在聊天室 {room} 中"),
+    ("cmd.room_left", "已退出聊天室 {room}"),
+    ("cmd.room_not_joined", "你不在聊天室 {room} 中"),
+    ("cmd.room_missing", "聊天室 {room} 不存在"),
+    ("cmd.room_owner_only", "只有聊天室 {room} 的创建者 {owner} 与管理员可以修改其设置"),
+    ("cmd.room_retention_set", "聊天室 {room} 的成员重新登录时最多补发最近 {count} 条消息（提到该成员的消息不受此限）"),
+    ("cmd.room_retention_invalid", "补发条数须为正整数，恢复默认值请用 retention default"),
+    ("cmd.room_info", "{visibility}聊天室 {room}，由 {owner} 创建，重新登录时最多补发最近 {retention} 条消息"),
+    ("cmd.room_private_label", "私有"),
+    ("cmd.room_public_label", "公开"),
+    ("cmd.room_private", "聊天室 {room} 是私有的，需要创建者或管理员提供的邀请码才能加入: /join <邀请码>"),
+    ("cmd.room_private_on", "聊天室 {room} 已设为私有，其他用户须凭 /invite-link {room} 生成的邀请码加入"),
+    ("cmd.room_private_off", "聊天室 {room} 已设为公开，任何用户都可以 /join {room} 加入"),
+    ("cmd.room_invite_created", "已生成聊天室 {room} 的邀请码 {code}：可使用 {max} 次，有效期至 {expires}；对方以 /join {code} 加入"),
+    ("cmd.room_invite_public", "注意: 聊天室 {room} 是公开的，不需要邀请码也能加入；/room {room} private on 设为私有"),
+    ("cmd.room_invite_entry", "{code} → {room}：已使用 {used}/{max} 次，有效期至 {expires}，由 {user} 生成"),
+    ("cmd.room_invite_empty", "没有有效的聊天室邀请码"),
+    ("cmd.room_no_members", "聊天室中没有成员"),
+    ("cmd.room_members", "成员 (共{count}人): {members}"),
+    ("cmd.rooms_empty", "你还没有加入任何聊天室，/join <#聊天室> 加入或创建"),
+    ("cmd.rooms_list", "你加入的聊天室 (共{count}个):"),
+    ("cmd.rooms_entry", "{room}（{count}人，{online}人在线）"),
+    ("cmd.xmpp_disabled", "服务器未配置 XMPP 网关"),
+    ("cmd.xmpp_linked", "已连接"),
+    ("cmd.xmpp_unlinked", "未连接"),
+    ("cmd.xmpp_status", "XMPP 网关 {domain}（{state}），你的 Jabber 地址为 {jid}"),
+    ("cmd.xmpp_subscribers", "订阅你在线状态的联系人 (共{count}个): {contacts}"),
+    ("cmd.xmpp_contacts", "在线的 Jabber 联系人 (共{count}个):"),
+    ("cmd.xmpp_no_contacts", "暂未收到 Jabber 联系人的在线状态"),
+    ("cmd.xmpp_show_online", "在线"),
+    ("cmd.xmpp_show_away", "离开"),
+    ("cmd.xmpp_show_dnd", "请勿打扰"),
+    // 时长
+    ("duration.days", "{days} 天 {hours} 小时"),
+    ("duration.hours", "{hours} 小时 {minutes} 分"),
+    ("duration.minutes", "{minutes} 分 {seconds} 秒"),
+    ("duration.seconds", "{seconds} 秒"),
+    // 脚本
+    ("script.error", "脚本第 {line} 行: {reason}"),
+    ("script.usage_send", "用法: send <接收者> [内容]"),
+    ("script.usage_expect", "用法: expect <发送者|*> [文本]"),
+    ("script.invalid_duration", "无效的时长"),
+    ("script.unknown_step", "未知的步骤 {step}"),
+    ("script.send_failed", "发送失败: {error}"),
+    ("script.disconnected", "连接已断开"),
+    ("script.expect_timeout", "{timeout} 内未收到来自 {from} 且包含 \"{text}\" 的消息"),
+    // 连接与编码
+    ("transport.invalid_address", "服务器地址 {addr} 无效，格式应为 主机:端口"),
+    ("transport.dns", "无法解析主机名 {host}: {error}"),
+    ("transport.refused", "服务器 {addr} 拒绝连接，请确认服务器已启动且端口正确"),
+    ("transport.timed_out", "连接服务器超时（{secs} 秒），请检查地址与网络是否可达"),
+    ("transport.tls", "TLS 握手失败: {error}"),
+    ("transport.io", "连接服务器失败: {error}"),
+    ("transport.no_records", "没有可用的地址记录"),
+    ("transport.tls_disabled", "当前构建未启用 tls 特性"),
+    ("srv.not_provided", "{domain} 的 SRV 记录表明该域名不提供聊天服务"),
+    ("srv.unsupported", "当前系统不支持查询 SRV 记录"),
+    ("srv.invalid_name", "无效的域名: {name}"),
+    ("srv.server_error", "域名服务器返回错误（响应码 {code}）"),
+    ("codec.io", "读写失败: {error}"),
+    ("codec.encode", "序列化失败: {error}"),
+    ("codec.decode", "解析帧失败: {error}"),
+    ("codec.too_large", "帧长度 {len} 超过上限 {max}"),
+    // 别名与密钥
+    ("alias.missing_eq", "缺少 `=`"),
+    ("alias.invalid_name", "无效的别名名称 `{name}`"),
+    ("alias.error", "{path} 第 {line} 行: {reason}"),
+    ("secrets.keyring", "密钥环操作失败: {error}"),
+    ("secrets.io", "读写密钥文件失败: {error}"),
+    ("secrets.decrypt", "解密失败，口令错误或文件已损坏"),
+    ("secrets.format", "密钥文件格式无效: {error}"),
+    ("secrets.keyring_disabled", "当前构建未启用 keyring 特性"),
+    ("secrets.unsupported_version", "不支持的版本 {version}"),
+    ("secrets.encrypt_failed", "加密失败"),
+    ("secrets.bad_hex", "字段 {field} 不是有效的十六进制数据"),
+    ("secrets.prompt", "密钥存储口令: "),
+    ("secrets.not_text", "条目 {entry} 不是有效的 UTF-8 文本"),
+    ("secrets.passphrase_required", "标准输入不是终端，请通过环境变量 {env} 提供密钥存储口令"),
+    ("secrets.no_data_dir", "无法确定数据目录"),
+    ("storage.invalid_key", "存储密钥须为 64 位十六进制数（32 字节）"),
+    ("storage.no_keys", "未提供任何存储密钥"),
+    ("storage.env_missing", "环境变量 {name} 未设置"),
+    ("storage.command_failed", "执行密钥命令失败: {error}"),
+    ("storage.invalid_source", "无效的密钥来源 {source}，应为 env:变量名 或 cmd:命令"),
+    ("storage.unknown_key", "消息日志中有以未知密钥 {id} 加密的消息，请提供该密钥"),
+    ("storage.decrypt_failed", "以密钥 {id} 解密消息日志失败，密钥不符或文件已被篡改"),
+    ("storage.encrypted_log", "消息日志已加密，请通过 --storage-keys 提供存储密钥"),
+    ("storage.no_data_dir", "数据目录 {dir} 不存在"),
+    ("storage.data_dir_not_empty", "数据目录 {dir} 中已有数据，如需覆盖请加 --force"),
+    ("storage.snapshot_version", "快照格式版本 {version} 高于本程序支持的版本 {supported}，请升级后再恢复"),
+    // 一致性检查
+    ("conformance.fixtures", "帧样例"),
+    ("conformance.exchanges", "交互用例（{addr}，{codec}）"),
+    ("conformance.pass", "通过"),
+    ("conformance.fail", "失败"),
+    ("conformance.summary", "共 {total} 项，失败 {failed} 项"),
+    ("conformance.decode_failed", "无法解析样例: {error}"),
+    ("conformance.encoding_differs", "{codec} 编码结果与样例不一致: {actual}"),
+    ("conformance.connect_failed", "无法连接 {addr}: {error}"),
+    ("conformance.closed", "服务器在发来 {expected} 之前关闭了连接"),
+    ("conformance.timeout", "等待 {expected} 超时"),
+    ("conformance.not_closed", "服务器没有关闭连接"),
+    ("conformance.properties", "属性检查（每项 {cases} 个随机用例）"),
+    ("conformance.roundtrip_empty", "{codec}: 解码时没有读出任何帧"),
+    ("conformance.roundtrip_trailing", "{codec}: 一帧解码后仍有剩余数据"),
+    ("conformance.roundtrip_differs", "{codec}: 解码结果与原值不同，原值 {expected}，解码得到 {actual}"),
+    // 压测
+    ("bench.summary", "送达 {delivered} 条消息，用时 {secs} 秒，吞吐量 {throughput} 条/秒"),
+    ("bench.latency", "延迟（微秒）：均值 {mean}，p50 {p50}，p90 {p90}，p99 {p99}，最大 {max}"),
+    ("bench.too_few_clients", "压测至少需要 2 个客户端"),
+    ("bench.timeout", "{secs} 秒内未能送达全部消息"),
+    ("bench.refused", "客户端 {name} 注册失败，服务器关闭了连接"),
+    ("bench.closed", "客户端 {name} 只收到 {received}/{expected} 条消息，服务器就关闭了连接"),
+    ("bench.compare", "与基线 {path} 比较："),
+    ("bench.mismatch", "注意：基线的压测参数与本次不同，比较结果仅供参考"),
+    ("bench.regressed", "退化"),
+    ("bench.regressions", "{count} 项指标退化超过 {threshold}%"),
+    ("bench.no_regression", "没有指标退化超过 {threshold}%"),
+    // 订阅源机器人
+    ("feedbot.started", "已开始抓取 {count} 个订阅源，每 {interval} 秒一次"),
+    ("feedbot.primed", "首次抓取 {url}，记录已有的 {count} 个条目（不发送）"),
+    ("feedbot.posting", "{url} 有 {count} 个新条目"),
+    ("feedbot.entry", "📰 {feed}: {title}\n{link}"),
+    ("feedbot.entry_no_link", "📰 {feed}: {title}"),
+    ("feedbot.fetch_failed", "抓取 {url} 失败: {error}"),
+    ("feedbot.parse_failed", "解析 {url} 失败: {error}"),
+    ("feedbot.send_failed", "向 {user} 发送新条目失败: {error}"),
+    ("feedbot.save_failed", "保存订阅源状态 {path} 失败: {error}"),
+    ("feedbot.reconnect_failed", "重新连接服务器失败，下一轮再试: {error}"),
+    ("link.bad_url", "无效的地址 {url}（须以 http:// 或 https:// 开头）"),
+    ("link.http_status", "服务器返回 HTTP {status}"),
+    ("link.too_many_redirects", "重定向次数过多"),
+];
+
+/// 英文目录
+const EN: &[(&str, &str)] = &[
+    // 命令行
+    ("cli.mode_required", "Please specify a mode: server, client, conformance, feedbot, admin or bench"),
+    ("cli.invalid_mode", "Invalid mode, use server, client, conformance, feedbot, admin or bench"),
+    ("cli.invalid_codec", "Invalid codec {value}, use json or length-prefixed"),
+    ("cli.invalid_lang", "Invalid language: {value} (zh or en)"),
+    ("cli.invalid_option", "Invalid --{key}: {value}"),
+    ("cli.starting_server", "Starting in server mode..."),
+    ("cli.starting_client", "Starting in client mode..."),
+    ("cli.starting_feedbot", "Starting the feed bot..."),
+    ("cli.admin_usage", "Usage: admin token <username> | admin secret set|delete <entry> [--name=<username>] [--secrets=<path>] | admin snapshot <path> --data-dir=<dir> [--spool=<dir>] | admin restore <path> --data-dir=<dir> [--spool=<dir>] [--force] | admin status|drain|audit [<count>]|reports [dismiss <id>]|quarantine [release|discard <id>]|maintenance [on [HH:MM]|off] --admin-socket=<path>"),
+    ("cli.snapshot_written", "Wrote {users} users, {messages} messages and {spooled} spooled federation messages to snapshot {path}"),
+    ("cli.snapshot_restored", "Restored {users} users, {messages} messages and {spooled} spooled federation messages from the snapshot taken at {created} into {dir}"),
+    ("cli.admin_failed", "Operation failed: {error}"),
+    ("cli.invalid_chaos", "Invalid fault injection spec {value}, expected drop=p,truncate=p,duplicate=p,delay=p,max-delay=ms,seed=n with probabilities summing to at most 1"),
+    ("cli.invalid_bench_option", "Invalid benchmark option --{option}={value}"),
+    ("cli.invalid_transport", "Invalid transport {value}, use duplex or tcp"),
+    ("cli.starting_bench", "Benchmarking: {clients} clients over {transport} ({codec}) each sending {messages} messages of {size} bytes"),
+    ("cli.bench_failed", "Benchmark failed: {error}"),
+    ("cli.baseline_saved", "Saved the results as baseline {path}"),
+    ("cli.baseline_failed", "Cannot read or write baseline {path}: {error}"),
+    (
+        "cli.feature_disabled",
+        "{what} requires the {feature} feature, which this build does not enable (rebuild with --features {feature})",
+    ),
+    ("cli.takeover_requires_admin_socket", "--takeover requires the old process's --admin-socket"),
+    ("cli.took_over", "The old process disconnected all users and exited, accepting connections"),
+    ("cli.nothing_to_take_over", "No server is running on the admin socket, starting normally"),
+    ("cli.takeover_failed", "Failed to take over the old process: {error}"),
+    ("cli.feedbot_usage", "Usage: feedbot [server address] --name=<bot> --token=<token> --feeds=<url>,... --to=<user>,... [--interval=<seconds>] [--state=<path>]"),
+    ("cli.invalid_interval", "Invalid poll interval: {value}"),
+    ("cli.feedbot_state_failed", "Failed to load the feed state file: {error}"),
+    ("cli.invalid_idle_timeout", "Invalid idle timeout: {value}"),
+    ("cli.invalid_slow_grace", "Invalid grace period: {value}"),
+    ("cli.invalid_reconnect_after", "Invalid reconnect delay: {value}"),
+    ("cli.invalid_max_connections", "Invalid connection limit: {value}"),
+    ("cli.invalid_fd_reserve", "Invalid file descriptor reserve: {value}"),
+    ("cli.invalid_max_message_len", "Invalid maximum message length: {value}"),
+    ("cli.invalid_spam", "Invalid spam rules: {value} (format: rule=count,..., where rule is repeat, recipients, links or window (seconds), e.g. repeat=5,links=10)"),
+    ("cli.invalid_spam_throttle", "Invalid throttle duration: {value}"),
+    ("cli.invalid_max_file_size", "Invalid file size limit: {value} (bytes, optionally with a K, M or G suffix)"),
+    ("cli.invalid_connect_rate", "Invalid connection rate: {value} (use attempts/seconds, e.g. 10/60)"),
+    ("cli.invalid_cooldown", "Invalid cooldown: {value}"),
+    ("cli.invalid_bot_rate", "Invalid bot rate limit: {value} (format: messages/seconds, e.g. 60/60)"),
+    ("cli.invalid_telegram_chat", "Invalid Telegram chat id: {value}"),
+    ("cli.telegram_token_required", "The Telegram bridge needs a bot token via --telegram-token, the CHAT_TELEGRAM_TOKEN environment variable or the telegram-token entry in the secret store"),
+    ("cli.invalid_xmpp", "Invalid XMPP gateway setting: {value} (format: domain=secret or domain=secret@host:port)"),
+    ("cli.invalid_archive_after", "Invalid archive age: {value} (expected a positive number of days)"),
+    ("cli.invalid_archive_to", "Invalid archive location: {value} (format: https://endpoint/bucket[/prefix])"),
+    ("cli.archive_incomplete", "--archive-after and --archive-to must be given together"),
+    ("cli.archive_credentials_required", "Archiving to object storage requires access keys in the AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY environment variables or the aws-access-key-id and aws-secret-access-key entries in the secret store"),
+    ("cli.invalid_job", "Invalid maintenance job: {value} (format: job=minute hour day month weekday, where job is vacuum, expire or prune)"),
+    ("cli.invalid_queue_full", "Invalid queue-full policy: {value} (wait or drop)"),
+    ("cli.invalid_connect_timeout", "Invalid connect timeout: {value}"),
+    ("cli.invalid_heartbeat", "Invalid heartbeat interval: {value}"),
+    ("cli.open_data_dir_failed", "Cannot open data directory {dir}: {error}"),
+    ("cli.storage_keys_failed", "Cannot read storage keys: {error}"),
+    ("cli.storage_reencrypted", "Re-encrypted {count} messages in data directory {dir} with the current storage key"),
+    ("cli.open_geoip_failed", "Cannot load GeoIP database {path}: {error}"),
+    ("cli.invalid_admin", "Invalid admin: {value} (use username=token-digest, generated by chat admin token <username>)"),
+    ("cli.admin_token_issued", "Token for admin {name} (shown only once, give it to the admin to pass with --token):\n  {token}\nServer option:\n  --admins={name}={digest}"),
+    ("cli.invalid_peer", "Invalid federation peer: {value} (use name=secret or name=secret@host:port)"),
+    ("cli.federation_name_required", "--peers, --upstream and --spool require --federation=name to set this server's name"),
+    ("cli.invalid_upstream", "Upstream {value} is not a peer configured in --peers"),
+    ("cli.invalid_vhost", "Invalid virtual host: {value} (use name or name=data-dir)"),
+    ("cli.invalid_pow", "Invalid proof-of-work difficulty: {value} (1 to {max})"),
+    ("cli.vhosts", "Virtual hosts: {list}"),
+    ("cli.server_failed", "Server error: {error}"),
+    ("cli.client_failed", "Client error: {error}"),
+    ("cli.read_script_failed", "Cannot read script {path}: {error}"),
+    ("cli.script_needs_name", "Script mode requires a username via --name=<username>"),
+    ("cli.script_error", "[error {code}] {message}"),
+    ("cli.prompt_name", "Username >> "),
+    ("cli.open_history_failed", "Cannot open chat history directory {dir}: {error}"),
+    ("cli.load_theme_failed", "Cannot load theme file: {error}"),
+    ("cli.load_aliases_failed", "Cannot load aliases file: {error}"),
+    ("cli.invalid_preview", "Invalid image preview mode {value}, use auto, kitty, sixel, text or off"),
+    ("cli.load_identity_failed", "Cannot load identity key {path}: {error}"),
+    ("cli.open_secrets_failed", "Cannot open the secret store: {error}"),
+    ("cli.secret_prompt", "Value for {entry}: "),
+    ("cli.secret_saved", "Updated entry {entry} in the secret store"),
+    // 客户端
+    ("client.prompt_recipient", "Recipient: "),
+    ("client.prompt_content", "Message: "),
+    ("client.connected", "Connected to server"),
+    ("client.connect_failed", "Cannot connect to server"),
+    ("client.no_address", "No server address given"),
+    ("client.not_connected", "Not connected to a server"),
+    ("client.switching", "Switching to a backup server..."),
+    ("client.reconnecting", "Reconnecting to server... (attempt {attempt})"),
+    ("client.reconnect_failed", "Reconnect failed"),
+    ("client.reconnect_exhausted", "Out of reconnect attempts"),
+    ("client.goodbye", "Goodbye! Thanks for using ChatApp!"),
+    ("client.self_message", "You cannot send a message to yourself"),
+    ("client.send_failed", "Failed to send message: {error}"),
+    ("client.usage_msg", "Usage: /msg <user> <text>"),
+    ("client.usage_forward", "Usage: /forward <number> <user>"),
+    ("client.usage_sendfile", "Usage: /sendfile <user> <path>"),
+    ("client.usage_accept", "Usage: /accept <number>"),
+    ("client.usage_reject", "Usage: /reject <number>"),
+    ("client.usage_cancel_transfer", "Usage: /cancel-transfer <number> (see /transfers)"),
+    ("client.usage_trust", "Usage: /trust <user>"),
+    ("client.usage_verify", "Usage: /verify <user> <fingerprint>"),
+    ("client.usage_unverify", "Usage: /unverify <user>"),
+    ("client.usage_e2e", "Usage: /e2e <user> or /e2e off <user>"),
+    ("client.usage_me", "Usage: /me <action>"),
+    ("client.usage_history", "Usage: /history <user> [count]"),
+    ("client.usage_unmute", "Usage: /unmute <user>"),
+    ("client.roster_joined", "{user} is online"),
+    ("client.roster_left", "{user} went offline"),
+    ("client.roster_status", "{user} is now {status}"),
+    ("client.status_online", "online"),
+    ("client.status_away", "away"),
+    ("client.status_dnd", "do not disturb"),
+    ("client.status_other", "unknown"),
+    ("client.usage_emoji", "Usage: /emoji <query>"),
+    ("client.no_message", "No message numbered #{id}"),
+    ("client.forwarded", "[Forwarded from {from}]"),
+    ("client.multiline_quoted", "Multi-line input, end with \"\"\" to send"),
+    ("client.multiline_end", "Multi-line input, finish with /end on its own line"),
+    ("client.no_aliases", "No aliases defined"),
+    ("client.history_disabled", "Local chat history is disabled"),
+    ("client.history_empty", "No chat history with {peer}"),
+    ("client.history_save_failed", "Failed to save chat history"),
+    ("client.history_read_failed", "Failed to read chat history"),
+    ("client.usage_export", "Usage: /export <user> [path]"),
+    ("client.exported", "Exported {count} messages to {path}"),
+    ("client.export_failed", "Failed to export chat history"),
+    ("export.title", "Chat history with {peer}"),
+    ("export.range", "{count} messages, {from} to {to}"),
+    ("client.no_links", "No links in recent messages"),
+    ("client.no_link", "No link numbered {id}"),
+    ("client.opened", "Opened"),
+    ("client.open_failed", "Cannot open link"),
+    ("client.usage_preview", "Usage: /preview <link number|image path> (see /open for link numbers)"),
+    ("preview.caption", "{name} ({format}, {width}×{height}, {size})"),
+    ("preview.placeholder", "[image] {caption}"),
+    ("preview.placeholder_plain", "[image] {name} ({size})"),
+    ("preview.not_image", "{name} is not a recognized image"),
+    ("preview.failed", "Cannot preview {name}"),
+    ("preview.too_large", "The file exceeds the preview size limit of {max}"),
+    ("client.none_muted", "No muted conversations"),
+    ("client.muted_list", "Muted: {list}"),
+    ("client.muted", "Muted conversation with {peer}"),
+    ("client.unmuted", "Unmuted conversation with {peer}"),
+    ("client.not_muted", "Conversation with {peer} is not muted"),
+    ("client.muted_label", " (muted)"),
+    ("client.no_emoji", "No emoji matching {query}"),
+    ("client.read_timeout", "Timed out reading from server, connection considered lost"),
+    ("client.server_closed", "Server closed the connection"),
+    ("client.server_shutdown", "{reason}; the connection is about to close"),
+    ("client.server_shutdown_reconnect", "{reason}; reconnecting automatically in {after}"),
+    ("client.shutdown_reason_shutdown", "The server is shutting down"),
+    ("client.shutdown_reason_upgrade", "The server is upgrading"),
+    ("client.shutdown_reason_other", "The server is closing the connection"),
+    ("client.reconnecting_now", "Reconnecting as suggested by the server..."),
+    ("client.parse_failed", "Failed to parse server message"),
+    ("client.read_failed", "Failed to read from server"),
+    ("client.heartbeat_timeout", "Heartbeat timed out, connection considered lost"),
+    ("client.pow_solving", "The server requires a proof of work (difficulty {difficulty}), computing..."),
+    ("client.pow_too_hard", "The server's proof-of-work difficulty {difficulty} is too high, giving up"),
+    // 服务器日志
+    ("server.listening", "Server listening on {addr}"),
+    ("server.listening_dual_stack", "Accepting both IPv4 and IPv6 connections"),
+    ("server.dual_stack_unavailable", "Cannot listen on IPv6 ({error}); listening on IPv4 only"),
+    ("server.ws_listening", "Listening for WebSocket connections on {addr}"),
+    ("server.webhook_listening", "Listening for incoming webhooks on {addr}"),
+    ("server.webhook_created", "Admin {admin} created webhook {name} for user {user}"),
+    ("server.webhook_revoked", "Webhook {name} deleted"),
+    ("server.webhook_delivered", "Webhook {name} from {addr} posted a message to {user}"),
+    ("server.webhook_refused", "Refused webhook request from {addr}: invalid token"),
+    ("server.telegram_started", "Bridging Telegram chat {chat}"),
+    ("server.telegram_connected", "Connected to the Telegram Bot API"),
+    ("server.telegram_poll_failed", "Failed to receive Telegram messages, retrying later: {error}"),
+    ("server.telegram_send_failed", "Failed to send a message to the Telegram chat: {error}"),
+    ("server.telegram_bad_api", "Invalid Bot API address {api} (must start with http:// or https://)"),
+    ("server.telegram_http_status", "Bot API returned HTTP {status}"),
+    ("server.xmpp_linked", "XMPP gateway {domain} connected to {addr}"),
+    ("server.xmpp_closed", "the XMPP server closed the connection"),
+    ("server.xmpp_unlinked", "XMPP gateway disconnected: {error}"),
+    ("server.xmpp_link_failed", "Failed to connect to XMPP server {addr}: {error}"),
+    ("server.xmpp_protocol", "the XMPP server sent unrecognized data"),
+    ("server.xmpp_stream_error", "the XMPP server reported an error: {condition}"),
+    ("server.xmpp_too_large", "XMPP stanza too large"),
+    ("server.xmpp_message", "Jabber user {from} sent a message to {user}"),
+    ("server.xmpp_subscribed", "Jabber user {contact} subscribed to {user}'s presence"),
+    ("server.ctrl_c", "Received Ctrl+C, shutting down the server..."),
+    ("server.sigterm", "Received SIGTERM, shutting down the server..."),
+    ("server.systemd_listening", "Using the listening socket {addr} passed by systemd"),
+    ("server.systemd_no_ws", "WebSocket is configured but systemd passed only one listening socket"),
+    ("server.systemd_status", "{users} users online"),
+    ("server.systemd_status_refusing", "{users} users online, refusing new connections"),
+    ("server.connection_limit", "Connection limit reached, rejecting connection from {addr}"),
+    ("server.fd_exhausted", "Too few free file descriptors, rejecting connection from {addr}"),
+    ("server.fd_limit_low", "⚠️ File descriptor limit {limit} cannot hold {max} connections plus {reserve} reserved descriptors; raise ulimit -n"),
+    ("server.refusing_started", "⚠️ Server started refusing new connections: {reason}"),
+    ("server.refusing_stopped", "Server is accepting new connections again ({count} refused meanwhile)"),
+    ("server.overload_connection_limit", "connection limit reached"),
+    ("server.overload_fd_headroom", "too few free file descriptors"),
+    ("server.overload_accept_failed", "accept failed"),
+    ("server.new_connection", "New connection from {addr}"),
+    ("server.new_ws_connection", "New WebSocket connection from {addr}"),
+    ("server.ip_refused", "Refused connection from {addr}: {reason}"),
+    ("server.vhost_selected", "Connection from {addr} entered virtual host {host}"),
+    ("server.invite_redeemed", "User {user} registered with invite code {code} (used {used}/{max} times)"),
+    ("server.invite_refused", "New user {user} from {addr} did not present a valid invite code, refused"),
+    ("server.bot_refused", "Token authentication for {user} from {addr} failed, refused"),
+    ("server.bot_authenticated", "Bot {user} authenticated by token"),
+    ("server.admin_authenticated", "Admin {user} authenticated by token"),
+    ("server.bot_created", "Admin {admin} created bot {name} (scopes: {scopes})"),
+    ("server.bot_revoked", "Token of bot {name} revoked"),
+    ("server.command_registered", "Bot {bot} registered command {command}"),
+    ("server.not_a_bot", "{user} is not a bot account and cannot log in with a token"),
+    ("server.bot_token_required", "{user} is a bot account and must log in with a token"),
+    ("server.bot_token_invalid", "The token for bot {user} is invalid or has been revoked"),
+    ("server.admin_token_required", "{user} is an admin account and must log in with the admin token"),
+    ("server.admin_token_invalid", "The admin token for {user} is invalid"),
+    ("server.bot_scope_denied", "The bot lacks the {scope} scope"),
+    ("server.bot_rate_limited", "Sending too fast: bots may send at most {max} messages per {window}"),
+    ("server.pow_refused", "New user {user} from {addr} failed the proof of work, refused"),
+    ("server.pow_clamped", "Proof-of-work difficulty {difficulty} exceeds the maximum of {max}, using {max}"),
+    ("server.identity_refused", "{user} from {addr} could not prove possession of the identity key bound to that name, refused"),
+    ("server.identity_bound", "The name {user} is now bound to identity key {fingerprint}"),
+    ("server.maintenance_refused", "Server is in maintenance mode, refused user {user} from {addr}"),
+    ("server.name_refused", "The name {user} is not allowed, refused the connection from {addr}"),
+    ("server.plugin_refused", "A plugin refused the connection from {addr}: {reason}"),
+    ("server.peer_linked", "Linked to federation peer {server} ({addr})"),
+    ("server.peer_unlinked", "Link to federation peer {server} lost: {error}"),
+    ("server.peer_link_failed", "Cannot link to federation peer {server} ({addr}): {error}"),
+    ("server.peer_closed", "peer closed the connection"),
+    ("server.peer_timeout", "timed out"),
+    ("server.peer_protocol", "peer is not a federated server"),
+    ("server.peer_auth_failed", "Federation authentication failed"),
+    ("server.peer_refused", "Refused federation link from {addr} (claiming {server}): not configured or authentication failed"),
+    ("server.peer_accepted", "Federation peer {server} connected from {addr}"),
+    ("server.peer_left", "Inbound link from federation peer {server} closed"),
+    ("server.relay_received", "Relayed message from federation peer {server}: {from} → {to}"),
+    ("server.relay_spooled", "Link to {server} is down, spooled relayed message from {from}"),
+    ("server.transfer_offered", "{from} offered {to} the file {name} ({size})"),
+    ("server.transfer_accepted", "{to} accepted the file {name} from {from}"),
+    ("server.transfer_resumed", "{to} resumed the file {name} from {from} at {offset}"),
+    ("server.transfer_rejected", "{to} rejected the file {name} from {from}"),
+    ("server.transfer_done", "{to} received the file {name} from {from} ({relayed} relayed by the server)"),
+    ("server.transfer_cancelled", "{user} cancelled the file transfer {name} with {peer}"),
+    ("server.transfer_too_large", "The file {name} ({size}) exceeds the server's size limit of {max}"),
+    ("server.transfer_extension", "The server does not allow transferring .{ext} files"),
+    ("server.transfer_blocked", "Blocked the file {name} from {from} to {to}: {reason}"),
+    ("server.transfer_scanning", "Received the whole file {name} from {from} to {to}, scanning"),
+    ("server.transfer_quarantined", "The file {name} from {from} to {to} failed the scan and was quarantined: {path}"),
+    ("server.transfer_scan_failed", "Failed to scan the spooled file {path}: {error}"),
+    ("server.transfer_infected", "The file {name} failed the server's security scan, transfer cancelled"),
+    ("server.transfer_unscanned", "The server could not scan the file {name}, transfer cancelled"),
+    ("server.spool_flushed", "Forwarded {count} spooled messages to federation peer {server}"),
+    ("server.spool_full", "Spool for federation peer {server} is full, not spooling new messages"),
+    ("server.spool_failed", "Spool I/O for federation peer {server} failed: {error}"),
+    ("server.relay_dropped", "Dropped message relayed by federation peer {server} ({from} → {to}): {reason}"),
+    ("federation.unknown_peer", "{server} is not a federation peer of this server"),
+    ("federation.unknown_host", "Unknown server {host} (this server is not federated)"),
+    ("federation.spooled", "Federation link to {server} is down; message spooled and will be forwarded when it recovers"),
+    ("federation.unavailable", "No federation link to {server}, message not sent"),
+    ("federation.spoofed", "origin does not match the link"),
+    ("federation.loop", "relay loop or too many hops"),
+    ("federation.bad_address", "invalid recipient address"),
+    ("transfer.offline", "{user} is not online, cannot send the file"),
+    ("transfer.unsupported", "{user}'s client does not support file transfers"),
+    ("transfer.too_many", "Too many concurrent file transfers (limit {max}), wait for one to finish"),
+    ("transfer.peer_left", "{user} went offline"),
+    ("transfer.not_a_file", "{path} is not a file"),
+    ("transfer.read_failed", "Cannot read {path}: {error}"),
+    ("transfer.save_failed", "Cannot save {path}: {error}"),
+    ("transfer.offered", "Offered {name} ({size}) to {peer}, waiting for them to accept"),
+    ("transfer.incoming", "{peer} wants to send you {name} ({size}); type /accept {number} to receive or /reject {number} to decline"),
+    ("transfer.incoming_resume", "{peer} wants to continue sending {name} ({size}, {received} already received); type /accept {number} to resume or /reject {number} to decline"),
+    ("transfer.no_offer", "No pending file numbered {number}"),
+    ("transfer.you_rejected", "Declined {name} from {peer}"),
+    ("transfer.rejected", "{peer} declined {name}"),
+    ("transfer.accepted", "{peer} accepted {name}, trying a direct connection"),
+    ("transfer.receiving", "Receiving {name} from {peer}"),
+    ("transfer.receiving_resumed", "Resuming {name} from {peer} at {received}"),
+    ("transfer.resumed", "{peer} is resuming {name} at {offset}, trying a direct connection"),
+    ("transfer.resuming", "{peer} is online, offering the interrupted {name} again"),
+    ("transfer.interrupted", "File transfer {name} with {peer} was interrupted by a disconnect; the transferred part is kept and will resume after reconnecting"),
+    ("transfer.direct_failed", "Direct transfer interrupted: {error}"),
+    ("transfer.sent_direct", "Sent {name} to {peer} over a direct connection, waiting for confirmation"),
+    ("transfer.relaying", "No direct connection to {peer}, relaying {name} through the server"),
+    ("transfer.delivered", "{peer} received {name}"),
+    ("transfer.saved", "Received {name} from {peer}, saved as {path}"),
+    ("transfer.checksum_mismatch", "checksum mismatch"),
+    ("transfer.cancelled", "File transfer {name} with {peer} was cancelled"),
+    ("transfer.cancelled_reason", "File transfer {name} with {peer} was cancelled: {reason}"),
+    ("transfer.outgoing", "↑ {name} → {peer}"),
+    ("transfer.incoming_file", "↓ {name} ← {peer}"),
+    ("transfer.progress", "#{number} {file} {bar} {percent}% {done}/{size} {rate}/s {eta} left"),
+    ("transfer.progress_waiting", "#{number} {file} starting"),
+    ("transfer.list", "File transfers ({count}):"),
+    ("transfer.list_offer", "#{number} {file} ({size}) waiting for you to accept"),
+    ("transfer.list_empty", "No pending or active file transfers"),
+    ("transfer.list_interrupted", "{file} interrupted, resumes when {peer} is online"),
+    ("transfer.no_transfer", "No active file transfer numbered {number}"),
+    ("transfer.already_complete", "#{number} has been fully transferred and is being verified; it can no longer be cancelled"),
+    ("identity.bad_key_file", "Identity key file {path} is malformed"),
+    ("identity.bad_key_entry", "Identity key {entry} in the secret store is malformed"),
+    ("identity.own", "Your identity key fingerprint: {fingerprint}"),
+    ("identity.disabled", "No identity key configured; outgoing messages are unsigned"),
+    ("identity.peer", "Identity key fingerprint of {user}: {fingerprint}"),
+    ("identity.peer_verified", "Identity key fingerprint of {user}: {fingerprint} ✓ verified"),
+    ("identity.unknown", "No signed message from {user} yet"),
+    ("identity.first_seen", "Remembered the identity key of {user}, fingerprint {fingerprint}; once it matches what they tell you out of band, mark it verified with /verify {user} <fingerprint>"),
+    ("identity.verified_changed", "SECURITY WARNING: the identity key of verified contact {user} has changed (new fingerprint {fingerprint}). Do not trust anything sent under this identity until you re-check it; once verified, accept it with /trust {user} and verify again with /verify"),
+    ("identity.changed", "Warning: the identity key of {user} has changed (new fingerprint {fingerprint}); they may have switched devices, or someone may be impersonating them. Once verified, accept it with /trust {user}"),
+    ("identity.invalid", "Warning: the signature on a message from {user} is invalid; the content may have been tampered with"),
+    ("identity.missing", "Warning: a message from {user} is unsigned, although their earlier messages were signed"),
+    ("identity.replayed", "Warning: dropped a repeated message from {user} (its signature nonce was already seen); it may have been replayed by the network"),
+    ("identity.stale", "Warning: dropped an outdated message from {user} (its signing time is outside the replay window); it may have been replayed by the network"),
+    ("identity.trusted", "Accepted the new identity key of {user}, fingerprint {fingerprint}"),
+    ("identity.not_changed", "The identity key of {user} has not changed"),
+    ("identity.verified", "Marked the identity key of {user} as verified; their validly signed messages now show ✓"),
+    ("identity.verify_mismatch", "The fingerprint does not match the identity key of {user} (remembered fingerprint {fingerprint}); not marked as verified"),
+    ("identity.unverified", "Removed the verified mark from {user}"),
+    ("identity.not_verified", "The identity key of {user} is not verified"),
+    ("identity.save_failed", "Failed to save known keys"),
+    ("e2e.unavailable", "Cannot start an end-to-end encrypted session with {user}: they are offline or do not support it"),
+    ("e2e.no_identity", "End-to-end encryption requires an identity key (see --identity)"),
+    ("e2e.requested", "Requested an end-to-end encrypted session with {user}, waiting for them to answer"),
+    ("e2e.already", "The end-to-end encrypted session with {user} is already established"),
+    ("e2e.started_by_peer", "{user} started an end-to-end encrypted session"),
+    ("e2e.established", "End-to-end encrypted session with {user} established; from now on only the two of you can read your messages"),
+    ("e2e.stopped", "Ended the end-to-end encrypted session with {user}; further messages are not encrypted"),
+    ("e2e.not_active", "No end-to-end encrypted session with {user}"),
+    ("e2e.none", "No end-to-end encrypted sessions"),
+    ("e2e.session_ready", "  {user}: established, {count} ephemeral key exchanges"),
+    ("e2e.session_pending", "  {user}: waiting for an answer"),
+    ("e2e.pending", "The end-to-end encrypted session with {user} is not established yet; message not sent, please try again shortly"),
+    ("e2e.bad_signature", "Warning: ignored a key exchange from {user} with an invalid signature"),
+    ("e2e.key_changed", "Warning: the identity key of {user} has changed; refusing to exchange encryption keys with them. Once verified, accept it with /trust {user}"),
+    ("e2e.decrypt_failed", "Cannot decrypt an encrypted message from {user} (the session is no longer valid); re-establish it with /e2e {user}"),
+    ("server.unknown_vhost", "Connection from {addr} asked for unknown virtual host {host}, closed"),
+    ("server.socket_options_failed", "Failed to set socket options for connection from {addr}: {error}"),
+    ("server.connection_error", "Error handling connection from {addr}: {error}"),
+    ("server.accept_failed", "Failed to accept connection: {error}; retrying in {delay} ms"),
+    ("server.force_close", "{count} connections did not close in time, aborting them"),
+    ("server.exited", "All user connections released, server exiting."),
+    ("server.handler_panic", "Task handling connection from {addr} panicked ({count} so far): {message}"),
+    ("server.unknown_addr", "unknown address"),
+    ("server.no_panic_message", "(no message)"),
+    ("server.registered", "User {user} registered (codec: {codec})"),
+    ("server.write_failed", "Writing to user {user} failed, disconnected: {error}"),
+    ("server.notices_missed", "User {user} missed {count} system notices"),
+    ("server.write_stalled", "Write to user {user} did not finish within {grace}, disconnecting"),
+    ("server.slow", "User {user} is receiving messages too slowly"),
+    ("server.slow_disconnect", "User {user} is receiving messages too slowly, disconnecting"),
+    ("server.slow_timeout", "User {user} has been too slow for over {grace}, disconnecting"),
+    ("server.send_failed", "Failed to send message: {error}"),
+    ("server.disconnected", "User {user} disconnected (online {online}, {stats})"),
+    ("server.left", "User {user} logged out (online {online}, {stats})"),
+    ("server.save_user_failed", "Failed to save data of user {user}: {error}"),
+    ("server.save_room_failed", "Failed to save settings of room {room}: {error}"),
+    ("server.room_created", "{user} created room {room}"),
+    ("server.room_invite_redeemed", "{user} joined room {room} with invite code {code} (used {used}/{max} times)"),
+    ("server.room_encrypted", "End-to-end encrypted messages cannot be sent to a room"),
+    ("server.room_not_member", "You are not a member of room {room}; join it first with /join {room}"),
+    ("server.rooms_rejoined", "Rejoined {count} rooms with new messages:"),
+    ("server.rooms_rejoined_entry", "{room}: {count} new messages while you were away, {mentions} mentioning you"),
+    ("server.parse_failed", "Failed to parse JSON message: {error}"),
+    ("server.duplicate", "Ignoring duplicate message from {user} (id {id})"),
+    ("server.sanitized", "Stripped terminal control characters from {user}'s message to {to}"),
+    ("server.message_log", "[{time}] {from} -> {to}: {content}"),
+    ("server.log_write_failed", "Failed to write message log: {error}"),
+    ("server.log_read_failed", "Failed to read message log: {error}"),
+    ("server.replayed", "Replaying {count} messages to user {user}"),
+    (
+        "server.history_truncated",
+        "Some missed messages are no longer kept by the server; replaying the {count} from #{from} on",
+    ),
+    ("server.idle", "User {user} ({addr}) idle for over {secs} seconds, disconnecting"),
+    ("server.handler_aborted", "Task for user {user} ({addr}) ended abnormally, removed from online users ({stats})"),
+    ("server.dead_letter", "Message moved to dead letters #{id}: {from} -> {to} ({reason})"),
+    ("server.audit", "[audit] {entry}"),
+    ("server.audit_open_failed", "Cannot open audit log {path}: {error}; events are kept in memory only"),
+    ("server.audit_write_failed", "Failed to write audit log: {error}"),
+    // 服务器发给用户的提示
+    ("server.last_seen", "last seen {elapsed} ago"),
+    ("server.last_seen_logged_out", "last seen {elapsed} ago, logged out"),
+    ("server.too_large", "Message length {len} exceeds the limit, connection closed"),
+    ("server.content_too_large", "Message is {len} bytes, over the {max}-byte limit; not sent"),
+    ("server.content_too_large_log", "Rejected an oversized message from {user} ({len} bytes)"),
+    ("server.spam_throttled", "You are sending too fast; message not sent, try again in {remaining}"),
+    ("server.spam_alert", "[{action}] {user}: {rule}"),
+    ("server.undelivered", "Message could not be delivered to {user}: {reason}"),
+    ("server.auto_reply", "[Auto-reply] {text}"),
+    ("server.dnd_reply", "I'm in do-not-disturb mode, your message will be delivered later"),
+    ("server.offline", "User {user} is offline"),
+    ("server.offline_since", "User {user} is offline ({last_seen})"),
+    ("server.unknown_user", "User {user} does not exist"),
+    ("server.unknown_vhost_reply", "Virtual host {host} does not exist"),
+    ("server.invite_required", "This server is invite-only, please sign in with an invite code"),
+    ("server.maintenance", "The server is under maintenance and not accepting new connections, please try again later"),
+    ("server.plugin_denied", "The server refused the connection: {reason}"),
+    ("server.maintenance_until", "The server is under maintenance, back at {time}"),
+    ("server.ip_too_many", "Too many connections from your address (limit {limit})"),
+    ("server.ip_cooldown", "Connecting too often, please retry in {remaining}"),
+    ("server.pow_required", "This server requires new users to complete a proof of work, please upgrade your client"),
+    ("server.pow_failed", "Proof of work failed or timed out"),
+    ("server.identity_failed", "Identity proof failed or timed out"),
+    ("server.identity_required", "The name {user} is bound to an identity key, please sign in with the identity key it was registered with"),
+    ("server.name_reserved", "That name is not allowed: it must not be empty or \"Server\", start with #, or contain control characters or /"),
+    ("server.invite_unknown", "The invite code is invalid, used up or revoked"),
+    ("server.invite_expired", "The invite code has expired"),
+    ("server.partner_lost", "Connection to {user} was lost, your recent messages may not have been delivered"),
+    ("server.partner_left", "{user} has left the chat"),
+    ("server.partner_left_message", "{user} has left the chat: {message}"),
+    ("server.idle_notice", "Disconnected after {secs} seconds of inactivity"),
+    ("server.slow_notice", "Your connection is receiving messages too slowly and will be closed if it does not recover within {grace}"),
+    ("server.shutdown_notice", "The server is shutting down, all users have been disconnected"),
+    ("server.upgrade_notice", "The server is upgrading; you will be disconnected and the client will reconnect automatically"),
+    ("server.admin_socket_listening", "Listening on admin socket {path}"),
+    ("server.admin_socket_in_use", "Another server is listening on admin socket {path}; pass --takeover to replace it"),
+    ("server.admin_socket_error", "Failed to handle admin command: {error}"),
+    ("server.admin_socket_unsupported", "The admin socket is only supported on Unix"),
+    ("server.admin_status", "Process {pid}, {users} users online"),
+    ("server.admin_accept_stats", "{errors} accept failures, {refused} connections refused{refusing}"),
+    ("server.refusing_now", ", refusing new connections"),
+    ("server.admin_unknown_command", "Unknown admin command: {command}"),
+    ("server.chaos_enabled", "⚠ Fault injection enabled (testing only): drop {drop}, truncate {truncate}, duplicate {duplicate}, delay {delay} (up to {max_delay} ms)"),
+    ("server.chaos_dropped", "[chaos] Dropped a frame for {user}"),
+    ("server.chaos_truncated", "[chaos] Wrote only {kept}/{len} bytes of a frame for {user}, then disconnected"),
+    ("server.chaos_duplicated", "[chaos] Wrote a frame for {user} twice"),
+    ("server.chaos_disconnect", "frame truncated by fault injection"),
+    ("server.draining", "Received drain, no longer accepting connections; exiting after all users are disconnected..."),
+    ("server.announcement", "[Announcement] {user}: {text}"),
+    ("reason.queue_full", "recipient queue is full"),
+    ("reason.disconnected", "recipient disconnected"),
+    ("reason.slow_consumer", "recipient was too slow and has been disconnected"),
+    ("reason.too_large", "frame length {len} exceeds the limit"),
+    (
+        "reason.held_overflow",
+        "too many messages held while the recipient is in do-not-disturb",
+    ),
+    ("receipt.sent", "sent"),
+    ("receipt.queued", "queued offline"),
+    ("receipt.delivered", "delivered"),
+    ("receipt.read", "read"),
+    ("trace.received", "received"),
+    ("trace.queued", "queued for later delivery"),
+    ("trace.routed", "entered the route queue"),
+    ("trace.relayed", "relayed over federation"),
+    ("trace.spooled", "spooled for federation"),
+    ("trace.delivered", "written to the recipient's connection"),
+    ("audit.spam_flagged", "spam flagged"),
+    ("audit.spam_throttled", "spam throttled"),
+    ("audit.reported", "reported"),
+    ("audit.report_dismissed", "report dismissed"),
+    ("audit.quarantined", "message quarantined"),
+    ("audit.quarantine_released", "quarantine released"),
+    ("audit.quarantine_discarded", "quarantine discarded"),
+    ("audit.maintenance_started", "maintenance started"),
+    ("audit.maintenance_ended", "maintenance ended"),
+    ("audit.transfer_blocked", "transfer blocked"),
+    ("audit.transfer_quarantined", "file quarantined"),
+    ("maintenance.audit_on", "admin {admin} turned on maintenance mode{back}"),
+    ("maintenance.audit_off", "admin {admin} turned off maintenance mode"),
+    ("moderation.hold_reported", "pending report #{id}"),
+    ("moderation.held", "#{id} [{time}] {from} -> {to}: {content} ({cause})"),
+    ("moderation.hold_alert", "[quarantine #{id}] message from {from} to {to} awaits review: {cause} (see /quarantine)"),
+    ("moderation.audit_hold", "#{id} to {to}: {cause}"),
+    ("moderation.audit_release", "#{id} to {to} released by {admin}"),
+    ("moderation.audit_discard", "#{id} to {to} discarded by {admin}"),
+    ("server.quarantined", "Your message to {user} is awaiting review by an admin"),
+    ("moderation.report", "#{id} [{time}] {reporter} reported {reported}: {reason}"),
+    ("moderation.alert", "[report #{id}] {reporter} reported {reported}: {reason} (see /reports)"),
+    ("moderation.audit_report", "#{id} filed by {reporter}: {reason}"),
+    ("moderation.audit_dismiss", "#{id} dismissed by {admin}"),
+    ("spam.repeated", "sent the same message {count} times"),
+    ("spam.recipients", "messaged {count} different recipients"),
+    ("spam.links", "sent {count} links"),
+    ("job.vacuumed", "dropped {dropped} unreadable lines, log shrank by {bytes} bytes"),
+    ("job.expired", "removed {invites} expired invite codes and {letters} expired dead letters"),
+    ("job.pruned", "deleted {count} messages beyond the replay window"),
+    ("stats.summary", "in {in_count} msgs/{in_bytes}, out {out_count} msgs/{out_bytes}, parse errors {errors}, idle {idle}"),
+    ("stats.slow", ", slow for {slow}"),
+    ("server.telegram_encrypted", "End-to-end encrypted messages cannot be sent to the Telegram chat"),
+    ("server.telegram_busy", "Too many messages are waiting to be sent to Telegram, please try again later"),
+    ("server.xmpp_encrypted", "End-to-end encrypted messages cannot be sent to Jabber users"),
+    ("server.xmpp_busy", "Too many messages are waiting to be sent to the XMPP server, please try again later"),
+    ("server.xmpp_offline", "The XMPP gateway is not connected; the message to {user} was not sent"),
+    ("server.archive_started", "History archival enabled: messages older than {days} days will be uploaded to {location}"),
+    ("server.archived", "Archived {count} messages (ids {first} to {last}, {size} bytes compressed) to {key}"),
+    ("server.archive_failed", "Failed to archive history, will retry at the next check: {error}"),
+    ("server.archive_http_status", "Object storage returned HTTP {status} {reason}"),
+    ("server.jobs_started", "{count} maintenance jobs enabled: {jobs}"),
+    ("server.job_finished", "Maintenance job {job} finished in {elapsed} ms: {result}"),
+    ("server.job_failed", "Maintenance job {job} failed: {error}"),
+    // 服务器指令
+    ("cmd.admin_only", "Permission denied: only administrators can use this command"),
+    ("cmd.unknown", "Unknown command {command}"),
+    ("cmd.list_empty", "No other users online"),
+    ("cmd.list", "Online users ({count}):"),
+    ("cmd.list_bot", "{user} [bot]"),
+    ("cmd.peers_disabled", "This server is not federated"),
+    ("cmd.peers", "Federation peers (this server is {server}):"),
+    ("cmd.peer", "{server}: outbound link {outbound}, inbound link {inbound}, online: {users}"),
+    ("cmd.peer_up", "up"),
+    ("cmd.peer_down", "down"),
+    ("cmd.peer_nobody", "nobody"),
+    ("cmd.peer_spooled", ", {count} spooled for forwarding"),
+    ("cmd.usage_whois", "Usage: /whois <user>"),
+    ("cmd.whois", "Profile of {user}:"),
+    ("cmd.whois_display_name", "Display name: {name}"),
+    ("cmd.whois_avatar", "Avatar: {avatar}"),
+    ("cmd.whois_pronouns", "Pronouns: {pronouns}"),
+    ("cmd.whois_bio", "Bio: {bio}"),
+    ("cmd.whois_status", "Status: {status}"),
+    ("cmd.whois_idle", "Idle: {idle}"),
+    ("cmd.whois_client", "Client: {client}"),
+    ("cmd.whois_origin", "Origin: {origin}"),
+    ("cmd.whois_bot", "Account: bot"),
+    ("cmd.whois_bot_admin", "Account: bot (scopes: {scopes}, created by {admin})"),
+    ("cmd.status_away", "away ({text})"),
+    ("cmd.status_dnd", "do not disturb"),
+    ("cmd.status_online", "online"),
+    ("cmd.status_offline", "offline"),
+    ("cmd.status_offline_since", "offline ({last_seen})"),
+    ("cmd.display_name_cleared", "Display name cleared"),
+    ("cmd.display_name_set", "Display name set to {name}"),
+    ("cmd.usage_profile", "Usage: /profile <name|avatar|pronouns|bio> [<value>] (empty value clears)"),
+    ("cmd.profile_set", "{field} set to {value}"),
+    ("cmd.profile_cleared", "{field} cleared"),
+    ("cmd.profile_too_long", "{field} must be at most {max} characters"),
+    ("cmd.profile_bad_avatar", "Avatar must be an emoji (at most {chars} characters) or an image link starting with http:// or https:// (at most {len} bytes)"),
+    ("cmd.profile_control", "{field} must not contain line breaks or control characters"),
+    ("cmd.privacy", "Current privacy settings:"),
+    ("cmd.privacy_public", "public"),
+    ("cmd.privacy_hidden", "hidden"),
+    ("cmd.privacy_updated", "Privacy setting {field} updated to {value}"),
+    ("cmd.privacy_unknown", "Unknown privacy setting {field}"),
+    ("cmd.usage_privacy", "Usage: /privacy [<status|idle|client> <on|off>]"),
+    ("cmd.notify", "Notification preferences (shared by all your devices):"),
+    ("cmd.notify_none", "none"),
+    ("cmd.notify_muted", "Muted conversations: {list}"),
+    ("cmd.notify_keywords", "Keywords: {list}"),
+    ("cmd.notify_quiet", "Quiet hours: {hours}"),
+    ("cmd.notify_muted_set", "Muted {conversation}: its messages no longer notify unless they mention you or match a keyword"),
+    ("cmd.notify_already_muted", "{conversation} is already muted"),
+    ("cmd.notify_unmuted", "Unmuted {conversation}"),
+    ("cmd.notify_not_muted", "{conversation} is not muted"),
+    ("cmd.notify_keyword_added", "Added keyword {keyword}"),
+    ("cmd.notify_keyword_removed", "Removed keyword {keyword}"),
+    ("cmd.notify_keyword_unchanged", "Keyword {keyword} unchanged"),
+    ("cmd.notify_quiet_set", "Quiet hours set to {hours} (server time); messages during them will not notify"),
+    ("cmd.notify_quiet_cleared", "Quiet hours cleared"),
+    ("cmd.notify_invalid_quiet", "Invalid quiet hours: {value} (format: HH:MM-HH:MM, e.g. 22:00-07:00)"),
+    ("cmd.receipts", "Last {count} messages to {user}:"),
+    ("cmd.receipt", "#{id} [{time}] {preview} — {state} ({updated})"),
+    ("cmd.receipt_encrypted", "[encrypted]"),
+    ("cmd.receipts_empty", "No recent receipts for messages to {user}"),
+    ("cmd.usage_receipts", "Usage: /receipts <user> [<count>]"),
+    ("cmd.usage_trace", "Usage: /trace [<id>]"),
+    ("cmd.trace", "Route of message #{id} (to {to}):"),
+    ("cmd.trace_hop", "[{time}] +{elapsed} ms {node}: {event}"),
+    ("cmd.trace_unknown", "No route recorded for your message #{id}"),
+    ("cmd.traces", "Your last {count} traced messages:"),
+    ("cmd.trace_item", "#{id} to {to} [{time}] — {event} ({node})"),
+    ("cmd.traces_empty", "No traced messages recently"),
+    ("cmd.trace_disabled", "Route tracing is not enabled on this server"),
+    ("cmd.usage_report", "Usage: /report <user> <reason>"),
+    ("cmd.report_self", "You cannot report yourself"),
+    ("cmd.report_unknown", "User {user} does not exist"),
+    ("cmd.reported", "Reported {user} (#{id}, with {context} recent messages); an admin will review it soon"),
+    ("cmd.usage_notify", "Usage: /notify [mute <conversation>|unmute <conversation>|keyword add <keyword>|keyword remove <keyword>|quiet <HH:MM-HH:MM>|quiet off]"),
+    ("cmd.away_cleared", "Away status cleared"),
+    ("cmd.away_set", "Away status set, auto-reply: {text}"),
+    ("cmd.usage_dnd", "Usage: /dnd [on|off]"),
+    ("cmd.dnd_on", "Do not disturb enabled, messages will be held until you turn it off"),
+    ("cmd.dnd_off", "Do not disturb disabled, delivering {count} held messages"),
+    ("cmd.stats_denied", "Permission denied: you can only view your own statistics"),
+    ("cmd.stats", "Connection statistics of {user}:"),
+    ("cmd.stats_addr", "address: {addr}"),
+    ("cmd.stats_panics", "Server: connection handler panicked {count} times"),
+    ("cmd.stats_accept", "Server: {errors} accept failures, {refused} connections refused{refusing}"),
+    ("cmd.usage_announce", "Usage: /announce <text>"),
+    ("cmd.announced", "Announcement sent to {count} connections"),
+    ("cmd.deadletter_empty", "The dead letter queue is empty"),
+    ("cmd.deadletter", "Dead letters ({count}):"),
+    ("cmd.deadletter_missing", "No dead letter numbered #{id}"),
+    ("cmd.deadletter_offline", "User {user} is offline, message kept as #{id}"),
+    ("cmd.deadletter_replayed", "Redelivered #{id} to {user}"),
+    ("cmd.deadletter_replay_failed", "Redelivery failed ({reason}), message kept as #{id}"),
+    ("cmd.deadletter_dropped", "Deleted #{id}"),
+    ("cmd.deadletter_cleared", "Dead letter queue cleared ({count} entries)"),
+    ("cmd.audit_empty", "The audit log is empty"),
+    ("cmd.audit", "Audit log (last {count}):"),
+    ("cmd.audit_usage", "Usage: /audit [<count>]"),
+    ("cmd.reports_empty", "No pending reports"),
+    ("cmd.reports", "Pending reports ({count}):"),
+    ("cmd.report_missing", "Report #{id} does not exist"),
+    ("cmd.report_dismissed", "Report #{id} dismissed and removed from the queue"),
+    ("cmd.usage_reports", "Usage: /reports [dismiss <id>]"),
+    ("cmd.quarantine_empty", "No quarantined messages"),
+    ("cmd.quarantine", "Quarantined messages ({count}):"),
+    ("cmd.quarantine_missing", "Quarantined message #{id} does not exist"),
+    ("cmd.quarantine_released", "Released quarantined message #{id} to {user}"),
+    ("cmd.quarantine_discarded", "Discarded quarantined message #{id}"),
+    ("cmd.usage_quarantine", "Usage: /quarantine [release|discard <id>]"),
+    ("cmd.usage_maintenance", "Usage: /maintenance [on [HH:MM]|off]"),
+    ("cmd.maintenance_back", ", back at {time}"),
+    ("cmd.maintenance_on", "Maintenance mode on{back}: new registrations will be refused (except admins); the {users} online sessions are unaffected"),
+    ("cmd.maintenance_off", "Maintenance mode off, accepting new registrations again"),
+    ("cmd.maintenance_not_on", "Maintenance mode is not on"),
+    ("cmd.maintenance_status_off", "Maintenance mode is off"),
+    ("cmd.maintenance_status_on", "Maintenance mode turned on by {admin} at {since}{back}, {users} sessions still online"),
+    ("cmd.usage_deadletter", "Usage: /deadletter [replay <number>|drop <number>|clear]"),
+    ("cmd.usage_jobs", "Usage: /jobs [run <job>]"),
+    ("cmd.jobs", "Maintenance jobs ({count}):"),
+    ("cmd.jobs_empty", "No maintenance jobs are configured"),
+    ("cmd.job", "{job} ({schedule}): {runs} runs, {failures} failures, next run {next}"),
+    ("cmd.job_last", "; last run at {at} took {elapsed} ms: {result}"),
+    ("cmd.job_never", "none"),
+    ("cmd.job_error", "failed ({error})"),
+    ("cmd.job_in_progress", " (running)"),
+    ("cmd.job_unknown", "No maintenance job named {job} is configured"),
+    ("cmd.job_running", "Maintenance job {job} is already running; try again later"),
+    ("cmd.job_done", "Maintenance job {job} finished: {result}"),
+    ("cmd.job_failed", "Maintenance job {job} failed: {error}"),
+    ("cmd.usage_invite", "Usage: /invite [<uses> [<valid hours, 0 for no expiry>]|list|revoke <code>]"),
+    ("cmd.invite_created", "Created invite code {code} (usable {max} times, valid until {expires})"),
+    ("cmd.invite_not_required", "Note: the server is not invite-only (--invite-only), new users need no invite code"),
+    ("cmd.invite_failed", "Failed to create an invite code: {error}"),
+    ("cmd.invite_never", "never expires"),
+    ("cmd.invite_empty", "There are no valid invite codes"),
+    ("cmd.invite_list", "Valid invite codes ({count}):"),
+    ("cmd.invite_entry", "{code} used {used}/{max} times, valid until {expires}, created by {admin}"),
+    ("cmd.invite_revoked", "Revoked invite code {code}"),
+    ("cmd.invite_missing", "Invite code {code} does not exist or is no longer valid"),
+    ("cmd.usage_bot", "Usage: /bot [list|create <name> [<scopes>]|revoke <name>|scope <name> <scopes>], scopes being a comma-separated list of message, command"),
+    ("cmd.bot_invalid_scope", "Invalid scopes; choose from message, command (comma-separated)"),
+    ("cmd.bot_created", "Created bot {name} (scopes: {scopes}); the token is shown only this once, keep it safe: {token}"),
+    ("cmd.bot_exists", "Bot {name} already exists; run /bot revoke {name} first to issue a new token"),
+    ("cmd.bot_user_exists", "User {name} already exists and cannot be used as a bot account"),
+    ("cmd.bot_failed", "Failed to generate a token: {error}"),
+    ("cmd.bot_missing", "Bot {name} does not exist"),
+    ("cmd.bot_revoked", "Revoked the token of bot {name}; reissue one with /bot create {name}"),
+    ("cmd.bot_already_revoked", "The token of bot {name} is already revoked"),
+    ("cmd.bot_scoped", "Set the scopes of bot {name} to {scopes}"),
+    ("cmd.bot_empty", "There are no bot accounts"),
+    ("cmd.bot_list", "Bot accounts ({count}):"),
+    ("cmd.bot_entry", "{name} scopes: {scopes}, created by {admin}, {state}"),
+    ("cmd.bot_state_online", "online"),
+    ("cmd.bot_state_offline", "offline"),
+    ("cmd.bot_state_revoked", "token revoked"),
+    ("cmd.bot_only", "Only bot accounts can use this command"),
+    ("cmd.usage_register", "Usage: /register <command> [<description>], e.g. /register /weather Look up the weather"),
+    ("cmd.usage_unregister", "Usage: /unregister <command>"),
+    ("cmd.command_invalid", "Invalid command name {command}: it must start with / followed by up to 32 letters, digits, - or _"),
+    ("cmd.command_reserved", "{command} is a built-in command and cannot be registered"),
+    ("cmd.command_taken", "Command {command} is already registered by bot {bot}"),
+    ("cmd.command_registered", "Registered command {command}; invocations will be routed to you"),
+    ("cmd.command_unregistered", "Unregistered command {command}"),
+    ("cmd.command_not_registered", "You have not registered command {command}"),
+    ("cmd.command_own", "{command} is a command you registered yourself"),
+    ("cmd.command_bot_offline", "Command {command} is handled by bot {bot}, which is currently offline"),
+    ("cmd.commands_empty", "No bot commands are registered"),
+    ("cmd.commands", "Bot commands ({count}):"),
+    ("cmd.command_entry", "{command} ({bot})"),
+    ("cmd.usage_webhook", "Usage: /webhook [list|create <name> <user>|revoke <name>]"),
+    ("cmd.webhook_invalid_name", "Invalid webhook name {name}: use up to 32 letters, digits, - or _"),
+    ("cmd.webhook_exists", "Webhook {name} already exists"),
+    ("cmd.webhook_created", "Created webhook {name}, posting to {user}; the path is shown only this once, keep it safe: POST {path}"),
+    ("cmd.webhook_not_listening", " (the server is not listening for webhooks; restart it with --hooks to use them)"),
+    ("cmd.webhook_failed", "Failed to generate a token: {error}"),
+    ("cmd.webhook_missing", "Webhook {name} does not exist"),
+    ("cmd.webhook_revoked", "Deleted webhook {name}; its token no longer works"),
+    ("cmd.webhook_empty", "There are no webhooks"),
+    ("cmd.webhook_list", "Webhooks ({count}):"),
+    ("cmd.webhook_entry", "{name} → {user}, created by {admin}"),
+    ("cmd.usage_telegram", "Usage: /telegram [join|leave]"),
+    ("cmd.telegram_disabled", "This server has no Telegram bridge configured"),
+    ("cmd.telegram_joined", "Joined the Telegram bridge room: messages from the chat are relayed to you, and messages to {room} (or replies to tg: users) go to the chat; /telegram leave to leave"),
+    ("cmd.telegram_already_joined", "You are already in the Telegram bridge room"),
+    ("cmd.telegram_left", "Left the Telegram bridge room"),
+    ("cmd.telegram_not_joined", "You are not in the Telegram bridge room"),
+    ("cmd.telegram_connected", "connected"),
+    ("cmd.telegram_disconnected", "not connected"),
+    ("cmd.telegram_status", "Telegram chat {chat} ({state}), room address {room}"),
+    ("cmd.telegram_no_members", "The room has no members yet"),
+    ("cmd.telegram_members", "Members ({count}): {members}"),
+    ("cmd.usage_join", "Usage: /join <#room>|<invite code> (up to 32 letters, digits, - or _)"),
+    ("cmd.usage_part", "Usage: /part <#room>"),
+    ("cmd.usage_room", "Usage: /room <#room> [retention <count>|retention default|private on|off]"),
+    ("cmd.usage_invite_link", "Usage: /invite-link <#room> [<uses> [<hours valid>]], /invite-link list or /invite-link revoke <code> (at least 1 use; 0 hours means it never expires)"),
+    ("cmd.room_created", "Created and joined room {room}: messages to {room} go to the other members; /room {room} retention <count> changes how many messages are replayed on login"),
+    ("cmd.room_joined", "Joined room {room} ({count} members); /part {room} to leave"),
+    ("cmd.room_already_joined", "You are already in room {room}"),
+    ("cmd.room_left", "Left room {room}"),
+    ("cmd.room_not_joined", "You are not in room {room}"),
+    ("cmd.room_missing", "Room {room} does not exist"),
+    ("cmd.room_owner_only", "Only {owner}, who created room {room}, and admins can change its settings"),
+    ("cmd.room_retention_set", "Members of room {room} get at most the last {count} messages replayed on login (messages mentioning them are always replayed)"),
+    ("cmd.room_retention_invalid", "The replay count must be a positive number, use retention default to restore the default"),
+    ("cmd.room_info", "{visibility} room {room}, created by {owner}; replays at most the last {retention} messages on login"),
+    ("cmd.room_private_label", "Private"),
+    ("cmd.room_public_label", "Public"),
+    ("cmd.room_private", "Room {room} is private; you need an invite code from its creator or an admin: /join <code>"),
+    ("cmd.room_private_on", "Room {room} is now private; others must join with a code from /invite-link {room}"),
+    ("cmd.room_private_off", "Room {room} is now public; anyone can /join {room}"),
+    ("cmd.room_invite_created", "Created invite code {code} for room {room}: {max} uses, valid until {expires}; redeem with /join {code}"),
+    ("cmd.room_invite_public", "Note: room {room} is public and can be joined without a code; /room {room} private on makes it private"),
+    ("cmd.room_invite_entry", "{code} → {room}: used {used}/{max} times, valid until {expires}, created by {user}"),
+    ("cmd.room_invite_empty", "No valid room invite codes"),
+    ("cmd.room_no_members", "The room has no members"),
+    ("cmd.room_members", "Members ({count}): {members}"),
+    ("cmd.rooms_empty", "You have not joined any rooms; /join <#room> to join or create one"),
+    ("cmd.rooms_list", "Your rooms ({count}):"),
+    ("cmd.rooms_entry", "{room} ({count} members, {online} online)"),
+    ("cmd.xmpp_disabled", "This server has no XMPP gateway configured"),
+    ("cmd.xmpp_linked", "connected"),
+    ("cmd.xmpp_unlinked", "not connected"),
+    ("cmd.xmpp_status", "XMPP gateway {domain} ({state}), your Jabber address is {jid}"),
+    ("cmd.xmpp_subscribers", "Contacts subscribed to your presence ({count}): {contacts}"),
+    ("cmd.xmpp_contacts", "Online Jabber contacts ({count}):"),
+    ("cmd.xmpp_no_contacts", "No presence received from Jabber contacts yet"),
+    ("cmd.xmpp_show_online", "online"),
+    ("cmd.xmpp_show_away", "away"),
+    ("cmd.xmpp_show_dnd", "do not disturb"),
+    // 时长
+    ("duration.days", "{days}d {hours}h"),
+    ("duration.hours", "{hours}h {minutes}m"),
+    ("duration.minutes", "{minutes}m {seconds}s"),
+    ("duration.seconds", "{seconds}s"),
+    // 脚本
+    ("script.error", "Script line {line}: {reason}"),
+    ("script.usage_send", "usage: send <recipient> [text]"),
+    ("script.usage_expect", "usage: expect <sender|*> [text]"),
+    ("script.invalid_duration", "invalid duration"),
+    ("script.unknown_step", "unknown step {step}"),
+    ("script.send_failed", "send failed: {error}"),
+    ("script.disconnected", "connection closed"),
+    ("script.expect_timeout", "no message from {from} containing \"{text}\" within {timeout}"),
+    // 连接与编码
+    ("transport.invalid_address", "Invalid server address {addr}, expected host:port"),
+    ("transport.dns", "Cannot resolve host name {host}: {error}"),
+    ("transport.refused", "Server {addr} refused the connection, check that it is running and the port is correct"),
+    ("transport.timed_out", "Timed out connecting to the server ({secs} s), check the address and network"),
+    ("transport.tls", "TLS handshake failed: {error}"),
+    ("transport.io", "Failed to connect to the server: {error}"),
+    ("transport.no_records", "no address records found"),
+    ("transport.tls_disabled", "this build does not enable the tls feature"),
+    ("srv.not_provided", "the SRV record of {domain} says it provides no chat service"),
+    ("srv.unsupported", "SRV lookups are not supported on this system"),
+    ("srv.invalid_name", "invalid domain name: {name}"),
+    ("srv.server_error", "the name server returned an error (response code {code})"),
+    ("codec.io", "I/O error: {error}"),
+    ("codec.encode", "serialization failed: {error}"),
+    ("codec.decode", "failed to parse frame: {error}"),
+    ("codec.too_large", "frame length {len} exceeds the limit {max}"),
+    // 别名与密钥
+    ("alias.missing_eq", "missing `=`"),
+    ("alias.invalid_name", "invalid alias name `{name}`"),
+    ("alias.error", "{path} line {line}: {reason}"),
+    ("secrets.keyring", "keyring operation failed: {error}"),
+    ("secrets.io", "failed to read or write the secrets file: {error}"),
+    ("secrets.decrypt", "decryption failed, wrong passphrase or corrupted file"),
+    ("secrets.format", "invalid secrets file: {error}"),
+    ("secrets.keyring_disabled", "this build does not enable the keyring feature"),
+    ("secrets.unsupported_version", "unsupported version {version}"),
+    ("secrets.encrypt_failed", "encryption failed"),
+    ("secrets.bad_hex", "field {field} is not valid hex data"),
+    ("secrets.prompt", "Secret store passphrase: "),
+    ("secrets.not_text", "entry {entry} is not valid UTF-8 text"),
+    ("secrets.passphrase_required", "standard input is not a terminal, provide the secret store passphrase in the {env} environment variable"),
+    ("secrets.no_data_dir", "cannot determine the data directory"),
+    ("storage.invalid_key", "storage keys must be 64 hex digits (32 bytes)"),
+    ("storage.no_keys", "no storage key was provided"),
+    ("storage.env_missing", "environment variable {name} is not set"),
+    ("storage.command_failed", "key command failed: {error}"),
+    ("storage.invalid_source", "invalid key source {source}, expected env:NAME or cmd:COMMAND"),
+    ("storage.unknown_key", "the message log contains messages encrypted with unknown key {id}; please provide that key"),
+    ("storage.decrypt_failed", "failed to decrypt the message log with key {id}; wrong key or tampered file"),
+    ("storage.encrypted_log", "the message log is encrypted; provide the storage keys with --storage-keys"),
+    ("storage.no_data_dir", "data directory {dir} does not exist"),
+    ("storage.data_dir_not_empty", "data directory {dir} already contains data; pass --force to overwrite it"),
+    ("storage.snapshot_version", "snapshot format version {version} is newer than the supported version {supported}; upgrade before restoring"),
+    // 一致性检查
+    ("conformance.fixtures", "Fixtures"),
+    ("conformance.exchanges", "Exchanges ({addr}, {codec})"),
+    ("conformance.pass", "ok"),
+    ("conformance.fail", "FAILED"),
+    ("conformance.summary", "{total} checks, {failed} failed"),
+    ("conformance.decode_failed", "cannot parse fixture: {error}"),
+    ("conformance.encoding_differs", "{codec} encoding differs from fixture: {actual}"),
+    ("conformance.connect_failed", "cannot connect to {addr}: {error}"),
+    ("conformance.closed", "server closed the connection before sending {expected}"),
+    ("conformance.timeout", "timed out waiting for {expected}"),
+    ("conformance.not_closed", "server did not close the connection"),
+    ("conformance.properties", "Properties ({cases} random cases each)"),
+    ("conformance.roundtrip_empty", "{codec}: decoding produced no frame"),
+    ("conformance.roundtrip_trailing", "{codec}: bytes left over after decoding one frame"),
+    ("conformance.roundtrip_differs", "{codec}: decoded value differs, expected {expected}, got {actual}"),
+    // 压测
+    ("bench.summary", "Delivered {delivered} messages in {secs} s, throughput {throughput} msg/s"),
+    ("bench.latency", "Latency (µs): mean {mean}, p50 {p50}, p90 {p90}, p99 {p99}, max {max}"),
+    ("bench.too_few_clients", "The benchmark needs at least 2 clients"),
+    ("bench.timeout", "Not all messages were delivered within {secs} seconds"),
+    ("bench.refused", "Client {name} failed to register, the server closed the connection"),
+    ("bench.closed", "The server closed the connection after client {name} received only {received}/{expected} messages"),
+    ("bench.compare", "Compared with baseline {path}:"),
+    ("bench.mismatch", "Note: the baseline was run with different parameters, the comparison is only indicative"),
+    ("bench.regressed", "regressed"),
+    ("bench.regressions", "{count} metrics regressed by more than {threshold}%"),
+    ("bench.no_regression", "No metric regressed by more than {threshold}%"),
+    // 订阅源机器人
+    ("feedbot.started", "Polling {count} feeds every {interval} seconds"),
+    ("feedbot.primed", "First fetch of {url}: recorded {count} existing entries (not posted)"),
+    ("feedbot.posting", "{url} has {count} new entries"),
+    ("feedbot.entry", "📰 {feed}: {title}\n{link}"),
+    ("feedbot.entry_no_link", "📰 {feed}: {title}"),
+    ("feedbot.fetch_failed", "Failed to fetch {url}: {error}"),
+    ("feedbot.parse_failed", "Failed to parse {url}: {error}"),
+    ("feedbot.send_failed", "Failed to send a new entry to {user}: {error}"),
+    ("feedbot.save_failed", "Failed to save feed state {path}: {error}"),
+    ("feedbot.reconnect_failed", "Failed to reconnect to the server, will retry next round: {error}"),
+    ("link.bad_url", "Invalid URL {url} (must start with http:// or https://)"),
+    ("link.http_status", "The server returned HTTP {status}"),
+    ("link.too_many_redirects", "Too many redirects"),
+];
@@ -0,0 +1,531 @@
+/*!
+# 身份密钥与消息签名
+
+每个用户在客户端本地持有一把 Ed25519 身份密钥（首次启动时生成，私钥保存在 [`SecretStore`]
+即系统密钥环或口令加密的 `secrets.enc` 中，以身份文件路径区分，默认路径为
+`$XDG_DATA_HOME/async-chat/<用户名>/identity.key`），发出的每条消息都附上签名
+（[`Signature`]：签名者的公钥与签名），接收方显示前验证。签名覆盖发送者与接收者的用户名
+（不含 `@服务器` 部分）、时间戳、消息类型与内容，不包括服务器会改写的消息编号，
+因此服务器（或联邦中转发消息的其他服务器）无法冒充用户或篡改消息内容，只能丢弃消息。
+
+公钥的可信度采用"首次使用即信任"（TOFU）：第一次收到某用户签名的消息时记住其公钥
+（[`KnownKeys`]，保存在同一目录下的 `known_keys` 文件中），之后该用户改用其他公钥签名、
+签名无效或不再签名时以警告显示。双方可以通过 `/fingerprint <用户>` 查看公钥指纹并经
+电话、当面等带外渠道核对；核对无误后用 `/verify <用户> <指纹>` 标记为已验证（保存在同目录下的
+`verified_keys` 文件中），此后该用户以这把公钥签名的消息才显示 ✓。已验证的联系人更换公钥时
+以醒目的警告提示；确认对方确实更换了密钥后，用 `/trust <用户>` 接受新的公钥，新公钥需要重新验证。
+
+为防止网络上的攻击者重新注入截获的旧消息，签名还覆盖一个随机数与签名时的 Unix 时间。
+接收方按发送者记住 [`REPLAY_WINDOW`] 内见过的随机数（保存在 `known_keys` 同目录下的
+`seen_nonces` 文件中，重启后仍然有效），丢弃随机数重复的消息，以及签名时间早于该发送者
+最新消息超过 [`REPLAY_WINDOW`] 或晚于当前时间超过 [`MAX_CLOCK_SKEW`] 的消息。窗口以发送者
+自己最新的消息为准，离线期间由服务器暂存、重连后补发的旧消息不会因此被误判为过期。
+*/
+
+use crate::protocol::{Recipient, Signature};
+use crate::secrets::{decode_hex, encode_hex, SecretError, SecretStore};
+use crate::{Message, MessageKind};
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 签名内容的前缀，区分签名的用途与格式版本
+const DOMAIN: &str = "async-chat-message-v2";
+
+/// 登录时身份证明的签名前缀，与消息签名区分，防止把一方的签名挪作另一方使用
+const LOGIN_DOMAIN: &str = "async-chat-login-v1";
+
+/// 重放窗口：签名时间早于同一发送者最新消息超过该时长的消息视为过期，窗口内按随机数识别重放
+pub const REPLAY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// 签名时间允许晚于接收方当前时间的最大偏差（双方时钟不同步时）
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// 用户的身份密钥
+pub struct Identity {
+    key: SigningKey,
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Identity")
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+impl Identity {
+    /// 生成一把新的身份密钥
+    pub fn generate() -> io::Result<Self> {
+        let mut seed = [0; 32];
+        getrandom::getrandom(&mut seed).map_err(io::Error::from)?;
+        Ok(Self {
+            key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// 从密钥存储 `store` 载入身份密钥（32 字节种子），不存在时生成并保存一把新的
+    ///
+    /// `path` 标识这把密钥：种子保存在以该路径命名的条目中（见 [`Identity::secret_entry`]），
+    /// 不以明文写入磁盘。旧版本把种子以十六进制明文保存在 `path` 上，该文件存在时导入密钥存储后删除。
+    pub fn load_or_create(store: &SecretStore, path: &Path) -> Result<Self, SecretError> {
+        let entry = Self::secret_entry(path);
+        if let Some(seed) = store.get(&entry)? {
+            let seed = <[u8; 32]>::try_from(seed).map_err(|_| {
+                SecretError::Format(crate::tr!("identity.bad_key_entry", entry = entry))
+            })?;
+            return Ok(Self {
+                key: SigningKey::from_bytes(&seed),
+            });
+        }
+        let identity = match fs::read_to_string(path) {
+            Ok(text) => {
+                let seed = decode_hex(text.trim())
+                    .and_then(|seed| <[u8; 32]>::try_from(seed).ok())
+                    .ok_or_else(|| {
+                        SecretError::Format(crate::tr!(
+                            "identity.bad_key_file",
+                            path = path.display()
+                        ))
+                    })?;
+                Self {
+                    key: SigningKey::from_bytes(&seed),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::generate()?,
+            Err(e) => return Err(e.into()),
+        };
+        store.set(&entry, identity.key.as_bytes())?;
+        // 种子已保存到密钥存储后再删除明文文件，中途失败时下次启动仍可导入
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        Ok(identity)
+    }
+
+    /// 身份密钥在密钥存储中的条目名：`identity:` 加上 `path` 的绝对路径，同一用户可以有多把密钥
+    pub fn secret_entry(path: &Path) -> String {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        format!("identity:{}", path.display())
+    }
+
+    /// 用户 `owner` 的默认身份密钥文件
+    pub fn default_path(owner: &str) -> Option<PathBuf> {
+        crate::data_dir(owner).map(|dir| dir.join("identity.key"))
+    }
+
+    /// 公钥（十六进制）
+    pub fn public_key(&self) -> String {
+        encode_hex(self.key.verifying_key().as_bytes())
+    }
+
+    /// 公钥指纹，见 [`fingerprint`]
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key())
+    }
+
+    /// 为任意数据签名，返回（公钥，签名），均为十六进制
+    pub fn sign_bytes(&self, bytes: &[u8]) -> (String, String) {
+        let sig = self.key.sign(bytes);
+        (self.public_key(), encode_hex(&sig.to_bytes()))
+    }
+
+    /// 登录时证明持有私钥：为服务器发来的身份挑战 `nonce` 与用户名 `name` 签名，返回十六进制的签名
+    pub fn sign_login(&self, name: &str, nonce: &str) -> String {
+        encode_hex(&self.key.sign(&login_bytes(name, nonce)).to_bytes())
+    }
+
+    /// 为 `message` 签名，附上新的随机数与当前时间
+    pub fn sign(&self, message: &Message) -> Signature {
+        let signed_at = unix_now();
+        let mut nonce = [0u8; 16];
+        // 取随机数失败时退回到当前时间的纳秒数，同一发送者短时间内仍不会重复
+        if getrandom::getrandom(&mut nonce).is_err() {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos());
+            nonce = nanos.to_le_bytes();
+        }
+        let nonce = encode_hex(&nonce);
+        let sig = self.key.sign(&signed_bytes(message, &nonce, signed_at));
+        Signature {
+            key: self.public_key(),
+            nonce,
+            signed_at,
+            sig: encode_hex(&sig.to_bytes()),
+        }
+    }
+}
+
+/// 公钥指纹：公钥 SHA-256 的前 16 字节，每 2 字节一组，例如 `3f2a 91c0 ...`
+pub fn fingerprint(key: &str) -> String {
+    let digest = Sha256::digest(decode_hex(key).unwrap_or_default());
+    digest[..16]
+        .chunks(2)
+        .map(encode_hex)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 当前的 Unix 时间（秒）
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// 被签名的内容：发送者与接收者的用户名（去掉 `@服务器`）、时间戳、消息类型、内容、
+/// 随机数与签名时间
+fn signed_bytes(message: &Message, nonce: &str, signed_at: i64) -> Vec<u8> {
+    let user = |name: &str| match Recipient::parse(name) {
+        Recipient::Remote { user, .. } => user.to_string(),
+        _ => name.to_string(),
+    };
+    let kind = match message.kind() {
+        MessageKind::Text => "text",
+        MessageKind::Action => "action",
+        MessageKind::Encrypted => "encrypted",
+    };
+    let fields = [
+        DOMAIN,
+        &user(message.from()),
+        &user(message.to()),
+        message.time_stamp(),
+        kind,
+        message.content(),
+        nonce,
+        &signed_at.to_string(),
+    ];
+    fields.join("\0").into_bytes()
+}
+
+/// 验证消息签名的结果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// 消息没有签名
+    Unsigned,
+    /// 签名无效（公钥或签名格式错误，或与内容不符）
+    Invalid,
+    /// 签名有效，附带签名者的公钥
+    Valid(String),
+}
+
+/// 验证 `message` 的签名
+pub fn verify(message: &Message) -> Verification {
+    let Some(signature) = message.signature() else {
+        return Verification::Unsigned;
+    };
+    let bytes = signed_bytes(message, &signature.nonce, signature.signed_at);
+    if verify_bytes(&signature.key, &signature.sig, &bytes) {
+        Verification::Valid(signature.key.clone())
+    } else {
+        Verification::Invalid
+    }
+}
+
+/// 验证登录时的身份证明：`sig` 是否为公钥 `key` 对用户名 `name` 与挑战 `nonce` 的签名
+pub fn verify_login(key: &str, name: &str, nonce: &str, sig: &str) -> bool {
+    verify_bytes(key, sig, &login_bytes(name, nonce))
+}
+
+/// 登录身份证明中被签名的内容
+fn login_bytes(name: &str, nonce: &str) -> Vec<u8> {
+    [LOGIN_DOMAIN, name, nonce].join("\0").into_bytes()
+}
+
+/// 验证公钥 `key` 对 `bytes` 的签名 `sig`（均为十六进制），格式错误时视为无效
+pub fn verify_bytes(key: &str, sig: &str, bytes: &[u8]) -> bool {
+    let key = decode_hex(key)
+        .and_then(|key| <[u8; 32]>::try_from(key).ok())
+        .and_then(|key| VerifyingKey::from_bytes(&key).ok());
+    let sig = decode_hex(sig)
+        .and_then(|sig| <[u8; 64]>::try_from(sig).ok())
+        .map(|sig| ed25519_dalek::Signature::from_bytes(&sig));
+    match (key, sig) {
+        (Some(key), Some(sig)) => key.verify(bytes, &sig).is_ok(),
+        _ => false,
+    }
+}
+
+/// 收到的消息的可信程度
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trust {
+    /// 签名有效，且与记住的公钥一致
+    Verified,
+    /// 第一次收到该用户签名的消息，已记住其公钥
+    FirstSeen(String),
+    /// 签名有效，但与记住的公钥不同
+    Changed(String),
+    /// 签名无效
+    Invalid,
+    /// 签名有效，但随机数在重放窗口内已经出现过
+    Replayed,
+    /// 签名有效，但签名时间超出重放窗口
+    Stale,
+    /// 没有签名，且尚未记住该用户的公钥
+    Unsigned,
+    /// 没有签名，但此前该用户的消息都有签名
+    Missing,
+}
+
+/// 某个发送者在重放窗口内的消息
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SeenNonces {
+    /// 该发送者最新消息的签名时间
+    latest: i64,
+    /// 窗口内见过的（签名时间，随机数）
+    nonces: Vec<(i64, String)>,
+}
+
+impl SeenNonces {
+    /// 检查签名的时间与随机数，未重放时记住该随机数
+    fn check(&mut self, signature: &Signature, now: i64) -> Result<(), Trust> {
+        let window = REPLAY_WINDOW.as_secs() as i64;
+        if signature.signed_at > now + MAX_CLOCK_SKEW.as_secs() as i64
+            || signature.signed_at < self.latest - window
+        {
+            return Err(Trust::Stale);
+        }
+        if self
+            .nonces
+            .iter()
+            .any(|(_, nonce)| *nonce == signature.nonce)
+        {
+            return Err(Trust::Replayed);
+        }
+        self.latest = self.latest.max(signature.signed_at);
+        let oldest = self.latest - window;
+        self.nonces.retain(|(signed_at, _)| *signed_at >= oldest);
+        self.nonces
+            .push((signature.signed_at, signature.nonce.clone()));
+        Ok(())
+    }
+}
+
+/// 标记用户公钥为已验证的结果
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verify {
+    /// 指纹一致，已标记为已验证
+    Verified,
+    /// 指纹与记住的公钥不一致，附带记住的公钥的指纹
+    Mismatch(String),
+    /// 尚未记住该用户的公钥
+    Unknown,
+}
+
+/// 已知用户的公钥（首次使用即信任）及其近期消息的随机数
+#[derive(Debug, Default)]
+pub struct KnownKeys {
+    /// 保存公钥的文件，`None` 时只保存在内存中
+    path: Option<PathBuf>,
+    /// 用户名到公钥
+    keys: Mutex<BTreeMap<String, String>>,
+    /// 用户名到经带外核对、标记为已验证的公钥，保存在公钥文件同目录下的 `verified_keys`
+    verified: Mutex<BTreeMap<String, String>>,
+    /// 签名与记住的公钥不同的用户最近使用的公钥，供 `/trust` 接受
+    changed: Mutex<BTreeMap<String, String>>,
+    /// 用户名到其重放窗口内的消息，保存在公钥文件同目录下的 `seen_nonces`
+    seen: Mutex<BTreeMap<String, SeenNonces>>,
+}
+
+impl KnownKeys {
+    /// 只保存在内存中的公钥表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 载入 `path` 中的公钥表（每行 `用户名 公钥`）及同目录下已验证的公钥与见过的随机数，
+    /// 文件不存在时为空
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let keys = read_keys(&path)?;
+        let verified = read_keys(&path.with_file_name("verified_keys"))?;
+        // 无法解析时从空表开始，只影响重启前窗口内的重放识别
+        let seen = match fs::read(path.with_file_name("seen_nonces")) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path: Some(path),
+            keys: Mutex::new(keys),
+            verified: Mutex::new(verified),
+            changed: Mutex::new(BTreeMap::new()),
+            seen: Mutex::new(seen),
+        })
+    }
+
+    /// 验证 `message` 的签名，识别重放，并与记住的发送者公钥比对；首次见到的公钥随即记住
+    pub fn check(&self, message: &Message) -> Trust {
+        let sender = message.from();
+        let mut keys = self.keys.lock().unwrap();
+        let verification = verify(message);
+        // 只记住与已知公钥一致（或首次见到）的签名的随机数，免得冒充者的消息挤占窗口
+        if let (Verification::Valid(key), Some(signature)) = (&verification, message.signature()) {
+            if keys.get(sender).is_none_or(|known| known == key) {
+                let mut seen = self.seen.lock().unwrap();
+                let entry = seen.entry(sender.to_string()).or_default();
+                if let Err(trust) = entry.check(signature, unix_now()) {
+                    return trust;
+                }
+                // 保存失败时仍在本次运行中记住该随机数
+                let _ = self.save_seen(&seen);
+            }
+        }
+        match (verification, keys.get(sender)) {
+            (Verification::Unsigned, Some(_)) => Trust::Missing,
+            (Verification::Unsigned, None) => Trust::Unsigned,
+            (Verification::Invalid, _) => Trust::Invalid,
+            (Verification::Valid(key), _) => self.pin(&mut keys, sender, key),
+        }
+    }
+
+    /// 将 `user` 用于有效签名的公钥 `key` 与记住的公钥比对；首次见到的公钥随即记住
+    ///
+    /// 用于消息以外的签名（如端到端加密的密钥交换，见 [`crate::e2e`]）
+    pub fn check_key(&self, user: &str, key: &str) -> Trust {
+        let mut keys = self.keys.lock().unwrap();
+        self.pin(&mut keys, user, key.to_string())
+    }
+
+    fn pin(&self, keys: &mut BTreeMap<String, String>, user: &str, key: String) -> Trust {
+        match keys.get(user) {
+            Some(known) if *known == key => Trust::Verified,
+            Some(_) => {
+                self.changed
+                    .lock()
+                    .unwrap()
+                    .insert(user.to_string(), key.clone());
+                Trust::Changed(key)
+            }
+            None => {
+                keys.insert(user.to_string(), key.clone());
+                // 保存失败时仍在本次运行中记住该公钥
+                let _ = self.save(keys);
+                Trust::FirstSeen(key)
+            }
+        }
+    }
+
+    /// 记住的 `user` 的公钥
+    pub fn get(&self, user: &str) -> Option<String> {
+        self.keys.lock().unwrap().get(user).cloned()
+    }
+
+    /// 接受 `user` 最近使用的新公钥，新公钥需要重新验证
+    ///
+    /// # 返回值
+    /// 该用户没有更换过公钥时返回 `Ok(None)`，否则返回新的公钥
+    pub fn trust(&self, user: &str) -> io::Result<Option<String>> {
+        let Some(key) = self.changed.lock().unwrap().remove(user) else {
+            return Ok(None);
+        };
+        let mut keys = self.keys.lock().unwrap();
+        keys.insert(user.to_string(), key.clone());
+        self.save(&keys)?;
+        self.unverify(user)?;
+        Ok(Some(key))
+    }
+
+    /// `user` 记住的公钥是否经带外核对标记为已验证
+    pub fn is_verified(&self, user: &str) -> bool {
+        let keys = self.keys.lock().unwrap();
+        let verified = self.verified.lock().unwrap();
+        matches!((keys.get(user), verified.get(user)), (Some(key), Some(checked)) if key == checked)
+    }
+
+    /// 与对方带外核对指纹后，把 `user` 记住的公钥标记为已验证；对方告知的指纹 `claimed` 忽略空白与大小写
+    pub fn verify(&self, user: &str, claimed: &str) -> io::Result<Verify> {
+        let Some(key) = self.get(user) else {
+            return Ok(Verify::Unknown);
+        };
+        let expected = fingerprint(&key);
+        let normalize = |text: &str| -> String {
+            text.chars()
+                .filter(|c| !c.is_whitespace())
+                .flat_map(char::to_lowercase)
+                .collect()
+        };
+        if normalize(claimed) != normalize(&expected) {
+            return Ok(Verify::Mismatch(expected));
+        }
+        let mut verified = self.verified.lock().unwrap();
+        verified.insert(user.to_string(), key);
+        self.save_verified(&verified)?;
+        Ok(Verify::Verified)
+    }
+
+    /// 取消 `user` 的已验证标记，原本未标记时返回 `Ok(false)`
+    pub fn unverify(&self, user: &str) -> io::Result<bool> {
+        let mut verified = self.verified.lock().unwrap();
+        if verified.remove(user).is_none() {
+            return Ok(false);
+        }
+        self.save_verified(&verified)?;
+        Ok(true)
+    }
+
+    fn save(&self, keys: &BTreeMap<String, String>) -> io::Result<()> {
+        match &self.path {
+            Some(path) => write_keys(path, keys),
+            None => Ok(()),
+        }
+    }
+
+    fn save_verified(&self, verified: &BTreeMap<String, String>) -> io::Result<()> {
+        match &self.path {
+            Some(path) => write_keys(&path.with_file_name("verified_keys"), verified),
+            None => Ok(()),
+        }
+    }
+
+    fn save_seen(&self, seen: &BTreeMap<String, SeenNonces>) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(
+            path.with_file_name("seen_nonces"),
+            serde_json::to_vec(seen)?,
+        )
+    }
+}
+
+/// 读取每行 `用户名 公钥` 的文件，文件不存在时为空
+fn read_keys(path: &Path) -> io::Result<BTreeMap<String, String>> {
+    let mut keys = BTreeMap::new();
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            for line in text.lines() {
+                if let Some((user, key)) = line.trim().rsplit_once(' ') {
+                    keys.insert(user.to_string(), key.to_string());
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    Ok(keys)
+}
+
+/// 把 `keys` 整体写入 `path`，每行 `用户名 公钥`
+fn write_keys(path: &Path, keys: &BTreeMap<String, String>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text: String = keys
+        .iter()
+        .map(|(user, key)| format!("{} {}\n", user, key))
+        .collect();
+    fs::write(path, text)
+}
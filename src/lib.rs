@@ -6,20 +6,126 @@
 - **ArcString**
   封装 `Arc<String>`，用于避免在多处使用时重复克隆 `String`，提升性能。
 
-- **Message**
-  聊天消息结构体，包含发送者、接收者、时间戳和消息内容，支持序列化与反序列化。
+- **Message** 与 **MessageKind**
+  聊天消息结构体，包含发送者、接收者、时间戳、消息类型和消息内容，支持序列化与反序列化。
+
+- **Timestamp**
+  同时记下挂钟时间与单调时钟时刻的时间点：显示用挂钟时间，计算在线时长、延迟等经过的时间用单调时钟，
+  系统时间被调整时不受影响。
+
+- **alias**
+  客户端指令别名，在分发输入前展开。
+
+- **bench**（需启用 `metrics` 特性）
+  吞吐量与延迟压测：在进程内启动服务器与多个客户端，测量端到端延迟与吞吐量并与基线比较。
+
+- **script**
+  客户端脚本模式：按顺序执行 send/wait/expect 步骤，用于冒烟测试与自动化。
+
+- **theme**
+  客户端终端配色，支持从配置文件载入及 `NO_COLOR`。
+
+- **terminal**
+  终端操作（清除当前行、颜色能力检测、读取输入）：启用 `tui` 特性时基于 crossterm，兼容旧版 Windows 控制台，
+  否则按行读写。
 
 - **Task** 与 **TaskType**
-  用于区分运行模式（服务器或客户端）。
+  用于区分运行模式（服务器、客户端、一致性检查、订阅源机器人或管理命令）。
+
+- **client_core**
+  与传输方式无关的客户端协议核心（握手、断线续传、去重、工作量证明），不做 I/O，终端客户端与浏览器客户端共用。
+- **web**（仅 wasm32 目标）
+  浏览器中的客户端：经浏览器的 WebSocket 连接服务器的 `--ws` 地址，驱动 `client_core`，以 wasm-bindgen 导出 `WebClient`。
+
+- **codec**
+  客户端与服务器之间的分帧编码（JSON Lines 或长度前缀）。
+
+- **ffi**（需启用 `ffi` 特性）
+  供其他语言嵌入客户端的 C 接口（`chat_client_connect`、`chat_client_send` 与回调接收），头文件由 cbindgen 生成。
+
+- **python**（需启用 `chat-py` 特性）
+  Python 扩展模块 `chat`：以协程方式使用 `Client` 收发 `Message`，便于编写机器人与监控脚本。
+
+- **feedbot**（需启用 `feedbot` 特性）
+  内置的 RSS/Atom 订阅源机器人：定期抓取订阅源，把新条目发给指定用户，已见条目持久化；也是编写机器人的参考实现。
+
+- **conformance**（需启用 `conformance` 特性）
+  协议一致性检查：帧样例与针对服务器的交互用例，供本 crate 与第三方实现核对线上格式。
+
+- **history**
+  客户端本地聊天记录，按会话对象保存为 JSON Lines 文件。
+
+- **export**
+  把本地聊天记录中的一段会话渲染为带样式与时间戳的独立 HTML 文件（`/export`）。
+
+- **e2e**（需启用 `crypto` 特性）
+  客户端之间的端到端加密会话：经服务器交换签名的临时密钥，并定期重新交换以保证前向保密。
+
+- **emoji**
+  客户端发送前将 `:smile:` 等表情短代码展开为 Unicode 表情。
+
+- **dedup**
+  按消息编号去重的滑动窗口，避免重发或补发的消息被投递、显示两次。
+
+- **highlight**
+  客户端对 `@提及` 与关键词的高亮规则。
+
+- **notify**
+  保存在服务器端用户资料中的通知偏好（静音会话、关键词、免打扰时段），服务器据此为投递的消息附上提示建议。
+
+- **identity**（需启用 `crypto` 特性）
+  客户端身份密钥：为发出的消息签名、验证收到的消息签名，按首次使用即信任记住对方公钥。
+
+- **link**
+  识别消息中的链接，并通过系统默认浏览器打开（`/open`）。
+
+- **transfer**
+  客户端之间的文件传输：经服务器交换地址后尝试直连（NAT 打洞），失败时经服务器中转。
+
+- **sanitize**
+  服务器路由消息前清除内容中的终端转义序列、控制字符与双向控制字符，防止发送者操纵接收方的终端。
+
+- **srv**
+  客户端按 DNS SRV 记录（`_chat._tcp.域名`）发现服务器的主机与端口。
+
+- **secrets**（需启用 `crypto` 特性）
+  客户端敏感数据存储：操作系统密钥环，或口令保护的加密文件。
+
+- **markdown**
+  将消息中的基础 Markdown 解析为带样式的文本片段，供终端客户端渲染。
+
+- **pow**
+  握手时的工作量证明：服务器发出挑战，新连接的客户端求解后才能完成注册。
+
+- **preview**
+  客户端的图片预览：在支持 kitty 或 sixel 图形的终端中显示收到的图片与图片链接的缩略图，其他终端显示格式、尺寸与大小。
+
+- **protocol** 与 **session**
+  注册握手等控制帧，以及服务器端的连接级会话状态（`session` 需启用 `server` 特性）。
+
+- **profile** 与 **storage**
+  用户资料与隐私设置，以及服务器端的持久化存储（`storage` 需启用 `server` 特性）。
+
+- **i18n**
+  面向用户的提示文字的消息目录（中文、英文），按 `--lang` 或 `LANG` 选择语言。
+
+可选的子系统（服务器、加密、压测、终端界面、WebSocket、数据目录、桥接、GeoIP 等）通过 Cargo 特性启用，
+默认只编译核心的客户端，`full` 特性启用全部子系统（见 `Cargo.toml`）。依赖 Tokio 的网络部分（终端客户端、
+分帧编码、文件传输等）属于默认启用的 `net` 特性；服务器属于 `server` 特性，身份密钥、消息签名、端到端加密与
+密钥存储属于 `crypto` 特性，压测属于 `metrics` 特性。关闭默认特性时只剩协议、`client_core` 等与传输方式无关的模块，
+可以编译到 `wasm32-unknown-unknown`。
 
 详细文档请参见各结构体和函数的注释。
 */
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::env;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// `ArcString` 封装了 `Arc<String>`，用于高效共享字符串，避免不必要的克隆。
 ///
@@ -80,13 +186,50 @@ impl fmt::Display for ArcString {
     }
 }
 
+/// 消息类型
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    /// 普通文本消息
+    #[default]
+    Text,
+    /// 动作消息（`/me 挥手`），显示为 "* Alice 挥手"
+    Action,
+    /// 端到端加密的消息，内容为密文，解密后得到原本的类型与内容（见 [`e2e`]）
+    Encrypted,
+}
+
+impl MessageKind {
+    fn is_text(&self) -> bool {
+        *self == MessageKind::Text
+    }
+}
+
 /// 表示一条聊天消息，包含发送者、接收者、时间戳和内容
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Message {
     from: ArcString,
     to: String,
     time_stamp: String,
+    /// 普通文本消息不序列化该字段，与旧版本保持兼容
+    #[serde(default, skip_serializing_if = "MessageKind::is_text")]
+    kind: MessageKind,
+    /// 消息编号：客户端发出时为随机编号，供服务器对重发的消息去重；
+    /// 服务器转发时替换为递增的编号，客户端重连时据此请求补发（见 [`protocol::Hello::resume_from`]）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
     content: String,
+    /// 发送者身份密钥的签名（见 [`identity`]），服务器原样转发；装箱以免增大不带签名的消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Box<protocol::Signature>>,
+    /// 服务器按接收者的通知偏好附上的提示建议（见 [`notify`]），不在签名范围内
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify: Option<notify::NotifyHint>,
+    /// 开启路由追踪时服务器之间转发的消息经过的路由记录，投递给客户端前去掉；不在签名范围内。
+    /// 同样装箱，不带记录的消息只多占一个指针
+    #[allow(clippy::box_collection)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace: Option<Box<Vec<protocol::TraceHop>>>,
 }
 
 impl Message {
@@ -104,10 +247,83 @@ impl Message {
             from,
             to,
             content,
+            kind: MessageKind::Text,
+            id: None,
+            signature: None,
+            notify: None,
+            trace: None,
             time_stamp: Local::now().format("%H:%M:%S").to_string(),
         }
     }
 
+    /// 设置消息类型
+    pub fn with_kind(mut self, kind: MessageKind) -> Message {
+        self.kind = kind;
+        self
+    }
+
+    /// 更换消息内容（解密端到端加密的消息后换成明文）
+    pub fn with_content(mut self, content: String) -> Message {
+        self.content = content;
+        self
+    }
+
+    /// 更换发送者（服务器联邦转发时改写为 `用户@服务器`）
+    pub fn with_from(mut self, from: ArcString) -> Message {
+        self.from = from;
+        self
+    }
+
+    /// 更换接收者
+    pub fn with_to(mut self, to: String) -> Message {
+        self.to = to;
+        self
+    }
+
+    /// 设置服务器分配的消息编号
+    pub fn with_id(mut self, id: u64) -> Message {
+        self.id = Some(id);
+        self
+    }
+
+    /// 附上发送者的签名
+    pub fn with_signature(mut self, signature: protocol::Signature) -> Message {
+        self.signature = Some(Box::new(signature));
+        self
+    }
+
+    /// 获取发送者的签名
+    pub fn signature(&self) -> Option<&protocol::Signature> {
+        self.signature.as_deref()
+    }
+
+    /// 附上服务器计算的提示建议，[`notify::NotifyHint::Normal`] 表示不附加
+    pub fn with_notify(mut self, hint: notify::NotifyHint) -> Message {
+        self.notify = (hint != notify::NotifyHint::Normal).then_some(hint);
+        self
+    }
+
+    /// 获取服务器附上的提示建议，未附加时为 `None`（由客户端自己判断）
+    pub fn notify(&self) -> Option<notify::NotifyHint> {
+        self.notify
+    }
+
+    /// 更换路由记录，空列表表示去掉
+    pub fn with_trace(mut self, hops: Vec<protocol::TraceHop>) -> Message {
+        self.trace = (!hops.is_empty()).then(|| Box::new(hops));
+        self
+    }
+
+    /// 获取消息带有的路由记录，不带时为空
+    pub fn trace(&self) -> &[protocol::TraceHop] {
+        self.trace.as_deref().map_or(&[], Vec::as_slice)
+    }
+
+    /// 获取服务器分配的消息编号
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
     /// 获取发送者信息（只读）
     pub fn from(&self) -> &str {
         &self.from.0
@@ -123,17 +339,168 @@ impl Message {
         &self.time_stamp
     }
 
+    /// 获取消息类型
+    pub fn kind(&self) -> MessageKind {
+        self.kind
+    }
+
     /// 获取消息内容（只读）
     pub fn content(&self) -> &str {
         &self.content
     }
 }
 
-/// 定义任务类型，用于指定运行模式（服务器或客户端）
+/// 同时记下挂钟时间与单调时钟时刻的时间点
+///
+/// 挂钟时间用于显示与跨进程比较，经过的时间（在线时长、排队与投递延迟）一律按单调时钟计算：
+/// NTP 校时或手动修改系统时间使挂钟跳变时，按挂钟相减会得到负数或偏大的时长，单调时钟不会
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    wall: DateTime<Local>,
+    mono: Instant,
+}
+
+impl Timestamp {
+    /// 当前时间
+    pub fn now() -> Timestamp {
+        Timestamp {
+            wall: Local::now(),
+            mono: Instant::now(),
+        }
+    }
+
+    /// 以给定的挂钟时间与单调时钟时刻构造，供调用方使用自己的时间来源
+    pub fn from_parts(wall: DateTime<Local>, mono: Instant) -> Timestamp {
+        Timestamp { wall, mono }
+    }
+
+    /// 挂钟时间，用于显示
+    pub fn wall(&self) -> DateTime<Local> {
+        self.wall
+    }
+
+    /// 单调时钟时刻
+    pub fn instant(&self) -> Instant {
+        self.mono
+    }
+
+    /// 至今经过的时间（单调时钟）
+    pub fn elapsed(&self) -> Duration {
+        self.mono.elapsed()
+    }
+
+    /// 自 `earlier` 以来经过的时间（单调时钟），`earlier` 更晚时为零
+    pub fn since(&self, earlier: &Timestamp) -> Duration {
+        self.mono.saturating_duration_since(earlier.mono)
+    }
+}
+
+/// 以当前进程的语言将时长格式化为便于阅读的描述，见 [`humanize_duration_in`]
+pub fn humanize_duration(duration: Duration) -> String {
+    humanize_duration_in(i18n::lang(), duration)
+}
+
+/// 将时长格式化为便于阅读的描述，只保留最大的两个单位
+///
+/// # 参数
+/// - `lang`: 使用的语言
+/// - `duration`: 要格式化的时长
+///
+/// # 返回值
+/// 例如 "42 秒"、"5 分 3 秒"、"2 小时 10 分"、"3 天 4 小时"（英文为 "5m 3s" 等）
+pub fn humanize_duration_in(lang: i18n::Lang, duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+    );
+    if days > 0 {
+        tr!(lang => "duration.days", days = days, hours = hours)
+    } else if hours > 0 {
+        tr!(lang => "duration.hours", hours = hours, minutes = minutes)
+    } else if minutes > 0 {
+        tr!(lang => "duration.minutes", minutes = minutes, seconds = seconds)
+    } else {
+        tr!(lang => "duration.seconds", seconds = seconds)
+    }
+}
+
+/// 将字节数格式化为便于阅读的描述
+///
+/// # 返回值
+/// 例如 "512 B"、"2.3 KB"、"1.5 MB"
+pub fn humanize_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 客户端配置目录：`$XDG_CONFIG_HOME/async-chat`（默认 `~/.config/async-chat`），
+/// Windows 下为 `%APPDATA%\async-chat`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    }?;
+    Some(base.join("async-chat"))
+}
+
+/// 客户端用户 `owner` 的数据目录：`$XDG_DATA_HOME/async-chat/<用户名>`
+/// （默认 `~/.local/share/async-chat/<用户名>`），Windows 下为 `%APPDATA%\async-chat\<用户名>`
+///
+/// # 返回值
+/// 若无法确定用户数据目录（未设置相关环境变量），返回 `None`
+pub fn data_dir(owner: &str) -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    }?;
+    Some(base.join("async-chat").join(history::file_stem(owner)))
+}
+
+/// 读取 JSON 文件；文件不存在时返回 `Ok(None)`
+#[cfg(any(feature = "storage", feature = "feedbot"))]
+pub(crate) fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> std::io::Result<Option<T>> {
+    match std::fs::read(path) {
+        Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 先写入临时文件再重命名，避免写入中途崩溃留下损坏的文件
+#[cfg(any(feature = "storage", feature = "feedbot"))]
+pub(crate) fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// 定义任务类型，用于指定运行模式（服务器、客户端、协议一致性检查、订阅源机器人、管理命令或压测）
 #[derive(Debug)]
 pub enum TaskType {
     Server,
     Client,
+    Conformance,
+    FeedBot,
+    Admin,
+    Bench,
 }
 
 /// 辅助类型，用于从字符串转换为 `TaskType`
@@ -144,7 +511,7 @@ impl Task {
     /// 根据输入字符串返回对应的任务类型
     ///
     /// # 参数
-    /// - `task`: 输入字符串（"server" 或 "client"）
+    /// - `task`: 输入字符串（"server"、"client"、"conformance"、"feedbot"、"admin" 或 "bench"）
     ///
     /// # 返回值
     /// 若匹配成功，返回对应的 `TaskType`，否则返回 `None`
@@ -152,12 +519,107 @@ impl Task {
         match task.to_lowercase().as_str() {
             "server" => Some(TaskType::Server),
             "client" => Some(TaskType::Client),
+            "conformance" => Some(TaskType::Conformance),
+            "feedbot" => Some(TaskType::FeedBot),
+            "admin" => Some(TaskType::Admin),
+            "bench" => Some(TaskType::Bench),
             _ => None,
         }
     }
 }
 
+/// 声明 alias 模块
+pub mod alias;
+/// 声明 bench 模块
+#[cfg(feature = "metrics")]
+pub mod bench;
 /// 声明 client 模块
+#[cfg(feature = "net")]
 pub mod client;
+/// 声明 client_core 模块
+pub mod client_core;
+/// 声明 codec 模块
+#[cfg(feature = "net")]
+pub mod codec;
+/// 声明 conformance 模块
+#[cfg(feature = "conformance")]
+pub mod conformance;
+/// 声明 dedup 模块
+pub mod dedup;
+/// 声明 e2e 模块
+#[cfg(all(feature = "net", feature = "crypto"))]
+pub mod e2e;
+/// 声明 emoji 模块
+pub mod emoji;
+/// 声明 export 模块
+#[cfg(feature = "net")]
+pub mod export;
+/// 声明 feedbot 模块
+#[cfg(feature = "feedbot")]
+pub mod feedbot;
+/// 声明 ffi 模块
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// 声明 highlight 模块
+pub mod highlight;
+/// 声明 history 模块
+pub mod history;
+/// 声明 i18n 模块
+pub mod i18n;
+/// 声明 identity 模块
+#[cfg(feature = "crypto")]
+pub mod identity;
+/// 声明 link 模块
+#[cfg(feature = "net")]
+pub mod link;
+/// 声明 markdown 模块
+pub mod markdown;
+/// 声明 notify 模块
+pub mod notify;
+/// 声明 pow 模块
+pub mod pow;
+/// 声明 preview 模块
+#[cfg(feature = "net")]
+pub mod preview;
+/// 声明 profile 模块
+pub mod profile;
+/// 声明 protocol 模块
+pub mod protocol;
+/// 声明 python 模块
+#[cfg(feature = "chat-py")]
+pub mod python;
+/// 声明 sanitize 模块
+pub mod sanitize;
+/// 声明 script 模块
+#[cfg(feature = "net")]
+pub mod script;
+/// 声明 secrets 模块
+#[cfg(feature = "crypto")]
+pub mod secrets;
 /// 声明 server 模块
+#[cfg(feature = "server")]
 pub mod server;
+/// 声明 session 模块
+#[cfg(feature = "server")]
+pub mod session;
+/// 声明 srv 模块
+#[cfg(feature = "net")]
+pub mod srv;
+/// 声明 storage 模块
+#[cfg(feature = "server")]
+pub mod storage;
+/// 声明 terminal 模块
+#[cfg(feature = "net")]
+pub mod terminal;
+/// 声明 theme 模块
+#[cfg(feature = "net")]
+pub mod theme;
+/// 声明 transfer 模块
+#[cfg(feature = "net")]
+pub mod transfer;
+/// 声明 transport 模块
+#[cfg(feature = "net")]
+pub mod transport;
+/// 声明 web 模块
+#[cfg(target_arch = "wasm32")]
+pub mod web;
@@ -0,0 +1,225 @@
+/*!
+# 链接识别模块
+
+客户端在显示消息时识别其中的 `http://` 与 `https://` 链接，加上下划线并按出现顺序
+分配编号；终端里很难直接点击或复制长链接，`/open <编号>` 会用系统默认浏览器打开对应链接。
+
+[`fetch`] 以 HTTP/1.0 GET 抓取链接的内容，供订阅源机器人与图片预览使用；
+更一般的 [`request`] 发送任意方法、请求头与请求体的 HTTP/1.0 请求，供服务器的 Telegram 桥接
+与历史消息归档调用各自的 HTTP 接口。
+*/
+
+use crate::protocol::CLIENT_ID;
+use crate::tr;
+use crate::transport::{self, SocketOptions, TlsConfig};
+use std::io;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// 抓取时最多跟随的重定向次数
+pub const MAX_REDIRECTS: usize = 3;
+
+/// 单次抓取（包括重定向）的超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 查找文本中的链接，返回按出现顺序排列的字节范围
+///
+/// 链接在空白或非 ASCII 字符处结束（便于识别紧跟中文的链接），
+/// 末尾的标点及未配对的右括号不属于链接。
+pub fn find(text: &str) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find("http") {
+        let start = pos + offset;
+        let rest = &text[start..];
+        let is_scheme = rest.starts_with("http://") || rest.starts_with("https://");
+        let word_start = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_ascii_alphanumeric());
+        if !is_scheme || !word_start {
+            pos = start + "http".len();
+            continue;
+        }
+        let len = rest
+            .find(|c: char| !c.is_ascii_graphic() || matches!(c, '<' | '>' | '"'))
+            .unwrap_or(rest.len());
+        let url = trim_trailing(&rest[..len]);
+        if url.len() > rest.find("://").unwrap_or(0) + 3 {
+            found.push(start..start + url.len());
+        }
+        pos = start + len.max(1);
+    }
+    found
+}
+
+/// 去掉链接末尾的标点以及未配对的右括号
+fn trim_trailing(mut url: &str) -> &str {
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+        let unbalanced =
+            trimmed.ends_with(')') && trimmed.matches('(').count() < trimmed.matches(')').count();
+        let trimmed = if unbalanced {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+/// 用系统默认浏览器打开链接
+pub fn open(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // 在后台回收子进程，避免留下僵尸进程
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// 以 HTTP/1.0 GET 抓取 `url` 的内容（`https://` 需要启用 `tls` 特性），跟随重定向；
+/// `accept` 为请求的 `Accept` 头，响应（含响应头）最多读取 `max_len` 字节
+pub async fn fetch(url: &str, accept: &str, max_len: u64) -> io::Result<Vec<u8>> {
+    tokio::time::timeout(FETCH_TIMEOUT, fetch_inner(url, accept, max_len))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+async fn fetch_inner(url: &str, accept: &str, max_len: u64) -> io::Result<Vec<u8>> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let response = request("GET", &url, &[("Accept", accept)], &[], max_len).await?;
+        match response.status {
+            200 => return Ok(response.body),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = response.header("Location").unwrap_or_default();
+                url = match split_url(&url) {
+                    Some((https, authority, _)) if location.starts_with('/') => {
+                        let scheme = if https { "https" } else { "http" };
+                        format!("{}://{}{}", scheme, authority, location)
+                    }
+                    _ => location.to_string(),
+                };
+            }
+            status => return Err(io::Error::other(tr!("link.http_status", status = status))),
+        }
+    }
+    Err(io::Error::other(tr!("link.too_many_redirects")))
+}
+
+/// HTTP 应答
+#[derive(Debug)]
+pub struct Response {
+    /// 状态码
+    pub status: u16,
+    /// 应答头（名称，值）
+    pub headers: Vec<(String, String)>,
+    /// 应答体
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// 名称为 `name`（不区分大小写）的第一个应答头的值
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// 以 HTTP/1.0 向 `url` 发送一个请求（`https://` 需要启用 `tls` 特性），不跟随重定向
+///
+/// 自动加上 `Host` 与 `User-Agent` 头，`GET` 以外的请求还加上 `Content-Length`；
+/// 服务器不会使用分块编码，读到连接关闭即为完整的应答，应答（含应答头）最多读取 `max_len` 字节
+pub async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    max_len: u64,
+) -> io::Result<Response> {
+    let (https, authority, path) = split_url(url).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, tr!("link.bad_url", url = url))
+    })?;
+    let addr = match authority.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => authority.to_string(),
+        _ => format!("{}:{}", authority, if https { 443 } else { 80 }),
+    };
+    let tls = https.then(TlsConfig::new);
+    let (mut reader, mut writer) =
+        transport::connect(&addr, tls.as_ref(), &SocketOptions::default(), None)
+            .await
+            .map_err(io::Error::other)?;
+    let mut head = format!(
+        "{} {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: {}\r\n",
+        method, path, authority, CLIENT_ID
+    );
+    if method != "GET" {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body).await?;
+    writer.flush().await?;
+    let mut response = Vec::new();
+    (&mut reader)
+        .take(max_len)
+        .read_to_end(&mut response)
+        .await?;
+
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let head_len = match parsed.parse(&response) {
+        Ok(httparse::Status::Complete(len)) => len,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP")),
+    };
+    Ok(Response {
+        status: parsed.code.unwrap_or_default(),
+        headers: parsed
+            .headers
+            .iter()
+            .map(|header| {
+                let value = String::from_utf8_lossy(header.value).trim().to_string();
+                (header.name.to_string(), value)
+            })
+            .collect(),
+        body: response.split_off(head_len),
+    })
+}
+
+/// 拆分 URL 为（是否 https，`主机[:端口]`，路径）
+pub(crate) fn split_url(url: &str) -> Option<(bool, &str, &str)> {
+    let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else {
+        (false, url.strip_prefix("http://")?)
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    (!authority.is_empty()).then_some((https, authority, path))
+}
@@ -1,48 +1,1544 @@
 /*!
 # Chat App 主入口
 
-本程序支持两种模式运行：
+本程序支持六种模式运行：
 - **服务器模式**（server）：启动服务器，监听并处理所有客户端连接
 - **客户端模式**（client）：启动客户端，连接服务器后进行消息交互
+- **一致性检查**（conformance）：核对帧样例，并可对指定服务器运行协议交互用例
+- **订阅源机器人**（feedbot）：以机器人账号登录，定期抓取 RSS/Atom 订阅源并把新条目发给指定用户
+- **管理命令**（admin）：在服务器停止时导出或恢复数据目录的快照，用于迁移服务器或灾难恢复；
+  查询运行中的服务器的状态或要求其排空
+- **压测**（bench）：在进程内启动服务器与多个客户端，测量消息转发的延迟与吞吐量，并与保存的基线比较
+
+默认构建只包含客户端：服务器与管理命令需要 `server` 特性，压测需要 `metrics` 特性，身份密钥与密钥存储需要
+`crypto` 特性，一致性检查需要 `conformance` 特性，订阅源机器人需要 `feedbot` 特性；服务器各选项所需的特性见 README 的安装指南，
+使用未编译进来的功能时程序提示需要启用的特性。以下示例假定以 `--features full` 构建。
 
 使用方法：
 ```sh
-# 启动服务器
+# 启动服务器（默认监听 [::]:7891，同时接受 IPv4 与 IPv6 连接）
 cargo run -- server
 
+# 只监听 IPv4
+cargo run -- server 0.0.0.0:7891
+
+# 启动服务器，断开 300 秒内未发送任何数据的连接
+cargo run -- server 0.0.0.0:7891 --idle-timeout=300
+
+# 启动服务器，用户资料与最后在线时间保存到指定目录（否则仅保存在内存中）
+cargo run -- server --data-dir=./data
+
+# 启动服务器，消息日志以环境变量 CHAT_STORAGE_KEYS 中的密钥加密保存（也可用 cmd:命令 从 KMS 取密钥）
+CHAT_STORAGE_KEYS=$(openssl rand -hex 32) cargo run -- server --data-dir=./data --storage-keys=env:CHAT_STORAGE_KEYS
+
+# 管理员以令牌登录：生成 alice 的管理员令牌，输出令牌（交给 alice）与令牌摘要
+cargo run -- admin token alice
+
+# 把桥接令牌与对象存储的访问密钥写入服务器的密钥存储（值从终端读取，不回显；也可以经管道传入），
+# 之后启动服务器时无需在命令行或环境变量中给出；--name=用户名 写入该用户客户端的条目（如 token:alice）
+cargo run -- admin secret set telegram-token
+cargo run -- admin secret set token:alice --name=alice
+
+# 启动服务器，alice 出示令牌登录后为管理员；接收方队列已满时放弃投递（进入死信队列）而不是等待
+cargo run -- server --admins=alice=9f86d081... --queue-full=drop
+cargo run -- client --name=alice --token=adm_0123...
+
+# 启动服务器，接收过慢的连接 10 秒内未恢复即断开（默认 30 秒）
+cargo run -- server --slow-grace=10
+
+# 启动服务器，最多同时处理 500 个连接，超出时拒绝新连接
+cargo run -- server --max-connections=500
+
+# 保留 256 个空闲文件描述符（默认 64，0 表示不检查），余量不足时关闭新连接
+cargo run -- server --max-connections=10000 --fd-reserve=256
+
+# 消息内容最长 4096 字节（默认 16384，0 表示只受 64 KiB 的帧长度上限约束），过长的消息以 MESSAGE_TOO_LARGE 拒绝
+cargo run -- server --max-message-len=4096
+
+# 反垃圾规则（1 分钟内）：同样的消息超过 5 次、发给超过 20 个接收者或链接超过 10 个时告警，
+# 写入审计日志并提醒在线的管理员，管理员以 /audit 查看；--spam 单独使用时即为这些默认值
+cargo run -- server --admins=alice=9f86d081... --spam=repeat=5,recipients=20,links=10 --audit-log=./audit.jsonl
+
+# 触发规则的发送者限流 300 秒，期间的消息以 RATE_LIMITED 拒绝
+cargo run -- server --spam=repeat=3,links=5,window=30 --spam-throttle=300
+
+# 隔离待审核：触发规则或被举报（尚未处理）的发送者的消息暂不投递，管理员以 /quarantine release|discard 编号 处理
+cargo run -- server --admins=alice=9f86d081... --spam --quarantine
+
+# 文件传输策略：单个文件不超过 100 MiB，不允许 .exe、.bat 与 .scr；中转的内容先以 clamscan 扫描，
+# 未通过的文件移入 ./quarantine，发送方收到 TRANSFER_BLOCKED（需要扫描时双方只能经服务器中转）
+cargo run -- server --max-file-size=100M --block-extensions=exe,bat,scr \
+    --scan-command="clamscan --no-summary" --transfer-quarantine=./quarantine
+
+# 每个来源 IP 最多 5 个并发连接；60 秒内连接超过 20 次即冷却 120 秒，期间拒绝该 IP 的连接
+cargo run -- server --max-connections-per-ip=5 --connect-rate=20/60 --cooldown=120
+
+# 加入服务器联邦：本服务器名为 a.example，与 b.example（共享密钥 s3cret，地址按域名解析）
+# 及 c.example（指定地址）建立链路，用户可以向 bob@b.example 发送消息
+cargo run -- server --federation=a.example --peers=b.example=s3cret,c.example=k3y@10.0.0.3:7891
+
+# 站点之间的中继：其他站点把 relay.example 设为上游；链路中断期间的消息暂存在 ./spool，恢复后转发
+cargo run -- server --federation=site-a.example --peers=relay.example=s3cret@10.0.0.9:7891 \
+    --upstream=relay.example --spool=./spool
+
+# 载入 MaxMind 数据库，在连接日志、管理员的 /whois 与 /stats 中标注来源 IP 的国家/地区与 ASN
+cargo run -- server --geoip=GeoLite2-Country.mmdb,GeoLite2-ASN.mmdb
+
+# 额外在 8080 端口监听 WebSocket 连接，供浏览器中的客户端接入
+cargo run -- server --ws=0.0.0.0:8080
+
 # 启动客户端（可在第二个参数传入用户名，否则提示输入）
 cargo run -- client Alice
+
+# 启动客户端，连接超时 3 秒
+cargo run -- client 192.168.1.100:7891 --connect-timeout=3
+
+# 启动客户端，每 10 秒发送一次心跳，20 秒未收到服务器的任何数据即视为断线（0 表示关闭心跳）
+cargo run -- client --heartbeat=10
+
+# 启动客户端，聊天记录保存到指定目录（--no-history 则不保存）
+cargo run -- client --history-dir=./history
+
+# 启动客户端，高亮关键词 "发布" 与 "urgent"，静音的会话中命中时仍然提示
+cargo run -- client --highlight=发布,urgent --notify-muted
+
+# 启动客户端，收到的消息中的 :smile: 等表情短代码也展开显示
+cargo run -- client --emoji-display
+
+# 启动客户端，按原样显示消息，不渲染 Markdown
+cargo run -- client --plain
+
+# 启动客户端，使用指定的配色文件（默认读取 ~/.config/async-chat/theme.json）
+cargo run -- client --theme=./theme.json
+
+# 启动客户端，使用指定的别名文件（默认读取 ~/.config/async-chat/aliases.conf）
+cargo run -- client --aliases=./aliases.conf
+
+# 启动客户端，收到的文件保存到指定目录（默认为当前目录下的 downloads）
+cargo run -- client --downloads=./received
+
+# 启动客户端，以 sixel 显示收到的图片与 /preview 的缩略图（默认按终端自动判断，off 关闭自动预览）
+cargo run -- client --preview=sixel
+
+# 启动客户端，使用指定的身份密钥为消息签名（默认为数据目录下的 identity.key，不存在时生成）；
+# 私钥保存在系统密钥环（keyring 特性）或口令加密的数据目录下 secrets.enc 中，旧版本的明文密钥文件导入后删除
+cargo run -- client --identity=./alice.key
+
+# 启动客户端，私钥保存在指定的加密文件中，口令取自环境变量（未设置时在终端上提示输入）
+CHAT_SECRETS_PASSPHRASE=... cargo run -- client --secrets=./secrets.enc
+
+# 启动客户端，不输出颜色（也可设置 NO_COLOR 环境变量）
+cargo run -- client --no-color
+
+# 脚本模式：以 alice 的身份执行脚本中的步骤（标准输入不是终端时从标准输入读取）
+cargo run -- client --name=alice --script=commands.txt
+
+# 启动客户端，依次尝试多个服务器，连接中断时自动切换
+cargo run -- client 192.168.1.100:7891,192.168.1.101:7891
+
+# 只写域名：按 _chat._tcp.example.com 的 SRV 记录连接，没有记录时连接 example.com:7891
+cargo run -- client example.com
+
+# 以英文显示提示（默认按 LC_ALL、LC_MESSAGES、LANG 环境变量选择，无法识别时为中文）；
+# 客户端会把语言告知服务器，服务器发给该用户的提示也使用英文
+cargo run -- client --lang=en
+LANG=en_US.UTF-8 cargo run -- server
+
+# 虚拟主机：同一进程承载多个彼此隔离的聊天空间（名称=数据目录，省略目录则只保存在内存中），
+# 客户端通过 --vhost 选择空间，不指定时进入默认空间
+cargo run -- server --data-dir=./data --vhosts=books=./data-books,games
+cargo run -- client --vhost=books
+
+# 仅限邀请注册：新用户需要出示管理员通过 /invite 生成的邀请码，已注册的用户不受影响
+cargo run -- server --data-dir=./data --admins=alice=9f86d081... --invite-only
+cargo run -- client --name=bob --invite=7KQ4-MX2P
+
+# 机器人账号：管理员在客户端中执行 /bot create weather message 得到令牌，机器人以令牌登录
+# （也可以通过 CHAT_BOT_TOKEN 环境变量传入）；机器人每 30 秒最多发送 20 条消息（默认 60/60）
+cargo run -- server --admins=alice=9f86d081... --bot-rate=20/30
+cargo run -- client --name=weather --token=bot_0123...
+
+# 传入 Webhook：管理员执行 /webhook create ci alice 得到路径，CI 以 HTTP POST 向 alice 发消息
+cargo run -- server --data-dir=./data --admins=alice=9f86d081... --hooks=0.0.0.0:8081
+curl -X POST -d '{"content": "构建失败"}' http://127.0.0.1:8081/hooks/hook_0123...
+
+# Telegram 桥接（需要 tls 特性）：把群组 -1001234567890 与服务器上的桥接聊天室双向同步，
+# 用户以 /telegram join 加入，向 tg:group 发的消息会发到群组
+CHAT_TELEGRAM_TOKEN=123456:ABC... cargo run --features tls -- server --data-dir=./data --telegram=-1001234567890
+
+# XMPP 网关：以组件 chat.example.org（密钥 s3cret；省略时读取密钥存储的 xmpp-secret 条目）连接本机 XMPP 服务器的组件端口，
+# Jabber 用户可以给 alice@chat.example.org 发消息，alice 以 xmpp:juliet@example.org 回复
+cargo run -- server --data-dir=./data --xmpp=chat.example.org=s3cret@127.0.0.1:5347
+
+# 历史消息归档：每小时把写入超过 30 天的消息压缩上传到桶 chat-archive 的 prod/ 前缀下并从本地日志删除
+# （访问密钥取自 AWS_ACCESS_KEY_ID 与 AWS_SECRET_ACCESS_KEY 或密钥存储，--archive-region 默认 us-east-1）
+cargo run --features tls -- server --data-dir=./data --archive-after=30 \
+    --archive-to=https://s3.eu-west-1.amazonaws.com/chat-archive/prod --archive-region=eu-west-1
+
+# 维护任务（cron 式时间表，以分号分隔）：每天 3 点整理消息日志，每 10 分钟清除过期的邀请码与死信，
+# 每周日 4 点删除超出补发窗口的消息；管理员以 /jobs 查看运行统计
+cargo run -- server --data-dir=./data --admins=alice=9f86d081... \
+    --jobs="vacuum=0 3 * * *;expire=0-59/10 * * * *;prune=0 4 * * 0"
+
+# 不停机升级：服务器监听管理套接字并以 SO_REUSEPORT 绑定端口；新版本以相同参数加 --takeover 启动，
+# 绑定同一端口后要求旧进程排空，旧进程通知用户、断开连接并写完数据后退出，客户端自动重连到新进程
+cargo run -- server --data-dir=./data --admin-socket=/run/chat/admin.sock
+./chat-new server --data-dir=./data --admin-socket=/run/chat/admin.sock --takeover
+cargo run -- admin status --admin-socket=/run/chat/admin.sock
+cargo run -- admin audit 50 --admin-socket=/run/chat/admin.sock
+cargo run -- admin reports dismiss 3 --admin-socket=/run/chat/admin.sock
+cargo run -- admin quarantine release 4 --admin-socket=/run/chat/admin.sock
+
+# 维护模式：计划重启前拒绝新的注册（提示 12:30 恢复），在线会话自然结束后再排空或停止
+cargo run -- admin maintenance on 12:30 --admin-socket=/run/chat/admin.sock
+cargo run -- admin maintenance --admin-socket=/run/chat/admin.sock
+
+# systemd 托管：由 chat.socket 激活时沿用其监听套接字（第二个套接字用于 --ws），
+# Type=notify 的服务在开始接受连接后通知就绪，配置 WatchdogSec= 时定期喂狗，SIGTERM 时正常关闭
+systemctl start chat.socket
+
+# 工作量证明：新用户注册前须找到使哈希有 20 个前导零比特的解（已注册用户与管理员不受影响）
+cargo run -- server --pow=20
+
+# 协议一致性检查：核对帧样例并做编解码往返的属性检查；给出服务器地址时再对该服务器运行交互用例
+cargo run -- conformance
+cargo run -- conformance 127.0.0.1:7891 --codec=length-prefixed
+
+# 订阅源机器人：以机器人账号 news 登录，每 600 秒抓取一次订阅源并把新条目发给 alice 与 bob
+# （已见条目默认保存在数据目录下的 feeds.json，可用 --state=路径 指定）
+cargo run -- feedbot 127.0.0.1:7891 --name=news --token=bot_0123... \
+    --feeds=https://blog.rust-lang.org/feed.xml --to=alice,bob --interval=600
+
+# 快照与恢复：把数据目录（用户、桥接订阅、消息日志）与联邦暂存导出为一个快照文件（.gz 结尾时压缩），
+# 在新主机上恢复到空的数据目录（--force 覆盖已有数据）；消息日志加密时快照中仍为密文
+cargo run -- admin snapshot ./chat.snapshot.json.gz --data-dir=./data --spool=./spool
+cargo run -- admin restore ./chat.snapshot.json.gz --data-dir=/srv/chat/data --spool=/srv/chat/spool
+
+# 压测：16 个客户端经本机回环 TCP 各发送 2000 条消息，保存为基线；
+# 之后的运行与基线比较，吞吐量下降或延迟上升超过 5% 时退出码为 1（默认经进程内管道、阈值 10%）
+cargo run --release -- bench --clients=16 --messages=2000 --transport=tcp --save-baseline=bench.json
+cargo run --release -- bench --clients=16 --messages=2000 --transport=tcp --baseline=bench.json --max-regression=5
+
+# 关闭服务器输出中逐条记录的消息（消息量很大时）
+cargo run -- server --no-message-log
+
+# 路由追踪（调试投递延迟）：记录每条消息收到、排队、转发与送达的时间，发送者以 /trace 编号 查看；
+# 联邦中的收件服务器把沿途的记录报告回来
+cargo run -- server --trace --federation=a.example --peers=b.example=s3cret
+
+# 关闭服务器时建议客户端 30 秒后自动重连（平滑升级时总是建议重连，默认 1 秒后）
+cargo run -- server --reconnect-after=30
+
+# 故障注入（仅用于测试，需 `chaos` 特性）：发给客户端的帧 1% 被丢弃、0.1% 被截断并断线、2% 重复、
+# 10% 延迟最多 500 毫秒；固定种子使故障序列可以复现
+cargo run --features chaos -- server --chaos=drop=0.01,truncate=0.001,duplicate=0.02,delay=0.1,max-delay=500,seed=42
+
+# 套接字选项（服务器与客户端通用）：空闲 30 秒后每 5 秒发送 keepalive 探测（0 表示关闭），
+# 接收缓冲区 256 KB；--no-nodelay 重新开启 Nagle 算法
+cargo run -- server --keepalive=30 --keepalive-interval=5 --recv-buffer=262144
+cargo run -- client --keepalive=0 --no-nodelay --send-buffer=65536
+```
+
+脚本模式下所有步骤成功时退出码为 0，任一步骤失败时为 1；一致性检查同样以 0/1 表示是否全部通过，
+压测以 0/1 表示是否没有指标退化。
+客户端连接失败时以不同的退出码结束：2 地址无效、3 DNS 解析失败、
+4 连接被拒绝、5 连接超时、6 TLS 握手失败、1 其他错误。
 详细实现请参见各模块的文档注释。 */
 
-use chat::{Task, TaskType};
+use chat::alias::Aliases;
+#[cfg(feature = "metrics")]
+use chat::bench::{self, BenchConfig, Report, Transport};
+use chat::client::{Client, ClientBuilder};
+#[cfg(any(feature = "metrics", feature = "conformance"))]
+use chat::codec::Codec;
+#[cfg(feature = "conformance")]
+use chat::conformance;
+#[cfg(feature = "feedbot")]
+use chat::feedbot::{FeedBot, FeedConfig};
+use chat::highlight::HighlightRules;
+use chat::history::History;
+use chat::i18n::{self, Lang};
+#[cfg(feature = "crypto")]
+use chat::identity::{Identity, KnownKeys};
+#[cfg(feature = "server")]
+use chat::pow;
+use chat::preview::Preview;
+use chat::script::Script;
+#[cfg(feature = "crypto")]
+use chat::secrets::{self, SecretError, SecretStore};
+#[cfg(feature = "storage")]
+use chat::server::ArchiveConfig;
+#[cfg(feature = "chaos")]
+use chat::server::ChaosConfig;
+#[cfg(feature = "geoip")]
+use chat::server::GeoIp;
+#[cfg(feature = "server")]
+use chat::server::{
+    admin_request, take_over, BotRate, FederationConfig, JobConfig, Listeners, PeerConfig,
+    QueueFullPolicy, Server, ServerConfig, SpamAction, SpamConfig, TransferPolicy,
+};
+#[cfg(feature = "bridges")]
+use chat::server::{TelegramConfig, XmppConfig};
+#[cfg(feature = "server")]
+use chat::storage::encryption::StorageKeys;
+#[cfg(feature = "storage")]
+use chat::storage::{snapshot::Snapshot, JsonFileStorage};
+use chat::terminal;
+use chat::theme::{self, Theme};
+use chat::transport::{ConnectError, SocketOptions};
+use chat::{tr, ArcString, Message, Task, TaskType};
+use std::collections::HashMap;
 use std::env;
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+#[cfg(feature = "server")]
+use std::path::Path;
+#[cfg(any(feature = "server", feature = "feedbot"))]
+use std::path::PathBuf;
+use std::process;
+#[cfg(any(feature = "storage", feature = "geoip"))]
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// 创建服务器：给出数据目录时持久化到该目录（给出 `keys` 时消息日志加密保存），
+/// 否则数据只保存在内存中
+///
+/// 数据目录无法打开时输出错误并返回 `None`
+#[cfg(feature = "storage")]
+fn open_server(
+    config: ServerConfig,
+    data_dir: Option<&String>,
+    keys: Option<&StorageKeys>,
+) -> Option<Server> {
+    match data_dir {
+        Some(dir) => match keys.map_or_else(
+            || JsonFileStorage::open(dir),
+            |keys| JsonFileStorage::open_encrypted(dir, keys.clone()),
+        ) {
+            Ok(storage) => {
+                if storage.reencrypted() > 0 {
+                    println!(
+                        "{}",
+                        tr!(
+                            "cli.storage_reencrypted",
+                            dir = dir,
+                            count = storage.reencrypted()
+                        )
+                    );
+                }
+                Some(Server::with_storage(config, Arc::new(storage)))
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("cli.open_data_dir_failed", dir = dir, error = e));
+                None
+            }
+        },
+        None => Some(Server::with_config(config)),
+    }
+}
+
+/// 创建数据只保存在内存中的服务器；未启用 `storage` 特性时给出数据目录会输出错误并返回 `None`
+#[cfg(all(feature = "server", not(feature = "storage")))]
+fn open_server(
+    config: ServerConfig,
+    data_dir: Option<&String>,
+    _keys: Option<&StorageKeys>,
+) -> Option<Server> {
+    if data_dir.is_some() {
+        feature_disabled("--data-dir", "storage");
+        return None;
+    }
+    Some(Server::with_config(config))
+}
+
+/// 需要启用 Cargo 特性才能使用的命令行选项：选项、特性、当前构建是否启用了该特性
+const GATED_OPTIONS: &[(&str, &str, bool)] = &[
+    ("archive-after", "storage", cfg!(feature = "storage")),
+    ("archive-to", "storage", cfg!(feature = "storage")),
+    ("chaos", "chaos", cfg!(feature = "chaos")),
+    ("data-dir", "storage", cfg!(feature = "storage")),
+    ("geoip", "geoip", cfg!(feature = "geoip")),
+    ("identity", "crypto", cfg!(feature = "crypto")),
+    ("secrets", "crypto", cfg!(feature = "crypto")),
+    ("storage-keys", "storage", cfg!(feature = "storage")),
+    ("telegram", "bridges", cfg!(feature = "bridges")),
+    ("ws", "websocket", cfg!(feature = "websocket")),
+    ("xmpp", "bridges", cfg!(feature = "bridges")),
+];
+
+/// 服务器从密钥存储读取的条目（命令行与环境变量都未提供时）
+#[cfg(feature = "bridges")]
+const SECRET_TELEGRAM_TOKEN: &str = "telegram-token";
+#[cfg(feature = "bridges")]
+const SECRET_XMPP: &str = "xmpp-secret";
+#[cfg(feature = "storage")]
+const SECRET_AWS_ACCESS_KEY: &str = "aws-access-key-id";
+#[cfg(feature = "storage")]
+const SECRET_AWS_SECRET_KEY: &str = "aws-secret-access-key";
+
+/// 客户端令牌在密钥存储中的条目名
+#[cfg(feature = "crypto")]
+fn token_entry(username: &str) -> String {
+    format!("token:{}", username)
+}
+
+/// 机器人账号或管理员的令牌：依次取 `--token`、`CHAT_BOT_TOKEN` 环境变量与密钥存储中的
+/// `token:<用户名>` 条目；密钥存储无法打开（例如没有数据目录）时按未提供令牌处理
+fn client_token(
+    options: &HashMap<String, String>,
+    secrets: &mut LazySecrets,
+    username: &str,
+) -> Option<String> {
+    options
+        .get("token")
+        .cloned()
+        .or_else(|| env::var("CHAT_BOT_TOKEN").ok())
+        .or_else(|| secrets.token(username))
+}
+
+/// 按需打开的密钥存储：第一次用到时才打开（回退到加密文件时可能提示输入口令）
+///
+/// `--secrets` 指定加密文件；否则优先使用系统密钥环，不可用时回退到 `owner` 数据目录下的 `secrets.enc`
+#[cfg(feature = "crypto")]
+struct LazySecrets<'a> {
+    file: Option<&'a String>,
+    owner: &'a str,
+    store: Option<SecretStore>,
+}
+
+#[cfg(feature = "crypto")]
+impl<'a> LazySecrets<'a> {
+    fn new(options: &'a HashMap<String, String>, owner: &'a str) -> Self {
+        Self {
+            file: options.get("secrets"),
+            owner,
+            store: None,
+        }
+    }
+
+    /// 打开（或返回已打开的）密钥存储
+    fn store(&mut self) -> Result<&SecretStore, SecretError> {
+        if self.store.is_none() {
+            let store = match self.file {
+                Some(file) => {
+                    SecretStore::encrypted_file(file, secrets::passphrase(&tr!("secrets.prompt"))?)
+                }
+                None => SecretStore::open_default(self.owner)?,
+            };
+            self.store = Some(store);
+        }
+        Ok(self.store.as_ref().expect("store opened above"))
+    }
+
+    /// 读取文本条目 `entry`，不存在时为 `None`
+    fn get(&mut self, entry: &str) -> Result<Option<String>, SecretError> {
+        self.store()?
+            .get(entry)?
+            .map(|value| {
+                String::from_utf8(value)
+                    .map_err(|_| SecretError::Format(tr!("secrets.not_text", entry = entry)))
+            })
+            .transpose()
+    }
+
+    /// 用户 `username` 的令牌，密钥存储无法打开或读取时为 `None`
+    fn token(&mut self, username: &str) -> Option<String> {
+        self.get(&token_entry(username)).ok().flatten()
+    }
+
+    /// 依次取命令行参数、环境变量与密钥存储中的条目；打开或读取密钥存储失败时输出错误并返回 `Err`
+    #[cfg(any(feature = "bridges", feature = "storage"))]
+    fn lookup(
+        &mut self,
+        option: Option<&String>,
+        env_var: &str,
+        entry: &str,
+    ) -> Result<Option<String>, ()> {
+        if let Some(value) = option.cloned().or_else(|| env::var(env_var).ok()) {
+            return Ok(Some(value));
+        }
+        self.get(entry).map_err(|e| {
+            eprintln!("{}", tr!("cli.open_secrets_failed", error = e));
+        })
+    }
+}
+
+/// 未启用 `crypto` 特性时没有密钥存储，令牌只能由命令行参数或环境变量提供
+#[cfg(not(feature = "crypto"))]
+struct LazySecrets;
+
+#[cfg(not(feature = "crypto"))]
+impl LazySecrets {
+    fn new(_options: &HashMap<String, String>, _owner: &str) -> Self {
+        LazySecrets
+    }
+
+    fn token(&mut self, _username: &str) -> Option<String> {
+        None
+    }
+}
+
+/// 输出 `what` 需要启用 `feature` 特性的错误
+fn feature_disabled(what: &str, feature: &str) {
+    eprintln!(
+        "{}",
+        tr!("cli.feature_disabled", what = what, feature = feature)
+    );
+}
+
+/// 检查命令行中是否用到了当前构建未启用的特性，用到时输出错误并返回 `false`
+fn check_features(options: &HashMap<String, String>) -> bool {
+    for (option, feature, enabled) in GATED_OPTIONS {
+        if !enabled && options.contains_key(*option) {
+            feature_disabled(&format!("--{}", option), feature);
+            return false;
+        }
+    }
+    true
+}
+
+/// 将命令行参数拆分为位置参数与 `--key=value` / `--flag` 形式的选项
+fn parse_args(args: impl Iterator<Item = String>) -> (Vec<String>, HashMap<String, String>) {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    for arg in args {
+        match arg.strip_prefix("--") {
+            Some(option) => {
+                let (key, value) = option.split_once('=').unwrap_or((option, "true"));
+                options.insert(key.to_string(), value.to_string());
+            }
+            None => positional.push(arg),
+        }
+    }
+    (positional, options)
+}
+
+/// 从 `--no-nodelay`、`--keepalive=秒数`、`--keepalive-interval=秒数`、
+/// `--send-buffer=字节数`、`--recv-buffer=字节数` 解析套接字选项
+fn socket_options(options: &HashMap<String, String>) -> Result<SocketOptions, String> {
+    let mut socket = SocketOptions {
+        nodelay: !options.contains_key("no-nodelay"),
+        ..SocketOptions::default()
+    };
+    let secs = |key: &str| -> Result<Option<u64>, String> {
+        options
+            .get(key)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| tr!("cli.invalid_option", key = key, value = value))
+            })
+            .transpose()
+    };
+    if let Some(secs) = secs("keepalive")? {
+        socket.keepalive = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+    if let Some(secs) = secs("keepalive-interval")? {
+        socket.keepalive_interval = (secs > 0).then(|| Duration::from_secs(secs));
+    }
+    for (key, size) in [
+        ("send-buffer", &mut socket.send_buffer),
+        ("recv-buffer", &mut socket.recv_buffer),
+    ] {
+        if let Some(value) = options.get(key) {
+            match value.parse() {
+                Ok(bytes) if bytes > 0 => *size = Some(bytes),
+                _ => return Err(tr!("cli.invalid_option", key = key, value = value)),
+            }
+        }
+    }
+    Ok(socket)
+}
+
+/// 读取并解析脚本，`-` 表示从标准输入读取
+fn read_script(source: &str) -> Result<Script, Box<dyn std::error::Error>> {
+    let text = if source == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(source)
+            .map_err(|e| tr!("cli.read_script_failed", path = source, error = e))?
+    };
+    Ok(Script::parse(&text)?)
+}
+
+/// 运行协议一致性检查并打印结果，返回进程退出码
+/// 管理命令：`token <用户名>` 生成管理员令牌，`snapshot <路径>` 把数据目录导出为快照，`restore <路径>` 从快照恢复数据目录，
+/// `status`、`drain`、`audit [条数]`、`reports [dismiss <编号>]`、`quarantine [release|discard <编号>]`
+/// 与 `maintenance [on [HH:MM]|off]` 发给运行中的服务器的管理套接字
+///
+/// # 返回值
+/// 进程退出码：成功为 0，用法错误或读写失败为 1
+#[cfg(feature = "server")]
+async fn run_admin(args: &[String], options: &HashMap<String, String>) -> i32 {
+    match args.get(2).map(String::as_str) {
+        Some("token") => return issue_admin_token(args.get(3).map(String::as_str)),
+        Some("secret") => return edit_secret(&args[3..], options),
+        _ => {}
+    }
+    if let (
+        Some("status" | "drain" | "audit" | "reports" | "quarantine" | "maintenance"),
+        Some(socket),
+    ) = (args.get(2).map(String::as_str), options.get("admin-socket"))
+    {
+        let command = args[2..].join(" ");
+        return match admin_request(Path::new(socket), &command).await {
+            Ok(replies) => {
+                for line in replies {
+                    println!("{}", line);
+                }
+                0
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("cli.admin_failed", error = e));
+                1
+            }
+        };
+    }
+    run_snapshot(args, options)
+}
+
+/// `chat admin token <用户名>`：生成管理员令牌，输出令牌与服务器的 `--admins` 参数
+#[cfg(feature = "server")]
+fn issue_admin_token(name: Option<&str>) -> i32 {
+    let Some(name) = name.filter(|name| !name.contains([',', '='])) else {
+        eprintln!("{}", tr!("cli.admin_usage"));
+        return 1;
+    };
+    match chat::server::issue_admin_token() {
+        Ok((token, digest)) => {
+            println!(
+                "{}",
+                tr!(
+                    "cli.admin_token_issued",
+                    name = name,
+                    token = token,
+                    digest = digest
+                )
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("cli.admin_failed", error = e));
+            1
+        }
+    }
+}
+
+/// `chat admin secret set|delete <条目>`：写入或删除密钥存储中的条目，写入的值从终端（不回显）或标准输入读取
+///
+/// 条目属于 `--name` 指定的用户（客户端的令牌），省略时属于服务器（桥接令牌与对象存储的访问密钥）
+#[cfg(feature = "server")]
+fn edit_secret(args: &[String], options: &HashMap<String, String>) -> i32 {
+    let (Some(command @ ("set" | "delete")), Some(entry)) =
+        (args.first().map(String::as_str), args.get(1))
+    else {
+        eprintln!("{}", tr!("cli.admin_usage"));
+        return 1;
+    };
+    let owner = options.get("name").map_or("server", String::as_str);
+    let mut secrets = LazySecrets::new(options, owner);
+    let result = secrets.store().and_then(|store| match command {
+        "set" => {
+            let value = secrets::read_secret(&tr!("cli.secret_prompt", entry = entry))?;
+            store.set(entry, value.as_bytes())
+        }
+        _ => store.delete(entry),
+    });
+    match result {
+        Ok(()) => {
+            println!("{}", tr!("cli.secret_saved", entry = entry));
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("cli.open_secrets_failed", error = e));
+            1
+        }
+    }
+}
+
+/// `chat admin snapshot|restore`：导出或恢复服务器数据目录的快照
+#[cfg(feature = "storage")]
+fn run_snapshot(args: &[String], options: &HashMap<String, String>) -> i32 {
+    let (Some(command), Some(path), Some(dir)) =
+        (args.get(2), args.get(3), options.get("data-dir"))
+    else {
+        eprintln!("{}", tr!("cli.admin_usage"));
+        return 1;
+    };
+    let (path, dir) = (Path::new(path), Path::new(dir));
+    let spool = options.get("spool").map(Path::new);
+    let result = match command.as_str() {
+        "snapshot" => Snapshot::capture(dir, spool).and_then(|snapshot| {
+            snapshot.save(path)?;
+            Ok(tr!(
+                "cli.snapshot_written",
+                path = path.display(),
+                users = snapshot.users.len(),
+                messages = snapshot.messages.len(),
+                spooled = snapshot.spooled()
+            ))
+        }),
+        "restore" => Snapshot::load(path).and_then(|snapshot| {
+            snapshot.restore(dir, spool, options.contains_key("force"))?;
+            Ok(tr!(
+                "cli.snapshot_restored",
+                dir = dir.display(),
+                created = snapshot.created_at.format("%Y-%m-%d %H:%M:%S"),
+                users = snapshot.users.len(),
+                messages = snapshot.messages.len(),
+                spooled = snapshot.spooled()
+            ))
+        }),
+        _ => {
+            eprintln!("{}", tr!("cli.admin_usage"));
+            return 1;
+        }
+    };
+    match result {
+        Ok(summary) => {
+            println!("{}", summary);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", tr!("cli.admin_failed", error = e));
+            1
+        }
+    }
+}
+
+/// `chat admin snapshot|restore`：未启用 `storage` 特性时不可用
+#[cfg(all(feature = "server", not(feature = "storage")))]
+fn run_snapshot(args: &[String], _options: &HashMap<String, String>) -> i32 {
+    match args.get(2).map(String::as_str) {
+        Some(command @ ("snapshot" | "restore")) => feature_disabled(command, "storage"),
+        _ => eprintln!("{}", tr!("cli.admin_usage")),
+    }
+    1
+}
+
+/// `chat bench`：运行压测并输出结果；给出基线时与其比较，有指标退化时返回 1
+#[cfg(feature = "metrics")]
+async fn run_bench(options: &HashMap<String, String>) -> i32 {
+    let mut config = BenchConfig::default();
+    // 选项、对应的参数、允许的最小值
+    for (option, field, min) in [
+        ("clients", &mut config.clients, 2),
+        ("messages", &mut config.messages, 1),
+        ("size", &mut config.size, 1),
+    ] {
+        if let Some(value) = options.get(option) {
+            match value.parse::<usize>() {
+                Ok(n) if n >= min => *field = n,
+                _ => {
+                    eprintln!(
+                        "{}",
+                        tr!("cli.invalid_bench_option", option = option, value = value)
+                    );
+                    return 1;
+                }
+            }
+        }
+    }
+    if let Some(name) = options.get("transport") {
+        match Transport::from_string(name) {
+            Some(transport) => config.transport = transport,
+            None => {
+                eprintln!("{}", tr!("cli.invalid_transport", value = name));
+                return 1;
+            }
+        }
+    }
+    if let Some(name) = options.get("codec") {
+        match Codec::from_string(name) {
+            Some(codec) => config.codec = codec,
+            None => {
+                eprintln!("{}", tr!("cli.invalid_codec", value = name));
+                return 1;
+            }
+        }
+    }
+    let max_regression = match options.get("max-regression").map(|v| v.parse::<f64>()) {
+        None => bench::DEFAULT_MAX_REGRESSION,
+        Some(Ok(percent)) if percent >= 0.0 => percent,
+        Some(_) => {
+            let value = &options["max-regression"];
+            eprintln!(
+                "{}",
+                tr!(
+                    "cli.invalid_bench_option",
+                    option = "max-regression",
+                    value = value
+                )
+            );
+            return 1;
+        }
+    };
+    // 先读取基线，避免压测完才发现基线无法使用
+    let baseline = match options.get("baseline") {
+        Some(path) => match Report::load(Path::new(path)) {
+            Ok(report) => Some((path, report)),
+            Err(e) => {
+                eprintln!("{}", tr!("cli.baseline_failed", path = path, error = e));
+                return 1;
+            }
+        },
+        None => None,
+    };
+
+    println!(
+        "{}",
+        tr!(
+            "cli.starting_bench",
+            clients = config.clients,
+            transport = config.transport,
+            codec = config.codec,
+            messages = config.messages,
+            size = config.size
+        )
+    );
+    let report = match bench::run(&config).await {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", tr!("cli.bench_failed", error = e));
+            return 1;
+        }
+    };
+    print!("{}", report);
+    if let Some(path) = options.get("save-baseline") {
+        match report.save(Path::new(path)) {
+            Ok(()) => println!("{}", tr!("cli.baseline_saved", path = path)),
+            Err(e) => {
+                eprintln!("{}", tr!("cli.baseline_failed", path = path, error = e));
+                return 1;
+            }
+        }
+    }
+    let Some((path, baseline)) = baseline else {
+        return 0;
+    };
+    println!("{}", tr!("bench.compare", path = path));
+    if !report.comparable(&baseline) {
+        println!("{}", tr!("bench.mismatch"));
+    }
+    let changes = report.compare(&baseline, max_regression);
+    for change in &changes {
+        let verdict = if change.regressed {
+            tr!("bench.regressed")
+        } else {
+            String::new()
+        };
+        println!(
+            "  {:<10} {:>12.1} ← {:>12.1} {:>+8.1}% {}",
+            change.metric, change.current, change.baseline, change.percent, verdict
+        );
+    }
+    let regressions = changes.iter().filter(|change| change.regressed).count();
+    if regressions > 0 {
+        println!(
+            "{}",
+            tr!(
+                "bench.regressions",
+                count = regressions,
+                threshold = max_regression
+            )
+        );
+        1
+    } else {
+        println!("{}", tr!("bench.no_regression", threshold = max_regression));
+        0
+    }
+}
+
+#[cfg(feature = "conformance")]
+async fn run_conformance(addr: Option<&str>, codec: Codec) -> i32 {
+    let properties = tr!(
+        "conformance.properties",
+        cases = conformance::PROPERTY_CASES
+    );
+    let mut sections = vec![
+        (tr!("conformance.fixtures"), conformance::check_fixtures()),
+        (properties, conformance::check_roundtrips()),
+    ];
+    if let Some(addr) = addr {
+        let title = tr!("conformance.exchanges", addr = addr, codec = codec);
+        sections.push((title, conformance::run_exchanges(addr, codec).await));
+    }
+    let (mut total, mut failed) = (0, 0);
+    for (title, outcomes) in &sections {
+        println!("{}", title);
+        for outcome in outcomes {
+            total += 1;
+            match &outcome.result {
+                Ok(()) => println!("  {} {}", tr!("conformance.pass"), outcome.name),
+                Err(reason) => {
+                    failed += 1;
+                    println!("  {} {}: {}", tr!("conformance.fail"), outcome.name, reason);
+                }
+            }
+        }
+    }
+    println!(
+        "{}",
+        tr!("conformance.summary", total = total, failed = failed)
+    );
+    i32::from(failed > 0)
+}
+
+/// 以脚本模式运行客户端，返回进程退出码
+async fn run_script(builder: ClientBuilder, addr: &str, script: &Script) -> i32 {
+    // 收到的消息逐条打印，并转发给脚本供 expect 检查；
+    // 服务器的错误帧视为来自 "Server" 的消息，脚本可以用 `expect Server <文本>` 检查
+    let (tx, mut inbox) = mpsc::unbounded_channel();
+    let errors = tx.clone();
+    let client = builder
+        .on_message(move |msg| {
+            println!("[{}] {}: {}", msg.time_stamp(), msg.from(), msg.content());
+            let _ = tx.send(msg);
+        })
+        .on_error(move |error| {
+            let code = format!("{:?}", error.code);
+            println!(
+                "{}",
+                tr!("cli.script_error", code = code, message = error.message)
+            );
+            let server = ArcString::new("Server".to_string());
+            let _ = errors.send(Message::new(server, String::new(), error.message));
+        })
+        .build();
+    let mut conn = match client.connect(addr).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            eprintln!("{}", e);
+            return e
+                .downcast_ref::<ConnectError>()
+                .map_or(1, ConnectError::exit_code);
+        }
+    };
+    let result = script.run(&mut conn, &mut inbox).await;
+    conn.close().await;
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
     // 从命令行参数获取运行模式
-    let args: Vec<String> = env::args().collect();
+    let (args, options) = parse_args(env::args());
+    theme::init_color(options.contains_key("no-color"));
+    if let Some(tag) = options.get("lang") {
+        match Lang::parse(tag) {
+            Some(lang) => i18n::set_lang(lang),
+            None => {
+                eprintln!("{}", tr!("cli.invalid_lang", value = tag));
+                return;
+            }
+        }
+    }
+    if !check_features(&options) {
+        process::exit(1);
+    }
     if args.len() < 2 {
-        eprintln!("请指定运行模式: server 或 client");
+        eprintln!("{}", tr!("cli.mode_required"));
         return;
     }
     let mode = args[1].as_str();
     match Task::from_string(mode) {
+        #[cfg(feature = "server")]
         Some(TaskType::Server) => {
-            println!("启动服务器模式...");
+            println!("{}", tr!("cli.starting_server"));
             let addr = if args.len() >= 3 {
                 args[2].clone()
             } else {
-                String::from("0.0.0.0:7891")
+                String::from("[::]:7891")
+            };
+
+            let mut config = ServerConfig::default();
+            match socket_options(&options) {
+                Ok(socket) => config.socket = socket,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            }
+            if let Some(secs) = options.get("idle-timeout") {
+                match secs.parse::<u64>() {
+                    Ok(secs) => config.idle_timeout = Some(Duration::from_secs(secs)),
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_idle_timeout", value = secs));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(secs) = options.get("slow-grace") {
+                match secs.parse::<u64>() {
+                    Ok(secs) => config.slow_grace = Duration::from_secs(secs),
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_slow_grace", value = secs));
+                        return;
+                    }
+                }
+            }
+            if let Some(max) = options.get("max-connections") {
+                match max.parse::<usize>() {
+                    Ok(max) if max > 0 => config.max_connections = Some(max),
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_max_connections", value = max));
+                        return;
+                    }
+                }
+            }
+            if let Some(reserve) = options.get("fd-reserve") {
+                match reserve.parse::<usize>() {
+                    Ok(reserve) => config.fd_reserve = reserve,
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_fd_reserve", value = reserve));
+                        return;
+                    }
+                }
+            }
+            if let Some(max) = options.get("max-message-len") {
+                match max.parse::<usize>() {
+                    Ok(0) => config.max_message_len = None,
+                    Ok(max) => config.max_message_len = Some(max),
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_max_message_len", value = max));
+                        return;
+                    }
+                }
+            }
+            if let Some(max) = options.get("max-connections-per-ip") {
+                match max.parse::<usize>() {
+                    Ok(max) if max > 0 => config.per_ip.max_connections = Some(max),
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_max_connections", value = max));
+                        return;
+                    }
+                }
+            }
+            if let Some(rate) = options.get("connect-rate") {
+                let parsed = rate.split_once('/').and_then(|(attempts, secs)| {
+                    Some((attempts.parse::<usize>().ok()?, secs.parse::<u64>().ok()?))
+                });
+                match parsed {
+                    Some((attempts, secs)) if attempts > 0 && secs > 0 => {
+                        config.per_ip.max_attempts = Some(attempts);
+                        config.per_ip.window = Duration::from_secs(secs);
+                    }
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_connect_rate", value = rate));
+                        return;
+                    }
+                }
+            }
+            if let Some(rate) = options.get("bot-rate") {
+                let parsed = rate.split_once('/').and_then(|(max, secs)| {
+                    Some((max.parse::<usize>().ok()?, secs.parse::<u64>().ok()?))
+                });
+                match parsed {
+                    Some((max, secs)) if max > 0 && secs > 0 => {
+                        config.bot_rate = BotRate {
+                            max_messages: max,
+                            window: Duration::from_secs(secs),
+                        };
+                    }
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_bot_rate", value = rate));
+                        return;
+                    }
+                }
+            }
+            if let Some(secs) = options.get("cooldown") {
+                match secs.parse::<u64>() {
+                    Ok(secs) => config.per_ip.cooldown = Duration::from_secs(secs),
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_cooldown", value = secs));
+                        return;
+                    }
+                }
+            }
+            #[cfg(feature = "geoip")]
+            if let Some(paths) = options.get("geoip") {
+                let paths: Vec<&str> = paths.split(',').map(str::trim).collect();
+                match GeoIp::open(&paths) {
+                    Ok(geoip) => config.geoip = Some(Arc::new(geoip)),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            tr!("cli.open_geoip_failed", path = paths.join(","), error = e)
+                        );
+                        return;
+                    }
+                }
+            }
+            if let Some(policy) = options.get("queue-full") {
+                match QueueFullPolicy::from_string(policy) {
+                    Some(policy) => config.queue_full = policy,
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_queue_full", value = policy));
+                        return;
+                    }
+                }
+            }
+            if let Some(spec) = options.get("spam") {
+                // 单独的 `--spam` 启用全部规则的默认值
+                let spec = if spec == "true" { "" } else { spec.as_str() };
+                match SpamConfig::from_string(spec) {
+                    Some(spam) => config.spam = Some(spam),
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_spam", value = spec));
+                        return;
+                    }
+                }
+            }
+            if let Some(secs) = options.get("spam-throttle") {
+                match secs.parse::<u64>() {
+                    Ok(secs) if secs > 0 => {
+                        let spam = config.spam.get_or_insert_with(SpamConfig::default);
+                        spam.action = SpamAction::Throttle(Duration::from_secs(secs));
+                    }
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_spam_throttle", value = secs));
+                        return;
+                    }
+                }
+            }
+            if let Some(size) = options.get("max-file-size") {
+                match TransferPolicy::parse_size(size) {
+                    Some(0) => config.transfer_policy.max_size = None,
+                    Some(max) => config.transfer_policy.max_size = Some(max),
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_max_file_size", value = size));
+                        return;
+                    }
+                }
+            }
+            if let Some(list) = options.get("block-extensions") {
+                config.transfer_policy.blocked_extensions = TransferPolicy::parse_extensions(list);
+            }
+            config.transfer_policy.scan_command = options.get("scan-command").cloned();
+            if let Some(dir) = options.get("transfer-quarantine") {
+                config.transfer_policy.quarantine_dir = PathBuf::from(dir);
+            }
+            config.audit_log = options.get("audit-log").map(PathBuf::from);
+            config.quarantine = options.contains_key("quarantine");
+            config.trace = options.contains_key("trace");
+            if let Some(secs) = options.get("reconnect-after") {
+                match secs.parse::<u64>() {
+                    Ok(secs) => config.reconnect_after = Some(Duration::from_secs(secs)),
+                    Err(_) => {
+                        eprintln!("{}", tr!("cli.invalid_reconnect_after", value = secs));
+                        return;
+                    }
+                }
+            }
+            config.invite_only = options.contains_key("invite-only");
+            config.log_messages = !options.contains_key("no-message-log");
+            #[cfg(feature = "chaos")]
+            if let Some(spec) = options.get("chaos") {
+                match ChaosConfig::from_string(spec) {
+                    Some(chaos) => config.chaos = Some(chaos),
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_chaos", value = spec));
+                        return;
+                    }
+                }
+            }
+            #[cfg(feature = "websocket")]
+            {
+                config.websocket = options.get("ws").cloned();
+            }
+            config.webhooks = options.get("hooks").cloned();
+            // 桥接令牌与对象存储的访问密钥未在命令行或环境变量中提供时从服务器的密钥存储读取
+            #[cfg(any(feature = "bridges", feature = "storage"))]
+            let mut server_secrets = LazySecrets::new(&options, "server");
+            #[cfg(feature = "bridges")]
+            if let Some(chat_id) = options.get("telegram") {
+                let Ok(chat_id) = chat_id.parse::<i64>() else {
+                    eprintln!("{}", tr!("cli.invalid_telegram_chat", value = chat_id));
+                    return;
+                };
+                let Ok(token) = server_secrets.lookup(
+                    options.get("telegram-token"),
+                    "CHAT_TELEGRAM_TOKEN",
+                    SECRET_TELEGRAM_TOKEN,
+                ) else {
+                    return;
+                };
+                let Some(token) = token else {
+                    eprintln!("{}", tr!("cli.telegram_token_required"));
+                    return;
+                };
+                let mut telegram = TelegramConfig::new(token, chat_id);
+                if let Some(api) = options.get("telegram-api") {
+                    telegram.api = api.clone();
+                }
+                config.telegram = Some(telegram);
+            }
+            #[cfg(feature = "bridges")]
+            if let Some(spec) = options.get("xmpp") {
+                // 省略密钥（`域名` 或 `域名@主机:端口`）时从密钥存储的 `xmpp-secret` 条目读取
+                let spec = match spec.contains('=') {
+                    true => spec.clone(),
+                    false => {
+                        let Ok(secret) =
+                            server_secrets.lookup(None, "CHAT_XMPP_SECRET", SECRET_XMPP)
+                        else {
+                            return;
+                        };
+                        let secret = secret.unwrap_or_default();
+                        match spec.split_once('@') {
+                            Some((domain, addr)) => format!("{}={}@{}", domain, secret, addr),
+                            None => format!("{}={}", spec, secret),
+                        }
+                    }
+                };
+                match XmppConfig::from_string(&spec) {
+                    Some(xmpp) => config.xmpp = Some(xmpp),
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_xmpp", value = spec));
+                        return;
+                    }
+                }
+            }
+            if let Some(jobs) = options.get("jobs") {
+                for spec in jobs.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                    match JobConfig::from_string(spec) {
+                        Some(job) => config.jobs.push(job),
+                        None => {
+                            eprintln!("{}", tr!("cli.invalid_job", value = spec));
+                            return;
+                        }
+                    }
+                }
+            }
+            if let Some(bits) = options.get("pow") {
+                match bits.parse::<u8>() {
+                    Ok(bits @ 1..) if bits <= pow::MAX_DIFFICULTY => {
+                        config.pow_difficulty = Some(bits)
+                    }
+                    _ => {
+                        eprintln!(
+                            "{}",
+                            tr!("cli.invalid_pow", value = bits, max = pow::MAX_DIFFICULTY)
+                        );
+                        return;
+                    }
+                }
+            }
+            if let Some(admins) = options.get("admins") {
+                for spec in admins.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    // 每项为 用户名=令牌摘要（`chat admin token <用户名>` 生成）
+                    let admin = spec.split_once('=').filter(|(name, digest)| {
+                        !name.trim().is_empty()
+                            && digest.len() == 64
+                            && digest.bytes().all(|b| b.is_ascii_hexdigit())
+                    });
+                    let Some((name, digest)) = admin else {
+                        eprintln!("{}", tr!("cli.invalid_admin", value = spec));
+                        return;
+                    };
+                    config
+                        .admins
+                        .insert(name.trim().to_string(), digest.to_ascii_lowercase());
+                }
+            }
+
+            // 服务器联邦只作用于默认空间
+            let peers = options.get("peers").map_or("", String::as_str);
+            let mut federation = options.get("federation").map(|name| FederationConfig {
+                name: name.trim().to_string(),
+                spool: options.get("spool").map(PathBuf::from),
+                ..FederationConfig::default()
+            });
+            let needs_name = !peers.trim().is_empty()
+                || options.contains_key("upstream")
+                || options.contains_key("spool");
+            if needs_name && federation.is_none() {
+                eprintln!("{}", tr!("cli.federation_name_required"));
+                return;
+            }
+            if let Some(federation) = &mut federation {
+                for spec in peers.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    match PeerConfig::from_string(spec) {
+                        Some(peer) => federation.peers.push(peer),
+                        None => {
+                            eprintln!("{}", tr!("cli.invalid_peer", value = spec));
+                            return;
+                        }
+                    }
+                }
+                if let Some(upstream) = options.get("upstream") {
+                    if !federation.peers.iter().any(|peer| peer.name == *upstream) {
+                        eprintln!("{}", tr!("cli.invalid_upstream", value = upstream));
+                        return;
+                    }
+                    federation.upstream = Some(upstream.clone());
+                }
+            }
+            // 历史消息归档只作用于默认空间
+            #[cfg(feature = "storage")]
+            let archive = match (options.get("archive-after"), options.get("archive-to")) {
+                (None, None) => None,
+                (Some(days), Some(url)) => {
+                    let Some(days) = days.parse::<u32>().ok().filter(|days| *days > 0) else {
+                        eprintln!("{}", tr!("cli.invalid_archive_after", value = days));
+                        return;
+                    };
+                    let (Ok(access_key), Ok(secret_key)) = (
+                        server_secrets.lookup(None, "AWS_ACCESS_KEY_ID", SECRET_AWS_ACCESS_KEY),
+                        server_secrets.lookup(None, "AWS_SECRET_ACCESS_KEY", SECRET_AWS_SECRET_KEY),
+                    ) else {
+                        return;
+                    };
+                    let (Some(access_key), Some(secret_key)) = (access_key, secret_key) else {
+                        eprintln!("{}", tr!("cli.archive_credentials_required"));
+                        return;
+                    };
+                    let Some(mut archive) =
+                        ArchiveConfig::from_url(url, days, access_key, secret_key)
+                    else {
+                        eprintln!("{}", tr!("cli.invalid_archive_to", value = url));
+                        return;
+                    };
+                    if let Some(region) = options.get("archive-region") {
+                        archive.region = region.clone();
+                    }
+                    Some(archive)
+                }
+                _ => {
+                    eprintln!("{}", tr!("cli.archive_incomplete"));
+                    return;
+                }
+            };
+            let main_config = ServerConfig {
+                federation,
+                #[cfg(feature = "storage")]
+                archive,
+                admin_socket: options.get("admin-socket").map(PathBuf::from),
+                ..config.clone()
+            };
+            let keys = match options
+                .get("storage-keys")
+                .map(|s| StorageKeys::from_source(s))
+            {
+                Some(Ok(keys)) => Some(keys),
+                Some(Err(e)) => {
+                    eprintln!("{}", tr!("cli.storage_keys_failed", error = e));
+                    return;
+                }
+                None => None,
+            };
+            // systemd 套接字激活时沿用传入的监听套接字，忽略命令行中的地址
+            #[cfg(feature = "websocket")]
+            let websocket = main_config.websocket.is_some();
+            #[cfg(not(feature = "websocket"))]
+            let websocket = false;
+            let inherited = match Listeners::from_systemd(websocket) {
+                Ok(listeners) => listeners,
+                Err(e) => {
+                    eprintln!("{}", tr!("cli.server_failed", error = format!("{:?}", e)));
+                    return;
+                }
+            };
+            // 接管旧进程：先绑定端口，待旧进程断开所有用户、写完数据后再载入数据目录
+            let listeners = if options.contains_key("takeover") {
+                let Some(socket) = main_config.admin_socket.clone() else {
+                    eprintln!("{}", tr!("cli.takeover_requires_admin_socket"));
+                    return;
+                };
+                let listeners = match inherited {
+                    Some(listeners) => listeners,
+                    None => match Listeners::bind(&addr, &main_config).await {
+                        Ok(listeners) => listeners,
+                        Err(e) => {
+                            eprintln!("{}", tr!("cli.server_failed", error = format!("{:?}", e)));
+                            return;
+                        }
+                    },
+                };
+                match take_over(&socket).await {
+                    Ok(true) => println!("{}", tr!("cli.took_over")),
+                    Ok(false) => println!("{}", tr!("cli.nothing_to_take_over")),
+                    Err(e) => {
+                        eprintln!("{}", tr!("cli.takeover_failed", error = e));
+                        return;
+                    }
+                }
+                Some(listeners)
+            } else {
+                inherited
+            };
+            let Some(mut server) = open_server(main_config, options.get("data-dir"), keys.as_ref())
+            else {
+                return;
+            };
+            // 每个虚拟主机沿用同一份配置，数据各自保存
+            let vhosts = options.get("vhosts").map_or("", String::as_str);
+            for entry in vhosts.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (name, dir) = match entry.split_once('=') {
+                    Some((name, dir)) => (name.trim(), Some(dir.trim().to_string())),
+                    None => (entry, None),
+                };
+                if name.is_empty() {
+                    eprintln!("{}", tr!("cli.invalid_vhost", value = entry));
+                    return;
+                }
+                let Some(host) = open_server(config.clone(), dir.as_ref(), keys.as_ref()) else {
+                    return;
+                };
+                server = server.with_vhost(name, host);
+            }
+            if !server.vhost_names().is_empty() {
+                println!(
+                    "{}",
+                    tr!("cli.vhosts", list = server.vhost_names().join(", "))
+                );
+            }
+            let result = match listeners {
+                Some(listeners) => server.run_on(listeners).await,
+                None => server.run(&addr).await,
+            };
+            if let Err(e) = result {
+                eprintln!("{}", tr!("cli.server_failed", error = format!("{:?}", e)));
+            }
+        }
+        #[cfg(not(feature = "server"))]
+        Some(TaskType::Server) => {
+            feature_disabled("server", "server");
+            process::exit(1);
+        }
+        #[cfg(feature = "feedbot")]
+        Some(TaskType::FeedBot) => {
+            println!("{}", tr!("cli.starting_feedbot"));
+            let addr = args
+                .get(2)
+                .cloned()
+                .unwrap_or_else(|| String::from("127.0.0.1:7891"));
+            let list = |key: &str| -> Vec<String> {
+                options
+                    .get(key)
+                    .map(|value| {
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|item| !item.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            let (feeds, to) = (list("feeds"), list("to"));
+            let Some(name) = options.get("name").map(|name| name.trim().to_string()) else {
+                eprintln!("{}", tr!("cli.feedbot_usage"));
+                process::exit(1);
+            };
+            if feeds.is_empty() || to.is_empty() {
+                eprintln!("{}", tr!("cli.feedbot_usage"));
+                process::exit(1);
+            }
+            let interval = match options.get("interval").map(|secs| secs.parse::<u64>()) {
+                None => Duration::from_secs(300),
+                Some(Ok(secs)) if secs > 0 => Duration::from_secs(secs),
+                Some(_) => {
+                    eprintln!(
+                        "{}",
+                        tr!("cli.invalid_interval", value = options["interval"])
+                    );
+                    process::exit(1);
+                }
+            };
+            let state = options
+                .get("state")
+                .map(PathBuf::from)
+                .or_else(|| chat::data_dir(&name).map(|dir| dir.join("feeds.json")));
+            let mut bot = match FeedBot::open(FeedConfig {
+                feeds,
+                to,
+                interval,
+                state,
+            }) {
+                Ok(bot) => bot,
+                Err(e) => {
+                    eprintln!("{}", tr!("cli.feedbot_state_failed", error = e));
+                    process::exit(1);
+                }
             };
 
-            let server = chat::server::Server::new();
-            if let Err(e) = server.run(&addr).await {
-                eprintln!("服务器运行出错: {:?}", e);
+            let token = client_token(&options, &mut LazySecrets::new(&options, &name), &name);
+            let mut builder = Client::builder(name).on_message(|_| {}).on_error(|error| {
+                let code = format!("{:?}", error.code);
+                eprintln!(
+                    "{}",
+                    tr!("cli.script_error", code = code, message = error.message)
+                );
+            });
+            match socket_options(&options) {
+                Ok(socket) => builder = builder.socket(socket),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
             }
+            if let Some(token) = token {
+                builder = builder.token(token);
+            }
+            if let Err(e) = bot.run(&builder.build(), &addr).await {
+                eprintln!("{}", e);
+                process::exit(
+                    e.downcast_ref::<ConnectError>()
+                        .map_or(1, ConnectError::exit_code),
+                );
+            }
+        }
+        #[cfg(not(feature = "feedbot"))]
+        Some(TaskType::FeedBot) => {
+            feature_disabled("feedbot", "feedbot");
+            process::exit(1);
+        }
+        #[cfg(feature = "conformance")]
+        Some(TaskType::Conformance) => {
+            let codec = match options.get("codec") {
+                Some(name) => match Codec::from_string(name) {
+                    Some(codec) => codec,
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_codec", value = name));
+                        process::exit(1);
+                    }
+                },
+                None => Codec::default(),
+            };
+            process::exit(run_conformance(args.get(2).map(String::as_str), codec).await);
+        }
+        #[cfg(not(feature = "conformance"))]
+        Some(TaskType::Conformance) => {
+            feature_disabled("conformance", "conformance");
+            process::exit(1);
+        }
+        #[cfg(feature = "server")]
+        Some(TaskType::Admin) => process::exit(run_admin(&args, &options).await),
+        #[cfg(not(feature = "server"))]
+        Some(TaskType::Admin) => {
+            feature_disabled("admin", "server");
+            process::exit(1);
+        }
+        #[cfg(feature = "metrics")]
+        Some(TaskType::Bench) => process::exit(run_bench(&options).await),
+        #[cfg(not(feature = "metrics"))]
+        Some(TaskType::Bench) => {
+            feature_disabled("bench", "metrics");
+            process::exit(1);
         }
         Some(TaskType::Client) => {
-            println!("启动客户端模式...");
+            println!("{}", tr!("cli.starting_client"));
             // 如果命令行传入了服务器IP地址，则使用；否则默认使用本地地址
             let addr = if args.len() >= 3 {
                 args[2].clone()
@@ -51,19 +1547,207 @@ async fn main() {
                 String::from("127.0.0.1:7891")
             };
 
-            print!("请输入用户名 >> ");
-            let mut input = String::new();
-            io::stdout().flush().unwrap();
-            io::stdin().read_line(&mut input).unwrap();
-            let username = input.trim().to_string();
+            // 脚本模式：--script=文件（`-` 表示标准输入），或标准输入不是终端
+            let script_source = match options.get("script") {
+                Some(source) => Some(source.as_str()),
+                None if !io::stdin().is_terminal() => Some("-"),
+                None => None,
+            };
+            let script = match script_source.map(read_script) {
+                Some(Ok(script)) => Some(script),
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+                None => None,
+            };
+
+            let username = match options.get("name") {
+                Some(name) => name.trim().to_string(),
+                None if script.is_some() => {
+                    eprintln!("{}", tr!("cli.script_needs_name"));
+                    process::exit(1);
+                }
+                None => {
+                    terminal::prompt(tr!("cli.prompt_name")).unwrap();
+                    terminal::read_line()
+                        .unwrap()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string()
+                }
+            };
+
+            let mut builder = Client::builder(username.clone());
+            match socket_options(&options) {
+                Ok(socket) => builder = builder.socket(socket),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            }
+            if let Some(secs) = options.get("connect-timeout") {
+                match secs.parse::<f64>() {
+                    Ok(secs) if secs > 0.0 => {
+                        builder = builder.connect_timeout(Duration::from_secs_f64(secs))
+                    }
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_connect_timeout", value = secs));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(secs) = options.get("heartbeat") {
+                match secs.parse::<f64>() {
+                    Ok(0.0) => builder = builder.no_heartbeat(),
+                    Ok(secs) if secs > 0.0 => {
+                        builder = builder.heartbeat(Duration::from_secs_f64(secs))
+                    }
+                    _ => {
+                        eprintln!("{}", tr!("cli.invalid_heartbeat", value = secs));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(vhost) = options.get("vhost") {
+                builder = builder.vhost(vhost.clone());
+            }
+            if let Some(code) = options.get("invite") {
+                builder = builder.invite(code.clone());
+            }
+            let mut secrets = LazySecrets::new(&options, &username);
+            if let Some(token) = client_token(&options, &mut secrets, &username) {
+                builder = builder.token(token);
+            }
+
+            // 身份密钥不存在时生成；已知用户的公钥保存在密钥文件所在目录
+            #[cfg(feature = "crypto")]
+            let identity_path = options
+                .get("identity")
+                .map(Into::into)
+                .or_else(|| Identity::default_path(&username));
+            #[cfg(feature = "crypto")]
+            if let Some(path) = identity_path {
+                // 私钥保存在密钥存储中：--secrets 指定加密文件，否则优先使用系统密钥环
+                let store = match secrets.store() {
+                    Ok(store) => store,
+                    Err(e) => {
+                        eprintln!("{}", tr!("cli.open_secrets_failed", error = e));
+                        return;
+                    }
+                };
+                match Identity::load_or_create(store, &path) {
+                    Ok(identity) => builder = builder.identity(identity),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            tr!("cli.load_identity_failed", path = path.display(), error = e)
+                        );
+                        return;
+                    }
+                }
+                let known = path.with_file_name("known_keys");
+                match KnownKeys::open(&known) {
+                    Ok(keys) => builder = builder.known_keys(keys),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "cli.load_identity_failed",
+                                path = known.display(),
+                                error = e
+                            )
+                        );
+                        return;
+                    }
+                }
+            }
+
+            if let Some(script) = script {
+                process::exit(run_script(builder, &addr, &script).await);
+            }
+
+            // 交互模式默认保存本地聊天记录，可通过 --no-history 关闭
+            if !options.contains_key("no-history") {
+                let dir = options
+                    .get("history-dir")
+                    .map(Into::into)
+                    .or_else(|| History::default_dir(&username));
+                if let Some(dir) = dir {
+                    match History::open(&dir) {
+                        Ok(history) => builder = builder.history(history),
+                        Err(e) => eprintln!(
+                            "{}",
+                            tr!("cli.open_history_failed", dir = dir.display(), error = e)
+                        ),
+                    }
+                }
+            }
+
+            // @自己 总是高亮，--highlight 追加以逗号分隔的关键词
+            let mut rules =
+                HighlightRules::new().notify_muted(options.contains_key("notify-muted"));
+            if let Some(keywords) = options.get("highlight") {
+                for keyword in keywords.split(',') {
+                    rules = rules.keyword(keyword);
+                }
+            }
+            let theme = match options.get("theme") {
+                Some(path) => Theme::load(path.as_ref()),
+                None => Theme::load_default(),
+            };
+            match theme {
+                Ok(theme) => builder = builder.theme(theme),
+                Err(e) => {
+                    eprintln!("{}", tr!("cli.load_theme_failed", error = e));
+                    return;
+                }
+            }
+
+            let aliases = match options.get("aliases") {
+                Some(path) => Aliases::load(path.as_ref()),
+                None => Aliases::default_path()
+                    .map_or_else(|| Ok(Aliases::new()), |path| Aliases::load(&path)),
+            };
+            match aliases {
+                Ok(aliases) => builder = builder.aliases(aliases),
+                Err(e) => {
+                    eprintln!("{}", tr!("cli.load_aliases_failed", error = e));
+                    return;
+                }
+            }
+
+            builder = builder
+                .highlight(rules)
+                .emoji_on_display(options.contains_key("emoji-display"))
+                .markdown(!options.contains_key("plain"));
+            if let Some(dir) = options.get("downloads") {
+                builder = builder.downloads(dir);
+            }
+            if let Some(mode) = options.get("preview") {
+                match Preview::from_string(mode) {
+                    Some(preview) => builder = builder.preview(preview),
+                    None => {
+                        eprintln!("{}", tr!("cli.invalid_preview", value = mode));
+                        return;
+                    }
+                }
+            }
 
-            let client = chat::client::Client::new(username);
+            let client = builder.build();
             if let Err(e) = client.run(addr).await {
-                eprintln!("客户端运行出错: {:?}", e);
+                if let Some(e) = e.downcast_ref::<ConnectError>() {
+                    eprintln!("{}", e);
+                    process::exit(e.exit_code());
+                }
+                eprintln!("{}", tr!("cli.client_failed", error = format!("{:?}", e)));
+                process::exit(1);
             }
         }
         None => {
-            eprintln!("无效的模式，请使用 server 或 client");
+            eprintln!("{}", tr!("cli.invalid_mode"));
         }
     }
 }
@@ -0,0 +1,246 @@
+/*!
+# Markdown 渲染模块
+
+把消息内容中的基础 Markdown（粗体、斜体、删除线、行内代码、代码块、链接、
+标题、列表与引用）解析为带样式的文本片段 [`Span`]，由终端客户端映射为 ANSI 样式。
+线路上传输的仍是原始文本；客户端以 `--plain` 启动时不做渲染。
+
+聊天消息中的单个换行会被保留，而不是像 CommonMark 那样折叠为空格。
+未启用 `tui` 特性时不解析，整段消息作为一个无样式的片段。
+*/
+
+#[cfg(feature = "tui")]
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+/// 文本片段的样式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    /// 粗体（`**文本**` 或标题）
+    pub bold: bool,
+    /// 斜体（`*文本*`）
+    pub italic: bool,
+    /// 删除线（`~~文本~~`）
+    pub strikethrough: bool,
+    /// 行内代码或代码块
+    pub code: bool,
+    /// 链接文字
+    pub link: bool,
+    /// 辅助信息（链接地址、列表符号、引用标记等），以暗色显示
+    pub dim: bool,
+}
+
+/// 一段使用相同样式的文本
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// 将 Markdown 文本解析为样式片段
+#[cfg(feature = "tui")]
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut renderer = Renderer::default();
+    for event in Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH) {
+        renderer.event(event);
+    }
+    renderer.spans
+}
+
+/// 未启用 `tui` 特性时原样返回整段文本
+#[cfg(not(feature = "tui"))]
+pub fn parse(text: &str) -> Vec<Span> {
+    vec![Span {
+        text: text.to_string(),
+        style: Style::default(),
+    }]
+}
+
+/// 解析过程中的状态
+#[cfg(feature = "tui")]
+#[derive(Default)]
+struct Renderer {
+    spans: Vec<Span>,
+    style: Style,
+    /// 上一个块级元素已结束，下一段内容前需要换行
+    pending_newline: bool,
+    /// 当前链接的地址，链接结束时追加显示
+    link_url: Option<String>,
+    /// 嵌套列表的下一个序号（无序列表为 `None`）
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+}
+
+#[cfg(feature = "tui")]
+impl Renderer {
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => {
+                let style = Style {
+                    code: true,
+                    ..self.style
+                };
+                self.push(&code, style);
+            }
+            Event::SoftBreak | Event::HardBreak => self.newline(),
+            Event::Html(html) | Event::InlineHtml(html) => self.text(&html),
+            Event::Rule => {
+                self.block_start();
+                self.push("────────", Style::dim());
+                self.pending_newline = true;
+            }
+            Event::TaskListMarker(done) => {
+                self.push(if done { "[x] " } else { "[ ] " }, Style::dim());
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Paragraph => self.block_start(),
+            Tag::Heading { .. } => {
+                self.block_start();
+                self.style.bold = true;
+            }
+            Tag::BlockQuote(_) => {
+                self.block_start();
+                self.quote_depth += 1;
+                // 外层引用前缀已由换行补上，这里只需补上新的一层
+                if !self.spans.is_empty() {
+                    self.push("│ ", Style::dim());
+                }
+            }
+            Tag::CodeBlock(kind) => {
+                self.block_start();
+                if let CodeBlockKind::Fenced(lang) = kind {
+                    if !lang.is_empty() {
+                        self.push(&format!("[{}]", lang), Style::dim());
+                        self.newline();
+                    }
+                }
+                self.style.code = true;
+            }
+            Tag::List(start) => {
+                self.block_start();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.block_start();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}{}. ", "  ".repeat(depth), *n - 1)
+                    }
+                    _ => format!("{}• ", "  ".repeat(depth)),
+                };
+                self.push(&marker, Style::dim());
+            }
+            Tag::Emphasis => self.style.italic = true,
+            Tag::Strong => self.style.bold = true,
+            Tag::Strikethrough => self.style.strikethrough = true,
+            Tag::Link { dest_url, .. } => {
+                self.style.link = true;
+                self.link_url = Some(dest_url.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Item => self.pending_newline = true,
+            TagEnd::Heading(_) => {
+                self.style.bold = false;
+                self.pending_newline = true;
+            }
+            TagEnd::BlockQuote(_) => {
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.pending_newline = true;
+            }
+            TagEnd::CodeBlock => {
+                self.style.code = false;
+                self.pending_newline = true;
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.pending_newline = true;
+            }
+            TagEnd::Emphasis => self.style.italic = false,
+            TagEnd::Strong => self.style.bold = false,
+            TagEnd::Strikethrough => self.style.strikethrough = false,
+            TagEnd::Link => {
+                self.style.link = false;
+                if let Some(url) = self.link_url.take() {
+                    // 链接文字与地址相同时（自动链接）不重复显示
+                    let shown = self.spans.last().is_some_and(|span| span.text == url);
+                    if !shown {
+                        self.push(&format!(" ({})", url), Style::dim());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 普通文本；代码块中的多行文本逐行输出，保证每行都带有引用前缀
+    fn text(&mut self, text: &str) {
+        let text = if self.style.code {
+            text.strip_suffix('\n').unwrap_or(text)
+        } else {
+            text
+        };
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if !line.is_empty() {
+                self.push(line, self.style);
+            }
+        }
+    }
+
+    /// 开始一个块级元素：补上前一个块留下的换行
+    fn block_start(&mut self) {
+        if self.pending_newline {
+            self.pending_newline = false;
+            self.newline();
+        }
+    }
+
+    fn newline(&mut self) {
+        self.push("\n", Style::default());
+        if self.quote_depth > 0 {
+            self.push(&"│ ".repeat(self.quote_depth), Style::dim());
+        }
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        if self.spans.is_empty() && self.quote_depth > 0 {
+            self.spans.push(Span {
+                text: "│ ".repeat(self.quote_depth),
+                style: Style::dim(),
+            });
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(text),
+            _ => self.spans.push(Span {
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+impl Style {
+    fn dim() -> Self {
+        Style {
+            dim: true,
+            ..Style::default()
+        }
+    }
+}
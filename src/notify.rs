@@ -0,0 +1,157 @@
+/*!
+# 通知偏好模块
+
+[`NotifyPrefs`] 是保存在服务器端用户资料中的通知偏好，随账号漫游到用户登录的每台设备：
+
+- 静音的会话（对方的用户名、`#` 开头的聊天室，或 `tg:`、`xmpp:` 等桥接地址）：来自这些会话的消息照常显示但不提示，
+  提及自己或命中关键词时除外；
+- 关键词：消息中出现关键词或 `@自己` 时醒目提示，匹配规则同 [`crate::highlight`]；
+- 免打扰时段（如 `22:00-07:00`，按服务器的本地时间，可以跨过午夜）：时段内的消息一律不提示。
+
+与 `/dnd` 不同，这些偏好只影响提示，消息仍然立即送达。服务器投递消息时按接收者的偏好计算
+[`NotifyHint`]，附在消息上（见 [`Message::notify`]），客户端据此决定是否响铃；偏好由 `/notify` 指令修改。
+*/
+
+use crate::highlight::HighlightRules;
+use crate::{Message, MessageKind};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// 免打扰时段的时间格式
+const TIME_FORMAT: &str = "%H:%M";
+
+/// 服务器附在消息上的提示建议
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyHint {
+    /// 按客户端自己的规则提示（服务器不附加该值）
+    #[default]
+    Normal,
+    /// 提及了接收者或命中其关键词，应醒目提示
+    Highlight,
+    /// 来自静音的会话或处于免打扰时段，不应提示
+    Silent,
+}
+
+/// 每天的免打扰时段，`start` 晚于 `end` 时跨过午夜
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct QuietHours {
+    /// 开始时间（含）
+    pub start: NaiveTime,
+    /// 结束时间（不含）
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// 解析 `22:00-07:00`
+    ///
+    /// # 返回值
+    /// 格式不正确或开始与结束时间相同时返回 `None`
+    pub fn from_string(spec: &str) -> Option<Self> {
+        let (start, end) = spec.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), TIME_FORMAT).ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), TIME_FORMAT).ok()?;
+        (start != end).then_some(QuietHours { start, end })
+    }
+
+    /// `time` 是否在时段内
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+/// 用户的通知偏好
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NotifyPrefs {
+    /// 静音的会话
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub muted: BTreeSet<String>,
+    /// 醒目提示的关键词
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    /// 每天的免打扰时段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl NotifyPrefs {
+    /// 静音会话 `conversation`，已经静音时返回 `false`
+    pub fn mute(&mut self, conversation: &str) -> bool {
+        self.muted.insert(conversation.to_string())
+    }
+
+    /// 取消静音会话 `conversation`，原本未静音时返回 `false`
+    pub fn unmute(&mut self, conversation: &str) -> bool {
+        self.muted.remove(conversation)
+    }
+
+    /// 添加关键词（忽略大小写去重），空白或已有的关键词返回 `false`
+    pub fn add_keyword(&mut self, keyword: &str) -> bool {
+        let keyword = keyword.trim();
+        if keyword.is_empty()
+            || self
+                .keywords
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(keyword))
+        {
+            return false;
+        }
+        self.keywords.push(keyword.to_string());
+        true
+    }
+
+    /// 删除关键词（忽略大小写），原本没有时返回 `false`
+    pub fn remove_keyword(&mut self, keyword: &str) -> bool {
+        let before = self.keywords.len();
+        self.keywords
+            .retain(|k| !k.eq_ignore_ascii_case(keyword.trim()));
+        self.keywords.len() != before
+    }
+
+    /// 按偏好计算 `own_name` 收到 `msg` 时的提示建议，`now` 为服务器的本地时间
+    ///
+    /// 免打扰时段优先于一切；其次提及自己或命中关键词时醒目提示，即使会话已静音；
+    /// 端到端加密的消息无法匹配关键词，只按静音与免打扰时段判断
+    pub fn hint(&self, own_name: &str, msg: &Message, now: NaiveTime) -> NotifyHint {
+        if self.quiet_hours.is_some_and(|quiet| quiet.contains(now)) {
+            return NotifyHint::Silent;
+        }
+        if msg.kind() != MessageKind::Encrypted
+            && !self.rules().find(own_name, msg.content()).is_empty()
+        {
+            return NotifyHint::Highlight;
+        }
+        // 聊天室中的消息按发送者或聊天室（接收方）静音
+        let room = Some(msg.to()).filter(|to| *to != own_name);
+        if self.muted.contains(msg.from()) || room.is_some_and(|room| self.muted.contains(room)) {
+            return NotifyHint::Silent;
+        }
+        NotifyHint::Normal
+    }
+
+    /// 以关键词构造的高亮规则
+    fn rules(&self) -> HighlightRules {
+        self.keywords
+            .iter()
+            .fold(HighlightRules::new(), |rules, keyword| {
+                rules.keyword(keyword.as_str())
+            })
+    }
+}
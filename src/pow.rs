@@ -0,0 +1,63 @@
+/*!
+# 工作量证明模块
+
+公开服务器可以要求新连接在注册前完成一个小的工作量证明，提高批量注册机器人的成本。
+
+服务器在握手时发出 [`Control::Challenge`](crate::protocol::Control::Challenge)，
+其中包含随机的 `nonce` 与难度（前导零比特数）；客户端寻找一个数字 `solution`，
+使 `SHA-256("{nonce}:{solution}")` 至少有 `difficulty` 个前导零比特，
+再以 [`Control::Proof`](crate::protocol::Control::Proof) 回复。
+难度每增加 1，客户端平均需要的计算量翻倍，而服务器验证只需计算一次哈希。
+*/
+
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::io;
+
+/// 允许的最大难度；客户端拒绝更高难度的挑战，以免长时间占用 CPU
+pub const MAX_DIFFICULTY: u8 = 32;
+
+/// 生成一个随机的挑战 nonce（32 个十六进制字符）
+pub fn new_nonce() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    let mut nonce = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(nonce, "{:02x}", byte);
+    }
+    Ok(nonce)
+}
+
+/// 检查 `solution` 是否满足挑战
+pub fn verify(nonce: &str, difficulty: u8, solution: u64) -> bool {
+    leading_zero_bits(&digest(nonce, solution)) >= u32::from(difficulty)
+}
+
+/// 从 0 开始依次尝试，返回第一个满足挑战的 `solution`
+pub fn solve(nonce: &str, difficulty: u8) -> u64 {
+    (0..)
+        .find(|&solution| verify(nonce, difficulty, solution))
+        .expect("在 u64 范围内必然存在解")
+}
+
+fn digest(nonce: &str, solution: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(nonce.as_bytes())
+        .chain_update(b":")
+        .chain_update(solution.to_string().as_bytes())
+        .finalize()
+        .into()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
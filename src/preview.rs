@@ -0,0 +1,284 @@
+/*!
+# 图片预览模块
+
+客户端收到图片文件，或以 `/preview <编号|路径>` 预览消息中的图片链接（见 [`crate::link`]）、本地图片时，
+在消息区显示一张低分辨率的缩略图（不超过 [`PREVIEW_WIDTH`]×[`PREVIEW_HEIGHT`] 像素）：
+
+- 支持 kitty 图形协议的终端（kitty、WezTerm、Ghostty）以 PNG 传输缩略图；
+- 支持 sixel 的终端（foot、mlterm、contour，或 `TERM` 中带有 `sixel` 的终端）以 216 色调色板编码；
+- 其他终端、标准输出不是终端或未启用 `tui` 特性时只显示一行占位文字：格式、尺寸与文件大小。
+
+终端的图形能力按环境变量判断（见 [`Preview::detect`]），判断不准时以 `--preview=kitty|sixel|text|off`
+指定。图片链接只在输入 `/preview` 时才会抓取，收到消息时不会自动访问其中的链接，以免向发送者暴露自己的地址。
+*/
+
+use crate::{humanize_bytes, link, tr};
+use std::io;
+use std::path::Path;
+
+/// 缩略图的最大宽度（像素）
+pub const PREVIEW_WIDTH: u32 = 320;
+
+/// 缩略图的最大高度（像素），为 6 的倍数以便按 sixel 的行带编码
+pub const PREVIEW_HEIGHT: u32 = 192;
+
+/// 预览时读取或抓取的最大字节数
+pub const MAX_PREVIEW_LEN: u64 = 16 * 1024 * 1024;
+
+/// 按扩展名视为图片的文件
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "gif"];
+
+/// 解码图片时最多分配的内存，防止尺寸极大的图片耗尽内存
+#[cfg(feature = "tui")]
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// kitty 图形协议每个转义序列携带的 Base64 字节数
+#[cfg(feature = "tui")]
+const KITTY_CHUNK: usize = 4096;
+
+/// 图片的显示方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preview {
+    /// kitty 图形协议
+    Kitty,
+    /// sixel 图形
+    Sixel,
+    /// 只显示格式、尺寸与文件大小
+    Text,
+    /// 收到图片时不预览（`/preview` 仍以文字显示）
+    Off,
+}
+
+impl Preview {
+    /// 按环境变量判断终端支持的图形协议；标准输出不是终端或未启用 `tui` 特性时为 [`Preview::Text`]
+    pub fn detect() -> Self {
+        #[cfg(feature = "tui")]
+        {
+            use std::io::IsTerminal;
+            if !io::stdout().is_terminal() {
+                return Preview::Text;
+            }
+            let term = std::env::var("TERM").unwrap_or_default();
+            let program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+            if term == "xterm-kitty"
+                || std::env::var_os("KITTY_WINDOW_ID").is_some()
+                || matches!(program.as_str(), "WezTerm" | "ghostty")
+            {
+                return Preview::Kitty;
+            }
+            if term.contains("sixel")
+                || ["foot", "mlterm", "contour"]
+                    .iter()
+                    .any(|name| term.starts_with(name))
+            {
+                return Preview::Sixel;
+            }
+            Preview::Text
+        }
+        #[cfg(not(feature = "tui"))]
+        {
+            Preview::Text
+        }
+    }
+
+    /// 解析 `--preview` 的取值：`auto`、`kitty`、`sixel`、`text` 或 `off`
+    pub fn from_string(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::detect()),
+            "kitty" => Some(Preview::Kitty),
+            "sixel" => Some(Preview::Sixel),
+            "text" => Some(Preview::Text),
+            "off" => Some(Preview::Off),
+            _ => None,
+        }
+    }
+}
+
+/// 文件名是否像图片（按扩展名判断）
+pub fn is_image(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// 读取本地图片，超过 [`MAX_PREVIEW_LEN`] 时返回错误
+pub async fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let len = tokio::fs::metadata(path).await?.len();
+    if len > MAX_PREVIEW_LEN {
+        return Err(io::Error::other(tr!(
+            "preview.too_large",
+            max = humanize_bytes(MAX_PREVIEW_LEN)
+        )));
+    }
+    tokio::fs::read(path).await
+}
+
+/// 抓取图片链接的内容（最多 [`MAX_PREVIEW_LEN`] 字节）
+pub async fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    link::fetch(url, "image/*", MAX_PREVIEW_LEN).await
+}
+
+/// 生成图片 `bytes` 的预览：图形序列加一行说明，或一行占位文字；`label` 为文件名或链接。
+/// 无法识别为图片时返回 `None`
+#[cfg(feature = "tui")]
+pub fn render(bytes: &[u8], label: &str, mode: Preview) -> Option<String> {
+    use image::ImageReader;
+    use std::io::Cursor;
+
+    let reader = || -> Option<ImageReader<Cursor<&[u8]>>> {
+        let mut reader = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .ok()?;
+        let mut limits = image::Limits::default();
+        limits.max_alloc = Some(MAX_DECODE_ALLOC);
+        reader.limits(limits);
+        Some(reader)
+    };
+    let format = reader()?.format()?;
+    let (width, height) = reader()?.into_dimensions().ok()?;
+    let caption = tr!(
+        "preview.caption",
+        name = label,
+        format = format!("{:?}", format).to_uppercase(),
+        width = width,
+        height = height,
+        size = humanize_bytes(bytes.len() as u64),
+    );
+    let graphics = match mode {
+        Preview::Kitty | Preview::Sixel => {
+            let thumbnail = reader()?
+                .decode()
+                .ok()?
+                .thumbnail(PREVIEW_WIDTH, PREVIEW_HEIGHT)
+                .to_rgba8();
+            match mode {
+                Preview::Kitty => kitty(&thumbnail)?,
+                _ => sixel(&thumbnail),
+            }
+        }
+        Preview::Text | Preview::Off => {
+            return Some(tr!("preview.placeholder", caption = caption));
+        }
+    };
+    Some(format!("{}\n{}", graphics, caption))
+}
+
+/// 未启用 `tui` 特性时不解码图片，按扩展名只显示文件名与大小
+#[cfg(not(feature = "tui"))]
+pub fn render(bytes: &[u8], label: &str, _mode: Preview) -> Option<String> {
+    is_image(label).then(|| {
+        tr!(
+            "preview.placeholder_plain",
+            name = label,
+            size = humanize_bytes(bytes.len() as u64),
+        )
+    })
+}
+
+/// 以 kitty 图形协议显示 `image`：PNG 编码后按 [`KITTY_CHUNK`] 分段传输，`q=2` 禁止终端回复
+#[cfg(feature = "tui")]
+fn kitty(image: &image::RgbaImage) -> Option<String> {
+    use base64ct::{Base64, Encoding};
+    use image::codecs::png::PngEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgba8,
+        )
+        .ok()?;
+    let data = Base64::encode_string(&png);
+    let chunks: Vec<&str> = data
+        .as_bytes()
+        .chunks(KITTY_CHUNK)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    let mut out = String::with_capacity(data.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            out.push_str(&format!("\x1b_Ga=T,f=100,q=2,m={};{}\x1b\\", more, chunk));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    Some(out)
+}
+
+/// 以 sixel 显示 `image`：颜色量化到 6×6×6 的调色板，每 6 行像素为一个行带，
+/// 行带内每种颜色一遍、连续相同的列以 `!` 重复计数压缩；半透明以下的像素不绘制
+#[cfg(feature = "tui")]
+fn sixel(image: &image::RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let color = |x: u32, y: u32| -> Option<usize> {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let level = |c: u8| (usize::from(c) * 5 + 127) / 255;
+        (a >= 128).then(|| level(r) * 36 + level(g) * 6 + level(b))
+    };
+    let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            i,
+            i / 36 * 20,
+            i / 6 % 6 * 20,
+            i % 6 * 20
+        ));
+    }
+    for top in (0..height).step_by(6) {
+        let rows = (height - top).min(6);
+        let mut used = [false; 216];
+        for y in top..top + rows {
+            for x in 0..width {
+                if let Some(c) = color(x, y) {
+                    used[c] = true;
+                }
+            }
+        }
+        let mut first = true;
+        for c in (0..216).filter(|c| used[*c]) {
+            if !first {
+                // 回到行带开头，以下一种颜色叠加绘制
+                out.push('$');
+            }
+            first = false;
+            out.push_str(&format!("#{}", c));
+            let mut run: Option<(char, usize)> = None;
+            for x in 0..width {
+                let bits = (0..rows)
+                    .filter(|dy| color(x, top + dy) == Some(c))
+                    .fold(0u8, |bits, dy| bits | 1 << dy);
+                let ch = char::from(63 + bits);
+                run = match run {
+                    Some((prev, n)) if prev == ch => Some((prev, n + 1)),
+                    Some((prev, n)) => {
+                        push_run(&mut out, prev, n);
+                        Some((ch, 1))
+                    }
+                    None => Some((ch, 1)),
+                };
+            }
+            if let Some((prev, n)) = run {
+                push_run(&mut out, prev, n);
+            }
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
+
+/// 输出 `n` 个连续的 sixel 字符 `ch`，超过 3 个时使用重复计数
+#[cfg(feature = "tui")]
+fn push_run(out: &mut String, ch: char, n: usize) {
+    if n > 3 {
+        out.push_str(&format!("!{}{}", n, ch));
+    } else {
+        out.extend(std::iter::repeat_n(ch, n));
+    }
+}
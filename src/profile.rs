@@ -0,0 +1,188 @@
+/*!
+# 用户资料模块
+
+[`Profile`] 保存用户自行设置的资料（显示名、头像、代词与简介）、隐私设置 [`Privacy`] 以及通知偏好
+（[`NotifyPrefs`]，见 [`crate::notify`]）。
+服务器在回答 `/whois` 时按照目标用户的隐私设置决定公开哪些信息。
+
+头像可以是一个表情（如 `🐱`，可由多个码点组成）或一张图片的链接：表情头像随在线名单推送给
+其他用户，客户端显示在消息的发送者之前；图片头像只在 `/whois` 中以链接给出，可用 `/preview` 查看。
+*/
+
+use crate::notify::NotifyPrefs;
+use crate::sanitize::is_dangerous;
+use serde::{Deserialize, Serialize};
+
+/// 显示名的最大字符数
+pub const MAX_DISPLAY_NAME_CHARS: usize = 64;
+
+/// 表情头像的最大字符数（组合表情由多个码点组成，如肤色、零宽连接符）
+pub const MAX_EMOJI_AVATAR_CHARS: usize = 16;
+
+/// 头像链接的最大字节数
+pub const MAX_AVATAR_URL_LEN: usize = 256;
+
+/// 代词的最大字符数
+pub const MAX_PRONOUNS_CHARS: usize = 32;
+
+/// 简介的最大字符数
+pub const MAX_BIO_CHARS: usize = 280;
+
+/// 隐私设置：控制 `/whois` 对其他用户公开哪些信息
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Privacy {
+    /// 公开在线状态
+    pub show_status: bool,
+    /// 公开空闲时长
+    pub show_idle: bool,
+    /// 公开客户端软件及版本
+    pub show_client: bool,
+}
+
+impl Default for Privacy {
+    fn default() -> Self {
+        Self {
+            show_status: true,
+            show_idle: true,
+            show_client: true,
+        }
+    }
+}
+
+impl Privacy {
+    /// 根据名称修改一项设置
+    ///
+    /// # 参数
+    /// - `field`: 设置项名称（"status"、"idle" 或 "client"）
+    /// - `visible`: 是否公开
+    ///
+    /// # 返回值
+    /// 若设置项名称无效，返回 `false`
+    pub fn set(&mut self, field: &str, visible: bool) -> bool {
+        match field {
+            "status" => self.show_status = visible,
+            "idle" => self.show_idle = visible,
+            "client" => self.show_client = visible,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// 修改资料失败的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldError {
+    /// 没有这一资料项
+    Unknown,
+    /// 超过该项的最大字符数
+    TooLong(usize),
+    /// 头像既不是表情也不是 `http://`、`https://` 链接
+    BadAvatar,
+    /// 含有控制字符（包括换行）
+    Control,
+}
+
+/// 用户资料
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Profile {
+    /// 显示名，未设置时使用用户名
+    pub display_name: Option<String>,
+    /// 头像：一个表情或图片链接
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// 代词，如 `she/her`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pronouns: Option<String>,
+    /// 简介
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// 隐私设置
+    pub privacy: Privacy,
+    /// 通知偏好，随账号在各设备间同步
+    #[serde(default)]
+    pub notify: NotifyPrefs,
+}
+
+impl Profile {
+    /// 根据名称修改显示名、头像、代词或简介，`value` 为空时清除该项
+    ///
+    /// # 参数
+    /// - `field`: 资料项名称（"name"、"avatar"、"pronouns" 或 "bio"）
+    /// - `value`: 新的取值，已去掉首尾空白
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), FieldError> {
+        let (slot, max) = match field {
+            "name" => (&mut self.display_name, MAX_DISPLAY_NAME_CHARS),
+            "avatar" => (&mut self.avatar, MAX_EMOJI_AVATAR_CHARS),
+            "pronouns" => (&mut self.pronouns, MAX_PRONOUNS_CHARS),
+            "bio" => (&mut self.bio, MAX_BIO_CHARS),
+            _ => return Err(FieldError::Unknown),
+        };
+        if value.is_empty() {
+            *slot = None;
+            return Ok(());
+        }
+        if value.chars().any(|c| c.is_control() || is_dangerous(c)) {
+            return Err(FieldError::Control);
+        }
+        if field == "avatar" {
+            if !is_avatar_url(value) && !is_emoji(value) {
+                return Err(FieldError::BadAvatar);
+            }
+        } else if value.chars().count() > max {
+            return Err(FieldError::TooLong(max));
+        }
+        *slot = Some(value.to_string());
+        Ok(())
+    }
+
+    /// 表情头像；未设置头像或头像是图片链接时为 `None`
+    pub fn avatar_emoji(&self) -> Option<&str> {
+        self.avatar
+            .as_deref()
+            .filter(|avatar| !is_avatar_url(avatar))
+    }
+}
+
+/// 是否为可用作头像的图片链接
+fn is_avatar_url(value: &str) -> bool {
+    (value.starts_with("http://") || value.starts_with("https://"))
+        && value.len() <= MAX_AVATAR_URL_LEN
+        && !value.contains(char::is_whitespace)
+}
+
+/// 是否像一个表情：不超过 [`MAX_EMOJI_AVATAR_CHARS`] 个字符，不全是 ASCII，且不含字母、数字与空白
+fn is_emoji(value: &str) -> bool {
+    !value.is_ascii()
+        && value.chars().count() <= MAX_EMOJI_AVATAR_CHARS
+        && !value
+            .chars()
+            .any(|c| c.is_alphanumeric() || c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_display_names() {
+        let mut profile = Profile::default();
+        assert_eq!(profile.set("name", "Alice Liddell"), Ok(()));
+        assert_eq!(profile.display_name.as_deref(), Some("Alice Liddell"));
+        let long = "a".repeat(MAX_DISPLAY_NAME_CHARS + 1);
+        assert_eq!(
+            profile.set("name", &long),
+            Err(FieldError::TooLong(MAX_DISPLAY_NAME_CHARS))
+        );
+        assert_eq!(
+            profile.set("name", "Admin\u{1b}[2J"),
+            Err(FieldError::Control)
+        );
+        assert_eq!(
+            profile.set("name", "evil\u{202e}"),
+            Err(FieldError::Control)
+        );
+        assert_eq!(profile.display_name.as_deref(), Some("Alice Liddell"));
+        assert_eq!(profile.set("name", ""), Ok(()));
+        assert_eq!(profile.display_name, None);
+    }
+}
@@ -0,0 +1,585 @@
+/*!
+# 协议模块
+
+定义客户端与服务器之间除聊天消息以外的控制帧，目前包括：
+
+- **Hello**
+  注册握手帧，携带用户名、客户端版本、客户端支持的可选特性、客户端的语言，
+  要进入的虚拟主机（同一服务器进程上彼此隔离的聊天空间），仅限邀请的服务器上
+  新用户出示的邀请码，机器人账号登录时出示的令牌，以及客户端的身份公钥。
+
+- **Registration**
+  服务器接收的注册帧，兼容旧客户端直接发送的用户名字符串。
+
+- **Frame** 与 **Control**
+  注册完成后双向传输的帧：聊天消息保持原有 JSON 结构不变，
+  控制帧通过 `type` 字段区分（如心跳 `ping`/`pong`）。
+
+断线重连：声明 `resume` 特性的客户端在注册后收到服务器当前最新的消息编号
+（[`Control::Seq`]），并随收到的消息更新；重连时在 [`Hello::resume_from`]
+中带上最后收到的编号，服务器补发此后发给该用户的消息。
+
+错误帧：声明 `errors` 特性的客户端在消息无法投递等情况下收到 [`Control::Error`]，
+其中的 [`ErrorCode`] 便于程序化使用者区分处理、交互式客户端按错误码本地化提示；
+未声明该特性的旧客户端仍收到 "Server" 发来的文字提示。
+
+工作量证明：服务器要求时（见 [`crate::pow`]），声明了 `pow` 特性的新用户在握手后收到
+[`Control::Challenge`]，回复 [`Control::Proof`] 并通过验证后才完成注册；
+未声明该特性的客户端直接被拒绝。
+
+身份证明：握手帧带有身份公钥（[`Hello::identity`]）时，服务器先发出 [`Control::IdentityChallenge`]，
+客户端以该公钥对应的私钥签名后回复 [`Control::IdentityProof`]（见 [`crate::identity::Identity::sign_login`]）。
+用户名在首次注册时绑定证明过的公钥，此后只有证明持有同一把私钥的连接才被视为该已注册用户，
+其余连接以 [`ErrorCode::IdentityRequired`] 拒绝；工作量证明与邀请码只对尚未绑定公钥的用户名要求。
+
+服务器联邦：对端服务器以声明了 `federation` 特性的握手帧连入（`name` 为其服务器名），
+经 [`Control::PeerChallenge`]、[`Control::PeerAuth`] 与 [`Control::PeerWelcome`] 以共享密钥
+互相认证后，在该链路上发送 [`Control::Relay`]（转发给本服务器用户的消息）与
+[`Control::Presence`]（对端的在线用户），见 `server` 模块的 `federation` 子模块。
+开启了路由追踪的服务器转发的消息带有已经过的路由记录（[`TraceHop`]），沿途与收件服务器
+追加各自的记录，收件服务器以 [`Control::TraceReport`] 把它们报告回发送者所在的服务器。
+
+文件传输：声明了 `files` 特性的客户端之间经 [`Control::FileOffer`] 与 [`Control::FileAnswer`]
+商定传输，再经服务器交换 [`Control::FileCandidates`]（各自的本地监听地址，以及服务器观察到的
+公网地址）尝试直连；直连失败时以 [`Control::FileChunk`] 经服务器中转，见 [`crate::transfer`]。
+因一方断线中断的传输（`interrupted` 为 `true` 的 [`Control::FileCancel`]）可以续传：发送方重新提议后，
+保留了部分内容的接收方以 [`Control::FileResume`] 代替 [`Control::FileAnswer`] 接受，告知从哪里继续。
+
+端到端加密：声明了 `e2e` 特性的客户端之间经服务器转发 [`Control::KeyExchange`]（以身份密钥签名的
+临时公钥）协商会话密钥，此后的消息以 [`MessageKind::Encrypted`](crate::MessageKind::Encrypted)
+发送，服务器只能看到密文；会话定期重新交换临时公钥，见 [`crate::e2e`]。
+
+在线名单推送：声明了 `roster` 特性的客户端在注册后收到当前在线用户的完整列表
+（[`Control::Roster`]），此后有用户上线、下线、更改状态（`/away`、`/dnd`）或头像时收到增量更新
+（[`Control::RosterUpdate`]），无需反复发送 `/list`。增量更新可以重复应用：上线与状态变化
+都是以用户名为键的覆盖写入，下线是删除；服务器来不及推送而丢失更新时重新发送完整列表。
+
+已读回执：客户端向用户显示了带编号的消息后发送 [`Control::Read`]，服务器据此更新发送者
+通过 `/receipts` 查看的投递状态。
+
+关闭通知：服务器关闭或平滑升级前，向声明了 `shutdown` 特性的客户端发送
+[`Control::ServerShutdown`]，说明原因（[`ShutdownReason`]），并可附带建议的重连等待时间，
+客户端据此提示用户并在到时后自动重连；未声明该特性的旧客户端仍收到 "Server" 发来的文字提示。
+
+主动退出：客户端在关闭连接前发送 [`Control::Goodbye`]（可附带告别语），服务器据此立即结束连接，
+并在日志、给会话对象的通知及最后在线时间中区分主动退出与连接中断。
+
+接收者地址：聊天消息的 `to` 可以是本服务器的用户名、以 `/` 开头的服务器指令，
+或 `用户@服务器` 形式的其他服务器上的用户，见 [`Recipient`]。
+
+帧的分隔与编码由 [`crate::codec`] 负责。
+*/
+
+use crate::i18n::Lang;
+use crate::Message;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 本客户端在握手中上报的软件标识
+pub const CLIENT_ID: &str = concat!("async-chat/", env!("CARGO_PKG_VERSION"));
+
+/// 服务器支持的可选特性，握手时与客户端声明的特性取交集
+pub const SUPPORTED_FEATURES: &[&str] = &[
+    FEATURE_RESUME,
+    FEATURE_ERRORS,
+    FEATURE_POW,
+    FEATURE_FILES,
+    FEATURE_E2E,
+    FEATURE_ROSTER,
+    FEATURE_SHUTDOWN,
+];
+
+/// 断线重连后按消息编号补发
+pub const FEATURE_RESUME: &str = "resume";
+
+/// 以结构化的错误帧代替文字提示
+pub const FEATURE_ERRORS: &str = "errors";
+
+/// 能够应答握手时的工作量证明挑战
+pub const FEATURE_POW: &str = "pow";
+
+/// 能够收发文件（由服务器撮合直连，失败时经服务器中转）
+pub const FEATURE_FILES: &str = "files";
+
+/// 能够建立端到端加密会话（经服务器转发密钥交换帧）
+pub const FEATURE_E2E: &str = "e2e";
+
+/// 接收在线名单的完整列表与增量更新
+pub const FEATURE_ROSTER: &str = "roster";
+
+/// 服务器关闭前收到结构化的关闭通知（原因与建议的重连等待时间）
+pub const FEATURE_SHUTDOWN: &str = "shutdown";
+
+/// 连接方是联邦中的对端服务器而不是用户；不参与与客户端的特性协商
+pub const FEATURE_FEDERATION: &str = "federation";
+
+/// 注册握手帧
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Hello {
+    /// 用户名
+    pub name: String,
+    /// 客户端使用的协议版本
+    #[serde(default)]
+    pub version: u32,
+    /// 客户端软件及版本，例如 `async-chat/0.1.0`
+    #[serde(default)]
+    pub client: String,
+    /// 客户端支持的可选特性
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// 重连时最后收到的消息编号，服务器补发编号更大的消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_from: Option<u64>,
+    /// 客户端的语言标签（如 `zh`、`en`），服务器发给该用户的提示使用该语言
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// 要进入的虚拟主机名称，省略时进入服务器的默认空间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// 邀请码：服务器仅限邀请注册时，新用户必须出示有效的邀请码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// 登录令牌：机器人账号出示管理员通过 `/bot create` 签发的令牌，管理员出示运营者为其生成的管理员令牌
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// 客户端的 Ed25519 身份公钥（十六进制），服务器要求以 [`Control::IdentityProof`] 证明持有对应的私钥
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+impl Hello {
+    /// 创建一个使用当前协议版本的握手帧
+    ///
+    /// # 参数
+    /// - `name`: 用户名
+    /// - `features`: 客户端支持的可选特性
+    pub fn new(name: String, features: Vec<String>) -> Self {
+        Self {
+            name,
+            version: PROTOCOL_VERSION,
+            client: CLIENT_ID.to_string(),
+            features,
+            resume_from: None,
+            lang: None,
+            host: None,
+            invite: None,
+            token: None,
+            identity: None,
+        }
+    }
+
+    /// 设置重连时最后收到的消息编号
+    pub fn with_resume(mut self, last_id: Option<u64>) -> Self {
+        self.resume_from = last_id;
+        self
+    }
+
+    /// 设置客户端的语言
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = Some(lang.tag().to_string());
+        self
+    }
+
+    /// 设置要进入的虚拟主机
+    pub fn with_host(mut self, host: Option<String>) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// 设置注册时出示的邀请码
+    pub fn with_invite(mut self, invite: Option<String>) -> Self {
+        self.invite = invite;
+        self
+    }
+
+    /// 设置机器人账号或管理员登录时出示的令牌
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// 设置客户端的身份公钥（十六进制）
+    pub fn with_identity(mut self, identity: Option<String>) -> Self {
+        self.identity = identity;
+        self
+    }
+
+    /// 与服务器支持的特性取交集，得到本次会话协商出的特性
+    pub fn negotiate(&self) -> Vec<String> {
+        self.features
+            .iter()
+            .filter(|feature| SUPPORTED_FEATURES.contains(&feature.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 服务器接收的注册帧
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Registration {
+    /// 完整的握手帧
+    Hello(Hello),
+    /// 旧客户端只发送用户名
+    Name(String),
+}
+
+impl Registration {
+    /// 统一转换为握手帧
+    pub fn into_hello(self) -> Hello {
+        match self {
+            Registration::Hello(hello) => hello,
+            Registration::Name(name) => Hello {
+                name,
+                version: 0,
+                client: String::new(),
+                features: Vec::new(),
+                resume_from: None,
+                lang: None,
+                host: None,
+                invite: None,
+                token: None,
+                identity: None,
+            },
+        }
+    }
+}
+
+/// 控制帧，通过 `type` 字段区分
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Control {
+    /// 心跳请求，接收方应回复携带相同序号的 `Pong`
+    Ping { seq: u64 },
+    /// 心跳响应
+    Pong { seq: u64 },
+    /// 服务器当前最新的消息编号，仅发给声明了 `resume` 特性的客户端
+    Seq { last_id: u64 },
+    /// 请求未能完成，仅发给声明了 `errors` 特性的客户端
+    Error(ErrorFrame),
+    /// 工作量证明挑战：客户端需找到使 `SHA-256("{nonce}:{solution}")` 至少有
+    /// `difficulty` 个前导零比特的 `solution`
+    Challenge { nonce: String, difficulty: u8 },
+    /// 对挑战的应答
+    Proof { solution: u64 },
+    /// 身份挑战：客户端需以握手帧中身份公钥对应的私钥为 `nonce` 签名
+    IdentityChallenge { nonce: String },
+    /// 对身份挑战的应答，`sig` 为十六进制的 Ed25519 签名
+    IdentityProof { sig: String },
+    /// 客户端主动退出，服务器收到后结束该连接，并把告别语转告与其互发过消息的用户
+    Goodbye {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+    /// 联邦链路认证：接受方发给连入的对端服务器的随机数
+    PeerChallenge { nonce: String },
+    /// 连入方的应答：`mac` 由共享密钥、接受方的 `nonce` 与连入方的服务器名计算，
+    /// `nonce` 是连入方反过来要求接受方证明身份的随机数
+    PeerAuth { mac: String, nonce: String },
+    /// 接受方认证通过后的应答：`mac` 由共享密钥、连入方的 `nonce` 与接受方的服务器名计算
+    PeerWelcome { mac: String },
+    /// 经联邦链路转发的消息；`via` 依次记录经过的服务器，第一个是发送者所在的服务器
+    Relay { message: Message, via: Vec<String> },
+    /// 对端服务器当前的在线用户（完整列表），成员变化时重新发送
+    Presence { users: Vec<String> },
+    /// 收件服务器报告给发送者所在服务器的路由记录：`sender` 是发送者在其服务器上的用户名，
+    /// `id` 是消息离开该服务器时的编号
+    TraceReport {
+        sender: String,
+        id: u64,
+        hops: Vec<TraceHop>,
+    },
+    /// 文件传输：发送方提议向 `peer` 发送文件；服务器转给接收方时 `peer` 换成发送方。
+    /// `id` 由发送方随机生成，以下文件传输帧均以 `id` 与对方用户名 `peer` 指明传输
+    FileOffer {
+        id: u64,
+        peer: String,
+        name: String,
+        size: u64,
+        /// 文件内容的 SHA-256（十六进制），接收方据此校验
+        sha256: String,
+        /// 发送方支持续传（可以按 [`Control::FileResume`] 从中间开始发送）
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        resumable: bool,
+    },
+    /// 接收方接受或拒绝文件传输
+    FileAnswer { id: u64, peer: String, accept: bool },
+    /// 接收方接受文件传输，并且已有该文件的前 `offset` 字节（之前中断的传输），
+    /// 发送方从 `offset` 处继续发送
+    FileResume { id: u64, peer: String, offset: u64 },
+    /// 直连候选地址：客户端上报为本次传输监听的本地地址，服务器转给对方时在前面加上
+    /// 服务器观察到的该客户端公网地址（端口与第一个本地地址相同）
+    FileCandidates {
+        id: u64,
+        peer: String,
+        addrs: Vec<String>,
+    },
+    /// 直连失败时经服务器中转的文件内容，`data` 为 Base64 编码，`offset` 为其在文件中的位置
+    FileChunk {
+        id: u64,
+        peer: String,
+        offset: u64,
+        data: String,
+    },
+    /// 接收方已收完文件并通过校验，传输结束
+    FileDone { id: u64, peer: String },
+    /// 取消文件传输（任一方，或对方离线时由服务器发出）
+    FileCancel {
+        id: u64,
+        peer: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        /// 因一方断线而中断（而不是主动取消），之后可以续传
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        interrupted: bool,
+    },
+    /// 端到端加密的密钥交换：向 `peer` 发送新的 X25519 临时公钥 `key`；服务器转给对方时
+    /// `peer` 换成发送方。`reply` 为 `false` 时对方应以自己新的临时公钥回复（`reply` 为 `true`）。
+    /// `signer` 与 `sig` 为发送方身份密钥（见 [`crate::identity`]）对交换内容的签名
+    KeyExchange {
+        peer: String,
+        key: String,
+        reply: bool,
+        signer: String,
+        sig: String,
+    },
+    /// 已读回执：客户端已向用户显示服务器编号为 `id` 的消息（同时视为已读同一发送者此前的消息）
+    Read { id: u64 },
+    /// 当前在线用户的完整列表（按用户名排序），仅发给声明了 `roster` 特性的客户端；
+    /// 客户端收到后以此替换本地的在线名单
+    Roster { users: Vec<RosterEntry> },
+    /// 在线名单的增量更新，仅发给声明了 `roster` 特性的客户端
+    RosterUpdate {
+        change: RosterChange,
+        user: RosterEntry,
+    },
+    /// 服务器即将关闭连接，仅发给声明了 `shutdown` 特性的客户端；
+    /// `reconnect_after` 为建议客户端收到通知后等待多少秒再重连，缺省表示不建议自动重连
+    ServerShutdown {
+        reason: ShutdownReason,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reconnect_after: Option<u64>,
+    },
+}
+
+/// 服务器关闭连接的原因，序列化为 `shutdown` 这样的小写字符串
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShutdownReason {
+    /// 服务器关闭
+    Shutdown,
+    /// 服务器平滑升级，新进程接管监听端口后即可重连
+    Upgrade,
+    /// 本客户端不认识的原因（来自更新版本的服务器）
+    #[serde(other)]
+    Other,
+}
+
+/// 消息路由中的一个事件
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// 服务器从发送者的连接或联邦链路收到消息
+    Received,
+    /// 接收者不在线或处于免打扰状态，消息暂存等待补发
+    Queued,
+    /// 消息进入在线接收者的路由队列
+    Routed,
+    /// 消息经联邦链路转发给下一台服务器
+    Relayed,
+    /// 联邦链路中断，消息写入暂存目录
+    Spooled,
+    /// 消息已写入接收者的连接
+    Delivered,
+}
+
+/// 消息的签名（见 [`crate::identity`]），随消息原样转发；未启用 `crypto` 特性时只转发、不验证
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Signature {
+    /// 签名者的 Ed25519 公钥（十六进制）
+    pub key: String,
+    /// 每条消息不同的随机数（十六进制），用于识别重放
+    pub nonce: String,
+    /// 签名时的 Unix 时间（秒）
+    pub signed_at: i64,
+    /// Ed25519 签名（十六进制）
+    pub sig: String,
+}
+
+/// 一条路由记录：哪台服务器在什么时间对消息做了什么
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TraceHop {
+    /// 服务器名（加入联邦时为联邦中的名字）
+    pub node: String,
+    /// 事件
+    pub event: TraceEvent,
+    /// 事件发生的时间（该服务器的本地时间，带时区）
+    pub at: DateTime<Local>,
+}
+
+/// 在线名单中的一个用户
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RosterEntry {
+    /// 用户名
+    pub name: String,
+    /// 在线状态；用户隐藏了状态时总是 `online`
+    pub status: RosterStatus,
+    /// 是否为机器人账号
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bot: bool,
+    /// 表情头像（`/profile avatar`），未设置或头像是图片链接时省略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+/// 在线状态，序列化为 `online` 这样的小写字符串
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RosterStatus {
+    /// 在线
+    #[default]
+    Online,
+    /// 离开（`/away`）
+    Away,
+    /// 请勿打扰（`/dnd`）
+    Dnd,
+    /// 本客户端不认识的状态（来自更新版本的服务器）
+    #[serde(other)]
+    Other,
+}
+
+/// 在线名单的变化
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RosterChange {
+    /// 用户上线（同名用户在别处重新登录时也会再次收到）
+    Joined,
+    /// 用户下线
+    Left,
+    /// 用户更改了在线状态或表情头像
+    Status,
+}
+
+/// 聊天消息接收者（[`Message::to`]）的地址形式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recipient<'a> {
+    /// 以 `/` 开头的服务器指令
+    Command(&'a str),
+    /// 本服务器上的用户
+    Local(&'a str),
+    /// `用户@服务器`：由服务器名为 `host` 的服务器投递给其用户 `user`
+    Remote { user: &'a str, host: &'a str },
+}
+
+impl<'a> Recipient<'a> {
+    /// 解析接收者地址；`@` 两侧任一为空时视为本地用户名
+    pub fn parse(to: &'a str) -> Self {
+        if to.starts_with('/') {
+            return Recipient::Command(to);
+        }
+        match to.rsplit_once('@') {
+            Some((user, host)) if !user.is_empty() && !host.is_empty() => {
+                Recipient::Remote { user, host }
+            }
+            _ => Recipient::Local(to),
+        }
+    }
+}
+
+/// 错误码，序列化为 `UNKNOWN_USER` 这样的大写字符串
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// 接收方不是已知用户
+    UnknownUser,
+    /// 接收方不在线，消息已写入消息日志，对方断线重连后补发
+    OfflineQueued,
+    /// 接收方拒收该发送方的消息
+    Blocked,
+    /// 接收方积压过多或发送过于频繁，请稍后再试
+    RateLimited,
+    /// 消息超过帧长度上限（连接随之断开），或内容超过服务器的长度上限（仅拒绝该消息）
+    MessageTooLarge,
+    /// 握手帧中的虚拟主机不存在，或收件地址 `用户@服务器` 中的服务器不是已知的联邦对端
+    UnknownHost,
+    /// 服务器仅限邀请注册，新用户未出示邀请码
+    InviteRequired,
+    /// 邀请码不存在、已用完或已过期
+    InvalidInvite,
+    /// 来自同一 IP 的并发连接数已达上限
+    TooManyConnections,
+    /// 服务器要求工作量证明，但客户端不支持 `pow` 特性
+    PowRequired,
+    /// 工作量证明未通过或超时
+    PowFailed,
+    /// 联邦对端服务器未配置或认证失败
+    PeerAuthFailed,
+    /// 与收件地址中服务器的联邦链路未建立
+    PeerUnavailable,
+    /// 文件传输无法进行：对方不在线、不支持文件传输，或传输不存在
+    TransferFailed,
+    /// 无法建立端到端加密会话：对方不在线或不支持端到端加密
+    E2eUnavailable,
+    /// 机器人账号或管理员未出示令牌、令牌无效或已被撤销，或普通用户出示了令牌
+    InvalidToken,
+    /// 机器人账号没有执行该操作的权限范围
+    Forbidden,
+    /// 服务器处于维护模式，暂不接受新的注册
+    Maintenance,
+    /// 服务器的接入策略（插件）拒绝了该连接
+    Denied,
+    /// 用户名已绑定身份公钥，客户端未出示该公钥或未能证明持有对应的私钥
+    IdentityRequired,
+    /// 服务器的文件传输策略拒绝了该文件：超过大小上限、扩展名被禁止或未通过扫描
+    TransferBlocked,
+    /// 重连时请求补发的部分消息已不在服务器的消息日志中（超出保留容量、已清理或已归档），
+    /// 或错过的消息超过补发上限，只补发了最近的一部分
+    HistoryTruncated,
+    /// 接收方是聊天室，发送者不是其成员
+    NotMember,
+    /// 握手帧中的用户名不可用：为空、是保留的 "Server"、以 `#` 开头，或含有控制字符或 `/`
+    InvalidName,
+    /// 本客户端不认识的错误码（来自更新版本的服务器）
+    #[serde(other)]
+    Other,
+}
+
+/// 错误帧
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ErrorFrame {
+    /// 错误码
+    pub code: ErrorCode,
+    /// 相关的接收方
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// 相关消息的编号（客户端发送时生成的编号）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    /// 给用户看的说明文字
+    pub message: String,
+}
+
+/// 注册完成后双向传输的帧
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Frame {
+    /// 聊天消息
+    Message(Message),
+    /// 控制帧
+    Control(Control),
+}
+
+impl From<Message> for Frame {
+    fn from(msg: Message) -> Self {
+        Frame::Message(msg)
+    }
+}
+
+impl From<Control> for Frame {
+    fn from(control: Control) -> Self {
+        Frame::Control(control)
+    }
+}
@@ -0,0 +1,285 @@
+/*!
+# Python 绑定
+
+启用 `chat-py` 特性后构建为 Python 扩展模块 `chat`，供数据与运维团队用几行 Python
+编写机器人和监控脚本。异步接口基于 pyo3-async-runtimes（pyo3-asyncio 的后继），
+所有网络操作在其管理的 Tokio 运行时上执行，在 Python 中以 `await` 等待：
+
+```python
+import asyncio, chat
+
+async def main():
+    client = chat.Client("bot")
+    await client.connect("127.0.0.1:7891")
+    await client.send("alice", "你好")
+    async for message in client:
+        if message.content == "ping":
+            await client.send(message.sender, "pong")
+
+asyncio.run(main())
+```
+
+- [`PyClient`]（Python 中的 `chat.Client`）：`connect`、`send`、`send_action`、`recv`、`close`
+  均为协程；也可以用 `async for` 逐条接收消息；
+- [`PyMessage`]（`chat.Message`）：只读的消息属性，发送者为 `sender`（`from` 是 Python 关键字）；
+- 连接、发送失败抛出 `chat.ChatError`；服务器发来的错误（如接收方不存在）交给构造时传入的
+  `on_error(code, message)` 回调，未传入时忽略；
+- 机器人账号在构造时传入管理员签发的令牌：`chat.Client("weather", token="bot_...")`。
+*/
+
+use crate::client::{Client, Connection};
+use crate::protocol::ErrorFrame;
+use crate::{tr, Message, MessageKind};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyStopAsyncIteration};
+use pyo3::prelude::*;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+
+create_exception!(chat, ChatError, PyException, "连接或发送失败");
+
+/// 一条收到的消息
+#[pyclass(name = "Message", module = "chat", frozen)]
+pub struct PyMessage {
+    message: Message,
+}
+
+#[pymethods]
+impl PyMessage {
+    /// 发送者
+    #[getter]
+    fn sender(&self) -> &str {
+        self.message.from()
+    }
+
+    /// 接收者
+    #[getter]
+    fn to(&self) -> &str {
+        self.message.to()
+    }
+
+    /// 消息内容
+    #[getter]
+    fn content(&self) -> &str {
+        self.message.content()
+    }
+
+    /// 发送时间（`时:分:秒`）
+    #[getter]
+    fn time_stamp(&self) -> &str {
+        self.message.time_stamp()
+    }
+
+    /// 消息类型：`text`、`action` 或 `encrypted`（未能解密的端到端加密消息）
+    #[getter]
+    fn kind(&self) -> &'static str {
+        match self.message.kind() {
+            MessageKind::Text => "text",
+            MessageKind::Action => "action",
+            MessageKind::Encrypted => "encrypted",
+        }
+    }
+
+    /// 服务器分配的消息编号
+    #[getter]
+    fn id(&self) -> Option<u64> {
+        self.message.id()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Message(sender={:?}, to={:?}, content={:?})",
+            self.message.from(),
+            self.message.to(),
+            self.message.content()
+        )
+    }
+}
+
+/// 聊天客户端
+#[pyclass(name = "Client", module = "chat", frozen)]
+pub struct PyClient {
+    name: String,
+    token: Option<String>,
+    on_error: Option<Arc<Py<PyAny>>>,
+    connection: Arc<Mutex<Option<Connection>>>,
+    tx: mpsc::UnboundedSender<Message>,
+    rx: Arc<Mutex<mpsc::UnboundedReceiver<Message>>>,
+}
+
+#[pymethods]
+impl PyClient {
+    #[new]
+    #[pyo3(signature = (name, on_error = None, token = None))]
+    fn new(name: String, on_error: Option<Py<PyAny>>, token: Option<String>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        PyClient {
+            name,
+            token,
+            on_error: on_error.map(Arc::new),
+            connection: Arc::new(Mutex::new(None)),
+            tx,
+            rx: Arc::new(Mutex::new(rx)),
+        }
+    }
+
+    /// 连接服务器并完成注册；`addr` 可以是以逗号分隔的多个地址。已连接时先关闭原连接
+    fn connect<'py>(&self, py: Python<'py>, addr: String) -> PyResult<Bound<'py, PyAny>> {
+        let tx = self.tx.clone();
+        let mut builder = Client::builder(self.name.clone()).on_message(move |message| {
+            let _ = tx.send(message);
+        });
+        if let Some(token) = &self.token {
+            builder = builder.token(token.clone());
+        }
+        if let Some(callback) = self.on_error.clone() {
+            builder = builder.on_error(move |error| report(&callback, &error));
+        }
+        let client = builder.build();
+        let connection = self.connection.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            // 连接过程中持有的错误对象不是 `Send`，在阻塞线程上等待连接完成
+            let handle = tokio::runtime::Handle::current();
+            let connected = tokio::task::spawn_blocking(move || {
+                handle
+                    .block_on(client.connect(&addr))
+                    .map_err(|e| e.to_string())
+            })
+            .await
+            .map_err(|e| ChatError::new_err(e.to_string()))?
+            .map_err(ChatError::new_err)?;
+            if let Some(previous) = connection.lock().await.replace(connected) {
+                previous.close().await;
+            }
+            Ok(())
+        })
+    }
+
+    /// 发送一条文本消息给 `to`
+    fn send<'py>(
+        &self,
+        py: Python<'py>,
+        to: String,
+        content: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.send_kind(py, to, content, MessageKind::Text)
+    }
+
+    /// 发送一条动作消息给 `to`，接收方显示为 "* 用户名 动作"
+    fn send_action<'py>(
+        &self,
+        py: Python<'py>,
+        to: String,
+        action: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.send_kind(py, to, action, MessageKind::Action)
+    }
+
+    /// 等待下一条消息；连接关闭且没有未取走的消息时返回 `None`
+    fn recv<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let (rx, connection) = (self.rx.clone(), self.connection.clone());
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            Ok(next_message(&rx, &connection).await)
+        })
+    }
+
+    /// 主动退出并关闭连接；附带的告别语由服务器转告与自己互发过消息的用户
+    #[pyo3(signature = (message = None))]
+    fn close<'py>(&self, py: Python<'py>, message: Option<String>) -> PyResult<Bound<'py, PyAny>> {
+        let connection = self.connection.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            if let Some(connection) = connection.lock().await.take() {
+                connection.leave(message).await;
+            }
+            Ok(())
+        })
+    }
+
+    fn __aiter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let (rx, connection) = (self.rx.clone(), self.connection.clone());
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            next_message(&rx, &connection)
+                .await
+                .ok_or_else(|| PyStopAsyncIteration::new_err(()))
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Client(name={:?})", self.name)
+    }
+}
+
+impl PyClient {
+    fn send_kind<'py>(
+        &self,
+        py: Python<'py>,
+        to: String,
+        content: String,
+        kind: MessageKind,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let connection = self.connection.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let mut connection = connection.lock().await;
+            let connection = connection
+                .as_mut()
+                .ok_or_else(|| ChatError::new_err(tr!("client.not_connected")))?;
+            let sent = match kind {
+                MessageKind::Action => connection.send_action(&to, &content).await,
+                _ => connection.send(&to, &content).await,
+            };
+            sent.map_err(|e| ChatError::new_err(e.to_string()))
+        })
+    }
+}
+
+/// 取出下一条消息；连接关闭（或尚未连接）且队列已空时返回 `None`
+async fn next_message(
+    rx: &Mutex<mpsc::UnboundedReceiver<Message>>,
+    connection: &Mutex<Option<Connection>>,
+) -> Option<PyMessage> {
+    let mut rx = rx.lock().await;
+    loop {
+        if let Ok(message) = rx.try_recv() {
+            return Some(PyMessage { message });
+        }
+        let open = connection
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|c| !c.is_closed());
+        if !open {
+            return None;
+        }
+        // 定期检查连接是否已关闭，避免在连接断开后永远等待
+        let wait = tokio::time::timeout(std::time::Duration::from_millis(500), rx.recv());
+        if let Ok(Some(message)) = wait.await {
+            return Some(PyMessage { message });
+        }
+    }
+}
+
+/// 在持有 GIL 时把服务器错误交给 Python 回调；回调抛出的异常打印到标准错误
+fn report(callback: &Py<PyAny>, error: &ErrorFrame) {
+    let code = serde_json::to_value(error.code)
+        .ok()
+        .and_then(|code| code.as_str().map(str::to_string))
+        .unwrap_or_default();
+    Python::with_gil(|py| {
+        if let Err(e) = callback.call1(py, (code, error.message.clone())) {
+            e.print(py);
+        }
+    });
+}
+
+/// Python 模块 `chat`
+#[pymodule]
+fn chat(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyClient>()?;
+    m.add_class::<PyMessage>()?;
+    m.add("ChatError", m.py().get_type::<ChatError>())?;
+    Ok(())
+}
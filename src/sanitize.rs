@@ -0,0 +1,94 @@
+/*!
+# 消息内容净化
+
+服务器在路由消息之前清除内容中会影响接收方终端的字节：发送者可以在消息里夹带 ANSI 转义序列，
+在对方的终端上移动光标、清屏、改写标题栏，甚至伪造其他用户的消息。[`sanitize`] 处理以下内容：
+
+- 完整的转义序列（CSI、OSC、DCS 等，包括以单个 C1 字节开头的形式）整段删除，不留下残余的参数；
+- 其余控制字符（C0、`DEL`、C1）删除，但保留换行与制表符；单独的回车改为换行，`\r\n` 合并为换行，
+  避免覆盖同一行已显示的内容；
+- 改变文字显示方向的双向控制字符（`U+202A`–`U+202E`、`U+2066`–`U+2069`）删除，
+  它们可以让显示的文字与实际内容不符。
+
+不含上述字节的内容原样返回，不分配内存。
+*/
+
+use std::borrow::Cow;
+
+/// 转义字符
+const ESC: char = '\u{1b}';
+
+/// 字符串终止符（C1 形式）
+const ST: char = '\u{9c}';
+
+/// 响铃，可以结束 OSC 序列
+const BEL: char = '\u{7}';
+
+/// 清除 `text` 中的终端控制序列与危险字符；无需改动时返回借用的原文
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(is_dangerous) {
+        return Cow::Borrowed(text);
+    }
+    let mut clean = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' | '\t' => clean.push(c),
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    clean.push('\n');
+                }
+            }
+            // 后面的字符不属于转义序列时只删除转义字符，其余字符照常处理
+            ESC => match chars.peek() {
+                Some('[') => {
+                    chars.next();
+                    skip_csi(&mut chars);
+                }
+                Some(']' | 'P' | 'X' | '^' | '_') => {
+                    chars.next();
+                    skip_string(&mut chars);
+                }
+                // 带中间字节的序列，如选择字符集的 `ESC ( B`
+                Some(' '..='/') => {
+                    while chars.next_if(|c| matches!(c, ' '..='/')).is_some() {}
+                    chars.next_if(|c| matches!(c, '0'..='~'));
+                }
+                // 其余两字节的序列，如 `ESC c`（重置终端）
+                Some('0'..='~') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            '\u{90}' | '\u{98}' | '\u{9d}' | '\u{9e}' | '\u{9f}' => skip_string(&mut chars),
+            c if is_dangerous(c) => {}
+            c => clean.push(c),
+        }
+    }
+    Cow::Owned(clean)
+}
+
+/// 是否需要从消息内容中清除（换行与制表符除外的控制字符，以及双向控制字符）
+pub fn is_dangerous(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t')
+        || matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// 跳过 CSI 序列的参数、中间字节与结束字节
+fn skip_csi(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    while chars.next_if(|c| matches!(c, ' '..='?')).is_some() {}
+    chars.next_if(|c| matches!(c, '@'..='~'));
+}
+
+/// 跳过 OSC、DCS 等字符串序列，直到字符串终止符（`ESC \`、C1 的 ST 或 OSC 使用的响铃）；
+/// 没有终止符时跳过余下的全部内容，终端同样会吞掉它们
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            BEL | ST => return,
+            ESC if chars.next_if_eq(&'\\').is_some() => return,
+            _ => {}
+        }
+    }
+}
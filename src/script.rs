@@ -0,0 +1,192 @@
+/*!
+# 脚本模式模块
+
+`chat client --script=commands.txt`（或标准输入不是终端时从标准输入读取）
+按顺序执行脚本中的步骤，全部成功时以退出码 0 结束，任一步骤失败时以 1 结束，
+便于对运行中的服务器做冒烟测试与自动化操作。
+
+每行一个步骤，`#` 开头的行为注释：
+
+```text
+# 发送消息；内容以 `/me ` 开头时发送动作消息，接收者以 `/` 开头时为服务器指令
+send bob 你好
+send /whois bob
+# 等待一段时间（支持 ms、s、m 后缀，不带后缀为秒）
+wait 500ms
+# 修改 expect 的超时时间（默认 5 秒）
+timeout 10s
+# 等待来自 bob（`*` 表示任意发送者）且内容包含指定文本的消息
+expect bob 你好
+expect Server 当前在线用户
+```
+
+`expect` 会依次检查自上一次 `expect` 以来收到的消息，不匹配的消息被跳过。
+*/
+
+use crate::client::Connection;
+use crate::{tr, Message};
+use std::fmt;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// `expect` 的默认超时时间
+pub const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 脚本中的一个步骤
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// 发送消息或服务器指令
+    Send { to: String, content: String },
+    /// 等待一段时间
+    Wait(Duration),
+    /// 修改 `expect` 的超时时间
+    Timeout(Duration),
+    /// 等待来自 `from`（`*` 表示任意发送者）且内容包含 `text` 的消息
+    Expect { from: String, text: String },
+}
+
+/// 脚本解析或执行失败
+#[derive(Debug)]
+pub struct ScriptError {
+    /// 出错的行号（从 1 开始）
+    pub line: usize,
+    /// 失败原因
+    pub reason: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = tr!("script.error", line = self.line, reason = self.reason);
+        f.write_str(&text)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// 解析后的脚本
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    steps: Vec<(usize, Step)>,
+}
+
+impl Script {
+    /// 解析脚本文本
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        let mut steps = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fail = |reason: String| ScriptError {
+                line: i + 1,
+                reason,
+            };
+            let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let args = args.trim();
+            let step = match command {
+                "send" => {
+                    let (to, content) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    if to.is_empty() {
+                        return Err(fail(tr!("script.usage_send")));
+                    }
+                    Step::Send {
+                        to: to.to_string(),
+                        content: content.trim().to_string(),
+                    }
+                }
+                "wait" => Step::Wait(
+                    parse_duration(args).ok_or_else(|| fail(tr!("script.invalid_duration")))?,
+                ),
+                "timeout" => Step::Timeout(
+                    parse_duration(args).ok_or_else(|| fail(tr!("script.invalid_duration")))?,
+                ),
+                "expect" => {
+                    let (from, text) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    if from.is_empty() {
+                        return Err(fail(tr!("script.usage_expect")));
+                    }
+                    Step::Expect {
+                        from: from.to_string(),
+                        text: text.trim().to_string(),
+                    }
+                }
+                other => return Err(fail(tr!("script.unknown_step", step = other))),
+            };
+            steps.push((i + 1, step));
+        }
+        Ok(Self { steps })
+    }
+
+    /// 在已注册的连接上依次执行脚本
+    ///
+    /// # 参数
+    /// - `conn`: 已注册的连接
+    /// - `inbox`: 连接收到的消息（由 [`crate::client::ClientBuilder::on_message`] 转发）
+    pub async fn run(
+        &self,
+        conn: &mut Connection,
+        inbox: &mut mpsc::UnboundedReceiver<Message>,
+    ) -> Result<(), ScriptError> {
+        let mut timeout = DEFAULT_EXPECT_TIMEOUT;
+        for (line, step) in &self.steps {
+            let fail = |reason: String| ScriptError {
+                line: *line,
+                reason,
+            };
+            match step {
+                Step::Send { to, content } => {
+                    let result = match content.strip_prefix("/me ") {
+                        Some(action) if !to.starts_with('/') => {
+                            conn.send_action(to, action.trim()).await
+                        }
+                        _ => conn.send(to, content).await,
+                    };
+                    result.map_err(|e| fail(tr!("script.send_failed", error = e)))?;
+                }
+                Step::Wait(duration) => tokio::time::sleep(*duration).await,
+                Step::Timeout(duration) => timeout = *duration,
+                Step::Expect { from, text } => {
+                    let deadline = Instant::now() + timeout;
+                    loop {
+                        match tokio::time::timeout_at(deadline, inbox.recv()).await {
+                            Ok(Some(msg)) => {
+                                let sender_matches = from == "*" || msg.from() == from;
+                                if sender_matches && msg.content().contains(text.as_str()) {
+                                    break;
+                                }
+                            }
+                            Ok(None) => return Err(fail(tr!("script.disconnected"))),
+                            Err(_) => {
+                                return Err(fail(tr!(
+                                    "script.expect_timeout",
+                                    timeout = format!("{:?}", timeout),
+                                    from = from,
+                                    text = text,
+                                )))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 解析 `500ms`、`2s`、`1m` 或不带后缀的秒数
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (number, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else if let Some(mins) = s.strip_suffix('m') {
+        (mins, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    let value: f64 = number.trim().parse().ok()?;
+    (value.is_finite() && value >= 0.0).then(|| Duration::from_secs_f64(value * scale))
+}
@@ -0,0 +1,428 @@
+/*!
+# 密钥存储模块
+
+客户端与服务器的敏感数据（身份私钥、登录令牌、桥接令牌与对象存储的访问密钥等）统一通过 [`SecretStore`] 保存：
+
+- **Keyring**
+  存入操作系统密钥环（macOS 钥匙串、Windows 凭据管理器、Linux 内核密钥环），
+  需要启用 `keyring` 特性。
+
+- **EncryptedFile**
+  供无密钥环可用的无界面服务器使用：所有条目加密后保存在单个文件中，
+  加密密钥由口令经 Argon2 派生，内容使用 ChaCha20-Poly1305 加密。
+
+[`SecretStore::detect`] 会优先使用密钥环，不可用时回退到加密文件。客户端默认的存储见
+[`SecretStore::open_default`]：回退的加密文件位于用户数据目录下的 `secrets.enc`，口令取自环境变量
+[`PASSPHRASE_ENV`]，未设置时在终端上提示输入（不回显）。
+
+命令行程序使用的条目：客户端的身份密钥种子（`identity:<路径>`，见 [`crate::identity`]）与
+机器人或管理员令牌（`token:<用户名>`），服务器的 Telegram 机器人令牌（`telegram-token`）、
+XMPP 组件密钥（`xmpp-secret`）与对象存储的访问密钥（`aws-access-key-id`、`aws-secret-access-key`）。
+命令行参数或环境变量未提供时才读取这些条目，可以用 `chat admin secret set <条目>` 写入。
+*/
+
+use crate::tr;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// 加密文件格式版本
+const FILE_VERSION: u32 = 1;
+
+/// 密钥环中的服务名
+pub const SERVICE: &str = "async-chat";
+
+/// 加密文件口令的环境变量，便于无人值守地运行
+pub const PASSPHRASE_ENV: &str = "CHAT_SECRETS_PASSPHRASE";
+
+/// 密钥存储操作可能出现的错误
+#[derive(Debug)]
+pub enum SecretError {
+    /// 操作系统密钥环不可用或操作失败
+    Keyring(String),
+    /// 读写加密文件失败
+    Io(io::Error),
+    /// 解密失败（口令错误或文件被篡改）
+    Decrypt,
+    /// 加密文件格式无效
+    Format(String),
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretError::Keyring(e) => f.write_str(&tr!("secrets.keyring", error = e)),
+            SecretError::Io(e) => f.write_str(&tr!("secrets.io", error = e)),
+            SecretError::Decrypt => f.write_str(&tr!("secrets.decrypt")),
+            SecretError::Format(e) => f.write_str(&tr!("secrets.format", error = e)),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+impl From<io::Error> for SecretError {
+    fn from(e: io::Error) -> Self {
+        SecretError::Io(e)
+    }
+}
+
+/// 加密文件的磁盘格式
+#[derive(Deserialize, Serialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// 敏感数据存储
+#[derive(Clone)]
+pub enum SecretStore {
+    /// 操作系统密钥环，`service` 用于区分不同应用
+    Keyring { service: String },
+    /// 口令保护的加密文件
+    EncryptedFile { path: PathBuf, passphrase: String },
+}
+
+impl fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretStore::Keyring { service } => {
+                f.debug_struct("Keyring").field("service", service).finish()
+            }
+            // 不打印口令
+            SecretStore::EncryptedFile { path, .. } => f
+                .debug_struct("EncryptedFile")
+                .field("path", path)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+impl SecretStore {
+    /// 使用操作系统密钥环
+    pub fn keyring(service: impl Into<String>) -> Self {
+        SecretStore::Keyring {
+            service: service.into(),
+        }
+    }
+
+    /// 使用口令保护的加密文件
+    pub fn encrypted_file(path: impl Into<PathBuf>, passphrase: impl Into<String>) -> Self {
+        SecretStore::EncryptedFile {
+            path: path.into(),
+            passphrase: passphrase.into(),
+        }
+    }
+
+    /// 优先使用密钥环；密钥环不可用时回退到加密文件
+    ///
+    /// # 参数
+    /// - `service`: 密钥环中的服务名
+    /// - `fallback`: 回退使用的加密文件路径
+    /// - `passphrase`: 仅在需要回退时调用，用于获取加密文件的口令
+    pub fn detect<F>(
+        service: &str,
+        fallback: impl Into<PathBuf>,
+        passphrase: F,
+    ) -> Result<Self, SecretError>
+    where
+        F: FnOnce() -> io::Result<String>,
+    {
+        let keyring = SecretStore::keyring(service);
+        if keyring.keyring_available() {
+            return Ok(keyring);
+        }
+        Ok(SecretStore::encrypted_file(fallback, passphrase()?))
+    }
+
+    /// 用户 `owner` 默认的密钥存储：优先使用密钥环，不可用时回退到数据目录下的 `secrets.enc`
+    pub fn open_default(owner: &str) -> Result<Self, SecretError> {
+        let fallback = crate::data_dir(owner)
+            .map(|dir| dir.join("secrets.enc"))
+            .ok_or_else(|| {
+                SecretError::Io(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    tr!("secrets.no_data_dir"),
+                ))
+            })?;
+        Self::detect(SERVICE, fallback, || passphrase(&tr!("secrets.prompt")))
+    }
+
+    /// 读取条目 `key`
+    ///
+    /// # 返回值
+    /// 条目不存在时返回 `Ok(None)`
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, SecretError> {
+        match self {
+            SecretStore::Keyring { service } => keyring_get(service, key),
+            SecretStore::EncryptedFile { path, passphrase } => {
+                let entries = load_file(path, passphrase)?;
+                entries
+                    .get(key)
+                    .map(|value| decode_hex(value).ok_or_else(|| bad_hex(key)))
+                    .transpose()
+            }
+        }
+    }
+
+    /// 写入（或覆盖）条目 `key`
+    pub fn set(&self, key: &str, value: &[u8]) -> Result<(), SecretError> {
+        match self {
+            SecretStore::Keyring { service } => keyring_set(service, key, value),
+            SecretStore::EncryptedFile { path, passphrase } => {
+                let mut entries = load_file(path, passphrase)?;
+                entries.insert(key.to_string(), encode_hex(value));
+                save_file(path, passphrase, &entries)
+            }
+        }
+    }
+
+    /// 删除条目 `key`；条目不存在时不报错
+    pub fn delete(&self, key: &str) -> Result<(), SecretError> {
+        match self {
+            SecretStore::Keyring { service } => keyring_delete(service, key),
+            SecretStore::EncryptedFile { path, passphrase } => {
+                let mut entries = load_file(path, passphrase)?;
+                if entries.remove(key).is_some() {
+                    save_file(path, passphrase, &entries)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// 通过读取一个探测条目判断密钥环是否可用
+    fn keyring_available(&self) -> bool {
+        match self {
+            SecretStore::Keyring { service } => keyring_get(service, "__probe__").is_ok(),
+            SecretStore::EncryptedFile { .. } => false,
+        }
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry(service: &str, key: &str) -> Result<keyring::Entry, SecretError> {
+    keyring::Entry::new(service, key).map_err(|e| SecretError::Keyring(e.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_get(service: &str, key: &str) -> Result<Option<Vec<u8>>, SecretError> {
+    match keyring_entry(service, key)?.get_secret() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(SecretError::Keyring(e.to_string())),
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_set(service: &str, key: &str, value: &[u8]) -> Result<(), SecretError> {
+    keyring_entry(service, key)?
+        .set_secret(value)
+        .map_err(|e| SecretError::Keyring(e.to_string()))
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_delete(service: &str, key: &str) -> Result<(), SecretError> {
+    match keyring_entry(service, key)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(SecretError::Keyring(e.to_string())),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_unsupported() -> SecretError {
+    SecretError::Keyring(tr!("secrets.keyring_disabled"))
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_get(_service: &str, _key: &str) -> Result<Option<Vec<u8>>, SecretError> {
+    Err(keyring_unsupported())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_set(_service: &str, _key: &str, _value: &[u8]) -> Result<(), SecretError> {
+    Err(keyring_unsupported())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_delete(_service: &str, _key: &str) -> Result<(), SecretError> {
+    Err(keyring_unsupported())
+}
+
+/// 取得加密文件的口令：优先读取环境变量 [`PASSPHRASE_ENV`]，否则以 `prompt` 提示并从终端读取一行（不回显）
+///
+/// 标准输入不是终端且未设置环境变量时返回错误
+pub fn passphrase(prompt: &str) -> io::Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            tr!("secrets.passphrase_required", env = PASSPHRASE_ENV),
+        ));
+    }
+    read_secret(prompt)
+}
+
+/// 读取一行敏感输入：标准输入是终端时以 `prompt` 提示并关闭回显，否则直接读取一行（便于经管道传入）
+pub fn read_secret(prompt: &str) -> io::Result<String> {
+    let mut line = String::new();
+    if io::stdin().is_terminal() {
+        eprint!("{}", prompt);
+        io::stderr().flush()?;
+        let _echo = EchoOff::new();
+        io::stdin().read_line(&mut line)?;
+    } else {
+        io::stdin().read_line(&mut line)?;
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// 读取敏感输入期间关闭终端回显（保留换行的回显），离开作用域时恢复
+struct EchoOff {
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+impl EchoOff {
+    #[cfg(unix)]
+    fn new() -> Self {
+        // SAFETY: termios 是普通的 C 结构体，全零是合法的初始值，由 tcgetattr 填充
+        let mut term: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: 文件描述符 0 在进程生命周期内有效，term 指向有效的内存
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut term) } != 0 {
+            return Self { saved: None };
+        }
+        let saved = term;
+        term.c_lflag &= !libc::ECHO;
+        term.c_lflag |= libc::ECHONL;
+        // SAFETY: 同上
+        let ok = unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term) } == 0;
+        Self {
+            saved: ok.then_some(saved),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(saved) = &self.saved {
+            // SAFETY: 恢复 new 中读取的终端设置
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved) };
+        }
+    }
+}
+
+/// 由口令和盐派生 256 位加密密钥
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, SecretError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| SecretError::Format(e.to_string()))?;
+    Ok(key)
+}
+
+/// 读取并解密加密文件；文件不存在时返回空集合
+fn load_file(path: &Path, passphrase: &str) -> Result<BTreeMap<String, String>, SecretError> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let file: EncryptedFile =
+        serde_json::from_slice(&content).map_err(|e| SecretError::Format(e.to_string()))?;
+    if file.version != FILE_VERSION {
+        return Err(SecretError::Format(tr!(
+            "secrets.unsupported_version",
+            version = file.version
+        )));
+    }
+    let salt = decode_hex(&file.salt).ok_or_else(|| bad_hex("salt"))?;
+    let nonce = decode_hex(&file.nonce).ok_or_else(|| bad_hex("nonce"))?;
+    let ciphertext = decode_hex(&file.ciphertext).ok_or_else(|| bad_hex("ciphertext"))?;
+    if nonce.len() != 12 {
+        return Err(bad_hex("nonce"));
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| SecretError::Decrypt)?;
+    serde_json::from_slice(&plaintext).map_err(|e| SecretError::Format(e.to_string()))
+}
+
+/// 加密并原子地写入加密文件，每次写入都使用新的盐和随机数
+fn save_file(
+    path: &Path,
+    passphrase: &str,
+    entries: &BTreeMap<String, String>,
+) -> Result<(), SecretError> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    getrandom::getrandom(&mut salt).map_err(io::Error::from)?;
+    getrandom::getrandom(&mut nonce).map_err(io::Error::from)?;
+
+    let plaintext = serde_json::to_vec(entries).map_err(io::Error::from)?;
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| SecretError::Format(tr!("secrets.encrypt_failed")))?;
+    let file = EncryptedFile {
+        version: FILE_VERSION,
+        salt: encode_hex(&salt),
+        nonce: encode_hex(&nonce),
+        ciphertext: encode_hex(&ciphertext),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(
+        &tmp,
+        serde_json::to_vec_pretty(&file).map_err(io::Error::from)?,
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn bad_hex(field: &str) -> SecretError {
+    SecretError::Format(tr!("secrets.bad_hex", field = field))
+}
+
+/// 将字节编码为小写十六进制字符串
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 解码十六进制字符串，格式无效时返回 `None`
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -5,27 +5,701 @@
 - 客户端注册（通过发送用户名）
 - 异步消息接收与转发（利用 mpsc 通道解耦读写）
 - 根据消息中的目标接收者查找在线用户，并将消息转发至对应客户端
-- 当目标用户不在线时，返回提示信息给发送者
+- 消息内容超过 [`ServerConfig::max_message_len`] 时不转发，以 `MESSAGE_TOO_LARGE` 错误告知发送者，
+  连接保持不变（超过帧长度上限的帧仍会断开连接）
+- 当目标用户不在线或不存在时告知发送者：声明了 `errors` 特性的客户端收到带错误码的错误帧
+  （见 [`crate::protocol::ErrorCode`]），其他客户端收到文字提示
+- 按发送者与消息编号丢弃窗口内重复发来的消息（客户端重连后重发）
+- 发给已知用户的消息分配递增的编号并写入消息日志；客户端重连时带上最后收到的编号，
+  服务器补发此后的消息（最多 [`MAX_REPLAY`] 条）；部分消息已不在日志中或超过补发上限时，
+  以 `HISTORY_TRUNCATED` 错误告知客户端
+- 投递消息时按接收者保存在资料中的通知偏好（静音会话、关键词、免打扰时段，见 [`crate::notify`]）
+  附上提示建议，用户通过 `/notify` 修改偏好，偏好随账号在各设备间同步
+- 发给已知用户的消息记录投递回执（已发送、离线暂存、已送达、已读，见 `receipts` 子模块），
+  发送者通过 `/receipts <用户>` 查看
+- 目标用户处于离开或免打扰状态时，向每个发送者自动回复一次；免打扰期间消息暂存，
+  关闭免打扰后补发
+- 每个连接的读写在同一任务中并发运行，任意一方结束即一并关闭，并且只清理一次；
+  同名用户重新登录时旧连接随之关闭；写入失败时立即拆除会话，并通知本次连接中与该用户
+  互发过消息的在线用户
+- 所有连接任务由 `run` 持有的 `JoinSet` 管理：结束时及时回收并报告 panic，
+  连接数超过 [`ServerConfig::max_connections`] 时拒绝新连接；
+  [`Server::shutdown`] 会断开所有用户并等待连接任务结束后再返回
+- 默认监听 `[::]`：以双栈套接字同时接受 IPv4 与 IPv6 连接（见 `upgrade` 子模块的绑定），
+  IPv4 对端的地址还原为 IPv4 形式后再做来源 IP 限制、GeoIP 查询与记录
+- 接受连接连续失败（如文件描述符耗尽）时逐次加长等待，空闲的文件描述符不足
+  [`ServerConfig::fd_reserve`] 时关闭新连接；开始与停止拒绝连接时各输出一条告警，
+  拒绝次数见 [`Server::accept_stats`]（见 `accept` 子模块）
+- 按来源 IP 限制并发连接数与连接频率（[`ServerConfig::per_ip`]，见 `limits` 子模块），
+  超过限制的连接收到 `TOO_MANY_CONNECTIONS` 或 `RATE_LIMITED` 错误后断开
+- 载入 GeoIP 数据库（[`ServerConfig::geoip`]，见 `geoip` 子模块，需启用 `geoip` 特性）后，接受连接时查询对端 IP 的
+  国家/地区与 ASN，附加在连接日志、管理员的 `/whois`、`/stats` 与在线用户快照中
+- 连接处理任务 panic 时只影响该连接：记录用户名与对端地址、计入
+  [`Server::handler_panics`]，并把该连接移出在线用户表
+- 断开超过 [`ServerConfig::idle_timeout`] 未发送任何数据（包括心跳）的连接
+- 收到客户端的消息时先把发送者改写为该连接注册的用户名，之后的日志、路由与投递都以此为准
+- 聊天消息先进入接收方的路由队列（见 `router` 子模块），由各连接的转发循环按发送方轮流
+  放入发送通道：一个接收方积压不会阻塞发给其他用户的消息，刷屏的发送方也不会饿死其他发送方
+- 接收方跟不上消息速度（发送通道持续已满或写入过慢）时提醒该用户，超过
+  [`ServerConfig::slow_grace`] 仍未恢复则断开其连接，避免拖慢发送方
+- 无法投递的消息（接收方路由队列已满且策略为丢弃、连接已断开、帧过大）进入死信队列
+  （见 `dead_letter` 子模块），管理员可通过 `/deadletter` 查看与重新投递
+- 路由消息之前清除内容中的终端转义序列与控制字符（见 [`crate::sanitize`]），
+  包括经联邦链路、Webhook 与桥接进入的消息，恶意发送者无法操纵接收方的终端
+- 反垃圾规则（[`ServerConfig::spam`]，见 `spam` 子模块）：同一内容重复发送、短时间内发给过多接收者
+  或链接过多的发送者被告警或限流，事件写入审计日志（见 `audit` 子模块）并提醒在线的管理员，
+  管理员通过 `/audit` 或管理套接字的 `audit` 命令查看
+- 举报（见 `moderation` 子模块）：用户通过 `/report <用户> <理由>` 举报滥用行为，举报附上双方最近的往来消息
+  进入审核队列并提醒在线的管理员，管理员通过 `/reports` 或管理套接字的 `reports` 命令查看与处理
+- 隔离待审核（[`ServerConfig::quarantine`]，见 `moderation` 子模块）：触发反垃圾规则或有待处理举报的发送者
+  发给本服务器用户或聊天室的消息暂不投递、不写入消息日志，管理员通过 `/quarantine` 放行或丢弃，每个决定都写入审计日志
+- 路由追踪（[`ServerConfig::trace`]，见 `trace` 子模块，用于调试）：记录每条消息收到、排队、转发与写入接收者连接的时间，
+  联邦中的收件服务器把沿途的记录报告回发送者所在的服务器，发送者通过 `/trace <编号>` 查看各环节的延迟
+- 聊天室（见 `rooms` 子模块）：发给 `#名称` 的消息写入一次消息日志、只序列化一次，并发转给其他成员；
+  成员关系随用户数据持久化，用户登录时自动重新加入并补发离线期间的聊天室消息与提及
+- 以 `/` 开头的接收者视为服务器指令（如 `/list`、`/whois`），由 `commands` 子模块处理
+- 用户上线或下线时重建在线名单（按用户名排序的投递句柄列表，通过 `ArcSwap` 发布），
+  `/list` 与通知所有在线用户等读操作直接读取名单，不再遍历并锁住在线用户表的分片
+- 在线名单推送（见 `roster_push` 子模块）：声明了 `roster` 特性的客户端注册后收到完整的在线名单，
+  此后收到用户上线、下线与状态变化的增量更新，无需反复发送 `/list`
+- 系统通知（关闭服务器、管理员 `/announce` 公告）通过 `broadcast` 通道发布，
+  每个连接的写循环都订阅该通道，发布时无需逐个等待各用户的发送通道
+- 发给用户的提示、指令回复与系统通知使用该用户握手时上报的语言（见 [`crate::i18n`]），
+  服务器日志使用服务器进程的语言
+- 通过 [`Server::online_users`] 查询在线用户详情，并定期生成在线用户快照
+  （[`Server::snapshot`]），供管理控制台与监控指标复用
+- 虚拟主机：通过 [`Server::with_vhost`] 在同一进程中挂载多个彼此隔离的聊天空间，
+  每个空间是一个独立的 `Server`，拥有自己的在线用户表、配置与存储；客户端在握手帧的
+  `host` 字段中选择空间，省略时进入默认空间，名称不存在时收到 `UNKNOWN_HOST` 错误后断开
+- 用户名不能为空，不能是系统通知使用的 "Server"，不能以 `#`（聊天室地址）开头，也不能含有控制字符或
+  `/`（指令），否则握手以 `INVALID_NAME` 错误被拒绝
+- 身份绑定：用户名在首次注册时绑定客户端以 `IdentityChallenge`/`IdentityProof` 证明过的
+  Ed25519 身份公钥，此后只有证明持有同一把私钥的连接才被视为该已注册用户，其余连接以
+  `IDENTITY_REQUIRED` 拒绝；下面的"尚未注册过的用户"均指尚未绑定身份公钥的用户名
+- 仅限邀请注册（[`ServerConfig::invite_only`]）：尚未注册过的用户必须在握手帧中出示
+  管理员通过 `/invite` 生成的邀请码（见 `invites` 子模块），否则在加入在线用户表之前被拒绝
+- 维护模式（见 `maintenance` 子模块）：计划重启前管理员以 `/maintenance on [HH:MM]` 开启，
+  此后新的注册（管理员除外）以 `MAINTENANCE` 错误被拒绝并提示预计恢复的时间，在线的会话照常进行直到自然结束
+- 插件（见 `plugin` 子模块）：通过 [`Server::with_plugin`] 挂载运营者实现的 [`Plugin`]，
+  如在注册之前按对端地址拒绝连接（公司网段、允许连接的时段等），无需修改接受连接的循环
+- 工作量证明（[`ServerConfig::pow_difficulty`]）：尚未注册过的用户在注册前须应答
+  [`crate::pow`] 挑战，增加批量注册机器人的成本；已注册的用户、机器人与管理员直接登录
+- 机器人账号（见 `bots` 子模块）：管理员通过 `/bot` 创建的非交互账号，以握手帧中的令牌认证，
+  按权限范围限制可做的事，消息按 [`ServerConfig::bot_rate`] 单独限流
+- 管理员（[`ServerConfig::admins`]）与机器人一样以握手帧中的令牌认证，只有出示了对应令牌的连接
+  才取得管理员权限，仅凭用户名登录的连接被拒绝
+- 机器人指令（见 `bot_commands` 子模块）：机器人注册的 `/指令` 由服务器转交给该机器人，
+  机器人的回复发回调用者
+
+- 服务器联邦（[`ServerConfig::federation`]，见 `federation` 子模块）：与配置的对端服务器
+  互相认证后建立链路，发给 `用户@服务器` 的消息经链路转发，并交换各自的在线用户；
+  转发的消息记录经过的服务器，丢弃来源不符、成环或跳数过多的消息；发往非对端服务器的消息
+  可以交给上游对端，链路中断期间的消息可以暂存到磁盘（见 `spool` 子模块），恢复后再转发
+- 文件传输撮合（见 `transfers` 子模块）：在声明了 `files` 特性的双方之间转发文件传输帧，
+  交换候选地址时附上服务器观察到的公网地址以便双方打洞直连，直连失败时中转文件内容；
+  任一方断开时取消其参与的传输并通知另一方
+- 文件传输策略（[`ServerConfig::transfer_policy`]，见 `transfer_policy` 子模块）：按大小、扩展名
+  与插件拒绝文件传输，可选地以外部命令扫描中转的内容，未通过的文件移入隔离目录
+- 端到端加密（见 `e2e` 子模块）：在声明了 `e2e` 特性的双方之间转发签名的密钥交换帧，
+  服务器只能看到加密消息的密文
+- WebSocket 接入（[`ServerConfig::websocket`]，见 `websocket` 子模块，需启用 `websocket` 特性）：额外监听 WebSocket 连接，
+  每条文本消息为一帧 JSON，握手完成后与 TCP 连接走同一套注册与会话处理，供浏览器中的客户端接入
+- 传入 Webhook（[`ServerConfig::webhooks`]，见 `webhooks` 子模块）：额外监听 HTTP，
+  把 `POST /hooks/<令牌>` 提交的 JSON 转换为发给管理员通过 `/webhook` 配置的用户的消息
+- Telegram 桥接（[`ServerConfig::telegram`]，见 `telegram` 子模块，需启用 `bridges` 特性）：以长轮询接收 Telegram 群组的消息
+  并转给加入桥接聊天室的用户，成员发给 `tg:` 地址的消息发到群组
+- XMPP 网关（[`ServerConfig::xmpp`]，见 `xmpp` 子模块，需启用 `bridges` 特性）：以外部组件的身份连接 XMPP 服务器，
+  Jabber 用户以 `用户@网关域名` 与本地用户互发消息，并互相推送在线状态
+- 历史消息归档（[`ServerConfig::archive`]，见 `archive` 子模块，需启用 `storage` 特性）：定期把写入超过若干天的消息
+  压缩上传到 S3 兼容的对象存储，并从本地消息日志中删除
+- 定期维护任务（[`ServerConfig::jobs`]，见 `jobs` 子模块）：按 cron 式的时间表整理消息日志、
+  清除过期的邀请码与死信、删除超出补发窗口的消息，管理员通过 `/jobs` 查看运行统计
+- 故障注入（[`ServerConfig::chaos`]，见 `chaos` 子模块，需启用 `chaos` 特性，仅用于测试）：按概率延迟、丢弃、
+  截断或重复发给客户端的帧，检验客户端的重连、续传与去重逻辑
+- 不停机升级（[`ServerConfig::admin_socket`]，见 `upgrade` 子模块）：监听 Unix 管理套接字并以
+  `SO_REUSEPORT` 绑定端口，新版本的进程绑定同一端口后通过管理套接字要求旧进程排空，
+  旧进程断开所有用户、写完数据后退出，重连的客户端进入新进程
+- systemd 集成（见 `systemd` 子模块）：沿用套接字激活传入的监听套接字（[`Listeners::from_systemd`]），
+  向 systemd 发送就绪、状态与看门狗通知，收到 `SIGTERM` 时与 Ctrl+C 一样正常关闭
 
 详细实现请参见各函数注释。
 */
 
-use crate::{ArcString, Message};
+#[cfg(feature = "chaos")]
+use crate::codec::Codec;
+use crate::codec::{CodecError, FrameReader, FrameWriter};
+use crate::dedup::DedupWindow;
+use crate::i18n::{self, Lang};
+use crate::identity;
+use crate::notify::NotifyHint;
+use crate::pow;
+use crate::protocol::{
+    Control, ErrorCode, ErrorFrame, Frame, Hello, Recipient, Registration, RosterChange,
+    ShutdownReason, TraceEvent, FEATURE_ERRORS, FEATURE_FEDERATION, FEATURE_POW, FEATURE_RESUME,
+    FEATURE_ROSTER, FEATURE_SHUTDOWN,
+};
+use crate::sanitize::{is_dangerous, sanitize};
+use crate::session::{Departure, Presence, Role, Session, SessionStats};
+use crate::storage::{MemoryStorage, RoomRecord, Storage, UserRecord};
+use crate::transport::{BoxReader, BoxWriter, SocketOptions};
+use crate::{
+    humanize_duration, humanize_duration_in, tr, ArcString, Message, MessageKind, Timestamp,
+};
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Local};
 use dashmap::DashMap;
-use serde_json;
-use std::process;
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{tcp::OwnedReadHalf, TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc, Notify};
+use tokio::task::{self, JoinError, JoinSet};
+
+mod accept;
+#[cfg(feature = "storage")]
+mod archive;
+mod audit;
+mod bot_commands;
+mod bots;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod commands;
+mod dead_letter;
+mod e2e;
+mod federation;
+mod geoip;
+mod invites;
+mod jobs;
+mod limits;
+mod maintenance;
+mod moderation;
+mod plugin;
+mod receipts;
+mod rooms;
+mod roster_push;
+mod router;
+mod spam;
+mod spool;
+mod systemd;
+#[cfg(feature = "bridges")]
+mod telegram;
+mod trace;
+mod transfer_policy;
+mod transfers;
+mod upgrade;
+mod webhooks;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(feature = "bridges")]
+mod xmpp;
+
+pub use accept::AcceptStats;
+use accept::{AcceptBackoff, AcceptMonitor, FdHeadroom, Overload};
+#[cfg(feature = "storage")]
+pub use archive::ArchiveConfig;
+use audit::AuditLog;
+pub use audit::{AuditAction, AuditEntry, AUDIT_CAPACITY};
+use bot_commands::BotCommands;
+use bots::BotLimits;
+pub use bots::{issue_admin_token, BotRate, BotRecord, BotScope};
+#[cfg(feature = "chaos")]
+use chaos::Chaos;
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosConfig;
+use dead_letter::{DeadLetters, Reason};
+use federation::Federation;
+pub use federation::{FederationConfig, PeerConfig, DEFAULT_PEER_PORT, MAX_HOPS};
+pub use geoip::GeoInfo;
+#[cfg(feature = "geoip")]
+pub use geoip::GeoIp;
+use invites::Invites;
+use jobs::Jobs;
+pub use jobs::{Job, JobConfig, Schedule};
+pub use limits::IpLimitConfig;
+use limits::IpLimits;
+use maintenance::Maintenance;
+use moderation::ModerationQueue;
+pub use moderation::{HoldCause, Quarantined, Report, MODERATION_CAPACITY, REPORT_CONTEXT};
+pub use plugin::{AcceptDecision, Plugin};
+use receipts::{DeliveryState, Receipts};
+use rooms::SharedMessage;
+use roster_push::{RosterUpdate, ROSTER_UPDATE_CAPACITY};
+use router::{Rejected, RouteQueue};
+use spam::{Screened, SpamGuard};
+pub use spam::{SpamAction, SpamConfig, SpamRule};
+#[cfg(feature = "bridges")]
+use telegram::TelegramBridge;
+#[cfg(feature = "bridges")]
+pub use telegram::TelegramConfig;
+use trace::Tracer;
+pub use trace::TRACES_KEPT;
+pub use transfer_policy::{TransferPolicy, DEFAULT_QUARANTINE_DIR, SCAN_TIMEOUT};
+use transfers::Transfers;
+use upgrade::Handoff;
+pub use upgrade::{admin_request, take_over, Listeners};
+pub use webhooks::WebhookRecord;
+#[cfg(feature = "bridges")]
+use xmpp::XmppGateway;
+#[cfg(feature = "bridges")]
+pub use xmpp::{XmppConfig, DEFAULT_COMPONENT_PORT};
+
+type ReadStream<'a> = &'a mut FrameReader<BoxReader>;
+
+/// 重连时最多补发的消息条数
+pub const MAX_REPLAY: usize = 500;
+
+/// 关闭服务器时等待连接任务结束的最长时间
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 系统通知通道的容量，写循环落后超过该数量时丢弃最早的通知
+const NOTICE_CAPACITY: usize = 16;
+
+/// 新连接应答工作量证明挑战的最长时间
+pub const POW_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 拒绝超过来源 IP 限制的连接时，等待其握手帧的最长时间
+const TURN_AWAY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 应答工作量证明挑战之前最多暂存的帧数
+const MAX_EARLY_FRAMES: usize = 64;
+
+/// 消息内容长度上限的默认值（字节）
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 16 * 1024;
+
+/// 端到端加密的消息内容是 Base64 编码的密文，在明文的长度上限之外另外允许的字节数
+/// （密钥编号、随机数、认证标签及明文外层的 JSON）
+const ENCRYPTED_OVERHEAD: usize = 256;
+
+/// 单帧写入超过该时长即视为接收方处理过慢
+const SLOW_WRITE: Duration = Duration::from_secs(2);
+
+/// 平滑升级时未配置 [`ServerConfig::reconnect_after`] 所建议的重连等待时间
+const UPGRADE_RECONNECT_AFTER: Duration = Duration::from_secs(1);
+
+/// 服务器配置
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// 在线用户快照的刷新间隔
+    pub snapshot_interval: Duration,
+    /// 空闲超时：连接超过该时长未发送任何帧（包括心跳）即被断开，`None` 表示不限制
+    pub idle_timeout: Option<Duration>,
+    /// 接收方路由队列已满时的处理方式
+    pub queue_full: QueueFullPolicy,
+    /// 管理员：用户名到其登录令牌的 SHA-256 摘要（十六进制，见 [`issue_admin_token`]）；
+    /// 以这些用户名登录必须在握手帧中出示对应的令牌
+    pub admins: HashMap<String, String>,
+    /// 接收过慢的连接在被断开前的宽限期
+    pub slow_grace: Duration,
+    /// 同时处理的连接数上限，`None` 表示不限制
+    pub max_connections: Option<usize>,
+    /// 保留的空闲文件描述符数：余量不足时关闭新连接（仅 Unix），为 0 表示不检查
+    pub fd_reserve: usize,
+    /// 接受连接后设置的套接字选项
+    pub socket: SocketOptions,
+    /// 按来源 IP 的并发连接数与连接频率限制
+    pub per_ip: IpLimitConfig,
+    /// 接受连接时查询对端 IP 所用的 GeoIP 数据库，`None` 表示不查询
+    #[cfg(feature = "geoip")]
+    pub geoip: Option<Arc<GeoIp>>,
+    /// 工作量证明的难度（前导零比特数，最大 [`pow::MAX_DIFFICULTY`]）：设置后尚未注册过的用户
+    /// （管理员除外）需要先完成 [`crate::pow`] 挑战才能注册，`None` 表示不要求
+    pub pow_difficulty: Option<u8>,
+    /// 仅限邀请注册：尚未注册过的用户（管理员除外）必须在握手时出示管理员通过 `/invite` 生成的邀请码
+    pub invite_only: bool,
+    /// 服务器联邦：本服务器的名称与对端服务器，`None` 表示不参与联邦
+    pub federation: Option<FederationConfig>,
+    /// 额外监听 WebSocket 连接的地址（供浏览器中的客户端接入），`None` 表示不监听
+    #[cfg(feature = "websocket")]
+    pub websocket: Option<String>,
+    /// 额外监听传入 Webhook（HTTP）的地址，`None` 表示不监听
+    pub webhooks: Option<String>,
+    /// 机器人账号（以及每个 Webhook）的消息限流
+    pub bot_rate: BotRate,
+    /// Telegram 桥接：同步的群组与 Bot API 令牌，`None` 表示不桥接
+    #[cfg(feature = "bridges")]
+    pub telegram: Option<TelegramConfig>,
+    /// XMPP 网关：组件的域名与 XMPP 服务器，`None` 表示不启用
+    #[cfg(feature = "bridges")]
+    pub xmpp: Option<XmppConfig>,
+    /// 历史消息归档：保留的天数与对象存储，`None` 表示不归档
+    #[cfg(feature = "storage")]
+    pub archive: Option<ArchiveConfig>,
+    /// 按时间表运行的维护任务，为空表示不运行
+    pub jobs: Vec<JobConfig>,
+    /// 管理套接字的路径，用于不停机升级；配置后以 `SO_REUSEPORT` 绑定监听端口，`None` 表示不监听
+    pub admin_socket: Option<PathBuf>,
+    /// 消息内容的长度上限（字节），超过时以 `MESSAGE_TOO_LARGE` 错误拒绝该消息；
+    /// `None` 表示只受帧长度上限（[`crate::codec::MAX_FRAME_LEN`]）约束
+    pub max_message_len: Option<usize>,
+    /// 反垃圾规则：重复消息、接收者过多与链接刷屏，`None` 表示不检查
+    pub spam: Option<SpamConfig>,
+    /// 文件传输策略：大小上限、禁止的扩展名与扫描命令，默认不限制
+    pub transfer_policy: TransferPolicy,
+    /// 审计日志的追加写入文件（JSON Lines），`None` 表示只保存在内存中
+    pub audit_log: Option<PathBuf>,
+    /// 隔离待审核：触发反垃圾规则或有待处理举报的发送者发给本服务器用户或聊天室的消息暂不投递，
+    /// 由管理员通过 `/quarantine` 放行或丢弃
+    pub quarantine: bool,
+    /// 路由追踪（用于调试）：记录每条带编号的消息经过的路由事件，发送者通过 `/trace <编号>` 查看
+    pub trace: bool,
+    /// 关闭服务器时在关闭通知中建议客户端等待多久后自动重连，`None` 表示不建议重连；
+    /// 平滑升级时总是建议重连，未配置时等待 [`UPGRADE_RECONNECT_AFTER`]
+    pub reconnect_after: Option<Duration>,
+    /// 是否在服务器输出中逐条记录转发的消息；压测或消息量很大时可以关闭
+    pub log_messages: bool,
+    /// 故障注入（仅用于测试）：按概率延迟、丢弃、截断或重复发给客户端的帧，`None` 表示不注入
+    #[cfg(feature = "chaos")]
+    pub chaos: Option<ChaosConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_interval: Duration::from_secs(10),
+            idle_timeout: None,
+            queue_full: QueueFullPolicy::default(),
+            admins: HashMap::new(),
+            slow_grace: Duration::from_secs(30),
+            max_connections: None,
+            fd_reserve: 64,
+            socket: SocketOptions::default(),
+            per_ip: IpLimitConfig::default(),
+            #[cfg(feature = "geoip")]
+            geoip: None,
+            pow_difficulty: None,
+            invite_only: false,
+            federation: None,
+            #[cfg(feature = "websocket")]
+            websocket: None,
+            webhooks: None,
+            bot_rate: BotRate::default(),
+            #[cfg(feature = "bridges")]
+            telegram: None,
+            #[cfg(feature = "bridges")]
+            xmpp: None,
+            #[cfg(feature = "storage")]
+            archive: None,
+            jobs: Vec::new(),
+            admin_socket: None,
+            max_message_len: Some(DEFAULT_MAX_MESSAGE_LEN),
+            spam: None,
+            transfer_policy: TransferPolicy::default(),
+            audit_log: None,
+            quarantine: false,
+            trace: false,
+            reconnect_after: None,
+            log_messages: true,
+            #[cfg(feature = "chaos")]
+            chaos: None,
+        }
+    }
+}
+
+/// 接收方路由队列已满时的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// 等待接收方的路由队列腾出空间（发送方的接收循环随之暂停，最长等到宽限期结束）
+    #[default]
+    Wait,
+    /// 立即放弃投递，消息进入死信队列
+    Drop,
+}
+
+impl QueueFullPolicy {
+    /// 从字符串解析（`wait` 或 `drop`）
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s {
+            "wait" => Some(Self::Wait),
+            "drop" => Some(Self::Drop),
+            _ => None,
+        }
+    }
+}
 
-type ReadStream<'a> = &'a mut OwnedReadHalf;
+/// 写入连接的一项：单独编码的帧，或已编码、由多个连接共享的聊天室消息
+#[derive(Debug)]
+enum Outgoing {
+    Frame(Frame),
+    Shared(Arc<SharedMessage>),
+}
+
+impl Outgoing {
+    /// 其中的聊天消息
+    fn message(&self) -> Option<&Message> {
+        match self {
+            Outgoing::Frame(Frame::Message(msg)) => Some(msg),
+            Outgoing::Frame(Frame::Control(_)) => None,
+            Outgoing::Shared(shared) => Some(&shared.message),
+        }
+    }
+
+    /// 按 `writer` 的编码写出
+    async fn write(&self, writer: &mut FrameWriter<BoxWriter>) -> Result<(), CodecError> {
+        match self {
+            Outgoing::Frame(frame) => writer.write_frame(frame).await,
+            Outgoing::Shared(shared) => writer.write_payload(&shared.payload).await,
+        }
+    }
+
+    /// 按 `codec` 编码为完整的一帧
+    #[cfg(feature = "chaos")]
+    fn encode(&self, codec: Codec) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Outgoing::Frame(frame) => codec.encode(frame),
+            Outgoing::Shared(shared) => Ok(codec.frame(&shared.payload)),
+        }
+    }
+}
+
+impl From<Frame> for Outgoing {
+    fn from(frame: Frame) -> Self {
+        Outgoing::Frame(frame)
+    }
+}
+
+impl From<Message> for Outgoing {
+    fn from(msg: Message) -> Self {
+        Outgoing::Frame(Frame::Message(msg))
+    }
+}
+
+impl From<Control> for Outgoing {
+    fn from(control: Control) -> Self {
+        Outgoing::Frame(Frame::Control(control))
+    }
+}
+
+/// 在线用户条目：消息发送通道及连接信息
+#[derive(Debug)]
+struct OnlineUser {
+    tx: mpsc::Sender<Outgoing>,
+    peer_addr: SocketAddr,
+    connected_at: Timestamp,
+    client: String,
+    stats: Arc<SessionStats>,
+    presence: Arc<Mutex<Presence>>,
+    closing: Arc<Notify>,
+    route: Arc<RouteQueue>,
+    partners: Arc<Mutex<HashSet<ArcString>>>,
+    lang: Lang,
+    /// 对端 IP 的 GeoIP 信息
+    geo: Option<GeoInfo>,
+    /// 握手时协商出的特性
+    features: Vec<String>,
+}
+
+impl OnlineUser {
+    /// 向该用户投递消息所需的句柄，可以在释放在线用户表的锁之后使用
+    fn outbox(&self, name: &ArcString) -> Outbox {
+        Outbox {
+            name: name.clone(),
+            tx: self.tx.clone(),
+            stats: self.stats.clone(),
+            closing: self.closing.clone(),
+            route: self.route.clone(),
+            partners: self.partners.clone(),
+            lang: self.lang,
+        }
+    }
+
+    /// 对端地址，载入了 GeoIP 数据库时附带查询结果，如 `1.2.3.4:5678 [CN AS4134 (Chinanet)]`
+    fn origin(&self) -> String {
+        match &self.geo {
+            Some(geo) => format!("{} [{}]", self.peer_addr, geo),
+            None => self.peer_addr.to_string(),
+        }
+    }
+}
+
+/// 投递消息的目标：路由队列、发送通道、统计信息及断开连接的通知
+#[derive(Clone, Debug)]
+struct Outbox {
+    name: ArcString,
+    tx: mpsc::Sender<Outgoing>,
+    stats: Arc<SessionStats>,
+    closing: Arc<Notify>,
+    route: Arc<RouteQueue>,
+    partners: Arc<Mutex<HashSet<ArcString>>>,
+    /// 发给该用户的提示使用的语言
+    lang: Lang,
+}
+
+/// 系统通知，由各连接的写循环按该用户的语言生成文字
+#[derive(Clone, Debug)]
+enum Notice {
+    /// 已经生成好的文字，原样发送
+    Text(String),
+    /// 服务器即将关闭
+    Shutdown,
+    /// 服务器正在升级，由新进程接替
+    Upgrade,
+    /// 管理员通过 `/announce` 发布的公告
+    Announcement { from: ArcString, text: String },
+}
+
+impl Notice {
+    /// 以 `lang` 生成通知文字
+    fn render(&self, lang: Lang) -> String {
+        match self {
+            Notice::Text(text) => text.clone(),
+            Notice::Shutdown => tr!(lang => "server.shutdown_notice"),
+            Notice::Upgrade => tr!(lang => "server.upgrade_notice"),
+            Notice::Announcement { from, text } => {
+                tr!(lang => "server.announcement", user = from, text = text)
+            }
+        }
+    }
+
+    /// 关闭与升级通知对应的结构化关闭帧，`reconnect_after` 为配置的重连等待时间；其他通知为 `None`
+    fn shutdown_frame(&self, reconnect_after: Option<Duration>) -> Option<Control> {
+        let (reason, reconnect_after) = match self {
+            Notice::Shutdown => (ShutdownReason::Shutdown, reconnect_after),
+            Notice::Upgrade => (
+                ShutdownReason::Upgrade,
+                Some(reconnect_after.unwrap_or(UPGRADE_RECONNECT_AFTER)),
+            ),
+            Notice::Text(_) | Notice::Announcement { .. } => return None,
+        };
+        Some(Control::ServerShutdown {
+            reason,
+            reconnect_after: reconnect_after.map(|after| after.as_secs()),
+        })
+    }
+}
+
+/// 单个连接对系统通知的订阅
+struct Subscription {
+    notices: broadcast::Receiver<Notice>,
+    /// 在线名单更新，客户端未声明 `roster` 特性时为 `None`
+    roster: Option<broadcast::Receiver<RosterUpdate>>,
+    /// 客户端声明了 `shutdown` 特性：关闭与升级通知以结构化的关闭帧发送
+    shutdown_frames: bool,
+    /// 该连接的用户的语言
+    lang: Lang,
+}
+
+/// 在线用户的公开信息
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserInfo {
+    /// 用户名
+    pub name: String,
+    /// 连接建立时间
+    pub connected_at: DateTime<Local>,
+    /// 对端地址
+    pub peer_addr: SocketAddr,
+    /// 该用户发出的消息数
+    pub messages_sent: u64,
+    /// 投递给该用户的消息数
+    pub messages_received: u64,
+    /// 从该用户读取的字节数
+    pub bytes_in: u64,
+    /// 写给该用户的字节数
+    pub bytes_out: u64,
+    /// 该连接上无法解析的帧数
+    pub parse_errors: u64,
+    /// 距离最近一次活动的秒数
+    pub idle_secs: u64,
+    /// 对端 IP 的 GeoIP 信息（服务器载入了 GeoIP 数据库且查询到结果时）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geo: Option<GeoInfo>,
+}
+
+/// 某一时刻的在线用户快照
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RosterSnapshot {
+    /// 快照生成时间
+    pub taken_at: DateTime<Local>,
+    /// 在线用户列表（按用户名排序）
+    pub users: Vec<UserInfo>,
+}
 
 /// 服务器结构体，管理所有在线用户及其消息发送通道
 #[derive(Debug)]
 pub struct Server {
-    /// 在线用户映射：键为用户名（ArcString），值为对应的 mpsc 发送者
-    online_users: Arc<DashMap<ArcString, mpsc::Sender<Message>>>,
+    /// 在线用户映射：键为用户名（ArcString），值为对应的发送通道及连接信息
+    online_users: Arc<DashMap<ArcString, OnlineUser>>,
+    /// 在线名单：在线用户表的只读副本，成员变化时整体替换
+    roster: Arc<ArcSwap<Vec<Outbox>>>,
+    /// 串行化在线名单的重建，避免较早的结果覆盖较新的结果
+    roster_rebuild: Arc<Mutex<()>>,
+    /// 最近一次生成的在线用户快照
+    snapshot: Arc<ArcSwap<RosterSnapshot>>,
+    /// 系统通知：所有连接的写循环都订阅该通道
+    notices: broadcast::Sender<Notice>,
+    /// 在线名单的增量更新：声明了 `roster` 特性的连接的写循环订阅该通道
+    roster_updates: broadcast::Sender<RosterUpdate>,
+    /// 已知用户的资料与最后在线时间：键为用户名，用户离线后仍然保留
+    users: Arc<DashMap<ArcString, UserRecord>>,
+    /// 聊天室的设置：键为聊天室地址（见 `rooms` 子模块）
+    rooms: Arc<DashMap<String, RoomRecord>>,
+    /// 持久化存储
+    storage: Arc<dyn Storage>,
+    /// 无法投递的消息
+    dead_letters: Arc<DeadLetters>,
+    /// 最近分配的消息编号
+    last_message_id: Arc<AtomicU64>,
+    /// 最近收到的 (发送者, 消息编号)，用于丢弃重发的消息
+    seen: Arc<Mutex<DedupWindow<(ArcString, u64)>>>,
+    /// 通知 `run` 停止服务
+    shutdown: Arc<Notify>,
+    /// 连接处理任务发生 panic 的次数
+    handler_panics: Arc<AtomicU64>,
+    /// 接受失败与拒绝连接的计数
+    accepts: Arc<AcceptMonitor>,
+    /// 空闲文件描述符的余量检查
+    fd_headroom: Arc<FdHeadroom>,
+    /// 管理员生成的邀请码
+    invites: Arc<Invites>,
+    /// 私有聊天室的邀请码
+    room_invites: Arc<Invites>,
+    /// 各来源 IP 的连接数与连接频率
+    ip_limits: Arc<IpLimits>,
+    /// 挂载在本服务器上的虚拟主机：键为握手帧中的主机名
+    vhosts: Arc<HashMap<String, Server>>,
+    /// 服务器联邦的链路状态，未配置联邦时为 `None`
+    federation: Option<Arc<Federation>>,
+    /// 由本服务器撮合、尚未结束的文件传输
+    transfers: Arc<Transfers>,
+    /// 各机器人账号最近发送消息的时间
+    bot_limits: Arc<BotLimits>,
+    /// 故障注入的运行状态，未启用时为 `None`
+    #[cfg(feature = "chaos")]
+    chaos: Option<Arc<Chaos>>,
+    /// 机器人注册的指令
+    bot_commands: Arc<BotCommands>,
+    /// Telegram 桥接的运行状态，未配置桥接时为 `None`
+    #[cfg(feature = "bridges")]
+    telegram: Option<Arc<TelegramBridge>>,
+    /// XMPP 网关的运行状态，未配置网关时为 `None`
+    #[cfg(feature = "bridges")]
+    xmpp: Option<Arc<XmppGateway>>,
+    /// 维护任务及其运行统计
+    jobs: Arc<Jobs>,
+    /// 反垃圾规则的统计状态，未配置时为 `None`
+    spam: Option<Arc<SpamGuard>>,
+    /// 发给已知用户的消息的投递回执
+    receipts: Arc<Receipts>,
+    /// 审计日志
+    audit: Arc<AuditLog>,
+    /// 待管理员处理的举报
+    moderation: Arc<ModerationQueue>,
+    /// 被追踪消息的路由记录
+    tracer: Arc<Tracer>,
+    /// 维护模式的开关
+    maintenance: Arc<Maintenance>,
+    /// 挂载的插件，按挂载顺序调用
+    plugins: Arc<Vec<Arc<dyn Plugin>>>,
+    /// 发来 `drain` 命令的管理连接，排空完毕时在其上回复
+    handoff: Arc<Mutex<Option<Handoff>>>,
+    config: Arc<ServerConfig>,
 }
 
 impl Default for Server {
@@ -36,184 +710,1914 @@ impl Default for Server {
 impl Server {
     /// 创建一个新的 `Server` 实例
     pub fn new() -> Self {
+        Self::with_config(ServerConfig::default())
+    }
+
+    /// 使用指定配置创建 `Server` 实例，数据仅保存在内存中
+    pub fn with_config(config: ServerConfig) -> Self {
+        Self::with_storage(config, Arc::new(MemoryStorage::new()))
+    }
+
+    /// 使用指定配置与持久化存储创建 `Server` 实例，并载入已有的用户数据
+    ///
+    /// 工作量证明的难度超过 [`pow::MAX_DIFFICULTY`] 时降为该上限（客户端拒绝求解更难的挑战，
+    /// 否则所有新用户都无法注册），为 0 时视为不要求
+    pub fn with_storage(mut config: ServerConfig, storage: Arc<dyn Storage>) -> Self {
+        config.pow_difficulty = match config.pow_difficulty {
+            Some(0) | None => None,
+            Some(difficulty) if difficulty > pow::MAX_DIFFICULTY => {
+                eprintln!(
+                    "{}",
+                    tr!(
+                        "server.pow_clamped",
+                        difficulty = difficulty,
+                        max = pow::MAX_DIFFICULTY
+                    )
+                );
+                Some(pow::MAX_DIFFICULTY)
+            }
+            difficulty => difficulty,
+        };
+        let users = DashMap::new();
+        for (name, record) in storage.users() {
+            users.insert(ArcString::new(name), record);
+        }
+        let rooms: DashMap<String, RoomRecord> = storage.rooms().into_iter().collect();
         Self {
             online_users: Arc::new(DashMap::new()),
+            roster: Arc::new(ArcSwap::default()),
+            roster_rebuild: Arc::new(Mutex::new(())),
+            snapshot: Arc::new(ArcSwap::from_pointee(RosterSnapshot {
+                taken_at: Local::now(),
+                users: Vec::new(),
+            })),
+            notices: broadcast::channel(NOTICE_CAPACITY).0,
+            roster_updates: broadcast::channel(ROSTER_UPDATE_CAPACITY).0,
+            users: Arc::new(users),
+            rooms: Arc::new(rooms),
+            dead_letters: Arc::new(DeadLetters::default()),
+            last_message_id: Arc::new(AtomicU64::new(storage.last_message_id())),
+            seen: Arc::new(Mutex::new(DedupWindow::default())),
+            shutdown: Arc::new(Notify::new()),
+            handler_panics: Arc::new(AtomicU64::new(0)),
+            accepts: Arc::new(AcceptMonitor::default()),
+            fd_headroom: Arc::new(FdHeadroom::new(config.fd_reserve)),
+            invites: Arc::new(Invites::default()),
+            room_invites: Arc::new(Invites::default()),
+            ip_limits: Arc::new(IpLimits::new(config.per_ip.clone())),
+            vhosts: Arc::new(HashMap::new()),
+            federation: config
+                .federation
+                .clone()
+                .map(|config| Arc::new(Federation::new(config))),
+            transfers: Arc::new(Transfers::default()),
+            bot_limits: Arc::new(BotLimits::default()),
+            #[cfg(feature = "chaos")]
+            chaos: config
+                .chaos
+                .clone()
+                .map(|config| Arc::new(Chaos::new(config))),
+            bot_commands: Arc::new(BotCommands::default()),
+            #[cfg(feature = "bridges")]
+            telegram: config
+                .telegram
+                .clone()
+                .map(|config| Arc::new(TelegramBridge::new(config))),
+            #[cfg(feature = "bridges")]
+            xmpp: config
+                .xmpp
+                .clone()
+                .map(|config| Arc::new(XmppGateway::new(config))),
+            jobs: Arc::new(Jobs::new(&config.jobs)),
+            spam: config
+                .spam
+                .clone()
+                .map(|config| Arc::new(SpamGuard::new(config))),
+            audit: Arc::new(AuditLog::new(config.audit_log.as_deref())),
+            receipts: Arc::new(Receipts::default()),
+            moderation: Arc::new(ModerationQueue::default()),
+            tracer: Arc::new(Tracer::default()),
+            maintenance: Arc::new(Maintenance::default()),
+            plugins: Arc::new(Vec::new()),
+            handoff: Arc::new(Mutex::new(None)),
+            storage,
+            config: Arc::new(config),
         }
     }
 
+    /// 挂载名为 `name` 的虚拟主机
+    ///
+    /// 虚拟主机由本服务器的 [`Server::run`] 接受连接，握手帧的 `host` 字段为 `name` 的连接
+    /// 交给 `host` 处理；`host` 的在线用户、通知、配置与存储与本服务器完全隔离。
+    /// 连接数上限与套接字选项以本服务器的配置为准，关闭本服务器时虚拟主机上的用户一并断开。
+    pub fn with_vhost(mut self, name: impl Into<String>, host: Server) -> Self {
+        Arc::make_mut(&mut self.vhosts).insert(name.into(), host);
+        self
+    }
+
+    /// 已挂载的虚拟主机名称（按名称排序）
+    pub fn vhost_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.vhosts.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// 查询当前所有在线用户的详细信息（按用户名排序）
+    pub fn online_users(&self) -> Vec<UserInfo> {
+        let mut users: Vec<UserInfo> = self
+            .online_users
+            .iter()
+            .map(|entry| {
+                let user = entry.value();
+                UserInfo {
+                    name: entry.key().get(),
+                    connected_at: user.connected_at.wall(),
+                    peer_addr: user.peer_addr,
+                    messages_sent: user.stats.messages_sent.load(Ordering::Relaxed),
+                    messages_received: user.stats.messages_received.load(Ordering::Relaxed),
+                    bytes_in: user.stats.bytes_in.load(Ordering::Relaxed),
+                    bytes_out: user.stats.bytes_out.load(Ordering::Relaxed),
+                    parse_errors: user.stats.parse_errors.load(Ordering::Relaxed),
+                    idle_secs: user.stats.idle_for().as_secs(),
+                    geo: user.geo.clone(),
+                }
+            })
+            .collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        users
+    }
+
+    /// 获取最近一次生成的在线用户快照
+    ///
+    /// 快照由 `run` 启动的后台任务按 [`ServerConfig::snapshot_interval`] 定期刷新，
+    /// 读取时不会遍历在线用户映射。
+    pub fn snapshot(&self) -> Arc<RosterSnapshot> {
+        self.snapshot.load_full()
+    }
+
+    /// 立即重新生成在线用户快照
+    pub fn refresh_snapshot(&self) -> Arc<RosterSnapshot> {
+        let snapshot = Arc::new(RosterSnapshot {
+            taken_at: Local::now(),
+            users: self.online_users(),
+        });
+        self.snapshot.store(snapshot.clone());
+        snapshot
+    }
+
+    /// 当前在线名单（按用户名排序），读取时不锁在线用户表
+    fn roster(&self) -> Arc<Vec<Outbox>> {
+        self.roster.load_full()
+    }
+
+    /// 用户上线或下线后重建在线名单
+    fn rebuild_roster(&self) {
+        let _rebuilding = self.roster_rebuild.lock().unwrap();
+        let mut roster: Vec<Outbox> = self
+            .online_users
+            .iter()
+            .map(|entry| entry.value().outbox(entry.key()))
+            .collect();
+        roster.sort_by(|a, b| a.name.cmp(&b.name));
+        self.roster.store(Arc::new(roster));
+        if let Some(federation) = &self.federation {
+            federation.roster_changed();
+        }
+        #[cfg(feature = "bridges")]
+        self.xmpp_status_changed();
+    }
+
     /// 启动服务器，监听指定地址，并处理所有新连接
-    pub async fn run(&self, addr: &String) -> Result<(), Box<dyn std::error::Error>> {
-        let listener = TcpListener::bind(addr).await?;
-        println!("服务器正在监听 {}", addr);
+    ///
+    /// 每个连接在 `JoinSet` 中运行：连接任务结束时及时回收并报告其中的 panic；
+    /// 调用 [`Server::shutdown`]（收到 Ctrl+C、`SIGTERM` 或管理套接字上的 `drain` 命令）后停止接受新连接，
+    /// 通知并断开所有在线用户，等待连接任务结束后返回。
+    pub async fn run(&self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let listeners = Listeners::bind(addr, &self.config).await?;
+        self.run_on(listeners).await
+    }
 
-        let server = self.clone();
+    /// 在已绑定的监听套接字上提供服务，接管旧进程时由调用方先绑定端口，见 [`Server::run`]
+    pub async fn run_on(&self, listeners: Listeners) -> Result<(), Box<dyn std::error::Error>> {
+        let admin_task = self.spawn_admin_socket().await?;
+        #[cfg(feature = "chaos")]
+        self.announce_chaos();
+        self.check_fd_limit();
+        let Listeners {
+            chat: listener,
+            websocket: ws_listener,
+        } = listeners;
+        let hooks_task = match &self.config.webhooks {
+            Some(hooks_addr) => {
+                let listener =
+                    upgrade::bind(hooks_addr, self.config.admin_socket.is_some()).await?;
+                println!("{}", tr!("server.webhook_listening", addr = hooks_addr));
+                let server = self.clone();
+                Some(tokio::spawn(async move {
+                    server.serve_webhooks(listener).await
+                }))
+            }
+            None => None,
+        };
 
-        let _shutdown_task = tokio::spawn(async move {
-            tokio::signal::ctrl_c().await.expect("无法监听 Ctrl+C");
-            println!("\n接收到 Ctrl+C，正在关闭服务器...");
-
-            // **通知所有在线用户**
-            let users = server.online_users.iter();
-            for entry in users {
-                let username = entry.key();
-                let sender = entry.value();
-                let notify_msg = Message::new(
-                    ArcString::new("Server".to_string()),
-                    username.get(),
-                    "服务器即将关闭，所有用户已断开连接".to_string(),
-                );
-                let _ = sender.send(notify_msg).await;
+        // **定期刷新在线用户快照**
+        let server = self.clone();
+        let snapshot_task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(server.config.snapshot_interval);
+            loop {
+                interval.tick().await;
+                server.refresh_snapshot();
+                for host in server.vhosts.values() {
+                    host.refresh_snapshot();
+                }
             }
+        });
+
+        // 维持到各联邦对端的出站链路
+        let peer_links = self.spawn_peer_links();
+
+        // Telegram 桥接的长轮询与发送任务
+        #[cfg(feature = "bridges")]
+        let telegram_task = self.spawn_telegram();
+
+        // XMPP 网关的组件连接
+        #[cfg(feature = "bridges")]
+        let xmpp_task = self.spawn_xmpp();
+
+        // 定期归档较早的历史消息
+        #[cfg(feature = "storage")]
+        let archive_task = self.spawn_archiver();
 
-            // **清空在线用户列表**
-            server.online_users.clear();
-            println!("所有用户连接已释放，服务器退出。");
-            process::exit(0);
+        // 按时间表运行维护任务
+        let jobs_task = self.spawn_jobs();
+
+        let server = self.clone();
+        tokio::spawn(async move {
+            systemd::shutdown_signal().await;
+            server.shutdown();
         });
+
+        // 通知 systemd 已经就绪，并按看门狗间隔定期报告
+        let watchdog_task = self.notify_ready();
+
+        // 连接任务及其对端地址
+        let mut connections = JoinSet::new();
+        let mut peers = HashMap::new();
+        // 两个监听套接字各自的接受失败退避
+        let mut backoff = AcceptBackoff::default();
+        let mut ws_backoff = AcceptBackoff::default();
         loop {
-            // 异步接受新连接
-            match listener.accept().await {
-                Ok((stream, addr)) => {
-                    println!("接收到来自 {} 的新连接", addr);
-                    // 克隆当前 Server 实例（低成本克隆内部 Arc）
-                    let server = self.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = server.handle_connection(stream).await {
-                            eprintln!("处理来自 {} 的连接时出错: {:?}", addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    eprintln!("接受连接失败: {:?}", e);
-                }
+            tokio::select! {
+                // 异步接受新连接
+                accepted = backoff.accept(Some(&listener)) => match accepted {
+                    Ok((stream, addr)) => {
+                        backoff.succeeded();
+                        self.admit(stream, addr, false, &mut connections, &mut peers);
+                    }
+                    Err(e) => self.accept_failed(&mut backoff, e),
+                },
+                accepted = ws_backoff.accept(ws_listener.as_ref()) => match accepted {
+                    Ok((stream, addr)) => {
+                        ws_backoff.succeeded();
+                        self.admit(stream, addr, true, &mut connections, &mut peers);
+                    }
+                    Err(e) => self.accept_failed(&mut ws_backoff, e),
+                },
+                Some(joined) = connections.join_next_with_id() => self.reap(&mut peers, joined),
+                () = self.shutdown.notified() => break,
+            }
+        }
+        self.notify_stopping();
+        // 关闭监听套接字，接管的新进程绑定了同一端口时新连接随即全部进入新进程
+        drop(listener);
+        drop(ws_listener);
+
+        // **通知所有在线用户（包括各虚拟主机上的用户）并断开连接，等待连接任务结束**
+        let notice = if self.draining() {
+            Notice::Upgrade
+        } else {
+            Notice::Shutdown
+        };
+        self.close_all(notice.clone());
+        for host in self.vhosts.values() {
+            host.close_all(notice.clone());
+        }
+        for link in &peer_links {
+            link.abort();
+        }
+        if let Some(federation) = &self.federation {
+            federation.close();
+        }
+        let drain = async {
+            while let Some(joined) = connections.join_next_with_id().await {
+                self.reap(&mut peers, joined);
             }
+        };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, drain).await.is_err() {
+            eprintln!("{}", tr!("server.force_close", count = connections.len()));
+            connections.shutdown().await;
+        }
+        snapshot_task.abort();
+        if let Some(task) = hooks_task {
+            task.abort();
+        }
+        #[cfg(feature = "bridges")]
+        for task in [telegram_task, xmpp_task].into_iter().flatten() {
+            task.abort();
+        }
+        #[cfg(feature = "storage")]
+        if let Some(task) = archive_task {
+            task.abort();
+        }
+        if let Some(task) = jobs_task {
+            task.abort();
+        }
+        if let Some(task) = admin_task {
+            task.abort();
+        }
+        if let Some(task) = watchdog_task {
+            task.abort();
+        }
+        self.finish_admin_socket().await;
+        println!("{}", tr!("server.exited"));
+        Ok(())
+    }
+
+    /// 检查连接数与来源 IP 限制后，在 `connections` 中启动新连接的处理任务
+    fn admit(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        websocket: bool,
+        connections: &mut JoinSet<()>,
+        peers: &mut HashMap<task::Id, SocketAddr>,
+    ) {
+        // 双栈套接字上的 IPv4 连接以 `::ffff:a.b.c.d` 的形式出现，还原为 IPv4 地址
+        let addr = SocketAddr::new(addr.ip().to_canonical(), addr.port());
+        if self
+            .config
+            .max_connections
+            .is_some_and(|max| connections.len() >= max)
+        {
+            println!("{}", tr!("server.connection_limit", addr = addr));
+            self.accepts.refuse(Overload::ConnectionLimit);
+            return;
         }
+        if self.fd_headroom.exhausted(connections.len()) {
+            println!("{}", tr!("server.fd_exhausted", addr = addr));
+            self.accepts.refuse(Overload::FdHeadroom);
+            return;
+        }
+        // 日志中的对端地址附带 GeoIP 信息（如 `1.2.3.4:5678 [CN AS4134 (Chinanet)]`）
+        #[cfg(feature = "geoip")]
+        let geo = self
+            .config
+            .geoip
+            .as_ref()
+            .and_then(|geoip| geoip.lookup(addr.ip()));
+        #[cfg(not(feature = "geoip"))]
+        let geo: Option<GeoInfo> = None;
+        let peer = match &geo {
+            Some(geo) => format!("{} [{}]", addr, geo),
+            None => addr.to_string(),
+        };
+        // 插件的接入策略先于来源 IP 的限制，被插件拒绝的连接不占用该 IP 的名额
+        if let Some(reason) = self.plugin_refusal(addr) {
+            println!(
+                "{}",
+                tr!("server.plugin_refused", addr = peer, reason = reason)
+            );
+            tokio::spawn(turn_away(
+                stream,
+                websocket,
+                ErrorCode::Denied,
+                move |lang| tr!(lang => "server.plugin_denied", reason = reason),
+            ));
+            return;
+        }
+        let permit = match self.ip_limits.admit(addr.ip()) {
+            Ok(permit) => permit,
+            Err(refusal) => {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.ip_refused",
+                        addr = peer,
+                        reason = refusal.describe(i18n::lang())
+                    )
+                );
+                tokio::spawn(turn_away(stream, websocket, refusal.code(), move |lang| {
+                    refusal.describe(lang)
+                }));
+                return;
+            }
+        };
+        self.accepts.admitted();
+        if websocket {
+            println!("{}", tr!("server.new_ws_connection", addr = peer));
+        } else {
+            println!("{}", tr!("server.new_connection", addr = peer));
+        }
+        if let Err(e) = self.config.socket.apply(&stream) {
+            eprintln!(
+                "{}",
+                tr!("server.socket_options_failed", addr = addr, error = e)
+            );
+        }
+        // 克隆当前 Server 实例（低成本克隆内部 Arc）
+        let server = self.clone();
+        let handle = connections.spawn(async move {
+            // 连接任务结束（包括 panic）时归还该 IP 的连接名额
+            let _permit = permit;
+            if let Err(e) = server.handle_connection(stream, websocket, addr, geo).await {
+                let error = format!("{:?}", e);
+                eprintln!(
+                    "{}",
+                    tr!("server.connection_error", addr = addr, error = error)
+                );
+            }
+        });
+        peers.insert(handle.id(), addr);
+    }
+
+    /// 记录一次接受失败，下一次接受前按 `backoff` 等待
+    fn accept_failed(&self, backoff: &mut AcceptBackoff, error: io::Error) {
+        let delay = backoff.failed();
+        eprintln!(
+            "{}",
+            tr!(
+                "server.accept_failed",
+                error = format!("{:?}", error),
+                delay = delay.as_millis()
+            )
+        );
+        self.accepts.refuse(Overload::AcceptFailed);
+    }
+
+    /// 发布关闭（或升级）通知，并要求所有在线连接结束
+    fn close_all(&self, notice: Notice) {
+        self.publish(notice);
+        for outbox in self.roster().iter() {
+            outbox.closing.notify_one();
+        }
+    }
+
+    /// 回收一个已结束的连接任务；任务 panic 时记录对端地址并计数
+    fn reap(
+        &self,
+        peers: &mut HashMap<task::Id, SocketAddr>,
+        joined: Result<(task::Id, ()), JoinError>,
+    ) {
+        let id = match &joined {
+            Ok((id, ())) => *id,
+            Err(e) => e.id(),
+        };
+        let peer = peers.remove(&id);
+        if let Err(e) = joined {
+            if e.is_panic() {
+                let total = self.handler_panics.fetch_add(1, Ordering::Relaxed) + 1;
+                let addr = peer.map_or_else(|| tr!("server.unknown_addr"), |peer| peer.to_string());
+                eprintln!(
+                    "{}",
+                    tr!(
+                        "server.handler_panic",
+                        addr = addr,
+                        count = total,
+                        message = panic_message(e.into_panic()),
+                    )
+                );
+            }
+        }
+    }
+
+    /// 连接处理任务发生 panic 的累计次数
+    pub fn handler_panics(&self) -> u64 {
+        self.handler_panics.load(Ordering::Relaxed)
+    }
+
+    /// 以 "Server" 身份向所有在线用户发送系统通知
+    ///
+    /// # 返回值
+    /// 收到通知的连接数
+    pub fn broadcast(&self, content: String) -> usize {
+        self.publish(Notice::Text(content))
+    }
+
+    /// 发布系统通知，返回收到通知的连接数
+    fn publish(&self, notice: Notice) -> usize {
+        self.notices.send(notice).unwrap_or(0)
+    }
+
+    /// 在线用户的语言，用户不在线时为服务器进程的语言
+    fn lang_of(&self, username: &ArcString) -> Lang {
+        self.online_users
+            .get(username)
+            .map_or_else(i18n::lang, |user| user.lang)
+    }
+
+    /// 请求 [`Server::run`] 停止接受新连接、断开所有用户并返回
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
     }
 
     async fn handle_connection(
         &self,
-        mut stream: TcpStream,
+        stream: TcpStream,
+        websocket: bool,
+        peer_addr: SocketAddr,
+        geo: Option<GeoInfo>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (reader, writer) = split_stream(stream, websocket).await?;
+        self.handle_stream(reader, writer, peer_addr, geo).await
+    }
+
+    /// 处理一条不经过监听端口的连接（例如进程内的 `tokio::io::duplex` 管道），直到连接结束
+    ///
+    /// 不受连接数与来源 IP 限制，`peer_addr` 只用于日志；`chat bench` 以此在进程内压测服务器
+    pub async fn serve_stream(
+        &self,
+        reader: BoxReader,
+        writer: BoxWriter,
+        peer_addr: SocketAddr,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut buf = [0u8; 1024];
+        self.handle_stream(reader, writer, peer_addr, None).await
+    }
 
-        // 读取客户端的注册信息（用户名）
-        let len = stream.read(&mut buf).await?;
-        if len == 0 {
+    async fn handle_stream(
+        &self,
+        reader: BoxReader,
+        writer: BoxWriter,
+        peer_addr: SocketAddr,
+        geo: Option<GeoInfo>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // 根据首字节识别客户端使用的编码
+        let Some(mut reader) = FrameReader::detect(reader).await? else {
             return Ok(());
+        };
+        let mut writer = FrameWriter::new(writer, reader.codec());
+
+        // 读取客户端的注册信息（握手帧或用户名）
+        let Some(registration) = reader.read_frame::<Registration>().await? else {
+            return Ok(());
+        };
+        let hello = registration.into_hello();
+        // 联邦中的对端服务器
+        if hello.features.iter().any(|f| f == FEATURE_FEDERATION) {
+            return self.accept_peer(reader, writer, hello, peer_addr).await;
+        }
+        match hello.host.as_deref().filter(|host| !host.is_empty()) {
+            None => self.serve(reader, writer, hello, peer_addr, geo).await,
+            Some(name) => match self.vhosts.get(name) {
+                Some(host) => {
+                    println!(
+                        "{}",
+                        tr!("server.vhost_selected", addr = peer_addr, host = name)
+                    );
+                    host.serve(reader, writer, hello, peer_addr, geo).await
+                }
+                None => {
+                    println!(
+                        "{}",
+                        tr!("server.unknown_vhost", addr = peer_addr, host = name)
+                    );
+                    let text = tr!(hello_lang(&hello) => "server.unknown_vhost_reply", host = name);
+                    refuse(
+                        &mut writer,
+                        &hello,
+                        ErrorCode::UnknownHost,
+                        Some(name),
+                        text,
+                    )
+                    .await
+                }
+            },
+        }
+    }
+
+    /// 完成注册并处理已选定本服务器（默认空间或某个虚拟主机）的连接，直到连接结束
+    async fn serve(
+        &self,
+        mut reader: FrameReader<BoxReader>,
+        mut writer: FrameWriter<BoxWriter>,
+        hello: Hello,
+        peer_addr: SocketAddr,
+        geo: Option<GeoInfo>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let resume_from = hello.resume_from;
+        let username = ArcString::new(hello.name.trim().to_string());
+
+        // 保留的用户名不能注册，以免冒充系统通知或与指令、聊天室地址混淆
+        if is_reserved_name(hello.name.trim()) {
+            println!(
+                "{}",
+                tr!(
+                    "server.name_refused",
+                    user = sanitize(hello.name.trim()),
+                    addr = peer_addr
+                )
+            );
+            let text = tr!(hello_lang(&hello) => "server.name_reserved");
+            return refuse(&mut writer, &hello, ErrorCode::InvalidName, None, text).await;
         }
 
-        let name = String::from_utf8_lossy(&buf[..len]).trim().to_string();
-        let username = ArcString::new(name);
+        // 机器人账号与管理员须出示有效的令牌，普通用户不能出示令牌
+        let role = match self.authenticate(&username, &hello, hello_lang(&hello)) {
+            Ok(role) => role,
+            Err((code, text)) => {
+                println!(
+                    "{}",
+                    tr!("server.bot_refused", user = username, addr = peer_addr)
+                );
+                return refuse(&mut writer, &hello, code, None, text).await;
+            }
+        };
+
+        // 维护模式下不接受新的注册，管理员除外
+        if role != Role::Admin {
+            if let Some(text) = self.maintenance_refusal(hello_lang(&hello)) {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.maintenance_refused",
+                        user = username,
+                        addr = peer_addr
+                    )
+                );
+                return refuse(&mut writer, &hello, ErrorCode::Maintenance, None, text).await;
+            }
+        }
+
+        // 出示了身份公钥的连接先证明持有对应的私钥
+        let mut early = VecDeque::new();
+        let proven = match hello.identity.as_deref() {
+            Some(key)
+                if !self
+                    .prove_identity(&mut reader, &mut writer, hello.name.trim(), key, &mut early)
+                    .await? =>
+            {
+                println!(
+                    "{}",
+                    tr!("server.identity_refused", user = username, addr = peer_addr)
+                );
+                let text = tr!(hello_lang(&hello) => "server.identity_failed");
+                return refuse(&mut writer, &hello, ErrorCode::IdentityRequired, None, text).await;
+            }
+            key => key.map(str::to_string),
+        };
+
+        // 已绑定身份公钥的用户名只接受证明持有同一把私钥的连接（机器人与管理员已由令牌认证），
+        // 只凭用户名不能冒充已注册的用户绕过下面的检查
+        let bound = self
+            .users
+            .get(&username)
+            .and_then(|record| record.identity_key.clone());
+        if role == Role::User && bound.is_some() && proven != bound {
+            println!(
+                "{}",
+                tr!("server.identity_refused", user = username, addr = peer_addr)
+            );
+            let text = tr!(hello_lang(&hello) => "server.identity_required", user = username);
+            return refuse(&mut writer, &hello, ErrorCode::IdentityRequired, None, text).await;
+        }
+
+        // 尚未绑定身份公钥的用户名（包括未出示身份公钥的旧客户端）需要通过工作量证明与邀请码检查；
+        // 机器人与管理员不受限制，管理员因此可以生成第一批邀请码
+        let newcomer = role == Role::User && bound.is_none();
+        if let (true, Some(difficulty)) = (newcomer, self.config.pow_difficulty) {
+            let lang = hello_lang(&hello);
+            let refused = if hello.features.iter().any(|f| f == FEATURE_POW) {
+                if self
+                    .challenge(&mut reader, &mut writer, difficulty, &mut early)
+                    .await?
+                {
+                    None
+                } else {
+                    Some((ErrorCode::PowFailed, tr!(lang => "server.pow_failed")))
+                }
+            } else {
+                Some((ErrorCode::PowRequired, tr!(lang => "server.pow_required")))
+            };
+            if let Some((code, text)) = refused {
+                println!(
+                    "{}",
+                    tr!("server.pow_refused", user = username, addr = peer_addr)
+                );
+                return refuse(&mut writer, &hello, code, None, text).await;
+            }
+        }
+        // 仅限邀请注册时，新用户必须出示有效的邀请码
+        if self.config.invite_only && newcomer {
+            let lang = hello_lang(&hello);
+            let refused = match hello.invite.as_deref() {
+                None => Some((
+                    ErrorCode::InviteRequired,
+                    tr!(lang => "server.invite_required"),
+                )),
+                Some(code) => match self.invites.redeem(code) {
+                    Ok(invite) => {
+                        println!(
+                            "{}",
+                            tr!(
+                                "server.invite_redeemed",
+                                user = username,
+                                code = invite.code,
+                                used = invite.used,
+                                max = invite.max_uses,
+                            )
+                        );
+                        None
+                    }
+                    Err(e) => Some((ErrorCode::InvalidInvite, e.describe(lang))),
+                },
+            };
+            if let Some((code, text)) = refused {
+                println!(
+                    "{}",
+                    tr!("server.invite_refused", user = username, addr = peer_addr)
+                );
+                return refuse(&mut writer, &hello, code, None, text).await;
+            }
+        }
+
+        // 首次注册的用户创建默认资料，尚未绑定身份公钥的用户名绑定本次证明过的公钥；
+        // 同一用户名并发注册时以先绑定者为准，其余连接按冒充处理
+        let mut changed = false;
+        let conflict = {
+            let mut record = self.users.entry(username.clone()).or_insert_with(|| {
+                changed = true;
+                UserRecord::default()
+            });
+            match (&record.identity_key, &proven) {
+                (None, Some(key)) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "server.identity_bound",
+                            user = username,
+                            fingerprint = identity::fingerprint(key)
+                        )
+                    );
+                    record.identity_key = Some(key.clone());
+                    changed = true;
+                    false
+                }
+                (Some(bound), proven) => role == Role::User && proven.as_ref() != Some(bound),
+                (None, None) => false,
+            }
+        };
+        if changed {
+            self.save_user(&username);
+        }
+        if conflict {
+            println!(
+                "{}",
+                tr!("server.identity_refused", user = username, addr = peer_addr)
+            );
+            let text = tr!(hello_lang(&hello) => "server.identity_required", user = username);
+            return refuse(&mut writer, &hello, ErrorCode::IdentityRequired, None, text).await;
+        }
+
+        let features = hello.negotiate();
+        let mut session = Session::new(username.clone(), peer_addr, hello.client, features);
+        session.role = role;
+        match role {
+            Role::Bot => println!("{}", tr!("server.bot_authenticated", user = username)),
+            Role::Admin => println!("{}", tr!("server.admin_authenticated", user = username)),
+            Role::User => {}
+        }
+        if let Some(lang) = hello.lang.as_deref().and_then(Lang::parse) {
+            session.lang = lang;
+        }
+        session.early = early;
+        session.geo = geo;
 
         // 创建 `mpsc` 通道用于消息转发
-        let (tx, mut rx) = mpsc::channel::<Message>(10);
-        self.online_users.insert(username.clone(), tx);
-        println!("用户 {} 已注册", username.get());
+        let (tx, rx) = mpsc::channel::<Outgoing>(10);
+        let user = OnlineUser {
+            tx,
+            peer_addr,
+            connected_at: session.connected_at,
+            client: session.client.clone(),
+            stats: session.stats.clone(),
+            presence: session.presence.clone(),
+            closing: session.closing.clone(),
+            route: Arc::new(RouteQueue::default()),
+            partners: session.partners.clone(),
+            lang: session.lang,
+            geo: session.geo.clone(),
+            features: session.features.clone(),
+        };
+        let outbox = user.outbox(&username);
+        // 在加入在线用户表之前订阅系统通知，之后发布的通知都不会错过
+        let notices = Subscription {
+            notices: self.notices.subscribe(),
+            roster: session
+                .has_feature(FEATURE_ROSTER)
+                .then(|| self.subscribe_roster()),
+            shutdown_frames: session.has_feature(FEATURE_SHUTDOWN),
+            lang: session.lang,
+        };
+        // 同名用户重新登录：旧连接的会话仍持有其发送端，需通知其结束
+        if let Some(replaced) = self.online_users.insert(username.clone(), user) {
+            replaced.closing.notify_one();
+        }
+        self.rebuild_roster();
+        self.push_roster(RosterChange::Joined, &username);
+        // 连接任务 panic 或被强制结束时，由守卫把本连接移出在线用户表
+        let mut guard = OnlineGuard {
+            server: self,
+            username: username.clone(),
+            peer_addr,
+            stats: session.stats.clone(),
+            route: outbox.route.clone(),
+            armed: true,
+        };
+        println!(
+            "{}",
+            tr!("server.registered", user = username, codec = reader.codec())
+        );
+        session
+            .stats
+            .bytes_in
+            .store(reader.bytes_read(), Ordering::Relaxed);
 
-        // **解决方法：使用 `into_split()` 分割 `TcpStream`**
-        let (mut reader, mut writer) = stream.into_split();
+        // 读、写与转发在同一任务中并发运行：任意一方结束（客户端断开、写入失败或超时）
+        // 即停止其余部分的主循环，之后统一清理一次
+        let (stats, route) = (session.stats.clone(), outbox.route.clone());
+        let write = self.write_loop(&username, &stats, &route, writer, rx, notices);
+        tokio::pin!(write);
+        let receive = async {
+            if session.has_feature(FEATURE_RESUME) {
+                self.resume(&session, resume_from).await;
+            }
+            self.rejoin_rooms(&session, resume_from).await;
+            self.handle_receive(&mut session, &mut reader).await
+        };
+        let (result, write_done) = tokio::select! {
+            result = receive => (result, false),
+            result = &mut write => (result, true),
+            () = self.forward_loop(&outbox) => (Ok(()), false),
+        };
+        guard.armed = false;
+        self.unregister(&session);
+        self.abort_transfers(&username).await;
+        if let (true, Err(e)) = (write_done, &result) {
+            println!("{}", tr!("server.write_failed", user = username, error = e));
+            self.notify_partners(&session).await;
+        } else if let Departure::Left(_) = session.departure {
+            self.notify_partners(&session).await;
+        }
+        self.abandon(&route);
+        // 释放本连接持有的发送端，通道关闭后写循环才能结束
+        drop(outbox);
 
-        // **写任务（发送消息给客户端）**
-        tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                if let Ok(json_msg) = serde_json::to_string(&msg) {
-                    if let Err(e) = writer.write_all(json_msg.as_bytes()).await {
-                        eprintln!("发送消息失败: {:?}", e);
-                        break;
+        // 移出在线用户表后通道随之关闭，写完已排队的帧（例如断开原因）后关闭写入端
+        if !write_done {
+            let _ = tokio::time::timeout(self.config.slow_grace, write).await;
+        }
+        Ok(result?)
+    }
+
+    /// 写循环：把发送队列中的帧写给客户端，队列关闭或写入失败、超时时结束并关闭写入端
+    ///
+    /// # 返回值
+    /// 因 I/O 错误结束时返回该错误，由调用方通知与该用户互发过消息的用户
+    async fn write_loop(
+        &self,
+        username: &ArcString,
+        stats: &SessionStats,
+        route: &RouteQueue,
+        mut writer: FrameWriter<BoxWriter>,
+        mut rx: mpsc::Receiver<Outgoing>,
+        mut notices: Subscription,
+    ) -> Result<(), CodecError> {
+        let slow_grace = self.config.slow_grace;
+        let server_name = ArcString::new("Server".to_string());
+        // 订阅了在线名单的连接先收到完整列表，此后只收到增量更新
+        let mut pending = notices
+            .roster
+            .is_some()
+            .then(|| Outgoing::from(self.roster_list()));
+        loop {
+            // 优先写出系统通知：关闭服务器的通知先于发送通道关闭发布，不会被跳过
+            let frame = if let Some(frame) = pending.take() {
+                frame
+            } else {
+                tokio::select! {
+                    biased;
+                    notice = notices.notices.recv() => match notice {
+                        Ok(notice) => match notice.shutdown_frame(self.config.reconnect_after) {
+                            Some(control) if notices.shutdown_frames => Outgoing::from(control),
+                            _ => {
+                                let content = notice.render(notices.lang);
+                                Outgoing::from(Message::new(server_name.clone(), username.get(), content))
+                            }
+                        },
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            println!("{}", tr!("server.notices_missed", user = username, count = missed));
+                            continue;
+                        }
+                        // 发送端由 Server 持有，不会先于写循环关闭
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    control = self.next_roster_frame(&mut notices.roster) => Outgoing::from(control),
+                    frame = rx.recv() => match frame {
+                        Some(frame) => frame,
+                        None => break,
+                    },
+                }
+            };
+            // 对端长时间不读取时写入会一直阻塞，超过宽限期即断开连接
+            let started = Instant::now();
+            #[cfg(feature = "chaos")]
+            let write = self.write_chaotic(username, &mut writer, &frame);
+            #[cfg(not(feature = "chaos"))]
+            let write = frame.write(&mut writer);
+            let written = tokio::time::timeout(slow_grace, write).await;
+            let Ok(written) = written else {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.write_stalled",
+                        user = username,
+                        grace = humanize_duration(slow_grace),
+                    )
+                );
+                return Ok(());
+            };
+            match written {
+                Ok(()) => {
+                    stats
+                        .bytes_out
+                        .store(writer.bytes_written(), Ordering::Relaxed);
+                    if let Some(msg) = frame.message() {
+                        stats.messages_received.fetch_add(1, Ordering::Relaxed);
+                        if let Some(id) = msg.id() {
+                            self.receipts.delivered(id);
+                        }
+                        self.trace_event(msg.from(), msg.id(), TraceEvent::Delivered);
+                    }
+                    if started.elapsed() >= SLOW_WRITE {
+                        if stats.mark_slow() {
+                            let notice = slow_notice(username, slow_grace, notices.lang);
+                            let _ = writer.write_frame(&Frame::from(notice)).await;
+                        }
+                        if stats.slow_for().is_some_and(|slow| slow >= slow_grace) {
+                            println!("{}", tr!("server.slow_disconnect", user = username));
+                            return Ok(());
+                        }
+                    } else if rx.is_empty() && route.is_empty() {
+                        stats.clear_slow();
+                    }
+                }
+                // 连接已不可写，由调用方拆除会话
+                Err(CodecError::Io(e)) => return Err(CodecError::Io(e)),
+                Err(e) => {
+                    eprintln!("{}", tr!("server.send_failed", error = e));
+                    if let (CodecError::FrameTooLarge(len), Some(msg)) = (e, frame.message()) {
+                        self.dead_letters.push(msg.clone(), Reason::TooLarge(len));
                     }
-                } else {
-                    eprintln!("消息序列化失败");
                 }
             }
-        });
+        }
+        let _ = writer.shutdown().await;
+        Ok(())
+    }
 
-        // **主任务（接收客户端消息并处理）**
-        self.handle_receive(username, &mut reader).await
+    /// 连接结束后的清理：移出在线用户表、记录最后在线时间并输出连接统计
+    fn unregister(&self, session: &Session) {
+        let online_for = session.connected_at.elapsed();
+        let (user, online) = (&session.username, humanize_duration(online_for));
+        let stats = session.stats.summary(i18n::lang());
+        let logged_out = matches!(session.departure, Departure::Left(_));
+        if logged_out {
+            println!(
+                "{}",
+                tr!("server.left", user = user, online = online, stats = stats)
+            );
+        } else {
+            println!(
+                "{}",
+                tr!(
+                    "server.disconnected",
+                    user = user,
+                    online = online,
+                    stats = stats
+                )
+            );
+        }
+        self.release(&session.username, &session.stats, logged_out);
+    }
+
+    /// 把连接移出在线用户表并记录最后在线时间及是否为主动退出
+    fn release(&self, username: &ArcString, stats: &Arc<SessionStats>, logged_out: bool) {
+        // 同名用户重新登录后在线用户表中已是新的连接，只移除属于本连接的条目
+        let removed = self
+            .online_users
+            .remove_if(username, |_, user| Arc::ptr_eq(&user.stats, stats));
+        let was_online = removed.is_some();
+        if let Some((_, user)) = removed {
+            // 免打扰期间暂存、尚未补发的消息放入死信队列，由管理员重新投递；
+            // 死信按接收方重新投递，聊天室消息改为发给该用户
+            for msg in user.presence.lock().unwrap().take_queued() {
+                self.dead_letters
+                    .push(msg.with_to(username.get()), Reason::Disconnected);
+            }
+            self.rebuild_roster();
+            self.push_roster(RosterChange::Left, username);
+        }
+        if let Some(mut user) = self.users.get_mut(username) {
+            user.last_seen = Some(Local::now());
+            user.logged_out = logged_out;
+            if was_online {
+                self.mark_rooms_seen(&mut user);
+            }
+        }
+        self.save_user(username);
+    }
+
+    /// 将用户数据写入持久化存储，失败时仅记录日志
+    fn save_user(&self, username: &ArcString) {
+        let Some(record) = self.users.get(username).map(|user| user.clone()) else {
+            return;
+        };
+        if let Err(e) = self.storage.save_user(&username.get(), &record) {
+            eprintln!(
+                "{}",
+                tr!("server.save_user_failed", user = username, error = e)
+            );
+        }
+    }
+
+    /// 以 `lang` 描述用户离线时长，例如 "最后在线: 2 小时 3 分前"；用户隐藏在线状态时返回 `None`
+    fn last_seen_text(&self, username: &ArcString, lang: Lang) -> Option<String> {
+        let user = self.users.get(username)?;
+        if !user.profile.privacy.show_status {
+            return None;
+        }
+        let last_seen = user.last_seen?;
+        let elapsed = (Local::now() - last_seen).to_std().unwrap_or_default();
+        let elapsed = humanize_duration_in(lang, elapsed);
+        Some(match user.logged_out {
+            true => tr!(lang => "server.last_seen_logged_out", elapsed = elapsed),
+            false => tr!(lang => "server.last_seen", elapsed = elapsed),
+        })
     }
 
     /// 处理客户端连接中的消息接收，根据消息转发逻辑进行处理
     async fn handle_receive<'a>(
         &self,
-        username: ArcString,
+        session: &mut Session,
         stream: ReadStream<'a>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut buf = [0u8; 1024];
-
+    ) -> Result<(), CodecError> {
+        let username = session.username.clone();
         loop {
-            let len = stream.read(&mut buf).await?;
-            if len == 0 {
+            // 读取下一帧（先处理注册完成前暂存的帧）；若配置了空闲超时，最多等待到超时时刻；
+            // 服务器要求断开时立即结束
+            let early = session.early.pop_front();
+            let next = async {
+                match early {
+                    Some(frame) => Ok(Some(frame)),
+                    None => stream.read_frame::<Frame>().await,
+                }
+            };
+            let remaining = self
+                .config
+                .idle_timeout
+                .map(|timeout| timeout.saturating_sub(session.idle_for()));
+            let idle = async move {
+                match remaining {
+                    Some(remaining) => tokio::time::sleep(remaining).await,
+                    None => future::pending().await,
+                }
+            };
+            let closing = session.closing.clone();
+            let result = tokio::select! {
+                result = next => result,
+                _ = idle => {
+                    if let Some(timeout) = self.config.idle_timeout {
+                        self.disconnect_idle(session, timeout).await;
+                    }
+                    break;
+                }
+                _ = closing.notified() => break,
+            };
+            session
+                .stats
+                .bytes_in
+                .store(stream.bytes_read(), Ordering::Relaxed);
+
+            let msg = match result {
                 // 客户端关闭连接
-                break;
-            }
+                Ok(None) => break,
+                // 客户端主动退出
+                Ok(Some(Frame::Control(Control::Goodbye { message }))) => {
+                    let message = message.filter(|text| !text.trim().is_empty());
+                    session.departure = Departure::Left(message);
+                    break;
+                }
+                // 客户端发来的消息不应带有路由记录；发送者一律改写为本连接的用户名，
+                // 客户端无法冒充其他用户（或 "Server"），路由队列也按真实的发送者轮转
+                Ok(Some(Frame::Message(msg))) => {
+                    session.touch();
+                    sanitized(msg.with_trace(Vec::new()).with_from(username.clone()))
+                }
+                Ok(Some(Frame::Control(control))) => {
+                    session.touch();
+                    self.handle_control(session, control).await;
+                    continue;
+                }
+                Err(CodecError::Decode(e)) => {
+                    session.touch();
+                    session.stats.parse_errors.fetch_add(1, Ordering::Relaxed);
+                    eprintln!("{}", tr!("server.parse_failed", error = format!("{:?}", e)));
+                    continue;
+                }
+                Err(CodecError::FrameTooLarge(len)) => {
+                    let text = tr!(session.lang => "server.too_large", len = len);
+                    self.reject(session, ErrorCode::MessageTooLarge, None, None, text)
+                        .await;
+                    return Err(CodecError::FrameTooLarge(len));
+                }
+                Err(e) => return Err(e),
+            };
+            let received_at = Timestamp::now();
 
-            // 将读取的数据转换为字符串，并尝试解析为 Message
-            let json_msg = String::from_utf8_lossy(&buf[..len]);
-            match serde_json::from_str::<Message>(&json_msg) {
-                Ok(msg) => {
+            // 内容过长的消息（包括服务器指令）不转发，连接保持不变；
+            // 在去重之前检查，缩短内容后以同一编号重发的消息不会被当作重复丢弃
+            if let Some(max) = self.config.max_message_len {
+                let len = msg.content().len();
+                let limit = match msg.kind() {
+                    MessageKind::Encrypted => max.div_ceil(3) * 4 + ENCRYPTED_OVERHEAD,
+                    MessageKind::Text | MessageKind::Action => max,
+                };
+                if len > limit {
                     println!(
-                        "[{}] {} 发送消息给 {}: {}",
-                        msg.time_stamp(),
-                        msg.from(),
-                        msg.to(),
-                        msg.content()
+                        "{}",
+                        tr!("server.content_too_large_log", user = username, len = len)
                     );
+                    let text =
+                        tr!(session.lang => "server.content_too_large", len = len, max = max);
+                    let target = ArcString::new(msg.to().to_string());
+                    self.reject(
+                        session,
+                        ErrorCode::MessageTooLarge,
+                        Some(&target),
+                        msg.id(),
+                        text,
+                    )
+                    .await;
+                    continue;
+                }
+            }
 
-                    if msg.to == "/list" {
-                        let online_list: Vec<String> = self
-                            .online_users
-                            .iter()
-                            .map(|entry| entry.key().get())
-                            .collect();
-                        // 构造美观的响应消息
-                        let response = if online_list.is_empty() {
-                            "当前无其他在线用户".to_string()
-                        } else {
-                            format!(
-                                "当前在线用户 (共{}人):\n  › {}",
-                                online_list.len(),
-                                online_list.join("\n  › ") // 用箭头符号美化列表
-                            )
-                        };
+            // 客户端重连后可能重发已经送达的消息
+            if let Some(id) = msg.id() {
+                if !self.seen.lock().unwrap().insert((username.clone(), id)) {
+                    println!("{}", tr!("server.duplicate", user = username, id = id));
+                    continue;
+                }
+            }
 
-                        // 发送给请求者（原消息发送者）
-                        if let Some(sender_tx) = self.online_users.get(&username) {
-                            let list_msg = Message::new(
-                                ArcString::new("Server".to_string()),
-                                username.get(),
-                                response, // 使用格式化后的内容
-                            );
-                            let _ = sender_tx.send(list_msg).await;
-                        }
-                        continue; // 跳过后续转发逻辑
+            // 机器人按权限范围与单独的限流检查每条消息（包括服务器指令）
+            let mut flagged = None;
+            if session.role == Role::Bot {
+                if let Some((code, text)) = self.bot_refusal(session, &msg) {
+                    self.reject(session, code, None, msg.id(), text).await;
+                    continue;
+                }
+            } else if !matches!(Recipient::parse(msg.to()), Recipient::Command(_)) {
+                // 反垃圾规则只检查聊天消息，被限流的发送者已收到错误
+                match self.screen_spam(session, &msg).await {
+                    Screened::Pass => {}
+                    Screened::Flagged(rule) => flagged = Some(rule),
+                    Screened::Rejected => continue,
+                }
+            }
+
+            session.stats.messages_sent.fetch_add(1, Ordering::Relaxed);
+
+            if self.config.log_messages {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.message_log",
+                        time = msg.time_stamp(),
+                        from = msg.from(),
+                        to = msg.to(),
+                        content = msg.content(),
+                    )
+                );
+            }
+
+            // 发给 `#` 开头地址的消息转给聊天室的其他成员
+            if self.route_to_room(session, &msg, flagged).await {
+                continue;
+            }
+            // 发给 `tg:` 地址的消息转发到桥接的 Telegram 群组
+            #[cfg(feature = "bridges")]
+            if self.route_to_telegram(session, &msg).await {
+                continue;
+            }
+            // 发给 `xmpp:` 地址的消息经 XMPP 网关发给 Jabber 用户
+            #[cfg(feature = "bridges")]
+            if self.route_to_xmpp(session, &msg).await {
+                continue;
+            }
+
+            // 按接收者地址分发：服务器指令、本服务器用户，或经服务器联邦转发的 `用户@服务器`
+            let msg = match Recipient::parse(msg.to()) {
+                Recipient::Command(_) => {
+                    self.handle_command(session, &msg).await;
+                    continue; // 跳过后续转发逻辑
+                }
+                Recipient::Local(_) => msg,
+                // 名字本身带 `@` 的已注册用户仍按本地用户处理
+                Recipient::Remote { .. }
+                    if self
+                        .users
+                        .contains_key(&ArcString::new(msg.to().to_string())) =>
+                {
+                    msg
+                }
+                Recipient::Remote { user, host } => {
+                    let (user, host) = (user.to_string(), host.to_string());
+                    match self
+                        .route_remote(session, msg, &user, &host, received_at)
+                        .await
+                    {
+                        Some(msg) => msg,
+                        None => continue,
                     }
-                    // 构造目标用户名的 ArcString
-                    let recipient = ArcString::new(msg.to().to_string());
-                    // 查找目标用户的发送者
-                    if let Some(tx) = self.online_users.get(&recipient) {
-                        // 将消息发送给目标用户
-                        let _ = tx.send(msg).await;
-                    } else {
-                        // 若目标用户不在线，给发送者返回提示信息
-                        if let Some(sender_tx) = self.online_users.get(&username) {
-                            let tip = Message::new(
-                                ArcString::new("Server".to_string()),
-                                username.get(),
-                                format!("用户 {} 不在线", msg.to()),
-                            );
-                            let _ = sender_tx.send(tip).await;
+                }
+            };
+            // 构造目标用户名的 ArcString
+            let recipient = ArcString::new(msg.to().to_string());
+            let client_id = msg.id();
+            // 触发反垃圾规则或有待处理举报的发送者发给已知用户的消息隔离待审核，不写入消息日志
+            if self.users.contains_key(&recipient) {
+                if let Some(cause) = self.quarantine_cause(&username, flagged) {
+                    self.quarantine(session, msg, cause).await;
+                    continue;
+                }
+            }
+            // 发给已知用户的消息写入消息日志，供其断线重连后补发
+            let msg = if self.users.contains_key(&recipient) {
+                let msg = self.log_message(msg);
+                self.trace_received(&msg, received_at);
+                msg
+            } else {
+                msg
+            };
+            // 查找目标用户的发送者
+            let target = self
+                .online_users
+                .get(&recipient)
+                .map(|user| (user.outbox(&recipient), user.presence.clone()));
+            if let Some((outbox, presence)) = target {
+                // 记录会话双方，任意一方的连接异常中断时通知另一方
+                session.partners.lock().unwrap().insert(recipient.clone());
+                outbox.partners.lock().unwrap().insert(username.clone());
+                // 目标处于免打扰状态时暂存消息，否则直接发送
+                let (auto_reply, deliver) = {
+                    let mut presence = presence.lock().unwrap();
+                    let auto_reply = presence.auto_reply(&username, session.lang);
+                    let state = match presence.dnd {
+                        true => DeliveryState::Queued,
+                        false => DeliveryState::Sent,
+                    };
+                    self.receipts.record(&username, &recipient, &msg, state);
+                    let event = match presence.dnd {
+                        true => TraceEvent::Queued,
+                        false => TraceEvent::Routed,
+                    };
+                    self.trace_event(msg.from(), msg.id(), event);
+                    if presence.dnd {
+                        if let Some(evicted) = presence.queue(msg) {
+                            self.dead_letters.push(evicted, Reason::HeldOverflow);
                         }
+                        (auto_reply, None)
+                    } else {
+                        (auto_reply, Some(msg))
+                    }
+                };
+                if let Some(msg) = deliver {
+                    if let Err((msg, reason)) = self.deliver(&outbox, msg).await {
+                        let tip = tr!(session.lang => "server.undelivered",
+                            user = msg.to(),
+                            reason = reason.describe(session.lang),
+                        );
+                        let code = match reason {
+                            Reason::QueueFull | Reason::HeldOverflow => ErrorCode::RateLimited,
+                            Reason::TooLarge(_) => ErrorCode::MessageTooLarge,
+                            Reason::Disconnected | Reason::SlowConsumer => ErrorCode::OfflineQueued,
+                        };
+                        self.dead_letters.push(msg, reason);
+                        self.reject(session, code, Some(&recipient), client_id, tip)
+                            .await;
                     }
                 }
-                Err(e) => {
-                    eprintln!("解析 JSON 消息失败: {:?}", e);
+                if let Some(text) = auto_reply {
+                    let text = tr!(session.lang => "server.auto_reply", text = text);
+                    self.send_as(&recipient, &username, text).await;
                 }
+            } else if self.users.contains_key(&recipient) {
+                // 目标用户不在线：消息已写入消息日志，给发送者返回提示（附带最后在线时间）
+                self.receipts
+                    .record(&username, &recipient, &msg, DeliveryState::Queued);
+                self.trace_event(msg.from(), msg.id(), TraceEvent::Queued);
+                let tip = match self.last_seen_text(&recipient, session.lang) {
+                    Some(last_seen) => tr!(session.lang => "server.offline_since",
+                        user = msg.to(),
+                        last_seen = last_seen,
+                    ),
+                    None => tr!(session.lang => "server.offline", user = msg.to()),
+                };
+                self.reject(
+                    session,
+                    ErrorCode::OfflineQueued,
+                    Some(&recipient),
+                    client_id,
+                    tip,
+                )
+                .await;
+            } else {
+                let tip = tr!(session.lang => "server.unknown_user", user = msg.to());
+                self.reject(
+                    session,
+                    ErrorCode::UnknownUser,
+                    Some(&recipient),
+                    client_id,
+                    tip,
+                )
+                .await;
             }
         }
 
-        println!("用户 {} 断开连接", username.get());
-        self.online_users.remove(&username);
         Ok(())
     }
+
+    /// 为消息分配编号并写入消息日志，写入失败时仅记录日志
+    fn log_message(&self, msg: Message) -> Message {
+        let id = self.last_message_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let msg = msg.with_id(id);
+        if let Err(e) = self.storage.append_message(&msg) {
+            eprintln!("{}", tr!("server.log_write_failed", error = e));
+        }
+        msg
+    }
+
+    /// 告知客户端当前最新的消息编号；重连时补发编号大于 `resume_from` 的消息
+    async fn resume(&self, session: &Session, resume_from: Option<u64>) {
+        let Some(tx) = self
+            .online_users
+            .get(&session.username)
+            .map(|user| user.tx.clone())
+        else {
+            return;
+        };
+        let last_id = self.last_message_id.load(Ordering::Relaxed);
+        let _ = tx.send(Control::Seq { last_id }.into()).await;
+        let Some(after) = resume_from else {
+            return;
+        };
+        let mut missed = match self.storage.messages_after(&session.username.get(), after) {
+            Ok(missed) => missed,
+            Err(e) => {
+                eprintln!("{}", tr!("server.log_read_failed", error = e));
+                return;
+            }
+        };
+        let skipped = missed.len().saturating_sub(MAX_REPLAY);
+        missed.drain(..skipped);
+        let dropped = self.storage.dropped_through(&session.username.get());
+        if skipped > 0 || dropped > after {
+            let text = tr!(
+                session.lang => "server.history_truncated",
+                count = missed.len(),
+                from = missed.first().and_then(Message::id).unwrap_or(last_id)
+            );
+            self.reject(session, ErrorCode::HistoryTruncated, None, None, text)
+                .await;
+        }
+        if !missed.is_empty() {
+            println!(
+                "{}",
+                tr!(
+                    "server.replayed",
+                    user = session.username,
+                    count = missed.len()
+                )
+            );
+        }
+        for msg in missed {
+            let _ = tx.send(msg.into()).await;
+        }
+    }
+
+    /// 把来自其他服务器的消息投递给本地用户：免打扰时暂存，不在线时留待重连补发，
+    /// 投递失败时放入死信队列
+    async fn deliver_local(&self, recipient: &ArcString, msg: Message) {
+        let Some((outbox, presence)) = self
+            .online_users
+            .get(recipient)
+            .map(|user| (user.outbox(recipient), user.presence.clone()))
+        else {
+            self.trace_event(msg.from(), msg.id(), TraceEvent::Queued);
+            return;
+        };
+        let msg = {
+            let mut presence = presence.lock().unwrap();
+            if presence.dnd {
+                self.trace_event(msg.from(), msg.id(), TraceEvent::Queued);
+                if let Some(evicted) = presence.queue(msg) {
+                    self.dead_letters.push(evicted, Reason::HeldOverflow);
+                }
+                return;
+            }
+            msg
+        };
+        self.trace_event(msg.from(), msg.id(), TraceEvent::Routed);
+        if let Err((msg, reason)) = self.deliver(&outbox, msg).await {
+            self.dead_letters.push(msg, reason);
+        }
+    }
+
+    /// 按接收者 `recipient` 的通知偏好为消息附上提示建议（见 [`crate::notify`]）
+    fn with_notify_hint(&self, recipient: &ArcString, msg: Message) -> Message {
+        match self.notify_hint(recipient, &msg) {
+            Some(hint) => msg.with_notify(hint),
+            None => msg,
+        }
+    }
+
+    /// 按接收者 `recipient` 的通知偏好计算 `msg` 的提示建议，未知用户为 `None`
+    fn notify_hint(&self, recipient: &ArcString, msg: &Message) -> Option<NotifyHint> {
+        let user = self.users.get(recipient)?;
+        let now = Local::now().time();
+        Some(user.profile.notify.hint(&recipient.get(), msg, now))
+    }
+
+    /// 把消息放入接收方的路由队列；队列已满时标记接收方过慢，并按队列已满策略处理
+    ///
+    /// # 返回值
+    /// 投递失败时交还消息及失败原因，由调用方决定是否放入死信队列
+    async fn deliver(&self, outbox: &Outbox, msg: Message) -> Result<(), (Message, Reason)> {
+        let msg = self.with_notify_hint(&outbox.name, msg);
+        let msg = match outbox.route.push(msg) {
+            Ok(()) => return Ok(()),
+            Err(Rejected::Closed(msg)) => return Err((msg, Reason::Disconnected)),
+            Err(Rejected::Full(msg)) => msg,
+        };
+        if self.slow_consumer(outbox) {
+            return Err((msg, Reason::SlowConsumer));
+        }
+        match self.config.queue_full {
+            QueueFullPolicy::Drop => Err((msg, Reason::QueueFull)),
+            QueueFullPolicy::Wait => {
+                // 最多等到宽限期结束，避免发送方被一直阻塞
+                match outbox
+                    .route
+                    .push_timeout(msg, self.remaining_grace(outbox))
+                    .await
+                {
+                    Ok(()) => Ok(()),
+                    Err(Rejected::Full(msg)) => {
+                        self.slow_consumer(outbox);
+                        Err((msg, Reason::SlowConsumer))
+                    }
+                    Err(Rejected::Closed(msg)) => Err((msg, Reason::Disconnected)),
+                }
+            }
+        }
+    }
+
+    /// 转发循环：按发送方轮流从路由队列取出消息放入发送通道；
+    /// 通道已满时标记接收方过慢，超过宽限期仍无空间则断开连接
+    async fn forward_loop(&self, outbox: &Outbox) {
+        loop {
+            let msg = outbox.route.pop().await;
+            match self.reserve(outbox).await {
+                Ok(permit) => permit.send(msg.into()),
+                Err(reason) => {
+                    self.dead_letters.push(msg, reason);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// 在接收方的发送通道中预留一个位置；通道已满时标记接收方过慢，最多等到宽限期结束
+    async fn reserve<'a>(&self, outbox: &'a Outbox) -> Result<mpsc::Permit<'a, Outgoing>, Reason> {
+        match outbox.tx.try_reserve() {
+            Ok(permit) => Ok(permit),
+            Err(mpsc::error::TrySendError::Closed(())) => Err(Reason::Disconnected),
+            Err(mpsc::error::TrySendError::Full(())) => {
+                if self.slow_consumer(outbox) {
+                    return Err(Reason::SlowConsumer);
+                }
+                let remaining = self.remaining_grace(outbox);
+                match tokio::time::timeout(remaining, outbox.tx.reserve()).await {
+                    Ok(Ok(permit)) => Ok(permit),
+                    Ok(Err(_)) => Err(Reason::Disconnected),
+                    Err(_) => {
+                        self.slow_consumer(outbox);
+                        Err(Reason::SlowConsumer)
+                    }
+                }
+            }
+        }
+    }
+
+    /// 接收方被判定过慢之前还剩的宽限时间
+    fn remaining_grace(&self, outbox: &Outbox) -> Duration {
+        let slow_for = outbox.stats.slow_for().unwrap_or_default();
+        self.config.slow_grace.saturating_sub(slow_for)
+    }
+
+    /// 通知本次连接中与该用户互发过消息且仍在线的用户：主动退出时转告告别语，
+    /// 连接因写入失败中断时提醒最近的消息可能未送达
+    async fn notify_partners(&self, session: &Session) {
+        let partners: Vec<ArcString> = session.partners.lock().unwrap().drain().collect();
+        let user = &session.username;
+        for partner in partners {
+            let lang = self.lang_of(&partner);
+            let content = match &session.departure {
+                Departure::Left(Some(message)) => {
+                    tr!(lang => "server.partner_left_message", user = user, message = message)
+                }
+                Departure::Left(None) => tr!(lang => "server.partner_left", user = user),
+                Departure::Dropped => tr!(lang => "server.partner_lost", user = user),
+            };
+            self.reply(&partner, content).await;
+        }
+    }
+
+    /// 关闭已结束连接的路由队列，未转发的消息进入死信队列
+    fn abandon(&self, route: &RouteQueue) {
+        for msg in route.close() {
+            self.dead_letters.push(msg, Reason::Disconnected);
+        }
+    }
+
+    /// 接收方的队列已满：首次发现时提醒该用户，持续超过宽限期则断开其连接
+    ///
+    /// # 返回值
+    /// 接收方已被断开时返回 `true`
+    fn slow_consumer(&self, outbox: &Outbox) -> bool {
+        let grace = self.config.slow_grace;
+        if outbox.stats.mark_slow() {
+            println!("{}", tr!("server.slow", user = outbox.name));
+            let notice = slow_notice(&outbox.name, grace, outbox.lang);
+            let _ = outbox.tx.try_send(notice.into());
+        }
+        if outbox.stats.slow_for().is_some_and(|slow| slow >= grace) {
+            println!(
+                "{}",
+                tr!(
+                    "server.slow_timeout",
+                    user = outbox.name,
+                    grace = humanize_duration(grace),
+                )
+            );
+            outbox.closing.notify_one();
+            return true;
+        }
+        false
+    }
+
+    /// 告知请求者请求未能完成：声明了 `errors` 特性的客户端收到错误帧，其他客户端收到文字提示
+    async fn reject(
+        &self,
+        session: &Session,
+        code: ErrorCode,
+        target: Option<&ArcString>,
+        id: Option<u64>,
+        text: String,
+    ) {
+        if !session.has_feature(FEATURE_ERRORS) {
+            self.reply(&session.username, text).await;
+            return;
+        }
+        let error = ErrorFrame {
+            code,
+            target: target.map(ArcString::get),
+            id,
+            message: text,
+        };
+        let tx = self
+            .online_users
+            .get(&session.username)
+            .map(|user| user.tx.clone());
+        if let Some(tx) = tx {
+            let _ = tx.send(Control::Error(error).into()).await;
+        }
+    }
+
+    /// 以 "Server" 身份向在线用户 `username` 发送一条提示消息
+    async fn reply(&self, username: &ArcString, content: String) {
+        self.send_as(&ArcString::new("Server".to_string()), username, content)
+            .await;
+    }
+
+    /// 以 `from` 的身份向在线用户 `username` 发送一条消息
+    async fn send_as(&self, from: &ArcString, username: &ArcString, content: String) {
+        let tx = self.online_users.get(username).map(|user| user.tx.clone());
+        if let Some(tx) = tx {
+            let msg = Message::new(from.clone(), username.get(), content);
+            let _ = tx.send(msg.into()).await;
+        }
+    }
+
+    /// 向尚未完成注册的连接发出一个挑战，并在 [`POW_TIMEOUT`] 内等待应答
+    ///
+    /// 客户端在应答之前发出的帧暂存到 `early`（最多 [`MAX_EARLY_FRAMES`] 个），注册完成后再处理。
+    ///
+    /// # 返回值
+    /// 收到的应答（`Proof` 或 `IdentityProof`，由调用方检查类型与内容）；超时、暂存过多或
+    /// 连接在应答前关闭时返回 `None`（应拒绝该连接）
+    async fn ask(
+        &self,
+        reader: ReadStream<'_>,
+        writer: &mut FrameWriter<BoxWriter>,
+        question: Control,
+        early: &mut VecDeque<Frame>,
+    ) -> Result<Option<Control>, Box<dyn std::error::Error>> {
+        writer.write_frame(&Frame::from(question)).await?;
+        let answer = async {
+            loop {
+                match reader.read_frame::<Frame>().await? {
+                    Some(Frame::Control(
+                        answer @ (Control::Proof { .. } | Control::IdentityProof { .. }),
+                    )) => return Ok(Some(answer)),
+                    // 求解期间客户端可能已开始发送心跳
+                    Some(Frame::Control(Control::Ping { .. })) => continue,
+                    Some(frame) if early.len() < MAX_EARLY_FRAMES => early.push_back(frame),
+                    _ => return Ok::<_, CodecError>(None),
+                }
+            }
+        };
+        match tokio::time::timeout(POW_TIMEOUT, answer).await {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// 发出工作量证明挑战
+    ///
+    /// # 返回值
+    /// 客户端在 [`POW_TIMEOUT`] 内给出有效应答时返回 `true`
+    async fn challenge(
+        &self,
+        reader: ReadStream<'_>,
+        writer: &mut FrameWriter<BoxWriter>,
+        difficulty: u8,
+        early: &mut VecDeque<Frame>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let nonce = pow::new_nonce()?;
+        let challenge = Control::Challenge {
+            nonce: nonce.clone(),
+            difficulty,
+        };
+        Ok(matches!(
+            self.ask(reader, writer, challenge, early).await?,
+            Some(Control::Proof { solution }) if pow::verify(&nonce, difficulty, solution)
+        ))
+    }
+
+    /// 发出身份挑战，要求客户端证明持有公钥 `key` 对应的私钥
+    ///
+    /// # 返回值
+    /// 客户端在 [`POW_TIMEOUT`] 内给出有效签名时返回 `true`
+    async fn prove_identity(
+        &self,
+        reader: ReadStream<'_>,
+        writer: &mut FrameWriter<BoxWriter>,
+        username: &str,
+        key: &str,
+        early: &mut VecDeque<Frame>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let nonce = pow::new_nonce()?;
+        let challenge = Control::IdentityChallenge {
+            nonce: nonce.clone(),
+        };
+        Ok(matches!(
+            self.ask(reader, writer, challenge, early).await?,
+            Some(Control::IdentityProof { sig }) if identity::verify_login(key, username, &nonce, &sig)
+        ))
+    }
+
+    /// 处理客户端发送的控制帧
+    async fn handle_control(&self, session: &Session, control: Control) {
+        match control {
+            Control::Ping { seq } => {
+                if let Some(user) = self.online_users.get(&session.username) {
+                    let _ = user.tx.send(Control::Pong { seq }.into()).await;
+                }
+            }
+            Control::Pong { .. }
+            | Control::Seq { .. }
+            | Control::Error(_)
+            | Control::Challenge { .. }
+            | Control::Proof { .. }
+            | Control::IdentityChallenge { .. }
+            | Control::IdentityProof { .. }
+            | Control::Goodbye { .. }
+            | Control::PeerChallenge { .. }
+            | Control::PeerAuth { .. }
+            | Control::PeerWelcome { .. }
+            | Control::Relay { .. }
+            | Control::TraceReport { .. }
+            | Control::Presence { .. }
+            | Control::Roster { .. }
+            | Control::RosterUpdate { .. }
+            | Control::ServerShutdown { .. } => {}
+            Control::Read { id } => self.receipts.read(&session.username, id),
+            control @ (Control::FileOffer { .. }
+            | Control::FileAnswer { .. }
+            | Control::FileResume { .. }
+            | Control::FileCandidates { .. }
+            | Control::FileChunk { .. }
+            | Control::FileDone { .. }
+            | Control::FileCancel { .. }) => self.handle_transfer(session, control).await,
+            control @ Control::KeyExchange { .. } => {
+                self.handle_key_exchange(session, control).await
+            }
+        }
+    }
+
+    /// 因空闲超时断开连接：记录原因并向用户发送最后一条通知
+    async fn disconnect_idle(&self, session: &Session, timeout: Duration) {
+        let secs = timeout.as_secs();
+        println!(
+            "{}",
+            tr!(
+                "server.idle",
+                user = session.username,
+                addr = session.peer_addr,
+                secs = secs,
+            )
+        );
+        let notice = tr!(session.lang => "server.idle_notice", secs = secs);
+        self.reply(&session.username, notice).await;
+    }
+}
+
+/// 已注册连接的守卫：连接处理任务未正常走完清理流程（panic 或被强制结束）时，
+/// 在任务被销毁时把该连接移出在线用户表，避免留下无法投递的在线条目
+struct OnlineGuard<'a> {
+    server: &'a Server,
+    username: ArcString,
+    peer_addr: SocketAddr,
+    stats: Arc<SessionStats>,
+    route: Arc<RouteQueue>,
+    /// 正常清理后置为 `false`
+    armed: bool,
+}
+
+impl Drop for OnlineGuard<'_> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        eprintln!(
+            "{}",
+            tr!(
+                "server.handler_aborted",
+                user = self.username,
+                addr = self.peer_addr,
+                stats = self.stats.summary(i18n::lang()),
+            )
+        );
+        self.server.release(&self.username, &self.stats, false);
+        self.server.abandon(&self.route);
+    }
+}
+
+/// 取出 panic 携带的文本信息
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || tr!("server.no_panic_message"),
+            |message| message.to_string(),
+        ),
+    }
+}
+
+/// 不能用作用户名的名字：空名字、系统通知使用的 "Server"、`#` 开头的聊天室地址，
+/// 以及含有控制字符或 `/` 的名字
+fn is_reserved_name(name: &str) -> bool {
+    name.is_empty()
+        || name.eq_ignore_ascii_case("Server")
+        || name.starts_with('#')
+        || name
+            .chars()
+            .any(|c| c == '/' || c.is_control() || is_dangerous(c))
+}
+
+/// 握手帧中的语言，未给出或无法识别时为服务器进程的语言
+fn hello_lang(hello: &Hello) -> Lang {
+    hello
+        .lang
+        .as_deref()
+        .and_then(Lang::parse)
+        .unwrap_or_else(i18n::lang)
+}
+
+/// 把接受的连接拆分为读写半部：TCP 连接直接拆分，WebSocket 连接先完成握手再桥接为字节流
+async fn split_stream(stream: TcpStream, websocket: bool) -> io::Result<(BoxReader, BoxWriter)> {
+    #[cfg(feature = "websocket")]
+    if websocket {
+        return websocket::accept(stream).await;
+    }
+    #[cfg(not(feature = "websocket"))]
+    let _ = websocket;
+    let (reader, writer) = stream.into_split();
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// 在注册之前拒绝连接（超过来源 IP 的限制或被插件拒绝）：读取握手帧以确定编码与语言后，
+/// 以 `code` 与 `describe` 生成的说明告知原因
+///
+/// 最多花费 [`TURN_AWAY_TIMEOUT`]，对端迟迟不发送握手帧时直接关闭。
+async fn turn_away(
+    stream: TcpStream,
+    websocket: bool,
+    code: ErrorCode,
+    describe: impl FnOnce(Lang) -> String,
+) {
+    let refused = async {
+        let (reader, writer) = split_stream(stream, websocket).await?;
+        let Some(mut reader) = FrameReader::detect(reader).await? else {
+            return Ok(());
+        };
+        let mut writer = FrameWriter::new(writer, reader.codec());
+        let Some(registration) = reader.read_frame::<Registration>().await? else {
+            return Ok(());
+        };
+        let hello = registration.into_hello();
+        let text = describe(hello_lang(&hello));
+        refuse(&mut writer, &hello, code, None, text).await
+    };
+    let _ = tokio::time::timeout(TURN_AWAY_TIMEOUT, refused).await;
+}
+
+/// 在注册完成之前拒绝连接：告知原因后关闭写入端
+///
+/// 声明了 `errors` 特性的客户端收到错误帧，其他客户端收到 "Server" 发来的文字提示。
+async fn refuse(
+    writer: &mut FrameWriter<BoxWriter>,
+    hello: &Hello,
+    code: ErrorCode,
+    target: Option<&str>,
+    text: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frame = if hello.features.iter().any(|f| f == FEATURE_ERRORS) {
+        Frame::Control(Control::Error(ErrorFrame {
+            code,
+            target: target.map(str::to_string),
+            id: None,
+            message: text,
+        }))
+    } else {
+        Frame::from(Message::new(
+            ArcString::new("Server".to_string()),
+            hello.name.clone(),
+            text,
+        ))
+    };
+    writer.write_frame(&frame).await?;
+    // 对端可能已先行关闭连接，关闭写入端失败无需报告
+    let _ = writer.shutdown().await;
+    Ok(())
+}
+
+/// 清除消息内容中的终端控制序列与危险字符，有改动时记录日志；端到端加密的消息是密文，原样保留
+fn sanitized(msg: Message) -> Message {
+    if msg.kind() == MessageKind::Encrypted {
+        return msg;
+    }
+    let clean = match sanitize(msg.content()) {
+        Cow::Borrowed(_) => return msg,
+        Cow::Owned(clean) => clean,
+    };
+    println!(
+        "{}",
+        tr!("server.sanitized", user = msg.from(), to = msg.to())
+    );
+    msg.with_content(clean)
+}
+
+/// 以该用户的语言 `lang` 提醒接收过慢的用户：宽限期内仍未恢复将被断开
+fn slow_notice(username: &ArcString, grace: Duration, lang: Lang) -> Message {
+    let grace = humanize_duration_in(lang, grace);
+    Message::new(
+        ArcString::new("Server".to_string()),
+        username.get(),
+        tr!(lang => "server.slow_notice", grace = grace),
+    )
 }
 
 /// 为了在任务中低成本克隆 Server，手动实现 Clone（只克隆内部 Arc）
@@ -221,6 +2625,173 @@ impl Clone for Server {
     fn clone(&self) -> Self {
         Server {
             online_users: Arc::clone(&self.online_users),
+            roster: Arc::clone(&self.roster),
+            roster_rebuild: Arc::clone(&self.roster_rebuild),
+            snapshot: Arc::clone(&self.snapshot),
+            notices: self.notices.clone(),
+            roster_updates: self.roster_updates.clone(),
+            users: Arc::clone(&self.users),
+            rooms: Arc::clone(&self.rooms),
+            storage: Arc::clone(&self.storage),
+            dead_letters: Arc::clone(&self.dead_letters),
+            last_message_id: Arc::clone(&self.last_message_id),
+            seen: Arc::clone(&self.seen),
+            shutdown: Arc::clone(&self.shutdown),
+            handler_panics: Arc::clone(&self.handler_panics),
+            accepts: Arc::clone(&self.accepts),
+            fd_headroom: Arc::clone(&self.fd_headroom),
+            invites: Arc::clone(&self.invites),
+            room_invites: Arc::clone(&self.room_invites),
+            ip_limits: Arc::clone(&self.ip_limits),
+            vhosts: Arc::clone(&self.vhosts),
+            federation: self.federation.clone(),
+            transfers: Arc::clone(&self.transfers),
+            bot_limits: Arc::clone(&self.bot_limits),
+            #[cfg(feature = "chaos")]
+            chaos: self.chaos.clone(),
+            bot_commands: Arc::clone(&self.bot_commands),
+            #[cfg(feature = "bridges")]
+            telegram: self.telegram.clone(),
+            #[cfg(feature = "bridges")]
+            xmpp: self.xmpp.clone(),
+            jobs: Arc::clone(&self.jobs),
+            spam: self.spam.clone(),
+            audit: Arc::clone(&self.audit),
+            receipts: Arc::clone(&self.receipts),
+            moderation: Arc::clone(&self.moderation),
+            tracer: Arc::clone(&self.tracer),
+            maintenance: Arc::clone(&self.maintenance),
+            plugins: Arc::clone(&self.plugins),
+            handoff: Arc::clone(&self.handoff),
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::Codec;
+
+    /// 进程内连接的客户端一端
+    pub(super) type Client = (FrameReader<BoxReader>, FrameWriter<BoxWriter>);
+
+    /// 等待每个期望帧的最长时间
+    const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// 经进程内管道连接到 `server`，以 `hello` 注册，不等待注册结果
+    pub(super) async fn connect(server: &Server, hello: Hello) -> Client {
+        let (local, remote) = tokio::io::duplex(64 * 1024);
+        let (reader, writer) = tokio::io::split(remote);
+        let server = server.clone();
+        tokio::spawn(async move {
+            let peer = SocketAddr::from(([127, 0, 0, 1], 0));
+            let _ = server
+                .serve_stream(Box::new(reader), Box::new(writer), peer)
+                .await;
+        });
+        let (reader, writer) = tokio::io::split(local);
+        let reader = FrameReader::new(Box::new(reader) as BoxReader, Codec::JsonLines);
+        let mut writer = FrameWriter::new(Box::new(writer) as BoxWriter, Codec::JsonLines);
+        writer
+            .write_frame(&Registration::Hello(hello))
+            .await
+            .unwrap();
+        (reader, writer)
+    }
+
+    /// 以 `name` 登录；服务器按顺序处理帧，收到心跳响应即说明注册已经完成
+    pub(super) async fn login(server: &Server, name: &str) -> Client {
+        let (mut reader, mut writer) =
+            connect(server, Hello::new(name.to_string(), Vec::new())).await;
+        writer
+            .write_frame(&Frame::Control(Control::Ping { seq: 0 }))
+            .await
+            .unwrap();
+        loop {
+            match next_frame(&mut reader).await {
+                Some(Frame::Control(Control::Pong { .. })) => break,
+                Some(_) => {}
+                None => panic!("{} was refused", name),
+            }
+        }
+        (reader, writer)
+    }
+
+    /// 读取下一帧，连接关闭时返回 `None`
+    pub(super) async fn next_frame(reader: &mut FrameReader<BoxReader>) -> Option<Frame> {
+        tokio::time::timeout(FRAME_TIMEOUT, reader.read_frame::<Frame>())
+            .await
+            .expect("timed out waiting for a frame")
+            .unwrap_or(None)
+    }
+
+    /// 以 `client` 向 `to` 发送 `content`，返回心跳响应之前收到的帧
+    pub(super) async fn send(client: &mut Client, to: &str, content: &str) -> Vec<Frame> {
+        let (reader, writer) = client;
+        let msg = Message::new(
+            ArcString::new(String::new()),
+            to.to_string(),
+            content.to_string(),
+        );
+        writer.write_frame(&Frame::from(msg)).await.unwrap();
+        writer
+            .write_frame(&Frame::Control(Control::Ping { seq: 0 }))
+            .await
+            .unwrap();
+        let mut frames = Vec::new();
+        loop {
+            match next_frame(reader).await {
+                Some(Frame::Control(Control::Pong { .. })) => return frames,
+                Some(frame) => frames.push(frame),
+                None => panic!("connection closed"),
+            }
+        }
+    }
+
+    /// 收到的帧中的聊天消息内容
+    pub(super) fn contents(frames: &[Frame]) -> Vec<String> {
+        frames
+            .iter()
+            .filter_map(|frame| match frame {
+                Frame::Message(msg) => Some(msg.content().to_string()),
+                Frame::Control(_) => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn relogin_closes_previous_connection() {
+        let server = Server::new();
+        let (mut first, _first_writer) = login(&server, "alice").await;
+        let (_second, _second_writer) = login(&server, "alice").await;
+        while next_frame(&mut first).await.is_some() {}
+        assert_eq!(server.online_users().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reserved_names_are_refused() {
+        let server = Server::new();
+        for name in [
+            "",
+            "  ",
+            "Server",
+            "server",
+            "#lobby",
+            "a/b",
+            "bell\u{7}",
+            "rtl\u{202e}",
+        ] {
+            let hello = Hello::new(name.to_string(), vec![FEATURE_ERRORS.to_string()]);
+            let (mut reader, _writer) = connect(&server, hello).await;
+            match next_frame(&mut reader).await {
+                Some(Frame::Control(Control::Error(error))) => {
+                    assert_eq!(error.code, ErrorCode::InvalidName, "{:?}", name)
+                }
+                other => panic!("{:?} was not refused: {:?}", name, other),
+            }
+            assert!(next_frame(&mut reader).await.is_none());
         }
+        assert!(server.online_users().is_empty());
     }
 }
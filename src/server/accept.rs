@@ -0,0 +1,265 @@
+/*!
+# 接受连接的退避与过载告警
+
+[`Server::run`](super::Server::run) 的接受循环在以下情况下不再空转、也不悄无声息地拒绝连接：
+
+- **接受失败退避**：`accept` 连续失败（如文件描述符耗尽的 `EMFILE`）时，下一次接受前等待
+  从 10 毫秒起逐次加倍、最长 1 秒的时间（[`AcceptBackoff`]），成功接受一个连接后恢复；
+  等待期间仍然回收结束的连接任务并响应关闭。
+- **文件描述符余量**：Unix 下按 `RLIMIT_NOFILE` 的软上限与已打开的文件描述符数估算余量，
+  余量不足 [`ServerConfig::fd_reserve`](super::ServerConfig::fd_reserve) 时直接关闭新连接，
+  为消息日志、联邦链路等留出文件描述符；启动时软上限容不下连接数上限也会给出警告。
+- **过载告警**：因连接数上限、文件描述符余量或接受失败而拒绝连接时计数（[`AcceptStats`]），
+  开始拒绝时输出一条警告，恢复接受后再输出一条并附上期间拒绝的次数；计数显示在管理员的
+  `/stats`、管理套接字的 `status` 与 systemd 的状态中。
+*/
+
+use super::Server;
+use crate::tr;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+
+/// 第一次接受失败后的等待时间
+const BACKOFF_MIN: Duration = Duration::from_millis(10);
+
+/// 接受失败后等待时间的上限
+const BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// 重新统计已打开的文件描述符数的最短间隔；余量接近下限时每次都重新统计
+const FD_RECOUNT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 拒绝连接的原因（用于告警）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Overload {
+    /// 连接数已达 [`ServerConfig::max_connections`](super::ServerConfig::max_connections)
+    ConnectionLimit,
+    /// 空闲的文件描述符不足
+    FdHeadroom,
+    /// `accept` 失败
+    AcceptFailed,
+}
+
+impl Overload {
+    fn describe(self) -> String {
+        match self {
+            Overload::ConnectionLimit => tr!("server.overload_connection_limit"),
+            Overload::FdHeadroom => tr!("server.overload_fd_headroom"),
+            Overload::AcceptFailed => tr!("server.overload_accept_failed"),
+        }
+    }
+}
+
+/// 接受连接的统计
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AcceptStats {
+    /// `accept` 失败的累计次数
+    pub accept_errors: u64,
+    /// 因连接数上限或文件描述符余量不足而关闭的连接数
+    pub refused: u64,
+    /// 当前是否处于拒绝新连接的状态
+    pub refusing: bool,
+}
+
+/// 拒绝连接的计数与告警状态，所有监听套接字共用
+#[derive(Debug, Default)]
+pub(super) struct AcceptMonitor {
+    accept_errors: AtomicU64,
+    refused: AtomicU64,
+    refusing: AtomicBool,
+    /// 本次开始拒绝以来的拒绝次数（含接受失败）
+    episode: AtomicU64,
+}
+
+impl AcceptMonitor {
+    /// 记录一次拒绝；从正常状态进入拒绝状态时输出告警
+    pub(super) fn refuse(&self, reason: Overload) {
+        match reason {
+            Overload::AcceptFailed => self.accept_errors.fetch_add(1, Ordering::Relaxed),
+            _ => self.refused.fetch_add(1, Ordering::Relaxed),
+        };
+        self.episode.fetch_add(1, Ordering::Relaxed);
+        if !self.refusing.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "{}",
+                tr!("server.refusing_started", reason = reason.describe())
+            );
+        }
+    }
+
+    /// 成功接纳一个连接；此前处于拒绝状态时输出恢复提示
+    pub(super) fn admitted(&self) {
+        if self.refusing.swap(false, Ordering::Relaxed) {
+            let count = self.episode.swap(0, Ordering::Relaxed);
+            println!("{}", tr!("server.refusing_stopped", count = count));
+        }
+    }
+
+    pub(super) fn stats(&self) -> AcceptStats {
+        AcceptStats {
+            accept_errors: self.accept_errors.load(Ordering::Relaxed),
+            refused: self.refused.load(Ordering::Relaxed),
+            refusing: self.refusing.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 单个监听套接字的接受失败退避
+#[derive(Debug, Default)]
+pub(super) struct AcceptBackoff {
+    /// 连续失败的次数
+    failures: u32,
+    /// 退避结束的时间，`None` 表示可以立即接受
+    resume_at: Option<Instant>,
+}
+
+impl AcceptBackoff {
+    /// 等待退避结束后在 `listener` 上接受一个连接；`listener` 为 `None` 时永远等待
+    pub(super) async fn accept(
+        &self,
+        listener: Option<&TcpListener>,
+    ) -> io::Result<(TcpStream, SocketAddr)> {
+        let Some(listener) = listener else {
+            return std::future::pending().await;
+        };
+        if let Some(resume_at) = self.resume_at {
+            tokio::time::sleep_until(resume_at.into()).await;
+        }
+        listener.accept().await
+    }
+
+    /// 接受成功，清除退避
+    pub(super) fn succeeded(&mut self) {
+        self.failures = 0;
+        self.resume_at = None;
+    }
+
+    /// 接受失败，返回下一次接受前的等待时间
+    pub(super) fn failed(&mut self) -> Duration {
+        let delay = BACKOFF_MIN
+            .saturating_mul(1 << self.failures.min(16))
+            .min(BACKOFF_MAX);
+        self.failures = self.failures.saturating_add(1);
+        self.resume_at = Some(Instant::now() + delay);
+        delay
+    }
+}
+
+/// 文件描述符余量的检查，Unix 以外的系统上不检查
+#[derive(Debug)]
+pub(super) struct FdHeadroom {
+    /// 保留的空闲文件描述符数，为 0 时不检查
+    reserve: u64,
+    /// 软上限，无法取得时为 `None`
+    limit: Option<u64>,
+    /// 最近一次统计：时间、已打开的文件描述符数、当时的连接数
+    counted: Mutex<Option<(Instant, u64, usize)>>,
+}
+
+impl FdHeadroom {
+    pub(super) fn new(reserve: usize) -> Self {
+        FdHeadroom {
+            reserve: reserve as u64,
+            limit: if reserve > 0 { fd_limit() } else { None },
+            counted: Mutex::new(None),
+        }
+    }
+
+    /// 文件描述符的软上限
+    pub(super) fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// 有 `connections` 个连接时，空闲的文件描述符是否不足保留的数量
+    ///
+    /// 距上次统计不足 [`FD_RECOUNT_INTERVAL`] 且余量充足时，按连接数的变化估算，不重新统计
+    pub(super) fn exhausted(&self, connections: usize) -> bool {
+        let Some(limit) = self.limit else {
+            return false;
+        };
+        let mut counted = self.counted.lock().unwrap();
+        let estimate = counted.and_then(|(at, open, then)| {
+            let open = (open + connections as u64).saturating_sub(then as u64);
+            let fresh = at.elapsed() < FD_RECOUNT_INTERVAL;
+            (fresh && limit.saturating_sub(open) >= self.reserve * 2).then_some(open)
+        });
+        let open = match estimate.or_else(open_fds) {
+            Some(open) => open,
+            None => return false,
+        };
+        if estimate.is_none() {
+            *counted = Some((Instant::now(), open, connections));
+        }
+        limit.saturating_sub(open) < self.reserve
+    }
+}
+
+/// 当前进程可打开的文件描述符数（`RLIMIT_NOFILE` 的软上限）
+#[cfg(unix)]
+fn fd_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` 是有效的 `rlimit`，getrlimit 只写入该结构
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return None;
+    }
+    (limit.rlim_cur != libc::RLIM_INFINITY).then_some(limit.rlim_cur)
+}
+
+#[cfg(not(unix))]
+fn fd_limit() -> Option<u64> {
+    None
+}
+
+/// 当前进程已打开的文件描述符数
+#[cfg(unix)]
+fn open_fds() -> Option<u64> {
+    let dir = if cfg!(target_os = "linux") {
+        "/proc/self/fd"
+    } else {
+        "/dev/fd"
+    };
+    // 读取目录本身也占用一个文件描述符，计数时扣除
+    let count = std::fs::read_dir(dir).ok()?.count() as u64;
+    Some(count.saturating_sub(1))
+}
+
+#[cfg(not(unix))]
+fn open_fds() -> Option<u64> {
+    None
+}
+
+impl Server {
+    /// 接受连接的统计：接受失败与拒绝的次数，以及当前是否在拒绝新连接
+    pub fn accept_stats(&self) -> AcceptStats {
+        self.accepts.stats()
+    }
+
+    /// 启动时检查文件描述符的软上限能否容纳连接数上限与保留的余量
+    pub(super) fn check_fd_limit(&self) {
+        let Some(limit) = self.fd_headroom.limit() else {
+            return;
+        };
+        let Some(max) = self.config.max_connections else {
+            return;
+        };
+        // 每个连接占用一个文件描述符
+        let needed = max as u64 + self.config.fd_reserve as u64;
+        if needed > limit {
+            eprintln!(
+                "{}",
+                tr!(
+                    "server.fd_limit_low",
+                    limit = limit,
+                    max = max,
+                    reserve = self.config.fd_reserve
+                )
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+/*!
+# 审计日志
+
+记录需要管理员事后查阅的事件：反垃圾规则的告警与限流（见 `spam` 子模块），
+用户提交的举报、消息的隔离及管理员的处理（见 `moderation` 子模块），维护模式的开启与关闭
+（见 `maintenance` 子模块），文件传输策略拒绝或隔离的文件（见 `transfer_policy` 子模块）。
+最近的 [`AUDIT_CAPACITY`] 条保存在内存中，管理员通过 `/audit [条数]` 或管理套接字的 `audit`
+命令查看；配置了 [`ServerConfig::audit_log`](super::ServerConfig::audit_log) 时每条事件还以
+JSON Lines 追加写入该文件，重启后仍可查阅。
+*/
+
+use super::Server;
+use crate::i18n::Lang;
+use crate::tr;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 内存中保留的审计事件条数
+pub const AUDIT_CAPACITY: usize = 500;
+
+/// `/audit` 与管理套接字的 `audit` 默认显示的条数
+const AUDIT_DEFAULT_SHOWN: usize = 20;
+
+/// 审计事件的类型，序列化为 `spam_flagged` 这样的小写字符串
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// 发送者触发了反垃圾规则，消息照常投递
+    SpamFlagged,
+    /// 发送者触发了反垃圾规则，被限流一段时间
+    SpamThrottled,
+    /// 用户被举报，举报进入审核队列
+    Reported,
+    /// 管理员处理完毕，举报移出审核队列
+    ReportDismissed,
+    /// 消息被隔离待审核
+    Quarantined,
+    /// 管理员放行了隔离的消息
+    QuarantineReleased,
+    /// 管理员丢弃了隔离的消息
+    QuarantineDiscarded,
+    /// 管理员开启了维护模式
+    MaintenanceStarted,
+    /// 管理员关闭了维护模式
+    MaintenanceEnded,
+    /// 文件传输策略拒绝了一次传输
+    TransferBlocked,
+    /// 文件未通过扫描，被移入隔离目录
+    TransferQuarantined,
+}
+
+impl AuditAction {
+    /// 以 `lang` 描述事件类型
+    pub fn describe(self, lang: Lang) -> String {
+        match self {
+            AuditAction::SpamFlagged => tr!(lang => "audit.spam_flagged"),
+            AuditAction::SpamThrottled => tr!(lang => "audit.spam_throttled"),
+            AuditAction::Reported => tr!(lang => "audit.reported"),
+            AuditAction::ReportDismissed => tr!(lang => "audit.report_dismissed"),
+            AuditAction::Quarantined => tr!(lang => "audit.quarantined"),
+            AuditAction::QuarantineReleased => tr!(lang => "audit.quarantine_released"),
+            AuditAction::QuarantineDiscarded => tr!(lang => "audit.quarantine_discarded"),
+            AuditAction::MaintenanceStarted => tr!(lang => "audit.maintenance_started"),
+            AuditAction::MaintenanceEnded => tr!(lang => "audit.maintenance_ended"),
+            AuditAction::TransferBlocked => tr!(lang => "audit.transfer_blocked"),
+            AuditAction::TransferQuarantined => tr!(lang => "audit.transfer_quarantined"),
+        }
+    }
+}
+
+/// 一条审计事件
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuditEntry {
+    /// 发生时间
+    pub time: DateTime<Local>,
+    /// 事件类型
+    pub action: AuditAction,
+    /// 相关的用户
+    pub user: String,
+    /// 说明（服务器进程的语言）
+    pub detail: String,
+}
+
+impl AuditEntry {
+    /// 以 `lang` 生成一行文字，如 `[2024-05-01 12:00:00] 反垃圾告警 alice: ……`
+    pub fn describe(&self, lang: Lang) -> String {
+        format!(
+            "[{}] {} {}: {}",
+            self.time.format("%Y-%m-%d %H:%M:%S"),
+            self.action.describe(lang),
+            self.user,
+            self.detail
+        )
+    }
+}
+
+/// 审计日志：内存中最近的事件，以及可选的追加写入文件
+#[derive(Debug)]
+pub(super) struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+    file: Option<Mutex<File>>,
+}
+
+impl AuditLog {
+    /// 创建审计日志；`path` 不为 `None` 时追加写入该文件，无法打开时只记录在内存中
+    pub(super) fn new(path: Option<&Path>) -> Self {
+        let file =
+            path.and_then(
+                |path| match OpenOptions::new().create(true).append(true).open(path) {
+                    Ok(file) => Some(Mutex::new(file)),
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            tr!("server.audit_open_failed", path = path.display(), error = e)
+                        );
+                        None
+                    }
+                },
+            );
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            file,
+        }
+    }
+
+    /// 记录一条事件，写入文件失败时仅记录日志
+    fn record(&self, entry: AuditEntry) {
+        if let Some(file) = &self.file {
+            let written = serde_json::to_string(&entry)
+                .map_err(std::io::Error::other)
+                .and_then(|line| writeln!(file.lock().unwrap(), "{}", line));
+            if let Err(e) = written {
+                eprintln!("{}", tr!("server.audit_write_failed", error = e));
+            }
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == AUDIT_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// 最近的 `count` 条事件（按发生顺序）
+    fn recent(&self, count: usize) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap();
+        let skip = entries.len().saturating_sub(count);
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Server {
+    /// 记录一条审计事件，并输出到服务器日志
+    pub(super) fn audit(&self, action: AuditAction, user: &str, detail: String) {
+        let entry = AuditEntry {
+            time: Local::now(),
+            action,
+            user: user.to_string(),
+            detail,
+        };
+        println!(
+            "{}",
+            tr!("server.audit", entry = entry.describe(crate::i18n::lang()))
+        );
+        self.audit.record(entry);
+    }
+
+    /// 最近的 `count` 条审计事件（按发生顺序）
+    pub fn audit_entries(&self, count: usize) -> Vec<AuditEntry> {
+        self.audit.recent(count)
+    }
+
+    /// `/audit [条数]`（仅管理员）与管理套接字的 `audit [条数]`：以 `lang` 列出最近的审计事件
+    pub(super) fn audit_command(&self, args: &str, lang: Lang) -> String {
+        let count = match args.trim() {
+            "" => AUDIT_DEFAULT_SHOWN,
+            count => match count.parse() {
+                Ok(count) if count > 0 => count,
+                _ => return tr!(lang => "cmd.audit_usage"),
+            },
+        };
+        let entries = self.audit_entries(count);
+        if entries.is_empty() {
+            return tr!(lang => "cmd.audit_empty");
+        }
+        let lines: Vec<String> = entries.iter().map(|entry| entry.describe(lang)).collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.audit", count = entries.len()),
+            lines.join("\n  › ")
+        )
+    }
+}
@@ -0,0 +1,255 @@
+/*!
+# 机器人指令
+
+机器人可以向服务器注册自己的指令前缀，实现 ChatOps 式的集成：
+
+- 机器人（须有 `command` 权限）发送 `/register /weather [<说明>]` 注册指令，
+  `/unregister /weather` 取消；服务器内置的指令与客户端本地处理的指令不能注册，
+  已被其他机器人注册的指令也不能抢占；
+- 任何用户发出 `/weather 北京` 时，服务器把这条消息原样（发送者为该用户、接收者为 `/weather`、
+  内容为参数）转交给注册它的机器人；机器人不在线时告知用户；
+- 机器人直接给调用者发消息作为回复；调用后 [`RESPONSE_WINDOW`] 内，即使机器人没有 `message`
+  权限，也可以回复该用户（仍计入机器人的限流）；
+- `/commands` 列出所有已注册的指令及其说明；注册只保存在内存中，机器人重启后应重新注册，
+  `/bot revoke` 撤销令牌时一并取消该机器人注册的指令。
+*/
+
+use super::Server;
+use crate::i18n::Lang;
+use crate::session::{Role, Session};
+use crate::{tr, ArcString, Message};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 机器人被调用后可以回复调用者的时长
+pub const RESPONSE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// 指令名（不含 `/`）的最大长度
+const MAX_COMMAND_LEN: usize = 32;
+
+/// 服务器内置的指令与客户端本地处理的指令，机器人不能注册
+const RESERVED_COMMANDS: &[&str] = &[
+    "/list",
+    "/whois",
+    "/profile",
+    "/privacy",
+    "/away",
+    "/dnd",
+    "/stats",
+    "/peers",
+    "/deadletter",
+    "/announce",
+    "/invite",
+    "/bot",
+    "/webhook",
+    "/jobs",
+    "/maintenance",
+    "/telegram",
+    "/xmpp",
+    "/register",
+    "/unregister",
+    "/commands",
+    "/exit",
+    "/msg",
+    "/forward",
+    "/history",
+    "/emoji",
+    "/alias",
+    "/sendfile",
+    "/accept",
+    "/reject",
+    "/transfers",
+    "/cancel-transfer",
+    "/fingerprint",
+    "/trust",
+    "/verify",
+    "/unverify",
+    "/e2e",
+    "/open",
+    "/preview",
+    "/mute",
+    "/unmute",
+    "/me",
+    "/multiline",
+    "/end",
+];
+
+/// 一条已注册的指令
+#[derive(Clone, Debug)]
+struct Registration {
+    /// 注册该指令的机器人
+    bot: ArcString,
+    /// 机器人提供的说明，可以为空
+    description: String,
+}
+
+/// 机器人注册的指令，以及等待机器人回复的调用者
+#[derive(Debug, Default)]
+pub(super) struct BotCommands {
+    /// 键为带 `/` 的指令名，按名称排序便于列出
+    commands: Mutex<BTreeMap<String, Registration>>,
+    /// (机器人, 调用者) → 最近一次调用的时间
+    pending: Mutex<HashMap<(ArcString, ArcString), Instant>>,
+}
+
+impl BotCommands {
+    /// 注册 `command` 的机器人
+    pub(super) fn owner(&self, command: &str) -> Option<ArcString> {
+        let commands = self.commands.lock().unwrap();
+        commands
+            .get(command)
+            .map(|registration| registration.bot.clone())
+    }
+
+    /// 记录 `user` 调用了 `bot` 的指令，并清理已过期的记录
+    fn invoked(&self, bot: &ArcString, user: &ArcString) {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, at| now.duration_since(*at) < RESPONSE_WINDOW);
+        pending.insert((bot.clone(), user.clone()), now);
+    }
+
+    /// `bot` 能否以回复调用者的身份给 `user` 发消息
+    pub(super) fn awaiting(&self, bot: &ArcString, user: &ArcString) -> bool {
+        let pending = self.pending.lock().unwrap();
+        pending
+            .get(&(bot.clone(), user.clone()))
+            .is_some_and(|at| at.elapsed() < RESPONSE_WINDOW)
+    }
+
+    /// 取消 `bot` 注册的所有指令
+    pub(super) fn remove_bot(&self, bot: &ArcString) {
+        self.commands
+            .lock()
+            .unwrap()
+            .retain(|_, registration| &registration.bot != bot);
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|(owner, _), _| owner != bot);
+    }
+}
+
+/// 指令名是否合法：`/` 后跟 1 到 [`MAX_COMMAND_LEN`] 个字母、数字、`-` 或 `_`
+fn valid_command(command: &str) -> bool {
+    command.strip_prefix('/').is_some_and(|name| {
+        !name.is_empty()
+            && name.chars().count() <= MAX_COMMAND_LEN
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+impl Server {
+    /// 把对已注册指令的调用转交给注册它的机器人
+    ///
+    /// # 返回值
+    /// `msg` 不是机器人注册的指令时返回 `false`，由调用方按内置指令处理
+    pub(super) async fn route_to_bot(&self, session: &Session, msg: &Message) -> bool {
+        let Some(bot) = self.bot_commands.owner(msg.to()) else {
+            return false;
+        };
+        let lang = session.lang;
+        let tip = if bot == session.username {
+            // 机器人调用自己注册的指令没有意义，也避免转交给自己
+            tr!(lang => "cmd.command_own", command = msg.to())
+        } else if self.online_users.contains_key(&bot) {
+            self.bot_commands.invoked(&bot, &session.username);
+            self.deliver_local(&bot, msg.clone()).await;
+            return true;
+        } else {
+            tr!(lang => "cmd.command_bot_offline", command = msg.to(), bot = bot)
+        };
+        self.reply(&session.username, tip).await;
+        true
+    }
+
+    /// `/register <指令> [<说明>]`：机器人注册指令
+    pub(super) fn register_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        if session.role != Role::Bot {
+            return tr!(lang => "cmd.bot_only");
+        }
+        let (command, description) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        if command.is_empty() {
+            return tr!(lang => "cmd.usage_register");
+        }
+        if !valid_command(command) {
+            return tr!(lang => "cmd.command_invalid", command = command);
+        }
+        if RESERVED_COMMANDS.contains(&command) {
+            return tr!(lang => "cmd.command_reserved", command = command);
+        }
+        {
+            let mut commands = self.bot_commands.commands.lock().unwrap();
+            if let Some(existing) = commands.get(command) {
+                if existing.bot != session.username {
+                    return tr!(lang => "cmd.command_taken", command = command, bot = existing.bot);
+                }
+            }
+            commands.insert(
+                command.to_string(),
+                Registration {
+                    bot: session.username.clone(),
+                    description: description.trim().to_string(),
+                },
+            );
+        }
+        println!(
+            "{}",
+            tr!(
+                "server.command_registered",
+                bot = session.username,
+                command = command
+            )
+        );
+        tr!(lang => "cmd.command_registered", command = command)
+    }
+
+    /// `/unregister <指令>`：机器人取消自己注册的指令
+    pub(super) fn unregister_command(&self, session: &Session, command: &str) -> String {
+        let lang = session.lang;
+        if session.role != Role::Bot {
+            return tr!(lang => "cmd.bot_only");
+        }
+        if command.is_empty() {
+            return tr!(lang => "cmd.usage_unregister");
+        }
+        let mut commands = self.bot_commands.commands.lock().unwrap();
+        match commands.get(command) {
+            Some(registration) if registration.bot == session.username => {
+                commands.remove(command);
+                tr!(lang => "cmd.command_unregistered", command = command)
+            }
+            _ => tr!(lang => "cmd.command_not_registered", command = command),
+        }
+    }
+
+    /// `/commands`：所有已注册的机器人指令
+    pub(super) fn commands_response(&self, lang: Lang) -> String {
+        let commands = self.bot_commands.commands.lock().unwrap();
+        if commands.is_empty() {
+            return tr!(lang => "cmd.commands_empty");
+        }
+        let lines: Vec<String> = commands
+            .iter()
+            .map(|(command, registration)| {
+                let mut line = tr!(lang => "cmd.command_entry",
+                    command = command,
+                    bot = registration.bot,
+                );
+                if !registration.description.is_empty() {
+                    line.push_str(&format!(" — {}", registration.description));
+                }
+                line
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.commands", count = commands.len()),
+            lines.join("\n  › ")
+        )
+    }
+}
@@ -0,0 +1,429 @@
+/*!
+# 机器人账号
+
+机器人是非交互的账号，由管理员通过 `/bot create` 创建，登录时在握手帧中出示长期有效的令牌
+（[`Hello::token`]）而不是直接以用户名登录：
+
+- 令牌只在创建时显示一次，服务器只保存其 SHA-256 摘要（随用户数据持久化，见 [`BotRecord`]）；
+- 机器人账号的用户名必须配合有效的令牌登录，普通用户出示令牌同样被拒绝；
+- 权限范围（[`BotScope`]）限定机器人能做什么：给用户发消息（`message`）、使用服务器指令（`command`）；
+- 机器人的消息按 [`BotRate`] 单独限流，与人类用户互不影响；
+- `/list` 与 `/whois` 标注机器人账号；`/bot revoke` 撤销令牌并断开在线的机器人，
+  之后可以用 `/bot create` 为其重新签发令牌；
+- 机器人可以注册由自己处理的指令，见 `bot_commands` 子模块。
+
+管理员同样以令牌登录：[`issue_admin_token`] 生成令牌，运营者把其摘要写入
+[`ServerConfig::admins`](super::ServerConfig::admins)，以管理员用户名登录而未出示对应令牌的连接被拒绝，
+仅凭用户名无法取得管理员权限。
+*/
+
+use super::Server;
+use crate::i18n::Lang;
+use crate::protocol::{ErrorCode, Hello, Recipient};
+use crate::secrets::encode_hex;
+use crate::session::{Role, Session};
+use crate::{humanize_duration_in, tr, ArcString, Message};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 机器人令牌的前缀，便于在配置与日志中识别
+const TOKEN_PREFIX: &str = "bot_";
+
+/// 管理员令牌的前缀
+const ADMIN_TOKEN_PREFIX: &str = "adm_";
+
+/// 令牌的随机字节数
+const TOKEN_BYTES: usize = 24;
+
+/// 机器人的权限范围
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BotScope {
+    /// 给用户（包括其他服务器上的用户）发消息
+    Message,
+    /// 使用 `/list`、`/whois` 等服务器指令
+    Command,
+}
+
+impl BotScope {
+    /// 全部权限范围，`/bot create` 未指定时授予
+    pub const ALL: &'static [BotScope] = &[BotScope::Message, BotScope::Command];
+
+    /// 名称，即 `/bot` 指令中使用的写法
+    pub fn name(self) -> &'static str {
+        match self {
+            BotScope::Message => "message",
+            BotScope::Command => "command",
+        }
+    }
+
+    /// 解析以逗号分隔的权限范围，如 `message,command`；含有未知名称时返回 `None`
+    pub fn parse_list(text: &str) -> Option<Vec<BotScope>> {
+        let mut scopes = Vec::new();
+        for name in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let scope = match name {
+                "message" => BotScope::Message,
+                "command" => BotScope::Command,
+                _ => return None,
+            };
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        scopes.sort();
+        Some(scopes)
+    }
+}
+
+/// 以逗号连接权限范围的名称，没有任何权限时显示 `-`
+fn describe_scopes(scopes: &[BotScope]) -> String {
+    if scopes.is_empty() {
+        return "-".to_string();
+    }
+    scopes
+        .iter()
+        .map(|scope| scope.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 机器人账号需要持久化的数据，保存在该用户的 [`UserRecord::bot`](crate::storage::UserRecord::bot) 中
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BotRecord {
+    /// 令牌的 SHA-256 摘要（十六进制），`None` 表示令牌已被撤销
+    pub token_sha256: Option<String>,
+    /// 创建该机器人的管理员
+    pub created_by: String,
+    pub created_at: DateTime<Local>,
+    /// 权限范围
+    pub scopes: Vec<BotScope>,
+}
+
+impl BotRecord {
+    /// `token` 是否为该机器人当前有效的令牌
+    pub fn verify(&self, token: &str) -> bool {
+        self.token_sha256
+            .as_deref()
+            .is_some_and(|digest| digest == token_digest(token))
+    }
+
+    /// 是否拥有 `scope` 权限
+    pub fn allows(&self, scope: BotScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// 机器人消息的限流配置：每个机器人在 `window` 内最多发送 `max_messages` 条消息（包括服务器指令）
+#[derive(Clone, Debug)]
+pub struct BotRate {
+    pub max_messages: usize,
+    pub window: Duration,
+}
+
+impl Default for BotRate {
+    fn default() -> Self {
+        Self {
+            max_messages: 60,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// 各机器人最近发送消息的时间，按机器人账号（而不是连接）计数，重连不会重置
+#[derive(Debug, Default)]
+pub(super) struct BotLimits {
+    recent: Mutex<HashMap<ArcString, VecDeque<Instant>>>,
+}
+
+impl BotLimits {
+    /// 记录 `bot` 的一条消息；超过 `rate` 时不记录并返回 `false`
+    pub(super) fn admit(&self, bot: &ArcString, rate: &BotRate) -> bool {
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        let sent = recent.entry(bot.clone()).or_default();
+        while sent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= rate.window)
+        {
+            sent.pop_front();
+        }
+        if sent.len() >= rate.max_messages {
+            return false;
+        }
+        sent.push_back(now);
+        true
+    }
+}
+
+/// 生成以 `prefix` 开头的新令牌，返回（令牌，摘要）
+pub(super) fn issue_token(prefix: &str) -> io::Result<(String, String)> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    let token = format!("{}{}", prefix, encode_hex(&bytes));
+    let digest = token_digest(&token);
+    Ok((token, digest))
+}
+
+/// 生成新的管理员登录令牌，返回（令牌，摘要）
+///
+/// 令牌交给管理员在客户端以 `--token` 出示，摘要写入服务器的 [`ServerConfig::admins`](super::ServerConfig::admins)
+pub fn issue_admin_token() -> io::Result<(String, String)> {
+    issue_token(ADMIN_TOKEN_PREFIX)
+}
+
+/// 令牌的 SHA-256 摘要（十六进制），服务器只保存摘要
+pub(super) fn token_digest(token: &str) -> String {
+    encode_hex(&Sha256::digest(token.trim().as_bytes()))
+}
+
+impl Server {
+    /// 按握手帧中的令牌认证：机器人账号与管理员须出示有效的令牌，普通用户不能出示令牌
+    ///
+    /// # 返回值
+    /// 通过时返回该连接的角色；拒绝时返回错误码与以 `lang` 描述的原因
+    pub(super) fn authenticate(
+        &self,
+        username: &ArcString,
+        hello: &Hello,
+        lang: Lang,
+    ) -> Result<Role, (ErrorCode, String)> {
+        let token = hello.token.as_deref();
+        if let Some(digest) = self.config.admins.get(username.get().as_str()) {
+            return match token {
+                Some(token) if token_digest(token) == *digest => Ok(Role::Admin),
+                Some(_) => Err((
+                    ErrorCode::InvalidToken,
+                    tr!(lang => "server.admin_token_invalid", user = username),
+                )),
+                None => Err((
+                    ErrorCode::InvalidToken,
+                    tr!(lang => "server.admin_token_required", user = username),
+                )),
+            };
+        }
+        let bot = self.users.get(username).and_then(|user| user.bot.clone());
+        match (bot, token) {
+            (None, None) => Ok(Role::User),
+            (None, Some(_)) => Err((
+                ErrorCode::InvalidToken,
+                tr!(lang => "server.not_a_bot", user = username),
+            )),
+            (Some(_), None) => Err((
+                ErrorCode::InvalidToken,
+                tr!(lang => "server.bot_token_required", user = username),
+            )),
+            (Some(bot), Some(token)) if bot.verify(token) => Ok(Role::Bot),
+            (Some(_), Some(_)) => Err((
+                ErrorCode::InvalidToken,
+                tr!(lang => "server.bot_token_invalid", user = username),
+            )),
+        }
+    }
+
+    /// 检查机器人能否发送 `msg`：须拥有相应的权限范围且未超过限流
+    ///
+    /// # 返回值
+    /// 不允许时返回错误码与以会话语言描述的原因
+    pub(super) fn bot_refusal(
+        &self,
+        session: &Session,
+        msg: &Message,
+    ) -> Option<(ErrorCode, String)> {
+        let lang = session.lang;
+        let scope = match Recipient::parse(msg.to()) {
+            Recipient::Command(_) => BotScope::Command,
+            _ => BotScope::Message,
+        };
+        let allowed = self
+            .users
+            .get(&session.username)
+            .and_then(|user| user.bot.as_ref().map(|bot| bot.allows(scope)))
+            .unwrap_or(false)
+            // 回复调用了自己指令的用户不需要 `message` 权限
+            || (scope == BotScope::Message
+                && self
+                    .bot_commands
+                    .awaiting(&session.username, &ArcString::new(msg.to().to_string())));
+        if !allowed {
+            return Some((
+                ErrorCode::Forbidden,
+                tr!(lang => "server.bot_scope_denied", scope = scope.name()),
+            ));
+        }
+        let rate = &self.config.bot_rate;
+        if !self.bot_limits.admit(&session.username, rate) {
+            return Some((
+                ErrorCode::RateLimited,
+                tr!(lang => "server.bot_rate_limited",
+                    max = rate.max_messages,
+                    window = humanize_duration_in(lang, rate.window),
+                ),
+            ));
+        }
+        None
+    }
+
+    /// `name` 是否为机器人账号
+    pub(super) fn is_bot(&self, name: &ArcString) -> bool {
+        self.users.get(name).is_some_and(|user| user.bot.is_some())
+    }
+
+    /// `/bot [list|create <名称> [<权限>]|revoke <名称>|scope <名称> <权限>]`：管理机器人账号
+    pub(super) fn bot_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        let action = parts.next();
+        let name = parts.next().map(|name| ArcString::new(name.to_string()));
+        let scopes = parts.next();
+        match (action, name) {
+            (None | Some("list"), None) => self.list_bots(lang),
+            (Some("create"), Some(name)) => {
+                let scopes = match scopes.map(BotScope::parse_list) {
+                    None => BotScope::ALL.to_vec(),
+                    Some(Some(scopes)) => scopes,
+                    Some(None) => return tr!(lang => "cmd.bot_invalid_scope"),
+                };
+                self.create_bot(session, name, scopes)
+            }
+            (Some("revoke"), Some(name)) => self.revoke_bot(&name, lang),
+            (Some("scope"), Some(name)) => match scopes.and_then(BotScope::parse_list) {
+                Some(scopes) => self.scope_bot(&name, scopes, lang),
+                None => tr!(lang => "cmd.bot_invalid_scope"),
+            },
+            _ => tr!(lang => "cmd.usage_bot"),
+        }
+    }
+
+    /// 创建机器人账号，或为令牌已被撤销的机器人重新签发令牌
+    fn create_bot(&self, session: &Session, name: ArcString, scopes: Vec<BotScope>) -> String {
+        let lang = session.lang;
+        let existing = self.users.get(&name).map(|user| user.bot.clone());
+        match existing {
+            Some(Some(bot)) if bot.token_sha256.is_some() => {
+                return tr!(lang => "cmd.bot_exists", name = name);
+            }
+            Some(None) => return tr!(lang => "cmd.bot_user_exists", name = name),
+            _ if self.config.admins.contains_key(name.get().as_str()) => {
+                return tr!(lang => "cmd.bot_user_exists", name = name);
+            }
+            _ => {}
+        }
+        let (token, digest) = match issue_token(TOKEN_PREFIX) {
+            Ok(issued) => issued,
+            Err(e) => return tr!(lang => "cmd.bot_failed", error = e),
+        };
+        let record = BotRecord {
+            token_sha256: Some(digest),
+            created_by: session.username.get(),
+            created_at: Local::now(),
+            scopes,
+        };
+        let scopes = describe_scopes(&record.scopes);
+        self.users.entry(name.clone()).or_default().bot = Some(record);
+        self.save_user(&name);
+        println!(
+            "{}",
+            tr!(
+                "server.bot_created",
+                admin = session.username,
+                name = name,
+                scopes = scopes
+            )
+        );
+        tr!(lang => "cmd.bot_created", name = name, scopes = scopes, token = token)
+    }
+
+    /// 撤销机器人的令牌并断开其连接
+    fn revoke_bot(&self, name: &ArcString, lang: Lang) -> String {
+        let revoked = match self.users.get_mut(name) {
+            Some(mut user) => match user.bot.as_mut() {
+                Some(bot) => bot.token_sha256.take().is_some(),
+                None => return tr!(lang => "cmd.bot_missing", name = name),
+            },
+            None => return tr!(lang => "cmd.bot_missing", name = name),
+        };
+        if !revoked {
+            return tr!(lang => "cmd.bot_already_revoked", name = name);
+        }
+        self.save_user(name);
+        self.bot_commands.remove_bot(name);
+        if let Some(user) = self.online_users.get(name) {
+            user.closing.notify_one();
+        }
+        println!("{}", tr!("server.bot_revoked", name = name));
+        tr!(lang => "cmd.bot_revoked", name = name)
+    }
+
+    /// 修改机器人的权限范围，对在线的机器人立即生效
+    fn scope_bot(&self, name: &ArcString, scopes: Vec<BotScope>, lang: Lang) -> String {
+        let scopes = {
+            let Some(mut user) = self.users.get_mut(name) else {
+                return tr!(lang => "cmd.bot_missing", name = name);
+            };
+            let Some(bot) = user.bot.as_mut() else {
+                return tr!(lang => "cmd.bot_missing", name = name);
+            };
+            bot.scopes = scopes;
+            describe_scopes(&bot.scopes)
+        };
+        self.save_user(name);
+        tr!(lang => "cmd.bot_scoped", name = name, scopes = scopes)
+    }
+
+    /// 所有机器人账号（按名称排序）及其权限、创建者与状态
+    fn list_bots(&self, lang: Lang) -> String {
+        let mut bots: Vec<(String, BotRecord)> = self
+            .users
+            .iter()
+            .filter_map(|entry| Some((entry.key().get(), entry.value().bot.clone()?)))
+            .collect();
+        if bots.is_empty() {
+            return tr!(lang => "cmd.bot_empty");
+        }
+        bots.sort_by(|a, b| a.0.cmp(&b.0));
+        let lines: Vec<String> = bots
+            .iter()
+            .map(|(name, bot)| {
+                let state = if bot.token_sha256.is_none() {
+                    tr!(lang => "cmd.bot_state_revoked")
+                } else if self
+                    .online_users
+                    .contains_key(&ArcString::new(name.clone()))
+                {
+                    tr!(lang => "cmd.bot_state_online")
+                } else {
+                    tr!(lang => "cmd.bot_state_offline")
+                };
+                tr!(lang => "cmd.bot_entry",
+                    name = name,
+                    scopes = describe_scopes(&bot.scopes),
+                    admin = bot.created_by,
+                    state = state,
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.bot_list", count = bots.len()),
+            lines.join("\n  › ")
+        )
+    }
+
+    /// `/whois` 中机器人账号的说明；管理员额外看到权限范围与创建者
+    pub(super) fn whois_bot(&self, name: &ArcString, admin: bool, lang: Lang) -> Option<String> {
+        let bot = self.users.get(name)?.bot.clone()?;
+        Some(if admin {
+            tr!(lang => "cmd.whois_bot_admin",
+                scopes = describe_scopes(&bot.scopes),
+                admin = bot.created_by,
+            )
+        } else {
+            tr!(lang => "cmd.whois_bot")
+        })
+    }
+}
@@ -0,0 +1,207 @@
+/*!
+# 故障注入
+
+仅用于测试的混沌模式（需启用 `chaos` 特性，配置见 [`ServerConfig::chaos`](super::ServerConfig::chaos)）：
+服务器写给客户端的每一帧按配置的概率遭遇一种故障，用来在发布前检验客户端的重连、续传、
+去重与心跳逻辑在恶劣网络下是否可靠：
+
+- **延迟**（`delay`）：写出前等待随机的一段时间（不超过 `max-delay` 毫秒）；
+- **丢弃**（`drop`）：不写出该帧，客户端只能靠消息编号的空缺或心跳超时发现；
+- **截断**（`truncate`）：只写出该帧的前一部分后断开连接，模拟传输中途断线；
+- **重复**（`duplicate`）：连续写出两次同一帧。
+
+命令行写法为逗号分隔的 `故障=概率`，例如 `--chaos=drop=0.01,duplicate=0.02,delay=0.1,max-delay=500`，
+各概率之和不能超过 1；`seed=数字` 固定随机数种子，使同样的帧序列遭遇同样的故障。
+启用后服务器启动时给出醒目的提示，每次丢弃、截断与重复都会记录在服务器输出中。
+*/
+
+use super::{Outgoing, Server};
+use crate::codec::{CodecError, FrameWriter};
+use crate::transport::BoxWriter;
+use crate::{tr, ArcString};
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 故障注入的概率与参数
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// 延迟写出的概率
+    pub delay: f64,
+    /// 延迟的上限
+    pub max_delay: Duration,
+    /// 丢弃的概率
+    pub drop: f64,
+    /// 截断后断开连接的概率
+    pub truncate: f64,
+    /// 重复写出的概率
+    pub duplicate: f64,
+    /// 随机数种子，`None` 表示每次启动随机选取
+    pub seed: Option<u64>,
+}
+
+impl ChaosConfig {
+    /// 解析 `drop=0.01,duplicate=0.02,truncate=0.001,delay=0.1,max-delay=500,seed=42`
+    ///
+    /// # 返回值
+    /// 有未知的项、概率不在 0 到 1 之间或概率之和超过 1 时返回 `None`
+    pub fn from_string(spec: &str) -> Option<Self> {
+        let mut config = ChaosConfig {
+            max_delay: Duration::from_millis(200),
+            ..ChaosConfig::default()
+        };
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = item.split_once('=')?;
+            let value = value.trim();
+            let probability = || {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|p| (0.0..=1.0).contains(p))
+            };
+            match key.trim() {
+                "delay" => config.delay = probability()?,
+                "drop" => config.drop = probability()?,
+                "truncate" => config.truncate = probability()?,
+                "duplicate" => config.duplicate = probability()?,
+                "max-delay" => config.max_delay = Duration::from_millis(value.parse().ok()?),
+                "seed" => config.seed = Some(value.parse().ok()?),
+                _ => return None,
+            }
+        }
+        let total = config.delay + config.drop + config.truncate + config.duplicate;
+        (total <= 1.0).then_some(config)
+    }
+}
+
+/// 一帧遭遇的故障
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fault {
+    Delay(Duration),
+    Drop,
+    Truncate,
+    Duplicate,
+}
+
+/// 故障注入的运行状态：配置与随机数发生器
+#[derive(Debug)]
+pub(super) struct Chaos {
+    config: ChaosConfig,
+    /// xorshift64* 的状态，所有连接共用
+    state: Mutex<u64>,
+}
+
+impl Chaos {
+    pub(super) fn new(config: ChaosConfig) -> Self {
+        let seed = config.seed.unwrap_or_else(|| {
+            let mut bytes = [0u8; 8];
+            let _ = getrandom::getrandom(&mut bytes);
+            u64::from_le_bytes(bytes)
+        });
+        Chaos {
+            config,
+            // xorshift 的状态不能为 0
+            state: Mutex::new(seed | 1),
+        }
+    }
+
+    /// 取下一个 [0, 1) 之间的随机数
+    fn next(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        let value = state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// 为下一帧抽取故障，`None` 表示正常写出
+    fn fault(&self) -> Option<Fault> {
+        let config = &self.config;
+        let roll = self.next();
+        let mut threshold = config.drop;
+        if roll < threshold {
+            return Some(Fault::Drop);
+        }
+        threshold += config.truncate;
+        if roll < threshold {
+            return Some(Fault::Truncate);
+        }
+        threshold += config.duplicate;
+        if roll < threshold {
+            return Some(Fault::Duplicate);
+        }
+        threshold += config.delay;
+        if roll < threshold {
+            return Some(Fault::Delay(config.max_delay.mul_f64(self.next())));
+        }
+        None
+    }
+}
+
+impl Server {
+    /// 启动时提示故障注入已启用
+    pub(super) fn announce_chaos(&self) {
+        if let Some(chaos) = &self.chaos {
+            let config = &chaos.config;
+            println!(
+                "{}",
+                tr!(
+                    "server.chaos_enabled",
+                    drop = config.drop,
+                    truncate = config.truncate,
+                    duplicate = config.duplicate,
+                    delay = config.delay,
+                    max_delay = config.max_delay.as_millis()
+                )
+            );
+        }
+    }
+
+    /// 按故障注入的配置写出发给 `username` 的一帧；未启用时直接写出
+    ///
+    /// 截断时写出该帧的前一部分后返回错误，由写循环断开连接
+    pub(super) async fn write_chaotic(
+        &self,
+        username: &ArcString,
+        writer: &mut FrameWriter<BoxWriter>,
+        frame: &Outgoing,
+    ) -> Result<(), CodecError> {
+        let Some(fault) = self.chaos.as_ref().and_then(|chaos| chaos.fault()) else {
+            return frame.write(writer).await;
+        };
+        match fault {
+            Fault::Delay(delay) => {
+                tokio::time::sleep(delay).await;
+                frame.write(writer).await
+            }
+            Fault::Drop => {
+                println!("{}", tr!("server.chaos_dropped", user = username));
+                Ok(())
+            }
+            Fault::Truncate => {
+                let encoded = frame.encode(writer.codec())?;
+                let keep = encoded.len() / 2;
+                writer.write_raw(&encoded[..keep]).await?;
+                println!(
+                    "{}",
+                    tr!(
+                        "server.chaos_truncated",
+                        user = username,
+                        kept = keep,
+                        len = encoded.len()
+                    )
+                );
+                Err(CodecError::Io(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    tr!("server.chaos_disconnect"),
+                )))
+            }
+            Fault::Duplicate => {
+                frame.write(writer).await?;
+                println!("{}", tr!("server.chaos_duplicated", user = username));
+                frame.write(writer).await
+            }
+        }
+    }
+}
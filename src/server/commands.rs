@@ -0,0 +1,628 @@
+/*!
+# 服务器指令
+
+客户端以 `/指令` 作为接收者、以指令参数作为消息内容发送给服务器，
+服务器在此处理并以 "Server" 身份、用请求者的语言回复。目前支持：
+
+- `/list`：查看在线用户列表，机器人账号带有标注
+- `/whois <用户>`：查看用户资料，按目标用户的隐私设置公开信息；管理员额外看到来源地址与 GeoIP 信息，
+  以及机器人账号的权限范围与创建者
+- `/profile <name|avatar|pronouns|bio> [<内容>]`：设置（或清除）自己的显示名、头像（表情或图片链接）、
+  代词与简介（见 [`crate::profile`]）；表情头像随在线名单推送给其他用户
+- `/privacy [<status|idle|client> <on|off>]`：查看或修改自己的隐私设置
+- `/notify [mute|unmute <会话>|keyword <add|remove> <关键词>|quiet <HH:MM-HH:MM>|quiet off]`：
+  查看或修改保存在服务器端的通知偏好（见 [`crate::notify`]），各设备共用
+- `/receipts <用户> [<条数>]`：查看自己最近发给该用户的消息的投递状态（见 `receipts` 子模块）
+- `/report <用户> <理由>`：举报用户，举报进入审核队列并提醒在线的管理员（见 `moderation` 子模块）
+- `/away [<自动回复内容>]`：设置（或取消）离开状态
+- `/dnd [on|off]`：开启或关闭免打扰，关闭时补发期间暂存的消息
+- `/peers`：查看联邦对端服务器的链路状态与其在线用户
+- `/stats [<用户>]`：查看自己连接的收发统计；管理员可以查看任意在线用户及服务器的 panic 计数
+- `/deadletter [replay <编号>|drop <编号>|clear]`（仅管理员）：查看死信队列，
+  重新投递或清除其中的消息
+- `/announce <公告>`（仅管理员）：向所有在线用户发布公告
+- `/invite [<次数> [<有效小时数>]|list|revoke <邀请码>]`（仅管理员）：生成、查看或撤销邀请码
+- `/bot [list|create <名称> [<权限>]|revoke <名称>|scope <名称> <权限>]`（仅管理员）：
+  管理机器人账号（见 `bots` 子模块）
+- `/webhook [list|create <名称> <用户>|revoke <名称>]`（仅管理员）：管理传入 Webhook
+  （见 `webhooks` 子模块）
+- `/jobs [run <任务>]`（仅管理员）：查看维护任务的时间表与运行统计，或立即运行一次（见 `jobs` 子模块）
+- `/audit [<条数>]`（仅管理员）：查看最近的审计事件，如反垃圾规则的告警与限流（见 `audit` 子模块）
+- `/reports [dismiss <编号>]`（仅管理员）：查看待处理的举报，或把处理完毕的举报移出审核队列
+- `/quarantine [release|discard <编号>]`（仅管理员）：查看隔离待审核的消息，放行或丢弃其中一条
+- `/maintenance [on [<HH:MM>]|off]`（仅管理员）：查看、开启或关闭维护模式（见 `maintenance` 子模块）
+- `/register <指令> [<说明>]`、`/unregister <指令>`（仅机器人）：注册或取消机器人指令，
+  此后其他用户发出的该指令转交给机器人（见 `bot_commands` 子模块）
+- `/commands`：查看机器人注册的指令
+- `/join <#聊天室>|<邀请码>`、`/part <#聊天室>`、`/rooms`、
+  `/room <#聊天室> [retention <条数>|retention default|private on|off]`：加入（不存在时创建）或退出聊天室，
+  查看自己加入的聊天室，查看聊天室的成员，修改补发条数或设为私有（见 `rooms` 子模块）
+- `/invite-link <#聊天室> [<次数> [<有效小时数>]]|list|revoke <邀请码>`（仅聊天室创建者与管理员）：
+  生成、查看或撤销私有聊天室的邀请码
+- `/telegram [join|leave]`：查看 Telegram 桥接状态，加入或退出桥接聊天室（见 `telegram` 子模块）
+- `/xmpp`：查看 XMPP 网关状态、自己的 Jabber 地址、订阅者与 Jabber 联系人的状态（见 `xmpp` 子模块）
+*/
+
+use super::rooms::parse_room;
+use super::{Notice, Server};
+use crate::i18n::Lang;
+use crate::notify::QuietHours;
+use crate::profile::{FieldError, MAX_AVATAR_URL_LEN, MAX_EMOJI_AVATAR_CHARS};
+use crate::protocol::RosterChange;
+use crate::session::{Role, Session};
+use crate::{humanize_duration_in, tr, ArcString, Message};
+use std::time::Duration;
+
+/// `/invite` 未指定有效期时，邀请码的有效小时数
+pub const DEFAULT_INVITE_HOURS: u64 = 24 * 7;
+
+impl Server {
+    /// 分发并处理一条服务器指令
+    pub(super) async fn handle_command(&self, session: &Session, msg: &Message) {
+        if self.route_to_bot(session, msg).await {
+            return;
+        }
+        let args = msg.content().trim();
+        let lang = session.lang;
+        let response = match msg.to() {
+            "/list" => self.list_response(lang),
+            "/whois" => self.whois_response(session, args),
+            "/profile" => self.update_profile(&session.username, args, lang),
+            "/privacy" => self.update_privacy(&session.username, args, lang),
+            "/notify" => self.update_notify(&session.username, args, lang),
+            "/receipts" => self.receipts_command(session, args),
+            "/trace" => self.trace_command(session, args),
+            "/report" => self.report_command(session, args).await,
+            "/away" => Self::update_away(session, args),
+            "/dnd" => self.update_dnd(session, args).await,
+            "/stats" => self.stats_response(session, args),
+            "/peers" => self.peers_response(lang),
+            "/commands" => self.commands_response(lang),
+            "/join" => self.join_command(session, args),
+            "/part" => self.part_command(session, args),
+            "/rooms" => self.rooms_command(session),
+            "/room" => self.room_command(session, args),
+            "/invite-link" => self.invite_link_command(session, args),
+            #[cfg(feature = "bridges")]
+            "/telegram" => self.telegram_command(session, args),
+            #[cfg(feature = "bridges")]
+            "/xmpp" => self.xmpp_response(session),
+            "/register" => self.register_command(session, args),
+            "/unregister" => self.unregister_command(session, args),
+            "/deadletter" if session.role == Role::Admin => self.dead_letter(args, lang).await,
+            "/announce" if session.role == Role::Admin => self.announce(session, args),
+            "/invite" if session.role == Role::Admin => self.invite(session, args),
+            "/bot" if session.role == Role::Admin => self.bot_command(session, args),
+            "/webhook" if session.role == Role::Admin => self.webhook_command(session, args),
+            "/jobs" if session.role == Role::Admin => self.jobs_command(lang, args).await,
+            "/audit" if session.role == Role::Admin => self.audit_command(args, lang),
+            "/reports" if session.role == Role::Admin => {
+                self.reports_command(Some(&session.username), args, lang)
+            }
+            "/quarantine" if session.role == Role::Admin => {
+                self.quarantine_command(Some(&session.username), args, lang)
+                    .await
+            }
+            "/maintenance" if session.role == Role::Admin => {
+                self.maintenance_command(Some(&session.username), args, lang)
+            }
+            "/deadletter" | "/announce" | "/invite" | "/bot" | "/webhook" | "/jobs" | "/audit"
+            | "/reports" | "/quarantine" | "/maintenance" => tr!(lang => "cmd.admin_only"),
+            other => tr!(lang => "cmd.unknown", command = other),
+        };
+        if matches!(msg.to(), "/away" | "/dnd" | "/privacy") {
+            // 状态可能有变化，随之向订阅了在线名单的连接与 XMPP 网关的订阅者推送
+            self.push_roster(RosterChange::Status, &session.username);
+            #[cfg(feature = "bridges")]
+            self.xmpp_status_changed();
+        } else if msg.to() == "/profile" {
+            // 表情头像可能有变化，推送给订阅了在线名单的连接；头像未变的更新由客户端忽略
+            self.push_roster(RosterChange::Status, &session.username);
+        }
+        self.reply(&session.username, response).await;
+    }
+
+    /// `/list`：在线用户列表
+    fn list_response(&self, lang: Lang) -> String {
+        let online_list: Vec<String> = self
+            .roster()
+            .iter()
+            .map(|outbox| match self.is_bot(&outbox.name) {
+                true => tr!(lang => "cmd.list_bot", user = outbox.name),
+                false => outbox.name.get(),
+            })
+            .collect();
+        // 构造美观的响应消息
+        if online_list.is_empty() {
+            tr!(lang => "cmd.list_empty")
+        } else {
+            format!(
+                "{}\n  › {}",
+                tr!(lang => "cmd.list", count = online_list.len()),
+                online_list.join("\n  › ") // 用箭头符号美化列表
+            )
+        }
+    }
+
+    /// `/peers`：联邦对端服务器的链路状态与在线用户
+    fn peers_response(&self, lang: Lang) -> String {
+        let Some(federation) = &self.federation else {
+            return tr!(lang => "cmd.peers_disabled");
+        };
+        let state = |up: bool| {
+            if up {
+                tr!(lang => "cmd.peer_up")
+            } else {
+                tr!(lang => "cmd.peer_down")
+            }
+        };
+        let mut lines = vec![tr!(lang => "cmd.peers", server = federation.config.name)];
+        for peer in federation.status() {
+            let users = if peer.online.is_empty() {
+                tr!(lang => "cmd.peer_nobody")
+            } else {
+                peer.online
+                    .iter()
+                    .map(|user| format!("{}@{}", user, peer.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let mut line = tr!(lang => "cmd.peer",
+                server = peer.name,
+                outbound = state(peer.outbound),
+                inbound = state(peer.inbound),
+                users = users,
+            );
+            if peer.spooled > 0 {
+                line.push_str(&tr!(lang => "cmd.peer_spooled", count = peer.spooled));
+            }
+            lines.push(line);
+        }
+        lines.join("\n  › ")
+    }
+
+    /// `/whois <用户>`：按目标用户的隐私设置汇总其资料；管理员还能看到在线用户的来源地址与 GeoIP 信息
+    fn whois_response(&self, session: &Session, target: &str) -> String {
+        let lang = session.lang;
+        if target.is_empty() {
+            return tr!(lang => "cmd.usage_whois");
+        }
+        let name = ArcString::new(target.to_string());
+        let Some(profile) = self.users.get(&name).map(|user| user.profile.clone()) else {
+            return tr!(lang => "server.unknown_user", user = target);
+        };
+        let online = self.online_users.get(&name);
+
+        let mut lines = vec![tr!(lang => "cmd.whois", user = target)];
+        if let Some(display_name) = &profile.display_name {
+            lines.push(tr!(lang => "cmd.whois_display_name", name = display_name));
+        }
+        if let Some(avatar) = &profile.avatar {
+            lines.push(tr!(lang => "cmd.whois_avatar", avatar = avatar));
+        }
+        if let Some(pronouns) = &profile.pronouns {
+            lines.push(tr!(lang => "cmd.whois_pronouns", pronouns = pronouns));
+        }
+        if let Some(bio) = &profile.bio {
+            lines.push(tr!(lang => "cmd.whois_bio", bio = bio));
+        }
+        if let Some(bot) = self.whois_bot(&name, session.role == Role::Admin, lang) {
+            lines.push(bot);
+        }
+        if profile.privacy.show_status {
+            let status = match (&online, self.last_seen_text(&name, lang)) {
+                (Some(user), _) => {
+                    let presence = user.presence.lock().unwrap();
+                    match (&presence.away, presence.dnd) {
+                        (Some(text), _) => tr!(lang => "cmd.status_away", text = text),
+                        (None, true) => tr!(lang => "cmd.status_dnd"),
+                        (None, false) => tr!(lang => "cmd.status_online"),
+                    }
+                }
+                (None, Some(last_seen)) => {
+                    tr!(lang => "cmd.status_offline_since", last_seen = last_seen)
+                }
+                (None, None) => tr!(lang => "cmd.status_offline"),
+            };
+            lines.push(tr!(lang => "cmd.whois_status", status = status));
+        }
+        if let Some(user) = &online {
+            if profile.privacy.show_idle {
+                let idle = humanize_duration_in(lang, user.stats.idle_for());
+                lines.push(tr!(lang => "cmd.whois_idle", idle = idle));
+            }
+            if profile.privacy.show_client && !user.client.is_empty() {
+                lines.push(tr!(lang => "cmd.whois_client", client = user.client));
+            }
+            if session.role == Role::Admin {
+                lines.push(tr!(lang => "cmd.whois_origin", origin = user.origin()));
+            }
+        }
+        lines.join("\n  › ")
+    }
+
+    /// `/profile <项目> [<内容>]`：修改自己的资料，内容为空时清除该项
+    fn update_profile(&self, username: &ArcString, args: &str, lang: Lang) -> String {
+        let (field, value) = args.split_once(' ').unwrap_or((args, ""));
+        let value = value.trim();
+        match field {
+            "name" | "avatar" | "pronouns" | "bio" => {
+                let set = self
+                    .users
+                    .entry(username.clone())
+                    .or_default()
+                    .profile
+                    .set(field, value);
+                match set {
+                    Ok(()) => {
+                        self.save_user(username);
+                        match (field, value.is_empty()) {
+                            ("name", true) => tr!(lang => "cmd.display_name_cleared"),
+                            ("name", false) => tr!(lang => "cmd.display_name_set", name = value),
+                            (_, true) => tr!(lang => "cmd.profile_cleared", field = field),
+                            (_, false) => {
+                                tr!(lang => "cmd.profile_set", field = field, value = value)
+                            }
+                        }
+                    }
+                    Err(FieldError::TooLong(max)) => {
+                        tr!(lang => "cmd.profile_too_long", field = field, max = max)
+                    }
+                    Err(FieldError::BadAvatar) => tr!(lang => "cmd.profile_bad_avatar",
+                        chars = MAX_EMOJI_AVATAR_CHARS,
+                        len = MAX_AVATAR_URL_LEN,
+                    ),
+                    Err(FieldError::Control) => tr!(lang => "cmd.profile_control", field = field),
+                    Err(FieldError::Unknown) => tr!(lang => "cmd.usage_profile"),
+                }
+            }
+            _ => tr!(lang => "cmd.usage_profile"),
+        }
+    }
+
+    /// `/privacy [<项目> <on|off>]`：查看或修改自己的隐私设置
+    fn update_privacy(&self, username: &ArcString, args: &str, lang: Lang) -> String {
+        let mut user = self.users.entry(username.clone()).or_default();
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (None, _) => {
+                let show = |visible: bool| match visible {
+                    true => tr!(lang => "cmd.privacy_public"),
+                    false => tr!(lang => "cmd.privacy_hidden"),
+                };
+                let privacy = &user.profile.privacy;
+                format!(
+                    "{}\n  › status: {}\n  › idle: {}\n  › client: {}",
+                    tr!(lang => "cmd.privacy"),
+                    show(privacy.show_status),
+                    show(privacy.show_idle),
+                    show(privacy.show_client)
+                )
+            }
+            (Some(field), Some(value @ ("on" | "off"))) => {
+                if user.profile.privacy.set(field, value == "on") {
+                    drop(user);
+                    self.save_user(username);
+                    tr!(lang => "cmd.privacy_updated", field = field, value = value)
+                } else {
+                    tr!(lang => "cmd.privacy_unknown", field = field)
+                }
+            }
+            _ => tr!(lang => "cmd.usage_privacy"),
+        }
+    }
+
+    /// `/notify [...]`：查看或修改自己的通知偏好
+    fn update_notify(&self, username: &ArcString, args: &str, lang: Lang) -> String {
+        let mut user = self.users.entry(username.clone()).or_default();
+        let prefs = &mut user.profile.notify;
+        let (action, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let response = match (action, rest.trim()) {
+            ("", _) => {
+                let none = tr!(lang => "cmd.notify_none");
+                let list = |items: Vec<&String>| match items.is_empty() {
+                    true => none.clone(),
+                    false => items
+                        .iter()
+                        .map(|item| item.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                };
+                let muted = list(prefs.muted.iter().collect());
+                let keywords = list(prefs.keywords.iter().collect());
+                let quiet = prefs
+                    .quiet_hours
+                    .map_or(none.clone(), |quiet| quiet.to_string());
+                return format!(
+                    "{}\n  › {}\n  › {}\n  › {}",
+                    tr!(lang => "cmd.notify"),
+                    tr!(lang => "cmd.notify_muted", list = muted),
+                    tr!(lang => "cmd.notify_keywords", list = keywords),
+                    tr!(lang => "cmd.notify_quiet", hours = quiet),
+                );
+            }
+            ("mute", name) if !name.is_empty() => {
+                let name = &parse_room(name).unwrap_or_else(|| name.to_string());
+                if !prefs.mute(name) {
+                    return tr!(lang => "cmd.notify_already_muted", conversation = name);
+                }
+                tr!(lang => "cmd.notify_muted_set", conversation = name)
+            }
+            ("unmute", name) if !name.is_empty() => {
+                let name = &parse_room(name).unwrap_or_else(|| name.to_string());
+                if !prefs.unmute(name) {
+                    return tr!(lang => "cmd.notify_not_muted", conversation = name);
+                }
+                tr!(lang => "cmd.notify_unmuted", conversation = name)
+            }
+            ("keyword", rest) => {
+                let (op, keyword) = rest.split_once(' ').unwrap_or((rest, ""));
+                let keyword = keyword.trim();
+                let changed = match op {
+                    _ if keyword.is_empty() => return tr!(lang => "cmd.usage_notify"),
+                    "add" => prefs.add_keyword(keyword),
+                    "remove" => prefs.remove_keyword(keyword),
+                    _ => return tr!(lang => "cmd.usage_notify"),
+                };
+                match (changed, op) {
+                    (false, _) => {
+                        return tr!(lang => "cmd.notify_keyword_unchanged", keyword = keyword)
+                    }
+                    (true, "add") => tr!(lang => "cmd.notify_keyword_added", keyword = keyword),
+                    (true, _) => tr!(lang => "cmd.notify_keyword_removed", keyword = keyword),
+                }
+            }
+            ("quiet", "off") => {
+                prefs.quiet_hours = None;
+                tr!(lang => "cmd.notify_quiet_cleared")
+            }
+            ("quiet", spec) => match QuietHours::from_string(spec) {
+                Some(quiet) => {
+                    prefs.quiet_hours = Some(quiet);
+                    tr!(lang => "cmd.notify_quiet_set", hours = quiet)
+                }
+                None => return tr!(lang => "cmd.notify_invalid_quiet", value = spec),
+            },
+            _ => return tr!(lang => "cmd.usage_notify"),
+        };
+        drop(user);
+        self.save_user(username);
+        response
+    }
+
+    /// `/away [<自动回复内容>]`：设置或取消离开状态
+    fn update_away(session: &Session, text: &str) -> String {
+        let mut presence = session.presence.lock().unwrap();
+        if text.is_empty() {
+            presence.set_away(None);
+            tr!(session.lang => "cmd.away_cleared")
+        } else {
+            presence.set_away(Some(text.to_string()));
+            tr!(session.lang => "cmd.away_set", text = text)
+        }
+    }
+
+    /// `/dnd [on|off]`：开启或关闭免打扰；关闭时补发暂存的消息
+    async fn update_dnd(&self, session: &Session, args: &str) -> String {
+        let (dnd, queued) = {
+            let mut presence = session.presence.lock().unwrap();
+            let dnd = match args {
+                "" => !presence.dnd,
+                "on" => true,
+                "off" => false,
+                _ => return tr!(session.lang => "cmd.usage_dnd"),
+            };
+            (dnd, presence.set_dnd(dnd))
+        };
+        if dnd {
+            return tr!(session.lang => "cmd.dnd_on");
+        }
+        let count = queued.len();
+        let outbox = self
+            .online_users
+            .get(&session.username)
+            .map(|user| user.outbox(&session.username));
+        if let Some(outbox) = outbox {
+            for msg in queued {
+                if let Err((msg, reason)) = self.deliver(&outbox, msg).await {
+                    self.dead_letters.push(msg, reason);
+                }
+            }
+        }
+        tr!(session.lang => "cmd.dnd_off", count = count)
+    }
+
+    /// `/stats [<用户>]`：连接的收发统计
+    fn stats_response(&self, session: &Session, target: &str) -> String {
+        let lang = session.lang;
+        let target = match target {
+            "" => session.username.clone(),
+            name if name == session.username.get() || session.role == Role::Admin => {
+                ArcString::new(name.to_string())
+            }
+            _ => return tr!(lang => "cmd.stats_denied"),
+        };
+        let mut response = match self.online_users.get(&target) {
+            Some(user) => format!(
+                "{}\n  › {}\n  › {}",
+                tr!(lang => "cmd.stats", user = target),
+                tr!(lang => "cmd.stats_addr", addr = user.origin()),
+                user.stats.summary(lang)
+            ),
+            None => tr!(lang => "server.offline", user = target),
+        };
+        if session.role == Role::Admin {
+            response.push('\n');
+            response.push_str(&tr!(lang => "cmd.stats_panics", count = self.handler_panics()));
+            let accepts = self.accept_stats();
+            let refusing = if accepts.refusing {
+                tr!(lang => "server.refusing_now")
+            } else {
+                String::new()
+            };
+            response.push('\n');
+            response.push_str(&tr!(
+                lang => "cmd.stats_accept",
+                errors = accepts.accept_errors,
+                refused = accepts.refused,
+                refusing = refusing
+            ));
+        }
+        response
+    }
+
+    /// `/announce <公告>`：通过系统通知通道发布公告，各用户以自己的语言收到公告标题
+    fn announce(&self, session: &Session, args: &str) -> String {
+        if args.is_empty() {
+            return tr!(session.lang => "cmd.usage_announce");
+        }
+        let count = self.publish(Notice::Announcement {
+            from: session.username.clone(),
+            text: args.to_string(),
+        });
+        tr!(session.lang => "cmd.announced", count = count)
+    }
+
+    /// `/invite [<次数> [<有效小时数>]|list|revoke <邀请码>]`：生成、查看或撤销邀请码
+    ///
+    /// 不带参数时生成一个 [`DEFAULT_INVITE_HOURS`] 小时内有效的一次性邀请码；
+    /// 有效小时数为 0 表示不过期。
+    fn invite(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        match parts.next() {
+            Some("list") => {
+                let invites = self.invites.list();
+                if invites.is_empty() {
+                    return tr!(lang => "cmd.invite_empty");
+                }
+                let lines: Vec<String> = invites
+                    .iter()
+                    .map(|invite| {
+                        let expires = match invite.expires_at {
+                            Some(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+                            None => tr!(lang => "cmd.invite_never"),
+                        };
+                        tr!(lang => "cmd.invite_entry",
+                            code = invite.code,
+                            used = invite.used,
+                            max = invite.max_uses,
+                            expires = expires,
+                            admin = invite.created_by,
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{}\n  › {}",
+                    tr!(lang => "cmd.invite_list", count = invites.len()),
+                    lines.join("\n  › ")
+                )
+            }
+            Some("revoke") => match parts.next() {
+                Some(code) if self.invites.revoke(code) => {
+                    tr!(lang => "cmd.invite_revoked", code = code)
+                }
+                Some(code) => tr!(lang => "cmd.invite_missing", code = code),
+                None => tr!(lang => "cmd.usage_invite"),
+            },
+            uses => {
+                let uses = uses.map_or(Some(1), |uses| uses.parse::<u32>().ok());
+                let hours = parts.next().map_or(Some(DEFAULT_INVITE_HOURS), |hours| {
+                    hours.parse::<u64>().ok()
+                });
+                let (Some(uses @ 1..), Some(hours), None) = (uses, hours, parts.next()) else {
+                    return tr!(lang => "cmd.usage_invite");
+                };
+                let ttl = (hours > 0).then(|| Duration::from_secs(hours * 3600));
+                let invite = match self
+                    .invites
+                    .create(&session.username.get(), None, uses, ttl)
+                {
+                    Ok(invite) => invite,
+                    Err(e) => return tr!(lang => "cmd.invite_failed", error = e),
+                };
+                let mut reply = tr!(lang => "cmd.invite_created",
+                    code = invite.code,
+                    max = invite.max_uses,
+                    expires = invite.expires_at.map_or_else(
+                        || tr!(lang => "cmd.invite_never"),
+                        |at| at.format("%Y-%m-%d %H:%M").to_string(),
+                    ),
+                );
+                if !self.config.invite_only {
+                    reply.push('\n');
+                    reply.push_str(&tr!(lang => "cmd.invite_not_required"));
+                }
+                reply
+            }
+        }
+    }
+
+    /// `/deadletter [replay <编号>|drop <编号>|clear]`：查看或处理死信队列
+    async fn dead_letter(&self, args: &str, lang: Lang) -> String {
+        let mut parts = args.split_whitespace();
+        let action = parts.next();
+        let id = parts
+            .next()
+            .and_then(|id| id.trim_start_matches('#').parse().ok());
+        match (action, id) {
+            (None, _) => {
+                let entries = self.dead_letters.list();
+                if entries.is_empty() {
+                    return tr!(lang => "cmd.deadletter_empty");
+                }
+                let lines: Vec<String> = entries
+                    .iter()
+                    .map(|entry| {
+                        format!(
+                            "#{} [{}] {} -> {}: {}（{}）",
+                            entry.id,
+                            entry.failed_at.format("%Y-%m-%d %H:%M:%S"),
+                            entry.message.from(),
+                            entry.message.to(),
+                            entry.message.content(),
+                            entry.reason.describe(lang)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{}\n  › {}",
+                    tr!(lang => "cmd.deadletter", count = entries.len()),
+                    lines.join("\n  › ")
+                )
+            }
+            (Some("replay"), Some(id)) => {
+                let Some(entry) = self.dead_letters.take(id) else {
+                    return tr!(lang => "cmd.deadletter_missing", id = id);
+                };
+                let recipient = ArcString::new(entry.message.to().to_string());
+                let outbox = self
+                    .online_users
+                    .get(&recipient)
+                    .map(|user| user.outbox(&recipient));
+                let Some(outbox) = outbox else {
+                    let new_id = self.dead_letters.push(entry.message, entry.reason);
+                    return tr!(lang => "cmd.deadletter_offline", user = recipient, id = new_id);
+                };
+                match self.deliver(&outbox, entry.message).await {
+                    Ok(()) => tr!(lang => "cmd.deadletter_replayed", id = id, user = recipient),
+                    Err((msg, reason)) => {
+                        let new_id = self.dead_letters.push(msg, reason);
+                        tr!(lang => "cmd.deadletter_replay_failed",
+                            reason = reason.describe(lang),
+                            id = new_id,
+                        )
+                    }
+                }
+            }
+            (Some("drop"), Some(id)) => match self.dead_letters.take(id) {
+                Some(_) => tr!(lang => "cmd.deadletter_dropped", id = id),
+                None => tr!(lang => "cmd.deadletter_missing", id = id),
+            },
+            (Some("clear"), _) => {
+                tr!(lang => "cmd.deadletter_cleared", count = self.dead_letters.clear())
+            }
+            _ => tr!(lang => "cmd.usage_deadletter"),
+        }
+    }
+}
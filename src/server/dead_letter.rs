@@ -0,0 +1,146 @@
+/*!
+# 死信队列
+
+无法投递且不会自动重试的消息（接收方队列已满且策略为丢弃、接收方连接已断开、
+接收方处理过慢被断开、编码后超过帧长度上限、免打扰期间暂存过多或暂存期间断开连接）连同失败原因保存在这里，
+管理员可以通过 `/deadletter` 查看、重新投递或清除。队列只保存在内存中，超过容量时丢弃最早的条目。
+*/
+
+use crate::i18n::{self, Lang};
+use crate::{tr, Message};
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+
+/// 死信队列的默认容量
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// 投递失败的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// 接收方发送队列已满，且配置为丢弃
+    QueueFull,
+    /// 接收方连接已断开
+    Disconnected,
+    /// 接收方长时间跟不上消息速度，已被断开
+    SlowConsumer,
+    /// 编码后的帧长度超过上限
+    TooLarge(usize),
+    /// 接收方免打扰期间暂存的消息过多，最早的一条被挤出
+    HeldOverflow,
+}
+
+impl Reason {
+    /// 以 `lang` 描述失败原因
+    pub fn describe(&self, lang: Lang) -> String {
+        match self {
+            Reason::QueueFull => tr!(lang => "reason.queue_full"),
+            Reason::Disconnected => tr!(lang => "reason.disconnected"),
+            Reason::SlowConsumer => tr!(lang => "reason.slow_consumer"),
+            Reason::TooLarge(len) => tr!(lang => "reason.too_large", len = len),
+            Reason::HeldOverflow => tr!(lang => "reason.held_overflow"),
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(i18n::lang()))
+    }
+}
+
+/// 死信队列中的一条消息
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    /// 条目编号，从 1 开始递增
+    pub id: u64,
+    /// 进入队列的时间
+    pub failed_at: DateTime<Local>,
+    pub message: Message,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: u64,
+    entries: VecDeque<DeadLetter>,
+}
+
+/// 有容量上限的死信队列
+#[derive(Debug)]
+pub struct DeadLetters {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+impl DeadLetters {
+    /// 创建容量为 `capacity` 的死信队列
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// 记录一条投递失败的消息，返回其编号
+    pub fn push(&self, message: Message, reason: Reason) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        println!(
+            "{}",
+            tr!(
+                "server.dead_letter",
+                id = id,
+                from = message.from(),
+                to = message.to(),
+                reason = reason,
+            )
+        );
+        if inner.entries.len() == self.capacity {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(DeadLetter {
+            id,
+            failed_at: Local::now(),
+            message,
+            reason,
+        });
+        id
+    }
+
+    /// 当前所有条目（按进入队列的顺序）
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.inner.lock().unwrap().entries.iter().cloned().collect()
+    }
+
+    /// 取出编号为 `id` 的条目
+    pub fn take(&self, id: u64) -> Option<DeadLetter> {
+        let mut inner = self.inner.lock().unwrap();
+        let index = inner.entries.iter().position(|entry| entry.id == id)?;
+        inner.entries.remove(index)
+    }
+
+    /// 清除进入队列早于 `before` 的条目，返回清除的条目数
+    pub fn expire(&self, before: DateTime<Local>) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.entries.len();
+        inner.entries.retain(|entry| entry.failed_at >= before);
+        count - inner.entries.len()
+    }
+
+    /// 清空队列，返回清除的条目数
+    pub fn clear(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.entries.len();
+        inner.entries.clear();
+        count
+    }
+}
+
+impl Default for DeadLetters {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
@@ -0,0 +1,53 @@
+/*!
+# 端到端加密的密钥交换转发
+
+服务器不参与端到端加密（见 [`crate::e2e`]），只在声明了 `e2e` 特性的两个在线用户之间
+转发 [`Control::KeyExchange`]，并在转发时把 `peer` 换成发送方。交换内容由双方的身份密钥签名，
+服务器无法替换其中的临时公钥。对方不在线或未声明该特性时，发送方收到 `E2E_UNAVAILABLE` 错误。
+*/
+
+use super::Server;
+use crate::protocol::{Control, ErrorCode, FEATURE_E2E};
+use crate::session::Session;
+use crate::{tr, ArcString};
+
+impl Server {
+    /// 转发客户端发来的密钥交换帧；未协商 `e2e` 特性的连接发来的帧被忽略
+    pub(super) async fn handle_key_exchange(&self, session: &Session, control: Control) {
+        let Control::KeyExchange {
+            peer,
+            key,
+            reply,
+            signer,
+            sig,
+        } = control
+        else {
+            return;
+        };
+        if !session.has_feature(FEATURE_E2E) {
+            return;
+        }
+        let peer = ArcString::new(peer);
+        if peer == session.username {
+            return;
+        }
+        let supported = self
+            .online_users
+            .get(&peer)
+            .map(|target| target.features.iter().any(|f| f == FEATURE_E2E));
+        if supported != Some(true) {
+            let text = tr!(session.lang => "e2e.unavailable", user = peer);
+            self.reject(session, ErrorCode::E2eUnavailable, Some(&peer), None, text)
+                .await;
+            return;
+        }
+        let exchange = Control::KeyExchange {
+            peer: session.username.get(),
+            key,
+            reply,
+            signer,
+            sig,
+        };
+        self.send_control(&peer, exchange).await;
+    }
+}
@@ -0,0 +1,773 @@
+/*!
+# 服务器联邦
+
+彼此独立的服务器可以组成联邦：服务器 A 上的用户向 `bob@B` 发送消息时，
+A 通过与 B 之间的链路把消息转发给 B，再由 B 投递给本地用户 `bob`，
+B 上的用户看到的发送者为 `alice@A`，可以直接回复。
+
+## 链路
+
+每个配置的对端（[`PeerConfig`]）由 [`Server::run`](super::Server::run) 启动一个出站链路任务，
+连接对端的普通监听端口；对端未配置地址时按服务器名解析 DNS，连接
+`服务器名:`[`DEFAULT_PEER_PORT`]。链路断开后按指数退避重连。
+出站链路只用于发送：转发的消息（[`Control::Relay`]）与本服务器的在线用户
+（[`Control::Presence`]，上线或下线时重新发送）；对端发来的消息与在线用户经由对端自己的出站链路，
+即本服务器的入站链路到达。
+
+## 认证
+
+连入方发送声明了 `federation` 特性、`name` 为自己服务器名的握手帧。双方各出一个随机数，
+以该对端配置的共享密钥计算 `SHA-256("{密钥}:{随机数}:{服务器名}")` 互相证明身份
+（[`Control::PeerChallenge`] → [`Control::PeerAuth`] → [`Control::PeerWelcome`]），
+未配置的服务器或密钥不符的连接收到 `PEER_AUTH_FAILED` 后断开。
+
+## 环路防护
+
+转发的消息在 `via` 中依次记录经过的服务器。收到转发时要求：最后一跳是该链路认证过的对端，
+发送者所在的服务器与 `via` 的起点一致，`via` 中不含本服务器，且跳数不超过 [`MAX_HOPS`]。
+收件服务器不是本服务器时，若本服务器与其有链路则继续转发，否则交给配置的上游对端
+（[`FederationConfig::upstream`]），都没有时丢弃并记录日志。
+
+## 路由追踪
+
+开启了路由追踪（见 `trace` 子模块）的服务器转发的消息带有路由记录；继续转发时追加本服务器的记录，
+收件服务器记录后以 [`Control::TraceReport`] 报告回发送者所在的服务器，并在投递前去掉消息上的记录。
+
+## 暂存转发
+
+配置了暂存目录（[`FederationConfig::spool`]）时，链路中断期间发往对端的消息写入磁盘，
+链路恢复后按原顺序转发，发送者收到 `OFFLINE_QUEUED` 提示。配合上游对端，可以在站点之间
+部署一台轻量的中继服务器：各站点把它设为上游，由它暂存并转发跨站点的消息，
+承受站点之间不稳定的链路。
+*/
+
+use super::spool::Spool;
+use super::{hello_lang, refuse, sanitized, Server};
+use crate::codec::{Codec, CodecError, FrameReader, FrameWriter};
+use crate::i18n::Lang;
+use crate::protocol::{
+    Control, ErrorCode, Frame, Hello, Recipient, TraceEvent, FEATURE_ERRORS, FEATURE_FEDERATION,
+};
+use crate::session::Session;
+use crate::transport::{self, BoxReader, BoxWriter};
+use crate::{pow, tr, ArcString, Message, Timestamp};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// 对端未配置地址时连接的端口
+pub const DEFAULT_PEER_PORT: u16 = 7891;
+
+/// 一条消息最多经过的服务器数量
+pub const MAX_HOPS: usize = 4;
+
+/// 建立出站链路（解析、连接与认证）的超时时间
+const PEER_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 出站链路断开后首次重连前的等待时间，之后每次失败翻倍
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+
+/// 重连等待时间的上限
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+
+/// 出站链路发送队列的容量
+const LINK_QUEUE: usize = 256;
+
+/// 出站链路的错误，需要在链路任务之间传递
+type LinkError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 一个对端服务器的配置
+#[derive(Clone)]
+pub struct PeerConfig {
+    /// 对端的服务器名，即其用户地址中 `@` 之后的部分
+    pub name: String,
+    /// 对端的地址（`主机:端口`），`None` 表示按服务器名解析
+    pub addr: Option<String>,
+    /// 与对端共享的密钥
+    pub secret: String,
+}
+
+impl fmt::Debug for PeerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerConfig")
+            .field("name", &self.name)
+            .field("addr", &self.addr)
+            .field("secret", &"***")
+            .finish()
+    }
+}
+
+impl PeerConfig {
+    /// 解析 `名称=密钥` 或 `名称=密钥@主机:端口` 形式的对端配置
+    pub fn from_string(spec: &str) -> Option<PeerConfig> {
+        let (name, rest) = spec.split_once('=')?;
+        let (secret, addr) = match rest.split_once('@') {
+            Some((secret, addr)) => (secret, Some(addr.trim().to_string())),
+            None => (rest, None),
+        };
+        let (name, secret) = (name.trim(), secret.trim());
+        if name.is_empty() || secret.is_empty() || addr.as_deref() == Some("") {
+            return None;
+        }
+        Some(PeerConfig {
+            name: name.to_string(),
+            addr,
+            secret: secret.to_string(),
+        })
+    }
+
+    /// 连接对端时使用的地址
+    pub fn addr(&self) -> String {
+        self.addr
+            .clone()
+            .unwrap_or_else(|| format!("{}:{}", self.name, DEFAULT_PEER_PORT))
+    }
+}
+
+/// 服务器联邦配置
+#[derive(Clone, Debug, Default)]
+pub struct FederationConfig {
+    /// 本服务器的名称，本服务器用户的地址为 `用户@名称`
+    pub name: String,
+    /// 对端服务器
+    pub peers: Vec<PeerConfig>,
+    /// 上游对端的名称：发往非对端服务器的消息交给上游转发，`None` 表示直接拒绝
+    pub upstream: Option<String>,
+    /// 暂存目录：链路中断期间发往对端的消息写入此处，恢复后再转发（见 `spool` 子模块），
+    /// `None` 表示不暂存、直接告知发送者
+    pub spool: Option<PathBuf>,
+}
+
+impl FederationConfig {
+    /// 按服务器名查找对端，服务器名不区分大小写
+    fn peer(&self, name: &str) -> Option<&PeerConfig> {
+        self.peers
+            .iter()
+            .find(|peer| peer.name.eq_ignore_ascii_case(name))
+    }
+
+    /// 发往服务器 `name` 的消息经由的对端：`name` 本身是对端时直接发送，否则交给上游
+    fn route(&self, name: &str) -> Option<&PeerConfig> {
+        self.peer(name)
+            .or_else(|| self.peer(self.upstream.as_deref()?))
+    }
+}
+
+/// 以共享密钥证明 `name` 的身份
+fn auth_mac(secret: &str, nonce: &str, name: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(secret.as_bytes())
+        .chain_update(b":")
+        .chain_update(nonce.as_bytes())
+        .chain_update(b":")
+        .chain_update(name.as_bytes())
+        .finalize();
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 校验对端的证明，比较时间与内容无关
+fn verify_mac(secret: &str, nonce: &str, name: &str, mac: &str) -> bool {
+    let expected = auth_mac(secret, nonce, name);
+    expected.len() == mac.len()
+        && expected
+            .bytes()
+            .zip(mac.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// 消息无法转发给对端服务器的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelayError {
+    /// 收件服务器不是配置的对端，且未配置上游
+    UnknownPeer,
+    /// 出站链路未建立且消息未能暂存，或发送队列已满
+    Unavailable,
+}
+
+impl RelayError {
+    /// 以 `lang` 描述原因
+    pub fn describe(&self, lang: Lang, server: &str) -> String {
+        match self {
+            RelayError::UnknownPeer => tr!(lang => "federation.unknown_peer", server = server),
+            RelayError::Unavailable => tr!(lang => "federation.unavailable", server = server),
+        }
+    }
+}
+
+/// 消息交给对端服务器的方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relayed {
+    /// 已放入出站链路的发送队列
+    Sent,
+    /// 链路中断，已写入暂存目录，恢复后转发
+    Spooled,
+}
+
+/// 一个对端服务器的链路状态
+#[derive(Debug, Default)]
+struct PeerState {
+    /// 出站链路的发送队列，链路未建立时为 `None`
+    outbound: Option<mpsc::Sender<Control>>,
+    /// 对端连入本服务器的入站链路是否已建立
+    inbound: bool,
+    /// 对端最近一次发来的在线用户
+    online: Vec<String>,
+}
+
+/// 对端服务器的状态（`/peers` 使用）
+#[derive(Clone, Debug)]
+pub struct PeerStatus {
+    pub name: String,
+    /// 出站链路已建立
+    pub outbound: bool,
+    /// 入站链路已建立
+    pub inbound: bool,
+    /// 对端的在线用户
+    pub online: Vec<String>,
+    /// 暂存待转发的消息数
+    pub spooled: usize,
+}
+
+/// 联邦的运行状态
+#[derive(Debug)]
+pub(super) struct Federation {
+    pub(super) config: FederationConfig,
+    peers: Mutex<HashMap<String, PeerState>>,
+    /// 链路中断期间待转发的消息
+    spool: Option<Spool>,
+    /// 本服务器在线用户变化时递增，出站链路据此重新发送在线用户
+    roster: watch::Sender<u64>,
+    /// 关闭服务器时置为 `true`，入站链路随之结束
+    closed: watch::Sender<bool>,
+}
+
+impl Federation {
+    pub(super) fn new(config: FederationConfig) -> Self {
+        Self {
+            spool: config.spool.as_ref().map(Spool::new),
+            config,
+            peers: Mutex::new(HashMap::new()),
+            roster: watch::channel(0).0,
+            closed: watch::channel(false).0,
+        }
+    }
+
+    /// 本服务器的在线用户发生变化
+    pub(super) fn roster_changed(&self) {
+        self.roster.send_modify(|generation| *generation += 1);
+    }
+
+    /// 结束所有入站链路
+    pub(super) fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// 经出站链路把控制帧发往服务器 `server`（不是对端时交给上游），链路中断时写入暂存目录
+    pub(super) fn send(&self, server: &str, control: Control) -> Result<Relayed, RelayError> {
+        let Some(peer) = self.config.route(server) else {
+            return Err(RelayError::UnknownPeer);
+        };
+        // 持有锁期间写入暂存，保证与链路建立时读出暂存的顺序一致
+        let peers = self.peers.lock().unwrap();
+        let outbound = peers
+            .get(&peer.name)
+            .and_then(|peer| peer.outbound.as_ref());
+        match outbound {
+            Some(tx) => tx
+                .try_send(control)
+                .map(|()| Relayed::Sent)
+                .map_err(|_| RelayError::Unavailable),
+            None if self.spool_push(&peer.name, &control) => Ok(Relayed::Spooled),
+            None => Err(RelayError::Unavailable),
+        }
+    }
+
+    /// 写入暂存目录，未配置暂存、暂存已满或写入失败时返回 `false`
+    fn spool_push(&self, peer: &str, control: &Control) -> bool {
+        let Some(spool) = &self.spool else {
+            return false;
+        };
+        match spool.push(peer, control) {
+            Ok(true) => true,
+            Ok(false) => {
+                eprintln!("{}", tr!("server.spool_full", server = peer));
+                false
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("server.spool_failed", server = peer, error = e));
+                false
+            }
+        }
+    }
+
+    fn set_outbound(&self, server: &str, outbound: mpsc::Sender<Control>) {
+        let mut peers = self.peers.lock().unwrap();
+        peers.entry(server.to_string()).or_default().outbound = Some(outbound);
+    }
+
+    /// 出站链路断开：不再接受新消息，发送队列中尚未发出的消息写入暂存目录
+    fn unlink(&self, server: &str, rx: &mut mpsc::Receiver<Control>) {
+        let mut peers = self.peers.lock().unwrap();
+        peers.entry(server.to_string()).or_default().outbound = None;
+        rx.close();
+        while let Ok(control) = rx.try_recv() {
+            self.spool_push(server, &control);
+        }
+    }
+
+    /// 记录入站链路的建立或断开；断开时清空对端的在线用户
+    fn set_inbound(&self, server: &str, inbound: bool) {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = peers.entry(server.to_string()).or_default();
+        peer.inbound = inbound;
+        if !inbound {
+            peer.online.clear();
+        }
+    }
+
+    fn set_presence(&self, server: &str, mut users: Vec<String>) {
+        users.sort();
+        let mut peers = self.peers.lock().unwrap();
+        peers.entry(server.to_string()).or_default().online = users;
+    }
+
+    /// 各对端服务器的状态，按配置顺序排列
+    pub(super) fn status(&self) -> Vec<PeerStatus> {
+        let peers = self.peers.lock().unwrap();
+        self.config
+            .peers
+            .iter()
+            .map(|config| {
+                let state = peers.get(&config.name);
+                PeerStatus {
+                    name: config.name.clone(),
+                    outbound: state.is_some_and(|state| state.outbound.is_some()),
+                    inbound: state.is_some_and(|state| state.inbound),
+                    online: state.map(|state| state.online.clone()).unwrap_or_default(),
+                    spooled: self
+                        .spool
+                        .as_ref()
+                        .map_or(0, |spool| spool.count(&config.name)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Server {
+    /// 为每个配置的对端启动出站链路任务
+    pub(super) fn spawn_peer_links(&self) -> Vec<tokio::task::JoinHandle<()>> {
+        let Some(federation) = &self.federation else {
+            return Vec::new();
+        };
+        federation
+            .config
+            .peers
+            .iter()
+            .map(|peer| {
+                let (server, peer) = (self.clone(), peer.clone());
+                tokio::spawn(async move { server.peer_link(peer).await })
+            })
+            .collect()
+    }
+
+    /// 维持到 `peer` 的出站链路，断开后按指数退避重连
+    async fn peer_link(&self, peer: PeerConfig) {
+        let mut backoff = RECONNECT_MIN;
+        loop {
+            let addr = peer.addr();
+            let connecting = self.connect_peer(&peer, &addr);
+            match tokio::time::timeout(PEER_CONNECT_TIMEOUT, connecting).await {
+                Ok(Ok((reader, writer))) => {
+                    backoff = RECONNECT_MIN;
+                    println!(
+                        "{}",
+                        tr!("server.peer_linked", server = peer.name, addr = addr)
+                    );
+                    let (tx, mut rx) = mpsc::channel(LINK_QUEUE);
+                    let result = self.run_peer_link(&peer, reader, writer, tx, &mut rx).await;
+                    if let Some(federation) = &self.federation {
+                        federation.unlink(&peer.name, &mut rx);
+                    }
+                    let error = match result {
+                        Ok(()) => tr!("server.peer_closed"),
+                        Err(e) => e.to_string(),
+                    };
+                    println!(
+                        "{}",
+                        tr!("server.peer_unlinked", server = peer.name, error = error)
+                    );
+                }
+                Ok(Err(e)) => eprintln!(
+                    "{}",
+                    tr!(
+                        "server.peer_link_failed",
+                        server = peer.name,
+                        addr = addr,
+                        error = e
+                    )
+                ),
+                Err(_) => eprintln!(
+                    "{}",
+                    tr!(
+                        "server.peer_link_failed",
+                        server = peer.name,
+                        addr = addr,
+                        error = tr!("server.peer_timeout"),
+                    )
+                ),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    }
+
+    /// 连接对端并完成双向认证
+    async fn connect_peer(
+        &self,
+        peer: &PeerConfig,
+        addr: &str,
+    ) -> Result<(FrameReader<BoxReader>, FrameWriter<BoxWriter>), LinkError> {
+        let federation = self.federation.as_ref().ok_or(tr!("server.peer_closed"))?;
+        let (reader, writer) = transport::connect(addr, None, &self.config.socket, None).await?;
+        let mut reader = FrameReader::new(reader, Codec::JsonLines);
+        let mut writer = FrameWriter::new(writer, Codec::JsonLines);
+        let features = vec![FEATURE_FEDERATION.to_string(), FEATURE_ERRORS.to_string()];
+        let hello = Hello::new(federation.config.name.clone(), features);
+        writer.write_frame(&hello).await?;
+
+        let challenge = match reader.read_frame::<Frame>().await? {
+            Some(Frame::Control(Control::PeerChallenge { nonce })) => nonce,
+            Some(Frame::Control(Control::Error(e))) => return Err(e.message.into()),
+            _ => return Err(tr!("server.peer_protocol").into()),
+        };
+        let nonce = pow::new_nonce()?;
+        let auth = Control::PeerAuth {
+            mac: auth_mac(&peer.secret, &challenge, &federation.config.name),
+            nonce: nonce.clone(),
+        };
+        writer.write_frame(&Frame::from(auth)).await?;
+        match reader.read_frame::<Frame>().await? {
+            Some(Frame::Control(Control::PeerWelcome { mac }))
+                if verify_mac(&peer.secret, &nonce, &peer.name, &mac) =>
+            {
+                Ok((reader, writer))
+            }
+            Some(Frame::Control(Control::Error(e))) => Err(e.message.into()),
+            _ => Err(tr!("server.peer_auth_failed").into()),
+        }
+    }
+
+    /// 在已认证的出站链路上先发出暂存的消息，再发送新转发的消息与本服务器的在线用户，直到链路断开
+    async fn run_peer_link(
+        &self,
+        peer: &PeerConfig,
+        mut reader: FrameReader<BoxReader>,
+        mut writer: FrameWriter<BoxWriter>,
+        tx: mpsc::Sender<Control>,
+        rx: &mut mpsc::Receiver<Control>,
+    ) -> Result<(), LinkError> {
+        let Some(federation) = &self.federation else {
+            return Ok(());
+        };
+        federation.set_outbound(&peer.name, tx);
+        let mut roster = federation.roster.subscribe();
+        writer.write_frame(&Frame::from(self.presence())).await?;
+        if let Some(spool) = &federation.spool {
+            // 链路建立后不再有新消息写入暂存，读出的就是全部积压
+            let spooled = spool.load(&peer.name).unwrap_or_else(|e| {
+                eprintln!(
+                    "{}",
+                    tr!("server.spool_failed", server = peer.name, error = e)
+                );
+                Vec::new()
+            });
+            if !spooled.is_empty() {
+                let count = spooled.len();
+                for control in spooled {
+                    writer.write_frame(&Frame::from(control)).await?;
+                }
+                if let Err(e) = spool.clear(&peer.name) {
+                    eprintln!(
+                        "{}",
+                        tr!("server.spool_failed", server = peer.name, error = e)
+                    );
+                }
+                println!(
+                    "{}",
+                    tr!("server.spool_flushed", server = peer.name, count = count)
+                );
+            }
+        }
+
+        // 对端在出站链路上只会发来错误帧或关闭连接；读取在整个链路期间持续进行
+        let closed = async {
+            loop {
+                match reader.read_frame::<Frame>().await {
+                    Ok(None) => return Ok(()),
+                    Ok(Some(Frame::Control(Control::Error(e)))) => {
+                        return Err::<(), LinkError>(e.message.into())
+                    }
+                    Ok(Some(_)) | Err(CodecError::Decode(_)) => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        tokio::pin!(closed);
+        loop {
+            tokio::select! {
+                result = &mut closed => return result,
+                control = rx.recv() => match control {
+                    Some(control) => writer.write_frame(&Frame::from(control)).await?,
+                    None => return Ok(()),
+                },
+                changed = roster.changed() => {
+                    changed?;
+                    writer.write_frame(&Frame::from(self.presence())).await?;
+                }
+            }
+        }
+    }
+
+    /// 本服务器当前的在线用户
+    fn presence(&self) -> Control {
+        let users = self
+            .roster()
+            .iter()
+            .map(|outbox| outbox.name.get())
+            .collect();
+        Control::Presence { users }
+    }
+
+    /// 处理对端服务器连入的入站链路：认证后接收转发的消息与在线用户，直到链路断开
+    pub(super) async fn accept_peer(
+        &self,
+        mut reader: FrameReader<BoxReader>,
+        mut writer: FrameWriter<BoxWriter>,
+        hello: Hello,
+        peer_addr: SocketAddr,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let name = hello.name.trim().to_string();
+        let federation = self.federation.as_ref();
+        let Some((federation, peer)) =
+            federation.and_then(|federation| Some((federation, federation.config.peer(&name)?)))
+        else {
+            println!(
+                "{}",
+                tr!("server.peer_refused", server = name, addr = peer_addr)
+            );
+            let text = tr!(hello_lang(&hello) => "server.peer_auth_failed");
+            return refuse(&mut writer, &hello, ErrorCode::PeerAuthFailed, None, text).await;
+        };
+
+        let nonce = pow::new_nonce()?;
+        let challenge = Control::PeerChallenge {
+            nonce: nonce.clone(),
+        };
+        writer.write_frame(&Frame::from(challenge)).await?;
+        let answer = tokio::time::timeout(PEER_CONNECT_TIMEOUT, reader.read_frame::<Frame>());
+        let welcome = match answer.await {
+            Ok(Ok(Some(Frame::Control(Control::PeerAuth { mac, nonce: theirs }))))
+                if verify_mac(&peer.secret, &nonce, &name, &mac) =>
+            {
+                Control::PeerWelcome {
+                    mac: auth_mac(&peer.secret, &theirs, &federation.config.name),
+                }
+            }
+            _ => {
+                println!(
+                    "{}",
+                    tr!("server.peer_refused", server = name, addr = peer_addr)
+                );
+                let text = tr!(hello_lang(&hello) => "server.peer_auth_failed");
+                return refuse(&mut writer, &hello, ErrorCode::PeerAuthFailed, None, text).await;
+            }
+        };
+        writer.write_frame(&Frame::from(welcome)).await?;
+        println!(
+            "{}",
+            tr!("server.peer_accepted", server = name, addr = peer_addr)
+        );
+
+        federation.set_inbound(&name, true);
+        let mut closed = federation.closed.subscribe();
+        let result = loop {
+            let frame = tokio::select! {
+                frame = reader.read_frame::<Frame>() => frame,
+                _ = closed.wait_for(|closed| *closed) => break Ok(()),
+            };
+            match frame {
+                Ok(None) => break Ok(()),
+                Ok(Some(Frame::Control(Control::Relay { message, via }))) => {
+                    self.accept_relay(&name, message, via).await
+                }
+                Ok(Some(Frame::Control(Control::Presence { users }))) => {
+                    federation.set_presence(&name, users)
+                }
+                Ok(Some(Frame::Control(Control::TraceReport { sender, id, hops }))) => {
+                    self.accept_trace_report(&sender, id, hops)
+                }
+                Ok(Some(Frame::Control(Control::Ping { seq }))) => {
+                    if let Err(e) = writer
+                        .write_frame(&Frame::from(Control::Pong { seq }))
+                        .await
+                    {
+                        break Err(e);
+                    }
+                }
+                Ok(Some(_)) => {}
+                Err(CodecError::Decode(e)) => {
+                    eprintln!("{}", tr!("server.parse_failed", error = format!("{:?}", e)));
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        federation.set_inbound(&name, false);
+        println!("{}", tr!("server.peer_left", server = name));
+        let _ = writer.shutdown().await;
+        Ok(result?)
+    }
+
+    /// 发给 `user@host` 的消息：`host` 是本服务器时返回改写为本地用户 `user` 的消息；
+    /// 否则经联邦转发并返回 `None`，本服务器未加入联邦、`host` 不是对端或链路未建立时告知发送者。
+    /// `received_at` 是收到消息的时间，开启路由追踪时记入路由记录
+    pub(super) async fn route_remote(
+        &self,
+        session: &Session,
+        msg: Message,
+        user: &str,
+        host: &str,
+        received_at: Timestamp,
+    ) -> Option<Message> {
+        let recipient = ArcString::new(msg.to().to_string());
+        let client_id = msg.id();
+        let Some(federation) = &self.federation else {
+            let tip = tr!(session.lang => "federation.unknown_host", host = host);
+            self.reject(
+                session,
+                ErrorCode::UnknownHost,
+                Some(&recipient),
+                client_id,
+                tip,
+            )
+            .await;
+            return None;
+        };
+        if host.eq_ignore_ascii_case(&federation.config.name) {
+            return Some(msg.with_to(user.to_string()));
+        }
+        let from = format!("{}@{}", session.username, federation.config.name);
+        let msg = self.trace_outgoing(msg, received_at);
+        let relay = Control::Relay {
+            message: msg.with_from(ArcString::new(from)),
+            via: vec![federation.config.name.clone()],
+        };
+        let sent = federation.send(host, relay);
+        let event = match sent {
+            Ok(Relayed::Spooled) => Some(TraceEvent::Spooled),
+            Ok(Relayed::Sent) => Some(TraceEvent::Relayed),
+            Err(_) => None,
+        };
+        if let Some(event) = event {
+            self.trace_event(&session.username.get(), client_id, event);
+        }
+        let refused = match sent {
+            Ok(Relayed::Sent) => None,
+            Ok(Relayed::Spooled) => Some((
+                ErrorCode::OfflineQueued,
+                tr!(session.lang => "federation.spooled", server = host),
+            )),
+            Err(e) => {
+                let code = match e {
+                    RelayError::UnknownPeer => ErrorCode::UnknownHost,
+                    RelayError::Unavailable => ErrorCode::PeerUnavailable,
+                };
+                Some((code, e.describe(session.lang, host)))
+            }
+        };
+        if let Some((code, tip)) = refused {
+            self.reject(session, code, Some(&recipient), client_id, tip)
+                .await;
+        }
+        None
+    }
+
+    /// 处理对端 `peer` 转发来的消息：检查来源与环路后投递给本地用户或继续转发
+    async fn accept_relay(&self, peer: &str, msg: Message, mut via: Vec<String>) {
+        let Some(federation) = &self.federation else {
+            return;
+        };
+        let own = &federation.config.name;
+        let origin = match Recipient::parse(msg.from()) {
+            Recipient::Remote { host, .. } => Some(host),
+            _ => None,
+        };
+        let dropped = |reason: String| {
+            println!(
+                "{}",
+                tr!(
+                    "server.relay_dropped",
+                    server = peer,
+                    from = msg.from(),
+                    to = msg.to(),
+                    reason = reason,
+                )
+            );
+        };
+        if via.last().map(String::as_str) != Some(peer)
+            || origin.is_none()
+            || origin != via.first().map(String::as_str)
+        {
+            return dropped(tr!("federation.spoofed"));
+        }
+        if via.len() > MAX_HOPS || via.contains(own) {
+            return dropped(tr!("federation.loop"));
+        }
+        let (user, server) = match Recipient::parse(msg.to()) {
+            Recipient::Remote { user, host } => (user.to_string(), host.to_string()),
+            _ => return dropped(tr!("federation.bad_address")),
+        };
+        if server.eq_ignore_ascii_case(own) {
+            let recipient = ArcString::new(user.clone());
+            if !self.users.contains_key(&recipient) {
+                return dropped(tr!("server.unknown_user", user = user));
+            }
+            // 路由记录只在服务器之间传递，投递给本地用户前去掉
+            let (origin_id, hops) = (msg.id(), msg.trace().to_vec());
+            let msg = self.log_message(sanitized(msg.with_to(user).with_trace(Vec::new())));
+            if !hops.is_empty() {
+                self.trace_arrived(origin_id, hops, &msg);
+            }
+            println!(
+                "{}",
+                tr!(
+                    "server.relay_received",
+                    server = peer,
+                    from = msg.from(),
+                    to = msg.to(),
+                )
+            );
+            self.deliver_local(&recipient, msg).await;
+        } else {
+            via.push(own.clone());
+            let relay = Control::Relay {
+                message: self.trace_forwarded(msg.clone()),
+                via,
+            };
+            match federation.send(&server, relay) {
+                Ok(Relayed::Sent) => {}
+                Ok(Relayed::Spooled) => println!(
+                    "{}",
+                    tr!("server.relay_spooled", server = server, from = msg.from())
+                ),
+                Err(e) => dropped(e.describe(crate::i18n::lang(), &server)),
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+/*!
+# GeoIP 标注
+
+服务器可以载入 MaxMind 格式的数据库（GeoLite2/GeoIP2 的 Country、City 或 ASN 库），
+在接受连接时查询对端 IP 所属的国家/地区与自治系统（ASN），结果附加在会话上，
+出现在连接日志、管理员的 `/whois`、`/stats` 以及在线用户快照中，便于定位滥用来源。
+
+Country/City 库与 ASN 库可以同时载入，按库的类型（元数据中的 `database_type`）自动区分。
+未载入数据库或查询不到时不附加任何信息。载入数据库（[`GeoIp`]）需启用 `geoip` 特性。
+*/
+
+#[cfg(feature = "geoip")]
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "geoip")]
+use std::{net::IpAddr, path::Path};
+
+/// 一个 IP 的 GeoIP 查询结果
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeoInfo {
+    /// ISO 3166-1 国家/地区代码，例如 `CN`、`US`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// 自治系统编号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    /// 自治系统所属组织
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+}
+
+impl fmt::Display for GeoInfo {
+    /// 格式为 `CN AS4134 (Chinanet)`，缺少的部分省略
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(country) = &self.country {
+            parts.push(country.clone());
+        }
+        match (self.asn, &self.as_org) {
+            (Some(asn), Some(org)) => parts.push(format!("AS{} ({})", asn, org)),
+            (Some(asn), None) => parts.push(format!("AS{}", asn)),
+            (None, Some(org)) => parts.push(org.clone()),
+            (None, None) => {}
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// 已载入的 GeoIP 数据库
+#[cfg(feature = "geoip")]
+#[derive(Default)]
+pub struct GeoIp {
+    /// Country 或 City 库
+    country: Option<Reader<Vec<u8>>>,
+    /// ASN 库
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+#[cfg(feature = "geoip")]
+impl fmt::Debug for GeoIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = |reader: &Option<Reader<Vec<u8>>>| {
+            reader
+                .as_ref()
+                .map(|reader| reader.metadata.database_type.clone())
+        };
+        f.debug_struct("GeoIp")
+            .field("country", &kind(&self.country))
+            .field("asn", &kind(&self.asn))
+            .finish()
+    }
+}
+
+#[cfg(feature = "geoip")]
+impl GeoIp {
+    /// 依次载入 `paths` 中的数据库文件；同一类型的库后载入的生效
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<Self, MaxMindDBError> {
+        let mut geoip = GeoIp::default();
+        for path in paths {
+            let reader = Reader::open_readfile(path)?;
+            if reader.metadata.database_type.contains("ASN") {
+                geoip.asn = Some(reader);
+            } else {
+                geoip.country = Some(reader);
+            }
+        }
+        Ok(geoip)
+    }
+
+    /// 查询 `ip`，两个库都查不到时返回 `None`
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let mut info = GeoInfo::default();
+        if let Some(reader) = &self.country {
+            if let Ok(country) = reader.lookup::<geoip2::Country>(ip) {
+                info.country = country
+                    .country
+                    .or(country.registered_country)
+                    .and_then(|country| country.iso_code)
+                    .map(str::to_string);
+            }
+        }
+        if let Some(reader) = &self.asn {
+            if let Ok(asn) = reader.lookup::<geoip2::Asn>(ip) {
+                info.asn = asn.autonomous_system_number;
+                info.as_org = asn.autonomous_system_organization.map(str::to_string);
+            }
+        }
+        (info != GeoInfo::default()).then_some(info)
+    }
+}
@@ -0,0 +1,176 @@
+/*!
+# 邀请码
+
+服务器开启 [`ServerConfig::invite_only`](super::ServerConfig::invite_only) 后，
+尚未注册过的用户必须在握手帧中出示有效的邀请码才能加入；已注册的用户与管理员不受影响。
+邀请码由管理员通过 `/invite` 生成，可以限定使用次数与有效期，用完或过期后失效。
+
+私有聊天室的邀请码（见 `rooms` 子模块）同样由 [`Invites`] 管理，但与注册邀请码分开保存：
+聊天室的创建者或管理员通过 `/invite-link` 生成，其他用户以 `/join <邀请码>` 加入该聊天室。
+
+邀请码只保存在内存中，服务器重启后需要重新生成。
+*/
+
+use crate::i18n::Lang;
+use crate::tr;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 邀请码使用的字符：去掉了容易混淆的 0/O、1/I/L
+const ALPHABET: &[u8] = b"23456789ABCDEFGHJKMNPQRSTUVWXYZ";
+
+/// 邀请码的字符数（不含中间的连字符）
+const CODE_LEN: usize = 8;
+
+/// 一个邀请码
+#[derive(Clone, Debug)]
+pub struct Invite {
+    /// 邀请码，形如 `7KQ4-MX2P`
+    pub code: String,
+    /// 生成该邀请码的用户
+    pub created_by: String,
+    /// 邀请加入的聊天室，注册邀请码为 `None`
+    pub room: Option<String>,
+    pub created_at: DateTime<Local>,
+    /// 可使用的总次数
+    pub max_uses: u32,
+    /// 已使用的次数
+    pub used: u32,
+    /// 过期时间，`None` 表示不过期
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+impl Invite {
+    fn expired(&self, now: DateTime<Local>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// 邀请码无法使用的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InviteError {
+    /// 邀请码不存在、已用完或已被撤销
+    Unknown,
+    /// 邀请码已过期
+    Expired,
+}
+
+impl InviteError {
+    /// 以 `lang` 描述原因
+    pub fn describe(&self, lang: Lang) -> String {
+        match self {
+            InviteError::Unknown => tr!(lang => "server.invite_unknown"),
+            InviteError::Expired => tr!(lang => "server.invite_expired"),
+        }
+    }
+}
+
+/// 当前有效的邀请码
+#[derive(Debug, Default)]
+pub struct Invites {
+    entries: Mutex<HashMap<String, Invite>>,
+}
+
+impl Invites {
+    /// 生成一个邀请加入 `room`（注册邀请码为 `None`）、可使用 `max_uses` 次、
+    /// 在 `ttl` 后过期（`None` 表示不过期）的邀请码
+    pub fn create(
+        &self,
+        created_by: &str,
+        room: Option<&str>,
+        max_uses: u32,
+        ttl: Option<Duration>,
+    ) -> io::Result<Invite> {
+        let now = Local::now();
+        let mut entries = self.entries.lock().unwrap();
+        let code = loop {
+            let code = random_code()?;
+            if !entries.contains_key(&code) {
+                break code;
+            }
+        };
+        let invite = Invite {
+            code: code.clone(),
+            created_by: created_by.to_string(),
+            room: room.map(str::to_string),
+            created_at: now,
+            max_uses,
+            used: 0,
+            expires_at: ttl
+                .and_then(|ttl| chrono::Duration::from_std(ttl).ok().map(|ttl| now + ttl)),
+        };
+        entries.insert(code, invite.clone());
+        Ok(invite)
+    }
+
+    /// 使用一次邀请码（不区分大小写），用完或过期的邀请码随之移除
+    ///
+    /// # 返回值
+    /// 成功时返回使用后的邀请码信息
+    pub fn redeem(&self, code: &str) -> Result<Invite, InviteError> {
+        let code = code.trim().to_uppercase();
+        let mut entries = self.entries.lock().unwrap();
+        let invite = entries.get_mut(&code).ok_or(InviteError::Unknown)?;
+        if invite.expired(Local::now()) {
+            entries.remove(&code);
+            return Err(InviteError::Expired);
+        }
+        invite.used += 1;
+        let invite = invite.clone();
+        if invite.used >= invite.max_uses {
+            entries.remove(&code);
+        }
+        Ok(invite)
+    }
+
+    /// 有效的邀请码 `code`（不区分大小写）的信息，不计入使用次数
+    pub fn get(&self, code: &str) -> Option<Invite> {
+        let code = code.trim().to_uppercase();
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&code)
+            .filter(|invite| !invite.expired(Local::now()))
+            .cloned()
+    }
+
+    /// 撤销邀请码，返回该邀请码是否存在
+    pub fn revoke(&self, code: &str) -> bool {
+        let code = code.trim().to_uppercase();
+        self.entries.lock().unwrap().remove(&code).is_some()
+    }
+
+    /// 清除已过期的邀请码，返回清除的个数
+    pub fn expire(&self) -> usize {
+        let now = Local::now();
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|_, invite| !invite.expired(now));
+        count - entries.len()
+    }
+
+    /// 当前有效的邀请码（按生成时间排序），同时清除已过期的邀请码
+    pub fn list(&self) -> Vec<Invite> {
+        self.expire();
+        let entries = self.entries.lock().unwrap();
+        let mut invites: Vec<Invite> = entries.values().cloned().collect();
+        invites.sort_by_key(|invite| invite.created_at);
+        invites
+    }
+}
+
+/// 生成随机邀请码，中间以连字符分成两组便于抄写
+fn random_code() -> io::Result<String> {
+    let mut bytes = [0u8; CODE_LEN];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    let mut code = String::with_capacity(CODE_LEN + 1);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i == CODE_LEN / 2 {
+            code.push('-');
+        }
+        code.push(ALPHABET[*byte as usize % ALPHABET.len()] as char);
+    }
+    Ok(code)
+}
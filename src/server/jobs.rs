@@ -0,0 +1,568 @@
+/*!
+# 定期维护任务
+
+服务器可以按 cron 式的时间表在后台运行维护任务（[`ServerConfig::jobs`](super::ServerConfig::jobs)），
+每项配置为 `任务=时间表`：
+
+- `vacuum`：整理消息日志，丢弃无法读取的行（例如写入中途崩溃留下的半行）并整体重写；
+- `expire`：清除已过期的邀请码，以及进入死信队列超过 [`DEAD_LETTER_TTL`] 的死信；
+- `prune`：每个接收者在消息日志中只保留最近 [`MAX_REPLAY`] 条消息，更早的消息重连时不会再补发
+  （同时配置了历史消息归档时，未归档的较早消息也随之删除）。
+
+时间表为五个以空格分隔的字段：分（0-59）、时（0-23）、日（1-31）、月（1-12）、周（0-7，0 与 7 为周日），
+每个字段可以是 `*`、数值、范围 `a-b`、带步长的范围 `a-b/n`（范围也可以写作 `*`），以及它们以逗号分隔的列表；
+日与周都受限时满足其一即可。也可以使用 `@hourly`、`@daily`、`@weekly`、`@monthly` 简写。时间按服务器本地时区计算。
+
+所有任务由 [`Server::run`](super::Server::run) 启动的同一个任务按时间先后依次运行，不会同时改写存储；
+错过的运行时间（例如任务运行过久）不补跑。每个任务的运行次数、失败次数、上次运行的时间、耗时与结果
+以及下次运行时间可以由管理员通过 `/jobs` 查看，`/jobs run <任务>` 立即运行一次。
+*/
+
+use super::{Server, MAX_REPLAY};
+use crate::i18n::{self, Lang};
+use crate::storage::Vacuumed;
+use crate::{tr, Timestamp};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::task::{self, JoinHandle};
+
+/// `expire` 任务清除进入死信队列超过该时长的死信
+pub const DEAD_LETTER_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// 查找下次运行时间时最多向后查找的天数（覆盖闰年的 2 月 29 日）
+const SEARCH_DAYS: u64 = 366 * 8;
+
+/// 维护任务的种类
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Job {
+    /// 整理消息日志
+    Vacuum,
+    /// 清除过期的邀请码与死信
+    Expire,
+    /// 删除超出补发窗口的消息
+    Prune,
+}
+
+impl Job {
+    /// 配置与指令中使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Job::Vacuum => "vacuum",
+            Job::Expire => "expire",
+            Job::Prune => "prune",
+        }
+    }
+
+    /// 由名称解析任务
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Job::Vacuum, Job::Expire, Job::Prune]
+            .into_iter()
+            .find(|job| job.name() == name)
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// cron 式的时间表：分、时、日、月、周
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    /// 原始表达式，用于显示
+    expr: String,
+    /// 各字段允许的取值，第 n 位表示取值 n
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// 日、周字段是否为 `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// 解析 cron 表达式，格式错误时返回 `None`
+    pub fn parse(expr: &str) -> Option<Self> {
+        let expr = expr.trim();
+        let expanded = match expr {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            _ => expr,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return None;
+        };
+        let mut weekday_bits = field(weekdays, 0, 7)?;
+        // 7 与 0 都表示周日
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Some(Schedule {
+            expr: expr.to_string(),
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_bits,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    /// `date` 这一天是否可能运行
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// 晚于 `after` 的下一个运行时间（精确到分钟）；时间表永远不会满足时返回 `None`
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.date_naive();
+        (0..SEARCH_DAYS)
+            .filter_map(|offset| start.checked_add_days(Days::new(offset)))
+            .filter(|date| self.matches_date(*date))
+            .find_map(|date| {
+                (0..24u32)
+                    .filter(|hour| self.hours & (1 << hour) != 0)
+                    .flat_map(|hour| {
+                        (0..60u32)
+                            .filter(|minute| self.minutes & (1 << minute) != 0)
+                            .map(move |minute| (hour, minute))
+                    })
+                    // 夏令时跳过的时刻不存在，略过
+                    .filter_map(|(hour, minute)| {
+                        date.and_hms_opt(hour, minute, 0)?
+                            .and_local_timezone(Local)
+                            .earliest()
+                    })
+                    .find(|time| *time > after)
+            })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+/// 解析 cron 表达式的一个字段，返回允许取值的位图
+fn field(spec: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in spec.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u32>().ok().filter(|s| *s > 0)?)),
+            None => (part, None),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((from, to)) => (from.parse().ok()?, to.parse().ok()?),
+                // `a/n` 表示从 a 开始到最大值
+                None if step.is_some() => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if from < min || to > max || from > to {
+            return None;
+        }
+        for value in (from..=to).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+/// 一项维护任务的配置
+#[derive(Clone, Debug)]
+pub struct JobConfig {
+    pub job: Job,
+    pub schedule: Schedule,
+}
+
+impl JobConfig {
+    /// 解析 `任务=时间表` 形式的配置，格式错误时返回 `None`
+    pub fn from_string(spec: &str) -> Option<Self> {
+        let (name, schedule) = spec.split_once('=')?;
+        Some(JobConfig {
+            job: Job::from_name(name.trim())?,
+            schedule: Schedule::parse(schedule)?,
+        })
+    }
+}
+
+/// 一次成功运行的结果
+#[derive(Clone, Copy, Debug)]
+enum Outcome {
+    Vacuumed(Vacuumed),
+    Expired { invites: usize, dead_letters: usize },
+    Pruned(usize),
+}
+
+impl Outcome {
+    /// 以 `lang` 描述结果
+    fn describe(&self, lang: Lang) -> String {
+        match self {
+            Outcome::Vacuumed(vacuumed) => tr!(lang => "job.vacuumed",
+                dropped = vacuumed.dropped,
+                bytes = vacuumed.reclaimed,
+            ),
+            Outcome::Expired {
+                invites,
+                dead_letters,
+            } => tr!(lang => "job.expired", invites = invites, letters = dead_letters),
+            Outcome::Pruned(count) => tr!(lang => "job.pruned", count = count),
+        }
+    }
+}
+
+/// 一项任务的运行统计
+#[derive(Debug, Default)]
+struct JobStats {
+    runs: u64,
+    failures: u64,
+    /// 上次开始运行的时间、耗时与结果
+    last: Option<(DateTime<Local>, Duration, Result<Outcome, String>)>,
+    /// 下次按时间表运行的时间
+    next: Option<DateTime<Local>>,
+}
+
+/// 一项已配置的任务
+#[derive(Debug)]
+struct Entry {
+    config: JobConfig,
+    /// 正在运行（按时间表或由 `/jobs run` 触发）
+    running: AtomicBool,
+    stats: Mutex<JobStats>,
+}
+
+/// 已配置的维护任务
+#[derive(Debug, Default)]
+pub(super) struct Jobs {
+    entries: Vec<Entry>,
+}
+
+impl Jobs {
+    pub(super) fn new(configs: &[JobConfig]) -> Self {
+        Jobs {
+            entries: configs
+                .iter()
+                .map(|config| Entry {
+                    config: config.clone(),
+                    running: AtomicBool::new(false),
+                    stats: Mutex::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Server {
+    /// 配置了维护任务时启动调度任务，按时间表依次运行各项任务
+    pub(super) fn spawn_jobs(&self) -> Option<JoinHandle<()>> {
+        if self.jobs.entries.is_empty() {
+            return None;
+        }
+        let now = Local::now();
+        let mut names = Vec::new();
+        for entry in &self.jobs.entries {
+            entry.stats.lock().unwrap().next = entry.config.schedule.next_after(now);
+            names.push(format!("{}（{}）", entry.config.job, entry.config.schedule));
+        }
+        println!(
+            "{}",
+            tr!(
+                "server.jobs_started",
+                count = names.len(),
+                jobs = names.join(", ")
+            )
+        );
+        let server = self.clone();
+        Some(tokio::spawn(async move {
+            loop {
+                let next = server
+                    .jobs
+                    .entries
+                    .iter()
+                    .filter_map(|entry| entry.stats.lock().unwrap().next)
+                    .min();
+                let Some(next) = next else {
+                    return;
+                };
+                let wait = (next - Local::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+                for entry in &server.jobs.entries {
+                    let due = entry
+                        .stats
+                        .lock()
+                        .unwrap()
+                        .next
+                        .is_some_and(|next| next <= Local::now());
+                    if due {
+                        server.run_job(entry).await;
+                        entry.stats.lock().unwrap().next =
+                            entry.config.schedule.next_after(Local::now());
+                    }
+                }
+            }
+        }))
+    }
+
+    /// 运行一次任务并记录统计；任务已在运行时返回 `None`
+    async fn run_job(&self, entry: &Entry) -> Option<Result<Outcome, String>> {
+        if entry.running.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        let started = Timestamp::now();
+        let storage = self.storage.clone();
+        let result = match entry.config.job {
+            Job::Vacuum => task::spawn_blocking(move || storage.vacuum())
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result.map_err(|e| e.to_string()))
+                .map(Outcome::Vacuumed),
+            Job::Expire => {
+                let ttl = chrono::Duration::from_std(DEAD_LETTER_TTL).unwrap_or_default();
+                Ok(Outcome::Expired {
+                    invites: self.invites.expire() + self.room_invites.expire(),
+                    dead_letters: self.dead_letters.expire(started.wall() - ttl),
+                })
+            }
+            Job::Prune => task::spawn_blocking(move || storage.prune(MAX_REPLAY))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|result| result.map_err(|e| e.to_string()))
+                .map(Outcome::Pruned),
+        };
+        let elapsed = started.elapsed();
+        match &result {
+            Ok(outcome) => println!(
+                "{}",
+                tr!(
+                    "server.job_finished",
+                    job = entry.config.job,
+                    elapsed = elapsed.as_millis(),
+                    result = outcome.describe(i18n::lang())
+                )
+            ),
+            Err(e) => eprintln!(
+                "{}",
+                tr!("server.job_failed", job = entry.config.job, error = e)
+            ),
+        }
+        {
+            let mut stats = entry.stats.lock().unwrap();
+            stats.runs += 1;
+            if result.is_err() {
+                stats.failures += 1;
+            }
+            stats.last = Some((started.wall(), elapsed, result.clone()));
+        }
+        entry.running.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    /// `/jobs [run <任务>]`（仅管理员）：查看维护任务的运行统计，或立即运行一次
+    pub(super) async fn jobs_command(&self, lang: Lang, args: &str) -> String {
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (None, _) => self.jobs_response(lang),
+            (Some("run"), Some(name)) => {
+                let Some(entry) = self
+                    .jobs
+                    .entries
+                    .iter()
+                    .find(|entry| entry.config.job.name() == name)
+                else {
+                    return tr!(lang => "cmd.job_unknown", job = name);
+                };
+                match self.run_job(entry).await {
+                    None => tr!(lang => "cmd.job_running", job = name),
+                    Some(Ok(outcome)) => {
+                        tr!(lang => "cmd.job_done", job = name, result = outcome.describe(lang))
+                    }
+                    Some(Err(e)) => tr!(lang => "cmd.job_failed", job = name, error = e),
+                }
+            }
+            _ => tr!(lang => "cmd.usage_jobs"),
+        }
+    }
+
+    /// 各项任务的时间表与运行统计
+    fn jobs_response(&self, lang: Lang) -> String {
+        if self.jobs.entries.is_empty() {
+            return tr!(lang => "cmd.jobs_empty");
+        }
+        let format = |time: DateTime<Local>| time.format("%Y-%m-%d %H:%M").to_string();
+        let lines: Vec<String> = self
+            .jobs
+            .entries
+            .iter()
+            .map(|entry| {
+                let stats = entry.stats.lock().unwrap();
+                let mut line = tr!(lang => "cmd.job",
+                    job = entry.config.job,
+                    schedule = entry.config.schedule,
+                    runs = stats.runs,
+                    failures = stats.failures,
+                    next = stats.next.map_or_else(|| tr!(lang => "cmd.job_never"), format),
+                );
+                if let Some((at, elapsed, result)) = &stats.last {
+                    let result = match result {
+                        Ok(outcome) => outcome.describe(lang),
+                        Err(e) => tr!(lang => "cmd.job_error", error = e),
+                    };
+                    line.push_str(&tr!(lang => "cmd.job_last",
+                        at = format(*at),
+                        elapsed = elapsed.as_millis(),
+                        result = result,
+                    ));
+                }
+                if entry.running.load(Ordering::Acquire) {
+                    line.push_str(&tr!(lang => "cmd.job_in_progress"));
+                }
+                line
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.jobs", count = lines.len()),
+            lines.join("\n  › ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// 本地时区的 `year-month-day hour:minute`
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    fn bits(values: &[u32]) -> u64 {
+        values.iter().fold(0, |bits, value| bits | 1 << value)
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(field("*", 0, 59), Some((0..=59).fold(0, |b, v| b | 1 << v)));
+        assert_eq!(field("7", 0, 59), Some(bits(&[7])));
+        assert_eq!(field("1-5", 0, 59), Some(bits(&[1, 2, 3, 4, 5])));
+        assert_eq!(field("*/15", 0, 59), Some(bits(&[0, 15, 30, 45])));
+        assert_eq!(field("10-30/10", 0, 59), Some(bits(&[10, 20, 30])));
+        assert_eq!(field("5/20", 0, 59), Some(bits(&[5, 25, 45])));
+        assert_eq!(field("20/1", 0, 23), Some(bits(&[20, 21, 22, 23])));
+        assert_eq!(field("1,3,5-7", 0, 59), Some(bits(&[1, 3, 5, 6, 7])));
+        assert_eq!(field("*/10,7", 1, 31), Some(bits(&[1, 7, 11, 21, 31])));
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        for spec in [
+            "", "60", "5-1", "*/0", "1-", "-1", "a", "1,,2", "1/x", "1.5",
+        ] {
+            assert_eq!(field(spec, 0, 59), None, "{:?}", spec);
+        }
+        assert_eq!(field("0", 1, 31), None);
+        assert_eq!(field("1-32", 1, 31), None);
+    }
+
+    #[test]
+    fn parses_expressions() {
+        let schedule = Schedule::parse(" 30 2 * * 1-5 ").unwrap();
+        assert_eq!(schedule.minutes, bits(&[30]));
+        assert_eq!(schedule.hours, bits(&[2]));
+        assert_eq!(schedule.weekdays, bits(&[1, 2, 3, 4, 5]));
+        assert!(schedule.any_day && !schedule.any_weekday);
+        assert_eq!(schedule.to_string(), "30 2 * * 1-5");
+
+        // 7 与 0 都表示周日
+        assert_eq!(Schedule::parse("0 0 * * 7").unwrap().weekdays & 1, 1);
+        assert_eq!(Schedule::parse("@weekly").unwrap().to_string(), "@weekly");
+
+        for expr in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * 32 * *",
+            "* * * 0 *",
+            "* * * 13 *",
+            "* * * * 8",
+            "@yearly",
+        ] {
+            assert!(Schedule::parse(expr).is_none(), "{:?}", expr);
+        }
+    }
+
+    #[test]
+    fn computes_next_fire() {
+        let next = |expr: &str, after| Schedule::parse(expr).unwrap().next_after(after);
+
+        assert_eq!(
+            next("*/15 * * * *", at(2026, 1, 10, 10, 7)),
+            Some(at(2026, 1, 10, 10, 15))
+        );
+        // 恰好在运行时间上时取下一次
+        assert_eq!(
+            next("*/15 * * * *", at(2026, 1, 10, 10, 15)),
+            Some(at(2026, 1, 10, 10, 30))
+        );
+        assert_eq!(
+            next("@hourly", at(2026, 1, 10, 23, 30)),
+            Some(at(2026, 1, 11, 0, 0))
+        );
+        assert_eq!(
+            next("@daily", at(2026, 1, 10, 10, 0)),
+            Some(at(2026, 1, 11, 0, 0))
+        );
+        assert_eq!(
+            next("@monthly", at(2026, 12, 15, 0, 0)),
+            Some(at(2027, 1, 1, 0, 0))
+        );
+        // 2026-01-10 是周六，下一个周一是 12 日
+        assert_eq!(
+            next("0 9 * * 1", at(2026, 1, 10, 10, 0)),
+            Some(at(2026, 1, 12, 9, 0))
+        );
+        // 日与周都受限时满足其一即可：13 日（周二）早于下一个周五（16 日）
+        assert_eq!(
+            next("0 0 13 * 5", at(2026, 1, 10, 10, 0)),
+            Some(at(2026, 1, 13, 0, 0))
+        );
+        // 只在闰年的 2 月 29 日运行
+        assert_eq!(
+            next("0 0 29 2 *", at(2026, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+        // 永远不会满足
+        assert_eq!(next("0 0 31 2 *", at(2026, 1, 1, 0, 0)), None);
+    }
+}
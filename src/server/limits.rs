@@ -0,0 +1,182 @@
+/*!
+# 按来源 IP 的连接限制
+
+[`Server::run`](super::Server::run) 接受连接时按来源 IP 检查两项限制，避免单个主机占满连接：
+
+- **并发连接数**：同一 IP 同时保持的连接数达到上限后拒绝新连接，连接结束时归还名额；
+- **连接频率**：同一 IP 在统计窗口内发起的连接次数超过上限后进入冷却期，
+  冷却期内的连接一律拒绝，冷却结束后重新计数。
+
+被拒绝的连接会在简短的握手后收到错误帧（或文字提示），见 [`Refusal`]。
+*/
+
+use crate::humanize_duration_in;
+use crate::i18n::Lang;
+use crate::protocol::ErrorCode;
+use crate::tr;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 记录的来源 IP 超过该数量时清理已无连接、无近期尝试的条目
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// 按来源 IP 的连接限制配置
+#[derive(Clone, Debug)]
+pub struct IpLimitConfig {
+    /// 同一 IP 的并发连接数上限，`None` 表示不限制
+    pub max_connections: Option<usize>,
+    /// 同一 IP 在 `window` 内最多发起的连接次数，`None` 表示不限制
+    pub max_attempts: Option<usize>,
+    /// 统计连接频率的时间窗口
+    pub window: Duration,
+    /// 超过连接频率后的冷却时长
+    pub cooldown: Duration,
+}
+
+impl Default for IpLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            max_attempts: None,
+            window: Duration::from_secs(60),
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+/// 连接被拒绝的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Refusal {
+    /// 该 IP 的并发连接数已达上限
+    TooManyConnections(usize),
+    /// 该 IP 连接过于频繁，处于冷却期，`Duration` 为剩余的冷却时长
+    Cooldown(Duration),
+}
+
+impl Refusal {
+    /// 告知客户端时使用的错误码
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Refusal::TooManyConnections(_) => ErrorCode::TooManyConnections,
+            Refusal::Cooldown(_) => ErrorCode::RateLimited,
+        }
+    }
+
+    /// 以 `lang` 描述原因
+    pub fn describe(&self, lang: Lang) -> String {
+        match self {
+            Refusal::TooManyConnections(limit) => {
+                tr!(lang => "server.ip_too_many", limit = limit)
+            }
+            Refusal::Cooldown(remaining) => {
+                // 不足一秒的剩余时长按一秒显示
+                let remaining =
+                    humanize_duration_in(lang, (*remaining).max(Duration::from_secs(1)));
+                tr!(lang => "server.ip_cooldown", remaining = remaining)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct IpState {
+    /// 当前连接数
+    active: usize,
+    /// 统计窗口内的连接时间
+    attempts: VecDeque<Instant>,
+    /// 冷却结束的时间
+    cooldown_until: Option<Instant>,
+}
+
+impl IpState {
+    fn idle(&self, now: Instant) -> bool {
+        self.active == 0
+            && self.attempts.is_empty()
+            && self.cooldown_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// 各来源 IP 的连接状态
+#[derive(Debug)]
+pub struct IpLimits {
+    config: IpLimitConfig,
+    state: Mutex<HashMap<IpAddr, IpState>>,
+}
+
+impl IpLimits {
+    pub fn new(config: IpLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 登记来自 `ip` 的一次连接
+    ///
+    /// # 返回值
+    /// 允许连接时返回名额，连接结束时丢弃名额即可归还；否则返回拒绝原因
+    pub fn admit(self: &Arc<Self>, ip: IpAddr) -> Result<IpPermit, Refusal> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if state.len() > PRUNE_THRESHOLD {
+            let window = self.config.window;
+            state.retain(|_, entry| {
+                entry.attempts.retain(|at| now.duration_since(*at) < window);
+                !entry.idle(now)
+            });
+        }
+        let entry = state.entry(ip).or_default();
+        if let Some(until) = entry.cooldown_until {
+            if until > now {
+                return Err(Refusal::Cooldown(until - now));
+            }
+            entry.cooldown_until = None;
+        }
+        if let Some(max) = self.config.max_attempts {
+            let window = self.config.window;
+            entry.attempts.retain(|at| now.duration_since(*at) < window);
+            entry.attempts.push_back(now);
+            if entry.attempts.len() > max {
+                entry.attempts.clear();
+                entry.cooldown_until = Some(now + self.config.cooldown);
+                return Err(Refusal::Cooldown(self.config.cooldown));
+            }
+        }
+        if let Some(max) = self.config.max_connections {
+            if entry.active >= max {
+                return Err(Refusal::TooManyConnections(max));
+            }
+        }
+        entry.active += 1;
+        Ok(IpPermit {
+            limits: self.clone(),
+            ip,
+        })
+    }
+
+    /// 归还 `ip` 的一个连接名额
+    fn release(&self, ip: IpAddr) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(entry) = state.get_mut(&ip) {
+            entry.active = entry.active.saturating_sub(1);
+            if entry.idle(Instant::now()) {
+                state.remove(&ip);
+            }
+        }
+    }
+}
+
+/// 一个来源 IP 的连接名额，随连接任务一起丢弃时归还
+#[derive(Debug)]
+pub struct IpPermit {
+    limits: Arc<IpLimits>,
+    ip: IpAddr,
+}
+
+impl Drop for IpPermit {
+    fn drop(&mut self) {
+        self.limits.release(self.ip);
+    }
+}
@@ -0,0 +1,139 @@
+/*!
+# 维护模式
+
+计划重启前，管理员以 `/maintenance on [HH:MM]`（或管理套接字的 `maintenance on [HH:MM]`）开启维护模式：
+服务器不再接受新的注册（管理员除外），以 `MAINTENANCE` 错误拒绝握手，并提示预计恢复的时间；
+已经在线的会话不受影响，可以自然结束。`/maintenance off` 关闭维护模式，`/maintenance` 查看当前状态
+及尚未结束的在线会话数。
+
+维护状态只保存在内存中，重启后恢复正常。虚拟主机上管理员的指令只作用于该虚拟主机，
+管理套接字的命令同时作用于默认空间与所有虚拟主机。开启与关闭都写入审计日志。
+*/
+
+use super::audit::AuditAction;
+use super::Server;
+use crate::i18n::Lang;
+use crate::{tr, ArcString};
+use chrono::{DateTime, Local, NaiveTime};
+use std::sync::Mutex;
+
+/// 一次维护
+#[derive(Clone, Debug)]
+pub(super) struct MaintenanceWindow {
+    /// 开启维护模式的时间
+    since: DateTime<Local>,
+    /// 预计恢复的时间（服务器本地时间），`None` 表示未指定
+    back_at: Option<NaiveTime>,
+    /// 开启维护模式的管理员，经管理套接字开启时为 `admin-socket`
+    by: String,
+}
+
+/// 维护模式的开关
+#[derive(Debug, Default)]
+pub(super) struct Maintenance {
+    window: Mutex<Option<MaintenanceWindow>>,
+}
+
+impl Maintenance {
+    /// 当前的维护，未开启时为 `None`
+    fn current(&self) -> Option<MaintenanceWindow> {
+        self.window.lock().unwrap().clone()
+    }
+}
+
+impl MaintenanceWindow {
+    /// 以 `lang` 生成拒绝注册时给客户端的说明
+    fn refusal(&self, lang: Lang) -> String {
+        match self.back_at {
+            Some(time) => tr!(lang => "server.maintenance_until", time = time.format("%H:%M")),
+            None => tr!(lang => "server.maintenance"),
+        }
+    }
+}
+
+impl Server {
+    /// 处于维护模式时，以 `lang` 返回拒绝新注册的说明
+    pub(super) fn maintenance_refusal(&self, lang: Lang) -> Option<String> {
+        self.maintenance
+            .current()
+            .map(|window| window.refusal(lang))
+    }
+
+    /// `/maintenance [on [HH:MM]|off]`（仅管理员）与管理套接字的 `maintenance` 命令：
+    /// 查看、开启或关闭维护模式；`admin` 为 `None` 表示来自管理套接字
+    pub(super) fn maintenance_command(
+        &self,
+        admin: Option<&ArcString>,
+        args: &str,
+        lang: Lang,
+    ) -> String {
+        let admin = admin.map_or_else(|| "admin-socket".to_string(), ArcString::get);
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => self.maintenance_status(lang),
+            (Some("on"), back_at, None) => {
+                let back_at = match back_at.map(|time| NaiveTime::parse_from_str(time, "%H:%M")) {
+                    None => None,
+                    Some(Ok(time)) => Some(time),
+                    Some(Err(_)) => return tr!(lang => "cmd.usage_maintenance"),
+                };
+                let detail = tr!(
+                    "maintenance.audit_on",
+                    admin = admin,
+                    back = back_hint(back_at, crate::i18n::lang()),
+                );
+                *self.maintenance.window.lock().unwrap() = Some(MaintenanceWindow {
+                    since: Local::now(),
+                    back_at,
+                    by: admin.clone(),
+                });
+                self.audit(AuditAction::MaintenanceStarted, &admin, detail);
+                tr!(lang => "cmd.maintenance_on",
+                    back = back_hint(back_at, lang),
+                    users = self.online_users.len(),
+                )
+            }
+            (Some("off"), None, _) => {
+                if self.maintenance.window.lock().unwrap().take().is_none() {
+                    return tr!(lang => "cmd.maintenance_not_on");
+                }
+                self.audit(
+                    AuditAction::MaintenanceEnded,
+                    &admin,
+                    tr!("maintenance.audit_off", admin = admin),
+                );
+                tr!(lang => "cmd.maintenance_off")
+            }
+            _ => tr!(lang => "cmd.usage_maintenance"),
+        }
+    }
+
+    /// 管理套接字的 `maintenance` 命令：作用于默认空间与所有虚拟主机，回复默认空间的结果
+    pub(super) fn maintenance_all(&self, args: &str, lang: Lang) -> String {
+        for host in self.vhosts.values() {
+            host.maintenance_command(None, args, lang);
+        }
+        self.maintenance_command(None, args, lang)
+    }
+
+    /// 维护模式的当前状态
+    fn maintenance_status(&self, lang: Lang) -> String {
+        let Some(window) = self.maintenance.current() else {
+            return tr!(lang => "cmd.maintenance_status_off");
+        };
+        tr!(lang => "cmd.maintenance_status_on",
+            admin = window.by,
+            since = window.since.format("%Y-%m-%d %H:%M:%S"),
+            back = back_hint(window.back_at, lang),
+            users = self.online_users.len(),
+        )
+    }
+}
+
+/// 以 `lang` 描述预计恢复的时间（如 `，预计 12:30 恢复`），未指定时为空
+fn back_hint(back_at: Option<NaiveTime>, lang: Lang) -> String {
+    back_at.map_or_else(
+        String::new,
+        |time| tr!(lang => "cmd.maintenance_back", time = time.format("%H:%M")),
+    )
+}
@@ -0,0 +1,466 @@
+/*!
+# 审核队列
+
+用户通过 `/report <用户> <理由>` 举报滥用行为，举报连同双方最近的 [`REPORT_CONTEXT`] 条往来消息
+（取自消息日志，已归档或清理的消息不再附带）进入审核队列，写入审计日志（见 `audit` 子模块），
+并提醒在线的管理员。管理员通过 `/reports` 或管理套接字的 `reports` 命令查看待处理的举报，
+`/reports dismiss <编号>` 处理完毕后移出队列（同样写入审计日志）。
+
+配置了 [`ServerConfig::quarantine`](super::ServerConfig::quarantine) 时，触发反垃圾规则（见 `spam` 子模块）
+或有待处理举报的发送者发给本服务器用户或聊天室的消息不投递，也不写入消息日志，而是隔离在审核队列中。
+管理员通过 `/quarantine` 或管理套接字的 `quarantine` 命令查看，`release <编号>` 放行（照常写入消息日志并投递，
+接收方不在线时留待重连补发），`discard <编号>` 丢弃；隔离、放行与丢弃都写入审计日志。
+
+举报与隔离的消息共用一个编号序列，只保存在内存中，各自超过 [`MODERATION_CAPACITY`] 条时丢弃最早的条目。
+*/
+
+use super::audit::AuditAction;
+use super::receipts::DeliveryState;
+use super::spam::SpamRule;
+use super::Server;
+use crate::i18n::Lang;
+use crate::session::Session;
+use crate::{tr, ArcString, Message, MessageKind};
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// 审核队列保留的举报条数，隔离的消息同样最多保留这么多条
+pub const MODERATION_CAPACITY: usize = 500;
+
+/// 举报附带的最近往来消息条数
+pub const REPORT_CONTEXT: usize = 5;
+
+/// 一条举报
+#[derive(Clone, Debug)]
+pub struct Report {
+    /// 举报编号，从 1 开始递增
+    pub id: u64,
+    /// 提交时间
+    pub filed_at: DateTime<Local>,
+    /// 举报者
+    pub reporter: ArcString,
+    /// 被举报的用户
+    pub reported: ArcString,
+    /// 举报理由
+    pub reason: String,
+    /// 双方最近的往来消息（按编号先后排列）
+    pub context: Vec<Message>,
+}
+
+impl Report {
+    /// 以 `lang` 描述举报及其附带的消息，每条消息一行
+    fn describe(&self, lang: Lang) -> String {
+        let mut text = tr!(lang => "moderation.report",
+            id = self.id,
+            time = self.filed_at.format("%Y-%m-%d %H:%M:%S"),
+            reporter = self.reporter,
+            reported = self.reported,
+            reason = self.reason,
+        );
+        for msg in &self.context {
+            let content = match msg.kind() {
+                MessageKind::Encrypted => tr!(lang => "cmd.receipt_encrypted"),
+                MessageKind::Text | MessageKind::Action => msg.content().replace('\n', " "),
+            };
+            text.push_str(&format!(
+                "\n      [{}] {}: {}",
+                msg.time_stamp(),
+                msg.from(),
+                content
+            ));
+        }
+        text
+    }
+}
+
+/// 消息被隔离的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoldCause {
+    /// 发送者触发了反垃圾规则
+    Spam(SpamRule),
+    /// 发送者有编号为该值的待处理举报
+    Reported(u64),
+}
+
+impl HoldCause {
+    /// 以 `lang` 描述隔离原因
+    pub fn describe(self, lang: Lang) -> String {
+        match self {
+            HoldCause::Spam(rule) => rule.describe(lang),
+            HoldCause::Reported(id) => tr!(lang => "moderation.hold_reported", id = id),
+        }
+    }
+}
+
+/// 一条隔离待审核的消息
+#[derive(Clone, Debug)]
+pub struct Quarantined {
+    /// 条目编号，与举报共用编号序列
+    pub id: u64,
+    /// 隔离的时间
+    pub held_at: DateTime<Local>,
+    /// 隔离的原因
+    pub cause: HoldCause,
+    /// 尚未写入消息日志的消息
+    pub message: Message,
+}
+
+impl Quarantined {
+    /// 以 `lang` 描述隔离的消息
+    fn describe(&self, lang: Lang) -> String {
+        let content = match self.message.kind() {
+            MessageKind::Encrypted => tr!(lang => "cmd.receipt_encrypted"),
+            MessageKind::Text | MessageKind::Action => self.message.content().replace('\n', " "),
+        };
+        tr!(lang => "moderation.held",
+            id = self.id,
+            time = self.held_at.format("%Y-%m-%d %H:%M:%S"),
+            from = self.message.from(),
+            to = self.message.to(),
+            content = content,
+            cause = self.cause.describe(lang),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: u64,
+    reports: VecDeque<Report>,
+    held: VecDeque<Quarantined>,
+}
+
+/// 待管理员处理的举报与隔离的消息
+#[derive(Debug, Default)]
+pub(super) struct ModerationQueue {
+    inner: Mutex<Inner>,
+}
+
+impl ModerationQueue {
+    /// 加入一条举报，返回其编号
+    fn file(
+        &self,
+        reporter: &ArcString,
+        reported: &ArcString,
+        reason: &str,
+        context: Vec<Message>,
+    ) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        if inner.reports.len() == MODERATION_CAPACITY {
+            inner.reports.pop_front();
+        }
+        inner.reports.push_back(Report {
+            id,
+            filed_at: Local::now(),
+            reporter: reporter.clone(),
+            reported: reported.clone(),
+            reason: reason.to_string(),
+            context,
+        });
+        id
+    }
+
+    /// 所有待处理的举报（按提交顺序）
+    fn reports(&self) -> Vec<Report> {
+        self.inner.lock().unwrap().reports.iter().cloned().collect()
+    }
+
+    /// 取出编号为 `id` 的举报
+    fn take_report(&self, id: u64) -> Option<Report> {
+        let mut inner = self.inner.lock().unwrap();
+        let index = inner.reports.iter().position(|report| report.id == id)?;
+        inner.reports.remove(index)
+    }
+
+    /// `user` 最早的待处理举报的编号
+    fn pending_report(&self, user: &ArcString) -> Option<u64> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .reports
+            .iter()
+            .find(|report| report.reported == *user)
+            .map(|report| report.id)
+    }
+
+    /// 隔离一条消息，返回其编号
+    fn hold(&self, message: Message, cause: HoldCause) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        if inner.held.len() == MODERATION_CAPACITY {
+            inner.held.pop_front();
+        }
+        inner.held.push_back(Quarantined {
+            id,
+            held_at: Local::now(),
+            cause,
+            message,
+        });
+        id
+    }
+
+    /// 所有隔离的消息（按隔离顺序）
+    fn held(&self) -> Vec<Quarantined> {
+        self.inner.lock().unwrap().held.iter().cloned().collect()
+    }
+
+    /// 取出编号为 `id` 的隔离消息
+    fn take_held(&self, id: u64) -> Option<Quarantined> {
+        let mut inner = self.inner.lock().unwrap();
+        let index = inner.held.iter().position(|held| held.id == id)?;
+        inner.held.remove(index)
+    }
+}
+
+impl Server {
+    /// `/report <用户> <理由>`：举报用户，附上双方最近的往来消息，提醒在线的管理员
+    pub(super) async fn report_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let (user, reason) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let reason = reason.trim();
+        if user.is_empty() || reason.is_empty() {
+            return tr!(lang => "cmd.usage_report");
+        }
+        let reported = ArcString::new(user.to_string());
+        if reported == session.username {
+            return tr!(lang => "cmd.report_self");
+        }
+        if !self.users.contains_key(&reported) {
+            return tr!(lang => "cmd.report_unknown", user = user);
+        }
+        let reporter = &session.username;
+        let context = self.report_context(reporter, &reported);
+        let id = self
+            .moderation
+            .file(reporter, &reported, reason, context.clone());
+        self.audit(
+            AuditAction::Reported,
+            user,
+            tr!(
+                "moderation.audit_report",
+                id = id,
+                reporter = reporter,
+                reason = reason
+            ),
+        );
+        self.alert_admins(|lang| {
+            tr!(lang => "moderation.alert",
+                id = id,
+                reporter = reporter,
+                reported = reported,
+                reason = reason,
+            )
+        })
+        .await;
+        tr!(lang => "cmd.reported", user = user, id = id, context = context.len())
+    }
+
+    /// `reporter` 与 `reported` 最近的 [`REPORT_CONTEXT`] 条往来消息，读取消息日志失败时为空
+    fn report_context(&self, reporter: &ArcString, reported: &ArcString) -> Vec<Message> {
+        let between = |to: &ArcString, from: &ArcString| {
+            let from = from.get();
+            self.storage
+                .messages_after(&to.get(), 0)
+                .map(|messages| {
+                    messages
+                        .into_iter()
+                        .filter(|msg| msg.from() == from)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", tr!("server.log_read_failed", error = e));
+                    Vec::new()
+                })
+        };
+        let mut context = between(reporter, reported);
+        context.extend(between(reported, reporter));
+        context.sort_by_key(Message::id);
+        context.drain(..context.len().saturating_sub(REPORT_CONTEXT));
+        context
+    }
+
+    /// 审核队列中待处理的举报（按提交顺序）
+    pub fn pending_reports(&self) -> Vec<Report> {
+        self.moderation.reports()
+    }
+
+    /// `/reports [dismiss <编号>]`（仅管理员）与管理套接字的 `reports`：以 `lang` 列出待处理的举报，
+    /// 或把处理完毕的举报移出队列；`admin` 为处理的管理员，管理套接字上为 `None`
+    pub(super) fn reports_command(
+        &self,
+        admin: Option<&ArcString>,
+        args: &str,
+        lang: Lang,
+    ) -> String {
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (None, _) => {
+                let reports = self.pending_reports();
+                if reports.is_empty() {
+                    return tr!(lang => "cmd.reports_empty");
+                }
+                let lines: Vec<String> =
+                    reports.iter().map(|report| report.describe(lang)).collect();
+                format!(
+                    "{}\n  › {}",
+                    tr!(lang => "cmd.reports", count = reports.len()),
+                    lines.join("\n  › ")
+                )
+            }
+            (Some("dismiss"), Some(id)) => {
+                let Some(id) = id.trim_start_matches('#').parse().ok() else {
+                    return tr!(lang => "cmd.usage_reports");
+                };
+                let Some(report) = self.moderation.take_report(id) else {
+                    return tr!(lang => "cmd.report_missing", id = id);
+                };
+                let admin = admin.map_or_else(|| "admin-socket".to_string(), ArcString::get);
+                self.audit(
+                    AuditAction::ReportDismissed,
+                    &report.reported.get(),
+                    tr!("moderation.audit_dismiss", id = id, admin = admin),
+                );
+                tr!(lang => "cmd.report_dismissed", id = id)
+            }
+            _ => tr!(lang => "cmd.usage_reports"),
+        }
+    }
+
+    /// 配置了隔离时，`sender` 接下来的消息应被隔离的原因：本条消息触发的反垃圾规则 `flagged`，
+    /// 或发送者的待处理举报
+    pub(super) fn quarantine_cause(
+        &self,
+        sender: &ArcString,
+        flagged: Option<SpamRule>,
+    ) -> Option<HoldCause> {
+        if !self.config.quarantine {
+            return None;
+        }
+        flagged.map(HoldCause::Spam).or_else(|| {
+            self.moderation
+                .pending_report(sender)
+                .map(HoldCause::Reported)
+        })
+    }
+
+    /// 隔离 `session` 发出的 `msg`：写入审计日志，提醒在线的管理员并告知发送者
+    pub(super) async fn quarantine(&self, session: &Session, msg: Message, cause: HoldCause) {
+        let to = msg.to().to_string();
+        let id = self.moderation.hold(msg, cause);
+        self.audit(
+            AuditAction::Quarantined,
+            &session.username.get(),
+            tr!(
+                "moderation.audit_hold",
+                id = id,
+                to = to,
+                cause = cause.describe(crate::i18n::lang()),
+            ),
+        );
+        self.alert_admins(|lang| {
+            tr!(lang => "moderation.hold_alert",
+                id = id,
+                from = session.username,
+                to = to,
+                cause = cause.describe(lang),
+            )
+        })
+        .await;
+        let text = tr!(session.lang => "server.quarantined", user = to);
+        self.reply(&session.username, text).await;
+    }
+
+    /// 审核队列中隔离的消息（按隔离顺序）
+    pub fn quarantined_messages(&self) -> Vec<Quarantined> {
+        self.moderation.held()
+    }
+
+    /// `/quarantine [release|discard <编号>]`（仅管理员）与管理套接字的 `quarantine`：以 `lang` 列出隔离的消息，
+    /// 或放行、丢弃其中一条；`admin` 为处理的管理员，管理套接字上为 `None`
+    pub(super) async fn quarantine_command(
+        &self,
+        admin: Option<&ArcString>,
+        args: &str,
+        lang: Lang,
+    ) -> String {
+        let mut parts = args.split_whitespace();
+        let action = parts.next();
+        let id = parts
+            .next()
+            .and_then(|id| id.trim_start_matches('#').parse().ok());
+        match (action, id) {
+            (None, _) => {
+                let held = self.quarantined_messages();
+                if held.is_empty() {
+                    return tr!(lang => "cmd.quarantine_empty");
+                }
+                let lines: Vec<String> = held.iter().map(|held| held.describe(lang)).collect();
+                format!(
+                    "{}\n  › {}",
+                    tr!(lang => "cmd.quarantine", count = held.len()),
+                    lines.join("\n  › ")
+                )
+            }
+            (Some(action @ ("release" | "discard")), Some(id)) => {
+                let Some(held) = self.moderation.take_held(id) else {
+                    return tr!(lang => "cmd.quarantine_missing", id = id);
+                };
+                let admin = admin.map_or_else(|| "admin-socket".to_string(), ArcString::get);
+                let sender = ArcString::new(held.message.from().to_string());
+                let recipient = ArcString::new(held.message.to().to_string());
+                if action == "discard" {
+                    self.audit(
+                        AuditAction::QuarantineDiscarded,
+                        &sender.get(),
+                        tr!(
+                            "moderation.audit_discard",
+                            id = id,
+                            to = recipient,
+                            admin = admin
+                        ),
+                    );
+                    return tr!(lang => "cmd.quarantine_discarded", id = id);
+                }
+                self.audit(
+                    AuditAction::QuarantineReleased,
+                    &sender.get(),
+                    tr!(
+                        "moderation.audit_release",
+                        id = id,
+                        to = recipient,
+                        admin = admin
+                    ),
+                );
+                // 放行的消息照常写入消息日志：接收方不在线时留待重连补发；聊天室消息转给其他成员
+                let Some(msg) = self.release_to_room(held.message).await else {
+                    return tr!(lang => "cmd.quarantine_released", id = id, user = recipient);
+                };
+                let msg = self.log_message(msg);
+                let state = match self.online_users.contains_key(&recipient) {
+                    true => DeliveryState::Sent,
+                    false => DeliveryState::Queued,
+                };
+                self.receipts.record(&sender, &recipient, &msg, state);
+                self.deliver_local(&recipient, msg).await;
+                tr!(lang => "cmd.quarantine_released", id = id, user = recipient)
+            }
+            _ => tr!(lang => "cmd.usage_quarantine"),
+        }
+    }
+
+    /// 以各管理员的语言提醒在线的管理员
+    pub(super) async fn alert_admins(&self, render: impl Fn(Lang) -> String) {
+        let admins: Vec<(ArcString, Lang)> = self
+            .roster()
+            .iter()
+            .filter(|outbox| self.config.admins.contains_key(outbox.name.get().as_str()))
+            .map(|outbox| (outbox.name.clone(), outbox.lang))
+            .collect();
+        for (admin, lang) in admins {
+            self.reply(&admin, render(lang)).await;
+        }
+    }
+}
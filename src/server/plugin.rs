@@ -0,0 +1,93 @@
+/*!
+# 服务器插件
+
+运营者通过 [`Server::with_plugin`] 挂载实现了 [`Plugin`] 的插件，在不修改 [`Server::run`] 的情况下
+插入自定义的策略。目前提供的钩子：
+
+- [`Plugin::on_accept`]：接受连接后、注册之前按对端地址决定是否允许，如只允许公司网段、
+  限定可以连接的时段。被拒绝的连接在简短的握手后收到 `DENIED` 错误帧（或文字提示），
+  其中附带插件给出的原因，随后断开。
+- [`Plugin::on_file_offer`]：撮合文件传输之前按发送方、接收方、文件名与大小决定是否允许，
+  如只允许特定用户发送文件。被拒绝时发送方收到 `TRANSFER_BLOCKED` 错误帧，其中附带插件给出的原因。
+
+挂载了多个插件时按挂载顺序调用，任一插件拒绝即拒绝该连接或传输。钩子在接受连接的循环中同步调用，
+应当尽快返回，不要在其中做阻塞的 I/O。插件只对挂载它的 `Server` 接受的连接生效：
+虚拟主机上的连接由默认空间接受，使用默认空间的插件。
+
+```text
+#[derive(Debug)]
+struct OfficeOnly;
+
+impl Plugin for OfficeOnly {
+    fn on_accept(&self, peer_addr: SocketAddr) -> AcceptDecision {
+        match peer_addr.ip() {
+            IpAddr::V4(ip) if ip.octets()[..2] == [10, 8] => AcceptDecision::Allow,
+            _ => AcceptDecision::Deny("仅限公司网络".into()),
+        }
+    }
+}
+
+let server = Server::with_config(config).with_plugin(OfficeOnly);
+```
+*/
+
+use super::Server;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// 插件对新连接或文件传输的决定
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AcceptDecision {
+    /// 允许，继续后续的检查与注册
+    Allow,
+    /// 拒绝，原因原样告知客户端并写入服务器日志
+    Deny(String),
+}
+
+/// 服务器插件，各钩子都有默认实现（不做任何限制），插件只需实现关心的钩子
+pub trait Plugin: Send + Sync + fmt::Debug {
+    /// 接受连接后、注册之前调用；`peer_addr` 为对端地址（IPv4 映射的 IPv6 地址已还原为 IPv4）
+    fn on_accept(&self, _peer_addr: SocketAddr) -> AcceptDecision {
+        AcceptDecision::Allow
+    }
+
+    /// 转发文件传输的请求之前调用；`size` 为发送方声明的文件大小（字节）
+    fn on_file_offer(&self, _from: &str, _to: &str, _name: &str, _size: u64) -> AcceptDecision {
+        AcceptDecision::Allow
+    }
+}
+
+impl Server {
+    /// 挂载插件，多个插件按挂载顺序调用
+    pub fn with_plugin(mut self, plugin: impl Plugin + 'static) -> Self {
+        Arc::make_mut(&mut self.plugins).push(Arc::new(plugin));
+        self
+    }
+
+    /// 依次询问各插件是否接受来自 `peer_addr` 的连接，返回第一个拒绝的原因
+    pub(super) fn plugin_refusal(&self, peer_addr: SocketAddr) -> Option<String> {
+        self.plugins
+            .iter()
+            .find_map(|plugin| match plugin.on_accept(peer_addr) {
+                AcceptDecision::Allow => None,
+                AcceptDecision::Deny(reason) => Some(reason),
+            })
+    }
+
+    /// 依次询问各插件是否允许 `from` 发给 `to` 的文件，返回第一个拒绝的原因
+    pub(super) fn plugin_file_refusal(
+        &self,
+        from: &str,
+        to: &str,
+        name: &str,
+        size: u64,
+    ) -> Option<String> {
+        self.plugins
+            .iter()
+            .find_map(|plugin| match plugin.on_file_offer(from, to, name, size) {
+                AcceptDecision::Allow => None,
+                AcceptDecision::Deny(reason) => Some(reason),
+            })
+    }
+}
@@ -0,0 +1,259 @@
+/*!
+# 投递回执
+
+服务器为发给本服务器已知用户的每条消息（即分配了编号的消息）记录投递状态，发送者通过
+`/receipts <用户> [条数]` 查看最近发给该用户的消息走到了哪一步：
+
+- **已发送**：接收方在线，消息已进入其路由队列；
+- **离线暂存**：接收方不在线或处于免打扰状态，消息等待其上线或关闭免打扰后补发；
+- **已送达**：消息已写入接收方的连接（包括重连后补发的消息）；
+- **已读**：接收方的客户端显示该消息后发来 [`Control::Read`](crate::protocol::Control::Read)，
+  同时视为已读该发送者此前发来的消息。
+
+状态只会前进，不会回退。每对发送者与接收者保留最近 [`RECEIPTS_KEPT`] 条，
+记录的会话超过 [`MAX_CONVERSATIONS`] 对时丢弃最久没有新消息的一对；回执只保存在内存中，重启后清空。
+*/
+
+use super::Server;
+use crate::i18n::Lang;
+use crate::session::Session;
+use crate::{tr, ArcString, Message, MessageKind};
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// 每对发送者与接收者保留的回执条数
+pub const RECEIPTS_KEPT: usize = 50;
+
+/// 最多记录回执的发送者与接收者对数
+pub const MAX_CONVERSATIONS: usize = 10_000;
+
+/// `/receipts` 默认显示的条数
+const RECEIPTS_DEFAULT_SHOWN: usize = 10;
+
+/// 回执中内容预览的最大字符数
+const PREVIEW_CHARS: usize = 30;
+
+/// 消息的投递状态，按投递进度排序
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeliveryState {
+    /// 已进入在线接收方的路由队列
+    Sent,
+    /// 接收方不在线或处于免打扰状态，等待补发
+    Queued,
+    /// 已写入接收方的连接
+    Delivered,
+    /// 接收方的客户端已显示
+    Read,
+}
+
+impl DeliveryState {
+    /// 以 `lang` 描述投递状态
+    pub fn describe(self, lang: Lang) -> String {
+        match self {
+            DeliveryState::Sent => tr!(lang => "receipt.sent"),
+            DeliveryState::Queued => tr!(lang => "receipt.queued"),
+            DeliveryState::Delivered => tr!(lang => "receipt.delivered"),
+            DeliveryState::Read => tr!(lang => "receipt.read"),
+        }
+    }
+}
+
+/// 一条消息的回执
+#[derive(Clone, Debug)]
+pub struct Receipt {
+    /// 服务器分配的消息编号
+    pub id: u64,
+    /// 服务器收到消息的时间
+    pub sent_at: DateTime<Local>,
+    /// 内容的开头部分，端到端加密的消息为空
+    pub preview: String,
+    /// 当前的投递状态
+    pub state: DeliveryState,
+    /// 进入当前状态的时间
+    pub updated_at: DateTime<Local>,
+}
+
+/// (发送者, 接收者)
+type Conversation = (ArcString, ArcString);
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// 各会话最近的回执，按消息编号递增
+    conversations: HashMap<Conversation, VecDeque<Receipt>>,
+    /// 消息编号所属的会话
+    index: HashMap<u64, Conversation>,
+}
+
+/// 所有会话的投递回执
+#[derive(Debug, Default)]
+pub(super) struct Receipts {
+    inner: Mutex<Inner>,
+}
+
+impl Receipts {
+    /// 记录 `sender` 发给 `recipient` 的消息 `msg` 的初始状态；没有编号的消息不记录
+    pub(super) fn record(
+        &self,
+        sender: &ArcString,
+        recipient: &ArcString,
+        msg: &Message,
+        state: DeliveryState,
+    ) {
+        let Some(id) = msg.id() else {
+            return;
+        };
+        let preview = match msg.kind() {
+            MessageKind::Encrypted => String::new(),
+            MessageKind::Text | MessageKind::Action => {
+                let mut chars = msg.content().chars();
+                let mut preview: String = chars.by_ref().take(PREVIEW_CHARS).collect();
+                if chars.next().is_some() {
+                    preview.push('…');
+                }
+                preview.replace('\n', " ")
+            }
+        };
+        let now = Local::now();
+        let key = (sender.clone(), recipient.clone());
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.conversations.contains_key(&key) && inner.conversations.len() >= MAX_CONVERSATIONS
+        {
+            inner.evict_oldest();
+        }
+        let Inner {
+            conversations,
+            index,
+        } = &mut *inner;
+        let receipts = conversations.entry(key.clone()).or_default();
+        if receipts.len() == RECEIPTS_KEPT {
+            if let Some(oldest) = receipts.pop_front() {
+                index.remove(&oldest.id);
+            }
+        }
+        receipts.push_back(Receipt {
+            id,
+            sent_at: now,
+            preview,
+            state,
+            updated_at: now,
+        });
+        index.insert(id, key);
+    }
+
+    /// 编号为 `id` 的消息已写入接收方的连接
+    pub(super) fn delivered(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(key) = inner.index.get(&id).cloned() else {
+            return;
+        };
+        if let Some(receipt) = inner
+            .conversations
+            .get_mut(&key)
+            .and_then(|receipts| receipts.iter_mut().find(|receipt| receipt.id == id))
+        {
+            receipt.advance(DeliveryState::Delivered);
+        }
+    }
+
+    /// `reader` 已读编号为 `id` 的消息，以及同一发送者此前发来的消息；
+    /// 该消息不是发给 `reader` 的则忽略
+    pub(super) fn read(&self, reader: &ArcString, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(key) = inner.index.get(&id).cloned() else {
+            return;
+        };
+        if key.1 != *reader {
+            return;
+        }
+        if let Some(receipts) = inner.conversations.get_mut(&key) {
+            for receipt in receipts.iter_mut().take_while(|receipt| receipt.id <= id) {
+                receipt.advance(DeliveryState::Read);
+            }
+        }
+    }
+
+    /// `sender` 最近发给 `recipient` 的 `count` 条消息的回执（按发送顺序）
+    pub(super) fn recent(
+        &self,
+        sender: &ArcString,
+        recipient: &ArcString,
+        count: usize,
+    ) -> Vec<Receipt> {
+        let inner = self.inner.lock().unwrap();
+        let key = (sender.clone(), recipient.clone());
+        let Some(receipts) = inner.conversations.get(&key) else {
+            return Vec::new();
+        };
+        let skip = receipts.len().saturating_sub(count);
+        receipts.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Inner {
+    /// 丢弃最久没有新消息的会话
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .conversations
+            .iter()
+            .min_by_key(|(_, receipts)| receipts.back().map(|receipt| receipt.sent_at))
+            .map(|(key, _)| key.clone());
+        if let Some(receipts) = oldest.and_then(|key| self.conversations.remove(&key)) {
+            for receipt in receipts {
+                self.index.remove(&receipt.id);
+            }
+        }
+    }
+}
+
+impl Receipt {
+    /// 前进到 `state`，已经在该状态或更靠后时不变
+    fn advance(&mut self, state: DeliveryState) {
+        if state > self.state {
+            self.state = state;
+            self.updated_at = Local::now();
+        }
+    }
+}
+
+impl Server {
+    /// `/receipts <用户> [条数]`：查看自己最近发给该用户的消息的投递状态
+    pub(super) fn receipts_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        let (Some(user), count) = (parts.next(), parts.next()) else {
+            return tr!(lang => "cmd.usage_receipts");
+        };
+        let count = match count.map(str::parse) {
+            None => RECEIPTS_DEFAULT_SHOWN,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => return tr!(lang => "cmd.usage_receipts"),
+        };
+        let recipient = ArcString::new(user.to_string());
+        let receipts = self.receipts.recent(&session.username, &recipient, count);
+        if receipts.is_empty() {
+            return tr!(lang => "cmd.receipts_empty", user = user);
+        }
+        let lines: Vec<String> = receipts
+            .iter()
+            .map(|receipt| {
+                let preview = match receipt.preview.is_empty() {
+                    true => tr!(lang => "cmd.receipt_encrypted"),
+                    false => receipt.preview.clone(),
+                };
+                tr!(lang => "cmd.receipt",
+                    id = receipt.id,
+                    time = receipt.sent_at.format("%H:%M:%S"),
+                    preview = preview,
+                    state = receipt.state.describe(lang),
+                    updated = receipt.updated_at.format("%H:%M:%S"),
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.receipts", user = user, count = receipts.len()),
+            lines.join("\n  › ")
+        )
+    }
+}
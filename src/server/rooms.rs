@@ -0,0 +1,917 @@
+/*!
+# 聊天室
+
+以 `#` 开头的地址（例如 `#ops`）是本服务器上的聊天室，发给它的消息转给聊天室的其他成员：
+
+- `/join #名称` 加入聊天室，聊天室不存在时随之创建，创建者可以修改聊天室的设置；
+  `/part #名称` 退出，`/rooms` 查看自己加入的聊天室；
+- 私有聊天室（创建者以 `/room #名称 private on` 设置）不能直接加入：创建者或管理员以
+  `/invite-link #名称 [<次数> [<有效小时数>]]` 生成邀请码，其他用户以 `/join <邀请码>` 加入；
+  邀请码的使用次数与有效期由服务器记录（见 `invites` 子模块），用完或过期后失效；
+- 成员关系随用户数据持久化（[`UserRecord::rooms`]），用户登录时自动重新加入，
+  并补发离线期间聊天室中其他成员发出的消息：每个聊天室最多补发最近的若干条
+  （[`RoomRecord::retention`]，默认 [`DEFAULT_ROOM_RETENTION`]，创建者以
+  `/room #名称 retention <条数>` 修改，至少 1 条），更早的消息中只补发提到该成员（`@用户名`）的，
+  补发后附上各聊天室的新消息数与提及数；
+- 聊天室的消息只写入一次消息日志（接收方为聊天室地址）；按成员的通知偏好附上的提示建议
+  （成员可以 `/notify mute #名称` 静音聊天室）每种只序列化一次：编码后的 JSON 负载（[`SharedMessage`]）
+  由收到同一提示建议的在线成员的写循环共享，各连接只按自己的分帧格式加上分隔符或长度前缀；
+  投递在同一任务中并发进行，同时进行的至多 [`ROOM_FANOUT`] 个，某个成员的发送通道已满时
+  不会拖住其他成员：
+  - 处于免打扰状态的成员：消息暂存，关闭免打扰后补发；
+  - 发送通道已满的成员：与私聊消息一样等待至宽限期结束，仍无空间时放入死信队列；
+  - 不在线的成员：重新登录时从聊天室的消息日志补发（见 [`Server::rooms_of`]）。
+
+Telegram 桥接聊天室（`tg:group`）同样以此广播与补发。聊天室不支持端到端加密。
+*/
+
+use super::commands::DEFAULT_INVITE_HOURS;
+use super::invites::{Invite, InviteError};
+use super::spam::SpamRule;
+use super::{Outbox, Outgoing, Server};
+use crate::codec::{self, CodecError};
+use crate::highlight::HighlightRules;
+use crate::i18n::Lang;
+use crate::notify::NotifyHint;
+use crate::protocol::{ErrorCode, TraceEvent};
+use crate::server::dead_letter::Reason;
+use crate::session::{Role, Session};
+use crate::storage::{RoomRecord, UserRecord};
+use crate::{tr, ArcString, Message, MessageKind};
+use bytes::Bytes;
+use chrono::Local;
+use futures_util::stream::{self, StreamExt};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 聊天室地址的前缀
+pub const ROOM_PREFIX: char = '#';
+
+/// 聊天室名称（不含前缀）的最大字符数
+const MAX_ROOM_NAME: usize = 32;
+
+/// 未设置保留条数的聊天室，成员重新登录时最多补发的最近消息条数
+pub const DEFAULT_ROOM_RETENTION: usize = 100;
+
+/// 聊天室广播时同时投递的成员数上限
+pub const ROOM_FANOUT: usize = 32;
+
+/// 已编码、由多个连接共享的聊天室消息
+#[derive(Debug)]
+pub(super) struct SharedMessage {
+    /// 消息本身，用于统计、回执、追踪与死信
+    pub(super) message: Message,
+    /// 编码后的 JSON 负载（不含分帧）
+    pub(super) payload: Bytes,
+}
+
+impl SharedMessage {
+    /// 编码消息，超过帧长度上限时返回错误
+    fn new(message: Message) -> Result<Self, CodecError> {
+        let payload = codec::encode_payload(&message)?;
+        Ok(SharedMessage { message, payload })
+    }
+}
+
+/// 解析聊天室地址：`#` 加 1 到 [`MAX_ROOM_NAME`] 个字母、数字、`-` 或 `_`，统一为小写
+pub fn parse_room(address: &str) -> Option<String> {
+    let name = address.strip_prefix(ROOM_PREFIX)?;
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_ROOM_NAME
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| address.to_lowercase())
+}
+
+/// `msg` 是否提到了 `username`
+fn mentions(username: &str, msg: &Message) -> bool {
+    msg.kind() != MessageKind::Encrypted
+        && !HighlightRules::new()
+            .find(username, msg.content())
+            .is_empty()
+}
+
+impl Server {
+    /// 把已写入消息日志的聊天室消息 `msg` 并发投递给 `members`
+    ///
+    /// 提示建议按各成员的通知偏好计算（见 [`crate::notify`]），每种提示建议的消息只编码一次
+    pub(super) async fn broadcast_room(&self, msg: Message, members: Vec<ArcString>) {
+        let mut encoded: Vec<(Option<NotifyHint>, Arc<SharedMessage>)> = Vec::new();
+        let mut deliveries = Vec::with_capacity(members.len());
+        for member in members {
+            let hint = self.notify_hint(&member, &msg);
+            let shared = match encoded.iter().find(|(encoded, _)| *encoded == hint) {
+                Some((_, shared)) => shared.clone(),
+                None => {
+                    let message = match hint {
+                        Some(hint) => msg.clone().with_notify(hint),
+                        None => msg.clone(),
+                    };
+                    let shared = match SharedMessage::new(message) {
+                        Ok(shared) => Arc::new(shared),
+                        Err(e) => {
+                            eprintln!("{}", tr!("server.send_failed", error = e));
+                            return;
+                        }
+                    };
+                    encoded.push((hint, shared.clone()));
+                    shared
+                }
+            };
+            deliveries.push((member, shared));
+        }
+        stream::iter(deliveries)
+            .for_each_concurrent(ROOM_FANOUT, |(member, shared)| {
+                self.deliver_shared(member, shared)
+            })
+            .await;
+    }
+
+    /// 把共享的聊天室消息投递给成员 `member`
+    async fn deliver_shared(&self, member: ArcString, shared: Arc<SharedMessage>) {
+        let msg = &shared.message;
+        let Some((outbox, presence)) = self
+            .online_users
+            .get(&member)
+            .map(|user| (user.outbox(&member), user.presence.clone()))
+        else {
+            // 不在线：重新登录时从聊天室的消息日志补发
+            self.trace_event(msg.from(), msg.id(), TraceEvent::Queued);
+            return;
+        };
+        {
+            let mut presence = presence.lock().unwrap();
+            if presence.dnd {
+                self.trace_event(msg.from(), msg.id(), TraceEvent::Queued);
+                if let Some(evicted) = presence.queue(msg.clone()) {
+                    self.dead_letters
+                        .push(evicted.with_to(member.get()), Reason::HeldOverflow);
+                }
+                return;
+            }
+        }
+        self.trace_event(msg.from(), msg.id(), TraceEvent::Routed);
+        if let Err(reason) = self.send_shared(&outbox, shared.clone()).await {
+            // 死信按接收方重新投递，改为发给该成员
+            let msg = shared.message.clone().with_to(member.get());
+            self.dead_letters.push(msg, reason);
+        }
+    }
+
+    /// 把共享的消息放入 `outbox` 的发送通道，通道已满时按过慢的接收方处理
+    async fn send_shared(&self, outbox: &Outbox, shared: Arc<SharedMessage>) -> Result<(), Reason> {
+        let permit = self.reserve(outbox).await?;
+        permit.send(Outgoing::Shared(shared));
+        Ok(())
+    }
+
+    /// 把发给聊天室的消息写入聊天室的消息日志，并转给其他成员；`flagged` 为消息触发的反垃圾规则，
+    /// 与发给用户的消息一样，需要审核的发送者的消息隔离待审核，不写入消息日志
+    ///
+    /// # 返回值
+    /// `msg` 不是发给聊天室时返回 `false`，由调用方按普通消息处理
+    pub(super) async fn route_to_room(
+        &self,
+        session: &Session,
+        msg: &Message,
+        flagged: Option<SpamRule>,
+    ) -> bool {
+        // 名字本身以 `#` 开头的已注册用户仍按用户处理
+        let target = ArcString::new(msg.to().to_string());
+        if self.users.contains_key(&target) {
+            return false;
+        }
+        let Some(room) = parse_room(msg.to()) else {
+            return false;
+        };
+        let lang = session.lang;
+        if msg.kind() == MessageKind::Encrypted {
+            let tip = tr!(lang => "server.room_encrypted");
+            self.reject(session, ErrorCode::Blocked, Some(&target), msg.id(), tip)
+                .await;
+            return true;
+        }
+        if !self.is_room_member(&session.username, &room) {
+            let tip = tr!(lang => "server.room_not_member", room = room);
+            self.reject(session, ErrorCode::NotMember, Some(&target), msg.id(), tip)
+                .await;
+            return true;
+        }
+        let msg = msg.clone().with_to(room);
+        if let Some(cause) = self.quarantine_cause(&session.username, flagged) {
+            self.quarantine(session, msg, cause).await;
+            return true;
+        }
+        self.post_to_room(msg).await;
+        true
+    }
+
+    /// 放行审核队列中隔离的消息：发给聊天室的写入消息日志并转给其他成员
+    ///
+    /// # 返回值
+    /// `msg` 不是发给聊天室时原样返回，由调用方投递给接收方
+    pub(super) async fn release_to_room(&self, msg: Message) -> Option<Message> {
+        let target = ArcString::new(msg.to().to_string());
+        if self.users.contains_key(&target) || parse_room(msg.to()).is_none() {
+            return Some(msg);
+        }
+        self.post_to_room(msg).await;
+        None
+    }
+
+    /// 把发给聊天室（`msg` 的接收方）的消息写入消息日志，并转给除发送者外的成员
+    async fn post_to_room(&self, msg: Message) {
+        let sender = ArcString::new(msg.from().to_string());
+        let room = msg.to().to_string();
+        let msg = self.log_message(msg);
+        let members = self
+            .room_members(&room)
+            .into_iter()
+            .filter(|member| *member != sender)
+            .collect();
+        self.broadcast_room(msg, members).await;
+    }
+
+    /// `username` 是否为聊天室 `room` 的成员
+    fn is_room_member(&self, username: &ArcString, room: &str) -> bool {
+        self.users
+            .get(username)
+            .is_some_and(|user| user.rooms.contains_key(room))
+    }
+
+    /// 聊天室 `room` 的成员（按用户名排序）
+    fn room_members(&self, room: &str) -> Vec<ArcString> {
+        let mut members: Vec<ArcString> = self
+            .users
+            .iter()
+            .filter(|entry| entry.value().rooms.contains_key(room))
+            .map(|entry| entry.key().clone())
+            .collect();
+        members.sort_by_key(|member| member.get());
+        members
+    }
+
+    /// 聊天室 `room` 的成员重新登录时最多补发的最近消息条数
+    fn room_retention(&self, room: &str) -> usize {
+        self.rooms
+            .get(room)
+            .and_then(|record| record.retention)
+            .filter(|count| *count > 0)
+            .unwrap_or(DEFAULT_ROOM_RETENTION)
+    }
+
+    /// 将聊天室的设置写入持久化存储，失败时仅记录日志
+    fn save_room(&self, room: &str) {
+        let Some(record) = self.rooms.get(room).map(|record| record.clone()) else {
+            return;
+        };
+        if let Err(e) = self.storage.save_room(room, &record) {
+            eprintln!("{}", tr!("server.save_room_failed", room = room, error = e));
+        }
+    }
+
+    /// `username` 所在的聊天室及各自补发的起点（不含该编号）
+    ///
+    /// 聊天室从上次离线时的编号补发；Telegram 桥接聊天室不记录离线时的编号，
+    /// 只在客户端请求补发时从 `resume_from` 补发
+    pub(super) fn rooms_of(
+        &self,
+        username: &ArcString,
+        resume_from: Option<u64>,
+    ) -> Vec<(String, u64)> {
+        let Some(user) = self.users.get(username) else {
+            return Vec::new();
+        };
+        let from = resume_from.unwrap_or(0);
+        let rooms = user
+            .rooms
+            .iter()
+            .map(|(room, seen)| (room.clone(), (*seen).max(from)));
+        #[cfg(feature = "bridges")]
+        let rooms = rooms.chain(
+            resume_from
+                .filter(|_| self.telegram.is_some() && user.telegram)
+                .map(|after| (super::telegram::ROOM.to_string(), after)),
+        );
+        rooms.collect()
+    }
+
+    /// 用户登录后自动重新加入所在的聊天室：补发离线期间其他成员发出的消息，
+    /// 并告知各聊天室的新消息数与提及数
+    pub(super) async fn rejoin_rooms(&self, session: &Session, resume_from: Option<u64>) {
+        let Some(tx) = self
+            .online_users
+            .get(&session.username)
+            .map(|user| user.tx.clone())
+        else {
+            return;
+        };
+        let username = session.username.get();
+        let mut summary = Vec::new();
+        let mut replay = Vec::new();
+        for (room, after) in self.rooms_of(&session.username, resume_from) {
+            let mut missed = match self.storage.messages_after(&room, after) {
+                Ok(missed) => missed,
+                Err(e) => {
+                    eprintln!("{}", tr!("server.log_read_failed", error = e));
+                    continue;
+                }
+            };
+            missed.retain(|msg| msg.from() != username);
+            if missed.is_empty() {
+                continue;
+            }
+            let count = missed.len();
+            let mentioned = missed.iter().filter(|msg| mentions(&username, msg)).count();
+            // 超出保留条数的较早消息只补发提到该成员的
+            let older = count.saturating_sub(self.room_retention(&room));
+            let mut index = 0;
+            missed.retain(|msg| {
+                index += 1;
+                index > older || mentions(&username, msg)
+            });
+            summary.push(tr!(session.lang => "server.rooms_rejoined_entry",
+                room = room,
+                count = count,
+                mentions = mentioned,
+            ));
+            replay.extend(missed);
+        }
+        if replay.is_empty() {
+            return;
+        }
+        replay.sort_by_key(|msg| msg.id());
+        println!(
+            "{}",
+            tr!(
+                "server.replayed",
+                user = session.username,
+                count = replay.len()
+            )
+        );
+        for msg in replay {
+            let _ = tx.send(msg.into()).await;
+        }
+        let text = format!(
+            "{}\n  › {}",
+            tr!(session.lang => "server.rooms_rejoined", count = summary.len()),
+            summary.join("\n  › ")
+        );
+        self.reply(&session.username, text).await;
+    }
+
+    /// 用户离线时记下消息日志中最大的消息编号，重新登录时从此补发聊天室消息
+    pub(super) fn mark_rooms_seen(&self, user: &mut UserRecord) {
+        let seen = self.last_message_id.load(Ordering::Relaxed);
+        for last in user.rooms.values_mut() {
+            *last = seen;
+        }
+    }
+
+    /// `/join <#聊天室>|<邀请码>`：加入聊天室，聊天室不存在时随之创建；私有聊天室须凭邀请码加入
+    pub(super) fn join_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let room = match parse_room(args) {
+            Some(room) => {
+                let private = self.rooms.get(&room).is_some_and(|record| record.private);
+                if private
+                    && session.role != Role::Admin
+                    && !self.is_room_member(&session.username, &room)
+                {
+                    return tr!(lang => "cmd.room_private", room = room);
+                }
+                room
+            }
+            None if args.is_empty() || args.starts_with(ROOM_PREFIX) => {
+                return tr!(lang => "cmd.usage_join");
+            }
+            // 不是聊天室地址时按私有聊天室的邀请码处理；已是成员时不计入使用次数
+            None => {
+                let invited = self.room_invites.get(args).and_then(|invite| invite.room);
+                if let Some(room) =
+                    invited.filter(|room| self.is_room_member(&session.username, room))
+                {
+                    return tr!(lang => "cmd.room_already_joined", room = room);
+                }
+                match self.room_invites.redeem(args) {
+                    Ok(Invite {
+                        code,
+                        room: Some(room),
+                        used,
+                        max_uses,
+                        ..
+                    }) => {
+                        println!(
+                            "{}",
+                            tr!(
+                                "server.room_invite_redeemed",
+                                user = session.username,
+                                room = room,
+                                code = code,
+                                used = used,
+                                max = max_uses,
+                            )
+                        );
+                        room
+                    }
+                    Ok(_) => return InviteError::Unknown.describe(lang),
+                    Err(e) => return e.describe(lang),
+                }
+            }
+        };
+        if self.is_room_member(&session.username, &room) {
+            return tr!(lang => "cmd.room_already_joined", room = room);
+        }
+        let created = !self.rooms.contains_key(&room);
+        if created {
+            let record = RoomRecord {
+                owner: session.username.get(),
+                created_at: Some(Local::now()),
+                ..RoomRecord::default()
+            };
+            self.rooms.insert(room.clone(), record);
+            self.save_room(&room);
+            println!(
+                "{}",
+                tr!("server.room_created", room = room, user = session.username)
+            );
+        }
+        let seen = self.last_message_id.load(Ordering::Relaxed);
+        if let Some(mut user) = self.users.get_mut(&session.username) {
+            user.rooms.insert(room.clone(), seen);
+        }
+        self.save_user(&session.username);
+        let count = self.room_members(&room).len();
+        match created {
+            true => tr!(lang => "cmd.room_created", room = room),
+            false => tr!(lang => "cmd.room_joined", room = room, count = count),
+        }
+    }
+
+    /// `/part <#聊天室>`：退出聊天室
+    pub(super) fn part_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let Some(room) = parse_room(args) else {
+            return tr!(lang => "cmd.usage_part");
+        };
+        let left = self
+            .users
+            .get_mut(&session.username)
+            .is_some_and(|mut user| user.rooms.remove(&room).is_some());
+        if !left {
+            return tr!(lang => "cmd.room_not_joined", room = room);
+        }
+        self.save_user(&session.username);
+        tr!(lang => "cmd.room_left", room = room)
+    }
+
+    /// `/rooms`：自己加入的聊天室及其成员数与在线人数
+    pub(super) fn rooms_command(&self, session: &Session) -> String {
+        let lang = session.lang;
+        let rooms: Vec<String> = self
+            .users
+            .get(&session.username)
+            .map(|user| user.rooms.keys().cloned().collect())
+            .unwrap_or_default();
+        if rooms.is_empty() {
+            return tr!(lang => "cmd.rooms_empty");
+        }
+        let lines: Vec<String> = rooms
+            .iter()
+            .map(|room| {
+                let members = self.room_members(room);
+                let online = members
+                    .iter()
+                    .filter(|member| self.online_users.contains_key(*member))
+                    .count();
+                tr!(lang => "cmd.rooms_entry",
+                    room = room,
+                    count = members.len(),
+                    online = online,
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.rooms_list", count = rooms.len()),
+            lines.join("\n  › ")
+        )
+    }
+
+    /// `session` 能否修改聊天室的设置并管理其邀请码：创建者与管理员
+    fn manages_room(&self, session: &Session, record: &RoomRecord) -> bool {
+        record.owner == session.username.get() || session.role == Role::Admin
+    }
+
+    /// `/room <#聊天室> [retention <条数>|retention default|private on|off]`：查看聊天室的设置与成员，
+    /// 创建者与管理员可以修改重新登录时补发的消息条数，或把聊天室设为私有
+    pub(super) fn room_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        let (Some(room), setting, value, None) = (
+            parts.next().and_then(parse_room),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return tr!(lang => "cmd.usage_room");
+        };
+        let Some(record) = self.rooms.get(&room).map(|record| record.clone()) else {
+            return tr!(lang => "cmd.room_missing", room = room);
+        };
+        // 私有聊天室只向成员与管理员公开
+        if record.private
+            && session.role != Role::Admin
+            && !self.is_room_member(&session.username, &room)
+        {
+            return tr!(lang => "cmd.room_private", room = room);
+        }
+        if setting.is_none() {
+            return self.room_info(&room, &record, lang);
+        }
+        if !self.manages_room(session, &record) {
+            return tr!(lang => "cmd.room_owner_only", room = room, owner = record.owner);
+        }
+        let reply = match (setting, value) {
+            (Some("retention"), Some(value)) => {
+                // 0 条会让重新登录的成员只收到提及，不补发任何上下文，视为无效
+                let retention = match value {
+                    "default" => None,
+                    value => match value.parse::<usize>() {
+                        Ok(count @ 1..) => Some(count),
+                        _ => return tr!(lang => "cmd.room_retention_invalid"),
+                    },
+                };
+                if let Some(mut record) = self.rooms.get_mut(&room) {
+                    record.retention = retention;
+                }
+                tr!(lang => "cmd.room_retention_set",
+                    room = room,
+                    count = retention.unwrap_or(DEFAULT_ROOM_RETENTION),
+                )
+            }
+            (Some("private"), Some(value @ ("on" | "off"))) => {
+                let private = value == "on";
+                if let Some(mut record) = self.rooms.get_mut(&room) {
+                    record.private = private;
+                }
+                match private {
+                    true => tr!(lang => "cmd.room_private_on", room = room),
+                    false => tr!(lang => "cmd.room_private_off", room = room),
+                }
+            }
+            _ => return tr!(lang => "cmd.usage_room"),
+        };
+        self.save_room(&room);
+        reply
+    }
+
+    /// 聊天室的创建者、是否私有、补发条数与成员
+    fn room_info(&self, room: &str, record: &RoomRecord, lang: Lang) -> String {
+        let members: Vec<String> = self
+            .room_members(room)
+            .iter()
+            .map(|member| member.get())
+            .collect();
+        let visibility = match record.private {
+            true => tr!(lang => "cmd.room_private_label"),
+            false => tr!(lang => "cmd.room_public_label"),
+        };
+        let mut reply = tr!(lang => "cmd.room_info",
+            room = room,
+            visibility = visibility,
+            owner = record.owner,
+            retention = self.room_retention(room),
+        );
+        reply.push('\n');
+        if members.is_empty() {
+            reply.push_str(&tr!(lang => "cmd.room_no_members"));
+        } else {
+            reply.push_str(&tr!(lang => "cmd.room_members",
+                count = members.len(),
+                members = members.join(", "),
+            ));
+        }
+        reply
+    }
+
+    /// `/invite-link <#聊天室> [<次数> [<有效小时数>]]|list|revoke <邀请码>`：
+    /// 生成、查看或撤销聊天室的邀请码（仅创建者与管理员）
+    pub(super) fn invite_link_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("list"), None) => self.list_room_invites(session),
+            (Some("revoke"), Some(code)) if parts.next().is_none() => {
+                self.revoke_room_invite(session, code)
+            }
+            (Some(room), uses) => {
+                let uses = uses.map_or(Some(1), |uses| uses.parse::<u32>().ok());
+                let hours = parts.next().map_or(Some(DEFAULT_INVITE_HOURS), |hours| {
+                    hours.parse::<u64>().ok()
+                });
+                let (Some(room), Some(uses @ 1..), Some(hours), None) =
+                    (parse_room(room), uses, hours, parts.next())
+                else {
+                    return tr!(lang => "cmd.usage_invite_link");
+                };
+                self.create_room_invite(session, &room, uses, hours)
+            }
+            (None, _) => tr!(lang => "cmd.usage_invite_link"),
+        }
+    }
+
+    /// 为聊天室 `room` 生成可使用 `uses` 次、`hours` 小时后过期（0 表示不过期）的邀请码
+    fn create_room_invite(&self, session: &Session, room: &str, uses: u32, hours: u64) -> String {
+        let lang = session.lang;
+        let Some(record) = self.rooms.get(room).map(|record| record.clone()) else {
+            return tr!(lang => "cmd.room_missing", room = room);
+        };
+        if !self.manages_room(session, &record) {
+            return tr!(lang => "cmd.room_owner_only", room = room, owner = record.owner);
+        }
+        let ttl = (hours > 0).then(|| Duration::from_secs(hours * 3600));
+        let invite = match self
+            .room_invites
+            .create(&session.username.get(), Some(room), uses, ttl)
+        {
+            Ok(invite) => invite,
+            Err(e) => return tr!(lang => "cmd.invite_failed", error = e),
+        };
+        let mut reply = tr!(lang => "cmd.room_invite_created",
+            room = room,
+            code = invite.code,
+            max = invite.max_uses,
+            expires = expires_text(&invite, lang),
+        );
+        if !record.private {
+            reply.push('\n');
+            reply.push_str(&tr!(lang => "cmd.room_invite_public", room = room));
+        }
+        reply
+    }
+
+    /// 有效的聊天室邀请码：创建者看到自己聊天室的，管理员看到全部
+    fn list_room_invites(&self, session: &Session) -> String {
+        let lang = session.lang;
+        let invites: Vec<Invite> = self
+            .room_invites
+            .list()
+            .into_iter()
+            .filter(|invite| {
+                invite.room.as_ref().is_some_and(|room| {
+                    self.rooms
+                        .get(room)
+                        .is_some_and(|record| self.manages_room(session, &record))
+                })
+            })
+            .collect();
+        if invites.is_empty() {
+            return tr!(lang => "cmd.room_invite_empty");
+        }
+        let lines: Vec<String> = invites
+            .iter()
+            .map(|invite| {
+                tr!(lang => "cmd.room_invite_entry",
+                    code = invite.code,
+                    room = invite.room.as_deref().unwrap_or_default(),
+                    used = invite.used,
+                    max = invite.max_uses,
+                    expires = expires_text(invite, lang),
+                    user = invite.created_by,
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.invite_list", count = invites.len()),
+            lines.join("\n  › ")
+        )
+    }
+
+    /// 撤销聊天室的邀请码 `code`（仅该聊天室的创建者与管理员）
+    fn revoke_room_invite(&self, session: &Session, code: &str) -> String {
+        let lang = session.lang;
+        let record = self
+            .room_invites
+            .get(code)
+            .and_then(|invite| invite.room)
+            .and_then(|room| self.rooms.get(&room).map(|record| record.clone()));
+        match record {
+            Some(record) if self.manages_room(session, &record) => {
+                self.room_invites.revoke(code);
+                tr!(lang => "cmd.invite_revoked", code = code)
+            }
+            _ => tr!(lang => "cmd.invite_missing", code = code),
+        }
+    }
+}
+
+/// 邀请码的有效期，不过期时为"永久"
+fn expires_text(invite: &Invite, lang: Lang) -> String {
+    invite.expires_at.map_or_else(
+        || tr!(lang => "cmd.invite_never"),
+        |at| at.format("%Y-%m-%d %H:%M").to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{connect, contents, login, send};
+    use super::super::ServerConfig;
+    use super::*;
+    use crate::protocol::{Frame, Hello};
+    use std::net::SocketAddr;
+
+    /// 已知用户 `name` 的会话，不经过连接
+    fn session(server: &Server, name: &str) -> Session {
+        let username = ArcString::new(name.to_string());
+        server.users.entry(username.clone()).or_default();
+        let peer = SocketAddr::from(([127, 0, 0, 1], 0));
+        Session::new(username, peer, String::new(), Vec::new())
+    }
+
+    /// 收到的帧中来自聊天室 `room` 的消息
+    fn room_messages(frames: Vec<Frame>, room: &str) -> Vec<Message> {
+        frames
+            .into_iter()
+            .filter_map(|frame| match frame {
+                Frame::Message(msg) if msg.to() == room => Some(msg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn room_hints_follow_each_member() {
+        let server = Server::new();
+        let mut alice = login(&server, "alice").await;
+        let mut bob = login(&server, "bob").await;
+        let mut carol = login(&server, "carol").await;
+        for client in [&mut alice, &mut bob, &mut carol] {
+            send(client, "/join", "#ops").await;
+        }
+        send(&mut bob, "/notify", "mute #OPS").await;
+
+        send(&mut alice, "#ops", "deploying now @carol").await;
+        let to_bob = room_messages(send(&mut bob, "/list", "").await, "#ops");
+        let to_carol = room_messages(send(&mut carol, "/list", "").await, "#ops");
+        assert_eq!(to_bob.len(), 1);
+        assert_eq!(to_bob[0].notify(), Some(NotifyHint::Silent));
+        assert_eq!(to_carol.len(), 1);
+        assert_eq!(to_carol[0].notify(), Some(NotifyHint::Highlight));
+
+        send(&mut bob, "/notify", "unmute #ops").await;
+        send(&mut alice, "#ops", "done").await;
+        let to_bob = room_messages(send(&mut bob, "/list", "").await, "#ops");
+        assert_eq!(to_bob[0].notify(), None);
+    }
+
+    #[tokio::test]
+    async fn room_messages_from_reported_senders_are_quarantined() {
+        let server = Server::with_config(ServerConfig {
+            quarantine: true,
+            ..ServerConfig::default()
+        });
+        let mut alice = login(&server, "alice").await;
+        let mut bob = login(&server, "bob").await;
+        send(&mut alice, "/join", "#ops").await;
+        send(&mut bob, "/join", "#ops").await;
+        send(&mut bob, "/report", "alice spam").await;
+
+        send(&mut alice, "#ops", "buy now").await;
+        let held = server.quarantined_messages();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].message.to(), "#ops");
+        assert!(contents(&send(&mut bob, "/list", "").await)
+            .iter()
+            .all(|content| content != "buy now"));
+
+        let release = format!("release {}", held[0].id);
+        server.quarantine_command(None, &release, Lang::En).await;
+        assert!(contents(&send(&mut bob, "/list", "").await).contains(&"buy now".to_string()));
+    }
+
+    #[test]
+    fn parses_room_addresses() {
+        assert_eq!(parse_room("#Ops"), Some("#ops".to_string()));
+        assert_eq!(parse_room("#dev-ops_2"), Some("#dev-ops_2".to_string()));
+        assert_eq!(parse_room("#Ünïcode"), Some("#ünïcode".to_string()));
+        assert!(parse_room(&format!("#{}", "a".repeat(MAX_ROOM_NAME))).is_some());
+        for invalid in ["#", "ops", "#a b", "#a/b", "##ops", " #ops"] {
+            assert_eq!(parse_room(invalid), None, "{:?}", invalid);
+        }
+        assert_eq!(
+            parse_room(&format!("#{}", "a".repeat(MAX_ROOM_NAME + 1))),
+            None
+        );
+    }
+
+    #[test]
+    fn marks_rooms_seen_at_the_latest_message() {
+        let server = Server::new();
+        server.last_message_id.store(42, Ordering::Relaxed);
+        let mut user = UserRecord::default();
+        user.rooms.insert("#a".to_string(), 0);
+        user.rooms.insert("#b".to_string(), 7);
+        server.mark_rooms_seen(&mut user);
+        assert!(user.rooms.values().all(|seen| *seen == 42));
+    }
+
+    #[test]
+    fn private_rooms_refuse_direct_joins() {
+        let server = Server::new();
+        let alice = session(&server, "alice");
+        let bob = session(&server, "bob");
+        server.join_command(&alice, "#secret");
+        server.room_command(&alice, "#secret private on");
+
+        let refused = tr!(bob.lang => "cmd.room_private", room = "#secret");
+        assert_eq!(server.join_command(&bob, "#Secret"), refused);
+        assert_eq!(server.room_command(&bob, "#secret"), refused);
+        assert!(!server.is_room_member(&bob.username, "#secret"));
+    }
+
+    #[test]
+    fn room_invites_run_out_and_expire() {
+        let server = Server::new();
+        let alice = session(&server, "alice");
+        server.join_command(&alice, "#secret");
+        server.room_command(&alice, "#secret private on");
+
+        let invite = server
+            .room_invites
+            .create("alice", Some("#secret"), 1, None)
+            .unwrap();
+        let bob = session(&server, "bob");
+        assert_eq!(
+            server.join_command(&bob, &invite.code),
+            tr!(bob.lang => "cmd.room_joined", room = "#secret", count = 2)
+        );
+        let carol = session(&server, "carol");
+        assert_eq!(
+            server.join_command(&carol, &invite.code),
+            InviteError::Unknown.describe(carol.lang)
+        );
+
+        let expired = server
+            .room_invites
+            .create("alice", Some("#secret"), 5, Some(Duration::ZERO))
+            .unwrap();
+        assert_eq!(
+            server.join_command(&carol, &expired.code),
+            InviteError::Expired.describe(carol.lang)
+        );
+        assert!(!server.is_room_member(&carol.username, "#secret"));
+    }
+
+    #[test]
+    fn rejects_zero_retention() {
+        let server = Server::new();
+        let alice = session(&server, "alice");
+        server.join_command(&alice, "#ops");
+        assert_eq!(
+            server.room_command(&alice, "#ops retention 0"),
+            tr!(alice.lang => "cmd.room_retention_invalid")
+        );
+        assert_eq!(server.room_retention("#ops"), DEFAULT_ROOM_RETENTION);
+        server.room_command(&alice, "#ops retention 3");
+        assert_eq!(server.room_retention("#ops"), 3);
+        server.rooms.get_mut("#ops").unwrap().retention = Some(0);
+        assert_eq!(server.room_retention("#ops"), DEFAULT_ROOM_RETENTION);
+    }
+
+    #[tokio::test]
+    async fn rejoin_replays_recent_messages_and_mentions() {
+        let server = Server::new();
+        let mut alice = login(&server, "alice").await;
+        let mut bob = login(&server, "bob").await;
+        send(&mut alice, "/join", "#ops").await;
+        send(&mut alice, "/room", "#ops retention 2").await;
+        send(&mut bob, "/join", "#ops").await;
+        drop(bob);
+        while server.online_users().iter().any(|user| user.name == "bob") {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        for content in ["@bob look", "m2", "m3", "m4", "m5"] {
+            send(&mut alice, "#ops", content).await;
+        }
+        let hello = Hello::new("bob".to_string(), Vec::new());
+        let mut bob = connect(&server, hello).await;
+        let frames = send(&mut bob, "/rooms", "").await;
+        let entry = tr!(crate::i18n::lang() => "server.rooms_rejoined_entry",
+            room = "#ops",
+            count = 5,
+            mentions = 1,
+        );
+        assert!(contents(&frames).iter().any(|text| text.contains(&entry)));
+        let replayed: Vec<String> = room_messages(frames, "#ops")
+            .iter()
+            .map(|msg| msg.content().to_string())
+            .collect();
+        assert_eq!(replayed, ["@bob look", "m4", "m5"]);
+    }
+}
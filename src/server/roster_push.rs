@@ -0,0 +1,111 @@
+/*!
+# 在线名单推送
+
+声明了 `roster` 特性的连接不必反复发送 `/list`：写循环开始时先发送当前在线用户的完整列表
+（[`Control::Roster`]），此后把用户上线、下线与更改状态（`/away`、`/dnd`、`/privacy`）或表情头像
+（`/profile avatar`）的增量更新（[`Control::RosterUpdate`]）推送给客户端。
+
+更新经独立的 `broadcast` 通道发布，不占用系统通知通道的容量；订阅在加入在线用户表之前完成，
+完整列表在写循环开始时才生成，二者之间发布的更新会在完整列表之后重复一次，由于更新可以
+重复应用，客户端的名单仍然正确。写循环落后、丢失更新时重新订阅并再发送一次完整列表。
+
+隐藏了在线状态的用户（`/privacy status off`）总是以 `online` 出现在名单中。
+*/
+
+use super::Server;
+use crate::protocol::{Control, RosterChange, RosterEntry, RosterStatus};
+use crate::session::Presence;
+use crate::ArcString;
+use tokio::sync::broadcast;
+
+/// 在线名单更新通道的容量，写循环落后超过该数量时改为重新发送完整列表
+pub(super) const ROSTER_UPDATE_CAPACITY: usize = 256;
+
+/// 一条在线名单更新
+pub(super) type RosterUpdate = (RosterChange, RosterEntry);
+
+impl Server {
+    /// `name` 在名单中的条目，`presence` 为其当前状态
+    fn roster_entry(&self, name: &ArcString, presence: Option<&Presence>) -> RosterEntry {
+        let (bot, hidden, avatar) = match self.users.get(name) {
+            Some(user) => (
+                user.bot.is_some(),
+                !user.profile.privacy.show_status,
+                user.profile.avatar_emoji().map(str::to_string),
+            ),
+            None => (false, false, None),
+        };
+        let status = match presence {
+            _ if hidden => RosterStatus::Online,
+            Some(presence) if presence.dnd => RosterStatus::Dnd,
+            Some(presence) if presence.away.is_some() => RosterStatus::Away,
+            _ => RosterStatus::Online,
+        };
+        RosterEntry {
+            name: name.get(),
+            status,
+            bot,
+            avatar,
+        }
+    }
+
+    /// 当前在线用户的完整列表（按用户名排序）
+    pub(super) fn roster_list(&self) -> Control {
+        let mut users: Vec<RosterEntry> = self
+            .online_users
+            .iter()
+            .map(|entry| {
+                let presence = entry.value().presence.lock().unwrap();
+                self.roster_entry(entry.key(), Some(&presence))
+            })
+            .collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        Control::Roster { users }
+    }
+
+    /// 向订阅了在线名单的连接推送 `name` 的变化；上线或更改状态时 `name` 已不在线则不推送
+    pub(super) fn push_roster(&self, change: RosterChange, name: &ArcString) {
+        let user = match change {
+            RosterChange::Left => self.roster_entry(name, None),
+            RosterChange::Joined | RosterChange::Status => {
+                let Some(presence) = self
+                    .online_users
+                    .get(name)
+                    .map(|user| user.presence.clone())
+                else {
+                    return;
+                };
+                let presence = presence.lock().unwrap();
+                self.roster_entry(name, Some(&presence))
+            }
+        };
+        // 没有连接订阅时发送失败，无需处理
+        let _ = self.roster_updates.send((change, user));
+    }
+
+    /// 订阅在线名单更新
+    pub(super) fn subscribe_roster(&self) -> broadcast::Receiver<RosterUpdate> {
+        self.roster_updates.subscribe()
+    }
+
+    /// 等待下一条要推送给客户端的在线名单帧；`updates` 为 `None`（未订阅）时永远等待
+    ///
+    /// 落后丢失更新时重新订阅，并以完整列表代替丢失的更新
+    pub(super) async fn next_roster_frame(
+        &self,
+        updates: &mut Option<broadcast::Receiver<RosterUpdate>>,
+    ) -> Control {
+        let Some(receiver) = updates else {
+            return std::future::pending().await;
+        };
+        match receiver.recv().await {
+            Ok((change, user)) => Control::RosterUpdate { change, user },
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                *receiver = self.subscribe_roster();
+                self.roster_list()
+            }
+            // 发送端由 Server 持有，不会先于写循环关闭
+            Err(broadcast::error::RecvError::Closed) => std::future::pending().await,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+/*!
+# 消息路由队列
+
+每个在线用户有一个 [`RouteQueue`]：发给该用户的聊天消息先进入这里，再由该连接的
+转发循环逐条放入容量很小的发送通道。这样做有两个好处：
+
+- 发送方只有在接收方积压超过 [`ROUTE_CAPACITY`] 条时才需要等待，某个接收方的发送通道
+  已满不会拖住同一发送方发给其他用户的消息；
+- 队列内按发送方分组并轮流取出，即使有人向同一接收方连续发送上千条消息，
+  其他发送方的消息也不必排在它们全部之后。
+
+分组依据消息的 `from`：服务器收到客户端的消息时已把它改写为该连接的用户名（经联邦转入的消息
+为对端服务器认证过的 `用户@服务器`），发送方无法靠每条消息换一个 `from` 绕过轮转。
+*/
+
+use crate::Message;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// 每个接收方最多积压的消息数
+pub const ROUTE_CAPACITY: usize = 1000;
+
+/// 消息未能进入路由队列
+#[derive(Debug)]
+pub enum Rejected {
+    /// 队列已满
+    Full(Message),
+    /// 接收方的连接已结束
+    Closed(Message),
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    /// 按轮转顺序排列的发送方及其待转发的消息（不含空队列）
+    senders: VecDeque<(String, VecDeque<Message>)>,
+    len: usize,
+    closed: bool,
+}
+
+/// 单个接收方的路由队列，按发送方轮流出队
+#[derive(Debug)]
+pub struct RouteQueue {
+    capacity: usize,
+    pending: Mutex<Pending>,
+    /// 有新消息入队
+    ready: Notify,
+    /// 有消息出队或队列关闭
+    space: Notify,
+}
+
+impl RouteQueue {
+    /// 创建最多积压 `capacity` 条消息的队列
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            pending: Mutex::new(Pending::default()),
+            ready: Notify::new(),
+            space: Notify::new(),
+        }
+    }
+
+    /// 消息入队，队列已满或已关闭时立即交还
+    pub fn push(&self, msg: Message) -> Result<(), Rejected> {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.closed {
+            return Err(Rejected::Closed(msg));
+        }
+        if pending.len >= self.capacity {
+            return Err(Rejected::Full(msg));
+        }
+        match pending
+            .senders
+            .iter_mut()
+            .find(|(from, _)| from == msg.from())
+        {
+            Some((_, queue)) => queue.push_back(msg),
+            None => {
+                let from = msg.from().to_string();
+                pending.senders.push_back((from, VecDeque::from([msg])));
+            }
+        }
+        pending.len += 1;
+        drop(pending);
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    /// 消息入队，队列已满时最多等待 `timeout`
+    pub async fn push_timeout(&self, mut msg: Message, timeout: Duration) -> Result<(), Rejected> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // 先登记等待再尝试入队，避免错过两者之间的出队通知
+            let space = self.space.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+            match self.push(msg) {
+                Err(Rejected::Full(rejected)) => msg = rejected,
+                result => return result,
+            }
+            if tokio::time::timeout_at(deadline, space).await.is_err() {
+                return Err(Rejected::Full(msg));
+            }
+        }
+    }
+
+    /// 取出下一条消息：每个发送方轮流取一条；队列为空时等待
+    pub async fn pop(&self) -> Message {
+        loop {
+            if let Some(msg) = self.try_pop() {
+                return msg;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    fn try_pop(&self) -> Option<Message> {
+        let mut pending = self.pending.lock().unwrap();
+        let (from, mut queue) = pending.senders.pop_front()?;
+        let msg = queue.pop_front()?;
+        if !queue.is_empty() {
+            pending.senders.push_back((from, queue));
+        }
+        pending.len -= 1;
+        drop(pending);
+        self.space.notify_waiters();
+        Some(msg)
+    }
+
+    /// 是否没有待转发的消息
+    pub fn is_empty(&self) -> bool {
+        self.pending.lock().unwrap().len == 0
+    }
+
+    /// 关闭队列并取出所有未转发的消息，之后的入队都会被拒绝
+    pub fn close(&self) -> Vec<Message> {
+        let mut pending = self.pending.lock().unwrap();
+        pending.closed = true;
+        pending.len = 0;
+        let left = pending
+            .senders
+            .drain(..)
+            .flat_map(|(_, queue)| queue)
+            .collect();
+        drop(pending);
+        self.space.notify_waiters();
+        left
+    }
+}
+
+impl Default for RouteQueue {
+    fn default() -> Self {
+        Self::new(ROUTE_CAPACITY)
+    }
+}
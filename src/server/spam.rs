@@ -0,0 +1,337 @@
+/*!
+# 反垃圾启发式规则
+
+配置了 [`ServerConfig::spam`](super::ServerConfig::spam) 时，服务器在统计窗口内按发送者检查三条规则：
+
+- **重复消息**：同样的内容（忽略首尾空白与大小写）发送超过 [`SpamConfig::repeat`] 次；
+- **接收者过多**：发给超过 [`SpamConfig::recipients`] 个不同的接收者；
+- **链接刷屏**：消息中的链接总数超过 [`SpamConfig::links`]（链接的识别同 [`crate::link`]）。
+
+触发规则后按 [`SpamAction`] 处理：只告警时消息照常投递（配置了 [`ServerConfig::quarantine`](super::ServerConfig::quarantine)
+时隔离待审核，见 `moderation` 子模块），同一规则在一个窗口内只告警一次；
+限流时拒绝该发送者在限流期内的所有消息（`RATE_LIMITED` 错误）。每次告警或开始限流都写入
+审计日志（见 `audit` 子模块），并提醒在线的管理员。服务器指令与机器人账号（有单独的限流）不参与检查。
+*/
+
+use super::audit::AuditAction;
+use super::Server;
+use crate::i18n::{self, Lang};
+use crate::protocol::ErrorCode;
+use crate::session::Session;
+use crate::{humanize_duration_in, link, tr, ArcString, Message, MessageKind};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 记录的发送者超过该数量时清理窗口内没有消息、也未被限流的条目
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// 反垃圾规则的配置；各项为 `None` 表示不检查该规则
+#[derive(Clone, Debug)]
+pub struct SpamConfig {
+    /// 窗口内同样内容的消息最多发送的次数
+    pub repeat: Option<usize>,
+    /// 窗口内最多发给的不同接收者数
+    pub recipients: Option<usize>,
+    /// 窗口内消息中最多包含的链接数
+    pub links: Option<usize>,
+    /// 统计窗口
+    pub window: Duration,
+    /// 触发规则后的处理方式
+    pub action: SpamAction,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            repeat: Some(5),
+            recipients: Some(20),
+            links: Some(10),
+            window: Duration::from_secs(60),
+            action: SpamAction::default(),
+        }
+    }
+}
+
+impl SpamConfig {
+    /// 解析 `repeat=5,recipients=20,links=10,window=60`：未出现的项使用默认值，数量为 0 表示不检查该规则，
+    /// `window` 为秒数；处理方式总是只告警
+    ///
+    /// # 返回值
+    /// 有未知的项、数量无法解析或窗口为 0 时返回 `None`
+    pub fn from_string(spec: &str) -> Option<Self> {
+        let mut config = SpamConfig::default();
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = item.split_once('=')?;
+            let value: u64 = value.trim().parse().ok()?;
+            let limit = (value > 0).then_some(value as usize);
+            match key.trim() {
+                "repeat" => config.repeat = limit,
+                "recipients" => config.recipients = limit,
+                "links" => config.links = limit,
+                "window" if value > 0 => config.window = Duration::from_secs(value),
+                _ => return None,
+            }
+        }
+        Some(config)
+    }
+}
+
+/// 触发反垃圾规则后的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpamAction {
+    /// 只告警（写入审计日志并提醒管理员），消息照常投递
+    #[default]
+    Flag,
+    /// 告警并在给定时长内拒绝该发送者的消息
+    Throttle(Duration),
+}
+
+/// 触发的规则及窗口内的实际数量
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpamRule {
+    /// 同样的内容发送了这么多次
+    Repeated(usize),
+    /// 发给了这么多个不同的接收者
+    Recipients(usize),
+    /// 消息中共有这么多个链接
+    Links(usize),
+}
+
+impl SpamRule {
+    /// 以 `lang` 描述触发的规则
+    pub fn describe(self, lang: Lang) -> String {
+        match self {
+            SpamRule::Repeated(count) => tr!(lang => "spam.repeated", count = count),
+            SpamRule::Recipients(count) => tr!(lang => "spam.recipients", count = count),
+            SpamRule::Links(count) => tr!(lang => "spam.links", count = count),
+        }
+    }
+
+    /// 规则的种类，用于同一规则在一个窗口内只告警一次
+    fn kind(self) -> u8 {
+        match self {
+            SpamRule::Repeated(_) => 0,
+            SpamRule::Recipients(_) => 1,
+            SpamRule::Links(_) => 2,
+        }
+    }
+}
+
+/// 服务器对一条消息按反垃圾规则筛查的结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Screened {
+    /// 未触发规则
+    Pass,
+    /// 触发规则并已告警，消息可以投递（配置了隔离时改为隔离待审核）
+    Flagged(SpamRule),
+    /// 发送者被限流，消息不应投递（已告知发送者）
+    Rejected,
+}
+
+/// 一条消息的检查结果
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Verdict {
+    /// 放行
+    Allow,
+    /// 触发规则，告警后放行
+    Flagged(SpamRule),
+    /// 拒绝：`rule` 为本条消息触发的规则，`None` 表示仍在此前开始的限流期内
+    Throttled {
+        rule: Option<SpamRule>,
+        remaining: Duration,
+    },
+}
+
+/// 窗口内的一条消息
+#[derive(Debug)]
+struct Sent {
+    at: Instant,
+    /// 规范化后内容的哈希，加密消息为 `None`（每条密文都不同，不参与重复检查）
+    digest: Option<u64>,
+    to: String,
+    links: usize,
+}
+
+/// 单个发送者的状态
+#[derive(Debug, Default)]
+struct SenderState {
+    sent: VecDeque<Sent>,
+    /// 各规则最近一次告警的时间
+    flagged: HashMap<u8, Instant>,
+    throttled_until: Option<Instant>,
+}
+
+/// 所有发送者的反垃圾状态
+#[derive(Debug)]
+pub(super) struct SpamGuard {
+    config: SpamConfig,
+    senders: Mutex<HashMap<ArcString, SenderState>>,
+}
+
+impl SpamGuard {
+    pub(super) fn new(config: SpamConfig) -> Self {
+        Self {
+            config,
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录 `sender` 发出的 `msg` 并检查规则
+    pub(super) fn check(&self, sender: &ArcString, msg: &Message) -> Verdict {
+        let now = Instant::now();
+        let window = self.config.window;
+        let mut senders = self.senders.lock().unwrap();
+        if senders.len() > PRUNE_THRESHOLD {
+            senders.retain(|_, state| {
+                state.sent.back().is_some_and(|sent| now - sent.at < window)
+                    || state.throttled_until.is_some_and(|until| until > now)
+            });
+        }
+        let state = senders.entry(sender.clone()).or_default();
+        if let Some(until) = state.throttled_until {
+            if until > now {
+                return Verdict::Throttled {
+                    rule: None,
+                    remaining: until - now,
+                };
+            }
+            state.throttled_until = None;
+        }
+        while state
+            .sent
+            .front()
+            .is_some_and(|sent| now - sent.at >= window)
+        {
+            state.sent.pop_front();
+        }
+        let (digest, links) = match msg.kind() {
+            MessageKind::Encrypted => (None, 0),
+            MessageKind::Text | MessageKind::Action => {
+                (Some(digest(msg.content())), link::find(msg.content()).len())
+            }
+        };
+        state.sent.push_back(Sent {
+            at: now,
+            digest,
+            to: msg.to().to_string(),
+            links,
+        });
+        let Some(rule) = self.violated(state, digest) else {
+            return Verdict::Allow;
+        };
+        match self.config.action {
+            SpamAction::Flag => {
+                let recent = state
+                    .flagged
+                    .get(&rule.kind())
+                    .is_some_and(|at| now - *at < window);
+                if recent {
+                    return Verdict::Allow;
+                }
+                state.flagged.insert(rule.kind(), now);
+                Verdict::Flagged(rule)
+            }
+            SpamAction::Throttle(duration) => {
+                state.throttled_until = Some(now + duration);
+                // 限流结束后重新计数
+                state.sent.clear();
+                Verdict::Throttled {
+                    rule: Some(rule),
+                    remaining: duration,
+                }
+            }
+        }
+    }
+
+    /// 窗口内触发的第一条规则
+    fn violated(&self, state: &SenderState, digest: Option<u64>) -> Option<SpamRule> {
+        if let (Some(limit), Some(digest)) = (self.config.repeat, digest) {
+            let count = state
+                .sent
+                .iter()
+                .filter(|sent| sent.digest == Some(digest))
+                .count();
+            if count > limit {
+                return Some(SpamRule::Repeated(count));
+            }
+        }
+        if let Some(limit) = self.config.recipients {
+            let count = state
+                .sent
+                .iter()
+                .map(|sent| sent.to.as_str())
+                .collect::<HashSet<_>>()
+                .len();
+            if count > limit {
+                return Some(SpamRule::Recipients(count));
+            }
+        }
+        if let Some(limit) = self.config.links {
+            let count: usize = state.sent.iter().map(|sent| sent.links).sum();
+            if count > limit {
+                return Some(SpamRule::Links(count));
+            }
+        }
+        None
+    }
+}
+
+/// 忽略首尾空白与大小写后的内容哈希
+fn digest(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.trim().to_lowercase().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Server {
+    /// 按反垃圾规则检查 `session` 发出的 `msg`：触发规则时写入审计日志并提醒管理员
+    pub(super) async fn screen_spam(&self, session: &Session, msg: &Message) -> Screened {
+        let Some(spam) = &self.spam else {
+            return Screened::Pass;
+        };
+        let username = &session.username;
+        match spam.check(username, msg) {
+            Verdict::Allow => Screened::Pass,
+            Verdict::Flagged(rule) => {
+                self.spam_alert(AuditAction::SpamFlagged, username, rule)
+                    .await;
+                Screened::Flagged(rule)
+            }
+            Verdict::Throttled { rule, remaining } => {
+                if let Some(rule) = rule {
+                    self.spam_alert(AuditAction::SpamThrottled, username, rule)
+                        .await;
+                }
+                let lang = session.lang;
+                let remaining = humanize_duration_in(lang, remaining.max(Duration::from_secs(1)));
+                let text = tr!(lang => "server.spam_throttled", remaining = remaining);
+                let target = ArcString::new(msg.to().to_string());
+                self.reject(
+                    session,
+                    ErrorCode::RateLimited,
+                    Some(&target),
+                    msg.id(),
+                    text,
+                )
+                .await;
+                Screened::Rejected
+            }
+        }
+    }
+
+    /// 把触发规则的事件写入审计日志，并提醒在线的管理员
+    async fn spam_alert(&self, action: AuditAction, user: &ArcString, rule: SpamRule) {
+        self.audit(action, &user.get(), rule.describe(i18n::lang()));
+        self.alert_admins(|lang| {
+            tr!(lang => "server.spam_alert",
+                user = user,
+                action = action.describe(lang),
+                rule = rule.describe(lang),
+            )
+        })
+        .await;
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+# 联邦转发暂存
+
+配置了暂存目录（[`FederationConfig::spool`](super::FederationConfig::spool)）时，
+发往链路暂时中断的对端服务器的消息不再直接失败，而是按对端写入暂存目录中的
+`对端名.jsonl`（每行一个 [`Control::Relay`] 帧），链路恢复后先按原顺序发出暂存的消息，
+再发送新消息。服务器重启后暂存的消息仍会在链路建立时发出。
+
+暂存的消息在全部写给对端后才清除，发送过程中链路再次中断时会在下次连接时重发，
+对端因此可能收到重复的消息。每个对端的暂存文件超过 [`SPOOL_LIMIT`] 字节后不再接受新消息。
+*/
+
+use crate::protocol::Control;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// 每个对端暂存文件的字节数上限
+pub const SPOOL_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// 按对端保存在磁盘上的待转发消息
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// 对端的暂存文件；服务器名中文件名不允许的字符替换为 `_`
+    fn path(&self, peer: &str) -> PathBuf {
+        let name: String = peer
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.jsonl", name))
+    }
+
+    /// 追加一条待发给 `peer` 的帧
+    ///
+    /// # 返回值
+    /// 暂存文件已超过 [`SPOOL_LIMIT`] 时返回 `Ok(false)`，帧未写入
+    pub fn push(&self, peer: &str, control: &Control) -> io::Result<bool> {
+        let path = self.path(peer);
+        if fs::metadata(&path).is_ok_and(|meta| meta.len() >= SPOOL_LIMIT) {
+            return Ok(false);
+        }
+        fs::create_dir_all(&self.dir)?;
+        let mut line = serde_json::to_vec(control)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&line)?;
+        Ok(true)
+    }
+
+    /// 读出暂存的全部帧（按写入顺序），无法解析的行被跳过
+    pub fn load(&self, peer: &str) -> io::Result<Vec<Control>> {
+        let file = match File::open(self.path(peer)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut frames = Vec::new();
+        for line in BufReader::new(file).lines() {
+            if let Ok(control) = serde_json::from_str(&line?) {
+                frames.push(control);
+            }
+        }
+        Ok(frames)
+    }
+
+    /// 暂存的帧数
+    pub fn count(&self, peer: &str) -> usize {
+        File::open(self.path(peer))
+            .map(|file| BufReader::new(file).lines().count())
+            .unwrap_or(0)
+    }
+
+    /// 清除暂存的帧
+    pub fn clear(&self, peer: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(peer)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
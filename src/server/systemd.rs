@@ -0,0 +1,141 @@
+/*!
+# systemd 集成
+
+- **套接字激活**：由 systemd 的 `.socket` 单元启动时，服务器沿用 systemd 传入的监听套接字
+  （[`Listeners::from_systemd`]），不再自行绑定端口；第一个套接字为聊天端口，配置了 WebSocket 时
+  第二个套接字为 WebSocket 端口。服务重启期间套接字仍由 systemd 持有，新连接在队列中等待。
+- **就绪通知**：设置了 `NOTIFY_SOCKET` 时（`Type=notify` 的服务），开始接受连接后通知 `READY=1`，
+  关闭时通知 `STOPPING=1`，并通过 `STATUS=` 报告在线用户数以及是否正在拒绝新连接。
+- **看门狗**：服务配置了 `WatchdogSec=` 时，按其一半的间隔发送 `WATCHDOG=1`，
+  服务器卡死时由 systemd 重启。
+- **停止信号**：除 Ctrl+C 外，Unix 下收到 systemd 停止服务时发送的 `SIGTERM` 同样正常关闭服务器
+  （[`shutdown_signal`]）。
+
+未由 systemd 启动时以上均不生效；通知仅支持 Unix 系统。套接字激活与通知需启用 `systemd` 特性，
+停止信号的处理始终可用。
+*/
+
+use super::{Listeners, Server};
+use crate::tr;
+#[cfg(feature = "systemd")]
+use listenfd::ListenFd;
+use std::io;
+#[cfg(feature = "systemd")]
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+#[cfg(all(unix, feature = "systemd"))]
+use {sd_notify::NotifyState, std::time::Duration};
+
+impl Listeners {
+    /// 取出 systemd 套接字激活传入的监听套接字；未经套接字激活启动时返回 `None`
+    ///
+    /// `websocket` 为真时第二个套接字作为 WebSocket 端口
+    #[cfg(feature = "systemd")]
+    pub fn from_systemd(websocket: bool) -> io::Result<Option<Self>> {
+        let mut fds = ListenFd::from_env();
+        let Some(chat) = take_listener(&mut fds, 0)? else {
+            return Ok(None);
+        };
+        println!(
+            "{}",
+            tr!("server.systemd_listening", addr = chat.local_addr()?)
+        );
+        let websocket = if websocket {
+            let listener = take_listener(&mut fds, 1)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, tr!("server.systemd_no_ws"))
+            })?;
+            println!(
+                "{}",
+                tr!("server.ws_listening", addr = listener.local_addr()?)
+            );
+            Some(listener)
+        } else {
+            None
+        };
+        Ok(Some(Listeners { chat, websocket }))
+    }
+
+    /// 取出 systemd 套接字激活传入的监听套接字；未启用 `systemd` 特性时总是返回 `None`
+    #[cfg(not(feature = "systemd"))]
+    pub fn from_systemd(_websocket: bool) -> io::Result<Option<Self>> {
+        Ok(None)
+    }
+}
+
+/// 等待 Ctrl+C 或（Unix 下）`SIGTERM`，并输出收到的信号
+pub(super) async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("无法监听 SIGTERM");
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.expect("无法监听 Ctrl+C"),
+            _ = terminate.recv() => {
+                println!("{}", tr!("server.sigterm"));
+                return;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.expect("无法监听 Ctrl+C");
+    println!("\n{}", tr!("server.ctrl_c"));
+}
+
+#[cfg(feature = "systemd")]
+fn take_listener(fds: &mut ListenFd, index: usize) -> io::Result<Option<TcpListener>> {
+    match fds.take_tcp_listener(index)? {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            TcpListener::from_std(listener).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+impl Server {
+    /// 通知 systemd 服务器已经就绪，并在配置了看门狗时定期喂狗；未由 systemd 管理时返回 `None`
+    #[cfg(all(unix, feature = "systemd"))]
+    pub(super) fn notify_ready(&self) -> Option<JoinHandle<()>> {
+        let status = self.systemd_status();
+        let _ = sd_notify::notify(false, &[NotifyState::Ready, NotifyState::Status(&status)]);
+        let mut usec = 0;
+        if !sd_notify::watchdog_enabled(false, &mut usec) {
+            return None;
+        }
+        let server = self.clone();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_micros(usec / 2));
+            loop {
+                interval.tick().await;
+                let status = server.systemd_status();
+                let _ = sd_notify::notify(
+                    false,
+                    &[NotifyState::Watchdog, NotifyState::Status(&status)],
+                );
+            }
+        }))
+    }
+
+    /// 通知 systemd 服务器已经就绪；未由 systemd 管理时返回 `None`
+    #[cfg(not(all(unix, feature = "systemd")))]
+    pub(super) fn notify_ready(&self) -> Option<JoinHandle<()>> {
+        None
+    }
+
+    /// 通知 systemd 服务器正在关闭
+    pub(super) fn notify_stopping(&self) {
+        #[cfg(all(unix, feature = "systemd"))]
+        let _ = sd_notify::notify(false, &[NotifyState::Stopping]);
+    }
+
+    /// 报告给 systemd 的状态：在线用户数，正在拒绝新连接时一并说明
+    #[cfg(all(unix, feature = "systemd"))]
+    fn systemd_status(&self) -> String {
+        let users = self.online_users.len();
+        if self.accept_stats().refusing {
+            tr!("server.systemd_status_refusing", users = users)
+        } else {
+            tr!("server.systemd_status", users = users)
+        }
+    }
+}
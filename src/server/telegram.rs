@@ -0,0 +1,470 @@
+/*!
+# Telegram 桥接
+
+配置 [`TelegramConfig`] 后，服务器以 Telegram 机器人的身份把一个 Telegram 群组与本服务器上的
+桥接聊天室双向同步：
+
+- 聊天室由加入桥接的用户组成：`/telegram join` 加入、`/telegram leave` 退出，
+  第一次向 `tg:` 开头的地址发消息时自动加入；成员关系随用户数据持久化；
+- 成员发给任意 `tg:` 地址（例如 [`ROOM`]，或回复某个 Telegram 用户）的消息以
+  `<用户名> 内容` 的形式发到 Telegram 群组，同时转给聊天室的其他成员；
+- Telegram 群组中的消息以 `tg:<Telegram 用户名>`（没有用户名时为名字）为发送者转给所有成员，
+  与 Webhook 消息一样写入消息日志，成员离线时在其重连后补发；
+- 由 [`Server::run`](super::Server::run) 启动的专门任务以长轮询（`getUpdates`）接收群组消息，
+  失败时等待 [`RETRY_DELAY`] 后重试；发往 Telegram 的消息经队列由同一任务按顺序发送，
+  遇到 Bot API 的限流时按其要求等待后重试一次。
+
+Bot API 只提供 HTTPS，需要启用 `tls` 特性；[`TelegramConfig::api`] 可以指向自建的
+Bot API 服务器或 HTTP 代理。机器人须在 BotFather 中关闭隐私模式（或设为群管理员）才能收到
+群组中的所有消息。
+*/
+
+use super::{sanitized, Server};
+use crate::i18n::Lang;
+use crate::link;
+use crate::protocol::ErrorCode;
+use crate::session::Session;
+use crate::{tr, ArcString, Message, MessageKind};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Telegram 一侧的发送者与桥接地址的前缀
+pub const PREFIX: &str = "tg:";
+
+/// 桥接聊天室的地址，成员向它发消息即发到 Telegram 群组
+pub const ROOM: &str = "tg:group";
+
+/// 默认的 Bot API 地址
+pub const DEFAULT_API: &str = "https://api.telegram.org";
+
+/// 长轮询的等待秒数
+const POLL_TIMEOUT: u64 = 30;
+
+/// 一次 Bot API 请求的最长时间（长轮询另加 [`POLL_TIMEOUT`]）
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 长轮询失败后重试前的等待时间
+pub const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// 发往 Telegram 的消息队列的容量，队列已满时拒绝成员的消息
+const QUEUE_CAPACITY: usize = 64;
+
+/// 应答的最大字节数
+const MAX_RESPONSE_LEN: u64 = 4 * 1024 * 1024;
+
+/// Telegram 单条消息的最大字符数
+const MAX_TEXT_CHARS: usize = 4096;
+
+/// Telegram 桥接配置
+#[derive(Clone)]
+pub struct TelegramConfig {
+    /// BotFather 签发的机器人令牌
+    pub token: String,
+    /// 桥接的群组编号（超级群组为 `-100` 开头的负数）
+    pub chat_id: i64,
+    /// Bot API 的地址，默认为 [`DEFAULT_API`]
+    pub api: String,
+}
+
+impl fmt::Debug for TelegramConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TelegramConfig")
+            .field("token", &"***")
+            .field("chat_id", &self.chat_id)
+            .field("api", &self.api)
+            .finish()
+    }
+}
+
+impl TelegramConfig {
+    /// 以默认的 Bot API 地址桥接群组 `chat_id`
+    pub fn new(token: impl Into<String>, chat_id: i64) -> Self {
+        TelegramConfig {
+            token: token.into(),
+            chat_id,
+            api: DEFAULT_API.to_string(),
+        }
+    }
+}
+
+/// Bot API 的应答
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseParameters {
+    /// 被限流时需要等待的秒数
+    #[serde(default)]
+    retry_after: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id: i64,
+    #[serde(default)]
+    message: Option<TgMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgMessage {
+    chat: TgChat,
+    #[serde(default)]
+    from: Option<TgUser>,
+    #[serde(default)]
+    text: Option<String>,
+    /// 图片等媒体消息的说明文字
+    #[serde(default)]
+    caption: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgChat {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TgUser {
+    first_name: String,
+    #[serde(default)]
+    username: Option<String>,
+}
+
+impl TgUser {
+    /// 在本服务器上显示的发送者：`tg:` 加 Telegram 用户名，没有用户名时用名字（空白替换为 `_`）
+    fn sender(&self) -> String {
+        let name = match &self.username {
+            Some(username) => username.clone(),
+            None => self
+                .first_name
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join("_"),
+        };
+        format!("{}{}", PREFIX, name)
+    }
+}
+
+/// 桥接的运行状态
+#[derive(Debug)]
+pub(super) struct TelegramBridge {
+    config: TelegramConfig,
+    /// 发往 Telegram 的消息
+    outgoing: mpsc::Sender<String>,
+    /// 发送队列的接收端，由桥接任务启动时取走
+    queue: Mutex<Option<mpsc::Receiver<String>>>,
+    /// 最近一次长轮询是否成功
+    connected: AtomicBool,
+}
+
+impl TelegramBridge {
+    pub(super) fn new(config: TelegramConfig) -> Self {
+        let (outgoing, queue) = mpsc::channel(QUEUE_CAPACITY);
+        TelegramBridge {
+            config,
+            outgoing,
+            queue: Mutex::new(Some(queue)),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    /// 调用 Bot API 方法 `method`
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+        timeout: Duration,
+    ) -> io::Result<ApiResponse<T>> {
+        tokio::time::timeout(timeout, self.request(method, params))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+
+    /// 以 POST 发送一个 JSON 请求
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> io::Result<ApiResponse<T>> {
+        let api = self.config.api.trim_end_matches('/');
+        if link::split_url(api).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!("server.telegram_bad_api", api = api),
+            ));
+        }
+        let url = format!("{}/bot{}/{}", api, self.config.token, method);
+        let headers = [("Content-Type", "application/json")];
+        let body = params.to_string();
+        let response =
+            link::request("POST", &url, &headers, body.as_bytes(), MAX_RESPONSE_LEN).await?;
+        // 出错时 Bot API 同样返回带 `description` 的 JSON
+        serde_json::from_slice(&response.body).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!("server.telegram_http_status", status = response.status),
+            )
+        })
+    }
+}
+
+impl Server {
+    /// 配置了 Telegram 桥接时启动桥接任务：长轮询群组消息，并按顺序发送队列中的消息
+    pub(super) fn spawn_telegram(&self) -> Option<JoinHandle<()>> {
+        let bridge = self.telegram.clone()?;
+        let queue = bridge.queue.lock().unwrap().take()?;
+        println!(
+            "{}",
+            tr!("server.telegram_started", chat = bridge.config.chat_id)
+        );
+        let server = self.clone();
+        Some(tokio::spawn(async move {
+            tokio::join!(server.poll_telegram(&bridge), send_telegram(&bridge, queue));
+        }))
+    }
+
+    /// 长轮询 Telegram 群组的新消息并转给聊天室成员，直到所在任务被取消
+    async fn poll_telegram(&self, bridge: &TelegramBridge) {
+        let mut offset = 0;
+        loop {
+            let params = serde_json::json!({
+                "offset": offset,
+                "timeout": POLL_TIMEOUT,
+                "allowed_updates": ["message"],
+            });
+            let timeout = REQUEST_TIMEOUT + Duration::from_secs(POLL_TIMEOUT);
+            let updates = match bridge
+                .call::<Vec<Update>>("getUpdates", params, timeout)
+                .await
+            {
+                Ok(ApiResponse {
+                    ok: true,
+                    result: Some(updates),
+                    ..
+                }) => updates,
+                Ok(response) => {
+                    let error = response.description.unwrap_or_default();
+                    self.telegram_failed(bridge, error).await;
+                    continue;
+                }
+                Err(e) => {
+                    self.telegram_failed(bridge, e.to_string()).await;
+                    continue;
+                }
+            };
+            if !bridge.connected.swap(true, Ordering::Relaxed) {
+                println!("{}", tr!("server.telegram_connected"));
+            }
+            for update in updates {
+                offset = offset.max(update.update_id + 1);
+                let Some(message) = update.message else {
+                    continue;
+                };
+                if message.chat.id != bridge.config.chat_id {
+                    continue;
+                }
+                let (Some(from), Some(content)) = (message.from, message.text.or(message.caption))
+                else {
+                    continue;
+                };
+                let sender = ArcString::new(from.sender());
+                self.mirror(&sender, &content, None).await;
+            }
+        }
+    }
+
+    /// 记录长轮询失败，等待 [`RETRY_DELAY`] 后再重试
+    async fn telegram_failed(&self, bridge: &TelegramBridge, error: String) {
+        bridge.connected.store(false, Ordering::Relaxed);
+        eprintln!("{}", tr!("server.telegram_poll_failed", error = error));
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+
+    /// 把桥接聊天室中的一条消息写入聊天室的消息日志，并转给除 `except` 以外的所有成员
+    async fn mirror(&self, from: &ArcString, content: &str, except: Option<&ArcString>) {
+        let msg = self.log_message(sanitized(Message::new(
+            from.clone(),
+            ROOM.to_string(),
+            content.to_string(),
+        )));
+        let members = self
+            .telegram_members()
+            .into_iter()
+            .filter(|member| Some(member) != except)
+            .collect();
+        self.broadcast_room(msg, members).await;
+    }
+
+    /// 加入了桥接聊天室的用户（按用户名排序）
+    fn telegram_members(&self) -> Vec<ArcString> {
+        let mut members: Vec<ArcString> = self
+            .users
+            .iter()
+            .filter(|entry| entry.value().telegram)
+            .map(|entry| entry.key().clone())
+            .collect();
+        members.sort_by_key(|member| member.get());
+        members
+    }
+
+    /// 设置 `username` 是否为桥接聊天室的成员
+    ///
+    /// # 返回值
+    /// 成员关系有变化时返回 `true`
+    fn set_telegram_member(&self, username: &ArcString, member: bool) -> bool {
+        let changed = match self.users.get_mut(username) {
+            Some(mut record) if record.telegram != member => {
+                record.telegram = member;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            self.save_user(username);
+        }
+        changed
+    }
+
+    /// 把发给 `tg:` 地址的消息发到 Telegram 群组，并转给聊天室的其他成员
+    ///
+    /// # 返回值
+    /// 未配置桥接或 `msg` 不是发给 `tg:` 地址时返回 `false`，由调用方按普通消息处理
+    pub(super) async fn route_to_telegram(&self, session: &Session, msg: &Message) -> bool {
+        if !msg.to().starts_with(PREFIX) {
+            return false;
+        }
+        let Some(bridge) = self.telegram.clone() else {
+            return false;
+        };
+        let lang = session.lang;
+        let target = ArcString::new(msg.to().to_string());
+        if msg.kind() == MessageKind::Encrypted {
+            let tip = tr!(lang => "server.telegram_encrypted");
+            self.reject(session, ErrorCode::Blocked, Some(&target), msg.id(), tip)
+                .await;
+            return true;
+        }
+        if self.set_telegram_member(&session.username, true) {
+            let tip = tr!(lang => "cmd.telegram_joined", room = ROOM);
+            self.reply(&session.username, tip).await;
+        }
+        let text = match msg.kind() {
+            MessageKind::Action => format!("* {} {}", session.username, msg.content()),
+            _ => format!("<{}> {}", session.username, msg.content()),
+        };
+        let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+        if bridge.outgoing.try_send(text).is_err() {
+            let tip = tr!(lang => "server.telegram_busy");
+            self.reject(
+                session,
+                ErrorCode::RateLimited,
+                Some(&target),
+                msg.id(),
+                tip,
+            )
+            .await;
+            return true;
+        }
+        let content = match msg.kind() {
+            MessageKind::Action => format!("* {} {}", session.username, msg.content()),
+            _ => msg.content().to_string(),
+        };
+        self.mirror(&session.username, &content, Some(&session.username))
+            .await;
+        true
+    }
+
+    /// `/telegram [join|leave]`：查看桥接状态，加入或退出桥接聊天室
+    pub(super) fn telegram_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let Some(bridge) = &self.telegram else {
+            return tr!(lang => "cmd.telegram_disabled");
+        };
+        match args {
+            "" => self.telegram_status(bridge, lang),
+            "join" => {
+                if self.set_telegram_member(&session.username, true) {
+                    tr!(lang => "cmd.telegram_joined", room = ROOM)
+                } else {
+                    tr!(lang => "cmd.telegram_already_joined")
+                }
+            }
+            "leave" => {
+                if self.set_telegram_member(&session.username, false) {
+                    tr!(lang => "cmd.telegram_left")
+                } else {
+                    tr!(lang => "cmd.telegram_not_joined")
+                }
+            }
+            _ => tr!(lang => "cmd.usage_telegram"),
+        }
+    }
+
+    /// 桥接的群组、长轮询状态与聊天室成员
+    fn telegram_status(&self, bridge: &TelegramBridge, lang: Lang) -> String {
+        let state = if bridge.connected.load(Ordering::Relaxed) {
+            tr!(lang => "cmd.telegram_connected")
+        } else {
+            tr!(lang => "cmd.telegram_disconnected")
+        };
+        let members: Vec<String> = self
+            .telegram_members()
+            .iter()
+            .map(|member| member.get())
+            .collect();
+        let mut reply = tr!(lang => "cmd.telegram_status",
+            chat = bridge.config.chat_id,
+            state = state,
+            room = ROOM,
+        );
+        reply.push('\n');
+        if members.is_empty() {
+            reply.push_str(&tr!(lang => "cmd.telegram_no_members"));
+        } else {
+            reply.push_str(&tr!(lang => "cmd.telegram_members",
+                count = members.len(),
+                members = members.join(", "),
+            ));
+        }
+        reply
+    }
+}
+
+/// 按顺序把队列中的消息发到 Telegram 群组，直到所在任务被取消
+async fn send_telegram(bridge: &TelegramBridge, mut queue: mpsc::Receiver<String>) {
+    while let Some(text) = queue.recv().await {
+        let params = serde_json::json!({ "chat_id": bridge.config.chat_id, "text": text });
+        for attempt in 0..2 {
+            let error = match bridge
+                .call::<serde_json::Value>("sendMessage", params.clone(), REQUEST_TIMEOUT)
+                .await
+            {
+                Ok(response) if response.ok => break,
+                Ok(response) => {
+                    // 被限流时按 Bot API 的要求等待后重试一次
+                    let retry_after = response.parameters.and_then(|p| p.retry_after);
+                    if let (Some(secs), 0) = (retry_after, attempt) {
+                        tokio::time::sleep(Duration::from_secs(secs)).await;
+                        continue;
+                    }
+                    response.description.unwrap_or_default()
+                }
+                Err(e) => e.to_string(),
+            };
+            eprintln!("{}", tr!("server.telegram_send_failed", error = error));
+            break;
+        }
+    }
+}
@@ -0,0 +1,338 @@
+/*!
+# 路由追踪
+
+调试投递延迟的手段。开启 [`ServerConfig::trace`](super::ServerConfig::trace) 后，服务器为每条带编号的消息
+记录路由事件（[`TraceEvent`]）：何时从发送者收到、何时进入接收者的路由队列或暂存、何时写入接收者的连接；
+发送者通过 `/trace <编号>` 查看，不带编号时列出自己最近被追踪的消息。本地消息的编号即 `/receipts` 中的编号，
+发往其他服务器的消息沿用客户端发出时的编号。
+
+在联邦中，开启追踪的服务器转发的消息带上已有的路由记录（见 [`Message::trace`]），沿途的服务器追加
+各自的转发记录；收件服务器无论自己是否开启追踪都继续记录该消息，并把沿途与自己的记录经联邦链路
+以 [`Control::TraceReport`] 报告回发送者所在的服务器，投递给本地用户前去掉消息上的记录。
+`/trace` 显示各事件相对第一个事件的延迟：本服务器记录的事件之间按单调时钟计算，不受系统时间调整的影响；
+与其他服务器报告的事件之间只能按各自的挂钟时间计算，跨服务器比较时注意时钟偏差。
+
+记录只保存在内存中，最多 [`TRACES_KEPT`] 条，超出时丢弃最早开始追踪的消息。
+*/
+
+use super::Server;
+use crate::i18n::Lang;
+use crate::protocol::{Control, Recipient, TraceEvent, TraceHop};
+use crate::session::Session;
+use crate::{tr, Message, Timestamp};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 最多保存路由记录的消息条数
+pub const TRACES_KEPT: usize = 10_000;
+
+/// 不带编号的 `/trace` 列出的条数
+const TRACES_LISTED: usize = 10;
+
+/// 未加入联邦时路由记录中的服务器名
+const LOCAL_NODE: &str = "local";
+
+impl TraceEvent {
+    /// 以 `lang` 描述路由事件
+    pub fn describe(self, lang: Lang) -> String {
+        match self {
+            TraceEvent::Received => tr!(lang => "trace.received"),
+            TraceEvent::Queued => tr!(lang => "trace.queued"),
+            TraceEvent::Routed => tr!(lang => "trace.routed"),
+            TraceEvent::Relayed => tr!(lang => "trace.relayed"),
+            TraceEvent::Spooled => tr!(lang => "trace.spooled"),
+            TraceEvent::Delivered => tr!(lang => "trace.delivered"),
+        }
+    }
+}
+
+/// 收件服务器记录的、由其他服务器上的用户发来的消息在发送者所在服务器上的标识
+#[derive(Clone, Debug)]
+struct Origin {
+    /// 发送者所在的服务器
+    server: String,
+    /// 发送者在其服务器上的用户名
+    sender: String,
+    /// 消息离开发送者所在服务器时的编号
+    id: u64,
+}
+
+/// 一个路由事件，本服务器记录的事件带有单调时钟时刻
+#[derive(Clone, Debug)]
+struct Hop {
+    hop: TraceHop,
+    mono: Option<Instant>,
+}
+
+impl Hop {
+    /// 其他服务器报告的事件
+    fn remote(hop: TraceHop) -> Hop {
+        Hop { hop, mono: None }
+    }
+
+    /// 相对 `first` 的延迟（毫秒）：两者都由本服务器记录时按单调时钟，否则按挂钟时间
+    fn elapsed_ms(&self, first: &Hop) -> i64 {
+        match (first.mono, self.mono) {
+            (Some(first), Some(this)) => this.saturating_duration_since(first).as_millis() as i64,
+            _ => (self.hop.at - first.hop.at).num_milliseconds(),
+        }
+    }
+}
+
+/// 一条消息的路由记录
+#[derive(Debug)]
+struct Trace {
+    /// 发送者（其他服务器上的用户为 `用户@服务器`）
+    sender: String,
+    /// 接收者
+    recipient: String,
+    /// 按记录顺序的路由事件
+    hops: Vec<Hop>,
+    /// 由其他服务器转发来的消息需要报告回去的位置
+    origin: Option<Origin>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// 以消息编号为键的路由记录
+    traces: HashMap<u64, Trace>,
+    /// 按开始追踪的顺序排列的消息编号
+    order: VecDeque<u64>,
+}
+
+/// 所有被追踪消息的路由记录
+#[derive(Debug, Default)]
+pub(super) struct Tracer {
+    inner: Mutex<Inner>,
+}
+
+impl Tracer {
+    /// 开始追踪编号为 `id` 的消息，`hops` 是已有的路由记录
+    fn start(&self, id: u64, msg: &Message, hops: Vec<Hop>, origin: Option<Origin>) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.traces.len() >= TRACES_KEPT {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.traces.remove(&oldest);
+            }
+        }
+        let trace = Trace {
+            sender: msg.from().to_string(),
+            recipient: msg.to().to_string(),
+            hops,
+            origin,
+        };
+        if inner.traces.insert(id, trace).is_none() {
+            inner.order.push_back(id);
+        }
+    }
+
+    /// 为编号为 `id`、由 `sender` 发出的消息追加路由事件，返回需要报告的位置；未被追踪时忽略
+    fn hop(&self, sender: &str, id: u64, hop: Hop) -> Option<Origin> {
+        let mut inner = self.inner.lock().unwrap();
+        let trace = inner.traces.get_mut(&id).filter(|t| t.sender == sender)?;
+        trace.hops.push(hop);
+        trace.origin.clone()
+    }
+
+    /// `sender` 发出的编号为 `id` 的消息的路由记录，按发生时间排序
+    fn get(&self, sender: &str, id: u64) -> Option<(String, Vec<Hop>)> {
+        let inner = self.inner.lock().unwrap();
+        let trace = inner.traces.get(&id).filter(|t| t.sender == sender)?;
+        let mut hops = trace.hops.clone();
+        hops.sort_by_key(|hop| hop.hop.at);
+        Some((trace.recipient.clone(), hops))
+    }
+
+    /// `sender` 最近被追踪的 `count` 条消息：(编号, 接收者, 最早的事件, 最近的事件)
+    fn recent(&self, sender: &str, count: usize) -> Vec<(u64, String, TraceHop, TraceHop)> {
+        let inner = self.inner.lock().unwrap();
+        let mut recent: Vec<_> = inner
+            .order
+            .iter()
+            .rev()
+            .filter_map(|id| {
+                let trace = inner.traces.get(id).filter(|t| t.sender == sender)?;
+                let first = trace.hops.iter().min_by_key(|hop| hop.hop.at)?;
+                let last = trace.hops.iter().max_by_key(|hop| hop.hop.at)?;
+                Some((
+                    *id,
+                    trace.recipient.clone(),
+                    first.hop.clone(),
+                    last.hop.clone(),
+                ))
+            })
+            .take(count)
+            .collect();
+        recent.reverse();
+        recent
+    }
+}
+
+impl Server {
+    /// 路由记录中本服务器的名字
+    fn node_name(&self) -> String {
+        match &self.federation {
+            Some(federation) => federation.config.name.clone(),
+            None => LOCAL_NODE.to_string(),
+        }
+    }
+
+    /// 本服务器在 `at` 发生的路由事件
+    fn trace_hop_at(&self, event: TraceEvent, at: Timestamp) -> Hop {
+        let hop = TraceHop {
+            node: self.node_name(),
+            event,
+            at: at.wall(),
+        };
+        Hop {
+            hop,
+            mono: Some(at.instant()),
+        }
+    }
+
+    /// 开启追踪时，开始追踪本服务器用户发出的、已分配编号的消息，`received_at` 是收到的时间
+    pub(super) fn trace_received(&self, msg: &Message, received_at: Timestamp) {
+        let Some(id) = msg.id().filter(|_| self.config.trace) else {
+            return;
+        };
+        let hop = self.trace_hop_at(TraceEvent::Received, received_at);
+        self.tracer.start(id, msg, vec![hop], None);
+    }
+
+    /// 为 `sender` 发出的编号为 `id` 的被追踪消息记录本服务器刚刚发生的路由事件；
+    /// 由其他服务器转发来的消息同时报告回去
+    pub(super) fn trace_event(&self, sender: &str, id: Option<u64>, event: TraceEvent) {
+        let Some(id) = id else {
+            return;
+        };
+        let hop = self.trace_hop_at(event, Timestamp::now());
+        if let Some(origin) = self.tracer.hop(sender, id, hop.clone()) {
+            self.trace_report(&origin, vec![hop.hop]);
+        }
+    }
+
+    /// 开启追踪时，为即将经联邦转发的消息开始追踪，返回带上路由记录的消息；
+    /// `received_at` 是收到的时间
+    pub(super) fn trace_outgoing(&self, msg: Message, received_at: Timestamp) -> Message {
+        let Some(id) = msg.id().filter(|_| self.config.trace) else {
+            return msg;
+        };
+        let hop = self.trace_hop_at(TraceEvent::Received, received_at);
+        let carried = vec![hop.hop.clone()];
+        self.tracer.start(id, &msg, vec![hop], None);
+        msg.with_trace(carried)
+    }
+
+    /// 转发经过本服务器的被追踪消息：在消息的路由记录上追加本服务器的转发事件
+    pub(super) fn trace_forwarded(&self, msg: Message) -> Message {
+        if msg.trace().is_empty() {
+            return msg;
+        }
+        let mut hops = msg.trace().to_vec();
+        hops.push(self.trace_hop_at(TraceEvent::Relayed, Timestamp::now()).hop);
+        msg.with_trace(hops)
+    }
+
+    /// 收件服务器收到被追踪的消息：`origin_id` 是消息在发送者所在服务器上的编号，
+    /// `msg` 已分配本服务器的编号；开始追踪并把沿途与本服务器收到的记录报告回去
+    pub(super) fn trace_arrived(&self, origin_id: Option<u64>, hops: Vec<TraceHop>, msg: &Message) {
+        let (Some(origin_id), Some(id)) = (origin_id, msg.id()) else {
+            return;
+        };
+        let Recipient::Remote { user, host } = Recipient::parse(msg.from()) else {
+            return;
+        };
+        let origin = Origin {
+            server: host.to_string(),
+            sender: user.to_string(),
+            id: origin_id,
+        };
+        let mut hops: Vec<Hop> = hops
+            .into_iter()
+            .filter(|hop| !hop.node.eq_ignore_ascii_case(&origin.server))
+            .map(Hop::remote)
+            .collect();
+        hops.push(self.trace_hop_at(TraceEvent::Received, Timestamp::now()));
+        let report = hops.iter().map(|hop| hop.hop.clone()).collect();
+        self.tracer.start(id, msg, hops, Some(origin.clone()));
+        self.trace_report(&origin, report);
+    }
+
+    /// 经联邦链路把路由记录报告给发送者所在的服务器，链路不通时放弃
+    fn trace_report(&self, origin: &Origin, hops: Vec<TraceHop>) {
+        let Some(federation) = &self.federation else {
+            return;
+        };
+        let report = Control::TraceReport {
+            sender: origin.sender.clone(),
+            id: origin.id,
+            hops,
+        };
+        let _ = federation.send(&origin.server, report);
+    }
+
+    /// 对端报告了本服务器用户 `sender` 发出的编号为 `id` 的消息在其他服务器上的路由记录
+    pub(super) fn accept_trace_report(&self, sender: &str, id: u64, hops: Vec<TraceHop>) {
+        for hop in hops {
+            self.tracer.hop(sender, id, Hop::remote(hop));
+        }
+    }
+
+    /// `/trace [编号]`：查看自己发出的消息的路由记录，不带编号时列出最近被追踪的消息
+    pub(super) fn trace_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let sender = session.username.get();
+        let args = args.trim();
+        if args.is_empty() {
+            let recent = self.tracer.recent(&sender, TRACES_LISTED);
+            if recent.is_empty() {
+                return match self.config.trace {
+                    true => tr!(lang => "cmd.traces_empty"),
+                    false => tr!(lang => "cmd.trace_disabled"),
+                };
+            }
+            let lines: Vec<String> = recent
+                .iter()
+                .map(|(id, recipient, first, last)| {
+                    tr!(lang => "cmd.trace_item",
+                        id = id,
+                        to = recipient,
+                        time = first.at.format("%H:%M:%S"),
+                        event = last.event.describe(lang),
+                        node = last.node,
+                    )
+                })
+                .collect();
+            return format!(
+                "{}\n  › {}",
+                tr!(lang => "cmd.traces", count = recent.len()),
+                lines.join("\n  › ")
+            );
+        }
+        let Ok(id) = args.parse::<u64>() else {
+            return tr!(lang => "cmd.usage_trace");
+        };
+        let Some((recipient, hops)) = self.tracer.get(&sender, id) else {
+            return tr!(lang => "cmd.trace_unknown", id = id);
+        };
+        let first = hops.first().cloned();
+        let lines: Vec<String> = hops
+            .iter()
+            .map(|hop| {
+                let elapsed = first.as_ref().map_or(0, |first| hop.elapsed_ms(first));
+                tr!(lang => "cmd.trace_hop",
+                    time = hop.hop.at.format("%H:%M:%S%.3f"),
+                    elapsed = elapsed,
+                    node = hop.hop.node,
+                    event = hop.hop.event.describe(lang),
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.trace", id = id, to = recipient),
+            lines.join("\n  › ")
+        )
+    }
+}
@@ -0,0 +1,408 @@
+/*!
+# 文件传输策略
+
+服务器在撮合文件传输时按 [`ServerConfig::transfer_policy`](super::ServerConfig::transfer_policy)
+检查每个 [`Control::FileOffer`]：
+
+- 文件大小超过 [`TransferPolicy::max_size`]，或扩展名在 [`TransferPolicy::blocked_extensions`] 之中；
+- 挂载的插件（[`Plugin::on_file_offer`](super::Plugin::on_file_offer)）拒绝了该文件。
+
+被拒绝的传输不会转给接收方，发送方收到 `TRANSFER_BLOCKED` 错误帧，其中附带原因，
+事件写入审计日志。
+
+配置了 [`TransferPolicy::scan_command`] 时，文件内容必须先经过服务器的扫描：服务器不再转发
+直连的候选地址（双方只能经服务器中转），续传也从头开始；中转的内容不直接转给接收方，
+而是按顺序写入隔离目录下的暂存文件，收齐后以文件路径为最后一个参数运行扫描命令
+（如 `clamscan --no-summary`）：
+
+- 退出码为 0：把暂存的内容按 [`CHUNK_SIZE`] 转给接收方，随后删除暂存文件；
+- 退出码非 0：文件移入隔离目录（`时间-发送方-文件名`，发送方只保留字母与数字并附上用户名摘要），
+  写入审计日志，发送方收到 `TRANSFER_BLOCKED` 错误，接收方收到附带原因的 [`Control::FileCancel`]；
+- 无法运行或超过 [`SCAN_TIMEOUT`]：按未通过处理，但不保留文件。
+*/
+
+use super::audit::AuditAction;
+use super::Server;
+use crate::i18n::Lang;
+use crate::protocol::{Control, ErrorCode, ErrorFrame};
+use crate::secrets::encode_hex;
+use crate::transfer::CHUNK_SIZE;
+use crate::{humanize_bytes, tr, ArcString};
+use base64ct::{Base64, Encoding};
+use chrono::Local;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::process::Command;
+
+/// 默认的隔离目录
+pub const DEFAULT_QUARANTINE_DIR: &str = "quarantine";
+
+/// 扫描命令的运行时间上限，超过时按未通过处理
+pub const SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 文件传输策略，默认不做任何限制
+#[derive(Clone, Debug)]
+pub struct TransferPolicy {
+    /// 单个文件的大小上限（字节），`None` 表示不限制
+    pub max_size: Option<u64>,
+    /// 不允许传输的扩展名（小写，不含 `.`）
+    pub blocked_extensions: Vec<String>,
+    /// 扫描文件内容的命令，文件路径作为最后一个参数，退出码为 0 表示未发现问题；`None` 表示不扫描
+    pub scan_command: Option<String>,
+    /// 未通过扫描的文件移入的目录，暂存待扫描的内容也写在这里
+    pub quarantine_dir: PathBuf,
+}
+
+impl Default for TransferPolicy {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            blocked_extensions: Vec::new(),
+            scan_command: None,
+            quarantine_dir: PathBuf::from(DEFAULT_QUARANTINE_DIR),
+        }
+    }
+}
+
+impl TransferPolicy {
+    /// 解析逗号分隔的扩展名列表，如 `exe,.bat, SCR`（忽略开头的 `.` 与大小写）
+    pub fn parse_extensions(list: &str) -> Vec<String> {
+        list.split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect()
+    }
+
+    /// 解析文件大小，可以带 `K`、`M`、`G` 后缀（1024 进制），如 `500M`
+    pub fn parse_size(size: &str) -> Option<u64> {
+        let size = size.trim();
+        let (number, unit) = match size.char_indices().last()? {
+            (i, 'k' | 'K') => (&size[..i], 1 << 10),
+            (i, 'm' | 'M') => (&size[..i], 1 << 20),
+            (i, 'g' | 'G') => (&size[..i], 1 << 30),
+            _ => (size, 1),
+        };
+        number.trim().parse::<u64>().ok()?.checked_mul(unit)
+    }
+
+    /// 以 `lang` 说明文件名为 `name`、大小为 `size` 的文件为何不允许传输，允许时为 `None`
+    fn refusal(&self, name: &str, size: u64, lang: Lang) -> Option<String> {
+        if let Some(max) = self.max_size.filter(|max| size > *max) {
+            return Some(tr!(lang => "server.transfer_too_large",
+                name = name,
+                size = humanize_bytes(size),
+                max = humanize_bytes(max),
+            ));
+        }
+        let ext = Path::new(name)
+            .extension()?
+            .to_string_lossy()
+            .to_lowercase();
+        self.blocked_extensions
+            .contains(&ext)
+            .then(|| tr!(lang => "server.transfer_extension", ext = ext))
+    }
+}
+
+/// 用户名在暂存与隔离文件名中的形式：只取字母与数字，避免写到隔离目录之外；
+/// 后缀完整用户名摘要的前几位，过滤后相同的不同用户（如 `a.b` 与 `ab`）不会共用文件
+fn file_label(sender: &ArcString) -> String {
+    let name = sender.get();
+    let kept: String = name.chars().filter(|c| c.is_alphanumeric()).collect();
+    format!(
+        "{}-{}",
+        kept,
+        encode_hex(&Sha256::digest(name.as_bytes())[..4])
+    )
+}
+
+impl Server {
+    /// 是否需要扫描中转的文件内容
+    pub(super) fn scans_transfers(&self) -> bool {
+        self.config.transfer_policy.scan_command.is_some()
+    }
+
+    /// 需要扫描时 `sender` 编号为 `id` 的传输暂存内容的文件
+    pub(super) fn spool_path(&self, sender: &ArcString, id: u64) -> Option<PathBuf> {
+        self.scans_transfers().then(|| {
+            self.config.transfer_policy.quarantine_dir.join(format!(
+                ".{}.{}.spool",
+                file_label(sender),
+                id
+            ))
+        })
+    }
+
+    /// 按传输策略与插件检查 `from` 发给 `to` 的文件，以 `lang` 返回拒绝的原因
+    pub(super) fn transfer_refusal(
+        &self,
+        from: &ArcString,
+        to: &ArcString,
+        name: &str,
+        size: u64,
+        lang: Lang,
+    ) -> Option<String> {
+        self.config
+            .transfer_policy
+            .refusal(name, size, lang)
+            .or_else(|| self.plugin_file_refusal(&from.get(), &to.get(), name, size))
+    }
+
+    /// 记录被拒绝的传输：写入服务器输出与审计日志
+    pub(super) fn log_transfer_blocked(
+        &self,
+        from: &ArcString,
+        to: &ArcString,
+        name: &str,
+        reason: &str,
+    ) {
+        let detail = tr!(
+            "server.transfer_blocked",
+            from = from,
+            to = to,
+            name = name,
+            reason = reason
+        );
+        println!("{}", detail);
+        self.audit(AuditAction::TransferBlocked, &from.get(), detail);
+    }
+
+    /// 把 `sender` 编号为 `id` 的传输中转的一段内容写入暂存文件 `path`，写入失败时取消该传输；
+    /// `complete` 为收齐全部内容时的（接收方，文件名），随即在后台开始扫描
+    pub(super) async fn spool_chunk(
+        &self,
+        sender: &ArcString,
+        id: u64,
+        offset: u64,
+        data: &str,
+        path: PathBuf,
+        complete: Option<(ArcString, String)>,
+    ) {
+        let written = async {
+            let bytes = Base64::decode_vec(data)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            fs::create_dir_all(&self.config.transfer_policy.quarantine_dir).await?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(offset == 0)
+                .open(&path)
+                .await?;
+            file.seek(io::SeekFrom::Start(offset)).await?;
+            file.write_all(&bytes).await?;
+            file.flush().await
+        }
+        .await;
+        let Some((to, name)) = complete else {
+            if let Err(e) = written {
+                self.scan_failed(sender, id, &path, &e).await;
+            }
+            return;
+        };
+        match written {
+            Ok(()) => {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.transfer_scanning",
+                        from = sender,
+                        to = to,
+                        name = name
+                    )
+                );
+                let server = self.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    server.scan_transfer(sender, id, to, name, path).await;
+                });
+            }
+            Err(e) => self.scan_failed(sender, id, &path, &e).await,
+        }
+    }
+
+    /// 扫描暂存的文件，根据结果转给接收方或隔离
+    async fn scan_transfer(
+        &self,
+        sender: ArcString,
+        id: u64,
+        to: ArcString,
+        name: String,
+        path: PathBuf,
+    ) {
+        let command = self
+            .config
+            .transfer_policy
+            .scan_command
+            .clone()
+            .unwrap_or_default();
+        let scanned = tokio::time::timeout(SCAN_TIMEOUT, run_scan(&command, &path)).await;
+        match scanned {
+            Ok(Ok(true)) => {
+                if let Err(e) = self.forward_spool(&sender, id, &to, &path).await {
+                    self.scan_failed(&sender, id, &path, &e).await;
+                }
+                let _ = fs::remove_file(&path).await;
+            }
+            Ok(Ok(false)) => self.quarantine_file(sender, id, to, name, path).await,
+            Ok(Err(e)) => self.scan_failed(&sender, id, &path, &e).await,
+            Err(_) => {
+                let e = io::Error::new(io::ErrorKind::TimedOut, "scan timed out");
+                self.scan_failed(&sender, id, &path, &e).await;
+            }
+        }
+    }
+
+    /// 把通过扫描的暂存文件按 [`CHUNK_SIZE`] 转给接收方；传输已被取消时停止
+    async fn forward_spool(
+        &self,
+        sender: &ArcString,
+        id: u64,
+        to: &ArcString,
+        path: &Path,
+    ) -> io::Result<()> {
+        let mut file = File::open(path).await?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut offset = 0;
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 || !self.transfers.accepted(sender, to, id) {
+                return Ok(());
+            }
+            let chunk = Control::FileChunk {
+                id,
+                peer: sender.get(),
+                offset,
+                data: Base64::encode_string(&buf[..n]),
+            };
+            self.send_control(to, chunk).await;
+            offset += n as u64;
+        }
+    }
+
+    /// 未通过扫描：把文件移入隔离目录，结束传输并通知双方
+    async fn quarantine_file(
+        &self,
+        sender: ArcString,
+        id: u64,
+        to: ArcString,
+        name: String,
+        path: PathBuf,
+    ) {
+        let file_name = Path::new(&name)
+            .file_name()
+            .map_or_else(|| "file".into(), |n| n.to_string_lossy().into_owned());
+        let target = self.config.transfer_policy.quarantine_dir.join(format!(
+            "{}-{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            file_label(&sender),
+            file_name.trim_start_matches('.'),
+        ));
+        let kept = match fs::rename(&path, &target).await {
+            Ok(()) => target.display().to_string(),
+            Err(e) => {
+                let _ = fs::remove_file(&path).await;
+                e.to_string()
+            }
+        };
+        let detail = tr!(
+            "server.transfer_quarantined",
+            from = sender,
+            to = to,
+            name = name,
+            path = kept,
+        );
+        println!("{}", detail);
+        self.audit(AuditAction::TransferQuarantined, &sender.get(), detail);
+        self.block_transfer(&sender, id, true).await;
+    }
+
+    /// 暂存或扫描失败：删除暂存文件，结束传输并通知双方
+    async fn scan_failed(&self, sender: &ArcString, id: u64, path: &Path, error: &io::Error) {
+        eprintln!(
+            "{}",
+            tr!(
+                "server.transfer_scan_failed",
+                path = path.display(),
+                error = error
+            )
+        );
+        let _ = fs::remove_file(path).await;
+        self.block_transfer(sender, id, false).await;
+    }
+
+    /// 以 `TRANSFER_BLOCKED` 结束 `sender` 编号为 `id` 的传输：发送方收到错误帧，
+    /// 接收方收到取消；`infected` 区分未通过扫描与无法扫描
+    async fn block_transfer(&self, sender: &ArcString, id: u64, infected: bool) {
+        let Some((to, name)) = self.transfers.finish_sent(sender, id) else {
+            return;
+        };
+        let reason = |lang| {
+            if infected {
+                tr!(lang => "server.transfer_infected", name = name)
+            } else {
+                tr!(lang => "server.transfer_unscanned", name = name)
+            }
+        };
+        let error = ErrorFrame {
+            code: ErrorCode::TransferBlocked,
+            target: Some(to.get()),
+            id: Some(id),
+            message: reason(self.lang_of(sender)),
+        };
+        self.send_control(sender, Control::Error(error)).await;
+        let cancel = Control::FileCancel {
+            id,
+            peer: sender.get(),
+            reason: Some(reason(self.lang_of(&to))),
+            interrupted: false,
+        };
+        self.send_control(&to, cancel).await;
+    }
+}
+
+/// 运行扫描命令，`path` 作为最后一个参数；退出码为 0 时返回 `true`
+async fn run_scan(command: &str, path: &Path) -> io::Result<bool> {
+    let mut scan = if cfg!(windows) {
+        let mut scan = Command::new("cmd");
+        scan.args(["/C", &format!("{} \"{}\"", command, path.display())]);
+        scan
+    } else {
+        // 路径作为位置参数传入，不经过 shell 的解析
+        let mut scan = Command::new("sh");
+        scan.args(["-c", &format!("{} \"$0\"", command)]).arg(path);
+        scan
+    };
+    let status = scan
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await?;
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_label_stays_inside_the_directory() {
+        let label = file_label(&ArcString::new("../../etc/passwd".to_string()));
+        assert!(label.starts_with("etcpasswd-"));
+        assert!(!label.contains(['/', '.']));
+    }
+
+    #[test]
+    fn file_label_keeps_filtered_names_apart() {
+        let dotted = file_label(&ArcString::new("a.b".to_string()));
+        let plain = file_label(&ArcString::new("ab".to_string()));
+        assert_ne!(dotted, plain);
+        assert!(dotted.starts_with("ab-") && plain.starts_with("ab-"));
+    }
+}
@@ -0,0 +1,503 @@
+/*!
+# 文件传输撮合
+
+客户端之间的文件传输（见 [`crate::transfer`]）由服务器撮合：服务器记录进行中的传输
+（发送方、接收方、文件名与大小），只在传输的双方之间转发文件传输帧，并在转发时把发送方
+替换为 `peer`，使收到的一方知道对方是谁：
+
+- [`Control::FileOffer`]：接收方须在线且声明了 `files` 特性，每个用户同时发起的传输
+  不超过 [`MAX_PENDING`]，否则发送方收到 `TRANSFER_FAILED` 错误；
+- [`Control::FileAnswer`]：只有接收方可以应答，拒绝时传输随即结束；
+- [`Control::FileResume`]：接收方接受并告知已有的字节数，发送方从该处续传（相当于接受的应答）；
+- [`Control::FileCandidates`]：接收方接受后双方各自上报本地监听地址，服务器转给对方时在前面
+  加上服务器观察到的该方公网地址（相当于 STUN 的绑定响应），处于 NAT 之后的双方据此同时
+  向对方发起连接以打通直连；
+- [`Control::FileChunk`]：直连失败时只能由发送方经服务器中转文件内容，超出文件大小的部分被丢弃；
+- [`Control::FileDone`] 与 [`Control::FileCancel`]：接收方校验完成或任一方取消后传输结束。
+
+登记传输前按传输策略（见 `transfer_policy` 子模块）检查文件，被拒绝时发送方收到
+`TRANSFER_BLOCKED` 错误；需要扫描时中转的内容先写入暂存文件，扫描通过后才转给接收方。
+
+任一方断开连接时，其参与的传输全部取消，并以 `interrupted` 的 [`Control::FileCancel`]
+通知仍在线的另一方，双方据此保留续传所需的状态。
+*/
+
+use super::Server;
+use crate::protocol::{Control, ErrorCode, FEATURE_FILES};
+use crate::session::Session;
+use crate::{humanize_bytes, tr, ArcString};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 每个用户同时发起（尚未结束）的传输数上限
+pub const MAX_PENDING: usize = 16;
+
+/// 转发给对方的候选地址数上限（不含服务器观察到的公网地址）
+const MAX_CANDIDATES: usize = 8;
+
+/// 一次进行中的传输
+#[derive(Debug)]
+struct Transfer {
+    to: ArcString,
+    name: String,
+    size: u64,
+    accepted: bool,
+    /// 经服务器中转的字节数
+    relayed: u64,
+    /// 需要扫描时暂存中转内容的文件，`None` 表示直接转给接收方
+    spool: Option<PathBuf>,
+}
+
+/// 中转的一段内容的去向
+#[derive(Debug)]
+pub(super) enum Relay {
+    /// 直接转给接收方
+    Forward,
+    /// 写入暂存文件等待扫描；`complete` 为收齐全部内容时的（接收方，文件名）
+    Spool {
+        path: PathBuf,
+        complete: Option<(ArcString, String)>,
+    },
+}
+
+/// 请求者在传输中的角色
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Sender,
+    Receiver,
+}
+
+/// 进行中的文件传输：键为（发送方，发送方生成的传输编号）
+#[derive(Debug, Default)]
+pub struct Transfers {
+    active: Mutex<HashMap<(ArcString, u64), Transfer>>,
+}
+
+impl Transfers {
+    /// `user` 在与 `peer` 之间编号为 `id` 的传输中的角色及传输的键
+    fn side(
+        active: &HashMap<(ArcString, u64), Transfer>,
+        user: &ArcString,
+        peer: &ArcString,
+        id: u64,
+    ) -> Option<(Side, (ArcString, u64))> {
+        let sent = (user.clone(), id);
+        if active.get(&sent).is_some_and(|t| t.to == *peer) {
+            return Some((Side::Sender, sent));
+        }
+        let received = (peer.clone(), id);
+        if active.get(&received).is_some_and(|t| t.to == *user) {
+            return Some((Side::Receiver, received));
+        }
+        None
+    }
+
+    /// 登记 `from` 发给 `to` 的传输，需要扫描时内容暂存在 `spool`；
+    /// `from` 进行中的传输已达上限或编号重复时返回 `false`
+    fn offer(
+        &self,
+        from: &ArcString,
+        id: u64,
+        to: &ArcString,
+        name: String,
+        size: u64,
+        spool: Option<PathBuf>,
+    ) -> bool {
+        let mut active = self.active.lock().unwrap();
+        let pending = active.keys().filter(|(sender, _)| sender == from).count();
+        if pending >= MAX_PENDING || active.contains_key(&(from.clone(), id)) {
+            return false;
+        }
+        let transfer = Transfer {
+            to: to.clone(),
+            name,
+            size,
+            accepted: false,
+            relayed: 0,
+            spool,
+        };
+        active.insert((from.clone(), id), transfer);
+        true
+    }
+
+    /// 接收方 `user` 应答 `sender` 的传输，返回文件名；不是该传输的接收方时返回 `None`
+    fn answer(
+        &self,
+        user: &ArcString,
+        sender: &ArcString,
+        id: u64,
+        accept: bool,
+    ) -> Option<String> {
+        let mut active = self.active.lock().unwrap();
+        match Self::side(&active, user, sender, id)? {
+            (Side::Receiver, key) if accept => {
+                let transfer = active.get_mut(&key)?;
+                transfer.accepted = true;
+                Some(transfer.name.clone())
+            }
+            (Side::Receiver, key) => active.remove(&key).map(|t| t.name),
+            (Side::Sender, _) => None,
+        }
+    }
+
+    /// `user` 是否参与了与 `peer` 之间编号为 `id`、已被接受的传输
+    pub(super) fn accepted(&self, user: &ArcString, peer: &ArcString, id: u64) -> bool {
+        let active = self.active.lock().unwrap();
+        Self::side(&active, user, peer, id).is_some_and(|(_, key)| active[&key].accepted)
+    }
+
+    /// 发送方 `user` 经服务器中转 `len` 字节，返回内容的去向；不是已接受传输的发送方、
+    /// 超出文件大小或（需要扫描时）不是紧接已暂存部分的内容时返回 `None`
+    fn relay(
+        &self,
+        user: &ArcString,
+        peer: &ArcString,
+        id: u64,
+        offset: u64,
+        len: u64,
+    ) -> Option<Relay> {
+        let mut active = self.active.lock().unwrap();
+        let Some((Side::Sender, key)) = Self::side(&active, user, peer, id) else {
+            return None;
+        };
+        let transfer = active.get_mut(&key).unwrap();
+        if !transfer.accepted || offset.saturating_add(len) > transfer.size {
+            return None;
+        }
+        let Some(path) = transfer.spool.clone() else {
+            transfer.relayed += len;
+            return Some(Relay::Forward);
+        };
+        if offset != transfer.relayed {
+            return None;
+        }
+        transfer.relayed += len;
+        let complete = (transfer.relayed == transfer.size)
+            .then(|| (transfer.to.clone(), transfer.name.clone()));
+        Some(Relay::Spool { path, complete })
+    }
+
+    /// 结束 `user` 参与的传输，返回其角色与传输记录
+    fn finish(&self, user: &ArcString, peer: &ArcString, id: u64) -> Option<(Side, Transfer)> {
+        let mut active = self.active.lock().unwrap();
+        let (side, key) = Self::side(&active, user, peer, id)?;
+        active.remove(&key).map(|transfer| (side, transfer))
+    }
+
+    /// 结束 `sender` 编号为 `id` 的传输（不论是否已被接受），返回（接收方，文件名）
+    pub(super) fn finish_sent(&self, sender: &ArcString, id: u64) -> Option<(ArcString, String)> {
+        let mut active = self.active.lock().unwrap();
+        let transfer = active.remove(&(sender.clone(), id))?;
+        Some((transfer.to, transfer.name))
+    }
+
+    /// 移除 `user` 参与的全部传输（连同暂存文件），返回（传输编号，另一方）
+    fn abandon(&self, user: &ArcString) -> Vec<(u64, ArcString)> {
+        let mut aborted = Vec::new();
+        self.active
+            .lock()
+            .unwrap()
+            .retain(|(sender, id), transfer| {
+                if sender == user {
+                    aborted.push((*id, transfer.to.clone()));
+                } else if transfer.to == *user {
+                    aborted.push((*id, sender.clone()));
+                } else {
+                    return true;
+                }
+                if let Some(spool) = &transfer.spool {
+                    let _ = std::fs::remove_file(spool);
+                }
+                false
+            });
+        aborted
+    }
+}
+
+impl Server {
+    /// 处理客户端发来的文件传输帧；未协商 `files` 特性的连接发来的帧被忽略
+    pub(super) async fn handle_transfer(&self, session: &Session, control: Control) {
+        if !session.has_feature(FEATURE_FILES) {
+            return;
+        }
+        let (user, lang) = (&session.username, session.lang);
+        match control {
+            Control::FileOffer {
+                id,
+                peer,
+                name,
+                size,
+                sha256,
+                resumable,
+            } => {
+                let peer = ArcString::new(peer);
+                if peer == *user {
+                    return;
+                }
+                let supported = self
+                    .online_users
+                    .get(&peer)
+                    .map(|target| target.features.iter().any(|f| f == FEATURE_FILES));
+                if let Some(reason) = self.transfer_refusal(user, &peer, &name, size, lang) {
+                    self.log_transfer_blocked(user, &peer, &name, &reason);
+                    self.reject(
+                        session,
+                        ErrorCode::TransferBlocked,
+                        Some(&peer),
+                        Some(id),
+                        reason,
+                    )
+                    .await;
+                    return;
+                }
+                let spool = self.spool_path(user, id);
+                let refused = match supported {
+                    None => Some(tr!(lang => "transfer.offline", user = peer)),
+                    Some(false) => Some(tr!(lang => "transfer.unsupported", user = peer)),
+                    Some(true)
+                        if !self
+                            .transfers
+                            .offer(user, id, &peer, name.clone(), size, spool) =>
+                    {
+                        Some(tr!(lang => "transfer.too_many", max = MAX_PENDING))
+                    }
+                    Some(true) => None,
+                };
+                if let Some(text) = refused {
+                    self.reject(
+                        session,
+                        ErrorCode::TransferFailed,
+                        Some(&peer),
+                        Some(id),
+                        text,
+                    )
+                    .await;
+                    return;
+                }
+                println!(
+                    "{}",
+                    tr!(
+                        "server.transfer_offered",
+                        from = user,
+                        to = peer,
+                        name = name,
+                        size = humanize_bytes(size),
+                    )
+                );
+                let offer = Control::FileOffer {
+                    id,
+                    peer: user.get(),
+                    name,
+                    size,
+                    sha256,
+                    resumable,
+                };
+                self.send_control(&peer, offer).await;
+            }
+            Control::FileAnswer { id, peer, accept } => {
+                let peer = ArcString::new(peer);
+                let Some(name) = self.transfers.answer(user, &peer, id, accept) else {
+                    return;
+                };
+                if accept {
+                    println!(
+                        "{}",
+                        tr!(
+                            "server.transfer_accepted",
+                            from = peer,
+                            to = user,
+                            name = name
+                        )
+                    );
+                } else {
+                    println!(
+                        "{}",
+                        tr!(
+                            "server.transfer_rejected",
+                            from = peer,
+                            to = user,
+                            name = name
+                        )
+                    );
+                }
+                let answer = Control::FileAnswer {
+                    id,
+                    peer: user.get(),
+                    accept,
+                };
+                self.send_control(&peer, answer).await;
+            }
+            Control::FileResume { id, peer, offset } => {
+                let peer = ArcString::new(peer);
+                let Some(name) = self.transfers.answer(user, &peer, id, true) else {
+                    return;
+                };
+                println!(
+                    "{}",
+                    tr!(
+                        "server.transfer_resumed",
+                        from = peer,
+                        to = user,
+                        name = name,
+                        offset = humanize_bytes(offset),
+                    )
+                );
+                // 需要扫描时暂存文件从头写起，接收方收到从 0 开始的内容后会放弃已有的部分
+                let resume = if self.scans_transfers() {
+                    Control::FileAnswer {
+                        id,
+                        peer: user.get(),
+                        accept: true,
+                    }
+                } else {
+                    Control::FileResume {
+                        id,
+                        peer: user.get(),
+                        offset,
+                    }
+                };
+                self.send_control(&peer, resume).await;
+            }
+            Control::FileCandidates { id, peer, addrs } => {
+                let peer = ArcString::new(peer);
+                // 需要扫描时不转发候选地址，双方打不通直连，只能经服务器中转
+                if self.scans_transfers() || !self.transfers.accepted(user, &peer, id) {
+                    return;
+                }
+                let candidates = Control::FileCandidates {
+                    id,
+                    peer: user.get(),
+                    addrs: with_reflexive(session.peer_addr, addrs),
+                };
+                self.send_control(&peer, candidates).await;
+            }
+            Control::FileChunk {
+                id,
+                peer,
+                offset,
+                data,
+            } => {
+                let peer = ArcString::new(peer);
+                match self
+                    .transfers
+                    .relay(user, &peer, id, offset, decoded_len(&data))
+                {
+                    None => return,
+                    Some(Relay::Forward) => {}
+                    Some(Relay::Spool { path, complete }) => {
+                        self.spool_chunk(user, id, offset, &data, path, complete)
+                            .await;
+                        return;
+                    }
+                }
+                let chunk = Control::FileChunk {
+                    id,
+                    peer: user.get(),
+                    offset,
+                    data,
+                };
+                self.send_control(&peer, chunk).await;
+            }
+            Control::FileDone { id, peer } => {
+                let peer = ArcString::new(peer);
+                let Some((Side::Receiver, transfer)) = self.transfers.finish(user, &peer, id)
+                else {
+                    return;
+                };
+                println!(
+                    "{}",
+                    tr!(
+                        "server.transfer_done",
+                        from = peer,
+                        to = user,
+                        name = transfer.name,
+                        relayed = humanize_bytes(transfer.relayed),
+                    )
+                );
+                let done = Control::FileDone {
+                    id,
+                    peer: user.get(),
+                };
+                self.send_control(&peer, done).await;
+            }
+            Control::FileCancel {
+                id, peer, reason, ..
+            } => {
+                let peer = ArcString::new(peer);
+                let Some((_, transfer)) = self.transfers.finish(user, &peer, id) else {
+                    return;
+                };
+                if let Some(spool) = &transfer.spool {
+                    let _ = tokio::fs::remove_file(spool).await;
+                }
+                println!(
+                    "{}",
+                    tr!(
+                        "server.transfer_cancelled",
+                        user = user,
+                        peer = peer,
+                        name = transfer.name
+                    )
+                );
+                let cancel = Control::FileCancel {
+                    id,
+                    peer: user.get(),
+                    reason,
+                    interrupted: false,
+                };
+                self.send_control(&peer, cancel).await;
+            }
+            _ => {}
+        }
+    }
+
+    /// 中断 `username` 参与的全部传输，并通知仍在线的另一方（之后可以续传）
+    pub(super) async fn abort_transfers(&self, username: &ArcString) {
+        for (id, peer) in self.transfers.abandon(username) {
+            let reason = tr!(self.lang_of(&peer) => "transfer.peer_left", user = username);
+            let cancel = Control::FileCancel {
+                id,
+                peer: username.get(),
+                reason: Some(reason),
+                interrupted: true,
+            };
+            self.send_control(&peer, cancel).await;
+        }
+    }
+
+    /// 向在线用户 `username` 发送一个控制帧
+    pub(super) async fn send_control(&self, username: &ArcString, control: Control) {
+        let tx = self.online_users.get(username).map(|user| user.tx.clone());
+        if let Some(tx) = tx {
+            let _ = tx.send(control.into()).await;
+        }
+    }
+}
+
+/// 在客户端上报的候选地址前加上服务器观察到的公网地址：IP 取 `observed`，
+/// 端口取第一个候选地址的端口（NAT 保留端口时即为对方可以连入的地址）；无效的地址被丢弃
+fn with_reflexive(observed: SocketAddr, addrs: Vec<String>) -> Vec<String> {
+    let addrs: Vec<SocketAddr> = addrs
+        .iter()
+        .filter_map(|addr| addr.parse().ok())
+        .take(MAX_CANDIDATES)
+        .collect();
+    let mut candidates = Vec::with_capacity(addrs.len() + 1);
+    if let Some(first) = addrs.first() {
+        candidates.push(SocketAddr::new(observed.ip().to_canonical(), first.port()));
+    }
+    for addr in addrs {
+        if !candidates.contains(&addr) {
+            candidates.push(addr);
+        }
+    }
+    candidates.iter().map(SocketAddr::to_string).collect()
+}
+
+/// Base64 编码的 `data` 解码后的字节数
+fn decoded_len(data: &str) -> u64 {
+    let padding = data.bytes().rev().take_while(|b| *b == b'=').count();
+    (data.len() / 4 * 3).saturating_sub(padding) as u64
+}
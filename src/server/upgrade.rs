@@ -0,0 +1,336 @@
+/*!
+# 不停机升级
+
+配置了管理套接字（[`ServerConfig::admin_socket`]）时，服务器监听该 Unix 套接字，每个连接接受一条管理命令：
+
+- `status`：回复进程号与在线用户数，以及接受失败与拒绝连接的次数
+- `drain`：回复 `draining` 后停止接受新连接，通知所有在线用户服务器正在升级并断开（客户端随即自动重连），
+  等待连接任务结束、数据写入完毕后删除管理套接字并回复 `drained`，随后进程退出
+- `audit [条数]`：回复最近的审计事件（默认 20 条，见 `audit` 子模块）
+- `reports [dismiss <编号>]`：回复待处理的举报，或把处理完毕的举报移出审核队列（见 `moderation` 子模块）
+- `quarantine [release|discard <编号>]`：回复隔离待审核的消息，或放行、丢弃其中一条
+- `maintenance [on [HH:MM]|off]`：回复维护模式的状态，或开启、关闭维护模式（同时作用于所有虚拟主机，
+  见 `maintenance` 子模块）；重启前先开启，等在线会话自然减少后再 `drain`
+
+配置了管理套接字的服务器以 `SO_REUSEPORT` 绑定监听端口，新版本的进程可以在旧进程运行时绑定同一端口。
+新进程接管旧进程（`--takeover`）时：
+
+1. 先绑定监听端口（[`Listeners::bind`]），此后到达的连接在新进程的队列中等待；
+2. 通过管理套接字向旧进程发送 `drain`（[`take_over`]），等待旧进程断开所有用户并写完数据；
+3. 再载入数据目录、接管管理套接字并开始接受连接（[`Server::run_on`]），重连的客户端进入新进程。
+
+旧进程写完数据后新进程才载入数据目录，两个进程不会同时写同一份数据。
+管理套接字仅支持 Unix 系统。
+*/
+
+use super::{Server, ServerConfig};
+use crate::tr;
+use crate::transport::BoxWriter;
+use socket2::SockRef;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpSocket};
+use tokio::task::JoinHandle;
+#[cfg(unix)]
+use {
+    std::process,
+    tokio::io::{AsyncBufReadExt, BufReader},
+    tokio::net::{UnixListener, UnixStream},
+};
+
+/// 等待旧进程断开所有用户并写完数据的最长时间
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 监听队列的长度：接管期间到达的连接在队列中等待
+const LISTEN_BACKLOG: u32 = 1024;
+
+/// 旧进程排空完毕时回复的一行
+const DRAINED: &str = "drained";
+
+/// 发来 `drain` 命令的管理连接，排空完毕时在其上回复
+pub(super) struct Handoff(BoxWriter);
+
+impl fmt::Debug for Handoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Handoff")
+    }
+}
+
+/// 服务器的监听套接字：聊天端口与可选的 WebSocket 端口
+#[derive(Debug)]
+pub struct Listeners {
+    pub(super) chat: TcpListener,
+    pub(super) websocket: Option<TcpListener>,
+}
+
+impl Listeners {
+    /// 按 `config` 绑定聊天端口 `addr` 与 WebSocket 端口；配置了管理套接字时以 `SO_REUSEPORT` 绑定
+    pub async fn bind(addr: &str, config: &ServerConfig) -> io::Result<Self> {
+        let reuse_port = config.admin_socket.is_some();
+        let chat = bind(addr, reuse_port).await?;
+        println!("{}", tr!("server.listening", addr = chat.local_addr()?));
+        if dual_stack(&chat) {
+            println!("{}", tr!("server.listening_dual_stack"));
+        }
+        #[cfg(feature = "websocket")]
+        let websocket = match &config.websocket {
+            Some(ws_addr) => {
+                let listener = bind(ws_addr, reuse_port).await?;
+                println!("{}", tr!("server.ws_listening", addr = ws_addr));
+                Some(listener)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "websocket"))]
+        let websocket = None;
+        Ok(Listeners { chat, websocket })
+    }
+
+    /// 聊天端口实际绑定的地址（绑定端口 0 时由系统分配端口）
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.chat.local_addr()
+    }
+}
+
+/// `listener` 是否为同时接受 IPv4 与 IPv6 连接的双栈套接字
+fn dual_stack(listener: &TcpListener) -> bool {
+    listener
+        .local_addr()
+        .is_ok_and(|addr| addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+        && SockRef::from(listener).only_v6().is_ok_and(|only| !only)
+}
+
+/// 绑定 TCP 监听端口，`reuse_port` 时允许其他进程同时绑定同一端口
+///
+/// `[::]` 绑定为同时接受 IPv4 与 IPv6 连接的双栈套接字；系统不支持 IPv6 或双栈时
+/// 退回只监听 IPv4 的 `0.0.0.0`。
+pub(super) async fn bind(addr: &str, reuse_port: bool) -> io::Result<TcpListener> {
+    if let Ok(socket_addr) = addr.parse::<SocketAddr>() {
+        if socket_addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+            return match listener(socket_addr, reuse_port) {
+                Err(e) if e.kind() != io::ErrorKind::AddrInUse => {
+                    eprintln!("{}", tr!("server.dual_stack_unavailable", error = e));
+                    listener(
+                        (Ipv4Addr::UNSPECIFIED, socket_addr.port()).into(),
+                        reuse_port,
+                    )
+                }
+                bound => bound,
+            };
+        }
+    }
+    if !reuse_port {
+        return TcpListener::bind(addr).await;
+    }
+    let mut last_error = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        match listener(addr, true) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::ErrorKind::AddrNotAvailable.into()))
+}
+
+/// 绑定 `addr` 上的监听套接字，`[::]` 时关闭 `IPV6_V6ONLY` 以同时接受 IPv4 连接
+fn listener(addr: SocketAddr, reuse_port: bool) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    // 与 `TcpListener::bind` 一致：Windows 上的 SO_REUSEADDR 允许抢占端口，不需要时不设置
+    if reuse_port || !cfg!(windows) {
+        socket.set_reuseaddr(true)?;
+    }
+    #[cfg(unix)]
+    if reuse_port {
+        socket.set_reuseport(true)?;
+    }
+    if addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+        SockRef::from(&socket).set_only_v6(false)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(LISTEN_BACKLOG)
+}
+
+/// 向管理套接字 `path` 发送一条命令，返回服务器回复的各行
+#[cfg(unix)]
+pub async fn admin_request(path: &Path, command: &str) -> io::Result<Vec<String>> {
+    let mut stream = UnixStream::connect(path).await?;
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .await?;
+    let mut lines = BufReader::new(stream).lines();
+    let mut replies = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        replies.push(line);
+    }
+    Ok(replies)
+}
+
+/// 向管理套接字 `path` 发送一条命令，返回服务器回复的各行
+#[cfg(not(unix))]
+pub async fn admin_request(_path: &Path, _command: &str) -> io::Result<Vec<String>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        tr!("server.admin_socket_unsupported"),
+    ))
+}
+
+/// 要求管理套接字 `path` 上的旧进程排空，等待其断开所有用户并写完数据
+///
+/// # 返回值
+/// 旧进程已排空时返回 `true`；没有进程在监听该管理套接字时返回 `false`
+pub async fn take_over(path: &Path) -> io::Result<bool> {
+    match tokio::time::timeout(TAKEOVER_TIMEOUT, admin_request(path, "drain")).await {
+        Ok(Ok(replies)) if replies.last().is_some_and(|line| line == DRAINED) => Ok(true),
+        Ok(Ok(replies)) => Err(io::Error::other(replies.join("; "))),
+        Ok(Err(e))
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            Ok(false)
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(io::ErrorKind::TimedOut.into()),
+    }
+}
+
+impl Server {
+    /// 监听管理套接字；未配置时返回 `None`
+    ///
+    /// 已有进程在监听该套接字时返回错误，残留的套接字文件会被删除
+    #[cfg(unix)]
+    pub(super) async fn spawn_admin_socket(&self) -> io::Result<Option<JoinHandle<()>>> {
+        let Some(path) = &self.config.admin_socket else {
+            return Ok(None);
+        };
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                tr!("server.admin_socket_in_use", path = path.display()),
+            ));
+        }
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        println!(
+            "{}",
+            tr!("server.admin_socket_listening", path = path.display())
+        );
+        let server = self.clone();
+        Ok(Some(tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = server.handle_admin(stream).await {
+                        eprintln!("{}", tr!("server.admin_socket_error", error = e));
+                    }
+                });
+            }
+        })))
+    }
+
+    /// 监听管理套接字；未配置时返回 `None`
+    #[cfg(not(unix))]
+    pub(super) async fn spawn_admin_socket(&self) -> io::Result<Option<JoinHandle<()>>> {
+        if self.config.admin_socket.is_some() {
+            eprintln!("{}", tr!("server.admin_socket_unsupported"));
+        }
+        Ok(None)
+    }
+
+    /// 处理管理套接字上的一条命令
+    #[cfg(unix)]
+    async fn handle_admin(&self, stream: UnixStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut command = String::new();
+        reader.read_line(&mut command).await?;
+        let mut stream = reader.into_inner();
+        match command.trim() {
+            "status" => {
+                let status = tr!(
+                    "server.admin_status",
+                    pid = process::id(),
+                    users = self.online_users.len()
+                );
+                let accepts = self.accept_stats();
+                let refusing = if accepts.refusing {
+                    tr!("server.refusing_now")
+                } else {
+                    String::new()
+                };
+                let accepts = tr!(
+                    "server.admin_accept_stats",
+                    errors = accepts.accept_errors,
+                    refused = accepts.refused,
+                    refusing = refusing
+                );
+                stream
+                    .write_all(format!("{}\n{}\n", status, accepts).as_bytes())
+                    .await
+            }
+            "drain" => {
+                stream.write_all(b"draining\n").await?;
+                println!("{}", tr!("server.draining"));
+                // 排空完毕后由 `run_on` 回复，见 [`Server::finish_admin_socket`]
+                *self.handoff.lock().unwrap() = Some(Handoff(Box::new(stream)));
+                self.shutdown();
+                Ok(())
+            }
+            command if command.split_whitespace().next() == Some("audit") => {
+                let args = command.trim_start_matches("audit");
+                let report = self.audit_command(args, crate::i18n::lang());
+                stream.write_all(format!("{}\n", report).as_bytes()).await
+            }
+            command if command.split_whitespace().next() == Some("reports") => {
+                let args = command.trim_start_matches("reports");
+                let report = self.reports_command(None, args, crate::i18n::lang());
+                stream.write_all(format!("{}\n", report).as_bytes()).await
+            }
+            command if command.split_whitespace().next() == Some("quarantine") => {
+                let args = command.trim_start_matches("quarantine");
+                let report = self
+                    .quarantine_command(None, args, crate::i18n::lang())
+                    .await;
+                stream.write_all(format!("{}\n", report).as_bytes()).await
+            }
+            command if command.split_whitespace().next() == Some("maintenance") => {
+                let args = command.trim_start_matches("maintenance");
+                let report = self.maintenance_all(args, crate::i18n::lang());
+                stream.write_all(format!("{}\n", report).as_bytes()).await
+            }
+            other => {
+                let error = tr!("server.admin_unknown_command", command = other);
+                stream.write_all(format!("{}\n", error).as_bytes()).await
+            }
+        }
+    }
+
+    /// 是否正在为接管排空：已收到 `drain` 命令
+    pub(super) fn draining(&self) -> bool {
+        self.handoff.lock().unwrap().is_some()
+    }
+
+    /// 服务结束时删除管理套接字，并告知发来 `drain` 的新进程排空完毕
+    ///
+    /// 先删除套接字再回复，新进程收到回复后即可绑定同一路径
+    pub(super) async fn finish_admin_socket(&self) {
+        if let Some(path) = &self.config.admin_socket {
+            let _ = std::fs::remove_file(path);
+        }
+        let handoff = self.handoff.lock().unwrap().take();
+        if let Some(Handoff(mut stream)) = handoff {
+            let _ = stream.write_all(format!("{}\n", DRAINED).as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    }
+}
@@ -0,0 +1,472 @@
+/*!
+# 传入 Webhook
+
+服务器通过 `--hooks=地址` 额外监听 HTTP，CI 系统与监控可以向 `POST /hooks/<令牌>` 提交
+JSON（`{"content": "构建失败"}`，也接受 Slack 风格的 `text` 字段），服务器把它转换成一条聊天消息
+发给该 Webhook 配置的用户：
+
+- Webhook 由管理员通过 `/webhook create <名称> <用户>` 创建，令牌只在创建时显示一次，
+  服务器只保存其 SHA-256 摘要（随目标用户的数据持久化，见 [`WebhookRecord`]）；
+- 消息的发送者为 `webhook:<名称>`，写入消息日志，目标用户离线时在其重连后补发；
+- 每个 Webhook 与机器人账号一样按 [`BotRate`](super::BotRate) 单独限流；
+- 内容超过 [`ServerConfig::max_message_len`](super::ServerConfig::max_message_len) 时以 `413` 与
+  `MESSAGE_TOO_LARGE` 拒绝，与客户端发送的过长消息一致；
+- 目前只能以本服务器（默认空间）的用户为目标。
+
+只实现 HTTP/1.1 的最小子集：每个连接处理一个请求，请求体须带 `Content-Length`，
+应答后关闭连接。成功时返回 `200` 与 `{"id": 消息编号}`，失败时返回相应的状态码与
+`{"error": "错误码"}`。
+*/
+
+use super::bots::{issue_token, token_digest};
+use super::{sanitized, Server};
+use crate::i18n::Lang;
+use crate::session::Session;
+use crate::{tr, ArcString, Message};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Webhook 令牌的前缀
+const TOKEN_PREFIX: &str = "hook_";
+
+/// Webhook 的路径前缀
+const HOOKS_PATH: &str = "/hooks/";
+
+/// 请求行与请求头的最大字节数
+const MAX_HEAD_LEN: usize = 8 * 1024;
+
+/// 最多解析的请求头个数
+const MAX_HEADERS: usize = 32;
+
+/// 请求体的最大字节数
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// 读取请求并应答的最长时间，超时后直接关闭连接
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook 名称的最大长度
+const MAX_NAME_LEN: usize = 32;
+
+/// 一个 Webhook，保存在目标用户的 [`UserRecord::webhooks`](crate::storage::UserRecord::webhooks) 中
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebhookRecord {
+    /// 名称，在服务器内唯一，消息的发送者显示为 `webhook:<名称>`
+    pub name: String,
+    /// 令牌的 SHA-256 摘要（十六进制）
+    pub token_sha256: String,
+    /// 创建该 Webhook 的管理员
+    pub created_by: String,
+    pub created_at: DateTime<Local>,
+}
+
+impl WebhookRecord {
+    /// 以该 Webhook 发出的消息的发送者
+    pub fn sender(&self) -> String {
+        format!("webhook:{}", self.name)
+    }
+}
+
+/// 请求体：`content` 与 `text` 任选其一
+#[derive(Debug, Deserialize)]
+struct Payload {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+/// 已读取完整的 HTTP 请求
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// HTTP 应答
+#[derive(Debug)]
+struct Reply {
+    status: u16,
+    reason: &'static str,
+    body: serde_json::Value,
+}
+
+impl Reply {
+    fn ok(id: Option<u64>) -> Self {
+        Reply {
+            status: 200,
+            reason: "OK",
+            body: serde_json::json!({ "id": id }),
+        }
+    }
+
+    fn error(status: u16, reason: &'static str, code: &str) -> Self {
+        Reply {
+            status,
+            reason,
+            body: serde_json::json!({ "error": code }),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason,
+            body.len(),
+            body
+        )
+        .into_bytes()
+    }
+}
+
+/// 读取一个请求；请求不合法时返回应答给对端的错误
+async fn read_request(stream: &mut TcpStream) -> io::Result<Result<Request, Reply>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+    let (head_len, method, path, content_length) = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        match request.parse(&buf) {
+            Ok(httparse::Status::Complete(head_len)) => {
+                let header = |name: &str| {
+                    request
+                        .headers
+                        .iter()
+                        .find(|header| header.name.eq_ignore_ascii_case(name))
+                        .map(|header| String::from_utf8_lossy(header.value).trim().to_string())
+                };
+                if header("Transfer-Encoding").is_some() {
+                    return Ok(Err(Reply::error(411, "Length Required", "LENGTH_REQUIRED")));
+                }
+                let content_length = match header("Content-Length").map(|len| len.parse()) {
+                    None => 0,
+                    Some(Ok(len)) => len,
+                    Some(Err(_)) => {
+                        return Ok(Err(Reply::error(400, "Bad Request", "BAD_REQUEST")))
+                    }
+                };
+                break (
+                    head_len,
+                    request.method.unwrap_or_default().to_string(),
+                    request.path.unwrap_or_default().to_string(),
+                    content_length,
+                );
+            }
+            Ok(httparse::Status::Partial) if buf.len() < MAX_HEAD_LEN => continue,
+            Ok(httparse::Status::Partial) => {
+                return Ok(Err(Reply::error(
+                    431,
+                    "Request Header Fields Too Large",
+                    "HEADERS_TOO_LARGE",
+                )))
+            }
+            Err(_) => return Ok(Err(Reply::error(400, "Bad Request", "BAD_REQUEST"))),
+        }
+    };
+    if content_length > MAX_BODY_LEN {
+        return Ok(Err(Reply::error(
+            413,
+            "Payload Too Large",
+            "PAYLOAD_TOO_LARGE",
+        )));
+    }
+    let mut body = buf.split_off(head_len);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+    Ok(Ok(Request { method, path, body }))
+}
+
+/// Webhook 名称是否合法：1 到 [`MAX_NAME_LEN`] 个字母、数字、`-` 或 `_`
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().count() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Server {
+    /// 在 `listener` 上接受 Webhook 请求，直到所在任务被取消
+    pub(super) async fn serve_webhooks(&self, listener: TcpListener) {
+        loop {
+            let (mut stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        tr!("server.accept_failed", error = format!("{:?}", e))
+                    );
+                    continue;
+                }
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                let handled = async {
+                    let reply = match read_request(&mut stream).await? {
+                        Ok(request) => server.handle_webhook(request, addr).await,
+                        Err(reply) => reply,
+                    };
+                    stream.write_all(&reply.encode()).await?;
+                    stream.shutdown().await
+                };
+                let _ = tokio::time::timeout(REQUEST_TIMEOUT, handled).await;
+            });
+        }
+    }
+
+    /// 处理一个 Webhook 请求：按令牌找到 Webhook，把请求体转换为发给目标用户的消息
+    async fn handle_webhook(&self, request: Request, addr: SocketAddr) -> Reply {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let Some(token) = path.strip_prefix(HOOKS_PATH) else {
+            return Reply::error(404, "Not Found", "NOT_FOUND");
+        };
+        if request.method != "POST" {
+            return Reply::error(405, "Method Not Allowed", "METHOD_NOT_ALLOWED");
+        }
+        let Some((user, hook)) = self.find_webhook(token) else {
+            println!("{}", tr!("server.webhook_refused", addr = addr));
+            return Reply::error(404, "Not Found", "UNKNOWN_HOOK");
+        };
+        let content = serde_json::from_slice::<Payload>(&request.body)
+            .ok()
+            .and_then(|payload| payload.content.or(payload.text))
+            .map(|content| content.trim().to_string())
+            .filter(|content| !content.is_empty());
+        let Some(content) = content else {
+            return Reply::error(400, "Bad Request", "INVALID_PAYLOAD");
+        };
+        let sender = ArcString::new(hook.sender());
+        if let Some(max) = self.config.max_message_len {
+            if content.len() > max {
+                println!(
+                    "{}",
+                    tr!(
+                        "server.content_too_large_log",
+                        user = sender,
+                        len = content.len()
+                    )
+                );
+                return Reply::error(413, "Payload Too Large", "MESSAGE_TOO_LARGE");
+            }
+        }
+        if !self.bot_limits.admit(&sender, &self.config.bot_rate) {
+            return Reply::error(429, "Too Many Requests", "RATE_LIMITED");
+        }
+        println!(
+            "{}",
+            tr!(
+                "server.webhook_delivered",
+                name = hook.name,
+                user = user,
+                addr = addr
+            )
+        );
+        let msg = self.log_message(sanitized(Message::new(sender, user.get(), content)));
+        let id = msg.id();
+        self.deliver_local(&user, msg).await;
+        Reply::ok(id)
+    }
+
+    /// 按令牌查找 Webhook 及其目标用户
+    fn find_webhook(&self, token: &str) -> Option<(ArcString, WebhookRecord)> {
+        let digest = token_digest(token);
+        self.users.iter().find_map(|entry| {
+            let hook = entry
+                .value()
+                .webhooks
+                .iter()
+                .find(|hook| hook.token_sha256 == digest)?;
+            Some((entry.key().clone(), hook.clone()))
+        })
+    }
+
+    /// 名为 `name` 的 Webhook 的目标用户
+    fn webhook_owner(&self, name: &str) -> Option<ArcString> {
+        self.users.iter().find_map(|entry| {
+            entry
+                .value()
+                .webhooks
+                .iter()
+                .any(|hook| hook.name == name)
+                .then(|| entry.key().clone())
+        })
+    }
+
+    /// `/webhook [list|create <名称> <用户>|revoke <名称>]`：管理传入 Webhook
+    pub(super) fn webhook_command(&self, session: &Session, args: &str) -> String {
+        let lang = session.lang;
+        let mut parts = args.split_whitespace();
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (None | Some("list"), None, None, None) => self.list_webhooks(lang),
+            (Some("create"), Some(name), Some(user), None) => {
+                self.create_webhook(session, name, user)
+            }
+            (Some("revoke"), Some(name), None, None) => self.revoke_webhook(name, lang),
+            _ => tr!(lang => "cmd.usage_webhook"),
+        }
+    }
+
+    /// 为用户 `user` 创建名为 `name` 的 Webhook
+    fn create_webhook(&self, session: &Session, name: &str, user: &str) -> String {
+        let lang = session.lang;
+        if !valid_name(name) {
+            return tr!(lang => "cmd.webhook_invalid_name", name = name);
+        }
+        if self.webhook_owner(name).is_some() {
+            return tr!(lang => "cmd.webhook_exists", name = name);
+        }
+        let user = ArcString::new(user.to_string());
+        if !self.users.contains_key(&user) {
+            return tr!(lang => "server.unknown_user", user = user);
+        }
+        let (token, digest) = match issue_token(TOKEN_PREFIX) {
+            Ok(issued) => issued,
+            Err(e) => return tr!(lang => "cmd.webhook_failed", error = e),
+        };
+        let record = WebhookRecord {
+            name: name.to_string(),
+            token_sha256: digest,
+            created_by: session.username.get(),
+            created_at: Local::now(),
+        };
+        if let Some(mut target) = self.users.get_mut(&user) {
+            target.webhooks.push(record);
+        }
+        self.save_user(&user);
+        println!(
+            "{}",
+            tr!(
+                "server.webhook_created",
+                admin = session.username,
+                name = name,
+                user = user
+            )
+        );
+        let mut reply = tr!(lang => "cmd.webhook_created",
+            name = name,
+            user = user,
+            path = format!("{}{}", HOOKS_PATH, token),
+        );
+        if self.config.webhooks.is_none() {
+            reply.push_str(&tr!(lang => "cmd.webhook_not_listening"));
+        }
+        reply
+    }
+
+    /// 删除名为 `name` 的 Webhook，其令牌随即失效
+    fn revoke_webhook(&self, name: &str, lang: Lang) -> String {
+        let Some(user) = self.webhook_owner(name) else {
+            return tr!(lang => "cmd.webhook_missing", name = name);
+        };
+        if let Some(mut target) = self.users.get_mut(&user) {
+            target.webhooks.retain(|hook| hook.name != name);
+        }
+        self.save_user(&user);
+        println!("{}", tr!("server.webhook_revoked", name = name));
+        tr!(lang => "cmd.webhook_revoked", name = name)
+    }
+
+    /// 所有 Webhook（按名称排序）及其目标用户与创建者
+    fn list_webhooks(&self, lang: Lang) -> String {
+        let mut hooks: Vec<(WebhookRecord, String)> = self
+            .users
+            .iter()
+            .flat_map(|entry| {
+                let user = entry.key().get();
+                entry
+                    .value()
+                    .webhooks
+                    .iter()
+                    .map(move |hook| (hook.clone(), user.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if hooks.is_empty() {
+            return tr!(lang => "cmd.webhook_empty");
+        }
+        hooks.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+        let lines: Vec<String> = hooks
+            .iter()
+            .map(|(hook, user)| {
+                tr!(lang => "cmd.webhook_entry",
+                    name = hook.name,
+                    user = user,
+                    admin = hook.created_by,
+                )
+            })
+            .collect();
+        format!(
+            "{}\n  › {}",
+            tr!(lang => "cmd.webhook_list", count = hooks.len()),
+            lines.join("\n  › ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ServerConfig;
+    use super::*;
+
+    /// 向 `token` 对应的 Webhook 提交内容为 `content` 的请求
+    fn request(token: &str, content: &str) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: format!("{}{}", HOOKS_PATH, token),
+            body: serde_json::json!({ "content": content })
+                .to_string()
+                .into_bytes(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_content_over_the_message_limit() {
+        let server = Server::with_config(ServerConfig {
+            max_message_len: Some(16),
+            ..ServerConfig::default()
+        });
+        let user = ArcString::new("alice".to_string());
+        let (token, digest) = issue_token(TOKEN_PREFIX).unwrap();
+        server
+            .users
+            .entry(user.clone())
+            .or_default()
+            .webhooks
+            .push(WebhookRecord {
+                name: "ci".to_string(),
+                token_sha256: digest,
+                created_by: "admin".to_string(),
+                created_at: Local::now(),
+            });
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+
+        let reply = server
+            .handle_webhook(request(&token, &"x".repeat(17)), addr)
+            .await;
+        assert_eq!(reply.status, 413);
+        assert_eq!(reply.body["error"], "MESSAGE_TOO_LARGE");
+
+        let reply = server
+            .handle_webhook(request(&token, "build failed"), addr)
+            .await;
+        assert_eq!(reply.status, 200);
+    }
+}
@@ -0,0 +1,69 @@
+/*!
+# WebSocket 接入
+
+浏览器无法建立原始 TCP 连接，服务器可以通过 `--ws=地址` 额外监听 WebSocket 连接，
+供浏览器中的客户端（复用 [`crate::client_core`] 的握手与状态处理）接入。
+
+每条文本消息承载一帧 JSON，与 JSON Lines 编码的一行相同；消息中作为空白出现的换行被替换为空格，
+保证一条消息正好对应一帧。握手完成后连接被桥接为字节流：收到的文本消息逐行写入读取端，
+服务器写出的每一行作为一条文本消息发送，之后的注册、会话处理与 TCP 连接完全相同。
+不接受二进制消息，收到时关闭连接。
+*/
+
+use crate::codec::MAX_FRAME_LEN;
+use crate::transport::{BoxReader, BoxWriter};
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use tokio::io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// 桥接管道每个方向的缓冲区大小
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// 完成 WebSocket 握手，并把连接桥接为与 TCP 连接相同的读写半部
+pub(super) async fn accept(stream: TcpStream) -> io::Result<(BoxReader, BoxWriter)> {
+    let config = WebSocketConfig {
+        max_message_size: Some(MAX_FRAME_LEN),
+        max_frame_size: Some(MAX_FRAME_LEN),
+        ..WebSocketConfig::default()
+    };
+    let socket = tokio_tungstenite::accept_async_with_config(stream, Some(config))
+        .await
+        .map_err(io::Error::other)?;
+    let (mut sink, mut source) = socket.split();
+    let (mut inbound, reader) = duplex(PIPE_CAPACITY);
+    let (writer, outbound) = duplex(PIPE_CAPACITY);
+
+    // 收到的文本消息 → 读取端的一行；对端关闭或发来二进制消息时结束读取端
+    tokio::spawn(async move {
+        while let Some(Ok(message)) = source.next().await {
+            let text = match message {
+                WsMessage::Text(text) => text,
+                WsMessage::Binary(_) | WsMessage::Close(_) => break,
+                // Ping/Pong 由 tungstenite 自动应答
+                _ => continue,
+            };
+            let mut line = text.replace(['\r', '\n'], " ").into_bytes();
+            line.push(b'\n');
+            if inbound.write_all(&line).await.is_err() {
+                break;
+            }
+        }
+        let _ = inbound.shutdown().await;
+    });
+
+    // 写入端的每一行 → 一条文本消息；服务器关闭写入端后关闭 WebSocket
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(outbound).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if sink.send(WsMessage::Text(line)).await.is_err() {
+                return;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    Ok((Box::new(reader), Box::new(writer)))
+}
@@ -0,0 +1,957 @@
+/*!
+# XMPP 网关
+
+服务器以外部组件（XEP-0114）的身份连接 XMPP 服务器，占用一个子域名（例如 `chat.example.org`），
+Jabber 客户端即可以 `用户@chat.example.org` 的地址与本服务器的用户互发消息：
+
+- 组件连接 XMPP 服务器的组件端口，以共享密钥完成握手（`SHA-1(流编号 + 密钥)`），
+  断开后按指数退避重连，与联邦链路相同；
+- Jabber 用户发来的一对一消息（`chat`/`normal` 类型，带 `<body>`）以 `xmpp:<JID>`（不含资源）
+  为发送者投递给本地用户，写入消息日志；接收者不存在时回复 `item-not-found` 错误；
+- 本地用户发给 `xmpp:<JID>` 的消息转换为 `chat` 类型的消息节，动作消息按 XEP-0245 以 `/me `
+  开头；网关未连接时告知发送者；
+- 在线状态：Jabber 联系人订阅（`subscribe`）本地用户后，网关自动同意并记录订阅者
+  （随用户数据持久化），此后该用户上线、下线、离开或免打扰时向订阅者推送状态；
+  隐藏了在线状态的用户始终显示为离线。Jabber 联系人发给本地用户的状态记录在内存中，
+  本地用户可以通过 `/xmpp` 查看；
+- 只支持一对一消息，不支持群聊（MUC）与服务发现以外的查询，其他 `iq` 请求回复
+  `service-unavailable`。
+*/
+
+use super::{sanitized, Server};
+use crate::i18n::Lang;
+use crate::protocol::ErrorCode;
+use crate::secrets::encode_hex;
+use crate::session::Session;
+use crate::transport::{self, BoxReader, BoxWriter};
+use crate::{tr, ArcString, Message, MessageKind};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// XMPP 一侧的发送者与收件地址的前缀
+pub const PREFIX: &str = "xmpp:";
+
+/// XMPP 服务器组件端口的默认值
+pub const DEFAULT_COMPONENT_PORT: u16 = 5347;
+
+/// 连接并完成握手的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 断开后首次重连前的等待时间，之后每次失败翻倍
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+
+/// 重连等待时间的上限
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+
+/// 空闲时发送空白保活的间隔
+const KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// 发往 XMPP 服务器的消息节队列的容量
+const QUEUE_CAPACITY: usize = 256;
+
+/// 单个消息节的最大字节数，超过时断开重连
+const MAX_STANZA_LEN: usize = 256 * 1024;
+
+const NS_COMPONENT: &str = "jabber:component:accept";
+const NS_STREAM: &str = "http://etherx.jabber.org/streams";
+const NS_STANZAS: &str = "urn:ietf:params:xml:ns:xmpp-stanzas";
+const NS_PING: &str = "urn:xmpp:ping";
+const NS_DISCO_INFO: &str = "http://jabber.org/protocol/disco#info";
+
+/// 网关的错误，需要在任务之间传递
+type GatewayError = Box<dyn std::error::Error + Send + Sync>;
+
+/// XMPP 网关配置
+#[derive(Clone)]
+pub struct XmppConfig {
+    /// 组件占用的域名，本服务器用户在 XMPP 一侧的地址为 `用户@域名`
+    pub domain: String,
+    /// XMPP 服务器组件端口的地址（`主机:端口`）
+    pub addr: String,
+    /// 在 XMPP 服务器上为该组件配置的共享密钥
+    pub secret: String,
+}
+
+impl fmt::Debug for XmppConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XmppConfig")
+            .field("domain", &self.domain)
+            .field("addr", &self.addr)
+            .field("secret", &"***")
+            .finish()
+    }
+}
+
+impl XmppConfig {
+    /// 解析 `域名=密钥` 或 `域名=密钥@主机:端口` 形式的配置；未写地址时连接本机的
+    /// [`DEFAULT_COMPONENT_PORT`]
+    pub fn from_string(spec: &str) -> Option<XmppConfig> {
+        let (domain, rest) = spec.split_once('=')?;
+        let (secret, addr) = match rest.rsplit_once('@') {
+            Some((secret, addr)) => (secret, addr.trim().to_string()),
+            None => (rest, format!("127.0.0.1:{}", DEFAULT_COMPONENT_PORT)),
+        };
+        let (domain, secret) = (domain.trim(), secret.trim());
+        if domain.is_empty() || secret.is_empty() || addr.is_empty() {
+            return None;
+        }
+        Some(XmppConfig {
+            domain: domain.to_string(),
+            addr,
+            secret: secret.to_string(),
+        })
+    }
+}
+
+/// 网关的运行状态
+#[derive(Debug)]
+pub(super) struct XmppGateway {
+    config: XmppConfig,
+    /// 当前连接的发送队列，未连接时为 `None`
+    outgoing: Mutex<Option<mpsc::Sender<String>>>,
+    /// 本地用户的在线状态有变化时递增，连接任务随之推送状态
+    status: watch::Sender<u64>,
+    /// 本地用户 → Jabber 联系人（不含资源的 JID）→ 最近收到的状态
+    contacts: Mutex<HashMap<ArcString, BTreeMap<String, String>>>,
+    /// 发出的消息节编号
+    next_id: AtomicU64,
+}
+
+impl XmppGateway {
+    pub(super) fn new(config: XmppConfig) -> Self {
+        XmppGateway {
+            config,
+            outgoing: Mutex::new(None),
+            status: watch::channel(0).0,
+            contacts: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// 本地用户上线、下线或修改了状态
+    pub(super) fn status_changed(&self) {
+        self.status.send_modify(|generation| *generation += 1);
+    }
+
+    /// 本地用户 `user` 在 XMPP 一侧的地址
+    fn jid(&self, user: &str) -> String {
+        format!("{}@{}", escape_node(user), self.config.domain)
+    }
+
+    /// 从发给本组件的地址中取出用户部分；域名不符或没有用户部分时返回 `None`
+    fn node(&self, to: &str) -> Option<String> {
+        let (node, domain) = bare(to).split_once('@')?;
+        (domain.eq_ignore_ascii_case(&self.config.domain) && !node.is_empty())
+            .then(|| unescape_node(node))
+    }
+
+    fn next_id(&self) -> String {
+        format!("chat-{}", self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// 本地用户在 XMPP 一侧显示的状态：`None` 为离线，否则为 `(show, status)`
+type Availability = Option<(Option<&'static str>, Option<String>)>;
+
+impl Server {
+    /// 配置了 XMPP 网关时启动组件连接任务
+    pub(super) fn spawn_xmpp(&self) -> Option<JoinHandle<()>> {
+        self.xmpp.as_ref()?;
+        let server = self.clone();
+        Some(tokio::spawn(async move { server.xmpp_link().await }))
+    }
+
+    /// 本地用户的在线状态可能有变化：上线、下线、`/away`、`/dnd` 或修改隐私设置
+    pub(super) fn xmpp_status_changed(&self) {
+        if let Some(xmpp) = &self.xmpp {
+            xmpp.status_changed();
+        }
+    }
+
+    /// 维持到 XMPP 服务器的组件连接，断开后按指数退避重连
+    async fn xmpp_link(&self) {
+        let Some(xmpp) = &self.xmpp else {
+            return;
+        };
+        let mut backoff = RECONNECT_MIN;
+        loop {
+            let addr = &xmpp.config.addr;
+            match tokio::time::timeout(CONNECT_TIMEOUT, self.connect_xmpp(xmpp)).await {
+                Ok(Ok((stanzas, writer))) => {
+                    backoff = RECONNECT_MIN;
+                    println!(
+                        "{}",
+                        tr!(
+                            "server.xmpp_linked",
+                            domain = xmpp.config.domain,
+                            addr = addr
+                        )
+                    );
+                    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                    *xmpp.outgoing.lock().unwrap() = Some(tx);
+                    let result = self.run_xmpp_link(xmpp, stanzas, writer, rx).await;
+                    *xmpp.outgoing.lock().unwrap() = None;
+                    xmpp.contacts.lock().unwrap().clear();
+                    let error = match result {
+                        Ok(()) => tr!("server.xmpp_closed"),
+                        Err(e) => e.to_string(),
+                    };
+                    println!("{}", tr!("server.xmpp_unlinked", error = error));
+                }
+                Ok(Err(e)) => {
+                    eprintln!("{}", tr!("server.xmpp_link_failed", addr = addr, error = e))
+                }
+                Err(_) => eprintln!(
+                    "{}",
+                    tr!(
+                        "server.xmpp_link_failed",
+                        addr = addr,
+                        error = tr!("server.peer_timeout"),
+                    )
+                ),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX);
+        }
+    }
+
+    /// 连接 XMPP 服务器并完成组件握手
+    async fn connect_xmpp(
+        &self,
+        xmpp: &XmppGateway,
+    ) -> Result<(StanzaReader, BoxWriter), GatewayError> {
+        let (reader, mut writer) =
+            transport::connect(&xmpp.config.addr, None, &self.config.socket, None).await?;
+        let header = format!(
+            "<?xml version='1.0'?><stream:stream xmlns='{}' xmlns:stream='{}' to='{}'>",
+            NS_COMPONENT,
+            NS_STREAM,
+            escape(&xmpp.config.domain)
+        );
+        writer.write_all(header.as_bytes()).await?;
+        writer.flush().await?;
+
+        let mut stanzas = StanzaReader::new(reader);
+        let stream_id = match stanzas.next().await? {
+            Some(Event::Header { id }) => id,
+            Some(Event::Stanza(stanza)) => return Err(stream_error(&stanza).into()),
+            _ => return Err(tr!("server.xmpp_protocol").into()),
+        };
+        let digest = Sha1::digest(format!("{}{}", stream_id, xmpp.config.secret).as_bytes());
+        let handshake = format!("<handshake>{}</handshake>", encode_hex(&digest));
+        writer.write_all(handshake.as_bytes()).await?;
+        writer.flush().await?;
+        match stanzas.next().await? {
+            Some(Event::Stanza(stanza)) if stanza.name == "handshake" => Ok((stanzas, writer)),
+            Some(Event::Stanza(stanza)) => Err(stream_error(&stanza).into()),
+            _ => Err(tr!("server.xmpp_protocol").into()),
+        }
+    }
+
+    /// 在已握手的连接上收发消息节并推送在线状态，直到连接断开
+    async fn run_xmpp_link(
+        &self,
+        xmpp: &XmppGateway,
+        mut stanzas: StanzaReader,
+        mut writer: BoxWriter,
+        mut rx: mpsc::Receiver<String>,
+    ) -> Result<(), GatewayError> {
+        let mut status = xmpp.status.subscribe();
+        // 已推送给订阅者的状态，只推送有变化的用户
+        let mut pushed: HashMap<ArcString, Availability> = HashMap::new();
+        self.push_presence(xmpp, &mut writer, &mut pushed).await?;
+        let mut keepalive = tokio::time::interval(KEEPALIVE);
+        keepalive.tick().await;
+        loop {
+            tokio::select! {
+                event = stanzas.next() => match event? {
+                    Some(Event::Stanza(stanza)) => {
+                        if stanza.name == "error" {
+                            return Err(stream_error(&stanza).into());
+                        }
+                        for reply in self.handle_stanza(xmpp, stanza).await {
+                            writer.write_all(reply.as_bytes()).await?;
+                        }
+                        writer.flush().await?;
+                    }
+                    Some(Event::Header { .. }) => return Err(tr!("server.xmpp_protocol").into()),
+                    None => return Ok(()),
+                },
+                stanza = rx.recv() => match stanza {
+                    Some(stanza) => {
+                        writer.write_all(stanza.as_bytes()).await?;
+                        writer.flush().await?;
+                    }
+                    None => return Ok(()),
+                },
+                changed = status.changed() => {
+                    changed?;
+                    self.push_presence(xmpp, &mut writer, &mut pushed).await?;
+                }
+                _ = keepalive.tick() => {
+                    writer.write_all(b" ").await?;
+                    writer.flush().await?;
+                }
+            }
+        }
+    }
+
+    /// 向订阅者推送状态有变化的本地用户
+    async fn push_presence(
+        &self,
+        xmpp: &XmppGateway,
+        writer: &mut BoxWriter,
+        pushed: &mut HashMap<ArcString, Availability>,
+    ) -> Result<(), GatewayError> {
+        let subscribed: Vec<(ArcString, Vec<String>)> = self
+            .users
+            .iter()
+            .filter(|entry| !entry.value().xmpp_subscribers.is_empty())
+            .map(|entry| (entry.key().clone(), entry.value().xmpp_subscribers.clone()))
+            .collect();
+        for (user, subscribers) in subscribed {
+            let availability = self.availability(&user);
+            if pushed.get(&user) == Some(&availability) {
+                continue;
+            }
+            for subscriber in &subscribers {
+                let stanza = presence_stanza(&xmpp.jid(&user.get()), subscriber, &availability);
+                writer.write_all(stanza.as_bytes()).await?;
+            }
+            pushed.insert(user, availability);
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// 发给本组件的地址对应的本地用户；XMPP 服务器可能把用户部分转为小写，找不到时不区分大小写再找一次
+    fn xmpp_local_user(&self, xmpp: &XmppGateway, to: &str) -> Option<ArcString> {
+        let node = xmpp.node(to)?;
+        let user = ArcString::new(node.clone());
+        if self.users.contains_key(&user) {
+            return Some(user);
+        }
+        self.users
+            .iter()
+            .find(|entry| entry.key().get().to_lowercase() == node.to_lowercase())
+            .map(|entry| entry.key().clone())
+    }
+
+    /// 本地用户当前在 XMPP 一侧显示的状态
+    fn availability(&self, user: &ArcString) -> Availability {
+        let visible = self
+            .users
+            .get(user)
+            .is_some_and(|record| record.profile.privacy.show_status);
+        if !visible {
+            return None;
+        }
+        let presence = self.online_users.get(user)?.presence.clone();
+        let presence = presence.lock().unwrap();
+        Some(if presence.dnd {
+            (Some("dnd"), None)
+        } else if let Some(text) = &presence.away {
+            (Some("away"), Some(text.clone()))
+        } else {
+            (None, None)
+        })
+    }
+
+    /// 处理 XMPP 服务器发来的一个消息节，返回需要应答的消息节
+    async fn handle_stanza(&self, xmpp: &XmppGateway, stanza: Stanza) -> Vec<String> {
+        let Some(from) = stanza.attr("from").map(str::to_string) else {
+            return Vec::new();
+        };
+        let to = stanza.attr("to").unwrap_or_default().to_string();
+        let kind = stanza.attr("type").unwrap_or_default().to_string();
+        match stanza.name.as_str() {
+            "message" => self.xmpp_message(xmpp, &stanza, &from, &to, &kind).await,
+            "presence" => self.xmpp_presence(xmpp, &stanza, &from, &to, &kind),
+            "iq" if kind == "get" || kind == "set" => {
+                let id = stanza.attr("id").unwrap_or_default();
+                let reply = if stanza.child_ns.as_deref() == Some(NS_PING) {
+                    format!(
+                        "<iq type='result' id='{}' from='{}' to='{}'/>",
+                        escape(id),
+                        escape(&to),
+                        escape(&from)
+                    )
+                } else if stanza.child_ns.as_deref() == Some(NS_DISCO_INFO) && kind == "get" {
+                    format!(
+                        "<iq type='result' id='{}' from='{}' to='{}'><query xmlns='{}'>\
+                         <identity category='gateway' type='chat' name='async-chat'/>\
+                         <feature var='{}'/><feature var='{}'/></query></iq>",
+                        escape(id),
+                        escape(&to),
+                        escape(&from),
+                        NS_DISCO_INFO,
+                        NS_DISCO_INFO,
+                        NS_PING
+                    )
+                } else {
+                    error_stanza("iq", &to, &from, Some(id), "service-unavailable")
+                };
+                vec![reply]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Jabber 用户发来的消息：转换为发给本地用户的聊天消息
+    async fn xmpp_message(
+        &self,
+        xmpp: &XmppGateway,
+        stanza: &Stanza,
+        from: &str,
+        to: &str,
+        kind: &str,
+    ) -> Vec<String> {
+        if !matches!(kind, "" | "chat" | "normal") {
+            return Vec::new();
+        }
+        // 没有正文的消息（例如输入状态通知）直接忽略
+        let Some(body) = stanza.body.clone().filter(|body| !body.trim().is_empty()) else {
+            return Vec::new();
+        };
+        let Some(user) = self.xmpp_local_user(xmpp, to) else {
+            let id = stanza.attr("id");
+            return vec![error_stanza("message", to, from, id, "item-not-found")];
+        };
+        let sender = ArcString::new(format!("{}{}", PREFIX, bare(from)));
+        println!(
+            "{}",
+            tr!("server.xmpp_message", from = bare(from), user = user)
+        );
+        let msg = match body.strip_prefix("/me ") {
+            Some(action) => {
+                Message::new(sender, user.get(), action.to_string()).with_kind(MessageKind::Action)
+            }
+            None => Message::new(sender, user.get(), body),
+        };
+        let msg = self.log_message(sanitized(msg));
+        self.deliver_local(&user, msg).await;
+        Vec::new()
+    }
+
+    /// Jabber 用户发来的状态：订阅请求、状态探测，或该联系人自己的在线状态
+    fn xmpp_presence(
+        &self,
+        xmpp: &XmppGateway,
+        stanza: &Stanza,
+        from: &str,
+        to: &str,
+        kind: &str,
+    ) -> Vec<String> {
+        let Some(user) = self.xmpp_local_user(xmpp, to) else {
+            if kind == "subscribe" || kind == "probe" {
+                return vec![error_stanza("presence", to, from, None, "item-not-found")];
+            }
+            return Vec::new();
+        };
+        let contact = bare(from).to_string();
+        let local = xmpp.jid(&user.get());
+        match kind {
+            "subscribe" => {
+                let added = match self.users.get_mut(&user) {
+                    Some(mut record) if !record.xmpp_subscribers.contains(&contact) => {
+                        record.xmpp_subscribers.push(contact.clone());
+                        true
+                    }
+                    _ => false,
+                };
+                if added {
+                    self.save_user(&user);
+                    println!(
+                        "{}",
+                        tr!("server.xmpp_subscribed", contact = contact, user = user)
+                    );
+                }
+                vec![
+                    format!(
+                        "<presence type='subscribed' from='{}' to='{}'/>",
+                        escape(&local),
+                        escape(&contact)
+                    ),
+                    presence_stanza(&local, &contact, &self.availability(&user)),
+                ]
+            }
+            "unsubscribe" => {
+                let removed = match self.users.get_mut(&user) {
+                    Some(mut record) if record.xmpp_subscribers.contains(&contact) => {
+                        record.xmpp_subscribers.retain(|jid| *jid != contact);
+                        true
+                    }
+                    _ => false,
+                };
+                if removed {
+                    self.save_user(&user);
+                }
+                vec![format!(
+                    "<presence type='unsubscribed' from='{}' to='{}'/>",
+                    escape(&local),
+                    escape(&contact)
+                )]
+            }
+            "probe" => {
+                let subscribed = self
+                    .users
+                    .get(&user)
+                    .is_some_and(|record| record.xmpp_subscribers.contains(&contact));
+                let availability = if subscribed {
+                    self.availability(&user)
+                } else {
+                    None
+                };
+                vec![presence_stanza(&local, from, &availability)]
+            }
+            "" | "unavailable" => {
+                let mut contacts = xmpp.contacts.lock().unwrap();
+                let known = contacts.entry(user).or_default();
+                if kind == "unavailable" {
+                    known.remove(&contact);
+                } else {
+                    known.insert(contact, stanza.show.clone().unwrap_or_default());
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// 把发给 `xmpp:` 地址的消息转换为消息节发给 XMPP 服务器
+    ///
+    /// # 返回值
+    /// 未配置网关或 `msg` 不是发给 `xmpp:` 地址时返回 `false`，由调用方按普通消息处理
+    pub(super) async fn route_to_xmpp(&self, session: &Session, msg: &Message) -> bool {
+        let Some(jid) = msg.to().strip_prefix(PREFIX) else {
+            return false;
+        };
+        let Some(xmpp) = &self.xmpp else {
+            return false;
+        };
+        let lang = session.lang;
+        let target = ArcString::new(msg.to().to_string());
+        let refusal = if msg.kind() == MessageKind::Encrypted {
+            Some((ErrorCode::Blocked, tr!(lang => "server.xmpp_encrypted")))
+        } else if !jid.contains('@') || jid.contains(char::is_whitespace) {
+            Some((
+                ErrorCode::UnknownUser,
+                tr!(lang => "server.unknown_user", user = msg.to()),
+            ))
+        } else {
+            let body = match msg.kind() {
+                MessageKind::Action => format!("/me {}", msg.content()),
+                _ => msg.content().to_string(),
+            };
+            let stanza = format!(
+                "<message type='chat' id='{}' from='{}' to='{}'><body>{}</body></message>",
+                xmpp.next_id(),
+                escape(&xmpp.jid(&session.username.get())),
+                escape(jid),
+                escape(&body)
+            );
+            let outgoing = xmpp.outgoing.lock().unwrap().clone();
+            match outgoing.map(|tx| tx.try_send(stanza)) {
+                Some(Ok(())) => None,
+                Some(Err(mpsc::error::TrySendError::Full(_))) => {
+                    Some((ErrorCode::RateLimited, tr!(lang => "server.xmpp_busy")))
+                }
+                _ => Some((
+                    ErrorCode::UnknownHost,
+                    tr!(lang => "server.xmpp_offline", user = msg.to()),
+                )),
+            }
+        };
+        if let Some((code, text)) = refusal {
+            self.reject(session, code, Some(&target), msg.id(), text)
+                .await;
+        }
+        true
+    }
+
+    /// `/xmpp`：网关的连接状态、自己在 XMPP 一侧的地址、订阅者与联系人的状态
+    pub(super) fn xmpp_response(&self, session: &Session) -> String {
+        let lang = session.lang;
+        let Some(xmpp) = &self.xmpp else {
+            return tr!(lang => "cmd.xmpp_disabled");
+        };
+        let state = if xmpp.outgoing.lock().unwrap().is_some() {
+            tr!(lang => "cmd.xmpp_linked")
+        } else {
+            tr!(lang => "cmd.xmpp_unlinked")
+        };
+        let mut lines = vec![tr!(lang => "cmd.xmpp_status",
+            domain = xmpp.config.domain,
+            state = state,
+            jid = xmpp.jid(&session.username.get()),
+        )];
+        let subscribers = self
+            .users
+            .get(&session.username)
+            .map(|record| record.xmpp_subscribers.clone())
+            .unwrap_or_default();
+        if !subscribers.is_empty() {
+            lines.push(tr!(lang => "cmd.xmpp_subscribers",
+                count = subscribers.len(),
+                contacts = subscribers.join(", "),
+            ));
+        }
+        let contacts = xmpp.contacts.lock().unwrap();
+        match contacts
+            .get(&session.username)
+            .filter(|known| !known.is_empty())
+        {
+            Some(known) => {
+                lines.push(tr!(lang => "cmd.xmpp_contacts", count = known.len()));
+                for (jid, show) in known {
+                    let show = describe_show(show, lang);
+                    lines.push(format!("  › {}{} ({})", PREFIX, jid, show));
+                }
+            }
+            None => lines.push(tr!(lang => "cmd.xmpp_no_contacts")),
+        }
+        lines.join("\n")
+    }
+}
+
+/// 以 `lang` 描述 Jabber 联系人的 `<show>` 状态
+fn describe_show(show: &str, lang: Lang) -> String {
+    match show {
+        "away" | "xa" => tr!(lang => "cmd.xmpp_show_away"),
+        "dnd" => tr!(lang => "cmd.xmpp_show_dnd"),
+        _ => tr!(lang => "cmd.xmpp_show_online"),
+    }
+}
+
+/// 从 `from` 发给 `to` 的状态消息节
+fn presence_stanza(from: &str, to: &str, availability: &Availability) -> String {
+    let (from, to) = (escape(from), escape(to));
+    match availability {
+        None => format!("<presence type='unavailable' from='{}' to='{}'/>", from, to),
+        Some((show, status)) => {
+            let mut children = String::new();
+            if let Some(show) = show {
+                children.push_str(&format!("<show>{}</show>", show));
+            }
+            if let Some(status) = status {
+                children.push_str(&format!("<status>{}</status>", escape(status)));
+            }
+            format!(
+                "<presence from='{}' to='{}'>{}</presence>",
+                from, to, children
+            )
+        }
+    }
+}
+
+/// 对 `from` 发来的消息节的错误应答
+fn error_stanza(name: &str, local: &str, from: &str, id: Option<&str>, condition: &str) -> String {
+    let id = id
+        .map(|id| format!(" id='{}'", escape(id)))
+        .unwrap_or_default();
+    format!(
+        "<{name} type='error'{id} from='{}' to='{}'><error type='cancel'><{condition} xmlns='{}'/></error></{name}>",
+        escape(local),
+        escape(from),
+        NS_STANZAS,
+    )
+}
+
+/// 流错误（`<stream:error>`）或握手失败的描述
+fn stream_error(stanza: &Stanza) -> String {
+    let condition = stanza.child_name.clone().unwrap_or_default();
+    tr!("server.xmpp_stream_error", condition = condition)
+}
+
+/// 去掉 JID 的资源部分
+fn bare(jid: &str) -> &str {
+    jid.split_once('/').map_or(jid, |(bare, _)| bare)
+}
+
+/// 按 XEP-0106 转义用户名中不能出现在 JID 用户部分的字符
+fn escape_node(user: &str) -> String {
+    let mut node = String::with_capacity(user.len());
+    for c in user.chars() {
+        match c {
+            ' ' => node.push_str("\\20"),
+            '"' => node.push_str("\\22"),
+            '&' => node.push_str("\\26"),
+            '\'' => node.push_str("\\27"),
+            '/' => node.push_str("\\2f"),
+            ':' => node.push_str("\\3a"),
+            '<' => node.push_str("\\3c"),
+            '>' => node.push_str("\\3e"),
+            '@' => node.push_str("\\40"),
+            '\\' => node.push_str("\\5c"),
+            c => node.push(c),
+        }
+    }
+    node
+}
+
+/// [`escape_node`] 的逆操作
+fn unescape_node(node: &str) -> String {
+    const ESCAPES: &[(&str, char)] = &[
+        ("\\20", ' '),
+        ("\\22", '"'),
+        ("\\26", '&'),
+        ("\\27", '\''),
+        ("\\2f", '/'),
+        ("\\3a", ':'),
+        ("\\3c", '<'),
+        ("\\3e", '>'),
+        ("\\40", '@'),
+        ("\\5c", '\\'),
+    ];
+    let mut user = String::with_capacity(node.len());
+    let mut rest = node;
+    while !rest.is_empty() {
+        match ESCAPES
+            .iter()
+            .find(|(escaped, _)| rest.starts_with(escaped))
+        {
+            Some((escaped, c)) => {
+                user.push(*c);
+                rest = &rest[escaped.len()..];
+            }
+            None => {
+                let c = rest.chars().next().unwrap_or_default();
+                user.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    user
+}
+
+/// 转义 XML 文本与属性值
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 一个解析后的顶层消息节，只保留网关用到的部分
+#[derive(Debug)]
+struct Stanza {
+    /// 元素名（不含命名空间前缀）
+    name: String,
+    attrs: HashMap<String, String>,
+    /// `<body>` 的文本
+    body: Option<String>,
+    /// `<show>` 的文本
+    show: Option<String>,
+    /// 第一个子元素的名称与命名空间（`iq` 的查询类型、流错误的条件）
+    child_name: Option<String>,
+    child_ns: Option<String>,
+}
+
+impl Stanza {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    /// 解析一个完整的顶层元素；流中声明的命名空间由外层包装元素补上
+    fn parse(xml: &str) -> Result<Stanza, roxmltree::Error> {
+        let wrapped = format!(
+            "<wrapper xmlns='{}' xmlns:stream='{}'>{}</wrapper>",
+            NS_COMPONENT, NS_STREAM, xml
+        );
+        let document = roxmltree::Document::parse(&wrapped)?;
+        let element = document
+            .root_element()
+            .first_element_child()
+            .unwrap_or_else(|| document.root_element());
+        let text = |name: &str| {
+            element
+                .children()
+                .find(|child| child.has_tag_name(name))
+                .map(|child| child.text().unwrap_or_default().to_string())
+        };
+        let child = element.first_element_child();
+        Ok(Stanza {
+            name: element.tag_name().name().to_string(),
+            attrs: element
+                .attributes()
+                .map(|attr| (attr.name().to_string(), attr.value().to_string()))
+                .collect(),
+            body: text("body"),
+            show: text("show"),
+            child_name: child.map(|child| child.tag_name().name().to_string()),
+            child_ns: child.and_then(|child| child.tag_name().namespace().map(str::to_string)),
+        })
+    }
+}
+
+/// XML 流中的事件
+#[derive(Debug)]
+enum Event {
+    /// 对端的流头，`id` 用于计算握手
+    Header { id: String },
+    /// 一个完整的顶层消息节
+    Stanza(Stanza),
+}
+
+/// 把 XML 流切分为顶层消息节
+///
+/// XMPP 的流是一个永不闭合的 `<stream:stream>` 元素，无法整体交给 XML 解析器：
+/// 这里按标签的嵌套深度找到每个完整的顶层元素，再单独解析。
+struct StanzaReader {
+    reader: BoxReader,
+    buf: Vec<u8>,
+    header_seen: bool,
+}
+
+impl StanzaReader {
+    fn new(reader: BoxReader) -> Self {
+        StanzaReader {
+            reader,
+            buf: Vec::new(),
+            header_seen: false,
+        }
+    }
+
+    /// 读取下一个事件；对端关闭流时返回 `None`
+    async fn next(&mut self) -> Result<Option<Event>, GatewayError> {
+        loop {
+            match self.split()? {
+                Split::Header(header) => {
+                    let document = format!("{}</stream:stream>", header);
+                    let document = roxmltree::Document::parse(&document)?;
+                    let id = document
+                        .root_element()
+                        .attribute("id")
+                        .unwrap_or_default()
+                        .to_string();
+                    return Ok(Some(Event::Header { id }));
+                }
+                Split::Stanza(xml) => return Ok(Some(Event::Stanza(Stanza::parse(&xml)?))),
+                Split::End => return Ok(None),
+                Split::Incomplete => {}
+            }
+            if self.buf.len() > MAX_STANZA_LEN {
+                return Err(tr!("server.xmpp_too_large").into());
+            }
+            let mut chunk = [0u8; 4096];
+            let n = self.reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// 从缓冲区开头取出一个完整的流头、顶层元素或流结束标记
+    fn split(&mut self) -> Result<Split, GatewayError> {
+        let (end, split) = match self.scan()? {
+            Some(found) => found,
+            None => return Ok(Split::Incomplete),
+        };
+        self.buf.drain(..end);
+        Ok(split)
+    }
+
+    /// 在缓冲区中找到第一个完整的单元，返回其结束位置
+    fn scan(&mut self) -> Result<Option<(usize, Split)>, GatewayError> {
+        let buf = &self.buf;
+        let mut depth = 0usize;
+        let mut start = None;
+        let mut i = 0;
+        while i < buf.len() {
+            if buf[i] != b'<' {
+                // 顶层只允许空白（保活）
+                if depth == 0 && !buf[i].is_ascii_whitespace() {
+                    return Err(tr!("server.xmpp_protocol").into());
+                }
+                i += 1;
+                continue;
+            }
+            let rest = &buf[i..];
+            let skip_to = |end: &[u8]| {
+                rest.windows(end.len())
+                    .position(|window| window == end)
+                    .map(|at| i + at + end.len())
+            };
+            // 处理指令、注释与 CDATA 不影响嵌套深度
+            let skipped = if rest.starts_with(b"<?") {
+                Some(skip_to(b"?>"))
+            } else if rest.starts_with(b"<!--") {
+                Some(skip_to(b"-->"))
+            } else if rest.starts_with(b"<![CDATA[") {
+                Some(skip_to(b"]]>"))
+            } else {
+                None
+            };
+            if let Some(end) = skipped {
+                let Some(end) = end else {
+                    return Ok(None);
+                };
+                i = end;
+                continue;
+            }
+            let Some(end) = tag_end(buf, i) else {
+                return Ok(None);
+            };
+            let tag = &buf[i..end];
+            let element_start = *start.get_or_insert(i);
+            if tag.starts_with(b"</") {
+                if depth == 0 {
+                    // 对端关闭了流
+                    return Ok(Some((end, Split::End)));
+                }
+                depth -= 1;
+            } else if !tag.ends_with(b"/>") {
+                if depth == 0 && !self.header_seen && tag.starts_with(b"<stream:stream") {
+                    let header = String::from_utf8_lossy(tag).into_owned();
+                    self.header_seen = true;
+                    return Ok(Some((end, Split::Header(header))));
+                }
+                depth += 1;
+            }
+            if depth == 0 {
+                let xml = String::from_utf8_lossy(&buf[element_start..end]).into_owned();
+                return Ok(Some((end, Split::Stanza(xml))));
+            }
+            i = end;
+        }
+        Ok(None)
+    }
+}
+
+/// [`StanzaReader::split`] 的结果
+enum Split {
+    Header(String),
+    Stanza(String),
+    End,
+    Incomplete,
+}
+
+/// 从 `start` 处的 `<` 开始找到标签结束的 `>` 之后的位置，跳过属性值中的 `>`
+fn tag_end(buf: &[u8], start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (offset, &byte) in buf[start..].iter().enumerate() {
+        match (quote, byte) {
+            (None, b'"' | b'\'') => quote = Some(byte),
+            (Some(q), _) if byte == q => quote = None,
+            (None, b'>') => return Some(start + offset + 1),
+            _ => {}
+        }
+    }
+    None
+}
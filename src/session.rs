@@ -0,0 +1,279 @@
+/*!
+# 会话模块
+
+[`Session`] 保存单条连接在其整个生命周期内的状态（用户名、对端地址、连接时间、
+角色、客户端版本、协商出的特性以及用户的语言），由服务器在 `handle_connection` 中创建，
+并一路传递给消息接收逻辑，供空闲超时、按会话限流等功能使用。
+
+需要被其他任务读取的计数与最近活跃时间放在 [`SessionStats`] 中，
+由会话、写任务与在线用户表共享；离开/免打扰状态放在 [`Presence`] 中，
+由会话与在线用户表共享。免打扰期间暂存的消息至多 [`MAX_QUEUED`] 条，超出时最早的一条
+交还调用者放入死信队列；连接结束时尚未补发的暂存消息同样放入死信队列。
+*/
+
+use crate::i18n::{self, Lang};
+use crate::protocol::Frame;
+use crate::server::GeoInfo;
+use crate::{humanize_bytes, humanize_duration_in, tr, ArcString, Message, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// 免打扰期间最多暂存的消息条数
+pub const MAX_QUEUED: usize = 500;
+
+/// 用户角色
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Role {
+    /// 普通用户
+    #[default]
+    User,
+    /// 管理员
+    Admin,
+    /// 机器人账号：以令牌登录，按权限范围限制可做的事，消息单独限流
+    Bot,
+}
+
+/// 会话的共享统计信息，读写任务及在线用户表共同持有
+#[derive(Debug)]
+pub struct SessionStats {
+    /// 该用户发出的消息数
+    pub messages_sent: AtomicU64,
+    /// 投递给该用户的消息数
+    pub messages_received: AtomicU64,
+    /// 从客户端读取的字节数
+    pub bytes_in: AtomicU64,
+    /// 写给客户端的字节数
+    pub bytes_out: AtomicU64,
+    /// 无法解析的帧数
+    pub parse_errors: AtomicU64,
+    /// 最近一次收到客户端数据的时间
+    last_active: Mutex<Instant>,
+    /// 被判定为接收过慢的时间，恢复后清除
+    slow_since: Mutex<Option<Instant>>,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            messages_sent: AtomicU64::new(0),
+            messages_received: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            parse_errors: AtomicU64::new(0),
+            last_active: Mutex::new(Instant::now()),
+            slow_since: Mutex::new(None),
+        }
+    }
+}
+
+impl SessionStats {
+    /// 记录一次客户端活动
+    pub fn touch(&self) {
+        *self.last_active.lock().unwrap() = Instant::now();
+    }
+
+    /// 距离最近一次客户端活动经过的时间
+    pub fn idle_for(&self) -> Duration {
+        self.last_active.lock().unwrap().elapsed()
+    }
+
+    /// 标记为接收过慢
+    ///
+    /// # 返回值
+    /// 此前未被标记时返回 `true`
+    pub fn mark_slow(&self) -> bool {
+        let mut slow_since = self.slow_since.lock().unwrap();
+        if slow_since.is_some() {
+            return false;
+        }
+        *slow_since = Some(Instant::now());
+        true
+    }
+
+    /// 清除接收过慢的标记
+    pub fn clear_slow(&self) {
+        *self.slow_since.lock().unwrap() = None;
+    }
+
+    /// 已持续接收过慢的时长，未被标记时返回 `None`
+    pub fn slow_for(&self) -> Option<Duration> {
+        self.slow_since.lock().unwrap().map(|since| since.elapsed())
+    }
+
+    /// 以 `lang` 描述的计数汇总，例如 "收 3 条/1.2 KB，发 5 条/2.0 KB，解析错误 0，空闲 4 秒"
+    pub fn summary(&self, lang: Lang) -> String {
+        tr!(lang => "stats.summary",
+            in_count = self.messages_sent.load(Ordering::Relaxed),
+            in_bytes = humanize_bytes(self.bytes_in.load(Ordering::Relaxed)),
+            out_count = self.messages_received.load(Ordering::Relaxed),
+            out_bytes = humanize_bytes(self.bytes_out.load(Ordering::Relaxed)),
+            errors = self.parse_errors.load(Ordering::Relaxed),
+            idle = humanize_duration_in(lang, self.idle_for()),
+        ) + &self
+            .slow_for()
+            .map(|slow| tr!(lang => "stats.slow", slow = humanize_duration_in(lang, slow)))
+            .unwrap_or_default()
+    }
+}
+
+/// 用户的离开与免打扰状态
+#[derive(Debug, Default)]
+pub struct Presence {
+    /// 离开状态的自动回复内容，`None` 表示未离开
+    pub away: Option<String>,
+    /// 是否处于免打扰状态
+    pub dnd: bool,
+    /// 本次状态下已自动回复过的发送者，每个会话对象只回复一次
+    replied: HashSet<ArcString>,
+    /// 免打扰期间暂存的消息，至多 [`MAX_QUEUED`] 条
+    queued: VecDeque<Message>,
+}
+
+impl Presence {
+    /// 设置（或清除）离开状态，并重新开始自动回复
+    pub fn set_away(&mut self, text: Option<String>) {
+        self.away = text;
+        self.replied.clear();
+    }
+
+    /// 开启或关闭免打扰
+    ///
+    /// # 返回值
+    /// 关闭免打扰时返回期间暂存的消息，由调用者补发
+    pub fn set_dnd(&mut self, dnd: bool) -> Vec<Message> {
+        self.dnd = dnd;
+        self.replied.clear();
+        if dnd {
+            Vec::new()
+        } else {
+            self.take_queued()
+        }
+    }
+
+    /// 取出全部暂存的消息（连接结束时由调用者放入死信队列）
+    pub fn take_queued(&mut self) -> Vec<Message> {
+        mem::take(&mut self.queued).into()
+    }
+
+    /// 免打扰期间暂存一条消息
+    ///
+    /// # 返回值
+    /// 暂存的消息已达 [`MAX_QUEUED`] 条时返回被挤出的最早一条，由调用者放入死信队列
+    pub fn queue(&mut self, msg: Message) -> Option<Message> {
+        self.queued.push_back(msg);
+        if self.queued.len() > MAX_QUEUED {
+            self.queued.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// 若需要向 `sender` 自动回复，返回回复内容；同一发送者只回复一次
+    ///
+    /// 免打扰状态的默认回复使用发送者的语言 `lang`
+    pub fn auto_reply(&mut self, sender: &ArcString, lang: Lang) -> Option<String> {
+        let text = match &self.away {
+            Some(text) => text.clone(),
+            None if self.dnd => tr!(lang => "server.dnd_reply"),
+            None => return None,
+        };
+        self.replied.insert(sender.clone()).then_some(text)
+    }
+}
+
+/// 连接结束的方式
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Departure {
+    /// 连接中断：客户端未发送 Goodbye 帧即断开，或读写出错、超时
+    #[default]
+    Dropped,
+    /// 客户端通过 Goodbye 帧主动退出，可附带告别语
+    Left(Option<String>),
+}
+
+/// 连接级会话状态
+#[derive(Debug)]
+pub struct Session {
+    /// 用户名
+    pub username: ArcString,
+    /// 对端地址
+    pub peer_addr: SocketAddr,
+    /// 连接建立时间
+    pub connected_at: Timestamp,
+    /// 用户角色
+    pub role: Role,
+    /// 客户端软件及版本（旧客户端为空字符串）
+    pub client: String,
+    /// 握手时协商出的特性
+    pub features: Vec<String>,
+    /// 共享的统计信息
+    pub stats: Arc<SessionStats>,
+    /// 共享的离开/免打扰状态
+    pub presence: Arc<Mutex<Presence>>,
+    /// 由其他任务通知以断开该连接（例如处理过慢的接收方）
+    pub closing: Arc<Notify>,
+    /// 本次连接中与该用户互发过消息的用户，连接异常中断时通知他们
+    pub partners: Arc<Mutex<HashSet<ArcString>>>,
+    /// 发给该用户的提示使用的语言，默认为服务器进程的语言
+    pub lang: Lang,
+    /// 连接结束的方式，收到 Goodbye 帧时更新
+    pub departure: Departure,
+    /// 注册完成之前已收到的帧（例如客户端在应答工作量证明挑战前发出的消息），接收循环优先处理
+    pub early: VecDeque<Frame>,
+    /// 对端 IP 的 GeoIP 信息，接受连接时查询
+    pub geo: Option<GeoInfo>,
+}
+
+impl Session {
+    /// 创建新的会话
+    ///
+    /// # 参数
+    /// - `username`: 用户名
+    /// - `peer_addr`: 对端地址
+    /// - `client`: 客户端软件及版本
+    /// - `features`: 握手时协商出的特性
+    pub fn new(
+        username: ArcString,
+        peer_addr: SocketAddr,
+        client: String,
+        features: Vec<String>,
+    ) -> Self {
+        Self {
+            username,
+            peer_addr,
+            connected_at: Timestamp::now(),
+            role: Role::default(),
+            client,
+            features,
+            stats: Arc::new(SessionStats::default()),
+            presence: Arc::new(Mutex::new(Presence::default())),
+            closing: Arc::new(Notify::new()),
+            partners: Arc::new(Mutex::new(HashSet::new())),
+            lang: i18n::lang(),
+            departure: Departure::default(),
+            early: VecDeque::new(),
+            geo: None,
+        }
+    }
+
+    /// 记录一次客户端活动
+    pub fn touch(&self) {
+        self.stats.touch();
+    }
+
+    /// 距离最近一次客户端活动经过的时间
+    pub fn idle_for(&self) -> Duration {
+        self.stats.idle_for()
+    }
+
+    /// 会话是否协商了指定特性
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
@@ -0,0 +1,466 @@
+/*!
+# DNS SRV 服务发现
+
+客户端的服务器地址只写域名（不带端口，如 `example.com`）时，先查询 `_chat._tcp.example.com` 的
+SRV 记录（RFC 2782），按记录给出的主机与端口连接；运维人员迁移服务器时只需修改 DNS 记录，
+用户无需更换地址。
+
+- 记录按优先级从小到大尝试，同一优先级内按权重随机排序（[`order`]），前一个连接失败时尝试下一个；
+- 唯一的记录以 `.` 为目标时表示该域名明确不提供服务；
+- 没有 SRV 记录（或查询失败）时退回 `域名:7891`。
+
+查询直接向 `/etc/resolv.conf` 中的域名服务器发送 UDP 报文，响应被截断时改用 TCP 重新查询；
+不读取 `/etc/resolv.conf` 的系统（Windows）上不查询 SRV 记录，直接使用默认端口。
+*/
+
+use crate::tr;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// 聊天服务的 SRV 服务名与协议
+pub const SERVICE: &str = "_chat._tcp";
+
+/// 等待每个域名服务器响应的时间
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// DNS 记录类型：SRV
+const TYPE_SRV: u16 = 33;
+
+/// DNS 记录类别：IN
+const CLASS_IN: u16 = 1;
+
+/// 解析域名时最多跟随的压缩指针数，防止恶意报文造成死循环
+const MAX_POINTERS: usize = 32;
+
+/// 一条 SRV 记录
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrvRecord {
+    /// 优先级，越小越先尝试
+    pub priority: u16,
+    /// 同一优先级内的权重，越大越可能先被尝试
+    pub weight: u16,
+    /// 端口
+    pub port: u16,
+    /// 目标主机名（不含末尾的 `.`）
+    pub target: String,
+}
+
+impl SrvRecord {
+    /// `主机:端口` 形式的地址
+    pub fn addr(&self) -> String {
+        match self.target.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, self.port),
+            _ => format!("{}:{}", self.target, self.port),
+        }
+    }
+}
+
+/// 查询 `domain` 的聊天服务 SRV 记录，按 [`order`] 排好尝试顺序
+///
+/// # 返回值
+/// 域名不存在或没有 SRV 记录时返回空列表；记录表明不提供服务（目标为 `.`）时返回 `None`
+pub async fn lookup(domain: &str) -> io::Result<Option<Vec<SrvRecord>>> {
+    let name = format!("{}.{}", SERVICE, domain.trim_end_matches('.'));
+    let query = encode_query(&name)?;
+    let mut last_error = None;
+    for server in nameservers()? {
+        match tokio::time::timeout(QUERY_TIMEOUT, query_server(server, &query)).await {
+            Ok(Ok(records)) => {
+                if let [record] = records.as_slice() {
+                    if record.target.is_empty() {
+                        return Ok(None);
+                    }
+                }
+                return Ok(Some(order(records)));
+            }
+            Ok(Err(e)) => last_error = Some(e),
+            Err(_) => last_error = Some(io::ErrorKind::TimedOut.into()),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::ErrorKind::NotFound.into()))
+}
+
+/// 按 RFC 2782 排列尝试顺序：优先级从小到大，同一优先级内按权重随机选取
+pub fn order(mut records: Vec<SrvRecord>) -> Vec<SrvRecord> {
+    records.sort_by_key(|record| record.priority);
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let end = records
+            .iter()
+            .position(|record| record.priority != priority)
+            .unwrap_or(records.len());
+        let mut group: Vec<SrvRecord> = records.drain(..end).collect();
+        // 权重为 0 的记录排在最前，使其仍有很小的机会被先选中
+        group.sort_by_key(|record| record.weight != 0);
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|record| u32::from(record.weight)).sum();
+            let mut pick = if total == 0 {
+                0
+            } else {
+                random() % (total + 1)
+            };
+            let index = group
+                .iter()
+                .position(|record| {
+                    let weight = u32::from(record.weight);
+                    if pick <= weight {
+                        true
+                    } else {
+                        pick -= weight;
+                        false
+                    }
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+fn random() -> u32 {
+    let mut bytes = [0u8; 4];
+    let _ = getrandom::getrandom(&mut bytes);
+    u32::from_le_bytes(bytes)
+}
+
+/// `/etc/resolv.conf` 中配置的域名服务器，未配置时使用本机
+#[cfg(unix)]
+fn nameservers() -> io::Result<Vec<SocketAddr>> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf")?;
+    let mut servers: Vec<SocketAddr> = conf
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|ip| {
+            // 去掉链路本地地址的区域标识，如 `fe80::1%eth0`
+            let ip = ip.trim().split('%').next()?;
+            ip.parse::<IpAddr>().ok()
+        })
+        .map(|ip| SocketAddr::new(ip, 53))
+        .collect();
+    if servers.is_empty() {
+        servers.push(SocketAddr::from(([127, 0, 0, 1], 53)));
+    }
+    Ok(servers)
+}
+
+#[cfg(not(unix))]
+fn nameservers() -> io::Result<Vec<SocketAddr>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        tr!("srv.unsupported"),
+    ))
+}
+
+/// 向 `server` 发送查询，响应被截断时改用 TCP
+async fn query_server(server: SocketAddr, query: &[u8]) -> io::Result<Vec<SrvRecord>> {
+    let bind: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(server).await?;
+    socket.send(query).await?;
+    let mut buf = vec![0u8; 1232];
+    let response = loop {
+        let len = socket.recv(&mut buf).await?;
+        // 忽略编号不符的报文（迟到的旧响应或伪造的响应）
+        if len >= 2 && buf[..2] == query[..2] {
+            break &buf[..len];
+        }
+    };
+    match parse_response(response)? {
+        Some(records) => Ok(records),
+        None => {
+            let mut stream = TcpStream::connect(server).await?;
+            stream.write_u16(query.len() as u16).await?;
+            stream.write_all(query).await?;
+            let len = stream.read_u16().await? as usize;
+            let mut response = vec![0u8; len];
+            stream.read_exact(&mut response).await?;
+            parse_response(&response)?.ok_or_else(|| io::ErrorKind::InvalidData.into())
+        }
+    }
+}
+
+/// 构造查询 `name` 的 SRV 记录的报文
+fn encode_query(name: &str) -> io::Result<Vec<u8>> {
+    let mut id = [0u8; 2];
+    let _ = getrandom::getrandom(&mut id);
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id);
+    // 标志：期望递归查询；问题数 1，其余为 0
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        if label.len() > 63 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!("srv.invalid_name", name = name),
+            ));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// 解析响应中的 SRV 记录
+///
+/// # 返回值
+/// 响应被截断时返回 `None`；域名不存在时返回空列表
+fn parse_response(msg: &[u8]) -> io::Result<Option<Vec<SrvRecord>>> {
+    let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+    if msg.len() < 12 || msg[2] & 0x80 == 0 {
+        return Err(invalid());
+    }
+    if msg[2] & 0x02 != 0 {
+        return Ok(None);
+    }
+    match msg[3] & 0x0f {
+        0 => {}
+        // NXDOMAIN：没有这个服务
+        3 => return Ok(Some(Vec::new())),
+        code => {
+            return Err(io::Error::other(tr!("srv.server_error", code = code)));
+        }
+    }
+    let questions = u16::from_be_bytes([msg[4], msg[5]]);
+    let answers = u16::from_be_bytes([msg[6], msg[7]]);
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(msg, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        pos = read_name(msg, pos)?.1;
+        let header = msg.get(pos..pos + 10).ok_or_else(invalid)?;
+        let kind = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let data = pos + 10;
+        pos = data + len;
+        if pos > msg.len() {
+            return Err(invalid());
+        }
+        // 跳过 CNAME 等其他类型的记录
+        if kind != TYPE_SRV || len < 7 {
+            continue;
+        }
+        let field = |at: usize| u16::from_be_bytes([msg[data + at], msg[data + at + 1]]);
+        records.push(SrvRecord {
+            priority: field(0),
+            weight: field(2),
+            port: field(4),
+            target: read_name(msg, data + 6)?.0,
+        });
+    }
+    Ok(Some(records))
+}
+
+/// 从 `pos` 读取一个（可能经过压缩的）域名，返回域名及其后的位置
+fn read_name(msg: &[u8], mut pos: usize) -> io::Result<(String, usize)> {
+    let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = *msg.get(pos).ok_or_else(invalid)? as usize;
+        match len {
+            0 => {
+                end.get_or_insert(pos + 1);
+                break;
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let low = *msg.get(pos + 1).ok_or_else(invalid)? as usize;
+                end.get_or_insert(pos + 2);
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return Err(invalid());
+                }
+                pos = ((len & 0x3f) << 8) | low;
+            }
+            len => {
+                let label = msg.get(pos + 1..pos + 1 + len).ok_or_else(invalid)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len;
+            }
+        }
+    }
+    Ok((labels.join("."), end.unwrap_or(pos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 查询 `_chat._tcp.example.org` 的问题部分（从偏移 12 开始）
+    fn question() -> Vec<u8> {
+        let mut question = encode_query("_chat._tcp.example.org").unwrap();
+        question.drain(..12);
+        question
+    }
+
+    /// 以 `answers` 中的 (优先级, 权重, 端口, 目标) 构造一个响应；答案的名称压缩指向问题
+    fn response(answers: &[(u16, u16, u16, &str)]) -> Vec<u8> {
+        let mut msg = vec![0x12, 0x34, 0x81, 0x80, 0, 1];
+        msg.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend(question());
+        for (priority, weight, port, target) in answers {
+            let mut data = Vec::new();
+            data.extend_from_slice(&priority.to_be_bytes());
+            data.extend_from_slice(&weight.to_be_bytes());
+            data.extend_from_slice(&port.to_be_bytes());
+            for label in target.split('.').filter(|label| !label.is_empty()) {
+                data.push(label.len() as u8);
+                data.extend_from_slice(label.as_bytes());
+            }
+            data.push(0);
+            msg.extend_from_slice(&[0xc0, 12]);
+            msg.extend_from_slice(&TYPE_SRV.to_be_bytes());
+            msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+            msg.extend_from_slice(&300u32.to_be_bytes());
+            msg.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            msg.extend(data);
+        }
+        msg
+    }
+
+    fn record(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_srv_answers() {
+        let msg = response(&[
+            (10, 60, 7891, "chat1.example.org"),
+            (20, 0, 7892, "chat2.example.org"),
+        ]);
+        assert_eq!(
+            parse_response(&msg).unwrap(),
+            Some(vec![
+                record(10, 60, 7891, "chat1.example.org"),
+                record(20, 0, 7892, "chat2.example.org"),
+            ])
+        );
+    }
+
+    #[test]
+    fn no_service_target_is_empty() {
+        let msg = response(&[(0, 0, 0, ".")]);
+        assert_eq!(
+            parse_response(&msg).unwrap(),
+            Some(vec![record(0, 0, 0, "")])
+        );
+    }
+
+    #[test]
+    fn nxdomain_and_truncation() {
+        let mut msg = response(&[]);
+        msg[3] |= 3;
+        assert_eq!(parse_response(&msg).unwrap(), Some(Vec::new()));
+
+        let mut msg = response(&[(10, 0, 7891, "chat.example.org")]);
+        msg[2] |= 0x02;
+        assert_eq!(parse_response(&msg).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_truncated_packets() {
+        let msg = response(&[(10, 0, 7891, "chat.example.org")]);
+        for len in 0..msg.len() {
+            assert!(parse_response(&msg[..len]).is_err(), "length {}", len);
+        }
+        // 不是响应
+        let mut query = msg.clone();
+        query[2] &= 0x7f;
+        assert!(parse_response(&query).is_err());
+    }
+
+    #[test]
+    fn rejects_pointer_loops() {
+        // 指向自身的指针
+        let msg = [0xc0, 0x00];
+        assert!(read_name(&msg, 0).is_err());
+        // 两个指针互相指向
+        let msg = [0xc0, 0x02, 0xc0, 0x00];
+        assert!(read_name(&msg, 0).is_err());
+
+        let mut msg = response(&[(10, 0, 7891, "chat.example.org")]);
+        let answer = 12 + question().len();
+        msg[answer + 1] = answer as u8;
+        assert!(parse_response(&msg).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_offsets() {
+        let msg = [3, b'f', b'o', b'o', 0xc0, 0x40];
+        assert!(read_name(&msg, 0).is_err());
+        assert!(read_name(&msg, 6).is_err());
+        // 标签长度超出报文
+        let msg = [10, b'f', b'o', b'o'];
+        assert!(read_name(&msg, 0).is_err());
+
+        let mut msg = response(&[(10, 0, 7891, "chat.example.org")]);
+        let rdlength = 12 + question().len() + 10;
+        msg[rdlength..rdlength + 2].copy_from_slice(&0xffffu16.to_be_bytes());
+        assert!(parse_response(&msg).is_err());
+    }
+
+    #[test]
+    fn follows_compressed_names() {
+        // "chat" 后接指向 "example.org" 的指针
+        let mut msg = vec![7];
+        msg.extend_from_slice(b"example");
+        msg.push(3);
+        msg.extend_from_slice(b"org");
+        msg.push(0);
+        let start = msg.len();
+        msg.push(4);
+        msg.extend_from_slice(b"chat");
+        msg.extend_from_slice(&[0xc0, 0]);
+        assert_eq!(
+            read_name(&msg, start).unwrap(),
+            ("chat.example.org".to_string(), msg.len())
+        );
+    }
+
+    #[test]
+    fn orders_by_priority_then_weight() {
+        let records = vec![
+            record(20, 10, 1, "c"),
+            record(10, 0, 2, "b"),
+            record(30, 5, 3, "d"),
+            record(10, 50, 4, "a"),
+            record(20, 0, 5, "e"),
+        ];
+        for _ in 0..100 {
+            let ordered = order(records.clone());
+            assert_eq!(ordered.len(), records.len());
+            let priorities: Vec<u16> = ordered.iter().map(|record| record.priority).collect();
+            assert_eq!(priorities, [10, 10, 20, 20, 30]);
+            for record in &records {
+                assert!(ordered.contains(record));
+            }
+        }
+        // 同一优先级内权重都为 0 时保持原有顺序
+        let zeros = vec![record(10, 0, 1, "a"), record(10, 0, 2, "b")];
+        assert_eq!(order(zeros.clone()), zeros);
+        // 权重为 0 的记录只在总权重的随机数恰好为 0 时排在最前
+        let weighted = vec![record(10, 0, 1, "zero"), record(10, 65535, 2, "heavy")];
+        let heavy_first = (0..100)
+            .filter(|_| order(weighted.clone())[0].target == "heavy")
+            .count();
+        assert!(heavy_first > 90);
+    }
+}
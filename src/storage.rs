@@ -0,0 +1,742 @@
+/*!
+# 服务器存储模块
+
+服务器需要跨重启保留的数据通过 [`Storage`] 特征读写，目前包括每个用户的
+资料、最后在线时间、机器人账号与以该用户为目标的 Webhook 的令牌摘要、加入的聊天室（[`UserRecord`]），
+聊天室的设置（[`RoomRecord`]），以及发给已知用户与聊天室的消息日志（用于断线重连后补发）。
+消息日志按写入日期建有索引，较早的消息可以整段取出（[`Storage::segment_before`]）归档到别处后
+从日志中删除（[`Storage::remove_through`]），删除后消息编号仍继续递增。
+定期维护时可以整理日志（[`Storage::vacuum`]），或删除重连时不会再补发的较早消息（[`Storage::prune`]）。
+每个接收者被删除（归档、清理或超出容量）的最大消息编号记录在 [`Storage::dropped_through`] 中，
+重连时据此判断能否补全客户端错过的消息。
+提供两种实现：
+
+- **MemoryStorage**
+  仅保存在内存中，服务器退出后丢失（默认）。每个接收者的消息保存在容量固定的环形缓冲区中，
+  写满后丢弃最早的消息。
+
+- **JsonFileStorage**（需启用 `storage` 特性）
+  用户数据与聊天室的设置分别保存在数据目录下的 `users.json` 与 `rooms.json` 中，每次修改后原子地整体重写；
+  消息日志逐条追加到 `messages.jsonl`，配置了存储密钥时逐行加密（见 [`encryption`]）；
+  日期索引、已归档的最大编号与各接收者已删除的最大编号保存在 `history.json` 中。
+
+数据目录可以整体导出为快照并在另一台主机上恢复（见 [`snapshot`]）。
+*/
+
+/// 声明 encryption 模块
+pub mod encryption;
+/// 声明 snapshot 模块
+#[cfg(feature = "storage")]
+pub mod snapshot;
+
+use crate::profile::Profile;
+use crate::server::{BotRecord, WebhookRecord};
+use crate::Message;
+use chrono::{DateTime, Local, NaiveDate};
+use encryption::StorageKeys;
+use serde::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+#[cfg(feature = "storage")]
+use {
+    crate::{read_json, tr, write_json},
+    encryption::Sealed,
+    std::fs::{self, OpenOptions},
+    std::io::Write,
+    std::path::{Path, PathBuf},
+};
+
+/// 单个用户需要持久化的数据
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UserRecord {
+    /// 用户资料
+    #[serde(default)]
+    pub profile: Profile,
+    /// 最近一次断开连接的时间
+    #[serde(default)]
+    pub last_seen: Option<DateTime<Local>>,
+    /// 最近一次断开连接是否为主动退出
+    #[serde(default)]
+    pub logged_out: bool,
+    /// 机器人账号的令牌摘要与权限范围，普通用户为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot: Option<BotRecord>,
+    /// 以该用户为目标的传入 Webhook
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookRecord>,
+    /// 是否加入了与 Telegram 群组桥接的聊天室
+    #[serde(default)]
+    pub telegram: bool,
+    /// 订阅了该用户在线状态的 Jabber 联系人（不含资源的 JID）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub xmpp_subscribers: Vec<String>,
+    /// 注册时证明过的身份公钥（十六进制）；此后只有证明持有对应私钥的连接才被视为该用户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_key: Option<String>,
+    /// 加入的聊天室 → 上次离线时消息日志中最大的消息编号，重新登录时补发此后的聊天室消息
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rooms: BTreeMap<String, u64>,
+}
+
+/// 单个聊天室需要持久化的设置；成员关系保存在各成员的 [`UserRecord::rooms`] 中
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RoomRecord {
+    /// 创建聊天室的用户，可以修改聊天室的设置
+    pub owner: String,
+    /// 创建时间
+    #[serde(default)]
+    pub created_at: Option<DateTime<Local>>,
+    /// 成员重新登录时最多补发的最近消息条数（提到该成员的消息不受此限），`None` 时使用默认值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<usize>,
+    /// 私有聊天室只能凭邀请码加入
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// 服务器持久化存储
+pub trait Storage: Send + Sync + fmt::Debug {
+    /// 读取所有已知用户
+    fn users(&self) -> Vec<(String, UserRecord)>;
+
+    /// 保存（或覆盖）一个用户的数据
+    fn save_user(&self, name: &str, record: &UserRecord) -> io::Result<()>;
+
+    /// 读取所有聊天室的设置
+    fn rooms(&self) -> Vec<(String, RoomRecord)>;
+
+    /// 保存（或覆盖）一个聊天室的设置
+    fn save_room(&self, name: &str, record: &RoomRecord) -> io::Result<()>;
+
+    /// 追加一条已分配编号的消息到消息日志
+    fn append_message(&self, message: &Message) -> io::Result<()>;
+
+    /// 发给 `recipient` 且编号大于 `after` 的消息（按编号先后排列）
+    fn messages_after(&self, recipient: &str, after: u64) -> io::Result<Vec<Message>>;
+
+    /// 消息日志中最大的消息编号，日志为空时为 0
+    ///
+    /// 日志中的消息都已归档时为已归档的最大编号，保证新消息的编号不会重复
+    fn last_message_id(&self) -> u64;
+
+    /// 在 `day` 之前写入消息日志的消息中最早的至多 `limit` 条，没有这样的消息时返回 `None`
+    fn segment_before(&self, day: NaiveDate, limit: usize) -> io::Result<Option<LogSegment>>;
+
+    /// 从消息日志中删除编号不大于 `through` 的消息（已归档），返回删除的条数
+    fn remove_through(&self, through: u64) -> io::Result<usize>;
+
+    /// 整理消息日志：丢弃无法读取的行并整体重写
+    fn vacuum(&self) -> io::Result<Vacuumed>;
+
+    /// 每个接收者只保留最近的 `keep` 条消息，返回删除的条数
+    fn prune(&self, keep: usize) -> io::Result<usize>;
+
+    /// 发给 `recipient` 的消息中已从日志删除（归档、清理或超出容量）的最大编号，没有时为 0
+    ///
+    /// 重连时请求补发的起点小于该编号，说明有消息已无法补发
+    fn dropped_through(&self, recipient: &str) -> u64;
+}
+
+/// 整理消息日志的结果
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Vacuumed {
+    /// 丢弃的无法读取的行数
+    pub dropped: usize,
+    /// 日志文件减少的字节数
+    pub reclaimed: u64,
+}
+
+/// 每个接收者只保留 `messages` 中最近的 `keep` 条，删除的消息记入 `history`，返回删除的条数
+fn prune_messages(messages: &mut Vec<Message>, keep: usize, history: &mut HistoryIndex) -> usize {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut kept: Vec<bool> = messages
+        .iter()
+        .rev()
+        .map(|msg| {
+            let count = counts.entry(msg.to()).or_default();
+            *count += 1;
+            *count <= keep
+        })
+        .collect();
+    kept.reverse();
+    let count = messages.len();
+    let mut kept = kept.into_iter();
+    messages.retain(|msg| {
+        let retained = kept.next().unwrap_or(true);
+        if !retained {
+            history.dropped(msg);
+        }
+        retained
+    });
+    count - messages.len()
+}
+
+/// 删除 `messages` 中编号不大于 `through` 的消息并记入 `history`，返回删除的条数
+fn remove_messages(messages: &mut Vec<Message>, through: u64, history: &mut HistoryIndex) -> usize {
+    let count = messages.len();
+    messages.retain(|msg| {
+        let keep = msg.id().unwrap_or(0) > through;
+        if !keep {
+            history.dropped(msg);
+        }
+        keep
+    });
+    history.archived(through);
+    count - messages.len()
+}
+
+/// 消息日志中较早的一段，归档后即可从日志中删除
+#[derive(Debug)]
+pub struct LogSegment {
+    /// 段中第一条消息的编号
+    pub first_id: u64,
+    /// 段中最后一条消息的编号
+    pub last_id: u64,
+    /// 消息条数
+    pub count: usize,
+    /// 与消息日志格式相同的 JSON Lines（配置了存储密钥时逐行加密）
+    pub lines: Vec<u8>,
+}
+
+impl LogSegment {
+    /// 把按编号排列的消息编码为一段，`messages` 为空时返回 `None`
+    fn encode(messages: &[Message], keys: Option<&StorageKeys>) -> io::Result<Option<Self>> {
+        let (Some(first), Some(last)) = (messages.first(), messages.last()) else {
+            return Ok(None);
+        };
+        let mut lines = Vec::new();
+        for message in messages {
+            lines.extend(encode_line(message, keys)?);
+        }
+        Ok(Some(LogSegment {
+            first_id: first.id().unwrap_or(0),
+            last_id: last.id().unwrap_or(0),
+            count: messages.len(),
+            lines,
+        }))
+    }
+}
+
+/// 消息日志的写入日期索引
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct HistoryIndex {
+    /// 日期 → 当天写入日志的第一条消息的编号
+    #[serde(default)]
+    days: BTreeMap<NaiveDate, u64>,
+    /// 已归档并从日志中删除的最大消息编号
+    #[serde(default)]
+    archived_through: u64,
+    /// 接收者 → 已从日志中删除的发给该接收者的最大消息编号
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    dropped: BTreeMap<String, u64>,
+}
+
+impl HistoryIndex {
+    /// 记录今天写入了编号为 `id` 的消息，返回索引是否有变化
+    fn record(&mut self, id: u64) -> bool {
+        match self.days.entry(Local::now().date_naive()) {
+            Entry::Vacant(entry) => {
+                entry.insert(id);
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    /// 在 `day` 之前写入的消息的编号都小于返回值
+    fn bound(&self, day: NaiveDate) -> u64 {
+        self.days
+            .range(day..)
+            .next()
+            .map_or(u64::MAX, |(_, first)| *first)
+    }
+
+    /// `message` 已从日志中删除
+    fn dropped(&mut self, message: &Message) {
+        let through = self.dropped.entry(message.to().to_string()).or_default();
+        *through = (*through).max(message.id().unwrap_or(0));
+    }
+
+    /// 发给 `recipient` 的消息中已从日志中删除的最大编号
+    fn dropped_through(&self, recipient: &str) -> u64 {
+        self.dropped.get(recipient).copied().unwrap_or(0)
+    }
+
+    /// 编号不大于 `through` 的消息已归档：移除其中的消息已全部删除的日期
+    fn archived(&mut self, through: u64) {
+        self.archived_through = self.archived_through.max(through);
+        let firsts: Vec<u64> = self.days.values().copied().collect();
+        let mut next = firsts.iter().skip(1);
+        self.days
+            .retain(|_, _| next.next().is_none_or(|first| *first > through + 1));
+    }
+}
+
+/// 消息日志中在 `day` 之前写入的至多 `limit` 条消息
+fn before(
+    messages: &[Message],
+    history: &HistoryIndex,
+    day: NaiveDate,
+    limit: usize,
+) -> Vec<Message> {
+    let bound = history.bound(day);
+    messages
+        .iter()
+        .filter(|msg| msg.id().unwrap_or(0) < bound)
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+/// 内存存储中每个接收者默认保留的消息条数，与重连时最多补发的条数相同
+pub const DEFAULT_MEMORY_CAPACITY: usize = crate::server::MAX_REPLAY;
+
+/// 仅保存在内存中的存储
+///
+/// 每个接收者的消息保存在容量为 `capacity` 的环形缓冲区中，写满后丢弃最早的一条
+#[derive(Debug)]
+pub struct MemoryStorage {
+    users: Mutex<BTreeMap<String, UserRecord>>,
+    rooms: Mutex<BTreeMap<String, RoomRecord>>,
+    log: Mutex<MemoryLog>,
+    history: Mutex<HistoryIndex>,
+    capacity: usize,
+}
+
+/// 内存中的消息日志
+#[derive(Debug, Default)]
+struct MemoryLog {
+    /// 接收者 → 发给该接收者的消息（按编号先后排列）
+    mailboxes: HashMap<String, VecDeque<Message>>,
+    /// 写入过的最大消息编号
+    last_id: u64,
+}
+
+impl MemoryLog {
+    /// 所有接收者的消息，按编号先后排列
+    fn sorted(&self) -> Vec<Message> {
+        let mut messages: Vec<Message> = self.mailboxes.values().flatten().cloned().collect();
+        messages.sort_by_key(|msg| msg.id().unwrap_or(0));
+        messages
+    }
+
+    /// 以 `messages` 替换全部消息，保持各接收者内的先后顺序
+    fn replace(&mut self, messages: Vec<Message>) {
+        self.mailboxes.clear();
+        for message in messages {
+            self.mailboxes
+                .entry(message.to().to_string())
+                .or_default()
+                .push_back(message);
+        }
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_MEMORY_CAPACITY)
+    }
+}
+
+impl MemoryStorage {
+    /// 创建空的内存存储，每个接收者保留最近 [`DEFAULT_MEMORY_CAPACITY`] 条消息
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建空的内存存储，每个接收者保留最近 `capacity` 条消息（至少 1 条）
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            users: Mutex::default(),
+            rooms: Mutex::default(),
+            log: Mutex::default(),
+            history: Mutex::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// 每个接收者保留的消息条数
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn users(&self) -> Vec<(String, UserRecord)> {
+        let users = self.users.lock().unwrap();
+        users.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    fn save_user(&self, name: &str, record: &UserRecord) -> io::Result<()> {
+        self.users
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), record.clone());
+        Ok(())
+    }
+
+    fn rooms(&self) -> Vec<(String, RoomRecord)> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    fn save_room(&self, name: &str, record: &RoomRecord) -> io::Result<()> {
+        self.rooms
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), record.clone());
+        Ok(())
+    }
+
+    fn append_message(&self, message: &Message) -> io::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        log.last_id = log.last_id.max(message.id().unwrap_or(0));
+        let mailbox = log.mailboxes.entry(message.to().to_string()).or_default();
+        mailbox.push_back(message.clone());
+        let evicted = if mailbox.len() > self.capacity {
+            mailbox.pop_front()
+        } else {
+            None
+        };
+        let mut history = self.history.lock().unwrap();
+        if let Some(evicted) = evicted {
+            history.dropped(&evicted);
+        }
+        if let Some(id) = message.id() {
+            history.record(id);
+        }
+        Ok(())
+    }
+
+    fn messages_after(&self, recipient: &str, after: u64) -> io::Result<Vec<Message>> {
+        let log = self.log.lock().unwrap();
+        Ok(log
+            .mailboxes
+            .get(recipient)
+            .map(|mailbox| {
+                mailbox
+                    .iter()
+                    .filter(|msg| msg.id().unwrap_or(0) > after)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn last_message_id(&self) -> u64 {
+        let log = self.log.lock().unwrap();
+        let archived = self.history.lock().unwrap().archived_through;
+        log.last_id.max(archived)
+    }
+
+    fn segment_before(&self, day: NaiveDate, limit: usize) -> io::Result<Option<LogSegment>> {
+        let messages = self.log.lock().unwrap().sorted();
+        let history = self.history.lock().unwrap();
+        LogSegment::encode(&before(&messages, &history, day, limit), None)
+    }
+
+    fn remove_through(&self, through: u64) -> io::Result<usize> {
+        let mut log = self.log.lock().unwrap();
+        let mut messages = log.sorted();
+        let removed = remove_messages(&mut messages, through, &mut self.history.lock().unwrap());
+        log.replace(messages);
+        Ok(removed)
+    }
+
+    fn vacuum(&self) -> io::Result<Vacuumed> {
+        // 内存中不会有无法读取的消息
+        Ok(Vacuumed::default())
+    }
+
+    fn prune(&self, keep: usize) -> io::Result<usize> {
+        let mut log = self.log.lock().unwrap();
+        let mut messages = log.sorted();
+        let pruned = prune_messages(&mut messages, keep, &mut self.history.lock().unwrap());
+        log.replace(messages);
+        Ok(pruned)
+    }
+
+    fn dropped_through(&self, recipient: &str) -> u64 {
+        self.history.lock().unwrap().dropped_through(recipient)
+    }
+}
+
+/// 保存在数据目录下 JSON 文件中的存储
+#[cfg(feature = "storage")]
+#[derive(Debug)]
+pub struct JsonFileStorage {
+    dir: PathBuf,
+    users: Mutex<BTreeMap<String, UserRecord>>,
+    rooms: Mutex<BTreeMap<String, RoomRecord>>,
+    /// 消息日志中最大的消息编号；同时用于串行化日志的追加写入
+    last_message_id: Mutex<u64>,
+    /// 消息日志的写入日期索引
+    history: Mutex<HistoryIndex>,
+    /// 加密消息日志的密钥，`None` 时以明文保存
+    keys: Option<StorageKeys>,
+    /// 打开时以当前密钥重新加密的消息条数
+    reencrypted: usize,
+}
+
+#[cfg(feature = "storage")]
+impl JsonFileStorage {
+    /// 打开（必要时创建）数据目录并载入已有数据；消息日志以明文保存
+    ///
+    /// 消息日志已加密时返回错误，须改用 [`JsonFileStorage::open_encrypted`]
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::open_with(dir.into(), None)
+    }
+
+    /// 打开数据目录，消息日志以 `keys` 中的当前密钥加密保存
+    ///
+    /// 日志中以其他密钥加密的行及启用加密前的明文行随即以当前密钥重新加密
+    pub fn open_encrypted(dir: impl Into<PathBuf>, keys: StorageKeys) -> io::Result<Self> {
+        Self::open_with(dir.into(), Some(keys))
+    }
+
+    fn open_with(dir: PathBuf, keys: Option<StorageKeys>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let users = read_json(&dir.join("users.json"))?.unwrap_or_default();
+        let rooms = read_json(&dir.join("rooms.json"))?.unwrap_or_default();
+        let path = dir.join("messages.jsonl");
+        let log = read_messages(&path, keys.as_ref())?;
+        let reencrypted = match &keys {
+            Some(keys) if log.outdated > 0 => {
+                rewrite_messages(&path, &log.messages, Some(keys))?;
+                log.outdated
+            }
+            _ => 0,
+        };
+        let history = match read_json::<HistoryIndex>(&dir.join("history.json"))? {
+            Some(history) => history,
+            None => {
+                // 建立索引之前写入的消息按今天写入计
+                let mut history = HistoryIndex::default();
+                if let Some(first) = log.messages.iter().filter_map(Message::id).min() {
+                    history.record(first);
+                    write_json(&dir.join("history.json"), &history)?;
+                }
+                history
+            }
+        };
+        let last_message_id = log
+            .messages
+            .iter()
+            .filter_map(Message::id)
+            .max()
+            .unwrap_or(0)
+            .max(history.archived_through);
+        Ok(Self {
+            dir,
+            users: Mutex::new(users),
+            rooms: Mutex::new(rooms),
+            last_message_id: Mutex::new(last_message_id),
+            history: Mutex::new(history),
+            keys,
+            reencrypted,
+        })
+    }
+
+    /// 打开时以当前密钥重新加密的消息条数（轮换密钥或首次启用加密后大于 0）
+    pub fn reencrypted(&self) -> usize {
+        self.reencrypted
+    }
+
+    /// 数据目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[cfg(feature = "storage")]
+impl Storage for JsonFileStorage {
+    fn users(&self) -> Vec<(String, UserRecord)> {
+        let users = self.users.lock().unwrap();
+        users.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    fn save_user(&self, name: &str, record: &UserRecord) -> io::Result<()> {
+        let mut users = self.users.lock().unwrap();
+        users.insert(name.to_string(), record.clone());
+        write_json(&self.dir.join("users.json"), &*users)
+    }
+
+    fn rooms(&self) -> Vec<(String, RoomRecord)> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    fn save_room(&self, name: &str, record: &RoomRecord) -> io::Result<()> {
+        let mut rooms = self.rooms.lock().unwrap();
+        rooms.insert(name.to_string(), record.clone());
+        write_json(&self.dir.join("rooms.json"), &*rooms)
+    }
+
+    fn append_message(&self, message: &Message) -> io::Result<()> {
+        let mut last_id = self.last_message_id.lock().unwrap();
+        let line = encode_line(message, self.keys.as_ref())?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join("messages.jsonl"))?
+            .write_all(&line)?;
+        *last_id = (*last_id).max(message.id().unwrap_or(0));
+        if let Some(id) = message.id() {
+            let mut history = self.history.lock().unwrap();
+            if history.record(id) {
+                write_json(&self.dir.join("history.json"), &*history)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn messages_after(&self, recipient: &str, after: u64) -> io::Result<Vec<Message>> {
+        let _guard = self.last_message_id.lock().unwrap();
+        let log = read_messages(&self.dir.join("messages.jsonl"), self.keys.as_ref())?;
+        Ok(log
+            .messages
+            .into_iter()
+            .filter(|msg| msg.to() == recipient && msg.id().unwrap_or(0) > after)
+            .collect())
+    }
+
+    fn last_message_id(&self) -> u64 {
+        *self.last_message_id.lock().unwrap()
+    }
+
+    fn segment_before(&self, day: NaiveDate, limit: usize) -> io::Result<Option<LogSegment>> {
+        let _guard = self.last_message_id.lock().unwrap();
+        let log = read_messages(&self.dir.join("messages.jsonl"), self.keys.as_ref())?;
+        let history = self.history.lock().unwrap();
+        LogSegment::encode(
+            &before(&log.messages, &history, day, limit),
+            self.keys.as_ref(),
+        )
+    }
+
+    fn remove_through(&self, through: u64) -> io::Result<usize> {
+        let _guard = self.last_message_id.lock().unwrap();
+        let path = self.dir.join("messages.jsonl");
+        let mut messages = read_messages(&path, self.keys.as_ref())?.messages;
+        let mut history = self.history.lock().unwrap();
+        let removed = remove_messages(&mut messages, through, &mut history);
+        rewrite_messages(&path, &messages, self.keys.as_ref())?;
+        write_json(&self.dir.join("history.json"), &*history)?;
+        Ok(removed)
+    }
+
+    fn vacuum(&self) -> io::Result<Vacuumed> {
+        let _guard = self.last_message_id.lock().unwrap();
+        let path = self.dir.join("messages.jsonl");
+        let size = |path: &Path| fs::metadata(path).map_or(0, |meta| meta.len());
+        let before = size(&path);
+        let log = read_messages(&path, self.keys.as_ref())?;
+        rewrite_messages(&path, &log.messages, self.keys.as_ref())?;
+        Ok(Vacuumed {
+            dropped: log.skipped,
+            reclaimed: before.saturating_sub(size(&path)),
+        })
+    }
+
+    fn prune(&self, keep: usize) -> io::Result<usize> {
+        let _guard = self.last_message_id.lock().unwrap();
+        let path = self.dir.join("messages.jsonl");
+        let mut messages = read_messages(&path, self.keys.as_ref())?.messages;
+        let mut history = self.history.lock().unwrap();
+        let pruned = prune_messages(&mut messages, keep, &mut history);
+        if pruned > 0 {
+            rewrite_messages(&path, &messages, self.keys.as_ref())?;
+            write_json(&self.dir.join("history.json"), &*history)?;
+        }
+        Ok(pruned)
+    }
+
+    fn dropped_through(&self, recipient: &str) -> u64 {
+        self.history.lock().unwrap().dropped_through(recipient)
+    }
+}
+
+/// 读出的消息日志
+#[cfg(feature = "storage")]
+struct MessageLog {
+    messages: Vec<Message>,
+    /// 需要以当前密钥重新加密的条数（明文或以旧密钥加密的行）
+    outdated: usize,
+    /// 无法解析而跳过的行数
+    skipped: usize,
+}
+
+/// 读取 JSON Lines 格式的消息日志，跳过无法解析的行；文件不存在时返回空列表
+///
+/// 给出 `keys` 时解密加密的行；未给出密钥却遇到加密的行，或行的密钥编号未知、无法解密时返回错误
+#[cfg(feature = "storage")]
+fn read_messages(path: &Path, keys: Option<&StorageKeys>) -> io::Result<MessageLog> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut log = MessageLog {
+        messages: Vec::new(),
+        outdated: 0,
+        skipped: 0,
+    };
+    for line in content.lines() {
+        let message = match (serde_json::from_str::<Sealed>(line), keys) {
+            (Ok(_), None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    tr!("storage.encrypted_log"),
+                ))
+            }
+            (Ok(sealed), Some(keys)) => {
+                if sealed.key != keys.current_id() {
+                    log.outdated += 1;
+                }
+                serde_json::from_slice(&keys.open(&sealed)?).ok()
+            }
+            (Err(_), keys) => {
+                let message = serde_json::from_str(line).ok();
+                if keys.is_some() && message.is_some() {
+                    log.outdated += 1;
+                }
+                message
+            }
+        };
+        match message {
+            Some(message) => log.messages.push(message),
+            None if !line.trim().is_empty() => log.skipped += 1,
+            None => {}
+        }
+    }
+    Ok(log)
+}
+
+/// 消息日志中的一行：给出 `keys` 时为加密后的 [`Sealed`]，否则为消息本身
+fn encode_line(message: &Message, keys: Option<&StorageKeys>) -> io::Result<Vec<u8>> {
+    let mut line = match keys {
+        Some(keys) => serde_json::to_vec(&keys.seal(&serde_json::to_vec(message)?)?)?,
+        None => serde_json::to_vec(message)?,
+    };
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// 以 `messages` 整体重写消息日志：先写入临时文件再重命名
+#[cfg(feature = "storage")]
+fn rewrite_messages(
+    path: &Path,
+    messages: &[Message],
+    keys: Option<&StorageKeys>,
+) -> io::Result<()> {
+    let mut content = Vec::new();
+    for message in messages {
+        content.extend(encode_line(message, keys)?);
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
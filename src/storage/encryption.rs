@@ -0,0 +1,145 @@
+/*!
+# 消息日志静态加密
+
+配置了存储密钥（[`StorageKeys`]）时，[`JsonFileStorage`](super::JsonFileStorage) 写入
+`messages.jsonl` 的每条消息先以 ChaCha20-Poly1305 整体加密（每行使用新的随机数），
+磁盘上只留下密钥编号、随机数与密文，数据库文件泄露时无法读出对话内容与收发双方。
+
+密钥由服务器持有，不写入数据目录，通过 [`StorageKeys::from_source`] 从环境变量
+（`env:变量名`）或外部命令（`cmd:命令`，例如从 KMS、Vault 取出密钥的脚本）读取；
+内容为以逗号或空白分隔的若干个 64 位十六进制密钥，第一个用于加密新消息，其余只用于解密。
+
+轮换密钥时把新密钥放在最前、旧密钥保留在后面并重启服务器：打开存储时用旧密钥加密的行
+（以及启用加密前的明文行）会以新密钥重新加密并原子地重写日志，之后即可移除旧密钥。
+*/
+
+use crate::secrets::{decode_hex, encode_hex};
+use crate::tr;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
+use std::io;
+use std::process::Command;
+
+/// 加密消息日志的密钥，第一个为当前密钥
+#[derive(Clone)]
+pub struct StorageKeys {
+    /// （密钥编号，密钥）
+    keys: Vec<(String, Key)>,
+}
+
+impl fmt::Debug for StorageKeys {
+    /// 只打印密钥编号
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.keys.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+impl StorageKeys {
+    /// 解析以逗号或空白分隔的十六进制密钥（每个 32 字节），第一个为当前密钥
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for hex in text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|hex| !hex.is_empty())
+        {
+            let bytes = decode_hex(hex)
+                .filter(|bytes| bytes.len() == 32)
+                .ok_or_else(|| tr!("storage.invalid_key"))?;
+            let id = encode_hex(&Sha256::digest(&bytes)[..4]);
+            keys.push((id, *Key::from_slice(&bytes)));
+        }
+        if keys.is_empty() {
+            return Err(tr!("storage.no_keys"));
+        }
+        Ok(Self { keys })
+    }
+
+    /// 从密钥来源读取密钥
+    ///
+    /// # 参数
+    /// - `source`: `env:变量名` 读取环境变量；`cmd:命令` 经系统 shell 执行命令并读取其标准输出
+    pub fn from_source(source: &str) -> Result<Self, String> {
+        if let Some(name) = source.strip_prefix("env:") {
+            let text = env::var(name).map_err(|_| tr!("storage.env_missing", name = name))?;
+            Self::parse(&text)
+        } else if let Some(command) = source.strip_prefix("cmd:") {
+            let output = if cfg!(windows) {
+                Command::new("cmd").args(["/C", command]).output()
+            } else {
+                Command::new("sh").args(["-c", command]).output()
+            }
+            .map_err(|e| tr!("storage.command_failed", error = e))?;
+            if !output.status.success() {
+                return Err(tr!("storage.command_failed", error = output.status));
+            }
+            Self::parse(&String::from_utf8_lossy(&output.stdout))
+        } else {
+            Err(tr!("storage.invalid_source", source = source))
+        }
+    }
+
+    /// 当前密钥的编号（密钥 SHA-256 的前 4 字节）
+    pub fn current_id(&self) -> &str {
+        &self.keys[0].0
+    }
+
+    /// 以当前密钥加密
+    pub(super) fn seal(&self, plaintext: &[u8]) -> io::Result<Sealed> {
+        let (id, key) = &self.keys[0];
+        let mut nonce = [0u8; 12];
+        getrandom::getrandom(&mut nonce).map_err(io::Error::from)?;
+        let data = ChaCha20Poly1305::new(key)
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| io::Error::other(tr!("secrets.encrypt_failed")))?;
+        Ok(Sealed {
+            key: id.clone(),
+            nonce: encode_hex(&nonce),
+            data: encode_hex(&data),
+        })
+    }
+
+    /// 解密一行密文
+    ///
+    /// # 返回值
+    /// 密钥编号未知或密文无法解密（密钥不符或内容被篡改）时返回错误
+    #[cfg(feature = "storage")]
+    pub(super) fn open(&self, sealed: &Sealed) -> io::Result<Vec<u8>> {
+        let Some((_, key)) = self.keys.iter().find(|(id, _)| *id == sealed.key) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!("storage.unknown_key", id = sealed.key),
+            ));
+        };
+        let corrupted = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!("storage.decrypt_failed", id = sealed.key),
+            )
+        };
+        let nonce = decode_hex(&sealed.nonce)
+            .filter(|nonce| nonce.len() == 12)
+            .ok_or_else(corrupted)?;
+        let data = decode_hex(&sealed.data).ok_or_else(corrupted)?;
+        ChaCha20Poly1305::new(key)
+            .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+            .map_err(|_| corrupted())
+    }
+}
+
+/// 消息日志中加密的一行
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Sealed {
+    /// 加密所用密钥的编号
+    pub key: String,
+    /// 随机数（十六进制）
+    pub nonce: String,
+    /// 密文（十六进制）
+    pub data: String,
+}
@@ -0,0 +1,197 @@
+/*!
+# 服务器数据快照
+
+把 [`JsonFileStorage`](super::JsonFileStorage) 的数据目录（以及可选的联邦暂存目录）整体导出为一个快照文件，
+在另一台主机上恢复，用于迁移服务器或从磁盘故障中恢复（`chat admin snapshot` / `chat admin restore`）：
+
+- 快照包含所有用户的数据（资料、最后在线时间、机器人账号、Webhook、聊天室与桥接的成员关系、订阅者）、
+  聊天室的设置、
+  消息日志（离线用户重连后补发的消息）及其日期索引，以及各联邦对端尚未转发的暂存消息；
+- 消息日志按原样保存：配置了存储密钥时快照中仍是密文，恢复后须使用相同的密钥启动服务器；
+- 快照为一个 JSON 文件，路径以 `.gz` 结尾时以 gzip 压缩；
+- 恢复时默认要求目标数据目录中没有已有数据，避免误覆盖。
+
+快照直接读取数据文件，最好在服务器停止时进行；服务器运行时导出的快照可能缺少正在写入的最后一条消息。
+*/
+
+use super::{HistoryIndex, RoomRecord, UserRecord};
+use crate::{read_json, tr, write_json};
+use chrono::{DateTime, Local};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// 当前的快照格式版本
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// 服务器数据快照
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    /// 快照格式版本
+    pub version: u32,
+    /// 生成快照的时间
+    pub created_at: DateTime<Local>,
+    /// 所有用户的数据
+    pub users: BTreeMap<String, UserRecord>,
+    /// 所有聊天室的设置
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rooms: BTreeMap<String, RoomRecord>,
+    /// 消息日志的各行（配置了存储密钥时为加密后的行）
+    pub messages: Vec<serde_json::Value>,
+    /// 消息日志的日期索引
+    #[serde(default)]
+    history: Option<HistoryIndex>,
+    /// 联邦暂存：暂存文件名 → 各行暂存的帧
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub spool: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+impl Snapshot {
+    /// 读取数据目录 `dir`（以及给出时的联邦暂存目录 `spool`）生成快照
+    pub fn capture(dir: &Path, spool: Option<&Path>) -> io::Result<Self> {
+        if !dir.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                tr!("storage.no_data_dir", dir = dir.display()),
+            ));
+        }
+        let mut spooled = BTreeMap::new();
+        if let Some(spool) = spool.filter(|spool| spool.is_dir()) {
+            for entry in fs::read_dir(spool)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "jsonl") {
+                    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                        spooled.insert(name.to_string(), read_lines(&path)?);
+                    }
+                }
+            }
+        }
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            created_at: Local::now(),
+            users: read_json(&dir.join("users.json"))?.unwrap_or_default(),
+            rooms: read_json(&dir.join("rooms.json"))?.unwrap_or_default(),
+            messages: read_lines(&dir.join("messages.jsonl"))?,
+            history: read_json(&dir.join("history.json"))?,
+            spool: spooled,
+        })
+    }
+
+    /// 把快照恢复到数据目录 `dir`（以及给出时的联邦暂存目录 `spool`）
+    ///
+    /// 目标目录中已有用户数据或消息日志时，除非 `force` 为真，否则返回错误
+    pub fn restore(&self, dir: &Path, spool: Option<&Path>, force: bool) -> io::Result<()> {
+        let existing = ["users.json", "messages.jsonl"]
+            .iter()
+            .any(|name| dir.join(name).exists());
+        if existing && !force {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                tr!("storage.data_dir_not_empty", dir = dir.display()),
+            ));
+        }
+        fs::create_dir_all(dir)?;
+        write_json(&dir.join("users.json"), &self.users)?;
+        write_json(&dir.join("rooms.json"), &self.rooms)?;
+        write_lines(&dir.join("messages.jsonl"), &self.messages)?;
+        match &self.history {
+            Some(history) => write_json(&dir.join("history.json"), history)?,
+            None => match fs::remove_file(dir.join("history.json")) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            },
+        }
+        if let Some(spool) = spool {
+            fs::create_dir_all(spool)?;
+            for (name, frames) in &self.spool {
+                // 文件名来自快照，只接受暂存文件本身的名称
+                let Some(name) = Path::new(name).file_name() else {
+                    continue;
+                };
+                write_lines(&spool.join(name), frames)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 联邦暂存中的帧数
+    pub fn spooled(&self) -> usize {
+        self.spool.values().map(Vec::len).sum()
+    }
+
+    /// 写入快照文件，路径以 `.gz` 结尾时压缩
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        let file = BufWriter::new(File::create(&tmp)?);
+        if is_gzip(path) {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            serde_json::to_writer(&mut encoder, self)?;
+            encoder.finish()?.flush()?;
+        } else {
+            let mut file = file;
+            serde_json::to_writer(&mut file, self)?;
+            file.flush()?;
+        }
+        fs::rename(&tmp, path)
+    }
+
+    /// 读取快照文件，拒绝更新版本的快照
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let reader: Box<dyn Read> = if is_gzip(path) {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!(
+                    "storage.snapshot_version",
+                    version = snapshot.version,
+                    supported = SNAPSHOT_VERSION
+                ),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// 读取 JSON Lines 文件的各行，跳过无法解析的行；文件不存在时返回空列表
+fn read_lines(path: &Path) -> io::Result<Vec<serde_json::Value>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(value) = serde_json::from_str(&line?) {
+            lines.push(value);
+        }
+    }
+    Ok(lines)
+}
+
+/// 以 JSON Lines 格式写入 `lines`：先写入临时文件再重命名
+fn write_lines(path: &Path, lines: &[serde_json::Value]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut file = BufWriter::new(File::create(&tmp)?);
+    for line in lines {
+        serde_json::to_writer(&mut file, line)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,490 @@
+/*!
+# 终端模块
+
+客户端对终端的操作都经过这里，由 crossterm 处理平台差异：支持 ANSI 转义序列的终端
+直接输出控制序列，旧版 Windows 控制台改用控制台 API。
+
+- [`Screen`] 是唯一写终端的渲染线程：接收任务打印的消息、输入循环显示的提示、
+  正在输入的内容都以绘制指令发给它，由它按顺序输出。打印消息时先清除输入行，
+  输出消息后再重画提示与已输入的内容，收到的消息不会打乱或抹掉正在输入的文字。
+  [`Screen::status`] 设置的状态行（如文件传输的进度）显示在输入行上方，随输入行一起重画。
+- [`Input`] 在专用线程中读取输入，不占用运行时的工作线程。标准输入与标准输出都是终端时
+  以原始模式逐键编辑（退格、`Ctrl+U` 清空、`Ctrl+C`/`Ctrl+D` 结束输入，`Tab` 按
+  [`set_completions`] 设置的候选词补全光标前的词），并把输入行的变化交给渲染线程重画；否则按行读取。
+
+标准输出不是终端（重定向到文件或管道）时不输出任何光标控制，也不重画提示与状态行。
+未启用 `tui` 特性时不依赖 crossterm：始终按行读取输入、按行输出，不重画提示，也不输出颜色。
+*/
+
+#[cfg(feature = "tui")]
+use crossterm::{
+    cursor::{MoveToColumn, MoveUp},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    terminal::{self as term, Clear, ClearType},
+};
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::sync::mpsc as std_mpsc;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use tokio::sync::mpsc;
+
+/// 终端能否显示 ANSI 颜色
+///
+/// Windows 上会尝试为控制台开启虚拟终端处理，旧版控制台开启失败时返回 `false`；
+/// 其他平台上 `TERM=dumb` 时返回 `false`。未启用 `tui` 特性时始终返回 `false`。
+pub fn supports_color() -> bool {
+    #[cfg(all(feature = "tui", windows))]
+    {
+        crossterm::ansi_support::supports_ansi()
+    }
+    #[cfg(all(feature = "tui", not(windows)))]
+    {
+        std::env::var("TERM").map_or(true, |term| term != "dumb")
+    }
+    #[cfg(not(feature = "tui"))]
+    {
+        false
+    }
+}
+
+/// 直接显示提示（不换行）并立即刷新，用于客户端启动之前的交互
+pub fn prompt(text: impl Display) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    write!(stdout, "{}", text)?;
+    stdout.flush()
+}
+
+/// 直接读取一行输入，去掉行尾的换行（包括 Windows 控制台的 `\r\n`）
+///
+/// # 返回值
+/// 输入已结束（EOF）时返回 `None`
+pub fn read_line() -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(Some(line))
+}
+
+/// 写出尚未输出的内容并退出原始模式，应在 `process::exit` 之前调用
+pub fn restore() {
+    screen().flush();
+    disable_raw_mode();
+}
+
+/// 标准输入与标准输出都是终端时进入原始模式；未启用 `tui` 特性时不进入
+fn enable_raw_mode() -> bool {
+    #[cfg(feature = "tui")]
+    {
+        io::stdin().is_terminal() && io::stdout().is_terminal() && term::enable_raw_mode().is_ok()
+    }
+    #[cfg(not(feature = "tui"))]
+    {
+        false
+    }
+}
+
+fn disable_raw_mode() {
+    #[cfg(feature = "tui")]
+    let _ = term::disable_raw_mode();
+}
+
+/// 按 `Tab` 时补全的候选词
+static COMPLETIONS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// 设置按 `Tab` 时补全的候选词（如在线用户名），替换之前设置的候选词
+pub fn set_completions(words: Vec<String>) {
+    *COMPLETIONS.lock().unwrap() = words;
+}
+
+/// 补全 `buffer` 末尾的词：唯一匹配时补全整个词，多个匹配时补全到共同前缀
+///
+/// # 返回值
+/// 末尾没有词（为空或以空白结尾）或没有候选词时返回 `None`，否则返回是否有补全
+#[cfg(feature = "tui")]
+fn complete(buffer: &mut String) -> Option<bool> {
+    let start = buffer.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &buffer[start..];
+    let words = COMPLETIONS.lock().unwrap();
+    if word.is_empty() || words.is_empty() {
+        return None;
+    }
+    let mut matches = words.iter().filter(|candidate| candidate.starts_with(word));
+    let Some(first) = matches.next() else {
+        return Some(false);
+    };
+    let mut common = first.as_str();
+    for other in matches {
+        let len = common
+            .char_indices()
+            .zip(other.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(common.len().min(other.len()), |((i, _), _)| i);
+        common = &common[..len];
+    }
+    if common.len() == word.len() {
+        return Some(false);
+    }
+    let common = common.to_string();
+    buffer.truncate(start);
+    buffer.push_str(&common);
+    Some(true)
+}
+
+/// 发给渲染线程的绘制指令
+#[derive(Debug)]
+enum Draw {
+    /// 在输入行上方输出一段文字
+    Print {
+        text: String,
+        stderr: bool,
+    },
+    /// 开始等待输入，显示提示
+    Prompt(String),
+    /// 输入行的内容有变化
+    #[cfg(feature = "tui")]
+    Edit(String),
+    /// 输入行已提交；`newline` 表示需要由渲染线程换行（原始模式下终端不回显换行）
+    Submit {
+        newline: bool,
+    },
+    Bell,
+    /// 设置或清除输入行上方的状态行
+    Status(Option<String>),
+    /// 之前的指令都已输出
+    Sync(std_mpsc::Sender<()>),
+}
+
+/// 渲染线程的句柄，通过 [`screen`] 获取
+#[derive(Debug)]
+pub struct Screen {
+    tx: std_mpsc::Sender<Draw>,
+}
+
+/// 进程唯一的渲染线程，首次调用时启动
+pub fn screen() -> &'static Screen {
+    static SCREEN: OnceLock<Screen> = OnceLock::new();
+    SCREEN.get_or_init(|| {
+        let (tx, rx) = std_mpsc::channel();
+        thread::spawn(move || Renderer::new().run(rx));
+        Screen { tx }
+    })
+}
+
+impl Screen {
+    /// 在输入行上方输出一段文字（可以包含换行）
+    pub fn println(&self, text: impl Into<String>) {
+        self.draw(Draw::Print {
+            text: text.into(),
+            stderr: false,
+        });
+    }
+
+    /// 同 [`Screen::println`]，但输出到标准错误
+    pub fn eprintln(&self, text: impl Into<String>) {
+        self.draw(Draw::Print {
+            text: text.into(),
+            stderr: true,
+        });
+    }
+
+    /// 显示输入提示，之后打印的消息输出完毕都会重画该提示，直到这一行输入提交
+    pub fn prompt(&self, text: impl Display) {
+        self.draw(Draw::Prompt(text.to_string()));
+    }
+
+    /// 响铃提示
+    pub fn bell(&self) {
+        self.draw(Draw::Bell);
+    }
+
+    /// 在输入行上方显示一行状态（超出终端宽度的部分截断），`None` 清除；标准输出不是终端时忽略
+    pub fn status(&self, text: Option<String>) {
+        self.draw(Draw::Status(text));
+    }
+
+    /// 等待之前的输出全部写到终端
+    pub fn flush(&self) {
+        let (tx, rx) = std_mpsc::channel();
+        self.draw(Draw::Sync(tx));
+        let _ = rx.recv();
+    }
+
+    fn draw(&self, draw: Draw) {
+        // 渲染线程不会提前退出，发送失败时忽略即可
+        let _ = self.tx.send(draw);
+    }
+}
+
+/// 渲染线程的状态：当前显示的提示与已输入的内容
+struct Renderer {
+    tty: bool,
+    /// 正在等待输入时为提示文字
+    prompt: Option<String>,
+    buffer: String,
+    /// 输入行上方的状态行
+    status: Option<String>,
+    /// 状态行当前是否显示在输入行上方，清除输入行时一并清除
+    status_shown: bool,
+}
+
+impl Renderer {
+    fn new() -> Self {
+        Self {
+            tty: cfg!(feature = "tui") && io::stdout().is_terminal(),
+            prompt: None,
+            buffer: String::new(),
+            status: None,
+            status_shown: false,
+        }
+    }
+
+    fn run(mut self, rx: std_mpsc::Receiver<Draw>) {
+        for draw in rx {
+            let _ = self.apply(draw);
+        }
+    }
+
+    fn apply(&mut self, draw: Draw) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        match draw {
+            Draw::Print { text, stderr } if !self.tty => match stderr {
+                true => writeln!(io::stderr(), "{}", text)?,
+                false => writeln!(stdout, "{}", text)?,
+            },
+            Draw::Print { text, stderr } => {
+                self.clear(&mut stdout)?;
+                // 原始模式下 `\n` 不会回到行首
+                let text = text.replace('\n', "\r\n");
+                if stderr {
+                    stdout.flush()?;
+                    write!(io::stderr(), "{}\r\n", text)?;
+                } else {
+                    write!(stdout, "{}\r\n", text)?;
+                }
+                self.redraw(&mut stdout)?;
+            }
+            Draw::Prompt(prompt) => {
+                if self.tty {
+                    self.clear(&mut stdout)?;
+                }
+                self.prompt = Some(prompt);
+                self.redraw(&mut stdout)?;
+            }
+            #[cfg(feature = "tui")]
+            Draw::Edit(buffer) => {
+                self.buffer = buffer;
+                if self.tty {
+                    self.clear(&mut stdout)?;
+                    self.redraw(&mut stdout)?;
+                }
+            }
+            Draw::Submit { newline } if self.status_shown => {
+                // 提交的输入行移到状态行的位置保留下来，状态行在其下方重画
+                self.clear(&mut stdout)?;
+                if newline {
+                    if let Some(prompt) = &self.prompt {
+                        write!(stdout, "{}", prompt)?;
+                    }
+                    write!(stdout, "{}\r\n", self.buffer)?;
+                }
+                self.prompt = None;
+                self.buffer.clear();
+                self.redraw(&mut stdout)?;
+            }
+            Draw::Submit { newline } => {
+                self.prompt = None;
+                self.buffer.clear();
+                if newline {
+                    write!(stdout, "\r\n")?;
+                }
+            }
+            Draw::Bell => {
+                if self.tty {
+                    stdout.write_all(b"\x07")?;
+                }
+            }
+            Draw::Status(status) => {
+                if self.tty {
+                    self.clear(&mut stdout)?;
+                    self.status = status;
+                    self.redraw(&mut stdout)?;
+                }
+            }
+            Draw::Sync(done) => {
+                let _ = done.send(());
+            }
+        }
+        stdout.flush()
+    }
+
+    /// 回到行首并清除输入行，显示着状态行时一并清除，光标停在状态行的行首
+    #[cfg(feature = "tui")]
+    fn clear(&mut self, out: &mut impl Write) -> io::Result<()> {
+        queue!(out, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
+        if self.status_shown {
+            self.status_shown = false;
+            queue!(out, MoveUp(1), Clear(ClearType::CurrentLine))?;
+        }
+        Ok(())
+    }
+
+    /// 未启用 `tui` 特性时不重画输入行，无需清除
+    #[cfg(not(feature = "tui"))]
+    fn clear(&mut self, _out: &mut impl Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 重画状态行、提示与已输入的内容
+    fn redraw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if let (true, Some(status)) = (self.tty, &self.status) {
+            write!(out, "{}\r\n", fit_width(status))?;
+            self.status_shown = true;
+        }
+        match &self.prompt {
+            Some(prompt) => write!(out, "{}{}", prompt, self.buffer),
+            None => write!(out, "{}", self.buffer),
+        }
+    }
+}
+
+/// 把状态行截断到终端宽度以内（宽字符按两列计），避免折行后清除状态行时错位
+fn fit_width(text: &str) -> String {
+    #[cfg(feature = "tui")]
+    // 部分伪终端报告的宽度为 0，此时按 80 列处理
+    let columns = match term::size() {
+        Ok((columns, _)) if columns > 0 => usize::from(columns),
+        _ => 80,
+    };
+    #[cfg(not(feature = "tui"))]
+    let columns = 80;
+    let mut width = 0;
+    text.chars()
+        .take_while(|&c| {
+            width += if is_wide(c) { 2 } else { 1 };
+            width < columns
+        })
+        .collect()
+}
+
+/// 是否为在终端中占两列的宽字符（中日韩文字、全角符号与表情）
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{3FFFD}')
+}
+
+/// 在专用线程中读取输入的输入源
+///
+/// 标准输入只能阻塞读取，在异步任务中直接读取会占住运行时的工作线程，
+/// 使心跳、重连与接收消息得不到调度。`Input` 把读取放在独立线程中，
+/// 读到的行经通道交给异步任务。读取线程最多预读一行，输入结束或出错后退出。
+#[derive(Debug)]
+pub struct Input {
+    lines: mpsc::Receiver<io::Result<String>>,
+    raw: bool,
+}
+
+impl Input {
+    /// 启动读取线程；标准输入与标准输出都是终端时进入原始模式逐键编辑
+    pub fn spawn() -> Self {
+        let (tx, lines) = mpsc::channel(1);
+        let raw = enable_raw_mode();
+        thread::spawn(move || loop {
+            let line = if raw { edit_line() } else { read_line() };
+            screen().draw(Draw::Submit { newline: raw });
+            let line = match line {
+                Ok(Some(line)) => Ok(line),
+                Ok(None) => return,
+                Err(e) => Err(e),
+            };
+            let failed = line.is_err();
+            if tx.blocking_send(line).is_err() || failed {
+                return;
+            }
+        });
+        Self { lines, raw }
+    }
+
+    /// 等待下一行输入
+    ///
+    /// # 返回值
+    /// 输入已结束（EOF，或在原始模式下按下 `Ctrl+C`/`Ctrl+D`）时返回 `None`
+    pub async fn read_line(&mut self) -> io::Result<Option<String>> {
+        self.lines.recv().await.transpose()
+    }
+}
+
+impl Drop for Input {
+    /// 写出尚未输出的内容，并在进入过原始模式时恢复终端
+    fn drop(&mut self) {
+        screen().flush();
+        if self.raw {
+            disable_raw_mode();
+        }
+    }
+}
+
+/// 原始模式下读取一行：逐键维护输入内容并交给渲染线程显示
+#[cfg(feature = "tui")]
+fn edit_line() -> io::Result<Option<String>> {
+    let mut buffer = String::new();
+    loop {
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+        if kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            // 部分终端以 `Ctrl+J` 表示换行、以 `Ctrl+H` 表示退格
+            KeyCode::Enter => return Ok(Some(buffer)),
+            KeyCode::Char('j') if ctrl => return Ok(Some(buffer)),
+            KeyCode::Char('c') if ctrl => return Ok(None),
+            KeyCode::Char('d') if ctrl && buffer.is_empty() => return Ok(None),
+            KeyCode::Char('u') if ctrl => buffer.clear(),
+            KeyCode::Char('h') if ctrl => {
+                buffer.pop();
+            }
+            KeyCode::Char(_) if ctrl => continue,
+            KeyCode::Char(c) => buffer.push(c),
+            KeyCode::Tab => match complete(&mut buffer) {
+                Some(true) => {}
+                Some(false) => {
+                    screen().bell();
+                    continue;
+                }
+                None => buffer.push('\t'),
+            },
+            KeyCode::Backspace => {
+                buffer.pop();
+            }
+            _ => continue,
+        }
+        screen().draw(Draw::Edit(buffer.clone()));
+    }
+}
+
+/// 未启用 `tui` 特性时不会进入原始模式，按行读取
+#[cfg(not(feature = "tui"))]
+fn edit_line() -> io::Result<Option<String>> {
+    read_line()
+}
@@ -0,0 +1,188 @@
+/*!
+# 终端配色模块
+
+客户端输出的所有颜色都来自 [`Theme`]，而不是在各处直接调用 `colored`。
+配色可以从 JSON 文件载入（默认 `$XDG_CONFIG_HOME/async-chat/theme.json`，
+Windows 下为 `%APPDATA%\async-chat\theme.json`），文件中只需写出要修改的项，例如：
+
+```json
+{
+  "sender": { "fg": "green", "bold": true },
+  "highlight": { "fg": "white", "bg": "red", "bold": true }
+}
+```
+
+颜色名称与 `colored` 相同（`red`、`bright blue` 等），也支持 `#rrggbb`。
+
+[`init_color`] 决定是否输出 ANSI 转义序列：设置了 `NO_COLOR` 环境变量、
+标准输出不是终端（重定向到日志或管道）或使用 `--no-color` 时关闭颜色，
+`CLICOLOR_FORCE` 可以强制开启。
+*/
+
+use crate::terminal;
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+/// 一种文本样式
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ThemeStyle {
+    /// 前景色
+    pub fg: Option<String>,
+    /// 背景色
+    pub bg: Option<String>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl ThemeStyle {
+    fn new(fg: &str) -> Self {
+        Self {
+            fg: Some(fg.to_string()),
+            ..Self::default()
+        }
+    }
+
+    fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// 以该样式渲染文本；无法识别的颜色名称会被忽略
+    pub fn paint(&self, text: &str) -> ColoredString {
+        let mut out = ColoredString::from(text);
+        if let Some(fg) = self.fg.as_deref().and_then(parse_color) {
+            out = out.color(fg);
+        }
+        if let Some(bg) = self.bg.as_deref().and_then(parse_color) {
+            out = out.on_color(bg);
+        }
+        if self.bold {
+            out = out.bold();
+        }
+        if self.italic {
+            out = out.italic();
+        }
+        if self.underline {
+            out = out.underline();
+        }
+        out
+    }
+}
+
+/// 客户端配色
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    /// 消息发送者
+    pub sender: ThemeStyle,
+    /// 消息时间戳
+    pub timestamp: ThemeStyle,
+    /// 消息正文
+    pub content: ThemeStyle,
+    /// 动作消息（`/me`）
+    pub action: ThemeStyle,
+    /// 提示与警告等系统消息
+    pub system: ThemeStyle,
+    /// 操作成功的提示
+    pub success: ThemeStyle,
+    /// 错误信息
+    pub error: ThemeStyle,
+    /// "请输入接收方" 提示
+    pub prompt: ThemeStyle,
+    /// "请输入消息内容" 提示
+    pub content_prompt: ThemeStyle,
+    /// 命中提及或关键词的片段
+    pub highlight: ThemeStyle,
+    /// 行内代码与代码块
+    pub code: ThemeStyle,
+    /// 链接文字
+    pub link: ThemeStyle,
+    /// 辅助信息（链接地址、列表符号、静音标记等）
+    pub dim: ThemeStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            sender: ThemeStyle::new("cyan").bold(),
+            timestamp: ThemeStyle::new("bright black"),
+            content: ThemeStyle::new("yellow"),
+            action: ThemeStyle::new("magenta"),
+            system: ThemeStyle::new("yellow").bold(),
+            success: ThemeStyle::new("green").bold(),
+            error: ThemeStyle::new("red").bold(),
+            prompt: ThemeStyle::new("cyan").bold(),
+            content_prompt: ThemeStyle::new("magenta").bold(),
+            highlight: ThemeStyle {
+                bg: Some("yellow".to_string()),
+                ..ThemeStyle::new("black").bold()
+            },
+            code: ThemeStyle {
+                bg: Some("black".to_string()),
+                ..ThemeStyle::new("bright white")
+            },
+            link: ThemeStyle {
+                underline: true,
+                ..ThemeStyle::new("blue")
+            },
+            dim: ThemeStyle::new("bright black"),
+        }
+    }
+}
+
+impl Theme {
+    /// 从 JSON 文件载入配色，未写出的项使用默认值
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read(path)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// 载入默认路径下的配色文件；文件不存在时使用默认配色
+    pub fn load_default() -> io::Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// 默认配色文件路径
+    pub fn default_path() -> Option<PathBuf> {
+        crate::config_dir().map(|dir| dir.join("theme.json"))
+    }
+}
+
+/// 根据环境决定是否输出颜色，应在程序启动时调用一次
+///
+/// # 参数
+/// - `no_color`: 命令行要求关闭颜色（`--no-color`）
+pub fn init_color(no_color: bool) {
+    let env_set = |name: &str| env::var_os(name).is_some_and(|value| !value.is_empty());
+    let forced = env::var_os("CLICOLOR_FORCE").is_some_and(|value| value != "0");
+    // 输出不是终端，或终端无法显示 ANSI 颜色（如旧版 Windows 控制台）时不输出颜色
+    let capable = io::stdout().is_terminal() && terminal::supports_color();
+    if no_color || env_set("NO_COLOR") || (!forced && !capable) {
+        colored::control::set_override(false);
+    }
+}
+
+/// 解析颜色名称（`bright_black` 与 `bright black` 等价）或 `#rrggbb`
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::TrueColor {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    name.replace('_', " ").parse().ok()
+}
@@ -0,0 +1,1433 @@
+/*!
+# 文件传输
+
+客户端之间经服务器撮合传输文件：`/sendfile <用户> <路径>` 向对方提议发送文件，
+对方以 `/accept <编号>` 接受或 `/reject <编号>` 拒绝，收到的文件保存在下载目录中
+（默认为当前目录下的 [`DEFAULT_DOWNLOADS`]），与已有文件重名时在文件名后加上序号。
+
+大文件不宜全部经服务器中转，因此接受后双方先尝试直连（NAT 打洞）：
+
+1. 双方各自在本机出口地址的随机端口上监听（允许端口复用），经服务器把监听地址告诉对方；
+   服务器转发时在前面加上它观察到的该方公网地址，相当于一次 STUN 绑定请求；
+2. 收到对方的候选地址后，双方从监听所用的同一端口反复向每个候选地址发起连接，同时继续接受连入。
+   向外的连接使各自的 NAT 为这对地址建立映射，对方的连接请求随之得以通过（TCP 同时打开）；
+   一方不在 NAT 之后，或 NAT 保留了端口时，也可以直接连入；
+3. 发送方在第一条建立的连接上写出握手行（传输编号与校验和）后发送文件内容，
+   接收方在建立的所有连接中等待握手行正确的那一条。
+
+发送方在 [`PUNCH_TIMEOUT`] 内未能建立直连，或直连中途断开时，改为把文件按 [`CHUNK_SIZE`]
+切分成 [`Control::FileChunk`] 帧经服务器中转。接收方收完后按 SHA-256 校验，通过后回复
+[`Control::FileDone`]；校验失败或任一方取消时以 [`Control::FileCancel`] 结束传输。
+收到的文件是图片时，随后在消息区显示预览（见 [`crate::preview`]）。
+
+传输文件内容期间，输入行上方的状态行（见 [`terminal::Screen::status`]）显示各传输的进度条、
+已传输的字节数、速率与预计剩余时间。`/transfers` 列出待接收的文件与进行中的传输，
+`/cancel-transfer <编号>` 取消其中一个（同时通知对方）。
+
+传输途中任一方断线（服务器以 `interrupted` 的 [`Control::FileCancel`] 通知另一方，
+自己的连接断开时则由接收任务中断全部传输）不会丢掉已传输的部分：接收方把内容写在下载目录中
+以对方与文件 SHA-256 命名的临时文件里，其长度即为已确认写入的字节数；发送方记下中断的文件，
+重新连接后对方在线（或对方重新上线）时自动以同一校验和重新提议。接收方 `/accept` 时发现
+已有该文件的部分内容，便以 [`Control::FileResume`] 告知发送方从该处继续，直连与中转都从断点
+开始，最终仍按完整文件的 SHA-256 校验。
+*/
+
+use crate::client::SharedWriter;
+use crate::preview::{self, Preview};
+use crate::protocol::{Control, ErrorCode, ErrorFrame, Frame};
+use crate::terminal;
+use crate::theme::Theme;
+use crate::{humanize_bytes, humanize_duration, tr};
+use base64ct::{Base64, Encoding};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::future;
+use std::io::{self, Read, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// 默认的下载目录
+pub const DEFAULT_DOWNLOADS: &str = "downloads";
+
+/// 发送方尝试建立直连的最长时间，超过后改为经服务器中转
+pub const PUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 经服务器中转时每帧携带的文件字节数（Base64 编码后仍在帧长度上限之内）
+pub const CHUNK_SIZE: usize = 32 * 1024;
+
+/// 两次连接尝试之间的间隔
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 直连握手行的前缀
+const HANDSHAKE: &str = "ASYNC-CHAT-FILE";
+
+/// 等待对方在直连上写出握手行的最长时间
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 刷新传输进度的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 进度条的格数
+const PROGRESS_BAR_WIDTH: u64 = 20;
+
+/// 对方发来、尚未应答的文件
+#[derive(Clone, Debug)]
+struct Offer {
+    peer: String,
+    id: u64,
+    name: String,
+    size: u64,
+    sha256: String,
+    /// 发送方支持续传
+    resumable: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// 尚未应答的文件，键为本地编号
+    offers: BTreeMap<u64, Offer>,
+    /// 最近分配的本地编号（待接收的文件与进行中的传输共用）
+    last_number: u64,
+    /// 进行中的传输，键为（对方，传输编号）
+    active: HashMap<(String, u64), Active>,
+    /// 刷新进度的任务是否在运行
+    ticking: bool,
+    /// 中断后等待对方在线、重新提议续传的发送
+    interrupted: Vec<Interrupted>,
+}
+
+impl State {
+    /// 把中断的传输移出进行中的列表，自己发送的文件记下来留待续传；返回传输任务的通道
+    fn interrupt(&mut self, key: &(String, u64)) -> Option<mpsc::UnboundedSender<Control>> {
+        let active = self.active.remove(key)?;
+        if let Role::Sending { server, source } = active.role {
+            self.interrupted.push(Interrupted {
+                peer: key.0.clone(),
+                server,
+                source,
+            });
+        }
+        Some(active.tx)
+    }
+}
+
+/// 自己要发送的文件
+#[derive(Clone, Debug)]
+struct Source {
+    path: PathBuf,
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+/// 中断后等待续传的发送
+#[derive(Debug)]
+struct Interrupted {
+    peer: String,
+    /// 发起传输时连接的服务器地址，用于确定本机的出口地址
+    server: String,
+    source: Source,
+}
+
+/// 自己在传输中的角色
+#[derive(Debug)]
+enum Role {
+    /// 发送方，记下文件以便中断后重新提议
+    Sending {
+        server: String,
+        source: Source,
+    },
+    Receiving,
+}
+
+/// 传输提前结束的原因
+enum Stop {
+    /// 任一方取消，附带对方给出的原因
+    Cancelled(Option<String>),
+    /// 一方断线，之后可以续传
+    Interrupted,
+}
+
+impl Stop {
+    fn new(reason: Option<String>, interrupted: bool) -> Self {
+        match interrupted {
+            true => Self::Interrupted,
+            false => Self::Cancelled(reason),
+        }
+    }
+}
+
+/// 进行中的传输
+#[derive(Debug)]
+struct Active {
+    /// 把对方的帧交给传输任务的通道
+    tx: mpsc::UnboundedSender<Control>,
+    /// 本地编号，接收的文件沿用 `/accept` 时的编号
+    number: u64,
+    name: String,
+    size: u64,
+    role: Role,
+    progress: Arc<Progress>,
+}
+
+/// 传输进度，由传输任务更新
+#[derive(Debug, Default)]
+struct Progress {
+    /// 已发送或已接收的字节数（续传时包括之前已有的部分）
+    done: AtomicU64,
+    /// 本次开始时已有的字节数（续传的断点），不计入速率
+    from: AtomicU64,
+    /// 开始传输文件内容的时间
+    started: OnceLock<Instant>,
+}
+
+impl Progress {
+    /// 又传输了 `bytes` 字节
+    fn add(&self, bytes: u64) {
+        self.started.get_or_init(Instant::now);
+        self.done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// 从第 `offset` 字节开始传输
+    fn start_at(&self, offset: u64) {
+        self.from.store(offset, Ordering::Relaxed);
+        self.done.store(offset, Ordering::Relaxed);
+    }
+
+    /// 直连中断后从断点重新传输，速率仍从最初开始传输的时间算起
+    fn restart(&self) {
+        self.done
+            .store(self.from.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
+
+impl Active {
+    /// 描述传输及其进度，如 `#2 ↑ report.pdf → bob [#####---------------] 25% 1.0 MB/4.0 MB 512.0 KB/s 剩余 6 秒`
+    fn describe(&self, peer: &str) -> String {
+        let file = match self.role {
+            Role::Sending { .. } => tr!("transfer.outgoing", name = self.name, peer = peer),
+            Role::Receiving => tr!("transfer.incoming_file", name = self.name, peer = peer),
+        };
+        let Some(started) = self.progress.started.get() else {
+            return tr!(
+                "transfer.progress_waiting",
+                number = self.number,
+                file = file
+            );
+        };
+        let done = self.progress.done.load(Ordering::Relaxed).min(self.size);
+        let percent = (done * 100).checked_div(self.size).unwrap_or(100);
+        let filled = (done * PROGRESS_BAR_WIDTH)
+            .checked_div(self.size)
+            .unwrap_or(PROGRESS_BAR_WIDTH);
+        let bar = format!(
+            "[{}{}]",
+            "#".repeat(filled as usize),
+            "-".repeat((PROGRESS_BAR_WIDTH - filled) as usize)
+        );
+        let elapsed = started.elapsed().as_secs_f64();
+        let moved = done.saturating_sub(self.progress.from.load(Ordering::Relaxed));
+        let rate = match elapsed > 0.0 {
+            true => moved as f64 / elapsed,
+            false => 0.0,
+        };
+        let eta = match rate > 0.0 {
+            true => humanize_duration(Duration::from_secs_f64(
+                ((self.size - done) as f64 / rate).ceil(),
+            )),
+            false => "?".to_string(),
+        };
+        tr!(
+            "transfer.progress",
+            number = self.number,
+            file = file,
+            bar = bar,
+            percent = percent,
+            done = humanize_bytes(done),
+            size = humanize_bytes(self.size),
+            rate = humanize_bytes(rate as u64),
+            eta = eta,
+        )
+    }
+}
+
+/// 客户端的文件传输状态，由输入循环与接收任务共享
+#[derive(Debug)]
+pub struct Transfers {
+    downloads: PathBuf,
+    theme: Arc<Theme>,
+    /// 收到图片时的预览方式
+    preview: Preview,
+    state: Mutex<State>,
+}
+
+impl Transfers {
+    pub fn new(downloads: impl Into<PathBuf>, theme: Arc<Theme>, preview: Preview) -> Self {
+        Self {
+            downloads: downloads.into(),
+            theme,
+            preview,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// 处理 `/sendfile <用户> <路径>`：向 `peer` 提议发送 `path`，对方接受后开始传输
+    ///
+    /// `server` 为当前连接的服务器地址，用于确定本机的出口地址
+    pub async fn send(
+        self: &Arc<Self>,
+        writer: SharedWriter,
+        server: &str,
+        peer: &str,
+        path: &str,
+    ) {
+        let path = PathBuf::from(path);
+        let name = match path.file_name() {
+            Some(name) if path.is_file() => name.to_string_lossy().into_owned(),
+            _ => {
+                self.error(tr!("transfer.not_a_file", path = path.display()));
+                return;
+            }
+        };
+        let hashed = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || hash_file(&path)).await
+        };
+        let (size, sha256) = match hashed.map_err(io::Error::other).and_then(|hashed| hashed) {
+            Ok(hashed) => hashed,
+            Err(e) => {
+                self.error(tr!(
+                    "transfer.read_failed",
+                    path = path.display(),
+                    error = e
+                ));
+                return;
+            }
+        };
+        let source = Source {
+            path,
+            name,
+            size,
+            sha256,
+        };
+        self.offer(writer, server, peer, source).await;
+    }
+
+    /// 向 `peer` 提议发送 `source`，对方接受后开始传输
+    async fn offer(
+        self: &Arc<Self>,
+        writer: SharedWriter,
+        server: &str,
+        peer: &str,
+        source: Source,
+    ) {
+        let id = match random_id() {
+            Ok(id) => id,
+            Err(e) => {
+                self.error(tr!("client.send_failed", error = e));
+                return;
+            }
+        };
+        let number = self.next_number();
+        let role = Role::Sending {
+            server: server.to_string(),
+            source: source.clone(),
+        };
+        let (rx, progress) = self.register(peer, id, number, &source.name, source.size, role);
+        let offer = Control::FileOffer {
+            id,
+            peer: peer.to_string(),
+            name: source.name.clone(),
+            size: source.size,
+            sha256: source.sha256.clone(),
+            resumable: true,
+        };
+        if let Err(e) = writer.lock().await.write_frame(&Frame::from(offer)).await {
+            self.finish(peer, id);
+            self.error(tr!("client.send_failed", error = e));
+            return;
+        }
+        self.system(tr!(
+            "transfer.offered",
+            name = source.name,
+            size = humanize_bytes(source.size),
+            peer = peer
+        ));
+        let outgoing = Outgoing {
+            peer: peer.to_string(),
+            id,
+            path: source.path,
+            name: source.name,
+            size: source.size,
+            sha256: source.sha256,
+            progress,
+        };
+        let transfers = self.clone();
+        let server = server.to_string();
+        tokio::spawn(async move { transfers.send_task(writer, server, outgoing, rx).await });
+    }
+
+    /// 处理 `/accept <编号>` 与 `/reject <编号>`：应答对方发来的文件
+    pub async fn answer(
+        self: &Arc<Self>,
+        writer: SharedWriter,
+        server: &str,
+        number: u64,
+        accept: bool,
+    ) {
+        let Some(offer) = self.state.lock().unwrap().offers.remove(&number) else {
+            self.system(tr!("transfer.no_offer", number = number));
+            return;
+        };
+        if !accept {
+            let answer = Control::FileAnswer {
+                id: offer.id,
+                peer: offer.peer.clone(),
+                accept: false,
+            };
+            let _ = writer.lock().await.write_frame(&Frame::from(answer)).await;
+            self.system(tr!(
+                "transfer.you_rejected",
+                name = offer.name,
+                peer = offer.peer
+            ));
+            return;
+        }
+        let resumed = match offer.resumable {
+            true => self.partial(&offer.peer, &offer.sha256, offer.size),
+            false => None,
+        };
+        let (rx, progress) = self.register(
+            &offer.peer,
+            offer.id,
+            number,
+            &offer.name,
+            offer.size,
+            Role::Receiving,
+        );
+        let transfers = self.clone();
+        let server = server.to_string();
+        tokio::spawn(async move {
+            transfers
+                .receive_task(writer, server, offer, rx, progress, resumed.unwrap_or(0))
+                .await
+        });
+    }
+
+    /// 重新连接后或对方重新上线时，向在线的对方重新提议中断的发送
+    ///
+    /// `online` 为当前在线的用户；对方保留了已接收的部分时从断点续传
+    pub async fn resume(self: &Arc<Self>, writer: &SharedWriter, online: &[String]) {
+        let ready: Vec<Interrupted> = {
+            let mut state = self.state.lock().unwrap();
+            let (ready, waiting) = std::mem::take(&mut state.interrupted)
+                .into_iter()
+                .partition(|interrupted| online.contains(&interrupted.peer));
+            state.interrupted = waiting;
+            ready
+        };
+        for interrupted in ready {
+            self.system(tr!(
+                "transfer.resuming",
+                name = interrupted.source.name,
+                peer = interrupted.peer
+            ));
+            self.offer(
+                writer.clone(),
+                &interrupted.server,
+                &interrupted.peer,
+                interrupted.source,
+            )
+            .await;
+        }
+    }
+
+    /// 与服务器的连接断开：中断全部传输（服务器同样会通知对方），尚未应答的文件随之作废
+    pub fn interrupt_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.offers.clear();
+        let keys: Vec<_> = state.active.keys().cloned().collect();
+        for (peer, id) in keys {
+            if let Some(tx) = state.interrupt(&(peer.clone(), id)) {
+                let _ = tx.send(Control::FileCancel {
+                    id,
+                    peer,
+                    reason: None,
+                    interrupted: true,
+                });
+            }
+        }
+    }
+
+    /// 处理 `/transfers`：列出待接收的文件与进行中的传输
+    pub fn list(&self) {
+        let state = self.state.lock().unwrap();
+        let mut lines: Vec<String> = state
+            .offers
+            .iter()
+            .map(|(number, offer)| {
+                tr!(
+                    "transfer.list_offer",
+                    number = number,
+                    file = tr!(
+                        "transfer.incoming_file",
+                        name = offer.name,
+                        peer = offer.peer
+                    ),
+                    size = humanize_bytes(offer.size),
+                )
+            })
+            .collect();
+        let mut active: Vec<_> = state.active.iter().collect();
+        active.sort_by_key(|(_, active)| active.number);
+        lines.extend(
+            active
+                .into_iter()
+                .map(|((peer, _), active)| active.describe(peer)),
+        );
+        lines.extend(state.interrupted.iter().map(|interrupted| {
+            tr!(
+                "transfer.list_interrupted",
+                file = tr!(
+                    "transfer.outgoing",
+                    name = interrupted.source.name,
+                    peer = interrupted.peer
+                ),
+                peer = interrupted.peer,
+            )
+        }));
+        drop(state);
+        if lines.is_empty() {
+            self.system(tr!("transfer.list_empty"));
+            return;
+        }
+        self.system(format!(
+            "{}\n  › {}",
+            tr!("transfer.list", count = lines.len()),
+            lines.join("\n  › ")
+        ));
+    }
+
+    /// 处理 `/cancel-transfer <编号>`：通知对方取消，并让传输任务在安全的位置结束
+    ///
+    /// 传输任务可能正在经服务器连接写出中转的内容，不能直接中止，
+    /// 而是像收到对方的取消一样处理：显示已取消，接收方删除临时文件。
+    pub async fn cancel(&self, writer: &SharedWriter, number: u64) {
+        let found = self
+            .state
+            .lock()
+            .unwrap()
+            .active
+            .iter()
+            .find(|(_, active)| active.number == number)
+            .map(|((peer, id), active)| {
+                let done = active.progress.done.load(Ordering::Relaxed);
+                (peer.clone(), *id, active.tx.clone(), done >= active.size)
+            });
+        let Some((peer, id, tx, complete)) = found else {
+            self.system(tr!("transfer.no_transfer", number = number));
+            return;
+        };
+        // 内容已经全部传完，正在校验或等待确认，此时取消会让双方看到的结果不一致
+        if complete {
+            self.system(tr!("transfer.already_complete", number = number));
+            return;
+        }
+        let cancel = Control::FileCancel {
+            id,
+            peer,
+            reason: None,
+            interrupted: false,
+        };
+        let _ = writer
+            .lock()
+            .await
+            .write_frame(&Frame::from(cancel.clone()))
+            .await;
+        let _ = tx.send(cancel);
+    }
+
+    /// 接收任务收到文件传输帧时调用：新的文件提议等待应答，其余的帧交给对应的传输任务
+    pub fn dispatch(&self, control: Control) {
+        let (peer, id) = match &control {
+            Control::FileOffer {
+                id,
+                peer,
+                name,
+                size,
+                sha256,
+                resumable,
+            } => {
+                let number = self.next_number();
+                let offer = Offer {
+                    peer: peer.clone(),
+                    id: *id,
+                    name: name.clone(),
+                    size: *size,
+                    sha256: sha256.clone(),
+                    resumable: *resumable,
+                };
+                self.state.lock().unwrap().offers.insert(number, offer);
+                let partial = match resumable {
+                    true => self.partial(peer, sha256, *size),
+                    false => None,
+                };
+                let text = match partial {
+                    Some(received) => tr!(
+                        "transfer.incoming_resume",
+                        peer = peer,
+                        name = name,
+                        size = humanize_bytes(*size),
+                        received = humanize_bytes(received),
+                        number = number
+                    ),
+                    None => tr!(
+                        "transfer.incoming",
+                        peer = peer,
+                        name = name,
+                        size = humanize_bytes(*size),
+                        number = number
+                    ),
+                };
+                self.notice(text);
+                terminal::screen().bell();
+                return;
+            }
+            Control::FileAnswer { id, peer, .. }
+            | Control::FileResume { id, peer, .. }
+            | Control::FileCandidates { id, peer, .. }
+            | Control::FileChunk { id, peer, .. }
+            | Control::FileDone { id, peer }
+            | Control::FileCancel { id, peer, .. } => (peer.clone(), *id),
+            _ => return,
+        };
+        let mut state = self.state.lock().unwrap();
+        let key = (peer.clone(), id);
+        // 对方断线：立即记下中断的发送，不必等传输任务处理
+        if let Control::FileCancel {
+            interrupted: true, ..
+        } = control
+        {
+            if let Some(tx) = state.interrupt(&key) {
+                let _ = tx.send(control);
+                return;
+            }
+        }
+        if let Some(active) = state.active.get(&key) {
+            let _ = active.tx.send(control);
+            return;
+        }
+        // 对方撤回了尚未应答的文件
+        if let Control::FileCancel { reason, .. } = control {
+            let number = state
+                .offers
+                .iter()
+                .find(|(_, offer)| offer.peer == peer && offer.id == id)
+                .map(|(number, _)| *number);
+            if let Some(offer) = number.and_then(|number| state.offers.remove(&number)) {
+                drop(state);
+                self.cancelled(&offer.name, &peer, reason);
+            }
+        }
+    }
+
+    /// 服务器拒绝了自己发起的传输（对方不在线、不支持或传输策略不允许）时结束对应的传输任务
+    pub fn refused(&self, error: &ErrorFrame) {
+        if let (ErrorCode::TransferFailed | ErrorCode::TransferBlocked, Some(peer), Some(id)) =
+            (error.code, &error.target, error.id)
+        {
+            self.finish(peer, id);
+        }
+    }
+
+    /// 接收时使用的临时文件，以对方与文件的 SHA-256 命名，中断后据此找到已接收的部分
+    fn part_path(&self, peer: &str, sha256: &str) -> PathBuf {
+        // 校验和来自对方，只取其中的十六进制字符，避免写到下载目录之外
+        let sha256: String = sha256.chars().filter(char::is_ascii_hexdigit).collect();
+        self.downloads.join(format!(".{}.{}.part", peer, sha256))
+    }
+
+    /// 之前中断的接收留下的字节数；没有或已经完整（无法续传）时为 `None`
+    fn partial(&self, peer: &str, sha256: &str, size: u64) -> Option<u64> {
+        let received = std::fs::metadata(self.part_path(peer, sha256)).ok()?.len();
+        (received > 0 && received < size).then_some(received)
+    }
+
+    /// 分配一个本地编号
+    fn next_number(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.last_number += 1;
+        state.last_number
+    }
+
+    /// 登记一个进行中的传输，并在需要时启动刷新进度的任务
+    fn register(
+        self: &Arc<Self>,
+        peer: &str,
+        id: u64,
+        number: u64,
+        name: &str,
+        size: u64,
+        role: Role,
+    ) -> (mpsc::UnboundedReceiver<Control>, Arc<Progress>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let progress = Arc::new(Progress::default());
+        let active = Active {
+            tx,
+            number,
+            name: name.to_string(),
+            size,
+            role,
+            progress: progress.clone(),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.active.insert((peer.to_string(), id), active);
+        if !state.ticking {
+            state.ticking = true;
+            tokio::spawn(self.clone().show_progress());
+        }
+        (rx, progress)
+    }
+
+    /// 每隔 [`PROGRESS_INTERVAL`] 在状态行显示已开始传输内容的传输的进度，
+    /// 没有进行中的传输时清除状态行并结束
+    async fn show_progress(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let status = {
+                let mut state = self.state.lock().unwrap();
+                if state.active.is_empty() {
+                    state.ticking = false;
+                    break;
+                }
+                let mut moving: Vec<_> = state
+                    .active
+                    .iter()
+                    .filter(|(_, active)| active.progress.started.get().is_some())
+                    .collect();
+                moving.sort_by_key(|(_, active)| active.number);
+                let parts: Vec<String> = moving
+                    .into_iter()
+                    .map(|((peer, _), active)| active.describe(peer))
+                    .collect();
+                (!parts.is_empty()).then(|| parts.join("  "))
+            };
+            terminal::screen().status(status);
+        }
+        terminal::screen().status(None);
+    }
+
+    fn finish(&self, peer: &str, id: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .active
+            .remove(&(peer.to_string(), id));
+    }
+
+    /// 传输提前结束：取消时移除传输，中断时记下来留待续传
+    fn stop(&self, name: &str, peer: &str, id: u64, stop: &Stop) {
+        match stop {
+            Stop::Cancelled(reason) => {
+                self.cancelled(name, peer, reason.clone());
+                self.finish(peer, id);
+            }
+            Stop::Interrupted => {
+                self.state
+                    .lock()
+                    .unwrap()
+                    .interrupt(&(peer.to_string(), id));
+                self.error(tr!("transfer.interrupted", name = name, peer = peer));
+            }
+        }
+    }
+
+    /// 发送方的传输任务：等待应答，尝试直连，失败时经服务器中转，最后等待接收方确认
+    async fn send_task(
+        self: Arc<Self>,
+        writer: SharedWriter,
+        server: String,
+        file: Outgoing,
+        mut rx: mpsc::UnboundedReceiver<Control>,
+    ) {
+        let (peer, id) = (file.peer.clone(), file.id);
+        let offset = loop {
+            match rx.recv().await {
+                Some(Control::FileAnswer { accept: true, .. }) => break 0,
+                Some(Control::FileResume { offset, .. }) => break offset.min(file.size),
+                Some(Control::FileAnswer { accept: false, .. }) => {
+                    self.system(tr!("transfer.rejected", peer = peer, name = file.name));
+                    return self.finish(&peer, id);
+                }
+                Some(Control::FileCancel {
+                    reason,
+                    interrupted,
+                    ..
+                }) => {
+                    return self.stop(&file.name, &peer, id, &Stop::new(reason, interrupted));
+                }
+                Some(_) => {}
+                None => return,
+            }
+        };
+        match offset {
+            0 => self.system(tr!("transfer.accepted", peer = peer, name = file.name)),
+            _ => self.system(tr!(
+                "transfer.resumed",
+                peer = peer,
+                name = file.name,
+                offset = humanize_bytes(offset)
+            )),
+        }
+        file.progress.start_at(offset);
+
+        // 没有剩余内容（如空文件）时无需直连，直接以中转方式结束
+        let rendezvous = match file.size > offset {
+            true => Rendezvous::bind(&server).ok(),
+            false => None,
+        };
+        let direct = match rendezvous {
+            Some(rendezvous) => {
+                let candidates = rendezvous.candidates(id, &peer);
+                let _ = writer.lock().await.write_frame(&candidates).await;
+                match tokio::time::timeout(PUNCH_TIMEOUT, rendezvous.punch(&mut rx)).await {
+                    Ok(Punched::Stream(stream)) => Some(stream),
+                    Ok(Punched::Stopped(stop)) => return self.stop(&file.name, &peer, id, &stop),
+                    Ok(Punched::Closed) => return,
+                    Err(_) => None,
+                }
+            }
+            None => None,
+        };
+        let sent_direct = match direct {
+            Some(stream) => tokio::select! {
+                sent = send_direct(stream, &file, offset) => match sent {
+                    Ok(()) => true,
+                    Err(e) => {
+                        self.error(tr!("transfer.direct_failed", error = e));
+                        false
+                    }
+                },
+                stop = stopped(&mut rx) => return self.stop(&file.name, &peer, id, &stop),
+            },
+            None => false,
+        };
+        if sent_direct {
+            self.system(tr!("transfer.sent_direct", name = file.name, peer = peer));
+        } else {
+            self.system(tr!("transfer.relaying", name = file.name, peer = peer));
+            file.progress.restart();
+            let relayed = self.relay(&writer, &file, offset, &mut rx).await;
+            if let Ok(Relayed::Stopped(stop)) = relayed {
+                return self.stop(&file.name, &peer, id, &stop);
+            }
+            if let Err(e) = relayed {
+                self.error(tr!(
+                    "transfer.read_failed",
+                    path = file.path.display(),
+                    error = e
+                ));
+                let cancel = Control::FileCancel {
+                    id,
+                    peer: peer.clone(),
+                    reason: None,
+                    interrupted: false,
+                };
+                let _ = writer.lock().await.write_frame(&Frame::from(cancel)).await;
+                return self.finish(&peer, id);
+            }
+        }
+        loop {
+            match rx.recv().await {
+                Some(Control::FileDone { .. }) => {
+                    self.success(tr!("transfer.delivered", name = file.name, peer = peer));
+                    break;
+                }
+                Some(Control::FileCancel {
+                    reason,
+                    interrupted,
+                    ..
+                }) => {
+                    return self.stop(&file.name, &peer, id, &Stop::new(reason, interrupted));
+                }
+                Some(_) => {}
+                None => return,
+            }
+        }
+        self.finish(&peer, id);
+    }
+
+    /// 把文件从 `offset` 处起按 [`CHUNK_SIZE`] 切分后经服务器中转；
+    /// 途中对方取消、任一方断线时提前结束
+    async fn relay(
+        &self,
+        writer: &SharedWriter,
+        file: &Outgoing,
+        mut offset: u64,
+        rx: &mut mpsc::UnboundedReceiver<Control>,
+    ) -> io::Result<Relayed> {
+        let mut source = File::open(&file.path).await?;
+        source.seek(SeekFrom::Start(offset)).await?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        while offset < file.size {
+            if let Ok(Control::FileCancel {
+                reason,
+                interrupted,
+                ..
+            }) = rx.try_recv()
+            {
+                return Ok(Relayed::Stopped(Stop::new(reason, interrupted)));
+            }
+            let len = source.read(&mut buf).await?;
+            if len == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let chunk = Control::FileChunk {
+                id: file.id,
+                peer: file.peer.clone(),
+                offset,
+                data: Base64::encode_string(&buf[..len]),
+            };
+            // 写不出去说明与服务器的连接已经断开，留待重新连接后续传
+            let written = writer.lock().await.write_frame(&Frame::from(chunk)).await;
+            if written.is_err() {
+                return Ok(Relayed::Stopped(Stop::Interrupted));
+            }
+            offset += len as u64;
+            file.progress.add(len as u64);
+        }
+        Ok(Relayed::Sent)
+    }
+
+    /// 接收方的传输任务：接受后尝试直连并同时接收经服务器中转的内容，收完后校验并保存；
+    /// `resumed` 为之前中断时已接收的字节数，从该处续传
+    async fn receive_task(
+        self: Arc<Self>,
+        writer: SharedWriter,
+        server: String,
+        offer: Offer,
+        mut rx: mpsc::UnboundedReceiver<Control>,
+        progress: Arc<Progress>,
+        resumed: u64,
+    ) {
+        let (peer, id) = (offer.peer.clone(), offer.id);
+        let part = self.part_path(&peer, &offer.sha256);
+        let file = async {
+            fs::create_dir_all(&self.downloads).await?;
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&part)
+                .await?;
+            reset(&mut file, resumed).await?;
+            Ok::<_, io::Error>(file)
+        };
+        let mut file = match file.await {
+            Ok(file) => file,
+            Err(e) => {
+                self.error(tr!(
+                    "transfer.save_failed",
+                    path = part.display(),
+                    error = e
+                ));
+                let answer = Control::FileAnswer {
+                    id,
+                    peer: peer.clone(),
+                    accept: false,
+                };
+                let _ = writer.lock().await.write_frame(&Frame::from(answer)).await;
+                return self.finish(&peer, id);
+            }
+        };
+        let answer = match resumed {
+            0 => Control::FileAnswer {
+                id,
+                peer: peer.clone(),
+                accept: true,
+            },
+            _ => Control::FileResume {
+                id,
+                peer: peer.clone(),
+                offset: resumed,
+            },
+        };
+        let _ = writer.lock().await.write_frame(&Frame::from(answer)).await;
+        progress.start_at(resumed);
+        let rendezvous = Rendezvous::bind(&server).ok();
+        if let Some(rendezvous) = &rendezvous {
+            let _ = writer
+                .lock()
+                .await
+                .write_frame(&rendezvous.candidates(id, &peer))
+                .await;
+        }
+        match resumed {
+            0 => self.system(tr!("transfer.receiving", name = offer.name, peer = peer)),
+            _ => self.system(tr!(
+                "transfer.receiving_resumed",
+                name = offer.name,
+                peer = peer,
+                received = humanize_bytes(resumed)
+            )),
+        }
+
+        let expected = handshake_line(id, &offer.sha256);
+        let mut listener = rendezvous.as_ref().map(|r| &r.listener);
+        let mut streams = JoinSet::new();
+        // 直连的尝试在两倍的打洞时间后放弃，此后只等待中转的内容
+        let deadline = tokio::time::sleep(PUNCH_TIMEOUT * 2);
+        tokio::pin!(deadline);
+        let mut received = resumed;
+        let complete = loop {
+            if received == offer.size {
+                break true;
+            }
+            let current = listener;
+            let accept = async move {
+                match current {
+                    Some(listener) => listener.accept().await,
+                    None => future::pending().await,
+                }
+            };
+            tokio::select! {
+                Ok((stream, _)) = accept => {
+                    streams.spawn(handshake(stream, expected.clone()));
+                }
+                Some(Ok(Some(mut stream))) = streams.join_next(), if !streams.is_empty() => {
+                    listener = None;
+                    streams.abort_all();
+                    let remaining = offer.size - resumed;
+                    let direct = tokio::select! {
+                        direct = receive_direct(&mut stream, &mut file, remaining, &progress) => direct,
+                        stop = stopped(&mut rx) => {
+                            drop(file);
+                            if let Stop::Cancelled(_) = stop {
+                                let _ = fs::remove_file(&part).await;
+                            }
+                            return self.stop(&offer.name, &peer, id, &stop);
+                        }
+                    };
+                    match direct {
+                        Ok(()) => break true,
+                        // 直连中途断开时发送方会改为中转，从断点重新接收
+                        Err(e) => {
+                            self.error(tr!("transfer.direct_failed", error = e));
+                            progress.restart();
+                            if reset(&mut file, resumed).await.is_err() {
+                                break false;
+                            }
+                        }
+                    }
+                }
+                () = &mut deadline, if listener.is_some() => {
+                    listener = None;
+                    streams.abort_all();
+                }
+                control = rx.recv() => match control {
+                    Some(Control::FileCandidates { addrs, .. }) if listener.is_some() => {
+                        let local = rendezvous.as_ref().map(|r| r.local);
+                        for addr in addrs.iter().filter_map(|addr| addr.parse().ok()) {
+                            if let Some(local) = local {
+                                let expected = expected.clone();
+                                streams.spawn(async move {
+                                    let stream = connect_until(local, addr).await;
+                                    handshake(stream, expected).await
+                                });
+                            }
+                        }
+                    }
+                    Some(Control::FileChunk { offset, data, .. }) => {
+                        listener = None;
+                        streams.abort_all();
+                        // 不支持续传的发送方从头发送
+                        if offset == 0 && received > 0 {
+                            if reset(&mut file, 0).await.is_err() {
+                                break false;
+                            }
+                            received = 0;
+                            progress.start_at(0);
+                        }
+                        let Ok(data) = Base64::decode_vec(&data) else {
+                            continue;
+                        };
+                        if offset != received || received + data.len() as u64 > offer.size {
+                            continue;
+                        }
+                        if let Err(e) = file.write_all(&data).await {
+                            self.error(tr!("transfer.save_failed", path = part.display(), error = e));
+                            break false;
+                        }
+                        received += data.len() as u64;
+                        progress.add(data.len() as u64);
+                        if received == offer.size {
+                            break true;
+                        }
+                    }
+                    Some(Control::FileCancel { reason, interrupted, .. }) => {
+                        let stop = Stop::new(reason, interrupted);
+                        drop(file);
+                        if let Stop::Cancelled(_) = stop {
+                            let _ = fs::remove_file(&part).await;
+                        }
+                        return self.stop(&offer.name, &peer, id, &stop);
+                    }
+                    Some(_) => {}
+                    None => return,
+                },
+            }
+        };
+        let _ = file.flush().await;
+        drop(file);
+
+        let verified = complete && {
+            let part = part.clone();
+            let hashed = tokio::task::spawn_blocking(move || hash_file(&part)).await;
+            matches!(hashed, Ok(Ok((_, sha256))) if sha256 == offer.sha256)
+        };
+        let saved = match verified {
+            true => save(&part, &self.downloads, &offer.name).await,
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                tr!("transfer.checksum_mismatch"),
+            )),
+        };
+        let reply = match &saved {
+            Ok(path) => {
+                self.success(tr!(
+                    "transfer.saved",
+                    name = offer.name,
+                    peer = peer,
+                    path = path.display()
+                ));
+                Control::FileDone {
+                    id,
+                    peer: peer.clone(),
+                }
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&part).await;
+                self.error(tr!("transfer.save_failed", path = offer.name, error = e));
+                Control::FileCancel {
+                    id,
+                    peer: peer.clone(),
+                    reason: Some(e.to_string()),
+                    interrupted: false,
+                }
+            }
+        };
+        let _ = writer.lock().await.write_frame(&Frame::from(reply)).await;
+        self.finish(&peer, id);
+        if let Ok(path) = saved {
+            self.show_preview(path).await;
+        }
+    }
+
+    /// 收到的文件是图片时在消息区显示预览
+    async fn show_preview(&self, path: PathBuf) {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if self.preview == Preview::Off || !preview::is_image(&name) {
+            return;
+        }
+        let Ok(bytes) = preview::read(&path).await else {
+            return;
+        };
+        let mode = self.preview;
+        let rendered =
+            tokio::task::spawn_blocking(move || preview::render(&bytes, &name, mode)).await;
+        if let Ok(Some(text)) = rendered {
+            terminal::screen().println(text);
+        }
+    }
+
+    fn cancelled(&self, name: &str, peer: &str, reason: Option<String>) {
+        let text = match reason {
+            Some(reason) => tr!(
+                "transfer.cancelled_reason",
+                name = name,
+                peer = peer,
+                reason = reason
+            ),
+            None => tr!("transfer.cancelled", name = name, peer = peer),
+        };
+        self.error(text);
+    }
+
+    fn notice(&self, text: String) {
+        terminal::screen().println(format!("\n{}", self.theme.system.paint(&text)));
+    }
+
+    fn system(&self, text: String) {
+        terminal::screen().println(self.theme.system.paint(&text).to_string());
+    }
+
+    fn success(&self, text: String) {
+        terminal::screen().println(self.theme.success.paint(&text).to_string());
+    }
+
+    fn error(&self, text: String) {
+        terminal::screen().eprintln(self.theme.error.paint(&text).to_string());
+    }
+}
+
+/// 自己发出的文件
+#[derive(Debug)]
+struct Outgoing {
+    peer: String,
+    id: u64,
+    path: PathBuf,
+    name: String,
+    size: u64,
+    sha256: String,
+    progress: Arc<Progress>,
+}
+
+/// 中转的结果
+enum Relayed {
+    /// 全部内容已经写出
+    Sent,
+    /// 任一方取消或断线
+    Stopped(Stop),
+}
+
+/// 打洞的结果
+enum Punched {
+    /// 建立了直连
+    Stream(TcpStream),
+    /// 任一方取消或断线
+    Stopped(Stop),
+    /// 传输已被移除
+    Closed,
+}
+
+/// 为一次传输监听的本地端口
+struct Rendezvous {
+    listener: TcpListener,
+    /// 监听的地址（本机的出口 IP 与随机端口）
+    local: SocketAddr,
+}
+
+impl Rendezvous {
+    /// 在本机连往服务器时使用的出口 IP 上监听随机端口，允许之后从同一端口向外连接
+    fn bind(server: &str) -> io::Result<Self> {
+        let ip = outbound_ip(server).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let socket = reusable_socket(ip)?;
+        socket.bind(SocketAddr::new(ip, 0))?;
+        let local = socket.local_addr()?;
+        let listener = socket.listen(8)?;
+        Ok(Self { listener, local })
+    }
+
+    /// 上报给服务器的候选地址帧
+    fn candidates(&self, id: u64, peer: &str) -> Frame {
+        Frame::from(Control::FileCandidates {
+            id,
+            peer: peer.to_string(),
+            addrs: vec![self.local.to_string()],
+        })
+    }
+
+    /// 发送方：收到对方的候选地址后同时连出与接受连入，返回第一条建立的连接
+    async fn punch(&self, rx: &mut mpsc::UnboundedReceiver<Control>) -> Punched {
+        let mut attempts = JoinSet::new();
+        loop {
+            tokio::select! {
+                Ok((stream, _)) = self.listener.accept() => return Punched::Stream(stream),
+                Some(Ok(stream)) = attempts.join_next(), if !attempts.is_empty() => {
+                    return Punched::Stream(stream);
+                }
+                control = rx.recv() => match control {
+                    Some(Control::FileCandidates { addrs, .. }) => {
+                        for addr in addrs.iter().filter_map(|addr| addr.parse().ok()) {
+                            attempts.spawn(connect_until(self.local, addr));
+                        }
+                    }
+                    Some(Control::FileCancel { reason, interrupted, .. }) => {
+                        return Punched::Stopped(Stop::new(reason, interrupted));
+                    }
+                    Some(_) => {}
+                    None => return Punched::Closed,
+                },
+            }
+        }
+    }
+}
+
+/// 可以与监听套接字共用端口的 TCP 套接字
+fn reusable_socket(ip: IpAddr) -> io::Result<TcpSocket> {
+    let socket = match ip {
+        IpAddr::V4(_) => TcpSocket::new_v4()?,
+        IpAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    socket.set_reuseaddr(true)?;
+    #[cfg(unix)]
+    socket.set_reuseport(true)?;
+    Ok(socket)
+}
+
+/// 从 `local` 反复向 `addr` 发起连接，直到连接建立（由调用方限定时间）
+async fn connect_until(local: SocketAddr, addr: SocketAddr) -> TcpStream {
+    loop {
+        let attempt = async {
+            let socket = reusable_socket(local.ip())?;
+            socket.bind(local)?;
+            socket.connect(addr).await
+        };
+        if let Ok(stream) = attempt.await {
+            return stream;
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+/// 本机连往 `server` 时使用的出口 IP（只确定路由，不发送任何数据）
+fn outbound_ip(server: &str) -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(server)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// 直连握手行
+fn handshake_line(id: u64, sha256: &str) -> String {
+    format!("{} {} {}\n", HANDSHAKE, id, sha256)
+}
+
+/// 接收方：读取对方在连接上写出的握手行，与 `expected` 相符时返回该连接
+async fn handshake(stream: TcpStream, expected: String) -> Option<BufReader<TcpStream>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    let read = reader.read_line(&mut line);
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, read).await {
+        Ok(Ok(_)) if line == expected => Some(reader),
+        _ => None,
+    }
+}
+
+/// 发送方：在直连上写出握手行与文件从 `offset` 处起的内容
+async fn send_direct(mut stream: TcpStream, file: &Outgoing, offset: u64) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    stream
+        .write_all(handshake_line(file.id, &file.sha256).as_bytes())
+        .await?;
+    let mut source = File::open(&file.path).await?;
+    source.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut sent = offset;
+    loop {
+        let len = source.read(&mut buf).await?;
+        if len == 0 {
+            break;
+        }
+        stream.write_all(&buf[..len]).await?;
+        sent += len as u64;
+        file.progress.add(len as u64);
+    }
+    if sent != file.size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    stream.shutdown().await
+}
+
+/// 接收方：从直连读取 `size` 字节写入 `file`
+async fn receive_direct(
+    stream: &mut BufReader<TcpStream>,
+    file: &mut File,
+    size: u64,
+    progress: &Progress,
+) -> io::Result<()> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut received = 0;
+    while received < size {
+        let want = (size - received).min(CHUNK_SIZE as u64) as usize;
+        let len = stream.read(&mut buf[..want]).await?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        file.write_all(&buf[..len]).await?;
+        received += len as u64;
+        progress.add(len as u64);
+    }
+    Ok(())
+}
+
+/// 等待传输被取消或中断（对方发来或自己转交的 [`Control::FileCancel`]），其余的帧忽略
+async fn stopped(rx: &mut mpsc::UnboundedReceiver<Control>) -> Stop {
+    loop {
+        match rx.recv().await {
+            Some(Control::FileCancel {
+                reason,
+                interrupted,
+                ..
+            }) => return Stop::new(reason, interrupted),
+            Some(_) => {}
+            None => future::pending().await,
+        }
+    }
+}
+
+/// 只保留前 `len` 字节，准备从该处接收
+async fn reset(file: &mut File, len: u64) -> io::Result<()> {
+    file.set_len(len).await?;
+    file.seek(SeekFrom::Start(len)).await?;
+    Ok(())
+}
+
+/// 把校验通过的临时文件移到下载目录，重名时在文件名后加上序号
+async fn save(part: &Path, downloads: &Path, name: &str) -> io::Result<PathBuf> {
+    // 只取文件名部分，避免对方提供的名字写到下载目录之外
+    let name = Path::new(name)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .unwrap_or_else(|| "download".to_string());
+    let path = Path::new(&name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut target = downloads.join(&name);
+    let mut n = 1;
+    while fs::try_exists(&target).await? {
+        target = downloads.join(format!("{} ({}){}", stem, n, extension));
+        n += 1;
+    }
+    fs::rename(part, &target).await?;
+    Ok(target)
+}
+
+/// 读取文件，返回其大小与 SHA-256（十六进制）
+fn hash_file(path: &Path) -> io::Result<(u64, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+        size += len as u64;
+    }
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// 随机的传输编号
+fn random_id() -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    Ok(u64::from_le_bytes(bytes))
+}
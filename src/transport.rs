@@ -0,0 +1,397 @@
+/*!
+# 传输层模块
+
+本模块负责建立到服务器的底层连接，屏蔽明文 TCP 与 TLS 的差异：
+- 明文连接直接使用 `TcpStream::into_split()`
+- 启用 `tls` 特性后，可通过 [`TlsConfig`] 建立 TLS 连接
+
+客户端建立的连接与服务器接受的连接都按 [`SocketOptions`] 设置套接字选项：
+聊天流量以小块写入为主且对延迟敏感，默认开启 `TCP_NODELAY` 与 TCP keepalive。
+
+地址只写域名而不带端口时，先查询 `_chat._tcp.域名` 的 SRV 记录（见 [`crate::srv`]），
+依次尝试记录中的主机与端口；没有记录时使用默认端口 [`DEFAULT_PORT`]。
+
+主机名解析出多个地址时按 Happy Eyeballs（RFC 8305）的方式连接：IPv6 与 IPv4 地址交替排列、
+IPv6 优先，前一个尝试 [`CONNECTION_ATTEMPT_DELAY`] 内没有结果（或已经失败）就并行发起下一个，
+先连上的胜出，其余尝试随即取消。IPv6 路由不通时不必等到超时才回退到 IPv4。
+
+连接建立后统一返回装箱的读写半部，供编解码层使用。连接失败时返回
+[`ConnectError`]，区分地址无效、DNS 解析失败、连接被拒绝、超时与 TLS 错误，
+便于向用户给出明确提示并返回不同的退出码。
+*/
+
+use crate::srv;
+use crate::tr;
+use socket2::{SockRef, TcpKeepalive};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::task::JoinSet;
+
+/// 服务器的默认端口
+pub const DEFAULT_PORT: u16 = 7891;
+
+/// Happy Eyeballs 中发起下一个连接尝试前的等待时间（RFC 8305 建议 250 毫秒）
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// 装箱的读取半部
+pub type BoxReader = Box<dyn AsyncRead + Send + Unpin>;
+/// 装箱的写入半部
+pub type BoxWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// 客户端 TLS 设置
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    /// 用于证书校验的服务器名称，未设置时使用连接地址中的主机名或 IP
+    pub server_name: Option<String>,
+    /// 自定义 CA 证书（PEM）路径，未设置时使用内置的 Web PKI 根证书
+    pub ca_file: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// 创建默认的 TLS 设置
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置证书校验使用的服务器名称
+    pub fn server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// 设置自定义 CA 证书路径
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_file = Some(path.into());
+        self
+    }
+}
+
+/// TCP 套接字选项
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// 关闭 Nagle 算法（`TCP_NODELAY`），小消息立即发出
+    pub nodelay: bool,
+    /// 连接空闲多久后开始发送 keepalive 探测，`None` 表示关闭 keepalive
+    pub keepalive: Option<Duration>,
+    /// keepalive 探测的间隔，`None` 使用系统默认值
+    pub keepalive_interval: Option<Duration>,
+    /// 发送缓冲区大小（字节），`None` 使用系统默认值
+    pub send_buffer: Option<usize>,
+    /// 接收缓冲区大小（字节），`None` 使用系统默认值
+    pub recv_buffer: Option<usize>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: Some(Duration::from_secs(60)),
+            keepalive_interval: Some(Duration::from_secs(10)),
+            send_buffer: None,
+            recv_buffer: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// 将选项应用到已建立的连接上
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        let socket = SockRef::from(stream);
+        match self.keepalive {
+            Some(time) => {
+                let keepalive = TcpKeepalive::new().with_time(time);
+                #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+                let keepalive = match self.keepalive_interval {
+                    Some(interval) => keepalive.with_interval(interval),
+                    None => keepalive,
+                };
+                socket.set_tcp_keepalive(&keepalive)?;
+            }
+            None => socket.set_keepalive(false)?,
+        }
+        if let Some(size) = self.send_buffer {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+}
+
+/// 建立连接失败的原因
+#[derive(Debug)]
+pub enum ConnectError {
+    /// 地址格式无效（缺少端口等）
+    InvalidAddress(String),
+    /// 主机名解析失败
+    Dns(String, io::Error),
+    /// 对端拒绝连接（端口未监听）
+    Refused(SocketAddr),
+    /// 在限定时间内未能建立连接
+    TimedOut(Duration),
+    /// TLS 握手或证书校验失败
+    Tls(io::Error),
+    /// 其他 I/O 错误
+    Io(io::Error),
+}
+
+impl ConnectError {
+    /// 该错误对应的进程退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConnectError::Io(_) => 1,
+            ConnectError::InvalidAddress(_) => 2,
+            ConnectError::Dns(..) => 3,
+            ConnectError::Refused(_) => 4,
+            ConnectError::TimedOut(_) => 5,
+            ConnectError::Tls(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ConnectError::InvalidAddress(addr) => tr!("transport.invalid_address", addr = addr),
+            ConnectError::Dns(host, e) => tr!("transport.dns", host = host, error = e),
+            ConnectError::Refused(addr) => tr!("transport.refused", addr = addr),
+            ConnectError::TimedOut(timeout) => {
+                tr!("transport.timed_out", secs = timeout.as_secs_f32())
+            }
+            ConnectError::Tls(e) => tr!("transport.tls", error = e),
+            ConnectError::Io(e) => tr!("transport.io", error = e),
+        };
+        f.write_str(&text)
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/// 从 `主机:端口` 形式的地址中取出主机部分（去掉 IPv6 的方括号）
+fn host_of(addr: &str) -> &str {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// 依次尝试的 `主机:端口`：带端口的地址原样返回；只写域名时按 SRV 记录给出，
+/// 没有记录时加上默认端口
+async fn targets(addr: &str) -> Result<Vec<String>, ConnectError> {
+    let bracketed = addr.starts_with('[') && addr.ends_with(']');
+    if addr.contains(':') && !bracketed {
+        return Ok(vec![addr.to_string()]);
+    }
+    let with_default_port = format!("{}:{}", addr, DEFAULT_PORT);
+    if bracketed || addr.parse::<IpAddr>().is_ok() {
+        return Ok(vec![with_default_port]);
+    }
+    match srv::lookup(addr).await {
+        Ok(Some(records)) if !records.is_empty() => {
+            Ok(records.iter().map(srv::SrvRecord::addr).collect())
+        }
+        Ok(None) => Err(ConnectError::Dns(
+            addr.to_string(),
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                tr!("srv.not_provided", domain = addr),
+            ),
+        )),
+        // 没有 SRV 记录或无法查询时退回默认端口
+        _ => Ok(vec![with_default_port]),
+    }
+}
+
+/// 解析地址，必要时进行 DNS 查询
+async fn resolve(addr: &str) -> Result<Vec<SocketAddr>, ConnectError> {
+    if let Ok(addr) = addr.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    let has_port = addr
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    if !has_port {
+        return Err(ConnectError::InvalidAddress(addr.to_string()));
+    }
+    let addrs: Vec<SocketAddr> = lookup_host(addr)
+        .await
+        .map_err(|e| ConnectError::Dns(host_of(addr).to_string(), e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(ConnectError::Dns(
+            host_of(addr).to_string(),
+            io::Error::new(io::ErrorKind::NotFound, tr!("transport.no_records")),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// 建立到 `addr`（`主机:端口`）的连接，并按需完成 TLS 握手
+///
+/// # 参数
+/// - `addr`: 服务器地址，主机部分可以是 IP 或主机名
+/// - `tls`: TLS 设置，`None` 表示明文连接
+/// - `socket`: 连接建立后设置的套接字选项
+/// - `timeout`: 整个连接过程（解析、TCP 连接、TLS 握手）的超时时间
+pub async fn connect(
+    addr: &str,
+    tls: Option<&TlsConfig>,
+    socket: &SocketOptions,
+    timeout: Option<Duration>,
+) -> Result<(BoxReader, BoxWriter), ConnectError> {
+    let connecting = connect_inner(addr, tls, socket);
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, connecting)
+            .await
+            .map_err(|_| ConnectError::TimedOut(timeout))?,
+        None => connecting.await,
+    }
+}
+
+async fn connect_inner(
+    addr: &str,
+    tls: Option<&TlsConfig>,
+    socket: &SocketOptions,
+) -> Result<(BoxReader, BoxWriter), ConnectError> {
+    let mut last_error = None;
+    let mut connected = None;
+    for target in targets(addr).await? {
+        let attempt = match resolve(&target).await {
+            Ok(addrs) => happy_eyeballs(interleave(addrs)).await,
+            Err(e) => Err(e),
+        };
+        match attempt {
+            Ok(stream) => {
+                connected = Some(stream);
+                break;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let Some(stream) = connected else {
+        return Err(last_error.unwrap_or_else(|| ConnectError::InvalidAddress(addr.to_string())));
+    };
+    socket.apply(&stream).map_err(ConnectError::Io)?;
+    match tls {
+        None => {
+            let (reader, writer) = stream.into_split();
+            Ok((Box::new(reader), Box::new(writer)))
+        }
+        Some(config) => tls_handshake(stream, host_of(addr), config)
+            .await
+            .map_err(ConnectError::Tls),
+    }
+}
+
+/// 按 IPv6、IPv4 交替排列解析出的地址，同一地址族内保持解析结果的顺序
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    let mut v6 = v6.into_iter();
+    let mut v4 = v4.into_iter();
+    let mut ordered = Vec::new();
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// 依次发起到 `addrs` 的连接尝试，前一个尝试 [`CONNECTION_ATTEMPT_DELAY`] 内没有结果或已经失败时
+/// 并行发起下一个，返回最先建立的连接；全部失败时返回最后一个错误
+async fn happy_eyeballs(addrs: Vec<SocketAddr>) -> Result<TcpStream, ConnectError> {
+    let mut pending = addrs.into_iter();
+    // 进行中的尝试，返回时未完成的尝试随 `JoinSet` 一并取消
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    loop {
+        if attempts.is_empty() {
+            let Some(addr) = pending.next() else {
+                break;
+            };
+            attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+        }
+        tokio::select! {
+            Some(joined) = attempts.join_next() => match joined {
+                Ok((_, Ok(stream))) => return Ok(stream),
+                Ok((addr, Err(e))) => {
+                    last_error = Some(match e.kind() {
+                        io::ErrorKind::ConnectionRefused => ConnectError::Refused(addr),
+                        _ => ConnectError::Io(e),
+                    });
+                    // 失败后立即发起下一个尝试
+                    if let Some(addr) = pending.next() {
+                        attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+                    }
+                }
+                Err(e) => last_error = Some(ConnectError::Io(io::Error::other(e))),
+            },
+            () = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if pending.len() > 0 => {
+                if let Some(addr) = pending.next() {
+                    attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+                }
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        ConnectError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            tr!("transport.no_records"),
+        ))
+    }))
+}
+
+#[cfg(feature = "tls")]
+async fn tls_handshake(
+    stream: TcpStream,
+    host: &str,
+    config: &TlsConfig,
+) -> io::Result<(BoxReader, BoxWriter)> {
+    use std::sync::Arc;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    let mut roots = RootCertStore::empty();
+    match &config.ca_file {
+        Some(path) => {
+            let pem = std::fs::read(path)?;
+            for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+                roots
+                    .add(cert?)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let client_config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    let name = config.server_name.as_deref().unwrap_or(host);
+    let server_name = ServerName::try_from(name.to_string())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let stream = TlsConnector::from(Arc::new(client_config))
+        .connect(server_name, stream)
+        .await?;
+    let (reader, writer) = tokio::io::split(stream);
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+#[cfg(not(feature = "tls"))]
+async fn tls_handshake(
+    _stream: TcpStream,
+    _host: &str,
+    _config: &TlsConfig,
+) -> io::Result<(BoxReader, BoxWriter)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        tr!("transport.tls_disabled"),
+    ))
+}
@@ -0,0 +1,224 @@
+/*!
+# 浏览器 WebSocket 传输
+
+在浏览器中（`wasm32-unknown-unknown`）经浏览器自带的 `WebSocket` 连接服务器的 `--ws` 监听地址，
+驱动与传输方式无关的 [`ClientCore`]：连接建立后发送握手帧，每条文本消息解析为一帧交给
+[`ClientCore::receive`]，工作量证明挑战在收到时直接求解并应答。
+
+页面通过 [`WebClient`] 使用，收到的事件以 JSON 字符串交给构造时传入的回调，`type` 字段区分：
+
+- `message`：一条新消息（`message` 字段与线上格式相同）
+- `error`：服务器的错误帧（`error` 字段）
+- `roster`：完整的在线名单（`users` 字段）
+- `presence`：在线名单中一个用户的变化（`change` 与 `user` 字段）
+- `shutdown`：服务器即将关闭连接（`reason` 与 `reconnect_after` 字段，后者为秒数）
+- `closed`：连接已关闭
+
+浏览器客户端没有身份密钥，不签名消息；要求工作量证明的服务器上求解在页面线程中进行，
+难度较高时页面会短暂无响应。
+*/
+
+use crate::client_core::{self, ClientCore, Event};
+use crate::protocol::{
+    ErrorFrame, Frame, RosterChange, RosterEntry, ShutdownReason, FEATURE_ERRORS, FEATURE_POW,
+    FEATURE_RESUME, FEATURE_ROSTER, FEATURE_SHUTDOWN,
+};
+use crate::{ArcString, Message, MessageKind};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use web_sys::{CloseEvent, MessageEvent, WebSocket};
+
+/// 交给页面回调的事件
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WebEvent<'a> {
+    Message {
+        message: &'a Message,
+    },
+    Error {
+        error: &'a ErrorFrame,
+    },
+    Roster {
+        users: Vec<&'a RosterEntry>,
+    },
+    Presence {
+        change: RosterChange,
+        user: &'a RosterEntry,
+    },
+    Shutdown {
+        reason: ShutdownReason,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reconnect_after: Option<u64>,
+    },
+    Closed,
+}
+
+/// 连接共享的状态，由各个 WebSocket 回调持有
+struct Shared {
+    socket: WebSocket,
+    core: RefCell<ClientCore>,
+    on_event: js_sys::Function,
+}
+
+impl Shared {
+    /// 把一帧编码为 JSON 文本消息发送
+    fn send_frame(&self, frame: &impl Serialize) -> Result<(), JsValue> {
+        let text = serde_json::to_string(frame).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.socket.send_with_str(&text)
+    }
+
+    /// 以 JSON 字符串调用页面的回调；回调抛出的异常被忽略，不影响后续事件
+    fn emit(&self, event: &WebEvent) {
+        if let Ok(json) = serde_json::to_string(event) {
+            let _ = self
+                .on_event
+                .call1(&JsValue::NULL, &JsValue::from_str(&json));
+        }
+    }
+
+    /// 处理一条收到的文本消息
+    fn receive(&self, text: &str) {
+        let Ok(frame) = serde_json::from_str::<Frame>(text) else {
+            return;
+        };
+        let event = self.core.borrow_mut().receive(frame);
+        match event {
+            Some(Event::Message(message)) => self.emit(&WebEvent::Message { message: &message }),
+            Some(Event::Error(error)) => self.emit(&WebEvent::Error { error: &error }),
+            Some(Event::Challenge { nonce, difficulty }) => {
+                // 难度超过上限时不求解，由服务器超时后拒绝
+                if let Some(proof) = client_core::solve_challenge(&nonce, difficulty) {
+                    let _ = self.send_frame(&Frame::from(proof));
+                }
+            }
+            Some(Event::Roster) => {
+                let core = self.core.borrow();
+                self.emit(&WebEvent::Roster {
+                    users: core.roster().collect(),
+                })
+            }
+            Some(Event::Presence { change, user }) => self.emit(&WebEvent::Presence {
+                change,
+                user: &user,
+            }),
+            Some(Event::Shutdown {
+                reason,
+                reconnect_after,
+            }) => self.emit(&WebEvent::Shutdown {
+                reason,
+                reconnect_after: reconnect_after.map(|after| after.as_secs()),
+            }),
+            Some(Event::Control(_)) | None => {}
+        }
+    }
+}
+
+/// 浏览器中的聊天客户端：一条 WebSocket 连接及其协议状态
+#[wasm_bindgen]
+pub struct WebClient {
+    shared: Rc<Shared>,
+    // 回调须与连接同生命周期，释放后浏览器再调用会出错
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+#[wasm_bindgen]
+impl WebClient {
+    /// 以用户名 `name` 连接 `url`（如 `wss://chat.example.org:7892`），事件以 JSON 字符串交给 `on_event`
+    #[wasm_bindgen(constructor)]
+    pub fn connect(
+        url: &str,
+        name: &str,
+        on_event: js_sys::Function,
+    ) -> Result<WebClient, JsValue> {
+        let features = [
+            FEATURE_RESUME,
+            FEATURE_ERRORS,
+            FEATURE_POW,
+            FEATURE_ROSTER,
+            FEATURE_SHUTDOWN,
+        ]
+        .map(str::to_string)
+        .to_vec();
+        let core = ClientCore::new(ArcString::new(name.trim().to_string()), features);
+        let shared = Rc::new(Shared {
+            socket: WebSocket::new(url)?,
+            core: RefCell::new(core),
+            on_event,
+        });
+
+        let on_open = {
+            let shared = shared.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                let hello = shared.core.borrow().hello();
+                let _ = shared.send_frame(&hello);
+            })
+        };
+        let on_message = {
+            let shared = shared.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                // 服务器只发送文本消息
+                if let Some(text) = event.data().as_string() {
+                    shared.receive(&text);
+                }
+            })
+        };
+        let on_close = {
+            let shared = shared.clone();
+            Closure::<dyn FnMut(CloseEvent)>::new(move |_: CloseEvent| {
+                shared.emit(&WebEvent::Closed);
+            })
+        };
+        shared
+            .socket
+            .set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        shared
+            .socket
+            .set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        shared
+            .socket
+            .set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        Ok(WebClient {
+            shared,
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        })
+    }
+
+    /// 给 `to` 发送一条文本消息；`to` 以 `/` 开头时为服务器指令
+    pub fn send(&self, to: &str, content: &str) -> Result<(), JsValue> {
+        self.send_kind(to, content, MessageKind::Text)
+    }
+
+    /// 给 `to` 发送一条动作消息（显示为 "* 用户名 内容"）
+    pub fn send_action(&self, to: &str, content: &str) -> Result<(), JsValue> {
+        self.send_kind(to, content, MessageKind::Action)
+    }
+
+    /// 关闭连接
+    pub fn close(&self) -> Result<(), JsValue> {
+        self.shared.socket.close()
+    }
+}
+
+impl WebClient {
+    fn send_kind(&self, to: &str, content: &str, kind: MessageKind) -> Result<(), JsValue> {
+        let message = self.shared.core.borrow().message(to, content, kind);
+        self.shared.send_frame(&Frame::from(message))
+    }
+}
+
+impl Drop for WebClient {
+    fn drop(&mut self) {
+        // 先解除回调，避免浏览器在闭包释放后仍调用
+        let socket = &self.shared.socket;
+        socket.set_onopen(None);
+        socket.set_onmessage(None);
+        socket.set_onclose(None);
+        let _ = socket.close();
+    }
+}